@@ -0,0 +1,271 @@
+//! Blend trees, computing the weights of the animations of an `AnimationControlSet` from
+//! parameters such as the speed or direction of movement.
+
+use fnv::FnvHashMap;
+use serde::{Deserialize, Serialize};
+
+/// Weight of a child of a `BlendNode::Weighted`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum BlendWeight {
+    /// A fixed weight
+    Constant(f32),
+    /// The value of a parameter of the tree
+    Parameter(String),
+}
+
+/// A node of a `BlendTree`.
+///
+/// The weight of a node is split among its children, so the weight of a clip is the product of
+/// the weights along its path from the root.
+///
+/// ### Type parameters:
+///
+/// - `I`: identifier type of the animations in the `AnimationControlSet`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum BlendNode<I> {
+    /// The animation with the given id
+    Clip(I),
+    /// Blends the two children whose thresholds are closest to the value of the parameter, on
+    /// either side of it. Values outside of the thresholds use the nearest child only.
+    Blend1D {
+        /// Name of the parameter
+        parameter: String,
+        /// Thresholds and children, in any order
+        children: Vec<(f32, BlendNode<I>)>,
+    },
+    /// Blends the children by the distance of their positions to the point given by two
+    /// parameters, with gradient band interpolation. The weight of a child is 1 at its position,
+    /// and fades out towards the positions of the other children.
+    Blend2D {
+        /// Names of the parameters of the x and y axes
+        parameters: (String, String),
+        /// Positions and children
+        children: Vec<([f32; 2], BlendNode<I>)>,
+    },
+    /// Adds up the children with the given weights, e.g. to layer an aiming animation over a
+    /// locomotion blend.
+    Weighted(Vec<(BlendWeight, BlendNode<I>)>),
+}
+
+/// A tree of blend nodes with the parameters driving it.
+///
+/// Set on an `AnimationControlSet` with `set_blend_tree`, the `AnimationControlSystem` evaluates
+/// it every frame and sets the weights of the animations of the set. Animations of the set which
+/// aren't in the tree keep their weight, and clips of the tree which aren't in the set are
+/// ignored, so all animations of the tree should be added to the set, usually looping.
+///
+/// ### Type parameters:
+///
+/// - `I`: identifier type of the animations in the `AnimationControlSet`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BlendTree<I> {
+    /// The root node
+    pub root: BlendNode<I>,
+    /// The values of the parameters, missing parameters are 0
+    #[serde(default)]
+    pub parameters: FnvHashMap<String, f32>,
+}
+
+impl<I> BlendTree<I> {
+    /// Creates a tree with the given root node and no parameters set.
+    pub fn new(root: BlendNode<I>) -> Self {
+        BlendTree {
+            root,
+            parameters: FnvHashMap::default(),
+        }
+    }
+
+    /// Set the value of a parameter
+    pub fn set_parameter<S: Into<String>>(&mut self, name: S, value: f32) -> &mut Self {
+        self.parameters.insert(name.into(), value);
+        self
+    }
+
+    /// Get the value of a parameter, 0 if it isn't set
+    pub fn parameter(&self, name: &str) -> f32 {
+        self.parameters.get(name).cloned().unwrap_or(0.)
+    }
+
+    /// Computes the weight of every clip of the tree, including the ones with a weight of 0.
+    /// Clips used by several nodes get the sum of their weights.
+    pub fn evaluate(&self) -> Vec<(I, f32)>
+    where
+        I: Clone + PartialEq,
+    {
+        self.root.evaluate(&self.parameters)
+    }
+}
+
+impl<I> BlendNode<I> {
+    /// Computes the weight of every clip of the node with the given parameter values, missing
+    /// parameters being 0. Clips used by several nodes get the sum of their weights.
+    pub fn evaluate(&self, parameters: &FnvHashMap<String, f32>) -> Vec<(I, f32)>
+    where
+        I: Clone + PartialEq,
+    {
+        let mut weights = Vec::new();
+        self.evaluate_into(parameters, 1., &mut weights);
+        weights
+    }
+
+    fn evaluate_into(
+        &self,
+        parameters: &FnvHashMap<String, f32>,
+        weight: f32,
+        weights: &mut Vec<(I, f32)>,
+    ) where
+        I: Clone + PartialEq,
+    {
+        let parameter = |name: &str| parameters.get(name).cloned().unwrap_or(0.);
+        match self {
+            BlendNode::Clip(id) => {
+                match weights.iter_mut().find(|w| w.0 == *id) {
+                    Some(entry) => entry.1 += weight,
+                    None => weights.push((id.clone(), weight)),
+                }
+            }
+            BlendNode::Blend1D {
+                parameter: name,
+                children,
+            } => {
+                let thresholds = children.iter().map(|c| c.0).collect::<Vec<_>>();
+                let child_weights = blend_1d(parameter(name), &thresholds);
+                for ((_, child), child_weight) in children.iter().zip(child_weights) {
+                    child.evaluate_into(parameters, weight * child_weight, weights);
+                }
+            }
+            BlendNode::Blend2D {
+                parameters: names,
+                children,
+            } => {
+                let point = [parameter(&names.0), parameter(&names.1)];
+                let positions = children.iter().map(|c| c.0).collect::<Vec<_>>();
+                let child_weights = blend_2d(point, &positions);
+                for ((_, child), child_weight) in children.iter().zip(child_weights) {
+                    child.evaluate_into(parameters, weight * child_weight, weights);
+                }
+            }
+            BlendNode::Weighted(children) => {
+                for (child_weight, child) in children {
+                    let child_weight = match child_weight {
+                        BlendWeight::Constant(w) => *w,
+                        BlendWeight::Parameter(name) => parameter(name),
+                    };
+                    child.evaluate_into(parameters, weight * child_weight.max(0.), weights);
+                }
+            }
+        }
+    }
+}
+
+/// Weights of the children of a 1D blend at the given value.
+fn blend_1d(value: f32, thresholds: &[f32]) -> Vec<f32> {
+    let mut weights = vec![0.; thresholds.len()];
+    let below = nearest(thresholds, |t| t <= value, |a, b| a > b);
+    let above = nearest(thresholds, |t| t > value, |a, b| a < b);
+    match (below, above) {
+        (Some(below), Some(above)) => {
+            let t = (value - thresholds[below]) / (thresholds[above] - thresholds[below]);
+            weights[below] = 1. - t;
+            weights[above] = t;
+        }
+        (Some(only), None) | (None, Some(only)) => weights[only] = 1.,
+        (None, None) => {}
+    }
+    weights
+}
+
+/// Index of the threshold matching `filter` which is `better` than all other matching ones.
+fn nearest(
+    thresholds: &[f32],
+    filter: impl Fn(f32) -> bool,
+    better: impl Fn(f32, f32) -> bool,
+) -> Option<usize> {
+    thresholds
+        .iter()
+        .enumerate()
+        .filter(|(_, t)| filter(**t))
+        .fold(None, |best: Option<usize>, (i, t)| {
+            match best {
+                Some(b) if !better(*t, thresholds[b]) => Some(b),
+                _ => Some(i),
+            }
+        })
+}
+
+/// Weights of the children of a 2D blend at the given point, normalized to a sum of 1.
+fn blend_2d(point: [f32; 2], positions: &[[f32; 2]]) -> Vec<f32> {
+    let mut weights = positions
+        .iter()
+        .enumerate()
+        .map(|(i, pi)| {
+            let to_point = [point[0] - pi[0], point[1] - pi[1]];
+            positions
+                .iter()
+                .enumerate()
+                .filter(|(j, _)| *j != i)
+                .map(|(_, pj)| {
+                    let edge = [pj[0] - pi[0], pj[1] - pi[1]];
+                    let length = edge[0] * edge[0] + edge[1] * edge[1];
+                    if length == 0. {
+                        1.
+                    } else {
+                        1. - (to_point[0] * edge[0] + to_point[1] * edge[1]) / length
+                    }
+                })
+                .fold(1f32, f32::min)
+                .max(0.)
+        })
+        .collect::<Vec<_>>();
+    let total: f32 = weights.iter().sum();
+    if total > 0. {
+        weights.iter_mut().for_each(|w| *w /= total);
+    }
+    weights
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn weight(weights: &[(u32, f32)], id: u32) -> f32 {
+        weights.iter().find(|w| w.0 == id).map_or(-1., |w| w.1)
+    }
+
+    #[test]
+    fn test_nested_blend_tree_weights() {
+        let locomotion = BlendNode::Blend1D {
+            parameter: "speed".into(),
+            children: vec![
+                (4., BlendNode::Clip(2)),
+                (0., BlendNode::Clip(0)),
+                (2., BlendNode::Clip(1)),
+            ],
+        };
+        let mut tree = BlendTree::new(BlendNode::Weighted(vec![
+            (BlendWeight::Constant(1.), locomotion),
+            (BlendWeight::Parameter("aim".into()), BlendNode::Clip(3)),
+        ]));
+
+        tree.set_parameter("speed", 3.).set_parameter("aim", 0.5);
+        let weights = tree.evaluate();
+        assert_eq!(weight(&weights, 0), 0.);
+        assert!((weight(&weights, 1) - 0.5).abs() < 1e-6);
+        assert!((weight(&weights, 2) - 0.5).abs() < 1e-6);
+        assert!((weight(&weights, 3) - 0.5).abs() < 1e-6);
+
+        tree.set_parameter("speed", 10.);
+        assert_eq!(weight(&tree.evaluate(), 2), 1.);
+    }
+
+    #[test]
+    fn test_2d_blend_weights() {
+        let positions = [[0., 0.], [1., 0.], [0., 1.], [-1., 0.]];
+        assert_eq!(blend_2d([1., 0.], &positions), vec![0., 1., 0., 0.]);
+
+        let weights = blend_2d([0.5, 0.5], &positions);
+        assert!((weights.iter().sum::<f32>() - 1.).abs() < 1e-6);
+        assert!((weights[1] - weights[2]).abs() < 1e-6);
+        assert_eq!(weights[3], 0.);
+    }
+}
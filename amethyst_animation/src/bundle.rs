@@ -1,10 +1,19 @@
 use std::{hash::Hash, marker};
 
-use amethyst_core::ecs::*;
+use amethyst_core::{ecs::*, shrev::EventChannel};
 use derivative::Derivative;
 use marker::PhantomData;
 
-use crate::{resources::AnimationSampling, skinning::VertexSkinningSystem};
+use crate::{
+    ik::IkSystem,
+    lod::AnimationLodSystem,
+    morph::MorphTargetSystem,
+    resources::{AnimationEvent, AnimationSampling},
+    retarget::RetargetSystem,
+    root_motion::RootMotionSystem,
+    skinning::VertexSkinningSystem,
+    tween::{TweenSystem, Tweens},
+};
 
 /// Bundle for vertex skinning
 ///
@@ -32,6 +41,177 @@ impl SystemBundle for VertexSkinningBundle {
     }
 }
 
+/// Bundle for root motion
+///
+/// This registers `RootMotionSystem`.
+/// Note that this bundle must be added after the `AnimationBundle` of `Transform`s, and before
+/// `TransformBundle`
+#[derive(Default, Debug)]
+pub struct RootMotionBundle;
+
+impl RootMotionBundle {
+    /// Create a new root motion bundle
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+impl SystemBundle for RootMotionBundle {
+    fn load(
+        &mut self,
+        _world: &mut World,
+        _resources: &mut Resources,
+        builder: &mut DispatcherBuilder,
+    ) -> amethyst_core::Result<()> {
+        builder.add_system(RootMotionSystem::default());
+        Ok(())
+    }
+}
+
+/// Bundle for tweening the fields of a component type
+///
+/// This registers `TweenSystem<C>`, and adds the `Tweens<C>` resource for starting tweens.
+///
+/// ### Type parameters:
+///
+/// - `C`: the component type that is tweened
+#[derive(Derivative, Debug)]
+#[derivative(Default(bound = ""))]
+pub struct TweenBundle<C> {
+    m: marker::PhantomData<C>,
+}
+
+impl<C> TweenBundle<C> {
+    /// Create a new tween bundle
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+impl<C> SystemBundle for TweenBundle<C>
+where
+    C: Component,
+{
+    fn load(
+        &mut self,
+        _world: &mut World,
+        resources: &mut Resources,
+        builder: &mut DispatcherBuilder,
+    ) -> amethyst_core::Result<()> {
+        resources.get_or_default::<Tweens<C>>();
+        builder.add_system(TweenSystem::<C>::default());
+        Ok(())
+    }
+}
+
+/// Bundle for animation level of detail
+///
+/// This registers `AnimationLodSystem`.
+/// Note that this bundle should be added after `TransformBundle`, the rates it computes are
+/// used by the `AnimationControlSystem`s in the next frame
+#[derive(Default, Debug)]
+pub struct AnimationLodBundle;
+
+impl AnimationLodBundle {
+    /// Create a new level of detail bundle
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+impl SystemBundle for AnimationLodBundle {
+    fn load(
+        &mut self,
+        _world: &mut World,
+        _resources: &mut Resources,
+        builder: &mut DispatcherBuilder,
+    ) -> amethyst_core::Result<()> {
+        builder.add_system(AnimationLodSystem::default());
+        Ok(())
+    }
+}
+
+/// Bundle for animation retargeting
+///
+/// This registers `RetargetSystem`.
+/// Note that this bundle must be added after the `AnimationBundle` of `Transform`s, and before
+/// `TransformBundle`
+#[derive(Default, Debug)]
+pub struct RetargetBundle;
+
+impl RetargetBundle {
+    /// Create a new retargeting bundle
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+impl SystemBundle for RetargetBundle {
+    fn load(
+        &mut self,
+        _world: &mut World,
+        _resources: &mut Resources,
+        builder: &mut DispatcherBuilder,
+    ) -> amethyst_core::Result<()> {
+        builder.add_system(RetargetSystem::default());
+        Ok(())
+    }
+}
+
+/// Bundle for inverse kinematics
+///
+/// This registers `IkSystem`.
+/// Note that this bundle must be added after the `AnimationBundle` of `Transform`s and the
+/// `RootMotionBundle`, and before `TransformBundle`
+#[derive(Default, Debug)]
+pub struct IkBundle;
+
+impl IkBundle {
+    /// Create a new inverse kinematics bundle
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+impl SystemBundle for IkBundle {
+    fn load(
+        &mut self,
+        _world: &mut World,
+        _resources: &mut Resources,
+        builder: &mut DispatcherBuilder,
+    ) -> amethyst_core::Result<()> {
+        builder.add_system(IkSystem::default());
+        Ok(())
+    }
+}
+
+/// Bundle for morph targets
+///
+/// This registers `MorphTargetSystem`.
+/// Note that this bundle must be added after the `AnimationBundle` of `MorphWeights`, if the
+/// weights are animated
+#[derive(Default, Debug)]
+pub struct MorphTargetBundle;
+
+impl MorphTargetBundle {
+    /// Create a new morph target bundle
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+impl SystemBundle for MorphTargetBundle {
+    fn load(
+        &mut self,
+        _world: &mut World,
+        _resources: &mut Resources,
+        builder: &mut DispatcherBuilder,
+    ) -> amethyst_core::Result<()> {
+        builder.add_system(MorphTargetSystem::default());
+        Ok(())
+    }
+}
+
 /// Bundle for only the sampler interpolation.
 ///
 /// Will add `SamplerInterpolationSystem<T>` with the given name.
@@ -66,7 +246,7 @@ where
 /// This will also add `SamplingBundle`, because it is a dependency of this bundle.
 ///
 /// Will add `AnimationControlSystem<T>` with the given name.
-/// Will also add `AnimationProcessor<T>`.
+/// Will also add `AnimationProcessor<T>`, and the `EventChannel<AnimationEvent<I>>` resource.
 ///
 /// ### Type parameters:
 ///
@@ -87,12 +267,52 @@ where
     fn load(
         &mut self,
         _world: &mut World,
-        _resources: &mut Resources,
+        resources: &mut Resources,
         builder: &mut DispatcherBuilder,
     ) -> amethyst_core::Result<()> {
+        resources.get_or_default::<EventChannel<AnimationEvent<I>>>();
         builder.add_bundle(SamplingBundle::<T> { m: PhantomData });
         builder.add_system(crate::systems::control::AnimationControlSystem::<I, T>::default());
 
         Ok(())
     }
 }
+
+/// Bundle for a complete animation setup driven by `AnimationStateMachine`s.
+///
+/// This will also add `AnimationBundle`, because it is a dependency of this bundle, so it
+/// shouldn't be added separately.
+///
+/// Will add `AnimationStateMachineSystem<I, T>`, which reads the `AnimationStateMachine<I>`
+/// assets, so that asset type must be registered.
+///
+/// ### Type parameters:
+///
+/// - `I`: identifier type for running animations, only one animation can be run at the same time
+///        with the same id (per entity)
+/// - `T`: the component type that sampling should be applied to
+#[derive(Derivative, Debug)]
+#[derivative(Default)]
+pub struct AnimationStateMachineBundle<I, T> {
+    m: marker::PhantomData<(I, T)>,
+}
+
+impl<I, T> SystemBundle for AnimationStateMachineBundle<I, T>
+where
+    I: std::fmt::Debug + PartialEq + Eq + Hash + Copy + Send + Sync + 'static,
+    T: AnimationSampling + Clone + std::fmt::Debug,
+{
+    fn load(
+        &mut self,
+        _world: &mut World,
+        _resources: &mut Resources,
+        builder: &mut DispatcherBuilder,
+    ) -> amethyst_core::Result<()> {
+        builder.add_system(
+            crate::systems::state_machine::AnimationStateMachineSystem::<I, T>::default(),
+        );
+        builder.add_bundle(AnimationBundle::<I, T> { m: PhantomData });
+
+        Ok(())
+    }
+}
@@ -0,0 +1,385 @@
+//! Animation clips, a human-editable animation format with cubic bezier curves, for UI, camera
+//! and property animations authored without a DCC tool.
+//!
+//! Clips are RON or JSON files with the `.clip` extension, e.g.
+//!
+//! ```ron
+//! (
+//!     channels: [
+//!         (
+//!             channel: Translation,
+//!             keys: [
+//!                 (time: 0.0, value: [0.0, 0.0, 0.0], out_handle: Some([0.0, 1.0, 0.0])),
+//!                 (time: 1.0, value: [0.0, 2.0, 0.0]),
+//!             ],
+//!         ),
+//!         (
+//!             channel: Scale,
+//!             interpolation: Step,
+//!             keys: [(time: 0.0, value: [1.0, 1.0, 1.0]), (time: 0.5, value: [2.0, 2.0, 2.0])],
+//!         ),
+//!     ],
+//! )
+//! ```
+
+use amethyst_assets::{
+    distill_importer::{typetag, SerdeImportable},
+    register_asset_type, register_importer, Asset, AssetProcessorSystem, DefaultLoader, Format,
+    Handle, Loader, ProcessingQueue,
+};
+use amethyst_error::{format_err, Error, ResultExt};
+use minterpolate::InterpolationFunction;
+use serde::{Deserialize, Serialize};
+use type_uuid::TypeUuid;
+use uuid::Uuid;
+
+use crate::{
+    resources::{Animation, AnimationSampling, Sampler},
+    transform::TransformChannel,
+    util::SamplerPrimitive,
+};
+
+/// Interpolation between the keys of a `ClipChannel`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ClipInterpolation {
+    /// Cubic bezier curves through the keys, shaped by their handles
+    Bezier,
+    /// Straight lines between the keys
+    Linear,
+    /// Spherical linear interpolation, for rotations
+    SphericalLinear,
+    /// Keep the value of a key until the next key
+    Step,
+}
+
+impl Default for ClipInterpolation {
+    fn default() -> Self {
+        ClipInterpolation::Bezier
+    }
+}
+
+/// A key of a `ClipChannel`.
+///
+/// The handles are the values of the bezier control points a third of the way to the previous
+/// and to the next key. Keys without handles get smooth handles from their neighbouring keys.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClipKey {
+    /// Time of the key in seconds
+    pub time: f32,
+    /// Value of the key, with 1 to 4 components
+    pub value: Vec<f32>,
+    /// Handle towards the previous key
+    #[serde(default)]
+    pub in_handle: Option<Vec<f32>>,
+    /// Handle towards the next key
+    #[serde(default)]
+    pub out_handle: Option<Vec<f32>>,
+}
+
+impl ClipKey {
+    /// Creates a key with smooth handles
+    pub fn new(time: f32, value: Vec<f32>) -> Self {
+        ClipKey {
+            time,
+            value,
+            in_handle: None,
+            out_handle: None,
+        }
+    }
+
+    /// Set the handles of the key
+    pub fn with_handles(mut self, in_handle: Vec<f32>, out_handle: Vec<f32>) -> Self {
+        self.in_handle = Some(in_handle);
+        self.out_handle = Some(out_handle);
+        self
+    }
+}
+
+/// A channel of an `AnimationClip`, animating a channel of a node.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClipChannel<C> {
+    /// Index of the node in the `AnimationHierarchy`, default is 0
+    #[serde(default)]
+    pub node: usize,
+    /// The animated channel
+    pub channel: C,
+    /// Interpolation between the keys, default is `Bezier`
+    #[serde(default)]
+    pub interpolation: ClipInterpolation,
+    /// The keys, sorted by time
+    pub keys: Vec<ClipKey>,
+}
+
+/// A human-editable animation, converted to an `Animation` when it's loaded.
+///
+/// ### Type parameters:
+///
+/// - `C`: the channel type of the animated component, e.g. `TransformChannel`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AnimationClip<C> {
+    /// The channels
+    pub channels: Vec<ClipChannel<C>>,
+    /// Time in seconds and name of the events of the animation
+    #[serde(default)]
+    pub events: Vec<(f32, String)>,
+}
+
+impl<C> AnimationClip<C>
+where
+    C: Clone,
+{
+    /// Build the samplers of the clip, with the nodes and channels they animate.
+    pub fn samplers(&self) -> Result<Vec<(usize, C, Sampler<SamplerPrimitive<f32>>)>, Error> {
+        self.channels
+            .iter()
+            .enumerate()
+            .map(|(index, channel)| {
+                let sampler = channel_sampler(channel)
+                    .with_context(|_| format_err!("Invalid channel {} of clip", index))?;
+                Ok((channel.node, channel.channel.clone(), sampler))
+            })
+            .collect()
+    }
+
+    /// Load the samplers and the `Animation` of the clip.
+    pub fn load<T>(
+        &self,
+        loader: &DefaultLoader,
+        sampler_queue: &ProcessingQueue<Sampler<SamplerPrimitive<f32>>>,
+        animation_queue: &ProcessingQueue<Animation<T>>,
+    ) -> Result<Handle<Animation<T>>, Error>
+    where
+        T: AnimationSampling<Channel = C, Primitive = SamplerPrimitive<f32>>,
+        Animation<T>: Asset,
+    {
+        let nodes = self
+            .samplers()?
+            .into_iter()
+            .map(|(node, channel, sampler)| {
+                (
+                    node,
+                    channel,
+                    loader.load_from_data(sampler, (), sampler_queue),
+                )
+            })
+            .collect();
+        let animation = Animation {
+            nodes,
+            events: self.events.clone(),
+        };
+        Ok(loader.load_from_data(animation, (), animation_queue))
+    }
+}
+
+fn channel_sampler<C>(channel: &ClipChannel<C>) -> Result<Sampler<SamplerPrimitive<f32>>, Error> {
+    let keys = &channel.keys;
+    let components = keys
+        .first()
+        .map(|key| key.value.len())
+        .ok_or_else(|| format_err!("Channel has no keys"))?;
+    if components == 0 || components > 4 {
+        return Err(format_err!(
+            "Values have {} components, must have 1 to 4",
+            components
+        ));
+    }
+    for key in keys {
+        let handles = key.in_handle.iter().chain(key.out_handle.iter());
+        let mut lengths = std::iter::once(key.value.len()).chain(handles.map(Vec::len));
+        if lengths.any(|len| len != components) {
+            return Err(format_err!(
+                "Values and handles of the key at {} don't have {} components",
+                key.time,
+                components
+            ));
+        }
+    }
+    if keys.windows(2).any(|pair| pair[1].time <= pair[0].time) {
+        return Err(format_err!("Keys aren't sorted by time"));
+    }
+
+    let input = keys.iter().map(|key| key.time).collect();
+    let (function, output) = match channel.interpolation {
+        ClipInterpolation::Bezier => {
+            (
+                InterpolationFunction::CubicSpline,
+                bezier_tangents(keys)
+                    .into_iter()
+                    .zip(keys)
+                    .flat_map(|((in_tangent, out_tangent), key)| {
+                        vec![
+                            primitive(&in_tangent),
+                            primitive(&key.value),
+                            primitive(&out_tangent),
+                        ]
+                    })
+                    .collect(),
+            )
+        }
+        interpolation => {
+            let function = match interpolation {
+                ClipInterpolation::SphericalLinear => InterpolationFunction::SphericalLinear,
+                ClipInterpolation::Step => InterpolationFunction::Step,
+                _ => InterpolationFunction::Linear,
+            };
+            (
+                function,
+                keys.iter().map(|key| primitive(&key.value)).collect(),
+            )
+        }
+    };
+    Ok(Sampler {
+        input,
+        output,
+        function,
+    })
+}
+
+/// The in and out tangents of the keys for a cubic hermite spline, in value per second.
+fn bezier_tangents(keys: &[ClipKey]) -> Vec<(Vec<f32>, Vec<f32>)> {
+    let slope = |from: &ClipKey, to: &ClipKey| -> Vec<f32> {
+        let duration = to.time - from.time;
+        from.value
+            .iter()
+            .zip(&to.value)
+            .map(|(a, b)| (b - a) / duration)
+            .collect()
+    };
+
+    (0..keys.len())
+        .map(|k| {
+            let key = &keys[k];
+            let previous = k.checked_sub(1).map(|p| &keys[p]);
+            let next = keys.get(k + 1);
+            // smooth tangent from the neighbouring keys
+            let smooth = match (previous, next) {
+                (Some(previous), Some(next)) => slope(previous, next),
+                (Some(previous), None) => slope(previous, key),
+                (None, Some(next)) => slope(key, next),
+                (None, None) => vec![0.; key.value.len()],
+            };
+            let in_tangent = match (previous, &key.in_handle) {
+                (Some(previous), Some(handle)) => {
+                    let handle =
+                        ClipKey::new(key.time - (key.time - previous.time) / 3., handle.clone());
+                    slope(&handle, key)
+                }
+                _ => smooth.clone(),
+            };
+            let out_tangent = match (next, &key.out_handle) {
+                (Some(next), Some(handle)) => {
+                    let handle =
+                        ClipKey::new(key.time + (next.time - key.time) / 3., handle.clone());
+                    slope(key, &handle)
+                }
+                _ => smooth,
+            };
+            (in_tangent, out_tangent)
+        })
+        .collect()
+}
+
+fn primitive(values: &[f32]) -> SamplerPrimitive<f32> {
+    match *values {
+        [s] => SamplerPrimitive::Scalar(s),
+        [x, y] => SamplerPrimitive::Vec2([x, y]),
+        [x, y, z] => SamplerPrimitive::Vec3([x, y, z]),
+        [x, y, z, w] => SamplerPrimitive::Vec4([x, y, z, w]),
+        _ => unreachable!("Components are checked when building the sampler"),
+    }
+}
+
+impl TypeUuid for AnimationClip<TransformChannel> {
+    const UUID: type_uuid::Bytes =
+        *Uuid::from_u128(306941376580872011317411620102233746527).as_bytes();
+}
+
+impl Asset for AnimationClip<TransformChannel> {
+    fn name() -> &'static str {
+        "animation::AnimationClip<TransformChannel>"
+    }
+    type Data = Self;
+}
+
+#[typetag::serde]
+impl SerdeImportable for AnimationClip<TransformChannel> {}
+register_asset_type!(AnimationClip<TransformChannel> => AnimationClip<TransformChannel>; AssetProcessorSystem<AnimationClip<TransformChannel>>);
+
+/// Format for `AnimationClip`s of `Transform`s in RON or JSON files with the `.clip` extension.
+///
+/// Files starting with `{` are read as JSON, the others as RON.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, TypeUuid)]
+#[uuid = "1b87c18e-4f2b-4cc2-8c6b-63b45ae9f1a4"]
+pub struct AnimationClipFormat;
+
+register_importer!(".clip", AnimationClipFormat);
+impl Format<AnimationClip<TransformChannel>> for AnimationClipFormat {
+    fn name(&self) -> &'static str {
+        "AnimationClip"
+    }
+
+    fn import_simple(&self, bytes: Vec<u8>) -> Result<AnimationClip<TransformChannel>, Error> {
+        let json = bytes
+            .iter()
+            .find(|b| !b.is_ascii_whitespace())
+            .map_or(false, |b| *b == b'{');
+        let clip: AnimationClip<TransformChannel> = if json {
+            serde_json::from_slice(&bytes)
+                .with_context(|_| format_err!("Failed deserializing Json clip"))?
+        } else {
+            ron::de::from_bytes(&bytes)
+                .with_context(|_| format_err!("Failed deserializing Ron clip"))?
+        };
+        // report invalid clips when importing them
+        clip.samplers()?;
+        Ok(clip)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use minterpolate::InterpolationPrimitive;
+
+    use super::*;
+
+    #[test]
+    fn test_bezier_channel() {
+        let channel = ClipChannel {
+            node: 0,
+            channel: TransformChannel::Translation,
+            interpolation: ClipInterpolation::Bezier,
+            keys: vec![
+                ClipKey::new(0., vec![0.]).with_handles(vec![0.], vec![0.]),
+                ClipKey::new(3., vec![3.]).with_handles(vec![3.], vec![3.]),
+            ],
+        };
+        let sampler = channel_sampler(&channel).unwrap();
+        assert_eq!(sampler.function, InterpolationFunction::CubicSpline);
+        assert_eq!(sampler.output.len(), 6);
+
+        // flat handles ease in and out, so the curve passes the middle at half time
+        let value = |time| {
+            sampler
+                .function
+                .interpolate(time, &sampler.input, &sampler.output, false)
+        };
+        assert!((value(1.5).magnitude() - 1.5).abs() < 1e-4);
+        assert!(value(0.3).magnitude() < 0.3);
+    }
+
+    #[test]
+    fn test_invalid_channels() {
+        let mut channel = ClipChannel {
+            node: 0,
+            channel: TransformChannel::Scale,
+            interpolation: ClipInterpolation::Linear,
+            keys: vec![
+                ClipKey::new(1., vec![1., 1.]),
+                ClipKey::new(0., vec![0., 0.]),
+            ],
+        };
+        assert!(channel_sampler(&channel).is_err());
+        channel.keys[1] = ClipKey::new(2., vec![0.]);
+        assert!(channel_sampler(&channel).is_err());
+    }
+}
@@ -0,0 +1,340 @@
+//! Animation compression, reducing the memory used by samplers with keyframe reduction,
+//! quantization and a packed representation.
+
+use amethyst_assets::{
+    distill_importer::{typetag, SerdeImportable},
+    register_asset_type, register_importer, Asset, AssetProcessorSystem, Format,
+};
+use amethyst_error::Error;
+use minterpolate::{InterpolationFunction, InterpolationPrimitive};
+use serde::{Deserialize, Serialize};
+use type_uuid::TypeUuid;
+
+use crate::{resources::Sampler, util::SamplerPrimitive};
+
+/// Settings for compressing samplers.
+///
+/// Also a `Format` for importing samplers from RON files with the `.sampler` extension, which
+/// are compressed with the settings of the import options of the file.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, TypeUuid)]
+#[uuid = "e6a96e67-662a-4dc1-9749-da6abf21d4a4"]
+#[serde(default)]
+pub struct SamplerCompression {
+    /// Largest error introduced by removing a keyframe, default is 0.0001
+    pub tolerance: f32,
+    /// Step the values are rounded to before removing keyframes, no rounding if `None`
+    pub precision: Option<f32>,
+}
+
+impl Default for SamplerCompression {
+    fn default() -> Self {
+        SamplerCompression {
+            tolerance: 0.0001,
+            precision: None,
+        }
+    }
+}
+
+register_importer!(".sampler", SamplerCompression);
+impl Format<Sampler<SamplerPrimitive<f32>>> for SamplerCompression {
+    fn name(&self) -> &'static str {
+        "CompressedSampler"
+    }
+
+    fn import_simple(&self, bytes: Vec<u8>) -> Result<Sampler<SamplerPrimitive<f32>>, Error> {
+        let mut sampler: Sampler<SamplerPrimitive<f32>> = ron::de::from_bytes(&bytes)?;
+        sampler.compress(self);
+        Ok(sampler)
+    }
+}
+
+impl Sampler<SamplerPrimitive<f32>> {
+    /// Quantize the values and remove the redundant keyframes with the given settings.
+    pub fn compress(&mut self, compression: &SamplerCompression) -> usize {
+        if let Some(precision) = compression.precision {
+            self.quantize(precision);
+        }
+        self.reduce_keyframes(compression.tolerance)
+    }
+
+    /// Round all values to a multiple of `precision`.
+    ///
+    /// Rotations sampled with `SphericalLinear` are normalized again after rounding.
+    pub fn quantize(&mut self, precision: f32) {
+        if precision <= 0. {
+            return;
+        }
+        let normalize = self.function == InterpolationFunction::SphericalLinear;
+        for value in &mut self.output {
+            let (mut values, len) = components(value);
+            for v in &mut values[..len] {
+                *v = (*v / precision).round() * precision;
+            }
+            *value = from_components(&values[..len]);
+            if normalize {
+                *value = value.normalize();
+            }
+        }
+    }
+
+    /// Remove the keyframes which can be interpolated from their neighbours with an error of at
+    /// most `tolerance`, and return the number of removed keyframes.
+    ///
+    /// Only `Linear`, `SphericalLinear` and `Step` samplers are reduced, the keyframes of splines
+    /// are kept.
+    pub fn reduce_keyframes(&mut self, tolerance: f32) -> usize {
+        let keep = match self.function {
+            InterpolationFunction::Linear
+            | InterpolationFunction::SphericalLinear
+            | InterpolationFunction::Step => self.reduced_keyframes(tolerance),
+            _ => return 0,
+        };
+        let removed = self.input.len() - keep.len();
+        if removed > 0 {
+            self.input = keep.iter().map(|i| self.input[*i]).collect();
+            self.output = keep.iter().map(|i| self.output[*i]).collect();
+        }
+        removed
+    }
+
+    /// Indices of the keyframes to keep, always including the first and the last one.
+    fn reduced_keyframes(&self, tolerance: f32) -> Vec<usize> {
+        let len = self.input.len().min(self.output.len());
+        if len <= 2 {
+            return (0..len).collect();
+        }
+
+        let mut keep = vec![0];
+        let mut start = 0;
+        for end in 2..len {
+            let inputs = [self.input[start], self.input[end]];
+            let outputs = [self.output[start], self.output[end]];
+            let fits = (start + 1..end).all(|k| {
+                let value = self
+                    .function
+                    .interpolate(self.input[k], &inputs, &outputs, false);
+                error(&self.function, &value, &self.output[k]) <= tolerance
+            });
+            if !fits {
+                start = end - 1;
+                keep.push(start);
+            }
+        }
+        keep.push(len - 1);
+        keep
+    }
+}
+
+fn error(
+    function: &InterpolationFunction<SamplerPrimitive<f32>>,
+    a: &SamplerPrimitive<f32>,
+    b: &SamplerPrimitive<f32>,
+) -> f32 {
+    let error = a.sub(b).magnitude();
+    if *function == InterpolationFunction::SphericalLinear {
+        // a quaternion and its negation are the same rotation
+        error.min(a.add(b).magnitude())
+    } else {
+        error
+    }
+}
+
+fn components(value: &SamplerPrimitive<f32>) -> ([f32; 4], usize) {
+    match *value {
+        SamplerPrimitive::Scalar(s) => ([s, 0., 0., 0.], 1),
+        SamplerPrimitive::Vec2([x, y]) => ([x, y, 0., 0.], 2),
+        SamplerPrimitive::Vec3([x, y, z]) => ([x, y, z, 0.], 3),
+        SamplerPrimitive::Vec4(v) => (v, 4),
+    }
+}
+
+fn from_components(values: &[f32]) -> SamplerPrimitive<f32> {
+    match *values {
+        [s] => SamplerPrimitive::Scalar(s),
+        [x, y] => SamplerPrimitive::Vec2([x, y]),
+        [x, y, z] => SamplerPrimitive::Vec3([x, y, z]),
+        [x, y, z, w] => SamplerPrimitive::Vec4([x, y, z, w]),
+        _ => panic!("Sampler primitives have 1 to 4 components"),
+    }
+}
+
+/// A sampler with its values packed into 16 bits per component, using about half the memory of
+/// a `Sampler`.
+///
+/// Every component is stored relative to the range of its values in the sampler, so the error
+/// is at most 1/65535th of that range. Sampling decodes only the keyframes around the sampled
+/// time.
+#[derive(Debug, Clone, Serialize, Deserialize, TypeUuid)]
+#[uuid = "7637a361-4722-4bbf-8c7f-cac34ea6eb12"]
+pub struct PackedSampler {
+    /// Time of key frames
+    pub input: Vec<f32>,
+    /// How interpolation should be done
+    pub function: InterpolationFunction<SamplerPrimitive<f32>>,
+    components: usize,
+    offsets: [f32; 4],
+    scales: [f32; 4],
+    values: Vec<u16>,
+}
+
+impl Asset for PackedSampler {
+    fn name() -> &'static str {
+        "animation::PackedSampler"
+    }
+    type Data = Self;
+}
+
+#[typetag::serde]
+impl SerdeImportable for PackedSampler {}
+register_asset_type!(PackedSampler => PackedSampler; AssetProcessorSystem<PackedSampler>);
+
+impl PackedSampler {
+    /// Pack the values of a sampler.
+    pub fn pack(sampler: &Sampler<SamplerPrimitive<f32>>) -> Self {
+        let components = sampler.output.first().map_or(1, |v| components(v).1);
+        let mut ranges = [(std::f32::MAX, std::f32::MIN); 4];
+        for value in &sampler.output {
+            let (values, _) = self::components(value);
+            for (range, v) in ranges.iter_mut().zip(&values).take(components) {
+                *range = (range.0.min(*v), range.1.max(*v));
+            }
+        }
+
+        let mut offsets = [0.; 4];
+        let mut scales = [0.; 4];
+        for (c, (min, max)) in ranges.iter().enumerate().take(components) {
+            if min <= max {
+                offsets[c] = *min;
+                scales[c] = (max - min) / f32::from(std::u16::MAX);
+            }
+        }
+
+        let values = sampler
+            .output
+            .iter()
+            .flat_map(|value| {
+                let (values, _) = self::components(value);
+                (0..components)
+                    .map(|c| {
+                        if scales[c] > 0. {
+                            ((values[c] - offsets[c]) / scales[c]).round() as u16
+                        } else {
+                            0
+                        }
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+
+        PackedSampler {
+            input: sampler.input.clone(),
+            function: sampler.function.clone(),
+            components,
+            offsets,
+            scales,
+            values,
+        }
+    }
+
+    /// Unpack into a sampler.
+    pub fn unpack(&self) -> Sampler<SamplerPrimitive<f32>> {
+        Sampler {
+            input: self.input.clone(),
+            function: self.function.clone(),
+            output: (0..self.len()).map(|i| self.value(i)).collect(),
+        }
+    }
+
+    /// Get the number of output values
+    pub fn len(&self) -> usize {
+        self.values.len() / self.components.max(1)
+    }
+
+    /// Check if the sampler has no output values
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Decode the output value with the given index.
+    pub fn value(&self, index: usize) -> SamplerPrimitive<f32> {
+        let start = index * self.components;
+        let values = (0..self.components)
+            .map(|c| self.offsets[c] + f32::from(self.values[start + c]) * self.scales[c])
+            .collect::<Vec<_>>();
+        from_components(&values)
+    }
+
+    /// Sample the value at the given time, clamped to the times of the keyframes.
+    ///
+    /// Returns `None` if the sampler has no keyframes.
+    pub fn sample(&self, time: f32) -> Option<SamplerPrimitive<f32>> {
+        let last = self.input.len().checked_sub(1)?;
+        if self.is_empty() {
+            return None;
+        }
+        let values_per_key = match self.function {
+            InterpolationFunction::CubicSpline => 3,
+            _ => 1,
+        };
+        if last == 0 {
+            return Some(self.value(values_per_key / 2));
+        }
+
+        let time = time.max(self.input[0]).min(self.input[last]);
+        if let InterpolationFunction::CatmullRomSpline = self.function {
+            // the tangents of catmull rom splines come from the neighbouring keyframes
+            let outputs = (0..self.len()).map(|i| self.value(i)).collect::<Vec<_>>();
+            return Some(
+                self.function
+                    .interpolate(time, &self.input, &outputs, false),
+            );
+        }
+        let start = self
+            .input
+            .iter()
+            .rposition(|t| *t <= time)
+            .unwrap_or(0)
+            .min(last - 1);
+        let outputs = (start * values_per_key..(start + 2) * values_per_key)
+            .map(|i| self.value(i))
+            .collect::<Vec<_>>();
+        Some(
+            self.function
+                .interpolate(time, &self.input[start..start + 2], &outputs, false),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keyframe_reduction_and_packing() {
+        let mut sampler = Sampler {
+            input: vec![0., 1., 2., 3., 4.],
+            output: vec![
+                SamplerPrimitive::Scalar(0.),
+                SamplerPrimitive::Scalar(1.),
+                SamplerPrimitive::Scalar(2.),
+                SamplerPrimitive::Scalar(2.),
+                SamplerPrimitive::Scalar(2.00001),
+            ],
+            function: InterpolationFunction::Linear,
+        };
+        let packed = PackedSampler::pack(&sampler);
+
+        // the ramp and the plateau only need their ends
+        assert_eq!(sampler.reduce_keyframes(0.001), 2);
+        assert_eq!(sampler.input, vec![0., 2., 4.]);
+
+        match packed.sample(0.5) {
+            Some(SamplerPrimitive::Scalar(v)) => assert!((v - 0.5).abs() < 1e-3),
+            other => panic!("Unexpected sample {:?}", other),
+        }
+        match packed.unpack().output[3] {
+            SamplerPrimitive::Scalar(v) => assert!((v - 2.).abs() < 1e-3),
+            other => panic!("Unexpected value {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,438 @@
+//! Inverse kinematics, rotating chains of joints so their end reaches a target, on top of the
+//! sampled animations.
+//!
+//! The solvers are components referring to the joints of a chain, from the joint closest to the
+//! root of the skeleton to the end of the chain, each joint being a child of the previous one.
+//! They are solved by the `IkSystem` after the animations are sampled, and before the global
+//! transforms and the skinning are updated.
+
+use amethyst_core::{
+    ecs::*,
+    math::{Matrix3, Matrix4, Rotation3, Unit, UnitQuaternion, Vector3, U3},
+    transform::Transform,
+};
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+
+/// Smallest distance considered by the solvers, to avoid dividing by zero.
+const EPSILON: f32 = 1e-6;
+
+/// A target of an IK solver, in world space.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum IkTarget {
+    /// The global position of an entity, e.g. a weapon grip for hand attachment
+    Entity(Entity),
+    /// A fixed position, e.g. the ground below a foot from a raycast
+    Position(Vector3<f32>),
+}
+
+/// Analytic IK solver for chains of two bones, such as legs and arms.
+///
+/// The middle joint only bends around the axis of the chain's current bend, or towards the pole
+/// target if there is one, e.g. in front of the knee.
+#[derive(Clone, Debug)]
+pub struct TwoBoneIk {
+    /// The upper, middle and end joints, e.g. the hip, knee and ankle
+    pub joints: [Entity; 3],
+    /// Position the end joint reaches for
+    pub target: IkTarget,
+    /// Position the middle joint points towards
+    pub pole: Option<IkTarget>,
+    /// Blend weight between the animation (0.0) and the solved pose (1.0), default is 1.0
+    pub weight: f32,
+}
+
+impl TwoBoneIk {
+    /// Create a two bone solver reaching for the given target
+    pub fn new(joints: [Entity; 3], target: IkTarget) -> Self {
+        TwoBoneIk {
+            joints,
+            target,
+            pole: None,
+            weight: 1.0,
+        }
+    }
+
+    /// Set the pole target
+    pub fn with_pole(mut self, pole: IkTarget) -> Self {
+        self.pole = Some(pole);
+        self
+    }
+}
+
+/// Iterative FABRIK (forward and backward reaching inverse kinematics) solver for chains of any
+/// length, such as spines, tails or tentacles.
+#[derive(Clone, Debug)]
+pub struct FabrikIk {
+    /// The joints of the chain, the last one reaching for the target
+    pub joints: Vec<Entity>,
+    /// Position the end of the chain reaches for
+    pub target: IkTarget,
+    /// Largest number of iterations, default is 10
+    pub iterations: usize,
+    /// Distance to the target at which the chain is solved, default is 0.001
+    pub tolerance: f32,
+    /// Blend weight between the animation (0.0) and the solved pose (1.0), default is 1.0
+    pub weight: f32,
+}
+
+impl FabrikIk {
+    /// Create a FABRIK solver reaching for the given target
+    pub fn new(joints: Vec<Entity>, target: IkTarget) -> Self {
+        FabrikIk {
+            joints,
+            target,
+            iterations: 10,
+            tolerance: 0.001,
+            weight: 1.0,
+        }
+    }
+}
+
+/// Rotates a single joint so it looks at a target, such as the head of a character.
+#[derive(Clone, Debug)]
+pub struct LookAtIk {
+    /// The joint to rotate
+    pub joint: Entity,
+    /// Position to look at
+    pub target: IkTarget,
+    /// The axis of the joint pointing forward, in its local space, default is +z
+    pub forward: Vector3<f32>,
+    /// Blend weight between the animation (0.0) and the solved pose (1.0), default is 1.0
+    pub weight: f32,
+}
+
+impl LookAtIk {
+    /// Create a look at solver for the given joint
+    pub fn new(joint: Entity, target: IkTarget) -> Self {
+        LookAtIk {
+            joint,
+            target,
+            forward: Vector3::z(),
+            weight: 1.0,
+        }
+    }
+}
+
+/// System solving the `TwoBoneIk`, `FabrikIk` and `LookAtIk` components.
+///
+/// Needs to run after `SamplerInterpolationSystem<Transform>` and `RootMotionSystem`, and before
+/// `TransformSystem`. Solvers use the global transforms of the previous frame for the targets
+/// and the parents of the chains.
+#[derive(Debug, Default)]
+pub struct IkSystem;
+
+impl System for IkSystem {
+    fn build(self) -> Box<dyn ParallelRunnable> {
+        Box::new(
+            SystemBuilder::new("IkSystem")
+                .write_component::<Transform>()
+                .with_query(<Read<TwoBoneIk>>::query())
+                .with_query(<Read<FabrikIk>>::query())
+                .with_query(<Read<LookAtIk>>::query())
+                .build(move |_, world, _, (two_bones, fabriks, look_ats)| {
+                    #[cfg(feature = "profiler")]
+                    profile_scope!("ik_system");
+
+                    let (solvers, mut transforms) = world.split_for_query(two_bones);
+                    for ik in two_bones.iter(&solvers) {
+                        solve_two_bone(&mut transforms, ik);
+                    }
+                    let (solvers, mut transforms) = world.split_for_query(fabriks);
+                    for ik in fabriks.iter(&solvers) {
+                        solve_fabrik(&mut transforms, ik);
+                    }
+                    let (solvers, mut transforms) = world.split_for_query(look_ats);
+                    for ik in look_ats.iter(&solvers) {
+                        solve_look_at(&mut transforms, ik);
+                    }
+                }),
+        )
+    }
+}
+
+fn solve_two_bone(world: &mut SubWorld<'_>, ik: &TwoBoneIk) {
+    let target = match target_position(world, &ik.target) {
+        Some(target) => target,
+        None => return,
+    };
+    let pole = ik.pole.and_then(|pole| target_position(world, &pole));
+    let weight = ik.weight.max(0.).min(1.);
+    let (a, b, c) = match joint_positions(world, &ik.joints) {
+        Some(positions) => (positions[0], positions[1], positions[2]),
+        None => return,
+    };
+    let (upper, lower) = ((b - a).norm(), (c - b).norm());
+    if upper < EPSILON || lower < EPSILON {
+        return;
+    }
+    let reach = (target - a)
+        .norm()
+        .max(EPSILON)
+        .min(upper + lower - EPSILON);
+
+    // bend the chain so its length matches the distance to the target
+    let angle = |x: Vector3<f32>, y: Vector3<f32>| {
+        x.normalize().dot(&y.normalize()).max(-1.).min(1.).acos()
+    };
+    let cosine = |adjacent1: f32, adjacent2: f32, opposite: f32| {
+        ((adjacent1 * adjacent1 + adjacent2 * adjacent2 - opposite * opposite)
+            / (2. * adjacent1 * adjacent2))
+            .max(-1.)
+            .min(1.)
+            .acos()
+    };
+    // a straight chain bends to the side of the pole, or to any side
+    let bend = Unit::try_new((c - a).cross(&(b - a)), EPSILON)
+        .or_else(|| pole.and_then(|p| Unit::try_new((c - a).cross(&(p - a)), EPSILON)))
+        .unwrap_or_else(|| Unit::new_normalize(perpendicular(&(c - a))));
+    let upper_delta = cosine(upper, reach, lower) - angle(c - a, b - a);
+    let middle_delta = cosine(upper, lower, reach) - angle(a - b, c - b);
+    let upper_rotation = UnitQuaternion::from_axis_angle(&bend, upper_delta);
+    let middle_rotation = UnitQuaternion::from_axis_angle(&bend, middle_delta);
+    rotate_joint(world, &ik.joints, 0, &upper_rotation, weight);
+    rotate_joint(world, &ik.joints, 1, &middle_rotation, weight);
+
+    // swing the chain onto the target
+    let end = match joint_positions(world, &ik.joints) {
+        Some(positions) => positions[2],
+        None => return,
+    };
+    if let Some(swing) = rotation_between(&(end - a), &(target - a)) {
+        rotate_joint(world, &ik.joints, 0, &swing, weight);
+    }
+
+    // twist the chain around its axis so the middle joint points to the pole
+    if let Some(pole) = pole {
+        let positions = match joint_positions(world, &ik.joints) {
+            Some(positions) => positions,
+            None => return,
+        };
+        if let Some(axis) = Unit::try_new(positions[2] - a, EPSILON) {
+            let project = |v: Vector3<f32>| v - axis.into_inner() * v.dot(&axis);
+            let middle = project(positions[1] - a);
+            if let Some(twist) = rotation_between(&middle, &project(pole - a)) {
+                rotate_joint(world, &ik.joints, 0, &twist, weight);
+            }
+        }
+    }
+}
+
+fn solve_fabrik(world: &mut SubWorld<'_>, ik: &FabrikIk) {
+    if ik.joints.len() < 2 {
+        return;
+    }
+    let target = match target_position(world, &ik.target) {
+        Some(target) => target,
+        None => return,
+    };
+    let current = match joint_positions(world, &ik.joints) {
+        Some(positions) => positions,
+        None => return,
+    };
+    let solved = fabrik(&current, target, ik.iterations, ik.tolerance);
+    let weight = ik.weight.max(0.).min(1.);
+
+    // rotate the joints from the root, so every bone points to its solved position
+    for index in 0..ik.joints.len() - 1 {
+        let positions = match joint_positions(world, &ik.joints) {
+            Some(positions) => positions,
+            None => return,
+        };
+        let from = positions[index + 1] - positions[index];
+        let to = solved[index + 1] - solved[index];
+        if let Some(rotation) = rotation_between(&from, &to) {
+            rotate_joint(world, &ik.joints, index, &rotation, weight);
+        }
+    }
+}
+
+fn solve_look_at(world: &mut SubWorld<'_>, ik: &LookAtIk) {
+    let target = match target_position(world, &ik.target) {
+        Some(target) => target,
+        None => return,
+    };
+    let global = match chain_globals(world, &[ik.joint]) {
+        Some(globals) => globals[0],
+        None => return,
+    };
+    let forward = rotation(&global) * ik.forward;
+    if let Some(rotation) = rotation_between(&forward, &(target - position(&global))) {
+        rotate_joint(world, &[ik.joint], 0, &rotation, ik.weight.max(0.).min(1.));
+    }
+}
+
+/// Solve the positions of a chain of joints reaching for the target, keeping the first joint in
+/// place and the distances between the joints.
+fn fabrik(
+    positions: &[Vector3<f32>],
+    target: Vector3<f32>,
+    iterations: usize,
+    tolerance: f32,
+) -> Vec<Vector3<f32>> {
+    let mut solved = positions.to_vec();
+    let lengths = positions
+        .windows(2)
+        .map(|pair| (pair[1] - pair[0]).norm())
+        .collect::<Vec<_>>();
+    let root = positions[0];
+    let last = solved.len() - 1;
+    let place = |from: Vector3<f32>, towards: Vector3<f32>, length: f32| {
+        let direction = towards - from;
+        let distance = direction.norm();
+        if distance < EPSILON {
+            from
+        } else {
+            from + direction * (length / distance)
+        }
+    };
+
+    if (target - root).norm() >= lengths.iter().sum::<f32>() {
+        // out of reach, stretch the chain towards the target
+        for i in 0..last {
+            solved[i + 1] = place(solved[i], target, lengths[i]);
+        }
+        return solved;
+    }
+
+    for _ in 0..iterations {
+        if (solved[last] - target).norm() <= tolerance {
+            break;
+        }
+        solved[last] = target;
+        for i in (0..last).rev() {
+            solved[i] = place(solved[i + 1], solved[i], lengths[i]);
+        }
+        solved[0] = root;
+        for i in 0..last {
+            solved[i + 1] = place(solved[i], solved[i + 1], lengths[i]);
+        }
+    }
+    solved
+}
+
+fn target_position(world: &SubWorld<'_>, target: &IkTarget) -> Option<Vector3<f32>> {
+    match target {
+        IkTarget::Position(position) => Some(*position),
+        IkTarget::Entity(entity) => {
+            world.entry_ref(*entity).ok().and_then(|entry| {
+                let transform = entry.get_component::<Transform>().ok();
+                transform.map(|transform| position(transform.global_matrix()))
+            })
+        }
+    }
+}
+
+/// Compute the global matrices of the joints of a chain from their current local transforms.
+fn chain_globals(world: &SubWorld<'_>, joints: &[Entity]) -> Option<Vec<Matrix4<f32>>> {
+    let mut globals: Vec<Matrix4<f32>> = Vec::with_capacity(joints.len());
+    for joint in joints {
+        let entry = world.entry_ref(*joint).ok()?;
+        let transform = entry.get_component::<Transform>().ok()?;
+        let parent = globals
+            .last()
+            .cloned()
+            .unwrap_or(*transform.parent_matrix());
+        globals.push(parent * transform.matrix());
+    }
+    Some(globals)
+}
+
+fn joint_positions(world: &SubWorld<'_>, joints: &[Entity]) -> Option<Vec<Vector3<f32>>> {
+    chain_globals(world, joints).map(|globals| globals.iter().map(position).collect())
+}
+
+/// Rotate a joint of a chain by a rotation in world space, scaled by the weight.
+fn rotate_joint(
+    world: &mut SubWorld<'_>,
+    joints: &[Entity],
+    index: usize,
+    world_rotation: &UnitQuaternion<f32>,
+    weight: f32,
+) {
+    let parent = if index == 0 {
+        world.entry_ref(joints[0]).ok().and_then(|entry| {
+            let transform = entry.get_component::<Transform>().ok();
+            transform.map(|transform| *transform.parent_matrix())
+        })
+    } else {
+        chain_globals(world, &joints[..index]).and_then(|globals| globals.last().cloned())
+    };
+    let parent = match parent {
+        Some(parent) => rotation(&parent),
+        None => return,
+    };
+    let world_rotation = UnitQuaternion::identity().nlerp(world_rotation, weight);
+    if let Ok(mut entry) = world.entry_mut(joints[index]) {
+        if let Ok(transform) = entry.get_component_mut::<Transform>() {
+            let local = *transform.rotation();
+            *transform.rotation_mut() = parent.inverse() * world_rotation * parent * local;
+        }
+    }
+}
+
+fn position(matrix: &Matrix4<f32>) -> Vector3<f32> {
+    matrix.column(3).xyz()
+}
+
+/// Rotation of a matrix, without its scale.
+fn rotation(matrix: &Matrix4<f32>) -> UnitQuaternion<f32> {
+    let mut basis: Matrix3<f32> = matrix.fixed_slice::<U3, U3>(0, 0).into();
+    for mut column in basis.column_iter_mut() {
+        let norm = column.norm();
+        if norm > EPSILON {
+            column /= norm;
+        }
+    }
+    UnitQuaternion::from_rotation_matrix(&Rotation3::from_matrix_unchecked(basis))
+}
+
+/// Shortest rotation from one direction to another.
+fn rotation_between(from: &Vector3<f32>, to: &Vector3<f32>) -> Option<UnitQuaternion<f32>> {
+    if from.norm() < EPSILON || to.norm() < EPSILON {
+        return None;
+    }
+    UnitQuaternion::rotation_between(from, to).or_else(|| {
+        // opposite directions, turn around any perpendicular axis
+        Some(UnitQuaternion::from_axis_angle(
+            &Unit::new_normalize(perpendicular(from)),
+            std::f32::consts::PI,
+        ))
+    })
+}
+
+/// Any direction perpendicular to a non zero vector.
+fn perpendicular(vector: &Vector3<f32>) -> Vector3<f32> {
+    let axis = vector.cross(&Vector3::x());
+    if axis.norm() < EPSILON {
+        vector.cross(&Vector3::y())
+    } else {
+        axis
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fabrik_reaches_target() {
+        let chain = vec![
+            Vector3::new(0., 0., 0.),
+            Vector3::new(0., 1., 0.),
+            Vector3::new(0., 2., 0.),
+            Vector3::new(0., 3., 0.),
+        ];
+        let target = Vector3::new(1.5, 1.5, 0.);
+        let solved = fabrik(&chain, target, 20, 1e-4);
+        assert!((solved[3] - target).norm() < 1e-3);
+        assert_eq!(solved[0], chain[0]);
+        for pair in solved.windows(2) {
+            assert!(((pair[1] - pair[0]).norm() - 1.).abs() < 1e-4);
+        }
+
+        // out of reach, the chain points at the target
+        let solved = fabrik(&chain, Vector3::new(10., 0., 0.), 20, 1e-4);
+        assert!((solved[3] - Vector3::new(3., 0., 0.)).norm() < 1e-4);
+    }
+}
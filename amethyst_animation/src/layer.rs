@@ -0,0 +1,108 @@
+//! Animation layers, playing animations over only a part of a hierarchy, such as an aiming
+//! animation on the upper body over a running animation on the whole body.
+
+use fnv::FnvHashMap;
+use serde::{Deserialize, Serialize};
+
+/// Weights of the nodes of an `AnimationHierarchy` for an `AnimationLayer`.
+///
+/// Nodes are identified by their index in the hierarchy, the same index used by the nodes of an
+/// `Animation`. Nodes which aren't in the mask have a weight of 0.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct AnimationMask {
+    /// The weights of the nodes in the mask
+    pub weights: FnvHashMap<usize, f32>,
+}
+
+impl AnimationMask {
+    /// Creates an empty mask
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Creates a mask including the given nodes with a weight of 1.0
+    pub fn from_nodes<N>(nodes: N) -> Self
+    where
+        N: IntoIterator<Item = usize>,
+    {
+        AnimationMask {
+            weights: nodes.into_iter().map(|node| (node, 1.0)).collect(),
+        }
+    }
+
+    /// Include a node in the mask with the given weight, for fading the layer out towards the
+    /// edges of the mask
+    pub fn with_node(mut self, node: usize, weight: f32) -> Self {
+        self.weights.insert(node, weight);
+        self
+    }
+
+    /// Get the weight of a node
+    pub fn weight(&self, node: usize) -> f32 {
+        self.weights.get(&node).cloned().unwrap_or(0.)
+    }
+}
+
+/// A layer of an `AnimationControlSet`.
+///
+/// The animations of a layer are blended with each other by their weights, and the result
+/// replaces the result of the lower layers by the weight of the layer on the nodes of its mask.
+/// Layers which aren't set on the control set have a weight of 1.0 and no mask.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AnimationLayer {
+    /// Weight of the layer over the lower layers, default is 1.0
+    pub weight: f32,
+    /// The nodes the layer applies to, all nodes if `None`
+    pub mask: Option<AnimationMask>,
+}
+
+impl Default for AnimationLayer {
+    fn default() -> Self {
+        AnimationLayer {
+            weight: 1.0,
+            mask: None,
+        }
+    }
+}
+
+impl AnimationLayer {
+    /// Creates a layer applying to all nodes
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Creates a layer applying to the nodes of the given mask
+    pub fn masked(mask: AnimationMask) -> Self {
+        AnimationLayer {
+            weight: 1.0,
+            mask: Some(mask),
+        }
+    }
+
+    /// Set the weight of the layer
+    pub fn with_weight(mut self, weight: f32) -> Self {
+        self.weight = weight;
+        self
+    }
+
+    /// Get the weight of the layer on a node
+    pub fn node_weight(&self, node: usize) -> f32 {
+        let mask = self.mask.as_ref().map_or(1.0, |mask| mask.weight(node));
+        self.weight * mask
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_node_weights() {
+        let layer = AnimationLayer::masked(AnimationMask::from_nodes(vec![1, 2]).with_node(3, 0.5))
+            .with_weight(0.8);
+        assert_eq!(layer.node_weight(0), 0.);
+        assert_eq!(layer.node_weight(1), 0.8);
+        assert_eq!(layer.node_weight(3), 0.4);
+        assert_eq!(AnimationLayer::new().node_weight(7), 1.);
+    }
+}
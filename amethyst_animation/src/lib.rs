@@ -61,26 +61,56 @@
 pub use minterpolate::{InterpolationFunction, InterpolationPrimitive};
 
 pub use self::{
-    bundle::{AnimationBundle, SamplingBundle, VertexSkinningBundle},
+    blend_tree::{BlendNode, BlendTree, BlendWeight},
+    bundle::{
+        AnimationBundle, AnimationLodBundle, AnimationStateMachineBundle, IkBundle,
+        MorphTargetBundle, RetargetBundle, RootMotionBundle, SamplingBundle, TweenBundle,
+        VertexSkinningBundle,
+    },
+    clip::{AnimationClip, AnimationClipFormat, ClipChannel, ClipInterpolation, ClipKey},
+    compression::{PackedSampler, SamplerCompression},
+    ik::{FabrikIk, IkSystem, IkTarget, LookAtIk, TwoBoneIk},
+    layer::{AnimationLayer, AnimationMask},
+    lod::{AnimationLod, AnimationLodSystem},
     material::{MaterialChannel, MaterialPrimitive},
+    morph::{MorphChannel, MorphTargetSystem},
     resources::{
-        Animation, AnimationCommand, AnimationControl, AnimationControlSet, AnimationHierarchy,
-        AnimationSampling, AnimationSet, BlendMethod, ControlState, DeferStartRelation, EndControl,
-        RestState, Sampler, SamplerControl, SamplerControlSet, StepDirection,
+        Animation, AnimationCommand, AnimationControl, AnimationControlSet, AnimationEvent,
+        AnimationHierarchy, AnimationSampling, AnimationSet, BlendMethod, ControlState,
+        DeferStartRelation, EndControl, RestState, Sampler, SamplerControl, SamplerControlSet,
+        StepDirection,
     },
+    retarget::{BoneMapping, Retarget, RetargetMap, RetargetSystem},
+    root_motion::{RootMotion, RootMotionSystem},
     skinning::{Joint, Skin, VertexSkinningSystem},
     sprite::{SpriteRenderChannel, SpriteRenderPrimitive},
+    state_machine::{
+        AnimationState, AnimationStateMachine, AnimationStateMachineControl, Transition,
+        TransitionCondition,
+    },
     transform::TransformChannel,
+    tween::{tween, Easing, FieldTween, Tween, TweenBuilder, TweenId, Tweenable, Tweens},
     util::{get_animation_set, SamplerPrimitive},
 };
 
+mod blend_tree;
 mod bundle;
+mod clip;
+mod compression;
+mod ik;
+mod layer;
+mod lod;
 mod material;
+mod morph;
 mod resources;
+mod retarget;
+mod root_motion;
 mod skinning;
 mod sprite;
+mod state_machine;
 mod systems;
 mod transform;
+mod tween;
 #[cfg(feature = "ui")]
 mod ui_transform;
 mod util;
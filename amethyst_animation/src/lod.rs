@@ -0,0 +1,105 @@
+//! Level of detail of animation sampling, sampling distant characters at a lower rate.
+
+use amethyst_core::{ecs::*, math::Vector3, transform::Transform};
+use amethyst_rendy::camera::{ActiveCamera, Camera};
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+
+/// Level of detail of the sampling of an animated hierarchy.
+///
+/// Attach it to the entity with the `AnimationControlSet`, and the `AnimationControlSystem` sets
+/// the rate of the `SamplerControlSet`s of the hierarchy to the rate of this component. The
+/// frames between two samples are interpolated, so the animation stays smooth but lags behind
+/// by one sampling interval.
+///
+/// The rate can be set directly, or be computed from the distance to the camera by the
+/// `AnimationLodSystem` from the levels.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AnimationLod {
+    /// Distances to the camera from which the animations are sampled at the given rates in Hz,
+    /// sorted by distance. Closer than the first distance, the animations are sampled every
+    /// frame.
+    pub levels: Vec<(f32, f32)>,
+    /// Number of times per second the animations are sampled, every frame if `None`
+    pub rate: Option<f32>,
+}
+
+impl AnimationLod {
+    /// Creates a level of detail with the given distances and rates.
+    pub fn new(mut levels: Vec<(f32, f32)>) -> Self {
+        levels.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+        AnimationLod { levels, rate: None }
+    }
+
+    /// Creates a fixed sampling rate, which isn't changed by the `AnimationLodSystem`.
+    pub fn fixed(rate: f32) -> Self {
+        AnimationLod {
+            levels: Vec::new(),
+            rate: Some(rate),
+        }
+    }
+
+    /// Get the rate of the level for the given distance to the camera
+    pub fn rate_at(&self, distance: f32) -> Option<f32> {
+        self.levels
+            .iter()
+            .rev()
+            .find(|(level, _)| distance >= *level)
+            .map(|(_, rate)| *rate)
+    }
+}
+
+/// System setting the rate of the `AnimationLod`s from their distance to the camera.
+///
+/// Uses the `ActiveCamera`, or the first camera if there is no active camera. Should run after
+/// `TransformSystem`, `AnimationLod`s without levels are left alone.
+#[derive(Debug, Default)]
+pub struct AnimationLodSystem;
+
+impl System for AnimationLodSystem {
+    fn build(self) -> Box<dyn ParallelRunnable> {
+        Box::new(
+            SystemBuilder::new("AnimationLodSystem")
+                .read_resource::<ActiveCamera>()
+                .with_query(<(Entity, Read<Camera>, Read<Transform>)>::query())
+                .with_query(<(Write<AnimationLod>, Read<Transform>)>::query())
+                .build(move |_, world, active_camera, (cameras, lods)| {
+                    #[cfg(feature = "profiler")]
+                    profile_scope!("animation_lod_system");
+
+                    let camera = cameras
+                        .iter(world)
+                        .find(|(entity, _, _)| active_camera.entity == Some(**entity))
+                        .or_else(|| cameras.iter(world).next())
+                        .map(|(_, _, transform)| position(transform));
+                    let camera = match camera {
+                        Some(camera) => camera,
+                        None => return,
+                    };
+
+                    for (lod, transform) in lods.iter_mut(world) {
+                        if !lod.levels.is_empty() {
+                            lod.rate = lod.rate_at((position(transform) - camera).norm());
+                        }
+                    }
+                }),
+        )
+    }
+}
+
+fn position(transform: &Transform) -> Vector3<f32> {
+    transform.global_matrix().column(3).xyz()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rate_of_distance() {
+        let lod = AnimationLod::new(vec![(50., 10.), (20., 30.)]);
+        assert_eq!(lod.rate_at(5.), None);
+        assert_eq!(lod.rate_at(20.), Some(30.));
+        assert_eq!(lod.rate_at(100.), Some(10.));
+    }
+}
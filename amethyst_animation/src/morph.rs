@@ -0,0 +1,95 @@
+use amethyst_assets::{
+    register_asset_type, AssetProcessorSystem, DefaultLoader, Handle, Loader, ProcessingQueue,
+    TypeUuid,
+};
+use amethyst_core::ecs::*;
+use amethyst_rendy::{
+    morph::{MorphTargets, MorphWeights},
+    types::{Mesh, MeshData},
+};
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+use uuid::Uuid;
+
+use crate::{
+    resources::{AnimationSampling, BlendMethod},
+    util::SamplerPrimitive,
+    Animation,
+};
+
+/// Channels that can be animated on `MorphWeights`
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq, Serialize, Deserialize)]
+pub enum MorphChannel {
+    /// The weight of the morph target with the given index
+    Weight(usize),
+}
+
+impl TypeUuid for Animation<MorphWeights> {
+    const UUID: type_uuid::Bytes =
+        *Uuid::from_u128(336925465025999861742708840199405426353).as_bytes();
+}
+register_asset_type!(Animation<MorphWeights> => Animation<MorphWeights>; AssetProcessorSystem<Animation<MorphWeights>>);
+
+impl AnimationSampling for MorphWeights {
+    type Primitive = SamplerPrimitive<f32>;
+    type Channel = MorphChannel;
+
+    fn apply_sample(
+        &mut self,
+        channel: &Self::Channel,
+        data: &Self::Primitive,
+        _buffer: &mut CommandBuffer,
+    ) {
+        match (channel, *data) {
+            (MorphChannel::Weight(index), SamplerPrimitive::Scalar(weight)) => {
+                self.set_weight(*index, weight);
+            }
+            _ => panic!("Attempt to apply invalid sample to MorphWeights"),
+        }
+    }
+
+    fn current_sample(&self, channel: &Self::Channel) -> Self::Primitive {
+        match channel {
+            MorphChannel::Weight(index) => SamplerPrimitive::Scalar(self.weight(*index)),
+        }
+    }
+
+    fn default_primitive(_: &Self::Channel) -> Self::Primitive {
+        SamplerPrimitive::Scalar(0.)
+    }
+
+    fn blend_method(&self, _: &Self::Channel) -> Option<BlendMethod> {
+        Some(BlendMethod::Linear)
+    }
+}
+
+/// System for applying morph targets.
+///
+/// Rebuilds the `Mesh` of the entities whose `MorphWeights` changed, from their `MorphTargets`.
+/// Needs to run after `SamplerInterpolationSystem<MorphWeights>` if the weights are animated.
+#[derive(Debug, Default)]
+pub struct MorphTargetSystem;
+
+impl System for MorphTargetSystem {
+    fn build(self) -> Box<dyn ParallelRunnable> {
+        Box::new(
+            SystemBuilder::new("MorphTargetSystem")
+                .read_resource::<DefaultLoader>()
+                .read_resource::<ProcessingQueue<MeshData>>()
+                .with_query(
+                    <(Read<MorphTargets>, Read<MorphWeights>, Write<Handle<Mesh>>)>::query()
+                        .filter(maybe_changed::<MorphWeights>()),
+                )
+                .build(move |_, world, (loader, mesh_queue), query| {
+                    #[cfg(feature = "profiler")]
+                    profile_scope!("morph_target_system");
+
+                    for (targets, weights, mesh) in query.iter_mut(world) {
+                        let data = MeshData(targets.build_mesh(&weights.weights));
+                        *mesh = loader.load_from_data(data, (), mesh_queue);
+                    }
+                }),
+        )
+    }
+}
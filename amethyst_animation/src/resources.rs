@@ -11,6 +11,8 @@ use log::debug;
 use minterpolate::{get_input_index, InterpolationFunction, InterpolationPrimitive};
 use serde::{Deserialize, Serialize};
 
+use crate::{blend_tree::BlendTree, layer::AnimationLayer};
+
 /// Blend method for sampler blending
 #[derive(Clone, Copy, Debug, PartialOrd, PartialEq, Eq, Hash)]
 pub enum BlendMethod {
@@ -187,7 +189,7 @@ where
 /// - `T`: the component type that the animation should be applied to
 ///
 /// [sampler]: struct.Sampler.html
-#[derive(Clone, Debug, Default, Deserialize)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 #[serde(bound(
     serialize = "T::Channel: Serialize, T::Primitive: Serialize",
     deserialize = "T::Channel: Deserialize<'de>, T::Primitive: Deserialize<'de>",
@@ -198,6 +200,9 @@ where
 {
     /// node index -> sampler handle
     pub nodes: Vec<(usize, T::Channel, Handle<Sampler<T::Primitive>>)>,
+    /// time in seconds -> event name, emitted as an `AnimationEvent` when playback crosses it
+    #[serde(default)]
+    pub events: Vec<(f32, String)>,
 }
 
 impl<T> Animation<T>
@@ -206,7 +211,10 @@ where
 {
     /// Create new empty animation
     pub fn new() -> Self {
-        Animation { nodes: vec![] }
+        Animation {
+            nodes: vec![],
+            events: vec![],
+        }
     }
 
     /// Create an animation with a single sampler
@@ -217,6 +225,7 @@ where
     ) -> Self {
         Animation {
             nodes: vec![(index, channel, sampler)],
+            events: vec![],
         }
     }
 
@@ -240,6 +249,38 @@ where
         self.nodes.push((node_index, channel, sampler));
         self
     }
+
+    /// Add an event to the timeline of the animation
+    pub fn add_event<S: Into<String>>(&mut self, time: f32, name: S) {
+        self.events.push((time, name.into()));
+    }
+
+    /// Add an event to the timeline of the animation
+    pub fn with_event<S: Into<String>>(mut self, time: f32, name: S) -> Self {
+        self.events.push((time, name.into()));
+        self
+    }
+}
+
+/// Event emitted when the playback of an animation crosses one of its events.
+///
+/// Events are emitted into an `EventChannel<AnimationEvent<I>>` by the `AnimationControlSystem`,
+/// in the order they are crossed, also when playing backwards or skipping over them during a
+/// long frame. Events of loops skipped entirely in a single frame are only emitted once.
+///
+/// ### Type parameters:
+///
+/// - `I`: identifier type for running animations
+#[derive(Clone, Debug, PartialEq)]
+pub struct AnimationEvent<I> {
+    /// The entity with the `AnimationControlSet` running the animation
+    pub entity: Entity,
+    /// Id of the animation in the `AnimationControlSet`
+    pub animation: I,
+    /// Name of the event
+    pub name: String,
+    /// Time of the event in the animation, in seconds
+    pub time: f32,
 }
 
 impl<T> Asset for Animation<T>
@@ -308,6 +349,12 @@ where
     pub channel: T::Channel,
     /// Blend weight
     pub blend_weight: f32,
+    /// Weight of the animation the sampler belongs to, multiplied with the blend weight
+    pub animation_weight: f32,
+    /// Layer of the animation the sampler belongs to
+    pub layer: usize,
+    /// Weight of the layer on the node of this sampler, over the lower layers
+    pub layer_weight: f32,
     /// Sampler
     pub sampler: Handle<Sampler<T::Primitive>>,
     /// State of sampling
@@ -326,6 +373,8 @@ where
 /// samplers. The target component specifies if it can be blended, if it can't, the last added
 /// sampler wins.
 ///
+/// The samplers can be sampled at a lower rate than the frame rate, see `set_update_rate`.
+///
 /// ### Type parameters:
 ///
 /// - `T`: the component type that the sampling should be applied to
@@ -336,6 +385,7 @@ where
 {
     /// The samplers in this set.
     pub samplers: Vec<SamplerControl<T>>,
+    pub(crate) lod: SamplingLod<T>,
 }
 
 impl<T> Default for SamplerControlSet<T>
@@ -345,6 +395,40 @@ where
     fn default() -> Self {
         SamplerControlSet {
             samplers: Vec::default(),
+            lod: SamplingLod::default(),
+        }
+    }
+}
+
+/// The state of the sampling of a `SamplerControlSet` at a lower rate than the frame rate.
+///
+/// The frames between two samples are interpolated between the last two samples, so the
+/// animation lags behind by one sampling interval.
+#[derive(Clone, Debug)]
+pub(crate) struct SamplingLod<T>
+where
+    T: AnimationSampling,
+{
+    /// Seconds between two samples, 0 for sampling every frame
+    pub interval: f32,
+    /// Seconds since the last sample
+    pub elapsed: f32,
+    /// The blended values of the channels in the sample before the last one
+    pub previous: Vec<(T::Channel, T::Primitive)>,
+    /// The blended values of the channels in the last sample
+    pub current: Vec<(T::Channel, T::Primitive)>,
+}
+
+impl<T> Default for SamplingLod<T>
+where
+    T: AnimationSampling,
+{
+    fn default() -> Self {
+        SamplingLod {
+            interval: 0.,
+            elapsed: 0.,
+            previous: Vec::default(),
+            current: Vec::default(),
         }
     }
 }
@@ -369,6 +453,14 @@ where
         }
     }
 
+    /// Set the number of times per second the samplers are sampled, or `None` for every frame
+    pub fn set_update_rate(&mut self, rate: Option<f32>) {
+        self.lod.interval = match rate {
+            Some(rate) if rate > 0. => 1. / rate,
+            _ => 0.,
+        };
+    }
+
     /// Clear sampler controls for the given animation
     pub fn clear(&mut self, control_id: u64) {
         self.samplers.retain(|t| t.control_id != control_id);
@@ -426,6 +518,25 @@ where
             .for_each(|sampler| sampler.rate_multiplier = rate_multiplier);
     }
 
+    /// Update the weight of the animation
+    pub fn set_animation_weight(&mut self, control_id: u64, animation_weight: f32) {
+        self.samplers
+            .iter_mut()
+            .filter(|t| t.control_id == control_id)
+            .for_each(|sampler| sampler.animation_weight = animation_weight);
+    }
+
+    /// Update the layer of the animation, and the weight of the layer on this node
+    pub fn set_layer(&mut self, control_id: u64, layer: usize, layer_weight: f32) {
+        self.samplers
+            .iter_mut()
+            .filter(|t| t.control_id == control_id)
+            .for_each(|sampler| {
+                sampler.layer = layer;
+                sampler.layer_weight = layer_weight;
+            });
+    }
+
     /// Forcibly set the input value (point of interpolation)
     pub fn set_input(&mut self, control_id: u64, input: f32)
     where
@@ -577,8 +688,12 @@ where
     pub state: ControlState,
     /// Animation command
     pub command: AnimationCommand<T>,
-    /// Control the rate of animation, default is 1.0
+    /// Control the rate of animation, default is 1.0, negative rates play the animation backwards
     pub rate_multiplier: f32,
+    /// Weight of the animation when blended with the other animations of the set, default is 1.0
+    pub weight: f32,
+    /// Layer of the animation in the set, default is 0
+    pub layer: usize,
     m: marker::PhantomData<T>,
 }
 
@@ -601,6 +716,8 @@ where
             state,
             command,
             rate_multiplier,
+            weight: 1.0,
+            layer: 0,
             m: marker::PhantomData,
         }
     }
@@ -628,8 +745,13 @@ where
 /// Contains all currently running animations for an entity.
 ///
 /// Have support for running multiple animations, will do linear blending between all active
-/// animations. The target component specifies if it can be blended, if it can't, the last added
-/// animation wins.
+/// animations by their weights. The weights can be set directly, or be computed from parameters
+/// by a `BlendTree`. The target component specifies if it can be blended, if it can't, the last
+/// added animation wins.
+///
+/// Animations can also be put on layers, and the animations of higher layers replace the lower
+/// layers on the nodes of the masks of their `AnimationLayer`s, e.g. an upper body aiming
+/// animation over a running animation.
 ///
 /// ### Type parameters:
 ///
@@ -644,6 +766,8 @@ where
     /// The animation set.
     pub animations: Vec<(I, AnimationControl<T>)>,
     pub(crate) deferred_animations: Vec<DeferredStart<I, T>>,
+    pub(crate) blend_tree: Option<BlendTree<I>>,
+    pub(crate) layers: FnvHashMap<usize, AnimationLayer>,
 }
 
 impl<I, T> Default for AnimationControlSet<I, T>
@@ -654,6 +778,8 @@ where
         AnimationControlSet {
             animations: Vec::default(),
             deferred_animations: Vec::default(),
+            blend_tree: None,
+            layers: FnvHashMap::default(),
         }
     }
 }
@@ -747,6 +873,113 @@ where
         self.set_command(id, AnimationCommand::SetBlendWeights(weights))
     }
 
+    /// Set the weight of an animation, used when blending it with the other animations
+    pub fn set_weight(&mut self, id: I, weight: f32) -> &mut Self {
+        if let Some(&mut (_, ref mut control)) = self.animations.iter_mut().find(|a| a.0 == id) {
+            control.weight = weight;
+        }
+        if let Some(ref mut control) = self
+            .deferred_animations
+            .iter_mut()
+            .find(|a| a.animation_id == id)
+        {
+            control.control.weight = weight;
+        }
+
+        self
+    }
+
+    /// Put an animation on a layer
+    pub fn set_layer(&mut self, id: I, layer: usize) -> &mut Self {
+        if let Some(&mut (_, ref mut control)) = self.animations.iter_mut().find(|a| a.0 == id) {
+            control.layer = layer;
+        }
+        if let Some(ref mut control) = self
+            .deferred_animations
+            .iter_mut()
+            .find(|a| a.animation_id == id)
+        {
+            control.control.layer = layer;
+        }
+
+        self
+    }
+
+    /// Set the weight and mask of a layer
+    pub fn set_animation_layer(
+        &mut self,
+        layer: usize,
+        animation_layer: AnimationLayer,
+    ) -> &mut Self {
+        self.layers.insert(layer, animation_layer);
+        self
+    }
+
+    /// Set the weight of a layer, e.g. for fading aiming in and out
+    pub fn set_layer_weight(&mut self, layer: usize, weight: f32) -> &mut Self {
+        self.layers.entry(layer).or_default().weight = weight;
+        self
+    }
+
+    /// Get a layer, if it has been set
+    pub fn animation_layer(&self, layer: usize) -> Option<&AnimationLayer> {
+        self.layers.get(&layer)
+    }
+
+    /// Remove a layer, its animations go back to a weight of 1.0 and no mask
+    pub fn remove_animation_layer(&mut self, layer: usize) -> Option<AnimationLayer> {
+        self.layers.remove(&layer)
+    }
+
+    /// Set the blend tree computing the weights of the animations
+    pub fn set_blend_tree(&mut self, tree: BlendTree<I>) -> &mut Self {
+        self.blend_tree = Some(tree);
+        self
+    }
+
+    /// Get the blend tree
+    pub fn blend_tree(&self) -> Option<&BlendTree<I>> {
+        self.blend_tree.as_ref()
+    }
+
+    /// Get the blend tree mutably
+    pub fn blend_tree_mut(&mut self) -> Option<&mut BlendTree<I>> {
+        self.blend_tree.as_mut()
+    }
+
+    /// Remove the blend tree, the animations it weighted go back to a weight of 1.0
+    pub fn clear_blend_tree(&mut self) -> &mut Self
+    where
+        I: Clone,
+    {
+        if let Some(tree) = self.blend_tree.take() {
+            for (id, _) in tree.evaluate() {
+                self.set_weight(id, 1.0);
+            }
+        }
+        self
+    }
+
+    /// Set a parameter of the blend tree, if there is one
+    pub fn set_blend_parameter(&mut self, name: &str, value: f32) -> &mut Self {
+        if let Some(tree) = self.blend_tree.as_mut() {
+            tree.set_parameter(name, value);
+        }
+        self
+    }
+
+    /// Set the weights of the animations in the blend tree from its parameters
+    pub(crate) fn apply_blend_tree(&mut self)
+    where
+        I: Clone,
+    {
+        if let Some(weights) = self.blend_tree.as_ref().map(BlendTree::evaluate) {
+            for (id, weight) in weights {
+                self.set_weight(id, weight);
+            }
+        }
+    }
+
     /// Abort animation
     pub fn abort(&mut self, id: I) -> &mut Self {
         self.set_command(id, AnimationCommand::Abort)
@@ -0,0 +1,302 @@
+//! Animation retargeting, playing the animations of one skeleton on another skeleton with
+//! different bone names and orientations, e.g. a shared library of humanoid animations.
+
+use amethyst_assets::{
+    distill_importer::{typetag, SerdeImportable},
+    register_asset_type, Asset, AssetProcessorSystem, AssetStorage, Handle,
+};
+use amethyst_core::{
+    ecs::*,
+    math::{Quaternion, UnitQuaternion, Vector4},
+    transform::{Parent, Transform},
+    Named,
+};
+use fnv::FnvHashMap;
+use log::warn;
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+use type_uuid::TypeUuid;
+
+use crate::resources::RestState;
+
+/// Maps a bone of the source skeleton to a bone of the target skeleton.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BoneMapping {
+    /// `Named` of the bone in the source skeleton
+    pub source: String,
+    /// `Named` of the bone in the target skeleton
+    pub target: String,
+    /// Rotation from the space of the source bone's parent to the space of the target bone's
+    /// parent, as a quaternion `[x, y, z, w]`, default is no rotation
+    #[serde(default = "identity")]
+    pub orientation: [f32; 4],
+    /// Copy the movement of the bone as well as its rotation, usually only for the hips
+    #[serde(default)]
+    pub translation: bool,
+}
+
+fn identity() -> [f32; 4] {
+    [0., 0., 0., 1.]
+}
+
+fn one() -> f32 {
+    1.
+}
+
+/// Retargeting map asset, mapping the bones of a source skeleton to a target skeleton.
+///
+/// Bones which aren't mapped keep their own animation.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TypeUuid)]
+#[uuid = "e4169d75-8710-4978-b07b-745b1bef498a"]
+pub struct RetargetMap {
+    /// The mapped bones
+    pub bones: Vec<BoneMapping>,
+    /// Scale of the copied translations, the height of the target skeleton divided by the
+    /// height of the source skeleton, default is 1.0
+    #[serde(default = "one")]
+    pub translation_scale: f32,
+}
+
+impl Asset for RetargetMap {
+    fn name() -> &'static str {
+        "animation::RetargetMap"
+    }
+    type Data = Self;
+}
+
+#[typetag::serde]
+impl SerdeImportable for RetargetMap {}
+register_asset_type!(RetargetMap => RetargetMap; AssetProcessorSystem<RetargetMap>);
+
+/// Plays the animations of a source skeleton on the skeleton this component is attached to.
+///
+/// Attach it to the root entity of the target skeleton, and play the animations on the source
+/// skeleton, which can be hidden. The bones of both skeletons are found by their `Named`
+/// components once the map is loaded, and their rest poses are taken from their
+/// `RestState<Transform>`, or their `Transform` at that time.
+#[derive(Debug, Clone)]
+pub struct Retarget {
+    /// The retargeting map
+    pub map: Handle<RetargetMap>,
+    /// Root entity of the source skeleton
+    pub source: Entity,
+    bones: Option<Vec<RetargetBone>>,
+}
+
+impl Retarget {
+    /// Creates a retargeting of the skeleton with the given root entity.
+    pub fn new(map: Handle<RetargetMap>, source: Entity) -> Self {
+        Retarget {
+            map,
+            source,
+            bones: None,
+        }
+    }
+
+    /// Find the bones again, e.g. after the map or one of the skeletons was changed
+    pub fn reset(&mut self) {
+        self.bones = None;
+    }
+}
+
+#[derive(Debug, Clone)]
+struct RetargetBone {
+    source: Entity,
+    target: Entity,
+    orientation: UnitQuaternion<f32>,
+    translation: bool,
+    source_rest: Transform,
+    target_rest: Transform,
+}
+
+/// System applying `Retarget`.
+///
+/// Needs to run after `SamplerInterpolationSystem<Transform>`, and before `TransformSystem`.
+#[derive(Debug, Default)]
+pub struct RetargetSystem;
+
+impl System for RetargetSystem {
+    fn build(self) -> Box<dyn ParallelRunnable> {
+        Box::new(
+            SystemBuilder::new("RetargetSystem")
+                .read_resource::<AssetStorage<RetargetMap>>()
+                .read_component::<Parent>()
+                .read_component::<RestState<Transform>>()
+                .write_component::<Transform>()
+                .with_query(<(Entity, Write<Retarget>)>::query())
+                .with_query(<(Entity, Read<Named>)>::query())
+                .build(move |_, world, maps, (retargets, named)| {
+                    #[cfg(feature = "profiler")]
+                    profile_scope!("retarget_system");
+
+                    let (mut retarget_world, mut world) = world.split_for_query(retargets);
+                    for (entity, retarget) in retargets.iter_mut(&mut retarget_world) {
+                        let map = match maps.get(&retarget.map) {
+                            Some(map) => map,
+                            None => continue,
+                        };
+                        if retarget.bones.is_none() {
+                            let source = retarget.source;
+                            retarget.bones = Some(resolve(&world, named, map, source, *entity));
+                        }
+                        for bone in retarget.bones.iter().flatten() {
+                            apply(&mut world, bone, map.translation_scale);
+                        }
+                    }
+                }),
+        )
+    }
+}
+
+/// Find the entities of the mapped bones and their rest poses.
+fn resolve(
+    world: &SubWorld<'_>,
+    named: &mut Query<(Entity, Read<Named>)>,
+    map: &RetargetMap,
+    source: Entity,
+    target: Entity,
+) -> Vec<RetargetBone> {
+    let mut source_bones = FnvHashMap::default();
+    let mut target_bones = FnvHashMap::default();
+    for (entity, name) in named.iter(world) {
+        if is_in_hierarchy(world, *entity, source) {
+            source_bones.insert(name.0.to_string(), *entity);
+        } else if is_in_hierarchy(world, *entity, target) {
+            target_bones.insert(name.0.to_string(), *entity);
+        }
+    }
+
+    map.bones
+        .iter()
+        .filter_map(|mapping| {
+            let bones = (
+                source_bones.get(&mapping.source),
+                target_bones.get(&mapping.target),
+            );
+            let (source, target) = match bones {
+                (Some(source), Some(target)) => (*source, *target),
+                _ => {
+                    warn!(
+                        "Bones {} and {} of the retargeting map weren't found",
+                        mapping.source, mapping.target
+                    );
+                    return None;
+                }
+            };
+            Some(RetargetBone {
+                source,
+                target,
+                orientation: UnitQuaternion::new_normalize(Quaternion::from(Vector4::from(
+                    mapping.orientation,
+                ))),
+                translation: mapping.translation,
+                source_rest: rest_pose(world, source)?,
+                target_rest: rest_pose(world, target)?,
+            })
+        })
+        .collect()
+}
+
+fn is_in_hierarchy(world: &SubWorld<'_>, mut entity: Entity, root: Entity) -> bool {
+    // bounded, in case of a cycle in the hierarchy
+    for _ in 0..256 {
+        if entity == root {
+            return true;
+        }
+        let parent = world
+            .entry_ref(entity)
+            .ok()
+            .and_then(|entry| entry.get_component::<Parent>().ok().map(|p| p.0));
+        match parent {
+            Some(parent) => entity = parent,
+            None => return false,
+        }
+    }
+    false
+}
+
+fn rest_pose(world: &SubWorld<'_>, entity: Entity) -> Option<Transform> {
+    let entry = world.entry_ref(entity).ok()?;
+    let rest = entry.get_component::<RestState<Transform>>().ok();
+    match rest {
+        Some(rest) => Some(rest.state().clone()),
+        None => entry.get_component::<Transform>().ok().cloned(),
+    }
+}
+
+fn apply(world: &mut SubWorld<'_>, bone: &RetargetBone, translation_scale: f32) {
+    let source = match world
+        .entry_ref(bone.source)
+        .ok()
+        .and_then(|entry| entry.get_component::<Transform>().ok().cloned())
+    {
+        Some(source) => source,
+        None => return,
+    };
+    let rotation = retarget_rotation(
+        &bone.orientation,
+        source.rotation(),
+        bone.source_rest.rotation(),
+        bone.target_rest.rotation(),
+    );
+    let translation = if bone.translation {
+        let offset = source.translation() - bone.source_rest.translation();
+        Some(bone.target_rest.translation() + bone.orientation * offset * translation_scale)
+    } else {
+        None
+    };
+
+    if let Ok(mut entry) = world.entry_mut(bone.target) {
+        if let Ok(transform) = entry.get_component_mut::<Transform>() {
+            *transform.rotation_mut() = rotation;
+            if let Some(translation) = translation {
+                *transform.translation_mut() = translation;
+            }
+        }
+    }
+}
+
+/// Apply the rotation of a source bone away from its rest pose to the rest pose of the target
+/// bone.
+fn retarget_rotation(
+    orientation: &UnitQuaternion<f32>,
+    source: &UnitQuaternion<f32>,
+    source_rest: &UnitQuaternion<f32>,
+    target_rest: &UnitQuaternion<f32>,
+) -> UnitQuaternion<f32> {
+    let delta = source * source_rest.inverse();
+    orientation * delta * orientation.inverse() * target_rest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rotations_are_retargeted_relative_to_rest_poses() {
+        let source_rest = UnitQuaternion::from_euler_angles(0., 0.5, 0.);
+        let target_rest = UnitQuaternion::from_euler_angles(0.2, 0., 0.);
+        let turn = UnitQuaternion::from_euler_angles(0., 0., 1.);
+
+        // the source bone at rest leaves the target bone at rest
+        let rotation = retarget_rotation(
+            &UnitQuaternion::identity(),
+            &source_rest,
+            &source_rest,
+            &target_rest,
+        );
+        assert!(rotation.angle_to(&target_rest) < 1e-5);
+
+        // an orientation of a quarter turn maps a turn around z to a turn around x
+        let orientation = UnitQuaternion::from_euler_angles(0., std::f32::consts::FRAC_PI_2, 0.);
+        let rotation = retarget_rotation(
+            &orientation,
+            &(turn * source_rest),
+            &source_rest,
+            &UnitQuaternion::identity(),
+        );
+        let expected = UnitQuaternion::from_euler_angles(1., 0., 0.);
+        assert!(rotation.angle_to(&expected) < 1e-5);
+    }
+}
@@ -0,0 +1,293 @@
+//! Root motion, moving a character by the movement of the root bone in its animations instead of
+//! moving the bone away from the character.
+
+use std::{f32::consts::PI, ops::Add};
+
+use amethyst_assets::AssetStorage;
+use amethyst_core::{
+    ecs::*,
+    math::{Quaternion, UnitQuaternion, Vector3, Vector4},
+    timing::duration_to_secs,
+    transform::Transform,
+};
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+
+use crate::{
+    resources::{ControlState, RestState, Sampler, SamplerControlSet},
+    transform::TransformChannel,
+    util::SamplerPrimitive,
+};
+
+/// Extracts the motion of a root bone from its `Transform` animations.
+///
+/// Attach it to the root bone of a skeleton. Every frame, the `RootMotionSystem` computes how far
+/// the running animations of the bone moved and turned it, weighted like their samples, and
+/// keeps the bone in its rest position instead. The motion is applied to the `Transform` of the
+/// `target` entity, or only recorded for a character controller to apply if there's no target.
+#[derive(Debug, Clone)]
+pub struct RootMotion {
+    /// Entity moved by the root motion, usually the character the skeleton belongs to
+    pub target: Option<Entity>,
+    /// Axes of the translation which are extracted, the others stay on the bone. Only the
+    /// horizontal movement is extracted by default, so the bone keeps bobbing up and down.
+    pub translation_mask: Vector3<f32>,
+    /// Extract the rotation around the y axis, for turning animations, true by default
+    pub extract_rotation: bool,
+    delta_translation: Vector3<f32>,
+    delta_rotation: f32,
+    previous: Vec<(u64, TransformChannel, f32)>,
+}
+
+impl RootMotion {
+    /// Creates a root motion applied to the given entity.
+    pub fn new(target: Option<Entity>) -> Self {
+        RootMotion {
+            target,
+            translation_mask: Vector3::new(1., 0., 1.),
+            extract_rotation: true,
+            delta_translation: Vector3::zeros(),
+            delta_rotation: 0.,
+            previous: Vec::new(),
+        }
+    }
+
+    /// Set the axes of the translation which are extracted
+    pub fn with_translation_mask(mut self, translation_mask: Vector3<f32>) -> Self {
+        self.translation_mask = translation_mask;
+        self
+    }
+
+    /// Set whether the rotation around the y axis is extracted
+    pub fn with_rotation(mut self, extract_rotation: bool) -> Self {
+        self.extract_rotation = extract_rotation;
+        self
+    }
+
+    /// Get the translation of the last frame, in the space of the parent of the bone
+    pub fn delta_translation(&self) -> &Vector3<f32> {
+        &self.delta_translation
+    }
+
+    /// Get the rotation around the y axis of the last frame, in radians
+    pub fn delta_rotation(&self) -> f32 {
+        self.delta_rotation
+    }
+
+    /// Compute the motion of the running samplers since the previous frame.
+    fn extract(
+        &mut self,
+        controls: Option<&SamplerControlSet<Transform>>,
+        samplers: &AssetStorage<Sampler<SamplerPrimitive<f32>>>,
+    ) {
+        let mut translation = (Vector3::zeros(), 0.);
+        let mut rotation = (0., 0.);
+        let mut current = Vec::new();
+
+        for control in controls.iter().flat_map(|set| set.samplers.iter()) {
+            let time = match control.state {
+                ControlState::Running(dur) | ControlState::Paused(dur) => duration_to_secs(dur),
+                _ => continue,
+            };
+            let sampler = match samplers.get(&control.sampler) {
+                Some(sampler) => sampler,
+                None => continue,
+            };
+            current.push((control.control_id, control.channel, time));
+            let previous = match self
+                .previous
+                .iter()
+                .find(|p| p.0 == control.control_id && p.1 == control.channel)
+            {
+                Some(previous) => previous.2,
+                None => continue,
+            };
+
+            let weight = control.blend_weight * control.animation_weight;
+            let rate = control.rate_multiplier;
+            match control.channel {
+                TransformChannel::Translation => {
+                    let delta = motion(sampler, previous, time, rate, translation_at, |a, b| a - b);
+                    if let Some(delta) = delta {
+                        translation.0 += delta * weight;
+                        translation.1 += weight;
+                    }
+                }
+                TransformChannel::Rotation if self.extract_rotation => {
+                    let delta = motion(sampler, previous, time, rate, yaw_at, |a, b| {
+                        normalize_angle(a - b)
+                    });
+                    if let Some(delta) = delta {
+                        rotation.0 += delta * weight;
+                        rotation.1 += weight;
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        self.previous = current;
+        self.delta_translation = if translation.1 > 0. {
+            (translation.0 / translation.1).component_mul(&self.translation_mask)
+        } else {
+            Vector3::zeros()
+        };
+        self.delta_rotation = if rotation.1 > 0. {
+            rotation.0 / rotation.1
+        } else {
+            0.
+        };
+    }
+
+    /// Move the extracted axes of the bone back to its rest state.
+    fn lock(&self, transform: &mut Transform, rest: &Transform) {
+        let translation = *transform.translation();
+        *transform.translation_mut() =
+            translation - (translation - rest.translation()).component_mul(&self.translation_mask);
+        if self.extract_rotation {
+            let rotation = *transform.rotation();
+            *transform.rotation_mut() =
+                twist(rest.rotation()) * twist(&rotation).inverse() * rotation;
+        }
+    }
+}
+
+/// Compute the motion of a sampler from `previous` to `current` seconds, taking into account
+/// that the sampler looped around if it moved against its rate.
+fn motion<V, S, D>(
+    sampler: &Sampler<SamplerPrimitive<f32>>,
+    previous: f32,
+    current: f32,
+    rate: f32,
+    sample: S,
+    difference: D,
+) -> Option<V>
+where
+    V: Add<Output = V>,
+    S: Fn(&Sampler<SamplerPrimitive<f32>>, f32) -> Option<V>,
+    D: Fn(V, V) -> V,
+{
+    let at = |time| sample(sampler, time);
+    let end = sampler.input.last().cloned()?;
+    if rate >= 0. && current < previous {
+        Some(difference(at(end)?, at(previous)?) + difference(at(current)?, at(0.)?))
+    } else if rate < 0. && current > previous {
+        Some(difference(at(0.)?, at(previous)?) + difference(at(current)?, at(end)?))
+    } else {
+        Some(difference(at(current)?, at(previous)?))
+    }
+}
+
+fn interpolate(sampler: &Sampler<SamplerPrimitive<f32>>, time: f32) -> SamplerPrimitive<f32> {
+    sampler
+        .function
+        .interpolate(time, &sampler.input, &sampler.output, false)
+}
+
+fn translation_at(sampler: &Sampler<SamplerPrimitive<f32>>, time: f32) -> Option<Vector3<f32>> {
+    match interpolate(sampler, time) {
+        SamplerPrimitive::Vec3(t) => Some(Vector3::from(t)),
+        _ => None,
+    }
+}
+
+fn yaw_at(sampler: &Sampler<SamplerPrimitive<f32>>, time: f32) -> Option<f32> {
+    match interpolate(sampler, time) {
+        SamplerPrimitive::Vec4(r) => {
+            Some(yaw(&UnitQuaternion::new_normalize(Quaternion::from(
+                Vector4::from(r),
+            ))))
+        }
+        _ => None,
+    }
+}
+
+/// Angle of the rotation around the y axis.
+fn yaw(rotation: &UnitQuaternion<f32>) -> f32 {
+    normalize_angle(2. * rotation.j.atan2(rotation.w))
+}
+
+/// The part of a rotation around the y axis.
+fn twist(rotation: &UnitQuaternion<f32>) -> UnitQuaternion<f32> {
+    UnitQuaternion::from_axis_angle(&Vector3::y_axis(), yaw(rotation))
+}
+
+fn normalize_angle(angle: f32) -> f32 {
+    let angle = angle % (2. * PI);
+    if angle > PI {
+        angle - 2. * PI
+    } else if angle <= -PI {
+        angle + 2. * PI
+    } else {
+        angle
+    }
+}
+
+/// System applying `RootMotion`.
+///
+/// Needs to run after `SamplerInterpolationSystem<Transform>`, and before the `Transform`s are
+/// used for the current frame.
+#[derive(Debug, Default)]
+pub struct RootMotionSystem;
+
+impl System for RootMotionSystem {
+    fn build(self) -> Box<dyn ParallelRunnable> {
+        let mut moves = Vec::new();
+
+        Box::new(
+            SystemBuilder::new("RootMotionSystem")
+                .read_resource::<AssetStorage<Sampler<SamplerPrimitive<f32>>>>()
+                .write_component::<Transform>()
+                .with_query(<(
+                    Write<RootMotion>,
+                    Write<Transform>,
+                    TryRead<SamplerControlSet<Transform>>,
+                    TryRead<RestState<Transform>>,
+                )>::query())
+                .build(move |_, world, samplers, query| {
+                    #[cfg(feature = "profiler")]
+                    profile_scope!("root_motion_system");
+
+                    moves.clear();
+                    for (root_motion, transform, controls, rest) in query.iter_mut(world) {
+                        root_motion.extract(controls, samplers);
+                        if let Some(rest) = rest {
+                            root_motion.lock(transform, rest.state());
+                        }
+                        if let Some(target) = root_motion.target {
+                            moves.push((
+                                target,
+                                root_motion.delta_translation,
+                                root_motion.delta_rotation,
+                            ));
+                        }
+                    }
+
+                    for (target, translation, rotation) in moves.drain(..) {
+                        if let Ok(mut entry) = world.entry_mut(target) {
+                            if let Ok(transform) = entry.get_component_mut::<Transform>() {
+                                let movement = transform.rotation()
+                                    * translation.component_mul(transform.scale());
+                                *transform.translation_mut() += movement;
+                                transform.append_rotation_y_axis(rotation);
+                            }
+                        }
+                    }
+                }),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_yaw_is_extracted_from_rotations() {
+        let rotation = UnitQuaternion::from_euler_angles(0.3, 1.2, 0.);
+        assert!((yaw(&UnitQuaternion::from_euler_angles(0., 1.2, 0.)) - 1.2).abs() < 1e-5);
+        let swing = twist(&rotation).inverse() * rotation;
+        assert!(yaw(&swing).abs() < 1e-5);
+        assert!((normalize_angle(3. * PI / 2.) + PI / 2.).abs() < 1e-5);
+    }
+}
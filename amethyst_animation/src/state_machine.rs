@@ -0,0 +1,330 @@
+//! Animation state machines, switching between the animations of an `AnimationControlSet` by
+//! parameters such as the speed of a character or whether it's on the ground.
+
+use amethyst_assets::{Asset, Handle};
+use fnv::FnvHashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::blend_tree::BlendNode;
+
+/// Condition of a `Transition`, on the parameters of the `AnimationStateMachineControl`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum TransitionCondition {
+    /// The parameter is greater than the value
+    Greater(String, f32),
+    /// The parameter is less than the value
+    Less(String, f32),
+    /// The parameter equals the value
+    Equal(String, f32),
+    /// The parameter doesn't equal the value
+    NotEqual(String, f32),
+    /// The parameter was set with `AnimationStateMachineControl::trigger`, it's reset when the
+    /// transition is taken
+    Trigger(String),
+    /// The current state was entered at least the given number of seconds ago
+    TimeInState(f32),
+}
+
+impl TransitionCondition {
+    fn holds(&self, parameters: &FnvHashMap<String, f32>, time_in_state: f32) -> bool {
+        let parameter = |name: &str| parameters.get(name).cloned().unwrap_or(0.);
+        match self {
+            TransitionCondition::Greater(name, value) => parameter(name) > *value,
+            TransitionCondition::Less(name, value) => parameter(name) < *value,
+            TransitionCondition::Equal(name, value) => {
+                (parameter(name) - value).abs() <= std::f32::EPSILON
+            }
+            TransitionCondition::NotEqual(name, value) => {
+                (parameter(name) - value).abs() > std::f32::EPSILON
+            }
+            TransitionCondition::Trigger(name) => parameter(name) != 0.,
+            TransitionCondition::TimeInState(time) => time_in_state >= *time,
+        }
+    }
+}
+
+/// A state of an `AnimationStateMachine`.
+///
+/// ### Type parameters:
+///
+/// - `I`: identifier type of the animations in the `AnimationSet`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AnimationState<I> {
+    /// Name of the state, used by transitions
+    pub name: String,
+    /// The animations played in the state, a single `BlendNode::Clip` or a blend of several
+    /// animations driven by the parameters of the state machine
+    pub motion: BlendNode<I>,
+    /// Whether the animations of the state loop, or stay at their last frame
+    #[serde(default = "default_looping")]
+    pub looping: bool,
+}
+
+fn default_looping() -> bool {
+    true
+}
+
+/// A transition between two states of an `AnimationStateMachine`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Transition {
+    /// The state the transition starts from, any state other than `to` if `None`
+    #[serde(default)]
+    pub from: Option<String>,
+    /// The state the transition leads to
+    pub to: String,
+    /// Conditions which must all hold for the transition to be taken
+    #[serde(default)]
+    pub conditions: Vec<TransitionCondition>,
+    /// Duration of the cross-fade between the animations of the states, in seconds
+    #[serde(default)]
+    pub duration: f32,
+}
+
+/// Data-driven animation state machine.
+///
+/// Every frame, the `AnimationStateMachineSystem` takes the first transition from the current
+/// state whose conditions hold, and cross-fades from the animations of the current state to the
+/// ones of the new state. The animations are taken from the `AnimationSet` of the entity, and
+/// added to its `AnimationControlSet` when their state is entered. Their weights are set by the
+/// state machine, so the control set shouldn't have a `BlendTree` as well.
+///
+/// To load state machines from files, implement `TypeUuid` for `AnimationStateMachine<I>` of
+/// your identifier type, and register it with `register_asset_type!`.
+///
+/// ### Type parameters:
+///
+/// - `I`: identifier type of the animations in the `AnimationSet`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AnimationStateMachine<I> {
+    /// The states
+    pub states: Vec<AnimationState<I>>,
+    /// The transitions, in order of priority
+    #[serde(default)]
+    pub transitions: Vec<Transition>,
+    /// Name of the state the machine starts in, the first state if `None`
+    #[serde(default)]
+    pub initial: Option<String>,
+}
+
+impl<I> AnimationStateMachine<I> {
+    /// Get the index of the state with the given name
+    pub fn state_index(&self, name: &str) -> Option<usize> {
+        self.states.iter().position(|s| s.name == name)
+    }
+
+    /// Index of the state the machine starts in.
+    pub(crate) fn initial_index(&self) -> Option<usize> {
+        match &self.initial {
+            Some(name) => self.state_index(name),
+            None if self.states.is_empty() => None,
+            None => Some(0),
+        }
+    }
+
+    /// Returns the first transition which can be taken from the given state, and the index of
+    /// the state it leads to.
+    pub(crate) fn find_transition(
+        &self,
+        current: usize,
+        parameters: &FnvHashMap<String, f32>,
+        time_in_state: f32,
+    ) -> Option<(&Transition, usize)> {
+        let name = &self.states[current].name;
+        self.transitions
+            .iter()
+            .filter(|t| t.from.as_ref().map_or(&t.to != name, |from| from == name))
+            .filter(|t| {
+                t.conditions
+                    .iter()
+                    .all(|c| c.holds(parameters, time_in_state))
+            })
+            .find_map(|t| self.state_index(&t.to).map(|index| (t, index)))
+    }
+}
+
+impl<I> Asset for AnimationStateMachine<I>
+where
+    I: Send + Sync + 'static,
+{
+    fn name() -> &'static str {
+        "animation::AnimationStateMachine"
+    }
+    type Data = Self;
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct ActiveState {
+    pub index: usize,
+    pub name: String,
+    pub time: f32,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct Fade {
+    pub from: usize,
+    pub elapsed: f32,
+    pub duration: f32,
+}
+
+/// Runs an `AnimationStateMachine` on an entity with an `AnimationSet` and an
+/// `AnimationControlSet`.
+///
+/// ### Type parameters:
+///
+/// - `I`: identifier type of the animations in the `AnimationSet`
+#[derive(Clone, Debug)]
+pub struct AnimationStateMachineControl<I> {
+    /// The state machine
+    pub machine: Handle<AnimationStateMachine<I>>,
+    pub(crate) parameters: FnvHashMap<String, f32>,
+    pub(crate) current: Option<ActiveState>,
+    pub(crate) fade: Option<Fade>,
+    pub(crate) requested: Option<(String, f32)>,
+    pub(crate) playing: Vec<I>,
+}
+
+impl<I> AnimationStateMachineControl<I> {
+    /// Creates a control of the given state machine, which starts in its initial state.
+    pub fn new(machine: Handle<AnimationStateMachine<I>>) -> Self {
+        AnimationStateMachineControl {
+            machine,
+            parameters: FnvHashMap::default(),
+            current: None,
+            fade: None,
+            requested: None,
+            playing: Vec::new(),
+        }
+    }
+
+    /// Set the value of a parameter
+    pub fn set_parameter<S: Into<String>>(&mut self, name: S, value: f32) -> &mut Self {
+        self.parameters.insert(name.into(), value);
+        self
+    }
+
+    /// Get the value of a parameter, 0 if it isn't set
+    pub fn parameter(&self, name: &str) -> f32 {
+        self.parameters.get(name).cloned().unwrap_or(0.)
+    }
+
+    /// Set a trigger parameter, which is reset by the first transition it causes
+    pub fn trigger<S: Into<String>>(&mut self, name: S) -> &mut Self {
+        self.set_parameter(name, 1.)
+    }
+
+    /// Cross-fade to the given state over `duration` seconds, regardless of the transitions
+    pub fn transition_to<S: Into<String>>(&mut self, state: S, duration: f32) -> &mut Self {
+        self.requested = Some((state.into(), duration));
+        self
+    }
+
+    /// Get the name of the current state, the one being faded to during a transition
+    pub fn current_state(&self) -> Option<&str> {
+        self.current.as_ref().map(|s| s.name.as_str())
+    }
+
+    /// Get the time spent in the current state, in seconds
+    pub fn time_in_state(&self) -> f32 {
+        self.current.as_ref().map_or(0., |s| s.time)
+    }
+
+    /// Check if a cross-fade between two states is running
+    pub fn is_transitioning(&self) -> bool {
+        self.fade.is_some()
+    }
+
+    /// Take a transition to the given state, or enter it if there's no current state.
+    pub(crate) fn enter(
+        &mut self,
+        machine: &AnimationStateMachine<I>,
+        index: usize,
+        duration: f32,
+    ) {
+        let previous = self.current.take();
+        self.fade = match previous {
+            Some(previous) if duration > 0. => {
+                Some(Fade {
+                    from: previous.index,
+                    elapsed: 0.,
+                    duration,
+                })
+            }
+            _ => None,
+        };
+        self.current = Some(ActiveState {
+            index,
+            name: machine.states[index].name.clone(),
+            time: 0.,
+        });
+    }
+
+    /// Resets the triggers among the conditions of a transition which was taken.
+    pub(crate) fn consume_triggers(&mut self, transition: &Transition) {
+        for condition in &transition.conditions {
+            if let TransitionCondition::Trigger(name) = condition {
+                self.parameters.remove(name);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transitions_are_found_by_conditions() {
+        let machine: AnimationStateMachine<u32> = AnimationStateMachine {
+            states: vec![
+                AnimationState {
+                    name: "idle".into(),
+                    motion: BlendNode::Clip(0),
+                    looping: true,
+                },
+                AnimationState {
+                    name: "run".into(),
+                    motion: BlendNode::Clip(1),
+                    looping: true,
+                },
+                AnimationState {
+                    name: "jump".into(),
+                    motion: BlendNode::Clip(2),
+                    looping: false,
+                },
+            ],
+            transitions: vec![
+                Transition {
+                    from: None,
+                    to: "jump".into(),
+                    conditions: vec![TransitionCondition::Trigger("jump".into())],
+                    duration: 0.1,
+                },
+                Transition {
+                    from: Some("idle".into()),
+                    to: "run".into(),
+                    conditions: vec![TransitionCondition::Greater("speed".into(), 0.5)],
+                    duration: 0.2,
+                },
+                Transition {
+                    from: Some("jump".into()),
+                    to: "idle".into(),
+                    conditions: vec![TransitionCondition::TimeInState(1.)],
+                    duration: 0.2,
+                },
+            ],
+            initial: None,
+        };
+        assert_eq!(machine.initial_index(), Some(0));
+
+        let mut parameters = FnvHashMap::default();
+        assert!(machine.find_transition(0, &parameters, 0.).is_none());
+        parameters.insert("speed".to_string(), 1.);
+        assert_eq!(machine.find_transition(0, &parameters, 0.).unwrap().1, 1);
+
+        // Triggers take priority, but an "any state" transition doesn't lead to itself
+        parameters.insert("jump".to_string(), 1.);
+        assert_eq!(machine.find_transition(0, &parameters, 0.).unwrap().1, 2);
+        assert!(machine.find_transition(2, &parameters, 0.5).is_none());
+        assert_eq!(machine.find_transition(2, &parameters, 1.).unwrap().1, 0);
+    }
+}
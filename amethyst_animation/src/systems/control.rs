@@ -1,7 +1,12 @@
 use std::{hash::Hash, marker::PhantomData, time::Duration};
 
 use amethyst_assets::{AssetStorage, Handle};
-use amethyst_core::{ecs::*, timing::secs_to_duration};
+use amethyst_core::{
+    ecs::*,
+    shrev::EventChannel,
+    timing::{duration_to_secs, secs_to_duration},
+    Time,
+};
 use derivative::Derivative;
 use fnv::FnvHashMap;
 use log::{debug, error};
@@ -9,10 +14,14 @@ use minterpolate::InterpolationPrimitive;
 #[cfg(feature = "profiler")]
 use thread_profiler::profile_scope;
 
-use crate::resources::{
-    Animation, AnimationCommand, AnimationControl, AnimationControlSet, AnimationHierarchy,
-    AnimationSampling, ControlState, DeferStartRelation, RestState, Sampler, SamplerControl,
-    SamplerControlSet, StepDirection,
+use crate::{
+    layer::AnimationLayer,
+    lod::AnimationLod,
+    resources::{
+        Animation, AnimationCommand, AnimationControl, AnimationControlSet, AnimationEvent,
+        AnimationHierarchy, AnimationSampling, ControlState, DeferStartRelation, EndControl,
+        RestState, Sampler, SamplerControl, SamplerControlSet, StepDirection,
+    },
 };
 
 /// System for setting up animations, should run before `SamplerInterpolationSystem`.
@@ -48,16 +57,19 @@ where
         let mut remove_ids = Vec::default();
         let mut state_set = FnvHashMap::default();
         let mut deferred_start = Vec::default();
+        let mut crossed = Vec::default();
 
         Box::new(
         SystemBuilder::new("AnimationControlSystem")  
         .read_resource::<AssetStorage<Animation<T>>>()
         .read_resource::<AssetStorage<Sampler<T::Primitive>>>()
+        .read_resource::<Time>()
+        .write_resource::<EventChannel<AnimationEvent<I>>>()
         .read_component::<T>()
         .write_component::<SamplerControlSet<T>>()
         .write_component::<RestState<T>>()
-        .with_query(<(Entity, Write<AnimationControlSet<I, T>>, TryRead<AnimationHierarchy<T>>)>::query())
-        .build(move |mut buffer, world, (animation_storage, sampler_storage), query| {
+        .with_query(<(Entity, Write<AnimationControlSet<I, T>>, TryRead<AnimationHierarchy<T>>, TryRead<AnimationLod>)>::query())
+        .build(move |mut buffer, world, (animation_storage, sampler_storage, time, events), query| {
             #[cfg(feature = "profiler")]
             profile_scope!("animation_control_system");
 
@@ -65,12 +77,14 @@ where
 
             let (mut query_world, mut world) = world.split_for_query(&query);
 
-            for (entity, control_set, hierarchy) in query.iter_mut(&mut query_world) {
+            for (entity, control_set, hierarchy, lod) in query.iter_mut(&mut query_world) {
                 debug!("{:?}", control_set);
 
                 remove_ids.clear();
                 state_set.clear();
 
+                control_set.apply_blend_tree();
+
                 // process each animation in control set
                 for  (ref id, ref mut control) in control_set.animations.iter_mut() {
                     debug!("{:?}, {:?}", id, control);
@@ -85,6 +99,7 @@ where
                                     &mut world,
                                     animation,
                                     control,
+                                    control_set.layers.get(&control.layer),
                                     hierarchy,
                                     &*sampler_storage,
                                     buffer,
@@ -97,6 +112,39 @@ where
                         control.state = state;
                     }
 
+                    // emit the events crossed by the samplers this frame
+                    if let Some(animation) = animation_storage.get(&control.animation) {
+                        if !animation.events.is_empty() && control.state.is_running() {
+                            let node = *hierarchy
+                                .and_then(|h| h.nodes.values().next())
+                                .unwrap_or(&entity);
+                            let position = world.entry_ref(node).ok().and_then(|entry| {
+                                let set = entry.get_component::<SamplerControlSet<T>>().ok()?;
+                                playback_position(control.id, set, &*sampler_storage)
+                            });
+                            if let Some((position, duration)) = position {
+                                crossed.clear();
+                                crossed_events(
+                                    &animation.events,
+                                    duration,
+                                    matches!(control.end, EndControl::Loop(_)),
+                                    position,
+                                    time.delta_seconds() * control.rate_multiplier,
+                                    &mut crossed,
+                                );
+                                for &index in &crossed {
+                                    let (event_time, ref name) = animation.events[index];
+                                    events.single_write(AnimationEvent {
+                                        entity: *entity,
+                                        animation: *id,
+                                        name: name.clone(),
+                                        time: event_time,
+                                    });
+                                }
+                            }
+                        }
+                    }
+
                     // update command for next iteration
                     if let AnimationCommand::Step(_) = control.command {
                         control.command = AnimationCommand::Start;
@@ -186,6 +234,7 @@ where
                                     &mut world,
                                     animation,
                                     &mut def.control,
+                                    control_set.layers.get(&def.control.layer),
                                     hierarchy,
                                     &*sampler_storage,
                                     &mut buffer,
@@ -200,6 +249,11 @@ where
                 }
 
                 self.next_id = next_id;
+
+                if let Some(lod) = lod {
+                    update_sampling_rate(*entity, hierarchy, &mut world, lod.rate);
+                }
+
                 for id in &remove_ids {
                     debug!("Removing AnimationControlSet {:?}", id);
                     control_set.remove(*id);
@@ -226,6 +280,115 @@ where
         .unwrap_or(0.)
 }
 
+/// Get the current time and the duration of the longest running sampler of an animation.
+fn playback_position<T>(
+    control_id: u64,
+    samplers: &SamplerControlSet<T>,
+    sampler_storage: &AssetStorage<Sampler<T::Primitive>>,
+) -> Option<(f32, f32)>
+where
+    T: AnimationSampling,
+{
+    samplers
+        .samplers
+        .iter()
+        .filter(|s| s.control_id == control_id)
+        .filter_map(|s| {
+            match s.state {
+                ControlState::Running(dur) => {
+                    sampler_storage
+                        .get(&s.sampler)
+                        .and_then(|sampler| sampler.input.last().cloned())
+                        .map(|last| (duration_to_secs(dur), last))
+                }
+                _ => None,
+            }
+        })
+        .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))
+}
+
+/// Find the events crossed when the playback moves from `position` by `delta` seconds, in the
+/// order they are crossed. Moving forward crosses the events in `[position, position + delta)`,
+/// moving backward the ones in `(position + delta, position]`, wrapping around when looping.
+fn crossed_events(
+    events: &[(f32, String)],
+    duration: f32,
+    looping: bool,
+    position: f32,
+    delta: f32,
+    crossed: &mut Vec<usize>,
+) {
+    let mut emit = |from: f32, to: f32, include_from: bool, include_to: bool| {
+        let start = crossed.len();
+        crossed.extend(events.iter().enumerate().filter_map(|(i, &(t, _))| {
+            let after_from = if from <= to { t > from } else { t < from };
+            let before_to = if from <= to { t < to } else { t > to };
+            if (after_from || (include_from && t == from)) && (before_to || (include_to && t == to))
+            {
+                Some(i)
+            } else {
+                None
+            }
+        }));
+        // order by the time they are crossed
+        crossed[start..].sort_by(|a, b| {
+            let order = events[*a]
+                .0
+                .partial_cmp(&events[*b].0)
+                .unwrap_or(std::cmp::Ordering::Equal);
+            if from <= to {
+                order
+            } else {
+                order.reverse()
+            }
+        });
+    };
+
+    if duration <= 0. || delta == 0. {
+        return;
+    }
+    if !looping {
+        let target = (position + delta).max(0.).min(duration);
+        if target > position {
+            emit(position, target, true, target == duration);
+        } else if target < position {
+            emit(position, target, true, target == 0.);
+        }
+        return;
+    }
+
+    let mut position = position.max(0.).min(duration);
+    // the end of a loop is its start, so events at the end are crossed at the start
+    let mut include_start = true;
+    // loops skipped entirely are only emitted once
+    let mut remaining = delta.abs();
+    if remaining > duration {
+        remaining = duration + remaining % duration;
+    }
+    while remaining > 0. {
+        if delta > 0. {
+            let end = position + remaining;
+            if end < duration {
+                emit(position, end, true, false);
+                return;
+            }
+            emit(position, duration, true, false);
+            remaining -= duration - position;
+            position = 0.;
+        } else {
+            let end = position - remaining;
+            if end > 0. {
+                emit(position, end, include_start, false);
+                return;
+            }
+            emit(position, 0., include_start, true);
+            remaining -= position;
+            position = duration;
+            include_start = false;
+        }
+    }
+}
+
 /// Check if the given animation list is for a single node. If so, we don't need an
 /// `AnimationHierarchy`.
 fn only_one_index<C, P>(nodes: &[(usize, C, Handle<Sampler<P>>)]) -> bool
@@ -247,6 +410,7 @@ where
 /// - `entity`: the entity the control object is active for
 /// - `animation`: the animation the control is for
 /// - `control`: animation control object
+/// - `layer`: the layer of the animation, if it has been set
 /// - `hierarchy`: the animation node hierarchy for the entity hierarchy the animation instance is
 ///                active for, if this is None the animation must be for a single node, which is the
 ///                local entity. If the animation contains more than a single node index, the
@@ -266,6 +430,7 @@ fn process_animation_control<T>(
     world: &mut SubWorld<'_>,
     animation: &Animation<T>,
     control: &mut AnimationControl<T>,
+    layer: Option<&AnimationLayer>,
     hierarchy: Option<&AnimationHierarchy<T>>,
     sampler_storage: &AssetStorage<Sampler<T::Primitive>>,
     buffer: &mut CommandBuffer,
@@ -317,6 +482,7 @@ where
                 animation,
                 sampler_storage,
                 control,
+                layer,
                 world,
                 buffer,
                 hierarchy,
@@ -351,6 +517,8 @@ where
 
         (&ControlState::Running(..), &AnimationCommand::SetBlendWeights(ref weights)) => {
             set_blend_weights(control.id, hierarchy, world, weights);
+            update_animation_weight(control.id, hierarchy, world, control.weight);
+            update_animation_layer(control.id, hierarchy, world, control.layer, layer);
             None
         }
 
@@ -378,6 +546,8 @@ where
             } else {
                 debug!("Animation Playing: {:?}", control.id);
                 update_animation_rate(control.id, hierarchy, world, control.rate_multiplier);
+                update_animation_weight(control.id, hierarchy, world, control.weight);
+                update_animation_layer(control.id, hierarchy, world, control.layer, layer);
             }
             None
         }
@@ -395,6 +565,7 @@ where
 /// - `animation`: the animation to start
 /// - `sampler_storage`: all samplers
 /// - `control`: the control object for the animation instance
+/// - `layer`: the layer of the animation, if it has been set
 /// - `hierarchy`: the animation node hierarchy for the entity hierarchy the animation instance is active for
 /// - `samplers`: the active sampler sets
 /// - `targets`: Target components, used to retrieve the rest pose before animation starts.
@@ -406,6 +577,7 @@ fn start_animation<T>(
     animation: &Animation<T>,
     sampler_storage: &AssetStorage<Sampler<T::Primitive>>,
     control: &AnimationControl<T>,
+    layer: Option<&AnimationLayer>,
     world: &mut SubWorld<'_>,
     buffer: &mut CommandBuffer,
     hierarchy: &AnimationHierarchy<T>,
@@ -458,6 +630,9 @@ where
                     after: component.current_sample(channel),
                     rate_multiplier: control.rate_multiplier,
                     blend_weight: 1.0,
+                    animation_weight: control.weight,
+                    layer: control.layer,
+                    layer_weight: layer.map_or(1.0, |l| l.node_weight(*node_index)),
                 };
                 if let Ok(set) = entry.get_component_mut::<SamplerControlSet<T>>() {
                     debug!("Adding SamplerControl to existing SamplerControlSet");
@@ -580,6 +755,63 @@ fn update_animation_rate<T>(
     }
 }
 
+fn update_animation_weight<T>(
+    control_id: u64,
+    hierarchy: &AnimationHierarchy<T>,
+    world: &mut SubWorld<'_>,
+    weight: f32,
+) where
+    T: AnimationSampling,
+{
+    for node_entity in hierarchy.nodes.values() {
+        if let Ok(mut entry) = world.entry_mut(*node_entity) {
+            if let Ok(ref mut s) = entry.get_component_mut::<SamplerControlSet<T>>() {
+                s.set_animation_weight(control_id, weight);
+            }
+        }
+    }
+}
+
+fn update_animation_layer<T>(
+    control_id: u64,
+    hierarchy: &AnimationHierarchy<T>,
+    world: &mut SubWorld<'_>,
+    layer_index: usize,
+    layer: Option<&AnimationLayer>,
+) where
+    T: AnimationSampling,
+{
+    for (node_index, node_entity) in &hierarchy.nodes {
+        if let Ok(mut entry) = world.entry_mut(*node_entity) {
+            if let Ok(ref mut s) = entry.get_component_mut::<SamplerControlSet<T>>() {
+                let weight = layer.map_or(1.0, |l| l.node_weight(*node_index));
+                s.set_layer(control_id, layer_index, weight);
+            }
+        }
+    }
+}
+
+fn update_sampling_rate<T>(
+    entity: Entity,
+    hierarchy: Option<&AnimationHierarchy<T>>,
+    world: &mut SubWorld<'_>,
+    rate: Option<f32>,
+) where
+    T: AnimationSampling,
+{
+    let nodes = match hierarchy {
+        Some(hierarchy) => hierarchy.nodes.values().cloned().collect(),
+        None => vec![entity],
+    };
+    for node_entity in nodes {
+        if let Ok(mut entry) = world.entry_mut(node_entity) {
+            if let Ok(ref mut s) = entry.get_component_mut::<SamplerControlSet<T>>() {
+                s.set_update_rate(rate);
+            }
+        }
+    }
+}
+
 /// Check if all nodes in an `AnimationHierarchy` are ready for termination, if so remove all
 /// `SamplerControlSet`s for the hierarchy, if not request termination on all sampler controls
 fn check_and_terminate_animation<T>(
@@ -640,3 +872,34 @@ where
             .check_termination(control_id)
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn crossed(looping: bool, position: f32, delta: f32) -> Vec<usize> {
+        let events = vec![
+            (0.0, "start".to_string()),
+            (0.5, "footstep".to_string()),
+            (0.25, "hit".to_string()),
+        ];
+        let mut crossed = Vec::new();
+        crossed_events(&events, 1.0, looping, position, delta, &mut crossed);
+        crossed
+    }
+
+    #[test]
+    fn test_crossed_events() {
+        assert_eq!(crossed(false, 0.0, 0.1), vec![0]);
+        assert_eq!(crossed(false, 0.1, 0.1), Vec::<usize>::new());
+        // A long frame crosses several events, in order
+        assert_eq!(crossed(false, 0.1, 2.0), vec![2, 1]);
+        // Looping wraps around to the start
+        assert_eq!(crossed(true, 0.4, 0.7), vec![1, 0]);
+        // Playing backwards
+        assert_eq!(crossed(true, 0.6, -0.4), vec![1, 2]);
+        assert_eq!(crossed(true, 0.1, -0.2), vec![0]);
+        // Skipped loops emit every event once more
+        assert_eq!(crossed(true, 0.4, 2.2), vec![1, 0, 2, 1]);
+    }
+}
@@ -1,2 +1,3 @@
 pub(crate) mod control;
 pub(crate) mod sampling;
+pub(crate) mod state_machine;
@@ -9,6 +9,7 @@ use amethyst_core::{
 use derivative::Derivative;
 use log::debug;
 use minterpolate::InterpolationPrimitive;
+use rayon::prelude::*;
 #[cfg(feature = "profiler")]
 use thread_profiler::profile_scope;
 
@@ -17,6 +18,33 @@ use crate::resources::{
     SamplerControlSet,
 };
 
+/// The value of a sampler for the current frame.
+struct Sample<T>
+where
+    T: AnimationSampling,
+{
+    weight: f32,
+    layer: usize,
+    layer_weight: f32,
+    channel: T::Channel,
+    primitive: T::Primitive,
+}
+
+impl<T> Sample<T>
+where
+    T: AnimationSampling,
+{
+    fn new(control: &SamplerControl<T>, primitive: T::Primitive) -> Self {
+        Sample {
+            weight: control.blend_weight * control.animation_weight,
+            layer: control.layer,
+            layer_weight: control.layer_weight,
+            channel: control.channel.clone(),
+            primitive,
+        }
+    }
+}
+
 /// System for interpolating active samplers.
 ///
 /// If other forms of animation is needed, this can be used in isolation, have no direct dependency
@@ -39,9 +67,6 @@ where
     T: AnimationSampling + std::fmt::Debug,
 {
     fn build(self) -> Box<dyn ParallelRunnable> {
-        let mut inner = Vec::default();
-        let mut channels = Vec::default();
-
         Box::new(
             SystemBuilder::new("SamplerInterpolationSystem")
                 .read_resource::<Time>()
@@ -51,66 +76,127 @@ where
                     #[cfg(feature = "profiler")]
                     profile_scope!("sampler_interpolation_system");
 
-                    for (control_set, comp) in query.iter_mut(world) {
-                        debug!("Processing SamplerControlSet: {:?}", control_set);
+                    let delta = time.delta_seconds();
+                    let samplers: &AssetStorage<Sampler<T::Primitive>> = &*samplers;
 
-                        inner.clear();
+                    // sample the entities in parallel, only applying the samples needs the
+                    // command buffer
+                    let mut entities = query.iter_mut(world).collect::<Vec<_>>();
+                    entities.par_iter_mut().for_each_init(
+                        || (Vec::default(), Vec::default()),
+                        |(inner, channels), (control_set, comp)| {
+                            debug!("Processing SamplerControlSet: {:?}", control_set);
+                            sample_set(control_set, comp, samplers, delta, inner, channels);
+                        },
+                    );
 
-                        for control in control_set.samplers.iter_mut() {
-                            if let Some(ref sampler) = samplers.get(&control.sampler) {
-                                process_sampler(control, sampler, &time, &mut inner);
-                            }
-                        }
-                        if !inner.is_empty() {
-                            channels.clear();
-                            channels
-                                .extend(inner.iter().map(|o| o.1.clone()).collect::<HashSet<_>>());
-                            for channel in &channels {
-                                match comp.blend_method(channel) {
-                                    None => {
-                                        if let Some(p) = inner
-                                            .iter()
-                                            .filter(|p| p.1 == *channel)
-                                            .map(|p| p.2.clone())
-                                            .last()
-                                        {
-                                            comp.apply_sample(channel, &p, commands);
-                                        }
-                                    }
-
-                                    Some(BlendMethod::Linear) => {
-                                        if let Some(p) = linear_blend::<T>(channel, &inner) {
-                                            comp.apply_sample(channel, &p, commands);
-                                        }
-                                    }
-                                }
-                            }
-                        }
+                    for (control_set, comp) in entities {
+                        apply_samples(control_set, comp, commands);
                     }
                 }),
         )
     }
 }
 
+/// Sample a `SamplerControlSet`, unless it's sampled at a lower rate and the next sample isn't
+/// due yet. The blended values of the channels are kept in the LOD state of the set.
+fn sample_set<T>(
+    control_set: &mut SamplerControlSet<T>,
+    comp: &T,
+    samplers: &AssetStorage<Sampler<T::Primitive>>,
+    delta: f32,
+    inner: &mut Vec<Sample<T>>,
+    channels: &mut Vec<T::Channel>,
+) where
+    T: AnimationSampling,
+{
+    let lod = &mut control_set.lod;
+    lod.elapsed += delta;
+    if lod.interval > 0. && lod.elapsed < lod.interval && !lod.current.is_empty() {
+        return;
+    }
+    // the samplers skipped the frames since the last sample
+    let delta = lod.elapsed;
+    lod.elapsed = 0.;
+
+    inner.clear();
+    for control in control_set.samplers.iter_mut() {
+        if let Some(ref sampler) = samplers.get(&control.sampler) {
+            process_sampler(control, sampler, delta, inner);
+        }
+    }
+
+    let lod = &mut control_set.lod;
+    std::mem::swap(&mut lod.previous, &mut lod.current);
+    lod.current.clear();
+    if inner.is_empty() {
+        return;
+    }
+    channels.clear();
+    channels.extend(
+        inner
+            .iter()
+            .map(|s| s.channel.clone())
+            .collect::<HashSet<_>>(),
+    );
+    for channel in channels.drain(..) {
+        let sample = match comp.blend_method(&channel) {
+            None => top_sample::<T>(&channel, inner),
+            Some(BlendMethod::Linear) => layered_blend::<T>(&channel, inner),
+        };
+        if let Some(p) = sample {
+            lod.current.push((channel, p));
+        }
+    }
+}
+
+/// Apply the last sample of a `SamplerControlSet`, or if it's sampled at a lower rate, the
+/// interpolation from the sample before it to the last one.
+fn apply_samples<T>(control_set: &SamplerControlSet<T>, comp: &mut T, buffer: &mut CommandBuffer)
+where
+    T: AnimationSampling,
+{
+    let lod = &control_set.lod;
+    let progress = if lod.interval > 0. {
+        (lod.elapsed / lod.interval).min(1.)
+    } else {
+        1.
+    };
+    for (channel, current) in &lod.current {
+        let previous = lod
+            .previous
+            .iter()
+            .find(|(c, _)| c == channel)
+            .map(|(_, p)| p);
+        match (previous, comp.blend_method(channel)) {
+            (Some(previous), Some(BlendMethod::Linear)) if progress < 1. => {
+                let p = previous.mul(1. - progress).add(&current.mul(progress));
+                comp.apply_sample(channel, &p, buffer);
+            }
+            _ => comp.apply_sample(channel, current, buffer),
+        }
+    }
+}
+
 /// Process a single `SamplerControl` object.
 ///
 /// ## Parameters:
 ///
 /// - `control`: sampler control object
 /// - `sampler`: the sampler reference from the control object
-/// - `component`: the component to update
-/// - `now`: synchronized `Instant` for the current frame
+/// - `delta`: seconds since the sampler was last processed
+/// - `output`: the samples of the active samplers
 fn process_sampler<T>(
     control: &mut SamplerControl<T>,
     sampler: &Sampler<T::Primitive>,
-    time: &Time,
-    output: &mut Vec<(f32, T::Channel, T::Primitive)>,
+    delta: f32,
+    output: &mut Vec<Sample<T>>,
 ) where
     T: AnimationSampling,
 {
     use crate::resources::ControlState::*;
 
-    let (new_state, new_end) = update_duration_and_check(&control, sampler, time);
+    let (new_state, new_end) = update_duration_and_check(&control, sampler, delta);
 
     // If a new end condition has been computed, update in control state
     if let Some(end) = new_end {
@@ -120,38 +206,33 @@ fn process_sampler<T>(
     // Do sampling
     match new_state {
         Running(duration) | Paused(duration) => {
-            output.push((
-                control.blend_weight,
-                control.channel.clone(),
-                sampler.function.interpolate(
-                    duration_to_secs(duration),
-                    &sampler.input,
-                    &sampler.output,
-                    false,
-                ),
-            ));
+            let primitive = sampler.function.interpolate(
+                duration_to_secs(duration),
+                &sampler.input,
+                &sampler.output,
+                false,
+            );
+            output.push(Sample::new(control, primitive));
         }
         Done => {
             if let EndControl::Normal = control.end {
-                output.push((
-                    control.blend_weight,
-                    control.channel.clone(),
-                    control.after.clone(),
-                ));
+                output.push(Sample::new(control, control.after.clone()));
             }
             if let EndControl::Stay = control.end {
-                let last_frame = sampler.input.last().cloned().unwrap_or(0.);
-
-                output.push((
-                    control.blend_weight,
-                    control.channel.clone(),
-                    sampler.function.interpolate(
-                        last_frame,
-                        &sampler.input,
-                        &sampler.output,
-                        false,
-                    ),
-                ));
+                // Samplers playing backwards end on their first frame
+                let last_frame = if control.rate_multiplier < 0. {
+                    sampler.input.first().cloned().unwrap_or(0.)
+                } else {
+                    sampler.input.last().cloned().unwrap_or(0.)
+                };
+
+                let primitive = sampler.function.interpolate(
+                    last_frame,
+                    &sampler.input,
+                    &sampler.output,
+                    false,
+                );
+                output.push(Sample::new(control, primitive));
             }
         }
         _ => {}
@@ -168,7 +249,7 @@ fn process_sampler<T>(
 ///
 /// - `control`: sampler control object
 /// - `sampler`: sampler reference from control
-/// - `now`: synchronized `Instant` for the current frame
+/// - `delta`: seconds since the sampler was last processed
 ///
 /// ## Returns
 ///
@@ -176,7 +257,7 @@ fn process_sampler<T>(
 fn update_duration_and_check<T>(
     control: &SamplerControl<T>,
     sampler: &Sampler<T::Primitive>,
-    time: &Time,
+    delta: f32,
 ) -> (ControlState, Option<EndControl>)
 where
     T: AnimationSampling,
@@ -196,14 +277,22 @@ where
 
         // sampling is running, update duration and check end condition
         Running(duration) => {
-            let current_dur =
-                duration + secs_to_duration(time.delta_seconds() * control.rate_multiplier);
+            let delta = delta * control.rate_multiplier;
             let last_frame = sampler
                 .input
                 .last()
                 .cloned()
                 .map(secs_to_duration)
                 .unwrap_or_else(|| Duration::from_secs(0));
+            if delta < 0. {
+                return update_reverse_duration(
+                    control,
+                    duration,
+                    secs_to_duration(-delta),
+                    last_frame,
+                );
+            }
+            let current_dur = duration + secs_to_duration(delta);
             // duration is past last frame of sampling
             if current_dur > last_frame {
                 // Check end conditions
@@ -239,6 +328,45 @@ where
     }
 }
 
+/// Update the duration of a sampler playing backwards, with a negative rate multiplier. Looping
+/// samplers wrap around to their last frame when they pass the first one.
+fn update_reverse_duration<T>(
+    control: &SamplerControl<T>,
+    duration: Duration,
+    rewind: Duration,
+    last_frame: Duration,
+) -> (ControlState, Option<EndControl>)
+where
+    T: AnimationSampling,
+{
+    use crate::resources::ControlState::*;
+
+    if rewind <= duration {
+        return (Running(duration - rewind), None);
+    }
+    if last_frame == Duration::from_secs(0) {
+        return (Done, None);
+    }
+    let (remainder, loops) = next_duration(last_frame, rewind - duration);
+    let wrapped = last_frame - remainder;
+    match control.end {
+        EndControl::Loop(None) => (Running(wrapped), None),
+        EndControl::Loop(Some(i)) => {
+            let remaining_loops = i.saturating_sub(loops + 1);
+            if remaining_loops <= 1 {
+                (Done, Some(EndControl::Normal))
+            } else {
+                (
+                    Running(wrapped),
+                    Some(EndControl::Loop(Some(remaining_loops))),
+                )
+            }
+        }
+        // All other end cases will be handled during sampling
+        _ => (Done, None),
+    }
+}
+
 fn next_duration(last_frame: Duration, duration: Duration) -> (Duration, u32) {
     let animation_duration = duration_to_nanos(last_frame);
     let current_duration = duration_to_nanos(duration);
@@ -247,33 +375,107 @@ fn next_duration(last_frame: Duration, duration: Duration) -> (Duration, u32) {
     (nanos_to_duration(remain_duration), loops as u32)
 }
 
-fn linear_blend<T>(
-    channel: &T::Channel,
-    output: &[(f32, T::Channel, T::Primitive)],
-) -> Option<T::Primitive>
+/// The last sample of the highest layer with a weight on the channel, for components which can't
+/// be blended.
+fn top_sample<T>(channel: &T::Channel, output: &[Sample<T>]) -> Option<T::Primitive>
+where
+    T: AnimationSampling,
+{
+    output
+        .iter()
+        .filter(|s| s.channel == *channel && s.layer_weight > 0.)
+        .max_by_key(|s| s.layer)
+        .map(|s| s.primitive.clone())
+}
+
+/// Blend the samples of each layer, and blend the result of each layer over the lower layers by
+/// the weight of the layer.
+fn layered_blend<T>(channel: &T::Channel, output: &[Sample<T>]) -> Option<T::Primitive>
 where
     T: AnimationSampling,
 {
-    let total_blend_weight = output.iter().filter(|o| o.1 == *channel).map(|o| o.0).sum();
+    let mut layers = output
+        .iter()
+        .filter(|s| s.channel == *channel)
+        .map(|s| s.layer)
+        .collect::<Vec<_>>();
+    layers.sort_unstable();
+    layers.dedup();
+
+    layers.into_iter().fold(None, |below, layer| {
+        let layer_weight = output
+            .iter()
+            .filter(|s| s.channel == *channel && s.layer == layer)
+            .map(|s| s.layer_weight)
+            .fold(0., f32::max)
+            .min(1.);
+        if layer_weight <= 0. {
+            return below;
+        }
+        match (below, linear_blend::<T>(channel, layer, output)) {
+            (below, None) => below,
+            (None, value) => value,
+            (Some(below), Some(value)) => {
+                Some(below.mul(1. - layer_weight).add(&value.mul(layer_weight)))
+            }
+        }
+    })
+}
+
+fn linear_blend<T>(channel: &T::Channel, layer: usize, output: &[Sample<T>]) -> Option<T::Primitive>
+where
+    T: AnimationSampling,
+{
+    let samples = || {
+        output
+            .iter()
+            .filter(move |s| s.channel == *channel && s.layer == layer)
+    };
+    let total_blend_weight: f32 = samples().map(|s| s.weight).sum();
     if total_blend_weight == 0. {
         None
     } else {
         Some(
-            output
-                .iter()
-                .filter(|o| o.1 == *channel)
-                .map(|o| single_blend::<T>(total_blend_weight, o))
+            samples()
+                .map(|s| s.primitive.mul(s.weight / total_blend_weight))
                 .fold(T::default_primitive(channel), |acc, p| acc.add(&p)),
         )
     }
 }
 
-fn single_blend<T>(
-    total: f32,
-    &(ref weight, _, ref primitive): &(f32, T::Channel, T::Primitive),
-) -> T::Primitive
-where
-    T: AnimationSampling,
-{
-    primitive.mul(*weight / total)
+#[cfg(test)]
+mod tests {
+    use amethyst_core::Transform;
+
+    use super::*;
+    use crate::{transform::TransformChannel, util::SamplerPrimitive};
+
+    fn sample(layer: usize, layer_weight: f32, x: f32) -> Sample<Transform> {
+        Sample {
+            weight: 1.,
+            layer,
+            layer_weight,
+            channel: TransformChannel::Translation,
+            primitive: SamplerPrimitive::Vec3([x, 0., 0.]),
+        }
+    }
+
+    #[test]
+    fn test_layers_replace_lower_layers_by_weight() {
+        let channel = TransformChannel::Translation;
+        let x = |output: &[Sample<Transform>]| {
+            match layered_blend::<Transform>(&channel, output) {
+                Some(SamplerPrimitive::Vec3([x, _, _])) => x,
+                other => panic!("Unexpected blend {:?}", other),
+            }
+        };
+
+        // animations of the same layer are blended by their weights
+        assert_eq!(x(&[sample(0, 1., 1.), sample(0, 1., 3.)]), 2.);
+        // a higher layer replaces the lower ones by its weight on the node
+        assert_eq!(x(&[sample(0, 1., 1.), sample(1, 1., 5.)]), 5.);
+        assert_eq!(x(&[sample(0, 1., 1.), sample(1, 0.25, 5.)]), 2.);
+        // a masked out layer leaves the lower layers alone
+        assert_eq!(x(&[sample(0, 1., 1.), sample(1, 0., 5.)]), 1.);
+    }
 }
@@ -0,0 +1,205 @@
+use std::{hash::Hash, marker::PhantomData};
+
+use amethyst_assets::AssetStorage;
+use amethyst_core::{ecs::*, Time};
+use derivative::Derivative;
+use log::{debug, error};
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+
+use crate::{
+    resources::{
+        AnimationCommand, AnimationControlSet, AnimationSampling, AnimationSet, EndControl,
+    },
+    state_machine::{AnimationStateMachine, AnimationStateMachineControl},
+};
+
+/// System for running animation state machines, should run before `AnimationControlSystem`.
+///
+/// Will process all `AnimationStateMachineControl`s, take the transitions whose conditions hold,
+/// and start the animations of the current states in the `AnimationControlSet` with the weights
+/// of the cross-fades.
+///
+/// ### Type parameters:
+///
+/// - `I`: identifier type for running animations
+/// - `T`: the component type that the animation should be applied to
+#[derive(Derivative)]
+#[derivative(Default)]
+pub(crate) struct AnimationStateMachineSystem<I, T> {
+    _marker: PhantomData<(I, T)>,
+}
+
+impl<I, T> System for AnimationStateMachineSystem<I, T>
+where
+    I: std::fmt::Debug + PartialEq + Eq + Hash + Copy + Send + Sync + 'static,
+    T: AnimationSampling + Clone + std::fmt::Debug,
+{
+    fn build(self) -> Box<dyn ParallelRunnable> {
+        let mut weights = Vec::default();
+
+        Box::new(
+            SystemBuilder::new("AnimationStateMachineSystem")
+                .read_resource::<Time>()
+                .read_resource::<AssetStorage<AnimationStateMachine<I>>>()
+                .with_query(<(
+                    Write<AnimationStateMachineControl<I>>,
+                    Read<AnimationSet<I, T>>,
+                    Write<AnimationControlSet<I, T>>,
+                )>::query())
+                .build(move |_, world, (time, machines), query| {
+                    #[cfg(feature = "profiler")]
+                    profile_scope!("animation_state_machine_system");
+
+                    for (control, animation_set, control_set) in query.iter_mut(world) {
+                        if let Some(machine) = machines.get(&control.machine) {
+                            update_state(machine, control, time.delta_seconds());
+                            weights.clear();
+                            state_weights(machine, control, &mut weights);
+                            apply_weights(control, animation_set, control_set, &weights);
+                        }
+                    }
+                }),
+        )
+    }
+}
+
+/// Advance the time of the current state and the cross-fade, and take a transition if one of
+/// them can be taken.
+fn update_state<I>(
+    machine: &AnimationStateMachine<I>,
+    control: &mut AnimationStateMachineControl<I>,
+    delta_seconds: f32,
+) {
+    let current = match control.current.as_mut() {
+        Some(current) => {
+            current.time += delta_seconds;
+            current.index
+        }
+        None => {
+            match machine.initial_index() {
+                Some(index) => {
+                    debug!("Entering initial state {}", machine.states[index].name);
+                    control.enter(machine, index, 0.);
+                    return;
+                }
+                None => {
+                    error!("Animation state machine has no initial state");
+                    return;
+                }
+            }
+        }
+    };
+
+    if let Some(fade) = control.fade.as_mut() {
+        fade.elapsed += delta_seconds;
+        if fade.elapsed >= fade.duration {
+            control.fade = None;
+        }
+    }
+
+    let next = match control.requested.take() {
+        Some((name, duration)) => {
+            let index = machine.state_index(&name);
+            if index.is_none() {
+                error!("Requested transition to unknown state {}", name);
+            }
+            index
+                .filter(|index| *index != current)
+                .map(|index| (index, duration))
+        }
+        None => {
+            let time = control.time_in_state();
+            machine
+                .find_transition(current, &control.parameters, time)
+                .map(|(transition, index)| {
+                    control.consume_triggers(transition);
+                    (index, transition.duration)
+                })
+        }
+    };
+
+    if let Some((index, duration)) = next {
+        debug!(
+            "Transition from state {} to {}",
+            machine.states[current].name, machine.states[index].name
+        );
+        control.enter(machine, index, duration);
+    }
+}
+
+/// Compute the weights of the animations of the current state, and of the state being faded
+/// from, with whether they loop.
+fn state_weights<I>(
+    machine: &AnimationStateMachine<I>,
+    control: &AnimationStateMachineControl<I>,
+    weights: &mut Vec<(I, f32, bool)>,
+) where
+    I: Copy + PartialEq,
+{
+    let current = match &control.current {
+        Some(current) => current.index,
+        None => return,
+    };
+    let alpha = control
+        .fade
+        .as_ref()
+        .map_or(1., |fade| (fade.elapsed / fade.duration).min(1.));
+
+    let mut add = |index: usize, factor: f32| {
+        let state = &machine.states[index];
+        for (id, weight) in state.motion.evaluate(&control.parameters) {
+            match weights.iter_mut().find(|w| w.0 == id) {
+                Some(entry) => entry.1 += weight * factor,
+                None => weights.push((id, weight * factor, state.looping)),
+            }
+        }
+    };
+    add(current, alpha);
+    if let Some(fade) = &control.fade {
+        add(fade.from, 1. - alpha);
+    }
+}
+
+/// Abort the animations of the states which were left, start the ones of the states which were
+/// entered, and set the weights of all of them.
+fn apply_weights<I, T>(
+    control: &mut AnimationStateMachineControl<I>,
+    animation_set: &AnimationSet<I, T>,
+    control_set: &mut AnimationControlSet<I, T>,
+    weights: &[(I, f32, bool)],
+) where
+    I: std::fmt::Debug + Eq + Hash + Copy,
+    T: AnimationSampling,
+{
+    control.playing.retain(|id| {
+        let active = weights.iter().any(|w| w.0 == *id);
+        if !active {
+            control_set.abort(*id);
+        }
+        active
+    });
+
+    for &(id, weight, looping) in weights {
+        if !control_set.has_animation(id) {
+            match animation_set.get(&id) {
+                Some(animation) => {
+                    let end = if looping {
+                        EndControl::Loop(None)
+                    } else {
+                        EndControl::Stay
+                    };
+                    control_set.add_animation(id, animation, end, 1.0, AnimationCommand::Start);
+                }
+                None => {
+                    debug!("Animation {:?} of the state machine isn't in the set", id);
+                    continue;
+                }
+            }
+            if !control.playing.contains(&id) {
+                control.playing.push(id);
+            }
+        }
+        control_set.set_weight(id, weight);
+    }
+}
@@ -0,0 +1,500 @@
+//! Tweens, animating a single field of a component from gameplay code without authoring an
+//! `Animation` asset.
+//!
+//! ```rust,ignore
+//! use amethyst::animation::{tween, Easing, Tweens};
+//!
+//! let id = resources
+//!     .get_mut::<Tweens<Transform>>()
+//!     .unwrap()
+//!     .add(
+//!         tween(entity)
+//!             .field(Transform::translation_mut)
+//!             .to(Vector3::new(0., 2., 0.), 0.3)
+//!             .ease(Easing::OutCubic),
+//!     );
+//! ```
+
+use std::{f32::consts::PI, fmt, marker::PhantomData};
+
+use amethyst_core::{
+    ecs::*,
+    math::{UnitQuaternion, Vector2, Vector3, Vector4},
+    Time,
+};
+use derivative::Derivative;
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+
+/// Easing functions, mapping the progress of a tween to the progress of its value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Easing {
+    /// Constant speed
+    Linear,
+    /// Quadratic, accelerating from zero velocity
+    InQuad,
+    /// Quadratic, decelerating to zero velocity
+    OutQuad,
+    /// Quadratic, accelerating until halfway, then decelerating
+    InOutQuad,
+    /// Cubic, accelerating from zero velocity
+    InCubic,
+    /// Cubic, decelerating to zero velocity
+    OutCubic,
+    /// Cubic, accelerating until halfway, then decelerating
+    InOutCubic,
+    /// Sinusoidal, accelerating from zero velocity
+    InSine,
+    /// Sinusoidal, decelerating to zero velocity
+    OutSine,
+    /// Sinusoidal, accelerating until halfway, then decelerating
+    InOutSine,
+    /// Exponential, accelerating from zero velocity
+    InExpo,
+    /// Exponential, decelerating to zero velocity
+    OutExpo,
+    /// Backing up a little before moving towards the target
+    InBack,
+    /// Overshooting the target a little before settling on it
+    OutBack,
+    /// Oscillating around the target before settling on it
+    OutElastic,
+    /// Bouncing off the target like a dropped ball
+    OutBounce,
+}
+
+impl Default for Easing {
+    fn default() -> Self {
+        Easing::Linear
+    }
+}
+
+impl Easing {
+    /// Map the progress of a tween, from 0 to 1, to the progress of its value.
+    pub fn apply(self, t: f32) -> f32 {
+        const BACK: f32 = 1.701_58;
+        let t = t.max(0.).min(1.);
+        match self {
+            Easing::Linear => t,
+            Easing::InQuad => t * t,
+            Easing::OutQuad => 1. - (1. - t) * (1. - t),
+            Easing::InOutQuad => {
+                if t < 0.5 {
+                    2. * t * t
+                } else {
+                    1. - (-2. * t + 2.).powi(2) / 2.
+                }
+            }
+            Easing::InCubic => t * t * t,
+            Easing::OutCubic => 1. - (1. - t).powi(3),
+            Easing::InOutCubic => {
+                if t < 0.5 {
+                    4. * t * t * t
+                } else {
+                    1. - (-2. * t + 2.).powi(3) / 2.
+                }
+            }
+            Easing::InSine => 1. - (t * PI / 2.).cos(),
+            Easing::OutSine => (t * PI / 2.).sin(),
+            Easing::InOutSine => -((PI * t).cos() - 1.) / 2.,
+            Easing::InExpo => {
+                if t == 0. {
+                    0.
+                } else {
+                    2f32.powf(10. * t - 10.)
+                }
+            }
+            Easing::OutExpo => {
+                if t == 1. {
+                    1.
+                } else {
+                    1. - 2f32.powf(-10. * t)
+                }
+            }
+            Easing::InBack => (BACK + 1.) * t * t * t - BACK * t * t,
+            Easing::OutBack => 1. + (BACK + 1.) * (t - 1.).powi(3) + BACK * (t - 1.).powi(2),
+            Easing::OutElastic => {
+                if t == 0. || t == 1. {
+                    t
+                } else {
+                    2f32.powf(-10. * t) * ((t * 10. - 0.75) * (2. * PI / 3.)).sin() + 1.
+                }
+            }
+            Easing::OutBounce => {
+                const N: f32 = 7.5625;
+                const D: f32 = 2.75;
+                if t < 1. / D {
+                    N * t * t
+                } else if t < 2. / D {
+                    let t = t - 1.5 / D;
+                    N * t * t + 0.75
+                } else if t < 2.5 / D {
+                    let t = t - 2.25 / D;
+                    N * t * t + 0.9375
+                } else {
+                    let t = t - 2.625 / D;
+                    N * t * t + 0.984_375
+                }
+            }
+        }
+    }
+}
+
+/// Values which can be tweened.
+pub trait Tweenable: Clone + Send + Sync + 'static {
+    /// Interpolate between `self` at 0 and `other` at 1, `t` can go beyond 0 and 1 for easings
+    /// which overshoot
+    fn lerp(&self, other: &Self, t: f32) -> Self;
+}
+
+impl Tweenable for f32 {
+    fn lerp(&self, other: &Self, t: f32) -> Self {
+        self + (other - self) * t
+    }
+}
+
+impl Tweenable for Vector2<f32> {
+    fn lerp(&self, other: &Self, t: f32) -> Self {
+        self + (other - self) * t
+    }
+}
+
+impl Tweenable for Vector3<f32> {
+    fn lerp(&self, other: &Self, t: f32) -> Self {
+        self + (other - self) * t
+    }
+}
+
+impl Tweenable for Vector4<f32> {
+    fn lerp(&self, other: &Self, t: f32) -> Self {
+        self + (other - self) * t
+    }
+}
+
+impl Tweenable for UnitQuaternion<f32> {
+    fn lerp(&self, other: &Self, t: f32) -> Self {
+        self.try_slerp(other, t, 1.0e-6)
+            .unwrap_or_else(|| self.nlerp(other, t))
+    }
+}
+
+/// Start building a tween of a component of the given entity.
+pub fn tween(entity: Entity) -> TweenBuilder {
+    TweenBuilder { entity }
+}
+
+/// Builder of a tween, choosing the field to tween.
+#[derive(Debug, Clone, Copy)]
+pub struct TweenBuilder {
+    entity: Entity,
+}
+
+impl TweenBuilder {
+    /// Tween the field of a component returned by the given accessor, e.g.
+    /// `Transform::translation_mut`.
+    pub fn field<C, V>(self, field: fn(&mut C) -> &mut V) -> FieldTween<C, V>
+    where
+        C: Send + Sync + 'static,
+        V: Tweenable,
+    {
+        FieldTween {
+            entity: self.entity,
+            field,
+            from: None,
+            to: None,
+            duration: 0.,
+            delay: 0.,
+            easing: Easing::Linear,
+            repeat: 1,
+            yoyo: false,
+            elapsed: 0.,
+        }
+    }
+}
+
+/// A tween of a field of a component, built with `tween`.
+///
+/// Tweens to the target value from the value of the field when the tween starts, unless a start
+/// value is given with `from`. Later tweens of the same field win over earlier ones.
+pub struct FieldTween<C, V> {
+    entity: Entity,
+    field: fn(&mut C) -> &mut V,
+    from: Option<V>,
+    to: Option<V>,
+    duration: f32,
+    delay: f32,
+    easing: Easing,
+    repeat: u32,
+    yoyo: bool,
+    elapsed: f32,
+}
+
+impl<C, V> fmt::Debug for FieldTween<C, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FieldTween")
+            .field("entity", &self.entity)
+            .field("duration", &self.duration)
+            .field("delay", &self.delay)
+            .field("easing", &self.easing)
+            .field("repeat", &self.repeat)
+            .field("yoyo", &self.yoyo)
+            .field("elapsed", &self.elapsed)
+            .finish()
+    }
+}
+
+impl<C, V> FieldTween<C, V>
+where
+    V: Tweenable,
+{
+    /// Tween to the given value in the given number of seconds
+    pub fn to(mut self, value: V, duration: f32) -> Self {
+        self.to = Some(value);
+        self.duration = duration;
+        self
+    }
+
+    /// Start from the given value instead of the value of the field
+    pub fn from(mut self, value: V) -> Self {
+        self.from = Some(value);
+        self
+    }
+
+    /// Set the easing function, default is `Easing::Linear`
+    pub fn ease(mut self, easing: Easing) -> Self {
+        self.easing = easing;
+        self
+    }
+
+    /// Wait the given number of seconds before starting
+    pub fn delay(mut self, delay: f32) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    /// Play the tween the given number of times, 0 for looping until it's cancelled
+    pub fn repeat(mut self, count: u32) -> Self {
+        self.repeat = count;
+        self
+    }
+
+    /// Play every other repetition backwards, going back and forth between the values
+    pub fn yoyo(mut self) -> Self {
+        self.yoyo = true;
+        self
+    }
+
+    /// The value at the given number of seconds after the start, and if the tween is done.
+    fn value(&self, from: &V, to: &V, time: f32) -> (V, bool) {
+        if self.duration <= 0. {
+            return (to.clone(), true);
+        }
+        let cycle = (time / self.duration).floor();
+        let done = self.repeat > 0 && cycle >= self.repeat as f32;
+        let (cycle, progress) = if done {
+            (self.repeat as f32 - 1., 1.)
+        } else {
+            (cycle, time / self.duration - cycle)
+        };
+        let progress = if self.yoyo && cycle % 2. == 1. {
+            1. - progress
+        } else {
+            progress
+        };
+        (from.lerp(to, self.easing.apply(progress)), done)
+    }
+}
+
+/// A tween of a component, implemented by `FieldTween`.
+pub trait Tween<C>: fmt::Debug + Send + Sync {
+    /// The entity of the tweened component
+    fn entity(&self) -> Entity;
+
+    /// Advance the tween by the given number of seconds and apply it to the component, returns
+    /// true once the tween is done
+    fn update(&mut self, component: &mut C, delta: f32) -> bool;
+}
+
+impl<C, V> Tween<C> for FieldTween<C, V>
+where
+    C: Send + Sync + 'static,
+    V: Tweenable,
+{
+    fn entity(&self) -> Entity {
+        self.entity
+    }
+
+    fn update(&mut self, component: &mut C, delta: f32) -> bool {
+        self.elapsed += delta;
+        let time = self.elapsed - self.delay;
+        if time < 0. {
+            return false;
+        }
+        let to = match self.to {
+            Some(ref to) => to.clone(),
+            None => return true,
+        };
+        let field = self.field;
+        let from = self
+            .from
+            .get_or_insert_with(|| field(component).clone())
+            .clone();
+        let (value, done) = self.value(&from, &to, time);
+        *field(component) = value;
+        done
+    }
+}
+
+/// Id of a running tween, for cancelling it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TweenId(u64);
+
+/// Resource with the running tweens of a component type.
+///
+/// Tweens are added with `add`, and removed when they're done, or when their entity or component
+/// is removed.
+#[derive(Derivative)]
+#[derivative(Default(bound = ""), Debug(bound = ""))]
+pub struct Tweens<C> {
+    tweens: Vec<(TweenId, Box<dyn Tween<C>>)>,
+    next_id: u64,
+}
+
+impl<C> Tweens<C>
+where
+    C: 'static,
+{
+    /// Start a tween
+    pub fn add<T: Tween<C> + 'static>(&mut self, tween: T) -> TweenId {
+        let id = TweenId(self.next_id);
+        self.next_id += 1;
+        self.tweens.push((id, Box::new(tween)));
+        id
+    }
+
+    /// Stop a tween, leaving the field at its current value
+    pub fn cancel(&mut self, id: TweenId) {
+        self.tweens.retain(|(i, _)| *i != id);
+    }
+
+    /// Stop all tweens of an entity
+    pub fn cancel_entity(&mut self, entity: Entity) {
+        self.tweens.retain(|(_, t)| t.entity() != entity);
+    }
+
+    /// Check if a tween is still running
+    pub fn is_running(&self, id: TweenId) -> bool {
+        self.tweens.iter().any(|(i, _)| *i == id)
+    }
+
+    /// Check if there are no running tweens
+    pub fn is_empty(&self) -> bool {
+        self.tweens.is_empty()
+    }
+}
+
+/// System updating the `Tweens` of a component type.
+///
+/// ### Type parameters:
+///
+/// - `C`: the component type that is tweened
+#[derive(Derivative)]
+#[derivative(Default(bound = ""), Debug(bound = ""))]
+pub(crate) struct TweenSystem<C> {
+    _marker: PhantomData<C>,
+}
+
+impl<C> System for TweenSystem<C>
+where
+    C: Component,
+{
+    fn build(self) -> Box<dyn ParallelRunnable> {
+        let mut done = Vec::default();
+
+        Box::new(
+            SystemBuilder::new("TweenSystem")
+                .read_resource::<Time>()
+                .write_resource::<Tweens<C>>()
+                .write_component::<C>()
+                .build(move |_, world, (time, tweens), _| {
+                    #[cfg(feature = "profiler")]
+                    profile_scope!("tween_system");
+
+                    let delta = time.delta_seconds();
+                    done.clear();
+                    for (id, tween) in tweens.tweens.iter_mut() {
+                        let finished = match world.entry_mut(tween.entity()) {
+                            Ok(mut entry) => {
+                                match entry.get_component_mut::<C>() {
+                                    Ok(component) => tween.update(component, delta),
+                                    Err(_) => true,
+                                }
+                            }
+                            Err(_) => true,
+                        };
+                        if finished {
+                            done.push(*id);
+                        }
+                    }
+                    if !done.is_empty() {
+                        tweens.tweens.retain(|(id, _)| !done.contains(id));
+                    }
+                }),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Position {
+        x: f32,
+    }
+
+    fn x(position: &mut Position) -> &mut f32 {
+        &mut position.x
+    }
+
+    #[test]
+    fn test_easings_start_and_end_on_the_values() {
+        let easings = [
+            Easing::Linear,
+            Easing::OutCubic,
+            Easing::InOutSine,
+            Easing::OutBack,
+            Easing::InExpo,
+            Easing::OutElastic,
+            Easing::OutBounce,
+        ];
+        for easing in &easings {
+            assert!(easing.apply(0.).abs() < 1e-3, "{:?}", easing);
+            assert!((easing.apply(1.) - 1.).abs() < 1e-3, "{:?}", easing);
+        }
+        assert_eq!(Easing::InQuad.apply(0.5), 0.25);
+    }
+
+    #[test]
+    fn test_field_tween() {
+        let mut world = World::default();
+        let entity = world.push((0,));
+        let mut position = Position { x: 1. };
+        let mut tween = tween(entity)
+            .field(x)
+            .to(3., 1.)
+            .delay(0.5)
+            .repeat(2)
+            .yoyo();
+
+        assert!(!tween.update(&mut position, 0.25));
+        assert_eq!(position.x, 1.);
+        assert!(!tween.update(&mut position, 0.75));
+        assert_eq!(position.x, 2.);
+        // going back on the second repetition
+        assert!(!tween.update(&mut position, 0.75));
+        assert_eq!(position.x, 2.5);
+        assert!(tween.update(&mut position, 1.));
+        assert_eq!(position.x, 1.);
+    }
+}
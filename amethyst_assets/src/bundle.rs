@@ -1,12 +1,15 @@
 use std::{default::Default, path::PathBuf};
 
-use amethyst_core::ecs::{DispatcherBuilder, Resources, SystemBundle, World};
+use amethyst_core::{
+    ecs::{DispatcherBuilder, Resources, SystemBundle, World},
+    shrev::EventChannel,
+};
 use amethyst_error::Error;
 use distill::daemon::{default_importer_contexts, default_importers, AssetDaemon, ImporterMap};
 use log::info;
 
 use crate::{
-    prefab::{ComponentRegistryBuilder, PrefabImporter},
+    prefab::{ComponentRegistryBuilder, PrefabImporter, SceneEvent, Scenes},
     simple_importer::get_source_importers,
     DefaultLoader, Loader,
 };
@@ -59,15 +62,19 @@ impl SystemBundle for LoaderBundle {
     ) -> Result<(), Error> {
         let component_registry = ComponentRegistryBuilder::default()
             .auto_register_components()
+            .auto_register_spawn_mappings()
             .build();
         resources.insert(component_registry);
         let mut loader = DefaultLoader::default();
         loader.init_world(resources);
         loader.init_dispatcher(builder);
         resources.insert(loader);
+        resources.insert(Scenes::default());
+        resources.insert(EventChannel::<SceneEvent>::new());
 
         builder.add_thread_local_fn(asset_loading_tick);
         builder.add_thread_local_fn(crate::prefab::system::prefab_spawning_tick);
+        builder.add_thread_local_fn(crate::prefab::scene::scene_tick);
 
         Ok(())
     }
@@ -27,14 +27,18 @@ mod loader;
 pub mod prefab;
 mod processor;
 mod progress;
+/// reflection of components for tools
+pub mod reflect;
 mod simple_importer;
+/// snapshots of the world for save games
+pub mod snapshot;
 mod source;
 mod storage;
 
 pub use distill::{
     importer as distill_importer,
     loader::{
-        handle::{AssetHandle, GenericHandle, Handle, WeakHandle},
+        handle::{make_handle, AssetHandle, GenericHandle, Handle, WeakHandle},
         storage::LoadHandle,
     },
 };
@@ -45,6 +49,8 @@ pub use {erased_serde, inventory, lazy_static};
 
 #[cfg(feature = "json")]
 pub use crate::json::JsonFormat;
+#[cfg(target_arch = "wasm32")]
+pub use crate::source::Fetch;
 pub use crate::{
     asset::{Asset, Format, FormatValue, ProcessableAsset, SerializableFormat},
     bundle::{start_asset_daemon, LoaderBundle},
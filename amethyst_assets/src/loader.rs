@@ -244,6 +244,20 @@ impl Loader for DefaultLoader {
     }
 }
 
+impl DefaultLoader {
+    /// Runs `f` with the context for serializing `Handle`s as the uuids of their assets, and
+    /// deserializing them into handles of this loader.
+    pub fn with_serde_context<R>(&self, f: impl FnOnce() -> R) -> R {
+        let mut result = None;
+        futures_executor::block_on(SerdeContext::with(
+            &self.loader,
+            self.ref_sender.clone(),
+            async { result = Some(f()) },
+        ));
+        result.expect("serde context future did not complete")
+    }
+}
+
 /// Storage for a particular asset type.
 ///
 /// This trait abtracts over the bridge between `distill_loader` and Amethyst's asset storage. These
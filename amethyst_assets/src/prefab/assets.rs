@@ -1,11 +1,13 @@
 use amethyst_core::ecs::World;
+use amethyst_error::Error;
 use distill::importer as distill_importer;
 use distill_importer::{typetag, SerdeImportable};
 use fnv::FnvHashSet;
 use serde::{Deserialize, Serialize};
 use type_uuid::TypeUuid;
 
-use crate::{asset::Asset, Handle, WeakHandle};
+use super::importers::registered_components;
+use crate::{asset::Asset, AssetUuid, Handle, WeakHandle};
 
 /// Prefab Asset, containing a cooked world.
 #[derive(TypeUuid, Serialize, Deserialize, SerdeImportable)]
@@ -40,6 +42,40 @@ impl Default for Prefab {
     }
 }
 
+impl Prefab {
+    /// Creates a prefab of the entities of a world, to be saved with `to_ron`.
+    pub fn new(world: World) -> Self {
+        Prefab {
+            raw: legion_prefab::Prefab::new(world),
+            ..Default::default()
+        }
+    }
+
+    /// Creates a prefab of the entities of a world with the id of its asset, which other prefabs
+    /// refer to it by. Importers producing a prefab next to other assets use this to keep the id
+    /// of the prefab between imports.
+    pub fn with_id(world: World, id: AssetUuid) -> Self {
+        let mut prefab = Prefab::new(world);
+        prefab.raw.prefab_meta.id = id.0;
+        prefab
+    }
+
+    /// Serializes the prefab in the `.prefab` format read by the `PrefabImporter`, keeping the
+    /// components registered with `register_component_type!`.
+    pub fn to_ron(&self) -> Result<String, Error> {
+        let registered_components = registered_components();
+        let prefab_serde_context = legion_prefab::PrefabSerdeContext {
+            registered_components: &registered_components,
+        };
+        let mut serializer =
+            ron::ser::Serializer::new(Some(ron::ser::PrettyConfig::default()), true);
+        let prefab_ser =
+            legion_prefab::PrefabFormatSerializer::new(prefab_serde_context, &self.raw);
+        prefab_format::serialize(&mut serializer, &prefab_ser, self.raw.prefab_id())?;
+        Ok(serializer.into_output_string())
+    }
+}
+
 impl Asset for Prefab {
     fn name() -> &'static str {
         "PREFAB"
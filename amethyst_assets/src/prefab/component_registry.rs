@@ -41,6 +41,15 @@ impl ComponentRegistryBuilder {
         self
     }
 
+    /// adds the mappings submitted with register_spawn_mapping!, called by LoaderBundle
+    pub fn auto_register_spawn_mappings(mut self) -> Self {
+        for mapping in inventory::iter::<SpawnMapping> {
+            (mapping.register)(&mut self.spawn_handler_set);
+        }
+
+        self
+    }
+
     /// adds a mapping between prefab component and spawned component
     pub fn add_spawn_mapping_into<FromT: Component + Clone + Into<IntoT>, IntoT: Component>(
         mut self,
@@ -87,6 +96,38 @@ impl ComponentRegistryBuilder {
     }
 }
 
+/// A mapping from a prefab component to the component spawned in its place.
+/// use register_spawn_mapping! macro to add new mappings
+pub struct SpawnMapping {
+    /// adds the mapping to the spawn handlers of a registry
+    pub register: fn(&mut SpawnCloneImplHandlerSet),
+}
+inventory::collect!(SpawnMapping);
+
+/// Spawns a prefab component as its `Into` conversion, for components which can't be registered
+/// with `register_component_type!` themselves, such as handles or components of other crates.
+///
+/// # Parameters
+///
+/// * `from`: The component stored in the prefab, registered with `register_component_type!`.
+/// * `into`: The component spawned in its place.
+#[macro_export]
+macro_rules! register_spawn_mapping {
+    ($from:ty => $into:ty) => {
+        $crate::register_spawn_mapping!(amethyst_assets; $from => $into);
+    };
+    ($krate:ident; $from:ty => $into:ty) => {
+        $crate::inventory::submit!{
+            #![crate = $krate]
+            $crate::prefab::SpawnMapping {
+                register: |handlers| {
+                    handlers.add_mapping_into::<$from, $into>();
+                },
+            }
+        }
+    };
+}
+
 /// stores information about how to construct components from prefabs
 pub struct ComponentRegistry {
     components: FnvHashMap<ComponentTypeId, ComponentRegistration>,
@@ -23,6 +23,14 @@ pub struct PrefabImporterState {
     pub id: Option<AssetUuid>,
 }
 
+/// The components registered with `register_component_type!`, by uuid.
+pub(crate) fn registered_components() -> HashMap<ComponentTypeUuid, ComponentRegistration> {
+    log::info!("Getting registered components");
+    legion_prefab::iter_component_registrations()
+        .map(|reg| (*reg.uuid(), reg.clone()))
+        .collect()
+}
+
 /// The importer for '.prefab' files.
 #[derive(Default, TypeUuid)]
 #[uuid = "5bdf4d06-a1cb-437b-b182-d6d8cb23512c"]
@@ -60,16 +68,7 @@ impl Importer for PrefabImporter {
         let mut de = ron::de::Deserializer::from_bytes(bytes.as_slice()).unwrap();
 
         // Create the component registry
-        let registered_components = {
-            let comp_registrations = legion_prefab::iter_component_registrations();
-            log::info!("Getting registered components");
-            let component_types: HashMap<ComponentTypeUuid, ComponentRegistration> =
-                comp_registrations
-                    .map(|reg| (*reg.uuid(), reg.clone()))
-                    .collect();
-
-            component_types
-        };
+        let registered_components = registered_components();
 
         let prefab_serde_context = legion_prefab::PrefabSerdeContext {
             registered_components: &registered_components,
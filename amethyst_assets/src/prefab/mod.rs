@@ -8,12 +8,18 @@ pub use assets::Prefab;
 pub(crate) mod system;
 
 mod component_registry;
-pub use component_registry::{ComponentRegistry, ComponentRegistryBuilder};
+pub use component_registry::{ComponentRegistry, ComponentRegistryBuilder, SpawnMapping};
 pub use legion_prefab::{self, register_component_type, ComponentRegistration};
 pub use serde_diff::{self, SerdeDiff};
 
 mod processor;
 
+mod overrides;
+pub use overrides::PrefabOverrides;
+
+mod scene;
+pub use scene::{SceneEvent, SceneId, Scenes};
+
 // register core components
 register_component_type!(amethyst_core::transform::Transform);
 register_component_type!(amethyst_core::transform::TransformValues);
@@ -0,0 +1,139 @@
+use std::{fmt, sync::Arc};
+
+use amethyst_core::ecs::{storage::Component, Entity, EntityStore, World};
+
+type Override = Box<dyn Fn(&mut World, Entity, &[Entity]) + Send + Sync>;
+
+/// Per-instance changes to a prefab, applied when it's spawned.
+///
+/// Add it to the entity holding the `Handle<Prefab>`, so instances of the same prefab get
+/// their own position, texture or stats. The overrides are applied again when the prefab is
+/// reloaded.
+///
+/// ```rust,ignore
+/// world.push((
+///     prefab_handle.clone(),
+///     PrefabOverrides::new()
+///         .with(Transform::from(Vector3::new(4.0, 0.0, 2.0)))
+///         .modify(|health: &mut Health| health.max *= 2),
+/// ));
+/// ```
+#[derive(Clone, Default)]
+pub struct PrefabOverrides {
+    overrides: Arc<Vec<Override>>,
+}
+
+impl fmt::Debug for PrefabOverrides {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PrefabOverrides")
+            .field("overrides", &self.overrides.len())
+            .finish()
+    }
+}
+
+impl PrefabOverrides {
+    /// Creates an empty set of overrides.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets a component on the root entity of the prefab, replacing the one of the prefab.
+    pub fn with<C: Component + Clone>(self, component: C) -> Self {
+        self.push(Box::new(move |world, root, _| {
+            if let Some(mut entry) = world.entry(root) {
+                entry.add_component(component.clone());
+            }
+        }))
+    }
+
+    /// Modifies a component on all the entities of the prefab which have it.
+    pub fn modify<C, F>(self, f: F) -> Self
+    where
+        C: Component,
+        F: Fn(&mut C) + Send + Sync + 'static,
+    {
+        self.push(Box::new(move |world, _, entities| {
+            for entity in entities {
+                if let Ok(mut entry) = world.entry_mut(*entity) {
+                    if let Ok(component) = entry.get_component_mut::<C>() {
+                        f(component);
+                    }
+                }
+            }
+        }))
+    }
+
+    /// Modifies the root entity of the prefab with a custom function.
+    pub fn with_fn<F>(self, f: F) -> Self
+    where
+        F: Fn(&mut World, Entity) + Send + Sync + 'static,
+    {
+        self.push(Box::new(move |world, root, _| f(world, root)))
+    }
+
+    /// Returns true if there are no overrides.
+    pub fn is_empty(&self) -> bool {
+        self.overrides.is_empty()
+    }
+
+    fn push(mut self, item: Override) -> Self {
+        match Arc::get_mut(&mut self.overrides) {
+            Some(overrides) => overrides.push(item),
+            None => {
+                // the overrides are shared with a clone, which keeps the previous ones
+                let mut overrides: Vec<Override> = Vec::new();
+                let shared = self.overrides.clone();
+                overrides.push(Box::new(move |world, root, entities| {
+                    for item in shared.iter() {
+                        item(world, root, entities);
+                    }
+                }));
+                overrides.push(item);
+                self.overrides = Arc::new(overrides);
+            }
+        }
+        self
+    }
+
+    /// Applies the overrides to a spawned prefab.
+    pub(crate) fn apply(&self, world: &mut World, root: Entity, entities: &[Entity]) {
+        for item in self.overrides.iter() {
+            item(world, root, entities);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct Health(u32);
+
+    fn health(world: &World, entity: Entity) -> Option<Health> {
+        world
+            .entry_ref(entity)
+            .ok()
+            .and_then(|entry| entry.get_component::<Health>().ok().cloned())
+    }
+
+    #[test]
+    fn overrides_apply_to_root_and_children() {
+        let mut world = World::default();
+        let root = world.push((Health(10),));
+        let child = world.push((Health(5), 0u8));
+        let overrides = PrefabOverrides::new()
+            .with(Health(20))
+            .modify(|health: &mut Health| health.0 += 1);
+        let shared = overrides
+            .clone()
+            .modify(|health: &mut Health| health.0 *= 2);
+
+        overrides.apply(&mut world, root, &[root, child]);
+        assert_eq!(health(&world, root), Some(Health(21)));
+        assert_eq!(health(&world, child), Some(Health(6)));
+
+        shared.apply(&mut world, child, &[child]);
+        assert_eq!(health(&world, child), Some(Health(42)));
+    }
+}
@@ -0,0 +1,295 @@
+use std::collections::BTreeMap;
+
+use amethyst_core::{
+    ecs::{Entity, Resources, World},
+    shrev::EventChannel,
+};
+use fnv::FnvHashSet;
+
+use crate::{
+    prefab::{system::PrefabInstance, Prefab},
+    Handle, Loader,
+};
+
+/// Identifier of a scene loaded in the `Scenes` resource.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SceneId(u32);
+
+/// Event sent by the `LoaderBundle` when a scene changed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SceneEvent {
+    /// All the prefabs of the scene were spawned
+    Loaded(SceneId),
+    /// The scene was unloaded, and its entities removed
+    Unloaded(SceneId),
+}
+
+#[derive(Debug)]
+struct Scene {
+    name: String,
+    roots: Vec<Entity>,
+    handles: Vec<Handle<Prefab>>,
+    loaded: bool,
+}
+
+/// Resource tracking scenes: sets of prefabs loaded and unloaded as a unit.
+///
+/// Each prefab of a scene can hold many entities and reference nested prefabs. A scene spawns
+/// a root entity per prefab, which the prefab is spawned into once it's loaded. Scenes are
+/// additive, loading a scene keeps the entities of the others. Unloading a scene removes all
+/// the entities spawned for its prefabs, and drops its handles so unused assets are released.
+///
+/// ```rust,ignore
+/// let level = scenes.load(
+///     world,
+///     &loader,
+///     "level_1",
+///     &["level_1/terrain.prefab", "level_1/props.prefab"],
+/// );
+/// let ui = scenes.load(world, &loader, "hud", &["ui/hud.prefab"]);
+///
+/// // when the player leaves the level, the hud stays
+/// scenes.unload(world, level);
+/// ```
+#[derive(Debug, Default)]
+pub struct Scenes {
+    scenes: BTreeMap<SceneId, Scene>,
+    next_id: u32,
+    events: Vec<SceneEvent>,
+}
+
+impl Scenes {
+    /// Loads the prefabs at the given paths as a new scene.
+    pub fn load(
+        &mut self,
+        world: &mut World,
+        loader: &impl Loader,
+        name: &str,
+        paths: &[&str],
+    ) -> SceneId {
+        let handles = paths.iter().map(|path| loader.load(path)).collect();
+        self.spawn(world, name, handles)
+    }
+
+    /// Creates a new scene from prefabs which are loaded or being loaded.
+    pub fn spawn(
+        &mut self,
+        world: &mut World,
+        name: &str,
+        prefabs: Vec<Handle<Prefab>>,
+    ) -> SceneId {
+        let id = SceneId(self.next_id);
+        self.next_id += 1;
+        let roots = prefabs
+            .iter()
+            .map(|handle| world.push((handle.clone(),)))
+            .collect();
+        self.scenes.insert(
+            id,
+            Scene {
+                name: name.to_string(),
+                roots,
+                handles: prefabs,
+                loaded: false,
+            },
+        );
+        id
+    }
+
+    /// Adds a prefab to a loaded scene, returning the root entity it is spawned into.
+    pub fn add(
+        &mut self,
+        world: &mut World,
+        id: SceneId,
+        prefab: Handle<Prefab>,
+    ) -> Option<Entity> {
+        let scene = self.scenes.get_mut(&id)?;
+        let root = world.push((prefab.clone(),));
+        scene.roots.push(root);
+        scene.handles.push(prefab);
+        scene.loaded = false;
+        Some(root)
+    }
+
+    /// Moves the prefabs of the scene `from` into the scene `into`, so they are unloaded
+    /// along with it. Returns false if one of the scenes doesn't exist.
+    pub fn merge(&mut self, into: SceneId, from: SceneId) -> bool {
+        if into == from || !self.scenes.contains_key(&into) {
+            return false;
+        }
+        let merged = match self.scenes.remove(&from) {
+            Some(merged) => merged,
+            None => return false,
+        };
+        let scene = self.scenes.get_mut(&into).expect("checked above");
+        scene.roots.extend(merged.roots);
+        scene.handles.extend(merged.handles);
+        scene.loaded &= merged.loaded;
+        true
+    }
+
+    /// Finds a scene by name.
+    pub fn find(&self, name: &str) -> Option<SceneId> {
+        self.scenes
+            .iter()
+            .find(|(_, scene)| scene.name == name)
+            .map(|(id, _)| *id)
+    }
+
+    /// The name of a scene.
+    pub fn name(&self, id: SceneId) -> Option<&str> {
+        self.scenes.get(&id).map(|scene| scene.name.as_str())
+    }
+
+    /// Iterate over the scenes, in the order they were created.
+    pub fn ids(&self) -> impl Iterator<Item = SceneId> + '_ {
+        self.scenes.keys().copied()
+    }
+
+    /// Returns true if all the prefabs of the scene were spawned.
+    pub fn is_loaded(&self, id: SceneId) -> bool {
+        self.scenes.get(&id).map_or(false, |scene| scene.loaded)
+    }
+
+    /// The entities of a scene: the roots of its prefabs, and the entities spawned for them.
+    pub fn entities(&self, world: &World, id: SceneId) -> Vec<Entity> {
+        let scene = match self.scenes.get(&id) {
+            Some(scene) => scene,
+            None => return Vec::new(),
+        };
+        let mut entities = FnvHashSet::default();
+        for root in &scene.roots {
+            entities.insert(*root);
+            if let Ok(entry) = world.entry_ref(*root) {
+                if let Ok(instance) = entry.into_component::<PrefabInstance>() {
+                    entities.extend(instance.entity_map.values().copied());
+                }
+            }
+        }
+        entities.into_iter().collect()
+    }
+
+    /// Unloads a scene, removing its entities. Returns false if the scene doesn't exist.
+    pub fn unload(&mut self, world: &mut World, id: SceneId) -> bool {
+        let entities = self.entities(world, id);
+        if self.scenes.remove(&id).is_none() {
+            return false;
+        }
+        for entity in entities {
+            world.remove(entity);
+        }
+        self.events.push(SceneEvent::Unloaded(id));
+        true
+    }
+
+    /// Marks the scenes whose prefabs were all spawned as loaded.
+    fn update(&mut self, world: &World) {
+        for (id, scene) in self.scenes.iter_mut().filter(|(_, scene)| !scene.loaded) {
+            let spawned = scene.roots.iter().all(|root| {
+                world.entry_ref(*root).map_or(false, |entry| {
+                    entry.get_component::<PrefabInstance>().is_ok()
+                })
+            });
+            if spawned {
+                scene.loaded = true;
+                self.events.push(SceneEvent::Loaded(*id));
+            }
+        }
+    }
+}
+
+/// Tracks the loading of the scenes, after their prefabs are spawned.
+pub(crate) fn scene_tick(world: &mut World, resources: &mut Resources) {
+    let events = match resources.get_mut::<Scenes>() {
+        Some(mut scenes) => {
+            scenes.update(world);
+            std::mem::take(&mut scenes.events)
+        }
+        None => return,
+    };
+    if let Some(mut channel) = resources.get_mut::<EventChannel<SceneEvent>>() {
+        channel.iter_write(events);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use serial_test::serial;
+
+    use super::*;
+    use crate::{DefaultLoader, ProcessingQueue};
+
+    #[serial]
+    #[test]
+    fn scenes_are_loaded_and_unloaded_as_a_unit() {
+        let loader = DefaultLoader::default();
+        let queue = ProcessingQueue::default();
+        let mut world = World::default();
+        let mut resources = Resources::default();
+        resources.insert(Scenes::default());
+        resources.insert(EventChannel::<SceneEvent>::new());
+        let mut reader = resources
+            .get_mut::<EventChannel<SceneEvent>>()
+            .unwrap()
+            .register_reader();
+
+        let handle: Handle<Prefab> = loader.load_from_data(Prefab::default(), (), &queue);
+        let (level, hud) = {
+            let mut scenes = resources.get_mut::<Scenes>().unwrap();
+            let level = scenes.spawn(&mut world, "level", vec![handle.clone()]);
+            let hud = scenes.spawn(&mut world, "hud", vec![handle]);
+            (level, hud)
+        };
+
+        // stand in for the prefab spawning, mapping the root and a child entity
+        let child = world.push((0u32,));
+        let roots: Vec<_> = {
+            let scenes = resources.get::<Scenes>().unwrap();
+            vec![
+                scenes.entities(&world, level)[0],
+                scenes.entities(&world, hud)[0],
+            ]
+        };
+        for (i, root) in roots.iter().enumerate() {
+            let mut entity_map = HashMap::default();
+            entity_map.insert(*root, *root);
+            if i == 0 {
+                entity_map.insert(child, child);
+            }
+            world.entry(*root).unwrap().add_component(PrefabInstance {
+                version: 1,
+                entity_map,
+            });
+        }
+        scene_tick(&mut world, &mut resources);
+
+        {
+            let mut scenes = resources.get_mut::<Scenes>().unwrap();
+            assert!(scenes.is_loaded(level) && scenes.is_loaded(hud));
+            assert_eq!(scenes.entities(&world, level).len(), 2);
+            assert!(scenes.unload(&mut world, level));
+            assert_eq!(scenes.find("hud"), Some(hud));
+        }
+        scene_tick(&mut world, &mut resources);
+
+        assert!(world.entry(child).is_none());
+        assert!(world.entry(roots[0]).is_none());
+        assert!(world.entry(roots[1]).is_some());
+        let events: Vec<_> = resources
+            .get::<EventChannel<SceneEvent>>()
+            .unwrap()
+            .read(&mut reader)
+            .copied()
+            .collect();
+        assert_eq!(
+            events,
+            vec![
+                SceneEvent::Loaded(level),
+                SceneEvent::Loaded(hud),
+                SceneEvent::Unloaded(level)
+            ]
+        );
+    }
+}
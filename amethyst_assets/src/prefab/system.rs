@@ -5,13 +5,14 @@ use amethyst_core::ecs::{
 };
 
 use crate::{
-    prefab::{ComponentRegistry, Prefab},
+    prefab::{ComponentRegistry, Prefab, PrefabOverrides},
     AssetStorage, Handle,
 };
 
-struct PrefabInstance {
-    version: u32,
-    entity_map: HashMap<Entity, Entity, EntityHasher>,
+/// The entities a prefab was spawned into, added to the entity holding its handle.
+pub(crate) struct PrefabInstance {
+    pub(crate) version: u32,
+    pub(crate) entity_map: HashMap<Entity, Entity, EntityHasher>,
 }
 
 /// Attaches prefabs to entities that have Handle<Prefab>
@@ -83,13 +84,20 @@ pub fn prefab_spawning_tick(world: &mut World, resources: &mut Resources) {
 
         log::debug!("Spawn for {:?}", entity);
 
-        if let Some(mut entry) = world.entry(entity) {
+        let spawned: Vec<Entity> = entity_map.values().copied().collect();
+        let overrides = if let Some(mut entry) = world.entry(entity) {
             entry.add_component(PrefabInstance {
                 version,
                 entity_map,
             });
+            entry.get_component::<PrefabOverrides>().ok().cloned()
         } else {
             log::error!("Could not update entity");
+            None
+        };
+        // applied after every spawn, the prefab overwrites the components on reloads
+        if let Some(overrides) = overrides {
+            overrides.apply(world, entity, &spawned);
         }
     }
 }
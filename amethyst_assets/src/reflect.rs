@@ -0,0 +1,364 @@
+//! Reflection of components, e.g. for debug UIs and editors.
+//!
+//! Components registered in a [ReflectRegistry] can be inspected and edited by name, without
+//! knowing their types. Their fields are read and written through their serde
+//! implementations, as RON values: struct fields by name, tuple and sequence items by index,
+//! and nested fields by paths such as `"stats.speed"` or `"color.0"`.
+
+use std::{any::type_name, collections::BTreeMap, marker::PhantomData};
+
+use amethyst_core::ecs::{storage::Component, Entity, EntityStore, World};
+use amethyst_error::{format_err, Error};
+use ron::{Map, Value};
+use serde::{de::DeserializeOwned, Serialize};
+
+/// Kind of value of a field.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FieldKind {
+    /// `true` or `false`
+    Bool,
+    /// A single character
+    Char,
+    /// An integer or floating point number
+    Number,
+    /// A string
+    String,
+    /// An optional value
+    Option,
+    /// A sequence or tuple, whose items are named by their index
+    Seq,
+    /// A struct or map, whose items are named by their key
+    Map,
+    /// The unit value
+    Unit,
+}
+
+impl FieldKind {
+    fn of(value: &Value) -> Self {
+        match value {
+            Value::Bool(_) => FieldKind::Bool,
+            Value::Char(_) => FieldKind::Char,
+            Value::Number(_) => FieldKind::Number,
+            Value::String(_) => FieldKind::String,
+            Value::Option(_) => FieldKind::Option,
+            Value::Seq(_) => FieldKind::Seq,
+            Value::Map(_) => FieldKind::Map,
+            Value::Unit => FieldKind::Unit,
+        }
+    }
+}
+
+/// A field of a reflected component.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Field {
+    /// Name of the field, or its index in a tuple
+    pub name: String,
+    /// Kind of value of the field
+    pub kind: FieldKind,
+    /// Value of the field, in RON
+    pub value: String,
+}
+
+/// Reads and writes a single component type of the registry.
+trait Reflect: Send + Sync {
+    fn type_name(&self) -> &'static str;
+    fn get(&self, world: &World, entity: Entity) -> Result<Option<Value>, Error>;
+    fn set(&self, world: &mut World, entity: Entity, value: Value) -> Result<(), Error>;
+}
+
+struct SerdeReflect<T>(PhantomData<fn() -> T>);
+
+impl<T> Reflect for SerdeReflect<T>
+where
+    T: Component + Serialize + DeserializeOwned,
+{
+    fn type_name(&self) -> &'static str {
+        type_name::<T>()
+    }
+
+    fn get(&self, world: &World, entity: Entity) -> Result<Option<Value>, Error> {
+        let entry = world
+            .entry_ref(entity)
+            .map_err(|e| format_err!("Failed reading entity {:?}: {}", entity, e))?;
+        let component = match entry.get_component::<T>() {
+            Ok(component) => component,
+            Err(_) => return Ok(None),
+        };
+        let text = ron::ser::to_string(component)
+            .map_err(|e| format_err!("Failed serializing {}: {}", type_name::<T>(), e))?;
+        ron::de::from_str(&text)
+            .map(Some)
+            .map_err(|e| format_err!("Failed reflecting {}: {}", type_name::<T>(), e))
+    }
+
+    fn set(&self, world: &mut World, entity: Entity, value: Value) -> Result<(), Error> {
+        let component: T = value
+            .into_rust()
+            .map_err(|e| format_err!("Invalid value for {}: {}", type_name::<T>(), e))?;
+        match world.entry(entity) {
+            Some(mut entry) => {
+                entry.add_component(component);
+                Ok(())
+            }
+            None => Err(format_err!("Entity {:?} doesn't exist", entity)),
+        }
+    }
+}
+
+/// Registry of the components which can be inspected and edited by name.
+///
+/// ```rust,ignore
+/// let mut registry = ReflectRegistry::new();
+/// registry.register::<Health>("health");
+///
+/// for field in registry.fields(&world, entity, "health", "")? {
+///     println!("{}: {}", field.name, field.value);
+/// }
+/// registry.set_field(&mut world, entity, "health", "max", "150")?;
+/// ```
+#[derive(Default)]
+#[allow(missing_debug_implementations)]
+pub struct ReflectRegistry {
+    components: BTreeMap<String, Box<dyn Reflect>>,
+}
+
+impl ReflectRegistry {
+    /// Creates an empty registry.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Registers the components of type `T` under the given name.
+    pub fn register<T>(&mut self, name: &str) -> &mut Self
+    where
+        T: Component + Serialize + DeserializeOwned,
+    {
+        self.components
+            .insert(name.to_string(), Box::new(SerdeReflect::<T>(PhantomData)));
+        self
+    }
+
+    /// The names of the registered components, in alphabetical order.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.components.keys().map(String::as_str)
+    }
+
+    /// The Rust type name of a registered component.
+    pub fn type_name(&self, component: &str) -> Option<&'static str> {
+        self.components
+            .get(component)
+            .map(|reflect| reflect.type_name())
+    }
+
+    /// The names of the registered components the entity has.
+    pub fn components_of(&self, world: &World, entity: Entity) -> Vec<&str> {
+        self.components
+            .iter()
+            .filter(|(_, reflect)| matches!(reflect.get(world, entity), Ok(Some(_))))
+            .map(|(name, _)| name.as_str())
+            .collect()
+    }
+
+    /// Get a component of an entity as RON.
+    pub fn get(&self, world: &World, entity: Entity, component: &str) -> Result<String, Error> {
+        to_ron(&self.value(world, entity, component)?)
+    }
+
+    /// Adds or replaces a component of an entity, from RON.
+    pub fn set(
+        &self,
+        world: &mut World,
+        entity: Entity,
+        component: &str,
+        value: &str,
+    ) -> Result<(), Error> {
+        self.reflect(component)?
+            .set(world, entity, from_ron(value)?)
+    }
+
+    /// The fields of a component of an entity, or of one of its nested fields when `path` isn't
+    /// empty.
+    pub fn fields(
+        &self,
+        world: &World,
+        entity: Entity,
+        component: &str,
+        path: &str,
+    ) -> Result<Vec<Field>, Error> {
+        let value = self.value(world, entity, component)?;
+        let items: Vec<(String, &Value)> = match lookup(&value, path)? {
+            Value::Map(map) => {
+                map.iter()
+                    .map(|(key, value)| (key_name(key), value))
+                    .collect()
+            }
+            Value::Seq(items) => {
+                items
+                    .iter()
+                    .enumerate()
+                    .map(|(i, value)| (i.to_string(), value))
+                    .collect()
+            }
+            other => vec![(String::new(), other)],
+        };
+        items
+            .into_iter()
+            .map(|(name, value)| {
+                Ok(Field {
+                    name,
+                    kind: FieldKind::of(value),
+                    value: to_ron(value)?,
+                })
+            })
+            .collect()
+    }
+
+    /// Get a field of a component of an entity as RON.
+    pub fn get_field(
+        &self,
+        world: &World,
+        entity: Entity,
+        component: &str,
+        path: &str,
+    ) -> Result<String, Error> {
+        to_ron(lookup(&self.value(world, entity, component)?, path)?)
+    }
+
+    /// Sets a field of a component of an entity, from RON. The component is rebuilt with its
+    /// serde implementation, so its invariants are checked like when it's loaded.
+    pub fn set_field(
+        &self,
+        world: &mut World,
+        entity: Entity,
+        component: &str,
+        path: &str,
+        value: &str,
+    ) -> Result<(), Error> {
+        let mut root = self.value(world, entity, component)?;
+        *lookup_mut(&mut root, path)? = from_ron(value)?;
+        self.reflect(component)?.set(world, entity, root)
+    }
+
+    fn reflect(&self, component: &str) -> Result<&dyn Reflect, Error> {
+        self.components
+            .get(component)
+            .map(|reflect| &**reflect)
+            .ok_or_else(|| format_err!("Component {:?} isn't registered", component))
+    }
+
+    fn value(&self, world: &World, entity: Entity, component: &str) -> Result<Value, Error> {
+        self.reflect(component)?
+            .get(world, entity)?
+            .ok_or_else(|| format_err!("Entity {:?} has no {:?}", entity, component))
+    }
+}
+
+fn to_ron(value: &Value) -> Result<String, Error> {
+    ron::ser::to_string(value).map_err(|e| format_err!("Failed serializing value: {}", e))
+}
+
+fn from_ron(text: &str) -> Result<Value, Error> {
+    ron::de::from_str(text).map_err(|e| format_err!("Invalid value {:?}: {}", text, e))
+}
+
+fn key_name(key: &Value) -> String {
+    match key {
+        Value::String(name) => name.clone(),
+        other => to_ron(other).unwrap_or_default(),
+    }
+}
+
+fn lookup<'a>(mut value: &'a Value, path: &str) -> Result<&'a Value, Error> {
+    for name in path.split('.').filter(|name| !name.is_empty()) {
+        value = match value {
+            Value::Map(map) => {
+                map.iter()
+                    .find(|(key, _)| key_name(key) == name)
+                    .map(|(_, v)| v)
+            }
+            Value::Seq(items) => name.parse::<usize>().ok().and_then(|i| items.get(i)),
+            Value::Option(Some(inner)) => lookup(inner, name).ok(),
+            _ => None,
+        }
+        .ok_or_else(|| format_err!("No field {:?} in path {:?}", name, path))?;
+    }
+    Ok(value)
+}
+
+fn lookup_mut<'a>(mut value: &'a mut Value, path: &str) -> Result<&'a mut Value, Error> {
+    for name in path.split('.').filter(|name| !name.is_empty()) {
+        value = match value {
+            Value::Map(map) => map_get_mut(map, name),
+            Value::Seq(items) => {
+                name.parse::<usize>()
+                    .ok()
+                    .and_then(move |i| items.get_mut(i))
+            }
+            Value::Option(Some(inner)) => lookup_mut(inner, name).ok(),
+            _ => None,
+        }
+        .ok_or_else(|| format_err!("No field {:?} in path {:?}", name, path))?;
+    }
+    Ok(value)
+}
+
+fn map_get_mut<'a>(map: &'a mut Map, name: &str) -> Option<&'a mut Value> {
+    map.iter_mut()
+        .find(|(key, _)| key_name(key) == name)
+        .map(|(_, value)| value)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Stats {
+        max: u32,
+        speed: (f32, f32),
+    }
+
+    #[test]
+    fn fields_are_read_and_written_by_path() {
+        let mut world = World::default();
+        let entity = world.push((Stats {
+            max: 100,
+            speed: (1.0, 2.0),
+        },));
+        let mut registry = ReflectRegistry::new();
+        registry.register::<Stats>("stats");
+
+        assert_eq!(registry.components_of(&world, entity), vec!["stats"]);
+        let fields = registry.fields(&world, entity, "stats", "").unwrap();
+        let names: Vec<_> = fields.iter().map(|field| field.name.as_str()).collect();
+        assert_eq!(names, vec!["max", "speed"]);
+        assert_eq!(fields[1].kind, FieldKind::Seq);
+
+        registry
+            .set_field(&mut world, entity, "stats", "speed.1", "4.5")
+            .unwrap();
+        registry
+            .set_field(&mut world, entity, "stats", "max", "150")
+            .unwrap();
+        assert_eq!(
+            registry.get_field(&world, entity, "stats", "max").unwrap(),
+            "150"
+        );
+        assert_eq!(
+            world
+                .entry_ref(entity)
+                .unwrap()
+                .get_component::<Stats>()
+                .ok(),
+            Some(&Stats {
+                max: 150,
+                speed: (1.0, 4.5),
+            })
+        );
+        assert!(registry
+            .set_field(&mut world, entity, "stats", "max", "\"many\"")
+            .is_err());
+    }
+}
@@ -0,0 +1,442 @@
+//! Snapshots of a `World` and its `Resources`, e.g. for save games.
+//!
+//! Components and resources are saved by the name they are registered with in a
+//! `SnapshotRegistry`. Entities are saved with stable ids, which the registry remembers across
+//! saves and loads, so entities referenced by components (such as `Parent`) are restored to the
+//! right entities. `Handle`s are saved as the uuids of their assets and loaded again when the
+//! snapshot is restored, which needs the `DefaultLoader` of the `LoaderBundle`.
+
+use std::{fmt, marker::PhantomData};
+
+use amethyst_core::{
+    ecs::{serialize::Canon, *},
+    random::{Random, RandomStream},
+};
+use amethyst_error::{format_err, Error};
+use bincode::Options;
+use log::warn;
+use serde::{
+    de::{self, DeserializeOwned, DeserializeSeed, IgnoredAny, MapAccess, SeqAccess, Visitor},
+    ser::{SerializeMap, SerializeStruct},
+    Deserializer, Serialize, Serializer,
+};
+
+use crate::DefaultLoader;
+
+/// Format of a saved snapshot.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SnapshotFormat {
+    /// Compact binary format, needs all saved resources to be registered when loading
+    Binary,
+    /// Human readable RON format
+    Ron,
+}
+
+/// Inserts a loaded resource, once the `Resources` aren't borrowed by the loader anymore.
+type ResourceInsert = Box<dyn FnOnce(&mut Resources)>;
+
+/// Reads and writes a single resource type of a snapshot.
+trait ResourceSnapshot: Send + Sync {
+    fn is_present(&self, resources: &Resources) -> bool;
+    fn with_value(&self, resources: &Resources, f: &mut dyn FnMut(&dyn erased_serde::Serialize));
+    fn deserialize<'de>(
+        &self,
+        deserializer: &mut dyn erased_serde::Deserializer<'de>,
+    ) -> Result<ResourceInsert, erased_serde::Error>;
+}
+
+struct SerdeResource<T>(PhantomData<fn() -> T>);
+
+impl<T> ResourceSnapshot for SerdeResource<T>
+where
+    T: Resource + Serialize + DeserializeOwned,
+{
+    fn is_present(&self, resources: &Resources) -> bool {
+        resources.contains::<T>()
+    }
+
+    fn with_value(&self, resources: &Resources, f: &mut dyn FnMut(&dyn erased_serde::Serialize)) {
+        if let Some(value) = resources.get::<T>() {
+            f(&*value);
+        }
+    }
+
+    fn deserialize<'de>(
+        &self,
+        deserializer: &mut dyn erased_serde::Deserializer<'de>,
+    ) -> Result<ResourceInsert, erased_serde::Error> {
+        let value: T = erased_serde::deserialize(deserializer)?;
+        Ok(Box::new(move |resources: &mut Resources| {
+            resources.insert(value)
+        }))
+    }
+}
+
+/// The components and resources saved in snapshots, and the ids of the saved entities.
+///
+/// Keep the same registry around while the game is running, so entities which were saved or
+/// loaded before keep their ids in the following snapshots.
+///
+/// ```rust,ignore
+/// let mut registry = SnapshotRegistry::new();
+/// registry
+///     .register_component::<Transform>("transform")
+///     .register_component::<Parent>("parent")
+///     .register_resource::<Score>("score");
+///
+/// let bytes = registry.save(&world, &resources, SnapshotFormat::Ron)?;
+/// registry.load(&bytes, SnapshotFormat::Ron, &mut world, &mut resources)?;
+/// ```
+#[derive(Default)]
+#[allow(missing_debug_implementations)]
+pub struct SnapshotRegistry {
+    components: Registry<String>,
+    resources: Vec<(String, Box<dyn ResourceSnapshot>)>,
+    canon: Canon,
+}
+
+impl SnapshotRegistry {
+    /// Creates an empty registry.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Saves the components of type `T` under the given name.
+    pub fn register_component<T>(&mut self, name: &str) -> &mut Self
+    where
+        T: Component + Serialize + DeserializeOwned,
+    {
+        self.components.register::<T>(name.to_string());
+        self
+    }
+
+    /// Saves the resource of type `T` under the given name, if it is present.
+    pub fn register_resource<T>(&mut self, name: &str) -> &mut Self
+    where
+        T: Resource + Serialize + DeserializeOwned,
+    {
+        self.resources
+            .push((name.to_string(), Box::new(SerdeResource::<T>(PhantomData))));
+        self
+    }
+
+    /// Saves the `Random` resource and the `RandomStream` components, so loaded snapshots
+    /// continue with the same random numbers.
+    pub fn register_random(&mut self) -> &mut Self {
+        self.register_resource::<Random>("random")
+            .register_component::<RandomStream>("random_stream")
+    }
+
+    /// Saves the registered components of all entities and the registered resources.
+    pub fn save(
+        &self,
+        world: &World,
+        resources: &Resources,
+        format: SnapshotFormat,
+    ) -> Result<Vec<u8>, Error> {
+        let snapshot = SnapshotSer {
+            registry: self,
+            world,
+            resources,
+        };
+        with_loader(resources, || {
+            match format {
+                SnapshotFormat::Binary => {
+                    bincode::options()
+                        .serialize(&snapshot)
+                        .map_err(|e| format_err!("Failed serializing binary snapshot: {}", e))
+                }
+                SnapshotFormat::Ron => {
+                    ron::ser::to_string_pretty(&snapshot, Default::default())
+                        .map(String::into_bytes)
+                        .map_err(|e| format_err!("Failed serializing RON snapshot: {}", e))
+                }
+            }
+        })
+    }
+
+    /// Restores a snapshot into an existing world. Entities saved from, or loaded into, this
+    /// world before are replaced, and the other entities are added. The saved resources replace
+    /// the current ones.
+    pub fn load(
+        &self,
+        bytes: &[u8],
+        format: SnapshotFormat,
+        world: &mut World,
+        resources: &mut Resources,
+    ) -> Result<(), Error> {
+        let mut inserts = Vec::new();
+        {
+            let seed = SnapshotSeed {
+                registry: self,
+                world,
+                inserts: &mut inserts,
+            };
+            with_loader(resources, || {
+                match format {
+                    SnapshotFormat::Binary => {
+                        let mut de = bincode::Deserializer::from_slice(bytes, bincode::options());
+                        seed.deserialize(&mut de)
+                            .map_err(|e| format_err!("Failed deserializing binary snapshot: {}", e))
+                    }
+                    SnapshotFormat::Ron => {
+                        ron::de::Deserializer::from_bytes(bytes)
+                            .and_then(|mut de| {
+                                seed.deserialize(&mut de)?;
+                                de.end()
+                            })
+                            .map_err(|e| format_err!("Failed deserializing RON snapshot: {}", e))
+                    }
+                }
+            })?;
+        }
+        for insert in inserts {
+            insert(resources);
+        }
+        Ok(())
+    }
+
+    /// Restores a snapshot into a new world.
+    pub fn load_new(
+        &self,
+        bytes: &[u8],
+        format: SnapshotFormat,
+        resources: &mut Resources,
+    ) -> Result<World, Error> {
+        let mut world = World::default();
+        self.load(bytes, format, &mut world, resources)?;
+        Ok(world)
+    }
+}
+
+/// Runs `f` with the context needed to save and load `Handle`s, if there is a loader.
+fn with_loader<R>(resources: &Resources, f: impl FnOnce() -> R) -> R {
+    match resources.get::<DefaultLoader>() {
+        Some(loader) => loader.with_serde_context(f),
+        None => f(),
+    }
+}
+
+struct SnapshotSer<'a> {
+    registry: &'a SnapshotRegistry,
+    world: &'a World,
+    resources: &'a Resources,
+}
+
+impl<'a> Serialize for SnapshotSer<'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let world =
+            self.world
+                .as_serializable(any(), &self.registry.components, &self.registry.canon);
+        let resources = ResourcesSer {
+            registry: self.registry,
+            resources: self.resources,
+        };
+        let mut state = serializer.serialize_struct("Snapshot", 2)?;
+        state.serialize_field("world", &world)?;
+        state.serialize_field("resources", &resources)?;
+        state.end()
+    }
+}
+
+struct ResourcesSer<'a> {
+    registry: &'a SnapshotRegistry,
+    resources: &'a Resources,
+}
+
+impl<'a> Serialize for ResourcesSer<'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let present: Vec<_> = self
+            .registry
+            .resources
+            .iter()
+            .filter(|(_, resource)| resource.is_present(self.resources))
+            .collect();
+        let mut map = serializer.serialize_map(Some(present.len()))?;
+        for (name, resource) in present {
+            let mut result = Ok(());
+            resource.with_value(self.resources, &mut |value| {
+                result = map.serialize_entry(name, value);
+            });
+            result?;
+        }
+        map.end()
+    }
+}
+
+struct SnapshotSeed<'a> {
+    registry: &'a SnapshotRegistry,
+    world: &'a mut World,
+    inserts: &'a mut Vec<ResourceInsert>,
+}
+
+impl<'de, 'a> DeserializeSeed<'de> for SnapshotSeed<'a> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_struct("Snapshot", &["world", "resources"], self)
+    }
+}
+
+impl<'de, 'a> Visitor<'de> for SnapshotSeed<'a> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str("a world snapshot")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        let SnapshotSeed {
+            registry,
+            world,
+            inserts,
+        } = self;
+        seq.next_element_seed(
+            registry
+                .components
+                .as_deserialize_into_world(world, &registry.canon),
+        )?
+        .ok_or_else(|| de::Error::invalid_length(0, &"world and resources"))?;
+        seq.next_element_seed(ResourcesSeed { registry, inserts })?
+            .ok_or_else(|| de::Error::invalid_length(1, &"world and resources"))?;
+        Ok(())
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+        let SnapshotSeed {
+            registry,
+            world,
+            inserts,
+        } = self;
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "world" => {
+                    map.next_value_seed(
+                        registry
+                            .components
+                            .as_deserialize_into_world(&mut *world, &registry.canon),
+                    )?
+                }
+                "resources" => {
+                    map.next_value_seed(ResourcesSeed {
+                        registry,
+                        inserts: &mut *inserts,
+                    })?
+                }
+                _ => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+struct ResourcesSeed<'a> {
+    registry: &'a SnapshotRegistry,
+    inserts: &'a mut Vec<ResourceInsert>,
+}
+
+impl<'de, 'a> DeserializeSeed<'de> for ResourcesSeed<'a> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de, 'a> Visitor<'de> for ResourcesSeed<'a> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str("a map of resources")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+        while let Some(name) = map.next_key::<String>()? {
+            match self.registry.resources.iter().find(|(n, _)| *n == name) {
+                Some((_, resource)) => {
+                    let insert = map.next_value_seed(ResourceSeed(resource.as_ref()))?;
+                    self.inserts.push(insert);
+                }
+                None => {
+                    warn!("Skipping unregistered resource {:?} of the snapshot", name);
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+struct ResourceSeed<'a>(&'a dyn ResourceSnapshot);
+
+impl<'de, 'a> DeserializeSeed<'de> for ResourceSeed<'a> {
+    type Value = ResourceInsert;
+
+    fn deserialize<D: Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> Result<ResourceInsert, D::Error> {
+        let mut erased = <dyn erased_serde::Deserializer<'_>>::erase(deserializer);
+        self.0.deserialize(&mut erased).map_err(de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    struct Health(u32);
+
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    struct Target(Entity);
+
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    struct Score(u64);
+
+    fn registry() -> SnapshotRegistry {
+        let mut registry = SnapshotRegistry::new();
+        registry
+            .register_component::<Health>("health")
+            .register_component::<Target>("target")
+            .register_resource::<Score>("score");
+        registry
+    }
+
+    fn round_trip(format: SnapshotFormat) {
+        let registry = registry();
+        let mut world = World::default();
+        let mut resources = Resources::default();
+        let enemy = world.push((Health(10),));
+        world.push((Health(20), Target(enemy)));
+        resources.insert(Score(42));
+
+        let bytes = registry.save(&world, &resources, format).unwrap();
+        // a new registry doesn't know the saved entity ids, like after restarting the game
+        let mut loaded_resources = Resources::default();
+        let loaded = registry()
+            .load_new(&bytes, format, &mut loaded_resources)
+            .unwrap();
+
+        assert_eq!(loaded_resources.get::<Score>().map(|s| s.0), Some(42));
+        let mut query = <(&Health, &Target)>::query();
+        let (health, target) = query.iter(&loaded).next().unwrap();
+        assert_eq!(*health, Health(20));
+        let target = loaded.entry_ref(target.0).unwrap();
+        assert_eq!(target.get_component::<Health>().ok(), Some(&Health(10)));
+    }
+
+    #[test]
+    fn round_trips_ron() {
+        round_trip(SnapshotFormat::Ron);
+    }
+
+    #[test]
+    fn round_trips_binary() {
+        round_trip(SnapshotFormat::Binary);
+    }
+}
@@ -0,0 +1,74 @@
+use amethyst_error::{format_err, Error, ResultExt};
+use web_sys::XmlHttpRequest;
+
+use crate::{error, source::Source};
+
+/// Source fetching assets over HTTP, for games running in the browser.
+///
+/// Paths are appended to the base url, so `Fetch::new("assets")` loads `texture/logo.png` from
+/// `assets/texture/logo.png` relative to the page.
+///
+/// `Source::load` is synchronous, so requests block until the response arrives. Browsers only
+/// allow this for text on the main thread, so the bytes are fetched as text with a charset
+/// mapping each byte to a character.
+#[derive(Debug)]
+pub struct Fetch {
+    url: String,
+}
+
+impl Fetch {
+    /// Creates a new source fetching from the base url.
+    pub fn new<S>(url: S) -> Self
+    where
+        S: Into<String>,
+    {
+        let mut url = url.into();
+        if !url.is_empty() && !url.ends_with('/') {
+            url.push('/');
+        }
+        Fetch { url }
+    }
+
+    fn request(&self, method: &str, path: &str) -> Result<XmlHttpRequest, Error> {
+        let url = format!("{}{}", self.url, path);
+        let send = || -> Result<XmlHttpRequest, wasm_bindgen::JsValue> {
+            let request = XmlHttpRequest::new()?;
+            request.open_with_async(method, &url, false)?;
+            request.override_mime_type("text/plain; charset=x-user-defined")?;
+            request.send()?;
+            Ok(request)
+        };
+        let request = send()
+            .map_err(|e| format_err!("Failed to fetch {:?}: {:?}", url, e))
+            .with_context(|_| error::Error::Source)?;
+        match request.status() {
+            Ok(status) if (200..300).contains(&status) => Ok(request),
+            status => {
+                Err(format_err!(
+                    "Failed to fetch {:?}: status {:?}",
+                    url,
+                    status
+                ))
+                .with_context(|_| error::Error::Source)
+            }
+        }
+    }
+}
+
+impl Source for Fetch {
+    /// Always `0`, as servers don't have to send when the asset was last modified.
+    fn modified(&self, _path: &str) -> Result<u64, Error> {
+        Ok(0)
+    }
+
+    fn load(&self, path: &str) -> Result<Vec<u8>, Error> {
+        let text = self
+            .request("GET", path)?
+            .response_text()
+            .ok()
+            .flatten()
+            .unwrap_or_default();
+        // each character holds a byte in its lower bits
+        Ok(text.chars().map(|c| c as u32 as u8).collect())
+    }
+}
@@ -3,8 +3,12 @@ use amethyst_error::Error;
 use thread_profiler::profile_scope;
 
 pub use self::dir::Directory;
+#[cfg(target_arch = "wasm32")]
+pub use self::fetch::Fetch;
 
 mod dir;
+#[cfg(target_arch = "wasm32")]
+mod fetch;
 
 /// A trait for asset sources, which provides
 /// methods for loading bytes.
@@ -1,14 +1,22 @@
 //! ECS audio bundles
 
 //use amethyst_assets::AssetProcessorSystemBundle;
-use amethyst_core::ecs::*;
+use amethyst_core::{ecs::*, shrev::EventChannel, Time};
 use amethyst_error::Error;
 
-use crate::{output::OutputWrapper, systems::*};
+use crate::{
+    capture::{VoiceCapture, VoiceCaptureSystem, VoiceFrame},
+    event::AudioEvent,
+    mixer::Mixer,
+    music::MusicController,
+    output::{OutputSelection, OutputWrapper},
+    spatial::DopplerFactor,
+    systems::*,
+};
 
 /// Audio bundle
 ///
-/// This will add an empty SelectedListener, OutputWrapper, add the audio system and the asset processor for `Source`.
+/// This will add an empty SelectedListener, OutputWrapper, a default Mixer, a MusicController, a DopplerFactor, an OutputSelection, an `EventChannel<AudioEvent>`, add the output, audio and music systems and the asset processor for `Source`.
 ///
 /// `DjSystem` must be added separately if you want to use our background music system.
 #[derive(Default, Debug)]
@@ -23,8 +31,39 @@ impl SystemBundle for AudioBundle {
     ) -> Result<(), Error> {
         resources.get_or_default::<OutputWrapper>();
         resources.get_or_default::<SelectedListener>();
+        resources.get_or_default::<Mixer>();
+        resources.get_or_default::<MusicController>();
+        resources.get_or_default::<DopplerFactor>();
+        resources.get_or_default::<OutputSelection>();
+        resources.get_or_default::<EventChannel<AudioEvent>>();
+        // Normally provided by the application, needed for time scaled buses.
+        resources.get_or_default::<Time>();
 
+        builder.add_system(OutputSystem);
         builder.add_system(AudioSystem);
+        builder.add_system(MusicSystem);
+        Ok(())
+    }
+}
+
+/// Voice capture bundle
+///
+/// This will add a `VoiceCapture`, an `EventChannel<VoiceFrame>` and the system sending the
+/// recorded frames. Recording starts with `VoiceCapture::start`.
+#[derive(Default, Debug)]
+pub struct VoiceCaptureBundle;
+
+impl SystemBundle for VoiceCaptureBundle {
+    fn load(
+        &mut self,
+        _world: &mut World,
+        resources: &mut Resources,
+        builder: &mut DispatcherBuilder,
+    ) -> Result<(), Error> {
+        resources.get_or_default::<VoiceCapture>();
+        resources.get_or_default::<EventChannel<VoiceFrame>>();
+
+        builder.add_system(VoiceCaptureSystem);
         Ok(())
     }
 }
@@ -0,0 +1,378 @@
+//! Recording from microphones, e.g. for voice chat.
+
+use std::{
+    error::Error,
+    fmt::{Display, Formatter, Result as FmtResult},
+    mem,
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use amethyst_core::{ecs::*, shrev::EventChannel};
+use cpal::{
+    traits::{DeviceTrait, EventLoopTrait, HostTrait},
+    EventLoop, StreamData, StreamId, UnknownTypeInputBuffer,
+};
+use log::error;
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+
+/// Longest stretch of captured audio kept while the `VoiceCaptureSystem` doesn't run.
+const MAX_BUFFERED: Duration = Duration::from_secs(1);
+
+/// An error occurred while starting to capture audio.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CaptureError {
+    /// There is no input device with the given name, or no default input device.
+    NoDevice(Option<String>),
+    /// The device can't be recorded from.
+    Device(String),
+}
+
+impl Display for CaptureError {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            CaptureError::NoDevice(Some(name)) => {
+                write!(formatter, "No audio input named `{}`", name)
+            }
+            CaptureError::NoDevice(None) => formatter.write_str("No default audio input"),
+            CaptureError::Device(e) => write!(formatter, "Failed to record audio: {}", e),
+        }
+    }
+}
+
+impl Error for CaptureError {}
+
+/// Mono audio recorded by the `VoiceCapture`, sent as an event by the `VoiceCaptureSystem`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VoiceFrame {
+    /// Samples between -1.0 and 1.0.
+    pub samples: Vec<f32>,
+    /// Number of samples per second.
+    pub sample_rate: u32,
+}
+
+/// Samples recorded since the `VoiceCaptureSystem` last ran.
+#[derive(Debug, Default)]
+struct Recorded {
+    samples: Vec<f32>,
+    sample_rate: u32,
+    channels: u16,
+}
+
+/// Records audio from an input device while capturing.
+///
+/// Recorded audio is mixed down to mono, converted to `sample_rate` and sent in frames of
+/// `frame_length` as `VoiceFrame` events. The defaults of 48kHz and 20ms frames can be passed
+/// to `VoiceEncoder` as they are.
+#[allow(missing_debug_implementations)]
+pub struct VoiceCapture {
+    /// Sample rate of the sent frames.
+    pub sample_rate: u32,
+    /// Duration of every sent frame.
+    pub frame_length: Duration,
+    event_loop: Option<Arc<EventLoop>>,
+    stream: Option<StreamId>,
+    recorded: Arc<Mutex<Recorded>>,
+}
+
+impl Default for VoiceCapture {
+    fn default() -> Self {
+        VoiceCapture {
+            sample_rate: 48000,
+            frame_length: Duration::from_millis(20),
+            event_loop: None,
+            stream: None,
+            recorded: Arc::default(),
+        }
+    }
+}
+
+impl VoiceCapture {
+    /// Creates a capture which doesn't record yet.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Returns the names of all input devices.
+    pub fn inputs() -> Vec<String> {
+        match cpal::default_host().input_devices() {
+            Ok(devices) => devices.filter_map(|d| d.name().ok()).collect(),
+            Err(e) => {
+                error!("Error retrieving input devices: `{}`", e);
+                Vec::new()
+            }
+        }
+    }
+
+    /// Starts recording from the default input device.
+    pub fn start(&mut self) -> Result<(), CaptureError> {
+        let device = cpal::default_host()
+            .default_input_device()
+            .ok_or(CaptureError::NoDevice(None))?;
+        self.start_device(&device)
+    }
+
+    /// Starts recording from the input device with the given name.
+    pub fn start_named(&mut self, name: &str) -> Result<(), CaptureError> {
+        let device = cpal::default_host()
+            .input_devices()
+            .map_err(|e| CaptureError::Device(e.to_string()))?
+            .find(|d| d.name().map_or(false, |n| n == name))
+            .ok_or_else(|| CaptureError::NoDevice(Some(name.to_string())))?;
+        self.start_device(&device)
+    }
+
+    fn start_device(&mut self, device: &cpal::Device) -> Result<(), CaptureError> {
+        self.stop();
+        let format = device
+            .default_input_format()
+            .map_err(|e| CaptureError::Device(e.to_string()))?;
+        let event_loop = self.event_loop();
+        let stream = event_loop
+            .build_input_stream(device, &format)
+            .map_err(|e| CaptureError::Device(e.to_string()))?;
+        {
+            let mut recorded = self.recorded.lock().expect("Recorded audio poisoned");
+            recorded.samples.clear();
+            recorded.sample_rate = format.sample_rate.0;
+            recorded.channels = format.channels.max(1);
+        }
+        event_loop
+            .play_stream(stream.clone())
+            .map_err(|e| CaptureError::Device(e.to_string()))?;
+        self.stream = Some(stream);
+        Ok(())
+    }
+
+    /// Returns the event loop, starting its thread the first time.
+    fn event_loop(&mut self) -> Arc<EventLoop> {
+        if let Some(event_loop) = &self.event_loop {
+            return event_loop.clone();
+        }
+        let event_loop = Arc::new(cpal::default_host().event_loop());
+        let recorded = self.recorded.clone();
+        let runner = event_loop.clone();
+        thread::Builder::new()
+            .name("amethyst-voice-capture".into())
+            .spawn(move || {
+                runner.run(move |_, data| {
+                    match data {
+                        Ok(StreamData::Input { buffer }) => record(&recorded, buffer),
+                        Ok(_) => {}
+                        Err(e) => error!("Error while recording audio: {}", e),
+                    }
+                })
+            })
+            .expect("Failed to spawn the audio capture thread");
+        self.event_loop = Some(event_loop.clone());
+        event_loop
+    }
+
+    /// Stops recording.
+    pub fn stop(&mut self) {
+        if let (Some(event_loop), Some(stream)) = (&self.event_loop, self.stream.take()) {
+            event_loop.destroy_stream(stream);
+        }
+    }
+
+    /// Returns true while recording.
+    pub fn is_capturing(&self) -> bool {
+        self.stream.is_some()
+    }
+
+    fn take_recorded(&self) -> (Vec<f32>, u32) {
+        let mut recorded = self.recorded.lock().expect("Recorded audio poisoned");
+        (mem::take(&mut recorded.samples), recorded.sample_rate)
+    }
+}
+
+/// Mixes a buffer from the capture thread down to mono and stores it.
+fn record(recorded: &Mutex<Recorded>, buffer: UnknownTypeInputBuffer<'_>) {
+    let mut recorded = recorded.lock().expect("Recorded audio poisoned");
+    let channels = usize::from(recorded.channels.max(1));
+    let max = (MAX_BUFFERED.as_secs_f32() * recorded.sample_rate as f32) as usize;
+    let mut push = |frame: &mut dyn Iterator<Item = f32>| {
+        recorded.samples.push(frame.sum::<f32>() / channels as f32);
+    };
+    match buffer {
+        UnknownTypeInputBuffer::U16(buffer) => {
+            for frame in buffer.chunks(channels) {
+                push(&mut frame.iter().map(|&s| (f32::from(s) - 32768.0) / 32768.0));
+            }
+        }
+        UnknownTypeInputBuffer::I16(buffer) => {
+            for frame in buffer.chunks(channels) {
+                push(&mut frame.iter().map(|&s| f32::from(s) / 32768.0));
+            }
+        }
+        UnknownTypeInputBuffer::F32(buffer) => {
+            for frame in buffer.chunks(channels) {
+                push(&mut frame.iter().copied());
+            }
+        }
+    }
+    if recorded.samples.len() > max {
+        let excess = recorded.samples.len() - max;
+        recorded.samples.drain(..excess);
+    }
+}
+
+/// Converts mono audio between sample rates by linear interpolation, across several buffers.
+#[derive(Debug, Default)]
+struct Resampler {
+    /// Last input sample of the previous buffer.
+    previous: f32,
+    /// Position of the next output sample, relative to `previous`.
+    position: f64,
+}
+
+impl Resampler {
+    fn process(&mut self, input: &[f32], from: u32, to: u32, output: &mut Vec<f32>) {
+        if from == to || from == 0 {
+            output.extend_from_slice(input);
+            return;
+        }
+        let step = f64::from(from) / f64::from(to);
+        let previous = self.previous;
+        let sample = |index: usize| {
+            if index == 0 {
+                previous
+            } else {
+                input[index - 1]
+            }
+        };
+        while self.position < input.len() as f64 {
+            let index = self.position.floor() as usize;
+            let fraction = (self.position - index as f64) as f32;
+            let current = sample(index);
+            let next = input[index];
+            output.push(current + (next - current) * fraction);
+            self.position += step;
+        }
+        self.position -= input.len() as f64;
+        if let Some(&last) = input.last() {
+            self.previous = last;
+        }
+    }
+}
+
+/// Sends the audio recorded by the `VoiceCapture` as `VoiceFrame` events.
+#[derive(Debug)]
+pub struct VoiceCaptureSystem;
+
+impl System for VoiceCaptureSystem {
+    fn build(self) -> Box<dyn ParallelRunnable> {
+        let mut resampler = Resampler::default();
+        let mut pending = Vec::new();
+
+        Box::new(
+            SystemBuilder::new("VoiceCaptureSystem")
+                .read_resource::<VoiceCapture>()
+                .write_resource::<EventChannel<VoiceFrame>>()
+                .build(move |_commands, _world, (capture, frames), _queries| {
+                    #[cfg(feature = "profiler")]
+                    profile_scope!("voice_capture_system");
+
+                    if !capture.is_capturing() {
+                        pending.clear();
+                        return;
+                    }
+                    let (samples, rate) = capture.take_recorded();
+                    resampler.process(&samples, rate, capture.sample_rate, &mut pending);
+
+                    let frame_len = ((capture.frame_length.as_secs_f64()
+                        * f64::from(capture.sample_rate))
+                        as usize)
+                        .max(1);
+                    while pending.len() >= frame_len {
+                        let rest = pending.split_off(frame_len);
+                        frames.single_write(VoiceFrame {
+                            samples: mem::replace(&mut pending, rest),
+                            sample_rate: capture.sample_rate,
+                        });
+                    }
+                }),
+        )
+    }
+}
+
+/// Compresses `VoiceFrame`s with the Opus codec, to send them over the network.
+#[cfg(feature = "opus")]
+#[allow(missing_debug_implementations)]
+pub struct VoiceEncoder {
+    encoder: opus::Encoder,
+    buffer: Vec<u8>,
+}
+
+#[cfg(feature = "opus")]
+impl VoiceEncoder {
+    /// Creates an encoder for frames with the given sample rate, which must be 8, 12, 16, 24
+    /// or 48kHz.
+    pub fn new(sample_rate: u32) -> Result<Self, opus::Error> {
+        Ok(VoiceEncoder {
+            encoder: opus::Encoder::new(
+                sample_rate,
+                opus::Channels::Mono,
+                opus::Application::Voip,
+            )?,
+            buffer: vec![0; 4000],
+        })
+    }
+
+    /// Encodes a frame which is 2.5, 5, 10, 20, 40 or 60ms long.
+    pub fn encode(&mut self, frame: &VoiceFrame) -> Result<Vec<u8>, opus::Error> {
+        let len = self
+            .encoder
+            .encode_float(&frame.samples, &mut self.buffer)?;
+        Ok(self.buffer[..len].to_vec())
+    }
+}
+
+/// Restores `VoiceFrame`s compressed by a `VoiceEncoder`.
+#[cfg(feature = "opus")]
+#[allow(missing_debug_implementations)]
+pub struct VoiceDecoder {
+    decoder: opus::Decoder,
+    sample_rate: u32,
+}
+
+#[cfg(feature = "opus")]
+impl VoiceDecoder {
+    /// Creates a decoder for packets of the given sample rate.
+    pub fn new(sample_rate: u32) -> Result<Self, opus::Error> {
+        Ok(VoiceDecoder {
+            decoder: opus::Decoder::new(sample_rate, opus::Channels::Mono)?,
+            sample_rate,
+        })
+    }
+
+    /// Decodes a packet, or conceals a lost packet if `packet` is empty.
+    pub fn decode(&mut self, packet: &[u8]) -> Result<VoiceFrame, opus::Error> {
+        // Enough for the longest frame Opus supports.
+        let mut samples = vec![0.0; self.sample_rate as usize * 120 / 1000];
+        let len = self.decoder.decode_float(packet, &mut samples, false)?;
+        samples.truncate(len);
+        Ok(VoiceFrame {
+            samples,
+            sample_rate: self.sample_rate,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resampling_keeps_duration_across_buffers() {
+        let mut resampler = Resampler::default();
+        let mut output = Vec::new();
+        for _ in 0..10 {
+            resampler.process(&[0.5; 441], 44100, 48000, &mut output);
+        }
+        assert!((output.len() as i64 - 4800).abs() <= 1);
+        assert!(output[10..].iter().all(|s| (s - 0.5).abs() < 1e-6));
+    }
+}
@@ -1,21 +1,63 @@
 use std::{
-    io::Cursor,
-    sync::{atomic::AtomicBool, Arc},
+    borrow::Cow,
+    sync::{atomic::AtomicBool, Arc, Mutex},
 };
 
-use rodio::{Decoder, SpatialSink};
+use amethyst_core::math::Point3;
+use rodio::{Sink, Source as RSource};
 use smallvec::SmallVec;
 
-use crate::{source::Source, DecoderError};
+use crate::{
+    effects::Effect,
+    event::{Marker, SoundId, TrackedSound},
+    mixer::SFX_BUS,
+    occlusion::{Occlusion, OcclusionState},
+    source::Source,
+    spatial::{Rolloff, SpatialParams},
+    stream::AudioStream,
+    DecoderError,
+};
+
+/// A sound waiting to be played by an emitter.
+pub(crate) type QueuedSound = TrackedSound<Box<dyn RSource<Item = i16> + Send + Sync>>;
+
+/// A sound played by an emitter, together with the positions it is rendered from.
+pub(crate) struct EmitterSink {
+    pub(crate) sink: Sink,
+    pub(crate) params: Arc<Mutex<SpatialParams>>,
+    pub(crate) ended: Arc<AtomicBool>,
+}
 
 /// An audio source, add this component to anything that emits sound.
 /// TODO: This should get a proper Debug impl parsing the sinks and sound queue
 #[allow(missing_debug_implementations)]
-#[derive(Default)]
 pub struct AudioEmitter {
-    pub(crate) sinks: SmallVec<[(SpatialSink, Arc<AtomicBool>); 4]>,
-    pub(crate) sound_queue: SmallVec<[Decoder<Cursor<Source>>; 4]>,
+    pub(crate) sinks: SmallVec<[EmitterSink; 4]>,
+    pub(crate) sound_queue: SmallVec<[QueuedSound; 4]>,
     pub(crate) picker: Option<Box<dyn FnMut(&mut AudioEmitter) -> bool + Send + Sync>>,
+    pub(crate) rolloff: Rolloff,
+    pub(crate) bus: Cow<'static, str>,
+    pub(crate) effects: SmallVec<[Effect; 2]>,
+    pub(crate) occlusion: Occlusion,
+    pub(crate) occlusion_state: Arc<OcclusionState>,
+    /// Position in the last frame, for emitters without an `AudioVelocity`.
+    pub(crate) last_position: Option<Point3<f32>>,
+}
+
+impl Default for AudioEmitter {
+    fn default() -> Self {
+        AudioEmitter {
+            sinks: SmallVec::new(),
+            sound_queue: SmallVec::new(),
+            picker: None,
+            rolloff: Rolloff::default(),
+            bus: SFX_BUS.into(),
+            effects: SmallVec::new(),
+            occlusion: Occlusion::default(),
+            occlusion_state: Arc::default(),
+            last_position: None,
+        }
+    }
 }
 
 impl AudioEmitter {
@@ -28,9 +70,88 @@ impl AudioEmitter {
 
     /// Plays an audio source from this emitter.
     pub fn play(&mut self, source: &Source) -> Result<(), DecoderError> {
-        self.sound_queue
-            .push(Decoder::new(Cursor::new(source.clone())).map_err(|_| DecoderError)?);
-        Ok(())
+        self.play_with_markers(source, Vec::new()).map(|_| ())
+    }
+
+    /// Plays an audio source from this emitter, sending an `AudioEvent::Marker` whenever
+    /// playback passes one of the markers.
+    pub fn play_with_markers(
+        &mut self,
+        source: &Source,
+        markers: Vec<Marker>,
+    ) -> Result<SoundId, DecoderError> {
+        let samples = source.samples()?;
+        Ok(self.queue(TrackedSound::new(samples, markers, None)))
+    }
+
+    /// Plays a stream from this emitter, see `AudioStream`.
+    pub fn play_stream(&mut self, stream: AudioStream) -> SoundId {
+        self.play_stream_with_markers(stream, Vec::new())
+    }
+
+    /// Plays a stream from this emitter, sending an `AudioEvent::Marker` whenever
+    /// playback passes one of the markers, including after the stream looped.
+    pub fn play_stream_with_markers(
+        &mut self,
+        stream: AudioStream,
+        markers: Vec<Marker>,
+    ) -> SoundId {
+        let control = stream.control();
+        self.queue(TrackedSound::new(Box::new(stream), markers, Some(control)))
+    }
+
+    fn queue(&mut self, sound: QueuedSound) -> SoundId {
+        let id = sound.id;
+        self.sound_queue.push(sound);
+        id
+    }
+
+    /// Returns the distance attenuation of this emitter.
+    pub fn rolloff(&self) -> &Rolloff {
+        &self.rolloff
+    }
+
+    /// Changes the distance attenuation of this emitter, which also applies to sounds
+    /// that are already playing.
+    pub fn set_rolloff(&mut self, rolloff: Rolloff) {
+        self.rolloff = rolloff;
+    }
+
+    /// Returns the name of the mixer bus this emitter plays into, `SFX_BUS` by default.
+    pub fn bus(&self) -> &str {
+        &self.bus
+    }
+
+    /// Routes sounds started from now on into the mixer bus with the given name.
+    pub fn set_bus<N: Into<Cow<'static, str>>>(&mut self, bus: N) {
+        self.bus = bus.into();
+    }
+
+    /// Returns the effects applied to the sounds of this emitter, in order.
+    pub fn effects(&self) -> &[Effect] {
+        &self.effects
+    }
+
+    /// Appends an effect which is applied to sounds started from now on, before they reach
+    /// the mixer bus.
+    pub fn push_effect(&mut self, effect: Effect) {
+        self.effects.push(effect);
+    }
+
+    /// Removes all effects of this emitter.
+    pub fn clear_effects(&mut self) {
+        self.effects.clear();
+    }
+
+    /// Returns how this emitter reacts to being occluded, see `OcclusionSystem`.
+    pub fn occlusion(&self) -> &Occlusion {
+        &self.occlusion
+    }
+
+    /// Changes how this emitter reacts to being occluded, which also applies to sounds
+    /// that are already playing.
+    pub fn set_occlusion(&mut self, occlusion: Occlusion) {
+        self.occlusion = occlusion;
     }
 
     /// An emitter's picker will be called by the AudioSystem whenever the emitter runs out of
@@ -70,7 +191,7 @@ mod tests {
         f.read_to_end(&mut buffer).unwrap();
 
         // Create a Source and AudioEmitter from those bytes
-        let src = Source { bytes: buffer };
+        let src = Source::new(buffer);
         let mut emitter = AudioEmitter::default();
 
         // Call play
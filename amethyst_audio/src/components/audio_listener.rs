@@ -1,5 +1,7 @@
 use amethyst_core::math::Point3;
 
+use crate::spatial::SpatialMode;
+
 /// An audio listener, add this component to the local player character.
 #[derive(Clone, Debug)]
 pub struct AudioListener {
@@ -7,6 +9,14 @@ pub struct AudioListener {
     pub left_ear: Point3<f32>,
     /// Position of the right ear relative to the global transform on this entity.
     pub right_ear: Point3<f32>,
+    /// How sounds are placed between the ears, set it to `SpatialMode::Interaural` for
+    /// headphones.
+    pub mode: SpatialMode,
+    /// Radius of the head used for interaural output, in world units.
+    ///
+    /// This only affects the delay between the ears, the ear positions decide the direction
+    /// the listener is facing.
+    pub head_radius: f32,
 }
 
 impl Default for AudioListener {
@@ -14,6 +24,8 @@ impl Default for AudioListener {
         AudioListener {
             left_ear: Point3::new(-1.0, 0.0, 0.0),
             right_ear: Point3::new(1.0, 0.0, 0.0),
+            mode: SpatialMode::default(),
+            head_radius: 0.0875,
         }
     }
 }
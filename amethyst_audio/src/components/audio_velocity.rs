@@ -0,0 +1,11 @@
+use amethyst_core::math::Vector3;
+use serde::{Deserialize, Serialize};
+
+/// Velocity of an `AudioEmitter` or `AudioListener` in world units per second, used for the
+/// doppler effect.
+///
+/// Entities without this component get their velocity from how far their `Transform` moved
+/// since the last frame. Add it to entities which teleport, or whose velocity is known
+/// anyway, e.g. from a physics engine.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct AudioVelocity(pub Vector3<f32>);
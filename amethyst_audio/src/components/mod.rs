@@ -1,6 +1,12 @@
 //! `amethyst` audio ecs components
 
-pub use self::{audio_emitter::AudioEmitter, audio_listener::AudioListener};
+pub(crate) use self::audio_emitter::EmitterSink;
+pub use self::{
+    audio_emitter::AudioEmitter, audio_listener::AudioListener, audio_velocity::AudioVelocity,
+    reverb_zone::ReverbZone,
+};
 
 mod audio_emitter;
 mod audio_listener;
+mod audio_velocity;
+mod reverb_zone;
@@ -0,0 +1,34 @@
+use serde::{Deserialize, Serialize};
+
+use crate::effects::Reverb;
+
+/// A region in which sounds reverberate, like a cave or a hall.
+///
+/// The zone is a sphere around the `Transform` of its entity. While the listener is inside,
+/// sounds from all emitters get the reverb of the zone, which fades out over `falloff` beyond
+/// the radius. When the listener is in several zones, the one it is deepest in is used.
+///
+/// Sounds started while there is no reverb zone at all are never reverberated.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ReverbZone {
+    /// Radius in which the reverb is applied fully.
+    pub radius: f32,
+    /// Distance beyond the radius over which the reverb fades out.
+    pub falloff: f32,
+    /// The reverb applied inside the zone.
+    pub reverb: Reverb,
+}
+
+impl ReverbZone {
+    /// Returns how strongly the zone applies to a listener at the given distance from its
+    /// center, between 0.0 and 1.0.
+    pub fn weight(&self, distance: f32) -> f32 {
+        if distance <= self.radius {
+            1.0
+        } else if self.falloff > 0.0 {
+            (1.0 - (distance - self.radius) / self.falloff).max(0.0)
+        } else {
+            0.0
+        }
+    }
+}
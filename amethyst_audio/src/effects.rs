@@ -0,0 +1,519 @@
+//! Built-in audio effects for emitters and mixer buses.
+
+use std::{
+    f32::consts::PI,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use rodio::Source as RSource;
+use serde::{Deserialize, Serialize};
+
+use crate::mixer::{BusEffect, MixSource};
+
+/// Longest tail an effect keeps playing after its input ended.
+const MAX_TAIL_SECS: f32 = 10.0;
+
+/// Number of frames between two reads of the parameters of a reverb zone.
+const ZONE_UPDATE_INTERVAL: u32 = 64;
+
+/// Comb filter lengths of the reverb at 44.1kHz, from Freeverb.
+const COMB_LENGTHS: [usize; 4] = [1116, 1188, 1277, 1356];
+/// All-pass filter lengths of the reverb at 44.1kHz, from Freeverb.
+const ALLPASS_LENGTHS: [usize; 2] = [556, 441];
+/// Extra delay of every channel after the first, which decorrelates the channels.
+const STEREO_SPREAD: usize = 23;
+/// Level of the signal fed into the comb filters.
+const REVERB_INPUT_GAIN: f32 = 0.015;
+/// Level of the summed comb filters in the output.
+const REVERB_WET_GAIN: f32 = 6.0;
+
+/// Settings of a reverb, used by `Effect::Reverb` and `ReverbZone`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Reverb {
+    /// Size of the simulated room from 0.0 (small) to 1.0 (huge), decides how long the tail is.
+    pub room_size: f32,
+    /// How quickly high frequencies die out from 0.0 (bright) to 1.0 (dull).
+    pub damping: f32,
+    /// Share of the reverberated signal in the output, 0.0 is dry and 1.0 is fully wet.
+    pub mix: f32,
+}
+
+impl Default for Reverb {
+    fn default() -> Self {
+        Reverb {
+            room_size: 0.5,
+            damping: 0.5,
+            mix: 0.3,
+        }
+    }
+}
+
+impl Reverb {
+    fn feedback(&self) -> f32 {
+        0.7 + 0.28 * self.room_size.max(0.0).min(1.0)
+    }
+}
+
+/// An effect which can be applied to the sounds of an emitter or a mixer bus.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Effect {
+    /// Removes frequencies above the cutoff, e.g. for sounds heard underwater or through walls.
+    LowPass {
+        /// Cutoff frequency in Hz.
+        cutoff: f32,
+    },
+    /// Repeats the sound after a delay, e.g. for echoes in a canyon.
+    Delay {
+        /// Time between repetitions in seconds.
+        time: f32,
+        /// Level of every repetition relative to the previous one, below 1.0.
+        feedback: f32,
+        /// Level of the repetitions in the output.
+        mix: f32,
+    },
+    /// Simulates the reflections of a room.
+    Reverb(Reverb),
+}
+
+impl Effect {
+    /// Applies this effect to a sound.
+    pub fn apply(&self, source: MixSource) -> MixSource {
+        let channels = source.channels().max(1) as usize;
+        let rate = source.sample_rate();
+        match *self {
+            Effect::LowPass { cutoff } => {
+                Box::new(EffectSource::new(
+                    source,
+                    LowPass::new(cutoff, rate, channels),
+                ))
+            }
+            Effect::Delay {
+                time,
+                feedback,
+                mix,
+            } => {
+                Box::new(EffectSource::new(
+                    source,
+                    Echo::new(time, feedback, mix, rate, channels),
+                ))
+            }
+            Effect::Reverb(reverb) => {
+                Box::new(EffectSource::new(
+                    source,
+                    Reverberator::new(reverb, None, rate, channels),
+                ))
+            }
+        }
+    }
+}
+
+impl From<Effect> for BusEffect {
+    fn from(effect: Effect) -> Self {
+        Arc::new(move |source: MixSource| effect.apply(source))
+    }
+}
+
+/// Reverb of the reverb zones around the listener, shared with every playing sound.
+#[derive(Debug, Default)]
+pub(crate) struct ZoneReverb {
+    room_size: AtomicU32,
+    damping: AtomicU32,
+    mix: AtomicU32,
+}
+
+impl ZoneReverb {
+    pub(crate) fn set(&self, reverb: Reverb) {
+        self.room_size
+            .store(reverb.room_size.to_bits(), Ordering::Relaxed);
+        self.damping
+            .store(reverb.damping.to_bits(), Ordering::Relaxed);
+        self.mix.store(reverb.mix.to_bits(), Ordering::Relaxed);
+    }
+
+    fn get(&self) -> Reverb {
+        let load = |value: &AtomicU32| f32::from_bits(value.load(Ordering::Relaxed));
+        Reverb {
+            room_size: load(&self.room_size),
+            damping: load(&self.damping),
+            mix: load(&self.mix),
+        }
+    }
+
+    /// Runs a sound through the reverb of the zones.
+    pub(crate) fn apply(self: &Arc<Self>, source: MixSource) -> MixSource {
+        let channels = source.channels().max(1) as usize;
+        let rate = source.sample_rate();
+        let reverb = self.get();
+        Box::new(EffectSource::new(
+            source,
+            Reverberator::new(reverb, Some(self.clone()), rate, channels),
+        ))
+    }
+}
+
+/// Per sample processing of an effect on interleaved samples.
+pub(crate) trait Processor: Send {
+    fn process(&mut self, channel: usize, input: f32) -> f32;
+
+    /// Number of frames the effect keeps producing sound after its input ended.
+    fn tail_frames(&self) -> u64;
+}
+
+/// Applies a `Processor` to a source, and plays its tail after the source ended.
+///
+/// The format of the source is read once, sounds which change their format while
+/// playing are processed with the initial format.
+pub(crate) struct EffectSource<P> {
+    input: MixSource,
+    processor: P,
+    channels: usize,
+    sample_rate: u32,
+    channel: usize,
+    tail: Option<u64>,
+}
+
+impl<P: Processor> EffectSource<P> {
+    pub(crate) fn new(input: MixSource, processor: P) -> Self {
+        EffectSource {
+            channels: input.channels().max(1) as usize,
+            sample_rate: input.sample_rate(),
+            input,
+            processor,
+            channel: 0,
+            tail: None,
+        }
+    }
+}
+
+impl<P: Processor> Iterator for EffectSource<P> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let input = match self.tail {
+            None => {
+                match self.input.next() {
+                    Some(sample) => sample,
+                    None => {
+                        self.tail = Some(self.processor.tail_frames() * self.channels as u64);
+                        return self.next();
+                    }
+                }
+            }
+            Some(0) => return None,
+            Some(ref mut remaining) => {
+                *remaining -= 1;
+                0.0
+            }
+        };
+        let output = self.processor.process(self.channel, input);
+        self.channel = (self.channel + 1) % self.channels;
+        Some(output)
+    }
+}
+
+impl<P: Processor> RSource for EffectSource<P> {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        self.channels as u16
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
+
+/// Second order low-pass filter.
+pub(crate) struct LowPass {
+    b: [f32; 3],
+    a: [f32; 2],
+    /// Last two inputs and outputs of every channel.
+    state: Vec<[f32; 4]>,
+}
+
+impl LowPass {
+    pub(crate) fn new(cutoff: f32, rate: u32, channels: usize) -> Self {
+        let mut low_pass = LowPass {
+            b: [1.0, 0.0, 0.0],
+            a: [0.0, 0.0],
+            state: vec![[0.0; 4]; channels],
+        };
+        low_pass.set_cutoff(cutoff, rate);
+        low_pass
+    }
+
+    /// Changes the cutoff frequency while keeping the filter state.
+    pub(crate) fn set_cutoff(&mut self, cutoff: f32, rate: u32) {
+        let rate = rate.max(1) as f32;
+        let cutoff = cutoff.max(10.0).min(0.49 * rate);
+        let w0 = 2.0 * PI * cutoff / rate;
+        let alpha = w0.sin() / (2.0 * std::f32::consts::FRAC_1_SQRT_2);
+        let cos = w0.cos();
+        let a0 = 1.0 + alpha;
+        self.b = [
+            (1.0 - cos) / 2.0 / a0,
+            (1.0 - cos) / a0,
+            (1.0 - cos) / 2.0 / a0,
+        ];
+        self.a = [-2.0 * cos / a0, (1.0 - alpha) / a0];
+    }
+}
+
+impl Processor for LowPass {
+    fn process(&mut self, channel: usize, input: f32) -> f32 {
+        let [x1, x2, y1, y2] = self.state[channel];
+        let output =
+            self.b[0] * input + self.b[1] * x1 + self.b[2] * x2 - self.a[0] * y1 - self.a[1] * y2;
+        self.state[channel] = [input, x1, output, y1];
+        output
+    }
+
+    fn tail_frames(&self) -> u64 {
+        0
+    }
+}
+
+/// Feedback delay line.
+struct Echo {
+    lines: Vec<Vec<f32>>,
+    index: usize,
+    feedback: f32,
+    mix: f32,
+    rate: u32,
+}
+
+impl Echo {
+    fn new(time: f32, feedback: f32, mix: f32, rate: u32, channels: usize) -> Self {
+        let frames = ((time.max(0.0) * rate as f32) as usize).max(1);
+        Echo {
+            lines: vec![vec![0.0; frames]; channels],
+            index: 0,
+            feedback: feedback.max(0.0).min(0.99),
+            mix,
+            rate,
+        }
+    }
+}
+
+impl Processor for Echo {
+    fn process(&mut self, channel: usize, input: f32) -> f32 {
+        let line = &mut self.lines[channel];
+        let delayed = line[self.index];
+        line[self.index] = input + delayed * self.feedback;
+        if channel + 1 == self.lines.len() {
+            self.index = (self.index + 1) % line.len();
+        }
+        input + delayed * self.mix
+    }
+
+    fn tail_frames(&self) -> u64 {
+        let frames = self.lines[0].len() as f32;
+        // Repetitions needed for the echo to drop by 60dB.
+        let repetitions = if self.feedback > 0.0 {
+            (0.001f32.ln() / self.feedback.ln()).ceil()
+        } else {
+            1.0
+        };
+        (frames * repetitions).min(MAX_TAIL_SECS * self.rate as f32) as u64
+    }
+}
+
+/// Comb filter with a low-pass in its feedback path.
+struct Comb {
+    buffer: Vec<f32>,
+    index: usize,
+    filtered: f32,
+}
+
+impl Comb {
+    fn process(&mut self, input: f32, feedback: f32, damping: f32) -> f32 {
+        let output = self.buffer[self.index];
+        self.filtered = output * (1.0 - damping) + self.filtered * damping;
+        self.buffer[self.index] = input + self.filtered * feedback;
+        self.index = (self.index + 1) % self.buffer.len();
+        output
+    }
+}
+
+struct AllPass {
+    buffer: Vec<f32>,
+    index: usize,
+}
+
+impl AllPass {
+    fn process(&mut self, input: f32) -> f32 {
+        let delayed = self.buffer[self.index];
+        self.buffer[self.index] = input + delayed * 0.5;
+        self.index = (self.index + 1) % self.buffer.len();
+        delayed - input
+    }
+}
+
+/// Freeverb style reverb with one set of filters per channel.
+struct Reverberator {
+    reverb: Reverb,
+    zone: Option<Arc<ZoneReverb>>,
+    frames_until_update: u32,
+    combs: Vec<[Comb; 4]>,
+    allpasses: Vec<[AllPass; 2]>,
+    rate: u32,
+}
+
+impl Reverberator {
+    fn new(reverb: Reverb, zone: Option<Arc<ZoneReverb>>, rate: u32, channels: usize) -> Self {
+        let scale = rate as f32 / 44100.0;
+        let length = |base: usize, channel: usize| {
+            (((base + STEREO_SPREAD * channel) as f32 * scale) as usize).max(1)
+        };
+        let comb = |base, channel| {
+            Comb {
+                buffer: vec![0.0; length(base, channel)],
+                index: 0,
+                filtered: 0.0,
+            }
+        };
+        let allpass = |base, channel| {
+            AllPass {
+                buffer: vec![0.0; length(base, channel)],
+                index: 0,
+            }
+        };
+        Reverberator {
+            reverb,
+            zone,
+            frames_until_update: ZONE_UPDATE_INTERVAL,
+            combs: (0..channels)
+                .map(|c| {
+                    [
+                        comb(COMB_LENGTHS[0], c),
+                        comb(COMB_LENGTHS[1], c),
+                        comb(COMB_LENGTHS[2], c),
+                        comb(COMB_LENGTHS[3], c),
+                    ]
+                })
+                .collect(),
+            allpasses: (0..channels)
+                .map(|c| {
+                    [
+                        allpass(ALLPASS_LENGTHS[0], c),
+                        allpass(ALLPASS_LENGTHS[1], c),
+                    ]
+                })
+                .collect(),
+            rate,
+        }
+    }
+}
+
+impl Processor for Reverberator {
+    fn process(&mut self, channel: usize, input: f32) -> f32 {
+        if channel == 0 {
+            if let Some(zone) = &self.zone {
+                self.frames_until_update -= 1;
+                if self.frames_until_update == 0 {
+                    self.reverb = zone.get();
+                    self.frames_until_update = ZONE_UPDATE_INTERVAL;
+                }
+            }
+        }
+        if self.reverb.mix <= 0.0 {
+            return input;
+        }
+
+        let feedback = self.reverb.feedback();
+        let damping = self.reverb.damping.max(0.0).min(1.0) * 0.4;
+        let scaled = input * REVERB_INPUT_GAIN;
+        let mut wet: f32 = self.combs[channel]
+            .iter_mut()
+            .map(|comb| comb.process(scaled, feedback, damping))
+            .sum();
+        for allpass in self.allpasses[channel].iter_mut() {
+            wet = allpass.process(wet);
+        }
+        let mix = self.reverb.mix.min(1.0);
+        input * (1.0 - mix) + wet * REVERB_WET_GAIN * mix
+    }
+
+    fn tail_frames(&self) -> u64 {
+        if self.reverb.mix <= 0.0 {
+            return 0;
+        }
+        // Time for the longest comb filter to decay by 60dB.
+        let longest = *COMB_LENGTHS.iter().max().unwrap() as f32 / 44100.0;
+        let seconds = -3.0 * longest / self.reverb.feedback().log10();
+        (seconds.min(MAX_TAIL_SECS) * self.rate as f32) as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rodio::buffer::SamplesBuffer;
+
+    use super::*;
+
+    fn run(effect: Effect, samples: Vec<f32>) -> Vec<f32> {
+        effect
+            .apply(Box::new(SamplesBuffer::new(1, 44100, samples)))
+            .collect()
+    }
+
+    #[test]
+    fn low_pass_keeps_low_frequencies() {
+        let cutoff = Effect::LowPass { cutoff: 500.0 };
+        let constant = run(cutoff, vec![1.0; 4410]);
+        assert!((constant.last().unwrap() - 1.0).abs() < 1e-3);
+
+        let alternating = (0..4410)
+            .map(|i| if i % 2 == 0 { 1.0 } else { -1.0 })
+            .collect();
+        let filtered = run(cutoff, alternating);
+        assert!(filtered[100..].iter().all(|s| s.abs() < 0.01));
+    }
+
+    #[test]
+    fn delay_repeats_after_its_time() {
+        let mut impulse = vec![0.0; 10];
+        impulse[0] = 1.0;
+        let out = run(
+            Effect::Delay {
+                time: 0.01,
+                feedback: 0.5,
+                mix: 0.5,
+            },
+            impulse,
+        );
+        assert!((out[0] - 1.0).abs() < 1e-6);
+        assert!((out[441] - 0.5).abs() < 1e-6);
+        assert!((out[882] - 0.25).abs() < 1e-6);
+        assert_eq!(out.len(), 10 + 441 * 10);
+    }
+
+    #[test]
+    fn reverb_has_a_tail() {
+        let mut impulse = vec![0.0; 10];
+        impulse[0] = 1.0;
+        let out = run(Effect::Reverb(Reverb::default()), impulse);
+        assert!(out.len() > 44100 / 2);
+        assert!(out[2000..].iter().any(|s| s.abs() > 1e-4));
+
+        let dry = run(
+            Effect::Reverb(Reverb {
+                mix: 0.0,
+                ..Default::default()
+            }),
+            vec![1.0; 10],
+        );
+        assert_eq!(dry.len(), 10);
+    }
+}
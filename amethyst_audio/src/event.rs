@@ -0,0 +1,308 @@
+//! Events sent while sounds play.
+
+use std::{
+    borrow::Cow,
+    mem,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use amethyst_core::ecs::Entity;
+use rodio::{Sample, Source as RSource};
+
+use crate::stream::StreamControl;
+
+/// Identifies a single playback of a sound.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SoundId(u64);
+
+impl SoundId {
+    pub(crate) fn next() -> Self {
+        static NEXT: AtomicU64 = AtomicU64::new(0);
+        SoundId(NEXT.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+/// A named point in time of a sound, which sends an `AudioEvent::Marker` when playback passes it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Marker {
+    /// Name sent with the event.
+    pub name: Cow<'static, str>,
+    /// Offset from the start of the sound.
+    pub offset: Duration,
+}
+
+impl Marker {
+    /// Creates a marker at the given offset.
+    pub fn new<N: Into<Cow<'static, str>>>(name: N, offset: Duration) -> Self {
+        Marker {
+            name: name.into(),
+            offset,
+        }
+    }
+}
+
+/// Events written to the `EventChannel<AudioEvent>` by the `AudioSystem`.
+///
+/// `emitter` is the entity of the `AudioEmitter` which played the sound, or `None` for music
+/// played through the `AudioSink`. Events are sent once per frame, so they lag behind the
+/// audio they describe by up to a frame.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AudioEvent {
+    /// A sound reached its end. Effects applied to it may still be audible for a while.
+    Finished {
+        /// The playback that finished.
+        sound: SoundId,
+        /// The emitter the sound was played from.
+        emitter: Option<Entity>,
+    },
+    /// A looping `AudioStream` started over.
+    Looped {
+        /// The playback that looped.
+        sound: SoundId,
+        /// The emitter the sound was played from.
+        emitter: Option<Entity>,
+        /// How often the stream looped so far.
+        count: u64,
+    },
+    /// Playback passed a `Marker`.
+    Marker {
+        /// The playback that passed the marker.
+        sound: SoundId,
+        /// The emitter the sound was played from.
+        emitter: Option<Entity>,
+        /// Name of the marker.
+        name: Cow<'static, str>,
+    },
+    /// The `OutputSystem` moved playback to another output device.
+    ///
+    /// The music continues on the new output, but the sounds of `AudioEmitter`s which were
+    /// playing are stopped, so they have to be played again if they matter.
+    OutputChanged {
+        /// Name of the new output.
+        name: String,
+    },
+}
+
+/// Collects events from the audio thread until the `AudioSystem` sends them.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct EventQueue(Arc<Mutex<Vec<AudioEvent>>>);
+
+impl EventQueue {
+    fn push(&self, event: AudioEvent) {
+        self.0
+            .lock()
+            .expect("Audio event queue poisoned")
+            .push(event);
+    }
+
+    pub(crate) fn drain(&self) -> Vec<AudioEvent> {
+        mem::take(&mut *self.0.lock().expect("Audio event queue poisoned"))
+    }
+}
+
+/// A sound waiting to be played, together with what is needed to report on its playback.
+pub(crate) struct TrackedSound<I> {
+    pub(crate) source: I,
+    pub(crate) id: SoundId,
+    pub(crate) markers: Vec<Marker>,
+    pub(crate) stream: Option<StreamControl>,
+}
+
+impl<I> TrackedSound<I>
+where
+    I: RSource + Send + 'static,
+    I::Item: Sample,
+{
+    pub(crate) fn new(source: I, markers: Vec<Marker>, stream: Option<StreamControl>) -> Self {
+        TrackedSound {
+            source,
+            id: SoundId::next(),
+            markers,
+            stream,
+        }
+    }
+
+    /// Wraps the sound into a source which reports its progress to `events`.
+    pub(crate) fn track(self, emitter: Option<Entity>, events: EventQueue) -> TrackedSource<I> {
+        let rate = u64::from(self.source.sample_rate().max(1));
+        let mut markers: Vec<_> = self
+            .markers
+            .into_iter()
+            .map(|m| {
+                let frame = (m.offset.as_secs_f64() * rate as f64) as u64;
+                (frame, m.name)
+            })
+            .collect();
+        markers.sort_by_key(|&(frame, _)| frame);
+        TrackedSource {
+            channels: self.source.channels().max(1),
+            input: self.source,
+            id: self.id,
+            emitter,
+            events,
+            markers,
+            next_marker: 0,
+            frames: 0,
+            sample_in_frame: 0,
+            stream: self.stream,
+            loops: 0,
+            finished: false,
+        }
+    }
+}
+
+/// Passes a source through while sending `AudioEvent`s about its progress.
+pub(crate) struct TrackedSource<I> {
+    input: I,
+    id: SoundId,
+    emitter: Option<Entity>,
+    events: EventQueue,
+    markers: Vec<(u64, Cow<'static, str>)>,
+    next_marker: usize,
+    frames: u64,
+    channels: u16,
+    sample_in_frame: u16,
+    stream: Option<StreamControl>,
+    loops: u64,
+    finished: bool,
+}
+
+impl<I> TrackedSource<I> {
+    fn frame_done(&mut self) {
+        if let Some(stream) = &self.stream {
+            let loops = stream.loop_count();
+            if loops != self.loops {
+                self.loops = loops;
+                self.events.push(AudioEvent::Looped {
+                    sound: self.id,
+                    emitter: self.emitter,
+                    count: loops,
+                });
+            }
+            let frames = stream.frames_played();
+            if frames < self.frames {
+                // The stream looped or was seeked backwards, markers may be passed again.
+                let start = frames.saturating_sub(1);
+                self.next_marker = self.markers.iter().take_while(|m| m.0 < start).count();
+            }
+            self.frames = frames;
+        } else {
+            self.frames += 1;
+        }
+
+        while let Some((frame, name)) = self.markers.get(self.next_marker) {
+            if *frame > self.frames {
+                break;
+            }
+            self.events.push(AudioEvent::Marker {
+                sound: self.id,
+                emitter: self.emitter,
+                name: name.clone(),
+            });
+            self.next_marker += 1;
+        }
+    }
+}
+
+impl<I> Iterator for TrackedSource<I>
+where
+    I: RSource,
+    I::Item: Sample,
+{
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<I::Item> {
+        let sample = self.input.next();
+        if sample.is_none() {
+            if !self.finished {
+                self.finished = true;
+                self.events.push(AudioEvent::Finished {
+                    sound: self.id,
+                    emitter: self.emitter,
+                });
+            }
+            return None;
+        }
+        self.sample_in_frame += 1;
+        if self.sample_in_frame >= self.channels {
+            self.sample_in_frame = 0;
+            self.frame_done();
+        }
+        sample
+    }
+}
+
+impl<I> RSource for TrackedSource<I>
+where
+    I: RSource,
+    I::Item: Sample,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        self.input.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.input.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.input.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.input.total_duration()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rodio::buffer::SamplesBuffer;
+
+    use super::*;
+
+    #[test]
+    fn markers_then_finished() {
+        let events = EventQueue::default();
+        let sound = TrackedSound::new(
+            SamplesBuffer::new(2, 1000, vec![0.0f32; 2000]),
+            vec![
+                Marker::new("end", Duration::from_millis(900)),
+                Marker::new("start", Duration::from_millis(0)),
+                Marker::new("never", Duration::from_secs(2)),
+            ],
+            None,
+        );
+        let id = sound.id;
+
+        let mut source = sound.track(None, events.clone());
+        source.by_ref().take(1000).for_each(drop);
+        assert_eq!(
+            events.drain(),
+            vec![AudioEvent::Marker {
+                sound: id,
+                emitter: None,
+                name: "start".into()
+            }]
+        );
+
+        source.for_each(drop);
+        assert_eq!(
+            events.drain(),
+            vec![
+                AudioEvent::Marker {
+                    sound: id,
+                    emitter: None,
+                    name: "end".into()
+                },
+                AudioEvent::Finished {
+                    sound: id,
+                    emitter: None
+                },
+            ]
+        );
+    }
+}
@@ -3,15 +3,26 @@ use amethyst_error::Error;
 use serde::{Deserialize, Serialize};
 use type_uuid::TypeUuid;
 
+/// An imported audio file, loaded as a `Source`.
 #[derive(Clone, Debug, Serialize, Deserialize, TypeUuid)]
 #[uuid = "caa6e38f-9cfa-428a-91bd-4dab5a7a47d5"]
-pub struct AudioData(pub Vec<u8>);
-amethyst_assets::register_asset_type!(AudioData => crate::Source; amethyst_assets::AssetProcessorSystem<crate::Source>);
+pub struct AudioData {
+    /// The bytes of the audio file.
+    pub bytes: Vec<u8>,
+    /// Decodes the sound while loading, see `Source::preloaded`.
+    pub preload: bool,
+}
+amethyst_assets::register_asset_type!(AudioData => crate::Source; crate::SourceProcessorSystem);
 
 /// Loads audio from wav files.
 #[derive(Clone, Copy, Default, Debug, Serialize, Deserialize, TypeUuid)]
 #[uuid = "e78ea33f-d506-4d4f-8276-861660bb6145"]
-pub struct WavFormat;
+pub struct WavFormat {
+    /// Decodes the sound while loading, so it starts playing without a delay at the cost of
+    /// keeping all of its samples in memory. Leave it off for sounds played as `AudioStream`s.
+    #[serde(default)]
+    pub preload: bool,
+}
 
 amethyst_assets::register_importer!(".wav", WavFormat);
 impl Format<AudioData> for WavFormat {
@@ -20,14 +31,21 @@ impl Format<AudioData> for WavFormat {
     }
 
     fn import_simple(&self, bytes: Vec<u8>) -> Result<AudioData, Error> {
-        Ok(AudioData(bytes))
+        Ok(AudioData {
+            bytes,
+            preload: self.preload,
+        })
     }
 }
 
 /// Loads audio from Ogg Vorbis files
 #[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, TypeUuid)]
 #[uuid = "8ce12d56-9091-4e25-b764-da162fa165aa"]
-pub struct OggFormat;
+pub struct OggFormat {
+    /// Decodes the sound while loading, see `WavFormat::preload`.
+    #[serde(default)]
+    pub preload: bool,
+}
 
 amethyst_assets::register_importer!(".ogg", OggFormat);
 impl Format<AudioData> for OggFormat {
@@ -36,14 +54,21 @@ impl Format<AudioData> for OggFormat {
     }
 
     fn import_simple(&self, bytes: Vec<u8>) -> Result<AudioData, Error> {
-        Ok(AudioData(bytes))
+        Ok(AudioData {
+            bytes,
+            preload: self.preload,
+        })
     }
 }
 
 /// Loads audio from Flac files.
 #[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, TypeUuid)]
 #[uuid = "15522fa0-9996-4416-840f-1e99c7a31f1a"]
-pub struct FlacFormat;
+pub struct FlacFormat {
+    /// Decodes the sound while loading, see `WavFormat::preload`.
+    #[serde(default)]
+    pub preload: bool,
+}
 
 amethyst_assets::register_importer!(".flac", FlacFormat);
 impl Format<AudioData> for FlacFormat {
@@ -52,14 +77,21 @@ impl Format<AudioData> for FlacFormat {
     }
 
     fn import_simple(&self, bytes: Vec<u8>) -> Result<AudioData, Error> {
-        Ok(AudioData(bytes))
+        Ok(AudioData {
+            bytes,
+            preload: self.preload,
+        })
     }
 }
 
 /// Loads audio from MP3 files.
 #[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, TypeUuid)]
 #[uuid = "f693f1ec-e148-4190-b6ac-e3dc9795031c"]
-pub struct Mp3Format;
+pub struct Mp3Format {
+    /// Decodes the sound while loading, see `WavFormat::preload`.
+    #[serde(default)]
+    pub preload: bool,
+}
 
 amethyst_assets::register_importer!(".mp3", Mp3Format);
 impl Format<AudioData> for Mp3Format {
@@ -68,6 +100,9 @@ impl Format<AudioData> for Mp3Format {
     }
 
     fn import_simple(&self, bytes: Vec<u8>) -> Result<AudioData, Error> {
-        Ok(AudioData(bytes))
+        Ok(AudioData {
+            bytes,
+            preload: self.preload,
+        })
     }
 }
@@ -18,23 +18,44 @@ use std::{
     fmt::{Display, Formatter, Result as FmtResult},
 };
 
+#[cfg(feature = "opus")]
+pub use self::capture::{VoiceDecoder, VoiceEncoder};
 pub use self::{
-    bundle::AudioBundle,
+    bundle::{AudioBundle, VoiceCaptureBundle},
+    capture::{CaptureError, VoiceCapture, VoiceCaptureSystem, VoiceFrame},
     components::*,
+    effects::{Effect, Reverb},
+    event::{AudioEvent, Marker, SoundId},
     formats::{FlacFormat, Mp3Format, OggFormat, WavFormat},
+    mixer::{
+        Bus, BusEffect, MixSource, Mixer, MixerError, MASTER_BUS, MUSIC_BUS, SFX_BUS, UI_BUS,
+        VOICE_BUS,
+    },
+    music::{MusicController, MusicTrack, Quantize, Tempo, Transition, MAIN_STEM},
+    occlusion::{Occlusion, OcclusionGeometry, OcclusionSystem},
     sink::AudioSink,
-    source::{Source, SourceHandle},
+    source::{SampleFormat, Source, SourceHandle, SourceProcessorSystem},
+    spatial::{DistanceModel, DopplerFactor, Rolloff, SpatialMode, SPEED_OF_SOUND},
+    stream::{AudioStream, StreamControl},
     systems::*,
 };
 
 pub mod output;
 
 mod bundle;
+mod capture;
 mod components;
+mod effects;
 mod end_signal;
+mod event;
 mod formats;
+mod mixer;
+mod music;
+mod occlusion;
 mod sink;
 mod source;
+mod spatial;
+mod stream;
 mod systems;
 
 /// An error occurred while decoding the source.
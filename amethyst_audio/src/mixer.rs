@@ -0,0 +1,583 @@
+//! Mixer buses which group sounds for volume control and effects.
+
+use std::{
+    borrow::Cow,
+    error::Error,
+    fmt::{Debug, Display, Formatter, Result as FmtResult},
+    sync::{
+        atomic::{AtomicBool, AtomicU32, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use rodio::Source as RSource;
+
+/// Name of the bus every other bus ends up in.
+pub const MASTER_BUS: &str = "master";
+/// Name of the default bus for background music.
+pub const MUSIC_BUS: &str = "music";
+/// Name of the default bus for sound effects, used by emitters unless configured otherwise.
+pub const SFX_BUS: &str = "sfx";
+/// Name of the default bus for dialogue.
+pub const VOICE_BUS: &str = "voice";
+/// Name of the default bus for menu sounds, which keeps playing while the game is paused.
+pub const UI_BUS: &str = "ui";
+
+/// Number of frames between two reads of the bus volumes.
+const UPDATE_INTERVAL: u32 = 64;
+
+/// Number of frames between two reads of the time scale, which restarts resampling.
+const SPEED_UPDATE_INTERVAL: u32 = 1024;
+
+/// Lowest speed sounds are slowed down to, lower time scales pause them instead.
+const MIN_SPEED: f32 = 0.05;
+
+/// A sound as it flows through the effects of a bus.
+pub type MixSource = Box<dyn RSource<Item = f32> + Send>;
+
+/// An effect on a bus, which wraps every sound routed through the bus.
+///
+/// Effects are applied when a sound starts playing, so changes to the effects of a bus
+/// only affect sounds started afterwards.
+pub type BusEffect = Arc<dyn Fn(MixSource) -> MixSource + Send + Sync>;
+
+/// An error occurred while changing the layout of the `Mixer`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MixerError {
+    /// There is no bus with the given name.
+    UnknownBus(String),
+    /// A bus with the given name already exists.
+    BusExists(String),
+}
+
+impl Display for MixerError {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            MixerError::UnknownBus(name) => write!(formatter, "No mixer bus named `{}`", name),
+            MixerError::BusExists(name) => {
+                write!(formatter, "A mixer bus named `{}` already exists", name)
+            }
+        }
+    }
+}
+
+impl Error for MixerError {}
+
+/// State shared by all buses of a mixer.
+#[derive(Debug)]
+struct MixerState {
+    game_paused: AtomicBool,
+    time_scale: AtomicU32,
+}
+
+struct BusState {
+    volume: AtomicU32,
+    muted: AtomicBool,
+    paused: AtomicBool,
+    pausable: AtomicBool,
+    time_scaled: AtomicBool,
+    effects: Mutex<Vec<BusEffect>>,
+    parent: Option<Bus>,
+    mixer: Arc<MixerState>,
+}
+
+/// A group of sounds with a shared volume, mute switch and effect chain.
+///
+/// The volume of a bus is multiplied with the volume of its parent, and muting or pausing a
+/// bus affects all buses below it. Sounds pick up these changes while they play.
+///
+/// `Bus` is a cheap handle, clones refer to the same bus.
+#[derive(Clone)]
+pub struct Bus {
+    name: Cow<'static, str>,
+    state: Arc<BusState>,
+}
+
+impl Bus {
+    fn new(name: Cow<'static, str>, parent: Option<Bus>, mixer: Arc<MixerState>) -> Self {
+        Bus {
+            name,
+            state: Arc::new(BusState {
+                volume: AtomicU32::new(1.0f32.to_bits()),
+                muted: AtomicBool::new(false),
+                paused: AtomicBool::new(false),
+                pausable: AtomicBool::new(true),
+                time_scaled: AtomicBool::new(false),
+                effects: Mutex::new(Vec::new()),
+                parent,
+                mixer,
+            }),
+        }
+    }
+
+    /// Returns the name of this bus.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the bus this bus is mixed into, `None` for the master bus.
+    pub fn parent(&self) -> Option<&Bus> {
+        self.state.parent.as_ref()
+    }
+
+    /// Returns the volume of this bus alone, 1.0 is unchanged.
+    pub fn volume(&self) -> f32 {
+        f32::from_bits(self.state.volume.load(Ordering::Relaxed))
+    }
+
+    /// Sets the volume of this bus, 1.0 is unchanged while 0.0 is silent.
+    pub fn set_volume(&self, volume: f32) {
+        self.state
+            .volume
+            .store(volume.max(0.0).to_bits(), Ordering::Relaxed);
+    }
+
+    /// Returns true if this bus itself is muted.
+    pub fn is_muted(&self) -> bool {
+        self.state.muted.load(Ordering::Relaxed)
+    }
+
+    /// Mutes or unmutes this bus.
+    pub fn set_muted(&self, muted: bool) {
+        self.state.muted.store(muted, Ordering::Relaxed);
+    }
+
+    /// Returns true if this bus itself is paused.
+    pub fn is_paused(&self) -> bool {
+        self.state.paused.load(Ordering::Relaxed)
+    }
+
+    /// Pauses or resumes this bus and all buses below it.
+    ///
+    /// Paused sounds keep their position and continue from there once resumed.
+    pub fn set_paused(&self, paused: bool) {
+        self.state.paused.store(paused, Ordering::Relaxed);
+    }
+
+    /// Returns true if `Mixer::pause_game` pauses the sounds of this bus.
+    pub fn is_pausable(&self) -> bool {
+        self.state.pausable.load(Ordering::Relaxed)
+    }
+
+    /// Changes whether `Mixer::pause_game` pauses the sounds of this bus, only the bus a sound
+    /// is routed into is taken into account.
+    pub fn set_pausable(&self, pausable: bool) {
+        self.state.pausable.store(pausable, Ordering::Relaxed);
+    }
+
+    /// Returns true if this bus itself follows the time scale.
+    pub fn is_time_scaled(&self) -> bool {
+        self.state.time_scaled.load(Ordering::Relaxed)
+    }
+
+    /// Changes whether sounds on this bus and all buses below it are sped up or slowed down,
+    /// including their pitch, according to `Time::time_scale`.
+    ///
+    /// This only affects sounds started afterwards. A time scale of zero pauses time scaled sounds.
+    pub fn set_time_scaled(&self, time_scaled: bool) {
+        self.state.time_scaled.store(time_scaled, Ordering::Relaxed);
+    }
+
+    fn ancestors(&self) -> impl Iterator<Item = &Bus> {
+        std::iter::successors(Some(self), |b| b.parent())
+    }
+
+    fn is_effectively_time_scaled(&self) -> bool {
+        self.ancestors().any(Bus::is_time_scaled)
+    }
+
+    /// Returns true if sounds on this bus should stay silent and keep their position.
+    fn is_suspended(&self, time_scaled: bool) -> bool {
+        let mixer = &self.state.mixer;
+        self.ancestors().any(Bus::is_paused)
+            || (self.is_pausable() && mixer.game_paused.load(Ordering::Relaxed))
+            || (time_scaled && self.time_scale() < MIN_SPEED)
+    }
+
+    fn time_scale(&self) -> f32 {
+        f32::from_bits(self.state.mixer.time_scale.load(Ordering::Relaxed))
+    }
+
+    fn speed(&self, time_scaled: bool) -> f32 {
+        if time_scaled {
+            self.time_scale().max(MIN_SPEED)
+        } else {
+            1.0
+        }
+    }
+
+    /// Returns the volume sounds on this bus are played at, taking all parents into account.
+    pub fn effective_volume(&self) -> f32 {
+        let mut volume = 1.0;
+        let mut bus = Some(self);
+        while let Some(b) = bus {
+            if b.is_muted() {
+                return 0.0;
+            }
+            volume *= b.volume();
+            bus = b.parent();
+        }
+        volume
+    }
+
+    /// Appends an effect to the end of this bus' effect chain.
+    pub fn push_effect(&self, effect: BusEffect) {
+        self.effects().push(effect);
+    }
+
+    /// Removes all effects of this bus.
+    pub fn clear_effects(&self) {
+        self.effects().clear();
+    }
+
+    /// Returns the number of effects in this bus' effect chain.
+    pub fn effect_count(&self) -> usize {
+        self.effects().len()
+    }
+
+    fn effects(&self) -> std::sync::MutexGuard<'_, Vec<BusEffect>> {
+        self.state.effects.lock().expect("Bus effects poisoned")
+    }
+
+    /// Runs a sound through the effects of this bus and all its parents, and applies their volume.
+    pub(crate) fn route<S>(&self, source: S) -> BusSource
+    where
+        S: RSource<Item = f32> + Send + 'static,
+    {
+        let mut source: MixSource = Box::new(source);
+        let mut bus = Some(self);
+        while let Some(b) = bus {
+            for effect in b.effects().iter() {
+                source = effect(source);
+            }
+            bus = b.parent();
+        }
+        BusSource::new(source, self.clone())
+    }
+}
+
+impl Debug for Bus {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_struct("Bus")
+            .field("name", &self.name)
+            .field("parent", &self.parent().map(Bus::name))
+            .field("volume", &self.volume())
+            .field("muted", &self.is_muted())
+            .field("paused", &self.is_paused())
+            .field("pausable", &self.is_pausable())
+            .field("time_scaled", &self.is_time_scaled())
+            .field("effects", &self.effect_count())
+            .finish()
+    }
+}
+
+/// World resource holding the mixer buses sounds are routed into.
+///
+/// By default it contains a `MASTER_BUS` with `MUSIC_BUS`, `SFX_BUS`, `VOICE_BUS` and `UI_BUS`
+/// below it, where only the `UI_BUS` keeps playing while the game is paused.
+#[derive(Debug, Clone)]
+pub struct Mixer {
+    buses: Vec<Bus>,
+    state: Arc<MixerState>,
+}
+
+impl Default for Mixer {
+    fn default() -> Self {
+        let mut mixer = Mixer::new();
+        for name in &[MUSIC_BUS, SFX_BUS, VOICE_BUS, UI_BUS] {
+            mixer
+                .add_bus(*name, MASTER_BUS)
+                .expect("Default buses are unique");
+        }
+        mixer.bus(UI_BUS).unwrap().set_pausable(false);
+        mixer
+    }
+}
+
+impl Mixer {
+    /// Creates a mixer which only contains the master bus.
+    pub fn new() -> Self {
+        let state = Arc::new(MixerState {
+            game_paused: AtomicBool::new(false),
+            time_scale: AtomicU32::new(1.0f32.to_bits()),
+        });
+        Mixer {
+            buses: vec![Bus::new(MASTER_BUS.into(), None, state.clone())],
+            state,
+        }
+    }
+
+    /// Pauses the sounds of all pausable buses, e.g. from `State::on_pause` when a pause
+    /// menu is pushed. Sounds on the `UI_BUS` keep playing.
+    pub fn pause_game(&self) {
+        self.state.game_paused.store(true, Ordering::Relaxed);
+    }
+
+    /// Resumes the sounds paused by `pause_game`.
+    pub fn resume_game(&self) {
+        self.state.game_paused.store(false, Ordering::Relaxed);
+    }
+
+    /// Returns true if the game sounds are paused.
+    pub fn is_game_paused(&self) -> bool {
+        self.state.game_paused.load(Ordering::Relaxed)
+    }
+
+    /// Sets the time scale for time scaled buses, kept in sync with `Time` by the `AudioSystem`.
+    pub(crate) fn set_time_scale(&self, time_scale: f32) {
+        self.state
+            .time_scale
+            .store(time_scale.to_bits(), Ordering::Relaxed);
+    }
+
+    /// Adds a bus which is mixed into `parent`.
+    pub fn add_bus<N: Into<Cow<'static, str>>>(
+        &mut self,
+        name: N,
+        parent: &str,
+    ) -> Result<&Bus, MixerError> {
+        let name = name.into();
+        if self.bus(&name).is_some() {
+            return Err(MixerError::BusExists(name.into_owned()));
+        }
+        let parent = self
+            .bus(parent)
+            .cloned()
+            .ok_or_else(|| MixerError::UnknownBus(parent.to_owned()))?;
+        self.buses
+            .push(Bus::new(name, Some(parent), self.state.clone()));
+        Ok(self.buses.last().expect("Bus was just added"))
+    }
+
+    /// Returns the bus with the given name.
+    pub fn bus(&self, name: &str) -> Option<&Bus> {
+        self.buses.iter().find(|b| b.name() == name)
+    }
+
+    /// Returns the master bus.
+    pub fn master(&self) -> &Bus {
+        &self.buses[0]
+    }
+
+    /// Returns an iterator over all buses, parents come before their children.
+    pub fn buses(&self) -> impl Iterator<Item = &Bus> {
+        self.buses.iter()
+    }
+
+    /// Returns the bus with the given name, or the master bus if there is none.
+    pub(crate) fn bus_or_master(&self, name: &str) -> &Bus {
+        self.bus(name).unwrap_or_else(|| {
+            log::warn!("No mixer bus named `{}`, using the master bus", name);
+            self.master()
+        })
+    }
+}
+
+/// A sound routed through a `Bus`.
+pub(crate) struct BusSource {
+    input: MixSource,
+    bus: Bus,
+    channels: u16,
+    sample_in_frame: u16,
+    gain: f32,
+    step: f32,
+    frames_until_update: u32,
+    paused: bool,
+    time_scaled: bool,
+    speed: f32,
+    frames_until_speed_update: u32,
+}
+
+impl BusSource {
+    fn new(input: MixSource, bus: Bus) -> Self {
+        let time_scaled = bus.is_effectively_time_scaled();
+        let paused = bus.is_suspended(time_scaled);
+        BusSource {
+            channels: input.channels().max(1),
+            input,
+            sample_in_frame: 0,
+            gain: if paused { 0.0 } else { bus.effective_volume() },
+            step: 0.0,
+            frames_until_update: UPDATE_INTERVAL,
+            paused,
+            time_scaled,
+            speed: bus.speed(time_scaled),
+            frames_until_speed_update: SPEED_UPDATE_INTERVAL,
+            bus,
+        }
+    }
+
+    fn end_frame(&mut self) {
+        self.gain = (self.gain + self.step).max(0.0);
+        self.frames_until_update -= 1;
+        if self.frames_until_update == 0 {
+            self.frames_until_update = UPDATE_INTERVAL;
+            if self.bus.is_suspended(self.time_scaled) {
+                // Fade out before pausing to avoid a click.
+                if self.gain <= std::f32::EPSILON {
+                    self.paused = true;
+                    self.gain = 0.0;
+                    self.step = 0.0;
+                } else {
+                    self.step = -self.gain / UPDATE_INTERVAL as f32;
+                }
+            } else {
+                self.paused = false;
+                self.step = (self.bus.effective_volume() - self.gain) / UPDATE_INTERVAL as f32;
+            }
+        }
+        if self.time_scaled {
+            self.frames_until_speed_update -= 1;
+            if self.frames_until_speed_update == 0 {
+                self.frames_until_speed_update = SPEED_UPDATE_INTERVAL;
+                self.speed = self.bus.speed(true);
+            }
+        }
+    }
+}
+
+impl Iterator for BusSource {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let sample = if self.paused { 0.0 } else { self.input.next()? };
+        let output = sample * self.gain;
+        self.sample_in_frame += 1;
+        if self.sample_in_frame == self.channels {
+            self.sample_in_frame = 0;
+            self.end_frame();
+        }
+        Some(output)
+    }
+}
+
+impl RSource for BusSource {
+    fn current_frame_len(&self) -> Option<usize> {
+        if self.time_scaled {
+            // Ends the frame whenever the speed may change, so the output picks up the new
+            // sample rate.
+            Some(
+                self.frames_until_speed_update as usize * self.channels as usize
+                    - self.sample_in_frame as usize,
+            )
+        } else {
+            self.input.current_frame_len()
+        }
+    }
+
+    fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    fn sample_rate(&self) -> u32 {
+        ((self.input.sample_rate() as f32 * self.speed).round() as u32).max(1)
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.input.total_duration()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rodio::buffer::SamplesBuffer;
+
+    use super::*;
+
+    #[test]
+    fn volume_and_mute_propagate() {
+        let mut mixer = Mixer::default();
+        mixer.add_bus("footsteps", SFX_BUS).unwrap();
+        assert_eq!(
+            mixer.add_bus("footsteps", SFX_BUS).unwrap_err(),
+            MixerError::BusExists("footsteps".into())
+        );
+        assert!(mixer.add_bus("ambience", "missing").is_err());
+
+        mixer.master().set_volume(0.5);
+        mixer.bus(SFX_BUS).unwrap().set_volume(0.5);
+        let footsteps = mixer.bus("footsteps").unwrap();
+        assert!((footsteps.effective_volume() - 0.25).abs() < 1e-6);
+
+        let played: Vec<f32> = footsteps
+            .route(SamplesBuffer::new(1, 44100, vec![1.0f32; 4]))
+            .collect();
+        assert!(played.iter().all(|s| (s - 0.25).abs() < 1e-6));
+
+        mixer.bus(SFX_BUS).unwrap().set_muted(true);
+        assert!(footsteps.effective_volume().abs() < 1e-6);
+        assert!((mixer.bus(MUSIC_BUS).unwrap().effective_volume() - 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn effects_apply_from_bus_to_master() {
+        let mixer = Mixer::default();
+        mixer
+            .master()
+            .push_effect(Arc::new(|source: MixSource| -> MixSource {
+                Box::new(source.amplify(2.0))
+            }));
+        mixer
+            .bus(VOICE_BUS)
+            .unwrap()
+            .push_effect(Arc::new(|source: MixSource| -> MixSource {
+                Box::new(source.take_duration(Duration::from_secs(0)))
+            }));
+
+        let voice: Vec<f32> = mixer
+            .bus(VOICE_BUS)
+            .unwrap()
+            .route(SamplesBuffer::new(1, 44100, vec![1.0f32; 4]))
+            .collect();
+        assert!(voice.is_empty());
+
+        let music: Vec<f32> = mixer
+            .bus(MUSIC_BUS)
+            .unwrap()
+            .route(SamplesBuffer::new(1, 44100, vec![1.0f32; 4]))
+            .collect();
+        assert_eq!(music.len(), 4);
+        assert!(music.iter().all(|s| (s - 2.0).abs() < 1e-6));
+    }
+
+    #[test]
+    fn pause_game_keeps_ui_playing() {
+        let mixer = Mixer::default();
+        mixer.pause_game();
+
+        let mut sfx =
+            mixer
+                .bus(SFX_BUS)
+                .unwrap()
+                .route(SamplesBuffer::new(1, 44100, vec![1.0f32; 4]));
+        assert!(sfx.by_ref().take(8).all(|s| s.abs() < 1e-6));
+
+        let ui: Vec<f32> = mixer
+            .bus(UI_BUS)
+            .unwrap()
+            .route(SamplesBuffer::new(1, 44100, vec![1.0f32; 4]))
+            .collect();
+        assert_eq!(ui.len(), 4);
+        assert!(ui.iter().all(|s| (s - 1.0).abs() < 1e-6));
+    }
+
+    #[test]
+    fn time_scale_changes_sample_rate() {
+        let mixer = Mixer::default();
+        let music = mixer.bus(MUSIC_BUS).unwrap();
+        music.set_time_scaled(true);
+        mixer.set_time_scale(0.5);
+
+        let scaled = music.route(SamplesBuffer::new(2, 44100, vec![1.0f32; 4]));
+        assert_eq!(scaled.sample_rate(), 22050);
+        assert_eq!(scaled.current_frame_len(), Some(2048));
+
+        let unscaled =
+            mixer
+                .bus(SFX_BUS)
+                .unwrap()
+                .route(SamplesBuffer::new(2, 44100, vec![1.0f32; 4]));
+        assert_eq!(unscaled.sample_rate(), 44100);
+    }
+}
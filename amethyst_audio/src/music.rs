@@ -0,0 +1,807 @@
+//! Music playback with crossfades, beat aligned transitions and layered stems.
+
+use std::{
+    borrow::Cow,
+    mem,
+    sync::{
+        atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use cpal::traits::DeviceTrait;
+use log::error;
+use rodio::{source::UniformSourceIterator, Sink, Source as RSource};
+
+use crate::{
+    mixer::{Mixer, MUSIC_BUS},
+    output::Output,
+    source::{SampleFormat, Source},
+    stream::{AudioStream, StreamControl},
+    DecoderError,
+};
+
+/// Name of the stem of tracks created with `MusicTrack::new`.
+pub const MAIN_STEM: &str = "main";
+
+/// Number of frames between two checks for new commands.
+const COMMAND_INTERVAL: u32 = 64;
+
+/// Format music is mixed in if the output device doesn't report one.
+const FALLBACK_FORMAT: SampleFormat = SampleFormat {
+    channels: 2,
+    sample_rate: 44100,
+};
+
+/// Tempo of a track, used to align transitions with its beats.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tempo {
+    /// Beats per minute.
+    pub bpm: f32,
+    /// Beats of every bar.
+    pub beats_per_bar: u32,
+}
+
+/// When a transition starts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Quantize {
+    /// Right away.
+    Immediate,
+    /// On the next beat of the current track.
+    Beat,
+    /// On the next bar of the current track.
+    Bar,
+}
+
+/// How the `MusicController` moves from one track to the next.
+///
+/// Transitions are aligned to the beats of the track which is playing when they start,
+/// and start right away if it has no `Tempo`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transition {
+    /// Duration of the fade between both tracks.
+    pub fade: Duration,
+    /// When the fade starts.
+    pub quantize: Quantize,
+}
+
+impl Transition {
+    /// Switches tracks right away, without fading.
+    pub fn immediate() -> Self {
+        Transition {
+            fade: Duration::from_secs(0),
+            quantize: Quantize::Immediate,
+        }
+    }
+
+    /// Fades from one track to the next over the given duration, starting right away.
+    pub fn crossfade(fade: Duration) -> Self {
+        Transition {
+            fade,
+            quantize: Quantize::Immediate,
+        }
+    }
+
+    /// Fades from one track to the next, starting on the next beat.
+    pub fn on_beat(fade: Duration) -> Self {
+        Transition {
+            fade,
+            quantize: Quantize::Beat,
+        }
+    }
+
+    /// Fades from one track to the next, starting on the next bar.
+    pub fn on_bar(fade: Duration) -> Self {
+        Transition {
+            fade,
+            quantize: Quantize::Bar,
+        }
+    }
+}
+
+impl Default for Transition {
+    fn default() -> Self {
+        Transition::crossfade(Duration::from_secs(2))
+    }
+}
+
+/// One layer of a track.
+struct Stem {
+    name: Cow<'static, str>,
+    stream: AudioStream,
+    volume: f32,
+}
+
+/// A stem handed to the audio thread, kept to continue it on another output.
+struct PlayingStem {
+    name: Cow<'static, str>,
+    bytes: Arc<[u8]>,
+    control: StreamControl,
+    volume: f32,
+}
+
+/// The track handed to the audio thread last, kept to continue it on another output.
+struct PlayingTrack {
+    stems: Vec<PlayingStem>,
+    tempo: Option<Tempo>,
+    looping: bool,
+}
+
+impl PlayingTrack {
+    fn new(track: &MusicTrack) -> Self {
+        let stems = track
+            .stems
+            .iter()
+            .map(|stem| {
+                let (bytes, control) = stem.stream.resumable();
+                PlayingStem {
+                    name: stem.name.clone(),
+                    bytes,
+                    control,
+                    volume: stem.volume,
+                }
+            })
+            .collect();
+        PlayingTrack {
+            stems,
+            tempo: track.tempo,
+            looping: track.looping,
+        }
+    }
+
+    /// Creates a track continuing this one from where its stems are.
+    fn resume(&self) -> Result<MusicTrack, DecoderError> {
+        let mut track = MusicTrack::empty();
+        track.tempo = self.tempo;
+        track.looping = self.looping;
+        // The remaining length isn't known anymore.
+        track.length = None;
+        for stem in &self.stems {
+            track.stems.push(Stem {
+                name: stem.name.clone(),
+                stream: AudioStream::resume(stem.bytes.clone(), &stem.control)?,
+                volume: stem.volume,
+            });
+        }
+        Ok(track)
+    }
+}
+
+/// A piece of music made of one or more stems, which play in sync.
+#[allow(missing_debug_implementations)]
+pub struct MusicTrack {
+    stems: Vec<Stem>,
+    tempo: Option<Tempo>,
+    looping: bool,
+    length: Option<Duration>,
+}
+
+impl MusicTrack {
+    /// Creates a track playing a single source, as its `MAIN_STEM`.
+    pub fn new(source: &Source) -> Result<Self, DecoderError> {
+        MusicTrack::empty().with_stem(MAIN_STEM, source, 1.0)
+    }
+
+    /// Creates a track without any stems.
+    pub fn empty() -> Self {
+        MusicTrack {
+            stems: Vec::new(),
+            tempo: None,
+            looping: false,
+            length: Some(Duration::from_secs(0)),
+        }
+    }
+
+    /// Adds a stem with the given name and initial volume.
+    ///
+    /// All stems start together and should share the tempo of the track.
+    pub fn with_stem<N>(
+        mut self,
+        name: N,
+        source: &Source,
+        volume: f32,
+    ) -> Result<Self, DecoderError>
+    where
+        N: Into<Cow<'static, str>>,
+    {
+        self.length = match (self.length, source.duration()) {
+            (Some(length), Some(duration)) => Some(length.max(duration)),
+            _ => None,
+        };
+        self.stems.push(Stem {
+            name: name.into(),
+            stream: AudioStream::new(source)?,
+            volume,
+        });
+        Ok(self)
+    }
+
+    /// Sets the tempo of the track, which beat aligned transitions follow.
+    pub fn with_tempo(mut self, bpm: f32, beats_per_bar: u32) -> Self {
+        self.tempo = Some(Tempo { bpm, beats_per_bar });
+        self
+    }
+
+    /// Makes the track start over once it ends, until the next transition.
+    pub fn looping(mut self) -> Self {
+        self.looping = true;
+        self
+    }
+}
+
+enum Command {
+    Play(MusicTrack, Transition),
+    Stop(Transition),
+    StemVolume {
+        stem: Cow<'static, str>,
+        volume: f32,
+        fade: Duration,
+    },
+}
+
+/// What the audio thread reports back to the `MusicController`.
+#[derive(Debug, Default)]
+struct MusicStatus {
+    playing: AtomicBool,
+    transitioning: AtomicBool,
+    sample_rate: AtomicU32,
+    position: AtomicU64,
+    remaining: AtomicU64,
+}
+
+/// Plays background music, fading between tracks and mixing the stems of the current one.
+///
+/// Music is played into the `MUSIC_BUS` of the `Mixer` once the `MusicSystem` finds an
+/// audio output, commands issued before that are kept until then. When the `OutputSystem`
+/// switches to another output, the current track continues on it where it was.
+#[allow(missing_debug_implementations)]
+pub struct MusicController {
+    commands: Arc<Mutex<Vec<Command>>>,
+    status: Arc<MusicStatus>,
+    sink: Option<Sink>,
+    current: Option<PlayingTrack>,
+}
+
+impl Default for MusicController {
+    fn default() -> Self {
+        let status = MusicStatus::default();
+        status.remaining.store(u64::MAX, Ordering::Relaxed);
+        MusicController {
+            commands: Arc::default(),
+            status: Arc::new(status),
+            sink: None,
+            current: None,
+        }
+    }
+}
+
+impl MusicController {
+    /// Creates a controller which doesn't play anything yet.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Moves from the current track, if any, to the given one.
+    pub fn play(&mut self, track: MusicTrack, transition: Transition) {
+        self.current = Some(PlayingTrack::new(&track));
+        self.send(Command::Play(track, transition));
+    }
+
+    /// Fades out the current track.
+    pub fn stop(&mut self, transition: Transition) {
+        self.current = None;
+        self.send(Command::Stop(transition));
+    }
+
+    /// Fades a stem of the current track to the given volume.
+    pub fn set_stem_volume<N>(&mut self, stem: N, volume: f32, fade: Duration)
+    where
+        N: Into<Cow<'static, str>>,
+    {
+        let stem: Cow<'static, str> = stem.into();
+        if let Some(current) = &mut self.current {
+            for s in current.stems.iter_mut().filter(|s| s.name == stem) {
+                s.volume = volume;
+            }
+        }
+        self.send(Command::StemVolume { stem, volume, fade });
+    }
+
+    fn send(&self, command: Command) {
+        let mut commands = self.commands.lock().expect("Music commands poisoned");
+        commands.push(command);
+    }
+
+    /// Returns true if a track is playing, not counting tracks which are fading out.
+    pub fn is_playing(&self) -> bool {
+        self.status.playing.load(Ordering::Relaxed)
+    }
+
+    /// Returns true while a transition is waiting to start or fading.
+    pub fn is_transitioning(&self) -> bool {
+        self.status.transitioning.load(Ordering::Relaxed)
+            || !self
+                .commands
+                .lock()
+                .expect("Music commands poisoned")
+                .is_empty()
+    }
+
+    /// Returns how long the current track has been playing.
+    pub fn position(&self) -> Duration {
+        self.frames_to_duration(self.status.position.load(Ordering::Relaxed))
+            .unwrap_or_default()
+    }
+
+    /// Returns how long the current track keeps playing, if it doesn't loop and its length
+    /// is known. Only preloaded sources know their length, see `Source::preloaded`.
+    pub fn remaining(&self) -> Option<Duration> {
+        match self.status.remaining.load(Ordering::Relaxed) {
+            u64::MAX => None,
+            frames => self.frames_to_duration(frames),
+        }
+    }
+
+    fn frames_to_duration(&self, frames: u64) -> Option<Duration> {
+        match self.status.sample_rate.load(Ordering::Relaxed) {
+            0 => None,
+            rate => Some(Duration::from_secs_f64(frames as f64 / f64::from(rate))),
+        }
+    }
+
+    /// Stops playing into the current output, dropping the tracks which are playing.
+    ///
+    /// The current track is queued again from where it was, so it continues once the
+    /// controller is attached to another output. Commands which didn't reach the audio thread
+    /// yet are kept, and replace the current track if there are any.
+    pub(crate) fn detach(&mut self) {
+        self.sink = None;
+        let mut commands = self.commands.lock().expect("Music commands poisoned");
+        if !commands.is_empty() {
+            return;
+        }
+        if !self.status.playing.load(Ordering::Relaxed) {
+            self.current = None;
+        }
+        if let Some(current) = &self.current {
+            match current.resume() {
+                Ok(track) => commands.push(Command::Play(track, Transition::immediate())),
+                Err(e) => error!("Failed to continue the music on the new output: {}", e),
+            }
+        }
+    }
+
+    /// Starts playing into the given output, unless the controller already does.
+    pub(crate) fn attach(&mut self, output: &Output, mixer: &Mixer) {
+        if self.sink.is_some() {
+            return;
+        }
+        let format = output
+            .device
+            .default_output_format()
+            .map(|format| {
+                SampleFormat {
+                    channels: format.channels,
+                    sample_rate: format.sample_rate.0,
+                }
+            })
+            .unwrap_or(FALLBACK_FORMAT);
+        let source = MusicSource::new(self.commands.clone(), self.status.clone(), format);
+        let sink = Sink::new(&output.device);
+        sink.append(mixer.bus_or_master(MUSIC_BUS).route(source));
+        self.sink = Some(sink);
+    }
+}
+
+/// Volume changing linearly over a span of frames.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Fade {
+    from: f32,
+    to: f32,
+    start: u64,
+    frames: u64,
+}
+
+impl Fade {
+    fn constant(volume: f32) -> Self {
+        Fade {
+            from: volume,
+            to: volume,
+            start: 0,
+            frames: 0,
+        }
+    }
+
+    fn volume(&self, clock: u64) -> f32 {
+        if clock >= self.end() {
+            self.to
+        } else if clock <= self.start {
+            self.from
+        } else {
+            let t = (clock - self.start) as f32 / self.frames as f32;
+            self.from + (self.to - self.from) * t
+        }
+    }
+
+    fn end(&self) -> u64 {
+        self.start + self.frames
+    }
+
+    /// Starts a new fade from the volume this one has at `start`.
+    fn retarget(&self, to: f32, start: u64, frames: u64) -> Self {
+        Fade {
+            from: self.volume(start),
+            to,
+            start,
+            frames,
+        }
+    }
+}
+
+/// Returns the number of frames from `position` to the next multiple of `period`.
+fn frames_to_boundary(position: u64, period: f64) -> u64 {
+    if period < 1.0 {
+        return 0;
+    }
+    let next = (position as f64 / period).ceil() * period;
+    (next - position as f64).round() as u64
+}
+
+struct ActiveStem {
+    name: Cow<'static, str>,
+    samples: UniformSourceIterator<AudioStream, f32>,
+    fade: Fade,
+    ended: bool,
+}
+
+struct ActiveTrack {
+    stems: Vec<ActiveStem>,
+    tempo: Option<Tempo>,
+    length: Option<u64>,
+    start: u64,
+    frames: u64,
+    fade: Fade,
+    stopping: bool,
+}
+
+impl ActiveTrack {
+    fn is_done(&self, clock: u64) -> bool {
+        self.stems.iter().all(|s| s.ended)
+            || (self.stopping && self.fade.to <= 0.0 && clock >= self.fade.end())
+    }
+}
+
+/// Mixes the tracks of a `MusicController` on the audio thread.
+struct MusicSource {
+    commands: Arc<Mutex<Vec<Command>>>,
+    status: Arc<MusicStatus>,
+    format: SampleFormat,
+    tracks: Vec<ActiveTrack>,
+    clock: u64,
+    frame: Vec<f32>,
+    sample_in_frame: usize,
+    frames_until_commands: u32,
+}
+
+impl MusicSource {
+    fn new(
+        commands: Arc<Mutex<Vec<Command>>>,
+        status: Arc<MusicStatus>,
+        format: SampleFormat,
+    ) -> Self {
+        let format = SampleFormat {
+            channels: format.channels.max(1),
+            sample_rate: format.sample_rate.max(1),
+        };
+        status
+            .sample_rate
+            .store(format.sample_rate, Ordering::Relaxed);
+        MusicSource {
+            commands,
+            status,
+            format,
+            tracks: Vec::new(),
+            clock: 0,
+            frame: vec![0.0; usize::from(format.channels)],
+            sample_in_frame: 0,
+            frames_until_commands: 0,
+        }
+    }
+
+    fn to_frames(&self, duration: Duration) -> u64 {
+        (duration.as_secs_f64() * f64::from(self.format.sample_rate)) as u64
+    }
+
+    /// Returns the frame a transition starts at.
+    fn transition_start(&self, quantize: Quantize) -> u64 {
+        let current = self.tracks.iter().rev().find(|t| !t.stopping);
+        let (track, tempo) = match current.and_then(|t| t.tempo.map(|tempo| (t, tempo))) {
+            Some(current) => current,
+            None => return self.clock,
+        };
+        let beat = 60.0 / f64::from(tempo.bpm.max(1.0)) * f64::from(self.format.sample_rate);
+        let period = match quantize {
+            Quantize::Immediate => return self.clock,
+            Quantize::Beat => beat,
+            Quantize::Bar => beat * f64::from(tempo.beats_per_bar.max(1)),
+        };
+        if track.start > self.clock {
+            // The track hasn't started yet, its first beat is at its start.
+            return track.start;
+        }
+        self.clock + frames_to_boundary(track.frames, period)
+    }
+
+    fn fade_out(&mut self, start: u64, frames: u64) {
+        for track in self.tracks.iter_mut().filter(|t| !t.stopping) {
+            track.stopping = true;
+            track.fade = track.fade.retarget(0.0, start.max(track.start), frames);
+        }
+    }
+
+    fn apply(&mut self, command: Command) {
+        match command {
+            Command::Play(track, transition) => {
+                let start = self.transition_start(transition.quantize);
+                let frames = self.to_frames(transition.fade);
+                self.fade_out(start, frames);
+                let format = self.format;
+                let stems = track
+                    .stems
+                    .into_iter()
+                    .map(|stem| {
+                        stem.stream.control().set_looping(track.looping);
+                        ActiveStem {
+                            name: stem.name,
+                            samples: UniformSourceIterator::new(
+                                stem.stream,
+                                format.channels,
+                                format.sample_rate,
+                            ),
+                            fade: Fade::constant(stem.volume),
+                            ended: false,
+                        }
+                    })
+                    .collect();
+                let length = if track.looping {
+                    None
+                } else {
+                    track.length.map(|length| self.to_frames(length))
+                };
+                self.tracks.push(ActiveTrack {
+                    stems,
+                    tempo: track.tempo,
+                    length,
+                    start,
+                    frames: 0,
+                    fade: Fade {
+                        from: 0.0,
+                        to: 1.0,
+                        start,
+                        frames,
+                    },
+                    stopping: false,
+                });
+            }
+            Command::Stop(transition) => {
+                let start = self.transition_start(transition.quantize);
+                let frames = self.to_frames(transition.fade);
+                self.fade_out(start, frames);
+            }
+            Command::StemVolume { stem, volume, fade } => {
+                let frames = self.to_frames(fade);
+                let clock = self.clock;
+                if let Some(track) = self.tracks.iter_mut().rev().find(|t| !t.stopping) {
+                    for s in track.stems.iter_mut().filter(|s| s.name == stem) {
+                        s.fade = s.fade.retarget(volume, clock.max(track.start), frames);
+                    }
+                }
+            }
+        }
+    }
+
+    fn update(&mut self) {
+        let queue = self.commands.clone();
+        // Hold the lock until the status is updated, so the controller always sees either
+        // the queued commands or their effect.
+        let mut commands = match queue.try_lock() {
+            Ok(commands) => commands,
+            Err(_) => return,
+        };
+        for command in mem::take(&mut *commands) {
+            self.apply(command);
+        }
+        let clock = self.clock;
+        self.tracks.retain(|t| !t.is_done(clock));
+
+        let current = self.tracks.iter().rev().find(|t| !t.stopping);
+        let transitioning = self
+            .tracks
+            .iter()
+            .any(|t| t.stopping || clock < t.fade.end());
+        self.status
+            .transitioning
+            .store(transitioning, Ordering::Relaxed);
+        self.status
+            .playing
+            .store(current.is_some(), Ordering::Relaxed);
+        self.status
+            .position
+            .store(current.map_or(0, |t| t.frames), Ordering::Relaxed);
+        let remaining = current
+            .and_then(|t| t.length.map(|length| length.saturating_sub(t.frames)))
+            .unwrap_or(u64::MAX);
+        self.status.remaining.store(remaining, Ordering::Relaxed);
+        drop(commands);
+    }
+
+    fn mix_frame(&mut self) {
+        if self.frames_until_commands == 0 {
+            self.frames_until_commands = COMMAND_INTERVAL;
+            self.update();
+        }
+        self.frames_until_commands -= 1;
+
+        for sample in self.frame.iter_mut() {
+            *sample = 0.0;
+        }
+        let clock = self.clock;
+        for track in self.tracks.iter_mut().filter(|t| clock >= t.start) {
+            let volume = track.fade.volume(clock);
+            for stem in track.stems.iter_mut().filter(|s| !s.ended) {
+                let volume = volume * stem.fade.volume(clock);
+                for sample in self.frame.iter_mut() {
+                    match stem.samples.next() {
+                        Some(s) => *sample += s * volume,
+                        None => {
+                            stem.ended = true;
+                            break;
+                        }
+                    }
+                }
+            }
+            track.frames += 1;
+        }
+        self.clock += 1;
+    }
+}
+
+impl Iterator for MusicSource {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if self.sample_in_frame == 0 {
+            self.mix_frame();
+        }
+        let sample = self.frame[self.sample_in_frame];
+        self.sample_in_frame = (self.sample_in_frame + 1) % self.frame.len();
+        Some(sample)
+    }
+}
+
+impl RSource for MusicSource {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        self.format.channels
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.format.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use amethyst_utils::app_root_dir::application_root_dir;
+
+    use super::*;
+
+    #[test]
+    fn fades_and_boundaries() {
+        let fade = Fade {
+            from: 0.0,
+            to: 1.0,
+            start: 10,
+            frames: 10,
+        };
+        assert_eq!(fade.volume(5), 0.0);
+        assert!((fade.volume(15) - 0.5).abs() < 1e-6);
+        assert_eq!(fade.volume(30), 1.0);
+        assert!((fade.retarget(0.0, 15, 10).from - 0.5).abs() < 1e-6);
+
+        assert_eq!(frames_to_boundary(0, 100.0), 0);
+        assert_eq!(frames_to_boundary(30, 100.0), 70);
+        assert_eq!(frames_to_boundary(250, 100.0), 50);
+    }
+
+    #[test]
+    fn transitions_wait_for_the_beat() {
+        let path = application_root_dir().unwrap().join("tests/sound_test.ogg");
+        let source = Source::new(fs::read(path).unwrap());
+        let commands = Arc::new(Mutex::new(Vec::new()));
+        let status = Arc::new(MusicStatus::default());
+        let format = SampleFormat {
+            channels: 2,
+            sample_rate: 1000,
+        };
+        let mut music = MusicSource::new(commands.clone(), status.clone(), format);
+
+        // 120 bpm at 1000 frames per second is a beat every 500 frames.
+        let first = MusicTrack::new(&source)
+            .unwrap()
+            .with_tempo(120.0, 4)
+            .looping();
+        commands
+            .lock()
+            .unwrap()
+            .push(Command::Play(first, Transition::immediate()));
+        music.by_ref().take(2 * 100).for_each(drop);
+        assert!(status.playing.load(Ordering::Relaxed));
+
+        let second = MusicTrack::new(&source).unwrap().looping();
+        commands.lock().unwrap().push(Command::Play(
+            second,
+            Transition::on_beat(Duration::from_millis(100)),
+        ));
+        music.by_ref().take(2 * 64).for_each(drop);
+        assert_eq!(music.tracks.len(), 2);
+        assert_eq!(music.tracks[1].start, 500);
+        assert!(status.transitioning.load(Ordering::Relaxed));
+
+        music.by_ref().take(2 * 600).for_each(drop);
+        assert_eq!(music.tracks.len(), 1);
+        assert!(!status.transitioning.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn detached_music_continues_where_it_was() {
+        let path = application_root_dir().unwrap().join("tests/sound_test.ogg");
+        let source = Source::new(fs::read(path).unwrap());
+        let format = SampleFormat {
+            channels: 2,
+            sample_rate: 1000,
+        };
+        let mut controller = MusicController::new();
+        controller.play(
+            MusicTrack::new(&source).unwrap().looping(),
+            Transition::immediate(),
+        );
+        controller.set_stem_volume(MAIN_STEM, 0.5, Duration::from_secs(0));
+        let mut music = MusicSource::new(
+            controller.commands.clone(),
+            controller.status.clone(),
+            format,
+        );
+        music.by_ref().take(2 * 500).for_each(drop);
+        let position = controller.current.as_ref().unwrap().stems[0]
+            .control
+            .position();
+        assert!(position > Duration::from_secs(0));
+
+        controller.detach();
+        let mut commands = mem::take(&mut *controller.commands.lock().unwrap());
+        assert_eq!(commands.len(), 1);
+        match commands.pop() {
+            Some(Command::Play(mut track, _)) => {
+                let mut stem = track.stems.remove(0);
+                assert_eq!(stem.volume, 0.5);
+                assert!(track.looping);
+                let control = stem.stream.control();
+                stem.stream.next();
+                // up to a frame is lost converting the position to a duration and back
+                assert!(control.position() + Duration::from_millis(1) >= position);
+            }
+            _ => panic!("The music wasn't queued again"),
+        }
+    }
+}
@@ -0,0 +1,256 @@
+//! Attenuation of sounds blocked by level geometry.
+
+use std::sync::{
+    atomic::{AtomicU32, Ordering},
+    Arc,
+};
+
+use amethyst_core::{
+    ecs::*,
+    math::{center, Point3},
+    transform::Transform,
+};
+use rodio::Source as RSource;
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+
+use crate::{
+    components::{AudioEmitter, AudioListener},
+    effects::{EffectSource, LowPass, Processor},
+    mixer::MixSource,
+    systems::SelectedListener,
+};
+
+/// Cutoff frequency used for sounds that aren't occluded at all.
+const OPEN_CUTOFF: f32 = 20000.0;
+
+/// Number of frames between two reads of the occlusion of an emitter.
+const UPDATE_INTERVAL: u32 = 64;
+
+/// Answers how much level geometry is in the way between two points, usually by raycasting
+/// against the physics or collision world of the game.
+pub trait OcclusionGeometry: Send + Sync + 'static {
+    /// Returns how much the path from the listener to the emitter is blocked, from 0.0 for
+    /// a clear line of sight to 1.0 for a fully blocked path.
+    fn occlusion(&self, listener: &Point3<f32>, emitter: &Point3<f32>) -> f32;
+}
+
+impl<F> OcclusionGeometry for F
+where
+    F: Fn(&Point3<f32>, &Point3<f32>) -> f32 + Send + Sync + 'static,
+{
+    fn occlusion(&self, listener: &Point3<f32>, emitter: &Point3<f32>) -> f32 {
+        self(listener, emitter)
+    }
+}
+
+/// How an emitter reacts to being occluded.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Occlusion {
+    /// If false, the emitter is never occluded.
+    pub enabled: bool,
+    /// Volume multiplier of a fully occluded emitter.
+    pub volume: f32,
+    /// Low-pass cutoff frequency in Hz of a fully occluded emitter.
+    pub cutoff: f32,
+}
+
+impl Default for Occlusion {
+    fn default() -> Self {
+        Occlusion {
+            enabled: true,
+            volume: 0.5,
+            cutoff: 800.0,
+        }
+    }
+}
+
+impl Occlusion {
+    /// Returns the volume multiplier and low-pass cutoff for the given amount of occlusion.
+    pub fn attenuation(&self, amount: f32) -> (f32, f32) {
+        if !self.enabled {
+            return (1.0, OPEN_CUTOFF);
+        }
+        let amount = amount.max(0.0).min(1.0);
+        let volume = 1.0 + (self.volume - 1.0) * amount;
+        let cutoff = OPEN_CUTOFF * (self.cutoff.max(10.0) / OPEN_CUTOFF).powf(amount);
+        (volume, cutoff)
+    }
+}
+
+/// Current attenuation of an emitter, shared with its playing sounds.
+#[derive(Debug)]
+pub(crate) struct OcclusionState {
+    volume: AtomicU32,
+    cutoff: AtomicU32,
+}
+
+impl Default for OcclusionState {
+    fn default() -> Self {
+        OcclusionState {
+            volume: AtomicU32::new(1.0f32.to_bits()),
+            cutoff: AtomicU32::new(OPEN_CUTOFF.to_bits()),
+        }
+    }
+}
+
+impl OcclusionState {
+    fn set(&self, (volume, cutoff): (f32, f32)) {
+        self.volume.store(volume.to_bits(), Ordering::Relaxed);
+        self.cutoff.store(cutoff.to_bits(), Ordering::Relaxed);
+    }
+
+    fn get(&self) -> (f32, f32) {
+        (
+            f32::from_bits(self.volume.load(Ordering::Relaxed)),
+            f32::from_bits(self.cutoff.load(Ordering::Relaxed)),
+        )
+    }
+
+    /// Runs a sound through the attenuation of its emitter.
+    pub(crate) fn apply(self: &Arc<Self>, source: MixSource) -> MixSource {
+        let channels = source.channels().max(1) as usize;
+        let rate = source.sample_rate();
+        Box::new(EffectSource::new(
+            source,
+            Occluder::new(self.clone(), rate, channels),
+        ))
+    }
+}
+
+/// Applies the attenuation of an emitter to one of its sounds.
+struct Occluder {
+    state: Arc<OcclusionState>,
+    low_pass: LowPass,
+    rate: u32,
+    volume: f32,
+    step: f32,
+    filtering: bool,
+    frames_until_update: u32,
+}
+
+impl Occluder {
+    fn new(state: Arc<OcclusionState>, rate: u32, channels: usize) -> Self {
+        let (volume, cutoff) = state.get();
+        Occluder {
+            low_pass: LowPass::new(cutoff, rate, channels),
+            filtering: cutoff < OPEN_CUTOFF,
+            state,
+            rate,
+            volume,
+            step: 0.0,
+            frames_until_update: UPDATE_INTERVAL,
+        }
+    }
+}
+
+impl Processor for Occluder {
+    fn process(&mut self, channel: usize, input: f32) -> f32 {
+        if channel == 0 {
+            self.volume += self.step;
+            self.frames_until_update -= 1;
+            if self.frames_until_update == 0 {
+                self.frames_until_update = UPDATE_INTERVAL;
+                let (volume, cutoff) = self.state.get();
+                self.step = (volume - self.volume) / UPDATE_INTERVAL as f32;
+                self.filtering = cutoff < OPEN_CUTOFF;
+                self.low_pass.set_cutoff(cutoff, self.rate);
+            }
+        }
+        // The filter keeps running while open, so it doesn't click when closing again.
+        let filtered = self.low_pass.process(channel, input);
+        let output = if self.filtering { filtered } else { input };
+        output * self.volume
+    }
+
+    fn tail_frames(&self) -> u64 {
+        0
+    }
+}
+
+/// Updates the occlusion of all emitters relative to the selected `AudioListener`.
+///
+/// The system asks the given `OcclusionGeometry` for the path between the listener and every
+/// emitter, so add it after the systems which move entities.
+#[allow(missing_debug_implementations)]
+pub struct OcclusionSystem<G> {
+    geometry: G,
+}
+
+impl<G: OcclusionGeometry> OcclusionSystem<G> {
+    /// Creates a system occluding emitters with the given geometry.
+    pub fn new(geometry: G) -> Self {
+        OcclusionSystem { geometry }
+    }
+}
+
+impl<G: OcclusionGeometry> System for OcclusionSystem<G> {
+    fn build(self) -> Box<dyn ParallelRunnable> {
+        let geometry = self.geometry;
+        Box::new(
+            SystemBuilder::new("OcclusionSystem")
+                .read_resource::<SelectedListener>()
+                .with_query(<(Entity, Read<AudioListener>, Read<Transform>)>::query())
+                .with_query(<(Read<AudioEmitter>, Read<Transform>)>::query())
+                .build(
+                    move |_commands, world, select_listener, (q_listener, q_emitter)| {
+                        #[cfg(feature = "profiler")]
+                        profile_scope!("occlusion_system");
+
+                        let listener = q_listener
+                            .iter(world)
+                            .find(|(e, _, _)| select_listener.0.map_or(true, |s| s == **e))
+                            .map(|(_, listener, transform)| {
+                                let matrix = transform.global_matrix();
+                                center(
+                                    &matrix.transform_point(&listener.left_ear),
+                                    &matrix.transform_point(&listener.right_ear),
+                                )
+                            });
+                        let listener = match listener {
+                            Some(listener) => listener,
+                            None => return,
+                        };
+
+                        for (emitter, transform) in q_emitter.iter(world) {
+                            let amount = if emitter.occlusion.enabled {
+                                let position =
+                                    Point3::from(transform.global_matrix().column(3).xyz());
+                                geometry.occlusion(&listener, &position)
+                            } else {
+                                0.0
+                            };
+                            emitter
+                                .occlusion_state
+                                .set(emitter.occlusion.attenuation(amount));
+                        }
+                    },
+                ),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn attenuation_follows_amount() {
+        let occlusion = Occlusion::default();
+        assert_eq!(occlusion.attenuation(0.0), (1.0, OPEN_CUTOFF));
+        let (volume, cutoff) = occlusion.attenuation(1.0);
+        assert!((volume - 0.5).abs() < 1e-6);
+        assert!((cutoff - 800.0).abs() < 1e-2);
+        let (volume, cutoff) = occlusion.attenuation(0.5);
+        assert!((volume - 0.75).abs() < 1e-6);
+        assert!((cutoff - 4000.0).abs() < 1.0);
+
+        let disabled = Occlusion {
+            enabled: false,
+            ..Default::default()
+        };
+        assert_eq!(disabled.attenuation(1.0), (1.0, OPEN_CUTOFF));
+    }
+}
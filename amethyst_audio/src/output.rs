@@ -3,16 +3,21 @@
 // We have to use types from this to provide an output iterator type.
 use std::{
     fmt::{Debug, Formatter, Result as FmtResult},
-    io::Cursor,
     sync::Arc,
+    time::Duration,
 };
 
 use amethyst_core::ecs::Resources;
 use cpal::{traits::DeviceTrait, Devices, OutputDevices};
 use log::error;
-use rodio::{default_output_device, output_devices, Decoder, Device, Sink, Source as RSource};
+use rodio::{default_output_device, output_devices, Device, Sink, Source as RSource};
 
-use crate::{sink::AudioSink, source::Source, DecoderError};
+use crate::{
+    mixer::{Mixer, MUSIC_BUS},
+    sink::AudioSink,
+    source::Source,
+    DecoderError,
+};
 
 #[derive(Default)]
 #[allow(missing_debug_implementations)]
@@ -24,6 +29,71 @@ pub struct OutputWrapper {
     pub audio_sink: Option<AudioSink>,
 }
 
+impl OutputWrapper {
+    /// Plays everything through the given output from now on.
+    ///
+    /// The `AudioSink` is replaced by one on the new output, which keeps the volume and bus
+    /// of the previous one but not its queued music.
+    pub fn switch_output(&mut self, output: Output, mixer: &Mixer) {
+        let mut sink = AudioSink::new(&output);
+        match &self.audio_sink {
+            Some(previous) => {
+                sink.set_volume(previous.volume());
+                if let Some(bus) = previous.bus() {
+                    sink.set_bus(bus.clone());
+                }
+            }
+            None => {
+                if let Some(bus) = mixer.bus(MUSIC_BUS) {
+                    sink.set_bus(bus.clone());
+                }
+            }
+        }
+        self.audio_sink = Some(sink);
+        self.output = Some(output);
+    }
+}
+
+/// Selects the output device the `OutputSystem` plays on.
+///
+/// When the selected device is missing, e.g. because it was unplugged, the default device of
+/// the system is used until it comes back.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutputSelection {
+    /// Name of the output to play on, as returned by `Output::name`, or `None` to follow the
+    /// default device of the system.
+    pub device: Option<String>,
+    /// How often the `OutputSystem` checks whether devices were added or removed.
+    pub check_interval: Duration,
+}
+
+impl Default for OutputSelection {
+    fn default() -> Self {
+        OutputSelection {
+            device: None,
+            check_interval: Duration::from_secs(1),
+        }
+    }
+}
+
+impl OutputSelection {
+    /// Selects the output with the given name.
+    pub fn named<N: Into<String>>(name: N) -> Self {
+        OutputSelection {
+            device: Some(name.into()),
+            ..Default::default()
+        }
+    }
+
+    /// Returns the output which should currently be played on, if there is one.
+    pub fn find(&self) -> Option<Output> {
+        self.device
+            .as_ref()
+            .and_then(|name| find_output(name))
+            .or_else(default_output)
+    }
+}
+
 /// A speaker(s) through which audio can be played.
 ///
 /// By convention, the default output is stored as a resource in the `World`.
@@ -92,7 +162,7 @@ impl Output {
     ) -> Result<(), DecoderError> {
         let sink = Sink::new(&self.device);
         for _ in 0..n {
-            sink.append(Decoder::new(Cursor::new(source.clone()))?.amplify(volume));
+            sink.append(source.samples()?.amplify(volume));
         }
         sink.detach();
         Ok(())
@@ -141,17 +211,38 @@ pub fn outputs() -> OutputIterator {
     OutputIterator { devices }
 }
 
+/// Get the output with the given name, returns none if it isn't available.
+pub fn find_output(name: &str) -> Option<Output> {
+    match output_devices() {
+        Ok(devices) => OutputIterator { devices }.find(|output| output.name() == name),
+        Err(e) => {
+            error!("Error retrieving output devices: `{}`", e);
+            None
+        }
+    }
+}
+
 /// Initialize default output
+///
+/// The `AudioSink` is routed into the `MUSIC_BUS` of the `Mixer`, which is added if missing.
 pub fn init_output(res: &mut Resources) {
     if !res.contains::<OutputWrapper>() {
         res.insert(OutputWrapper::default());
     }
+    if !res.contains::<Mixer>() {
+        res.insert(Mixer::default());
+    }
 
+    let mixer = res.get::<Mixer>().unwrap();
     let mut wrapper = res.get_mut::<OutputWrapper>().unwrap();
 
     if let Some(o) = default_output() {
         if wrapper.audio_sink.is_none() {
-            wrapper.audio_sink = Some(AudioSink::new(&o));
+            let mut sink = AudioSink::new(&o);
+            if let Some(bus) = mixer.bus(MUSIC_BUS) {
+                sink.set_bus(bus.clone());
+            }
+            wrapper.audio_sink = Some(sink);
         }
         if wrapper.output.is_none() {
             wrapper.output = Some(o);
@@ -207,7 +298,7 @@ mod tests {
         f.read_to_end(&mut buffer).unwrap();
 
         // Create a Source from those bytes
-        let src = Source { bytes: buffer };
+        let src = Source::new(buffer);
 
         // Set volume and number of times to play
         let vol: f32 = 4.0;
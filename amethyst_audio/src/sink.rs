@@ -1,8 +1,13 @@
-use std::io::Cursor;
+use rodio::{Sink, Source as RSource};
 
-use rodio::{Decoder, Sink};
-
-use crate::{output::Output, source::Source, DecoderError};
+use crate::{
+    event::{EventQueue, Marker, SoundId, TrackedSound},
+    mixer::Bus,
+    output::Output,
+    source::Source,
+    stream::AudioStream,
+    DecoderError,
+};
 
 /// This structure provides a way to programmatically pick and play music.
 // TODO: This needs a proper debug implementeation. This should probably propigate up to a TODO
@@ -10,6 +15,8 @@ use crate::{output::Output, source::Source, DecoderError};
 #[allow(missing_debug_implementations)]
 pub struct AudioSink {
     sink: Sink,
+    bus: Option<Bus>,
+    pub(crate) events: EventQueue,
 }
 
 impl AudioSink {
@@ -17,14 +24,60 @@ impl AudioSink {
     pub fn new(output: &Output) -> AudioSink {
         AudioSink {
             sink: Sink::new(&output.device),
+            bus: None,
+            events: EventQueue::default(),
         }
     }
 
+    /// Returns the mixer bus music is routed into, if any.
+    pub fn bus(&self) -> Option<&Bus> {
+        self.bus.as_ref()
+    }
+
+    /// Routes music appended from now on into the given mixer bus.
+    pub fn set_bus(&mut self, bus: Bus) {
+        self.bus = Some(bus);
+    }
+
     /// Adds a source to the sink's queue of music to play.
     pub fn append(&self, source: &Source) -> Result<(), DecoderError> {
-        self.sink
-            .append(Decoder::new(Cursor::new(source.clone())).map_err(|_| DecoderError)?);
-        Ok(())
+        self.append_with_markers(source, Vec::new()).map(|_| ())
+    }
+
+    /// Adds a source to the sink's queue of music to play, sending an `AudioEvent::Marker`
+    /// whenever playback passes one of the markers.
+    pub fn append_with_markers(
+        &self,
+        source: &Source,
+        markers: Vec<Marker>,
+    ) -> Result<SoundId, DecoderError> {
+        let samples = source.samples()?;
+        Ok(self.append_sound(TrackedSound::new(samples, markers, None)))
+    }
+
+    /// Adds a stream to the sink's queue of music to play, see `AudioStream`.
+    pub fn append_stream(&self, stream: AudioStream) -> SoundId {
+        self.append_stream_with_markers(stream, Vec::new())
+    }
+
+    /// Adds a stream to the sink's queue of music to play, sending an `AudioEvent::Marker`
+    /// whenever playback passes one of the markers, including after the stream looped.
+    pub fn append_stream_with_markers(&self, stream: AudioStream, markers: Vec<Marker>) -> SoundId {
+        let control = stream.control();
+        self.append_sound(TrackedSound::new(stream, markers, Some(control)))
+    }
+
+    fn append_sound<S>(&self, sound: TrackedSound<S>) -> SoundId
+    where
+        S: RSource<Item = i16> + Send + 'static,
+    {
+        let id = sound.id;
+        let source = sound.track(None, self.events.clone());
+        match &self.bus {
+            Some(bus) => self.sink.append(bus.route(source.convert_samples::<f32>())),
+            None => self.sink.append(source),
+        }
+        id
     }
 
     /// Returns true if the sink has no more music to play.
@@ -85,7 +138,7 @@ mod tests {
         f.read_to_end(&mut buffer).unwrap();
 
         // Create a Source from those bytes
-        let src = Source { bytes: buffer };
+        let src = Source::new(buffer);
 
         // Create a Output and AudioSink
         let output = Output::default();
@@ -1,9 +1,24 @@
 //! Provides structures used to load audio files.
-use amethyst_assets::{Asset, AssetStorage, Handle, LoadHandle, ProcessableAsset, ProcessingState};
+use std::{
+    fmt::{Debug, Formatter, Result as FmtResult},
+    io::Cursor,
+    sync::Arc,
+    time::Duration,
+};
+
+use amethyst_assets::{
+    Asset, AssetStorage, Handle, LoadHandle, ProcessableAsset, ProcessingQueue, ProcessingState,
+};
+use amethyst_core::ecs::*;
 use amethyst_error::Error;
+use cpal::traits::DeviceTrait;
+use log::warn;
+use rodio::{default_output_device, source::UniformSourceIterator, Decoder, Source as RSource};
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
 use type_uuid::TypeUuid;
 
-use crate::formats::AudioData;
+use crate::{formats::AudioData, DecoderError};
 
 /// A handle to a source asset.
 pub type SourceHandle = Handle<Source>;
@@ -14,6 +29,50 @@ pub type SourceHandle = Handle<Source>;
 pub struct Source {
     /// The bytes of this audio source.
     pub bytes: Vec<u8>,
+    /// Samples decoded while loading, played instead of decoding the bytes again.
+    pub(crate) decoded: Option<Decoded>,
+}
+
+impl Source {
+    /// Creates a source from the bytes of an audio file, which are decoded whenever it plays.
+    pub fn new(bytes: Vec<u8>) -> Self {
+        Source {
+            bytes,
+            decoded: None,
+        }
+    }
+
+    /// Creates a source which is decoded right away, converted to the given format if any.
+    ///
+    /// Decoded sources start playing without any delay, at the cost of keeping all of their
+    /// samples in memory.
+    pub fn preloaded(bytes: Vec<u8>, format: Option<SampleFormat>) -> Result<Self, DecoderError> {
+        let decoded = Decoded::new(&bytes, format)?;
+        Ok(Source {
+            bytes,
+            decoded: Some(decoded),
+        })
+    }
+
+    /// Returns the format this source was decoded to while loading, if it was.
+    pub fn decoded_format(&self) -> Option<SampleFormat> {
+        self.decoded.as_ref().map(|decoded| decoded.format)
+    }
+
+    /// Returns the length of this source, if it was decoded while loading.
+    pub fn duration(&self) -> Option<Duration> {
+        self.decoded.as_ref().map(|decoded| decoded.duration())
+    }
+
+    /// Returns the samples of this source, decoding it unless it was preloaded.
+    pub(crate) fn samples(
+        &self,
+    ) -> Result<Box<dyn RSource<Item = i16> + Send + Sync>, DecoderError> {
+        match &self.decoded {
+            Some(decoded) => Ok(Box::new(DecodedSource::new(decoded.clone()))),
+            None => Ok(Box::new(Decoder::new(Cursor::new(self.clone()))?)),
+        }
+    }
 }
 
 impl AsRef<[u8]> for Source {
@@ -35,6 +94,226 @@ impl ProcessableAsset for Source {
         _: &mut AssetStorage<Source>,
         _: &LoadHandle,
     ) -> Result<ProcessingState<AudioData, Source>, Error> {
-        Ok(ProcessingState::Loaded(Source { bytes: data.0 }))
+        Ok(ProcessingState::Loaded(load(data, None)))
+    }
+}
+
+/// Channel count and sample rate of interleaved samples.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SampleFormat {
+    /// Number of channels of every frame.
+    pub channels: u16,
+    /// Number of frames per second.
+    pub sample_rate: u32,
+}
+
+impl SampleFormat {
+    /// Returns the format sounds are mixed in by the default output device, if there is one.
+    pub fn default_output() -> Option<SampleFormat> {
+        let format = default_output_device()?.default_output_format().ok()?;
+        Some(SampleFormat {
+            channels: format.channels,
+            sample_rate: format.sample_rate.0,
+        })
+    }
+}
+
+/// Decoded samples of a sound, shared by all of its playbacks.
+#[derive(Clone, PartialEq, Eq)]
+pub(crate) struct Decoded {
+    format: SampleFormat,
+    samples: Arc<[i16]>,
+}
+
+impl Decoded {
+    fn new(bytes: &[u8], format: Option<SampleFormat>) -> Result<Self, DecoderError> {
+        let decoder = Decoder::new(Cursor::new(bytes.to_vec()))?;
+        let format = format.unwrap_or_else(|| {
+            SampleFormat {
+                channels: decoder.channels(),
+                sample_rate: decoder.sample_rate(),
+            }
+        });
+        let samples: Vec<i16> =
+            UniformSourceIterator::new(decoder, format.channels, format.sample_rate).collect();
+        Ok(Decoded {
+            format,
+            samples: samples.into(),
+        })
+    }
+
+    fn duration(&self) -> Duration {
+        let frames = self.samples.len() as u64 / u64::from(self.format.channels.max(1));
+        Duration::from_secs_f64(frames as f64 / f64::from(self.format.sample_rate.max(1)))
+    }
+}
+
+impl Debug for Decoded {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_struct("Decoded")
+            .field("format", &self.format)
+            .field("samples", &self.samples.len())
+            .finish()
+    }
+}
+
+/// Plays back the samples of a preloaded source.
+struct DecodedSource {
+    decoded: Decoded,
+    position: usize,
+}
+
+impl DecodedSource {
+    fn new(decoded: Decoded) -> Self {
+        DecodedSource {
+            decoded,
+            position: 0,
+        }
+    }
+}
+
+impl Iterator for DecodedSource {
+    type Item = i16;
+
+    fn next(&mut self) -> Option<i16> {
+        let sample = self.decoded.samples.get(self.position).copied();
+        self.position += 1;
+        sample
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.decoded.samples.len().saturating_sub(self.position);
+        (remaining, Some(remaining))
+    }
+}
+
+impl RSource for DecodedSource {
+    fn current_frame_len(&self) -> Option<usize> {
+        Some(self.decoded.samples.len().saturating_sub(self.position))
+    }
+
+    fn channels(&self) -> u16 {
+        self.decoded.format.channels
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.decoded.format.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        Some(self.decoded.duration())
+    }
+}
+
+/// Creates the source of a loaded sound, decoded if it asks to be preloaded.
+fn load(data: AudioData, format: Option<SampleFormat>) -> Source {
+    if data.preload {
+        preload(data.bytes, format)
+    } else {
+        Source::new(data.bytes)
+    }
+}
+
+/// Decodes the bytes of a sound, keeping them undecoded if that fails so that playing the
+/// source reports the error.
+fn preload(bytes: Vec<u8>, format: Option<SampleFormat>) -> Source {
+    match Decoded::new(&bytes, format) {
+        Ok(decoded) => {
+            Source {
+                bytes,
+                decoded: Some(decoded),
+            }
+        }
+        Err(_) => {
+            warn!("Failed to decode audio source, it will be decoded again when played");
+            Source::new(bytes)
+        }
+    }
+}
+
+/// Asset processing system for `Source` asset type.
+///
+/// Sources imported with `preload` set in their format options are decoded and converted to
+/// the format of the default output device while loading, so that playing them doesn't have to
+/// decode or resample anything. The others are decoded whenever they play.
+#[derive(Debug, Default)]
+pub struct SourceProcessorSystem;
+
+impl System for SourceProcessorSystem {
+    fn build(self) -> Box<dyn ParallelRunnable> {
+        // The device is only queried once the first source is loaded.
+        let mut format = None;
+        Box::new(
+            SystemBuilder::new("SourceProcessorSystem")
+                .write_resource::<ProcessingQueue<AudioData>>()
+                .write_resource::<AssetStorage<Source>>()
+                .build(move |_, _, (queue, storage), _| {
+                    #[cfg(feature = "profiler")]
+                    profile_scope!("source_processor");
+
+                    queue.process(storage, |data, _, _| {
+                        #[cfg(feature = "profiler")]
+                        profile_scope!("process_source");
+
+                        let format = if data.preload {
+                            *format.get_or_insert_with(SampleFormat::default_output)
+                        } else {
+                            None
+                        };
+                        Ok(ProcessingState::Loaded(load(data, format)))
+                    });
+                    storage.process_custom_drop(|_| {});
+                }),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use amethyst_assets::Format;
+    use amethyst_utils::app_root_dir::application_root_dir;
+
+    use super::*;
+    use crate::OggFormat;
+
+    #[test]
+    fn preloaded_source_is_converted() {
+        let path = application_root_dir().unwrap().join("tests/sound_test.ogg");
+        let bytes = fs::read(path).unwrap();
+        let format = SampleFormat {
+            channels: 2,
+            sample_rate: 22050,
+        };
+
+        let source = Source::preloaded(bytes.clone(), Some(format)).unwrap();
+        assert_eq!(source.decoded_format(), Some(format));
+        let samples = source.samples().unwrap();
+        assert_eq!(samples.channels(), 2);
+        assert_eq!(samples.sample_rate(), 22050);
+
+        let first: Vec<i16> = samples.collect();
+        let second: Vec<i16> = source.samples().unwrap().collect();
+        assert!(!first.is_empty());
+        assert_eq!(first, second);
+
+        assert!(Source::preloaded(b"not a sound".to_vec(), None).is_err());
+        assert_eq!(
+            preload(b"not a sound".to_vec(), None).decoded_format(),
+            None
+        );
+    }
+
+    #[test]
+    fn sources_are_only_preloaded_on_request() {
+        let path = application_root_dir().unwrap().join("tests/sound_test.ogg");
+        let bytes = fs::read(path).unwrap();
+
+        let data = OggFormat::default().import_simple(bytes.clone()).unwrap();
+        assert_eq!(load(data, None).decoded_format(), None);
+
+        let data = OggFormat { preload: true }.import_simple(bytes).unwrap();
+        assert!(load(data, None).decoded_format().is_some());
     }
 }
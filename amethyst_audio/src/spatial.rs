@@ -0,0 +1,528 @@
+//! Positioning of emitted sounds relative to the listener.
+
+use std::{
+    f32::consts::{FRAC_PI_4, PI},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use amethyst_core::math::{Point3, Vector3};
+use rodio::{Sample, Source};
+use serde::{Deserialize, Serialize};
+
+/// Speed of sound in world units per second, assuming a world unit is one meter.
+pub const SPEED_OF_SOUND: f32 = 343.0;
+
+/// Number of frames between two reads of the spatial parameters, parameter changes are ramped
+/// over this many frames to avoid clicks.
+const UPDATE_INTERVAL: u32 = 64;
+
+/// Length of the per-ear delay line, enough for the interaural delay of a human head at 192kHz.
+const DELAY_LINE_LEN: usize = 256;
+
+/// Cutoff frequency of the head shadow on the ear facing away from a sound at its side.
+const SHADOW_CUTOFF: f32 = 1500.0;
+
+/// Range of the doppler pitch shift, which keeps sounds recognizable at extreme velocities.
+const MIN_PITCH: f32 = 0.25;
+const MAX_PITCH: f32 = 4.0;
+
+/// Scales the doppler pitch shift of all emitters, add this as a resource to change it.
+///
+/// 1.0 is physically accurate, 0.0 disables the doppler effect.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DopplerFactor(pub f32);
+
+impl Default for DopplerFactor {
+    fn default() -> Self {
+        DopplerFactor(1.0)
+    }
+}
+
+/// Returns the pitch multiplier of an emitter heard by a listener, given their velocities.
+pub(crate) fn doppler_pitch(
+    listener: Point3<f32>,
+    listener_velocity: Vector3<f32>,
+    emitter: Point3<f32>,
+    emitter_velocity: Vector3<f32>,
+    factor: f32,
+) -> f32 {
+    let direction = match (listener - emitter).try_normalize(std::f32::EPSILON) {
+        Some(direction) if factor > 0.0 => direction,
+        _ => return 1.0,
+    };
+    // Neither may move away from the other faster than sound travels.
+    let max = SPEED_OF_SOUND / factor;
+    let listener_speed = listener_velocity.dot(&direction).min(max);
+    let emitter_speed = emitter_velocity.dot(&direction).min(max);
+    let pitch = (SPEED_OF_SOUND - factor * listener_speed)
+        / (SPEED_OF_SOUND - factor * emitter_speed).max(std::f32::EPSILON);
+    pitch.max(MIN_PITCH).min(MAX_PITCH)
+}
+
+/// How the volume of an emitter drops with its distance to the listener.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DistanceModel {
+    /// The volume doesn't depend on the distance.
+    None,
+    /// The volume drops linearly, reaching silence at the maximum distance.
+    Linear,
+    /// The volume is inversely proportional to the distance, like sound in open air.
+    Inverse,
+    /// The volume drops with the distance raised to the power of the rolloff factor.
+    Exponential,
+}
+
+impl Default for DistanceModel {
+    fn default() -> Self {
+        DistanceModel::Inverse
+    }
+}
+
+/// Distance attenuation of a single emitter.
+///
+/// Within `reference_distance` sounds play at full volume, and beyond `max_distance` the
+/// volume stops changing.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Rolloff {
+    /// The attenuation curve.
+    pub model: DistanceModel,
+    /// Distance up to which the sound plays at full volume.
+    pub reference_distance: f32,
+    /// Distance after which the sound doesn't get any quieter.
+    pub max_distance: f32,
+    /// How quickly the volume drops, 1 is physically accurate for `DistanceModel::Inverse`.
+    pub factor: f32,
+}
+
+impl Default for Rolloff {
+    fn default() -> Self {
+        Rolloff {
+            model: DistanceModel::default(),
+            reference_distance: 1.0,
+            max_distance: 100.0,
+            factor: 1.0,
+        }
+    }
+}
+
+impl Rolloff {
+    /// Returns the volume multiplier for a sound at the given distance, between 0.0 and 1.0.
+    pub fn gain(&self, distance: f32) -> f32 {
+        let reference = self.reference_distance.max(std::f32::EPSILON);
+        let max = self.max_distance.max(reference);
+        let distance = distance.max(reference).min(max);
+        let gain = match self.model {
+            DistanceModel::None => 1.0,
+            DistanceModel::Linear if max > reference => {
+                1.0 - self.factor * (distance - reference) / (max - reference)
+            }
+            DistanceModel::Linear => 1.0,
+            DistanceModel::Inverse => {
+                reference / (reference + self.factor * (distance - reference))
+            }
+            DistanceModel::Exponential => (distance / reference).powf(-self.factor),
+        };
+        gain.max(0.0).min(1.0)
+    }
+}
+
+/// How sounds are placed between the ears of the listener, `Panning` by default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SpatialMode {
+    /// Equal power stereo panning, which works on speakers as well as headphones.
+    Panning,
+    /// Interaural time and level differences for headphones, which have to be chosen on the
+    /// `AudioListener`.
+    ///
+    /// The ear away from the sound receives it later and quieter, through a low-pass filter,
+    /// with the delay of a spherical head following Woodworth's formula and a head shadow
+    /// growing with the angle. This is a simple model of the horizontal direction of the sound,
+    /// no HRTF is used, so elevation and front/back cues are not modelled.
+    Interaural,
+}
+
+impl Default for SpatialMode {
+    fn default() -> Self {
+        SpatialMode::Panning
+    }
+}
+
+/// Positions shared between the `AudioSystem` and a playing sound.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct SpatialParams {
+    pub emitter: Point3<f32>,
+    pub left_ear: Point3<f32>,
+    pub right_ear: Point3<f32>,
+    pub rolloff: Rolloff,
+    pub mode: SpatialMode,
+    pub head_radius: f32,
+    /// Playback speed caused by the doppler effect.
+    pub pitch: f32,
+}
+
+impl SpatialParams {
+    /// Computes the gain, delay and filter of both ears.
+    fn ears(&self, sample_rate: u32) -> [Ear; 2] {
+        let center = Point3::from((self.left_ear.coords + self.right_ear.coords) * 0.5);
+        let axis = (self.right_ear - self.left_ear)
+            .try_normalize(std::f32::EPSILON)
+            .unwrap_or_else(Vector3::x);
+        let offset = self.emitter - center;
+        let distance = offset.norm();
+        let gain = self.rolloff.gain(distance);
+        // Sine of the angle between the sound and the median plane, positive on the right.
+        let lateral = if distance > std::f32::EPSILON {
+            (offset.dot(&axis) / distance).max(-1.0).min(1.0)
+        } else {
+            0.0
+        };
+
+        match self.mode {
+            SpatialMode::Panning => {
+                let angle = (lateral + 1.0) * FRAC_PI_4;
+                [Ear::open(gain * angle.cos()), Ear::open(gain * angle.sin())]
+            }
+            SpatialMode::Interaural => {
+                let shadow = lateral.abs();
+                // Woodworth's formula for the extra path around a spherical head.
+                let itd = self.head_radius.max(0.0) / SPEED_OF_SOUND * (shadow.asin() + shadow);
+                let delay = (itd * sample_rate as f32).min(DELAY_LINE_LEN as f32 - 2.0);
+                let shadowed = 1.0 - (-2.0 * PI * SHADOW_CUTOFF / sample_rate as f32).exp();
+                let far = Ear {
+                    gain: gain * (1.0 - 0.3 * shadow),
+                    delay,
+                    coefficient: 1.0 + (shadowed - 1.0) * shadow,
+                };
+                let near = Ear::open(gain);
+                if lateral >= 0.0 {
+                    [far, near]
+                } else {
+                    [near, far]
+                }
+            }
+        }
+    }
+}
+
+/// Processing of a single ear.
+#[derive(Debug, Clone, Copy, Default)]
+struct Ear {
+    gain: f32,
+    /// Delay in frames.
+    delay: f32,
+    /// Coefficient of the one pole low-pass filter, 1.0 lets everything through.
+    coefficient: f32,
+}
+
+impl Ear {
+    fn open(gain: f32) -> Self {
+        Ear {
+            gain,
+            delay: 0.0,
+            coefficient: 1.0,
+        }
+    }
+
+    fn step_towards(&self, target: &Ear, frames: u32) -> Ear {
+        let frames = frames as f32;
+        Ear {
+            gain: (target.gain - self.gain) / frames,
+            delay: (target.delay - self.delay) / frames,
+            coefficient: (target.coefficient - self.coefficient) / frames,
+        }
+    }
+
+    fn advance(&mut self, step: &Ear) {
+        self.gain += step.gain;
+        self.delay += step.delay;
+        self.coefficient += step.coefficient;
+    }
+}
+
+/// Turns any source into a stereo source placed according to shared `SpatialParams`.
+pub(crate) struct SpatialSource<I>
+where
+    I: Source,
+    I::Item: Sample,
+{
+    input: I,
+    params: Arc<Mutex<SpatialParams>>,
+    history: [f32; DELAY_LINE_LEN],
+    write: usize,
+    ears: Option<[Ear; 2]>,
+    steps: [Ear; 2],
+    filtered: [f32; 2],
+    frames_until_update: u32,
+    pending: Option<f32>,
+    /// The two input frames the output is currently interpolated between.
+    window: Option<(f32, Option<f32>)>,
+    phase: f32,
+    pitch: f32,
+    pitch_step: f32,
+    finished: bool,
+}
+
+impl<I> SpatialSource<I>
+where
+    I: Source,
+    I::Item: Sample,
+{
+    pub(crate) fn new(input: I, params: Arc<Mutex<SpatialParams>>) -> Self {
+        SpatialSource {
+            input,
+            params,
+            history: [0.0; DELAY_LINE_LEN],
+            write: 0,
+            ears: None,
+            steps: [Ear::default(); 2],
+            filtered: [0.0; 2],
+            frames_until_update: 0,
+            pending: None,
+            window: None,
+            phase: 0.0,
+            pitch: 1.0,
+            pitch_step: 0.0,
+            finished: false,
+        }
+    }
+
+    /// Reads the next frame of the input mixed down to mono.
+    fn next_mono(&mut self) -> Option<f32> {
+        let channels = self.input.channels().max(1);
+        let mut sum = self.input.next()?.to_f32();
+        for _ in 1..channels {
+            sum += self.input.next().map_or(0.0, |s| s.to_f32());
+        }
+        Some(sum / f32::from(channels))
+    }
+
+    /// Reads the input at the current pitch, interpolating between its frames.
+    fn next_resampled(&mut self) -> Option<f32> {
+        if self.finished {
+            return None;
+        }
+        let (mut current, mut next) = match self.window {
+            Some(window) => window,
+            None => {
+                let first = self.next_mono()?;
+                (first, self.next_mono())
+            }
+        };
+        let output = current + (next.unwrap_or(0.0) - current) * self.phase;
+
+        self.pitch += self.pitch_step;
+        self.phase += self.pitch;
+        while self.phase >= 1.0 {
+            self.phase -= 1.0;
+            match next {
+                Some(sample) => {
+                    current = sample;
+                    next = self.next_mono();
+                }
+                None => {
+                    self.finished = true;
+                    break;
+                }
+            }
+        }
+        self.window = Some((current, next));
+        Some(output)
+    }
+
+    fn update(&mut self) {
+        let params = *self.params.lock().expect("Spatial parameters poisoned");
+        let target = params.ears(self.input.sample_rate());
+        match self.ears {
+            Some(current) => {
+                self.steps = [
+                    current[0].step_towards(&target[0], UPDATE_INTERVAL),
+                    current[1].step_towards(&target[1], UPDATE_INTERVAL),
+                ];
+                self.pitch_step = (params.pitch - self.pitch) / UPDATE_INTERVAL as f32;
+            }
+            None => {
+                self.ears = Some(target);
+                self.pitch = params.pitch;
+            }
+        }
+        self.frames_until_update = UPDATE_INTERVAL;
+    }
+
+    /// Reads the delay line `delay` frames in the past, interpolating between frames.
+    fn delayed(&self, delay: f32) -> f32 {
+        let whole = delay.floor();
+        let fraction = delay - whole;
+        let index = |offset: usize| {
+            (self.write + 2 * DELAY_LINE_LEN - 1 - whole as usize - offset) % DELAY_LINE_LEN
+        };
+        self.history[index(0)] * (1.0 - fraction) + self.history[index(1)] * fraction
+    }
+}
+
+impl<I> Iterator for SpatialSource<I>
+where
+    I: Source,
+    I::Item: Sample,
+{
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if let Some(right) = self.pending.take() {
+            return Some(right);
+        }
+
+        if self.frames_until_update == 0 {
+            self.update();
+        }
+        let sample = self.next_resampled()?;
+        self.frames_until_update -= 1;
+
+        self.history[self.write] = sample;
+        self.write = (self.write + 1) % DELAY_LINE_LEN;
+
+        let mut ears = self
+            .ears
+            .expect("Spatial parameters are read before the first frame");
+        let mut out = [0.0; 2];
+        for (i, ear) in ears.iter_mut().enumerate() {
+            ear.advance(&self.steps[i]);
+            let delayed = self.delayed(ear.delay.max(0.0));
+            self.filtered[i] += ear.coefficient * (delayed - self.filtered[i]);
+            out[i] = self.filtered[i] * ear.gain;
+        }
+        self.ears = Some(ears);
+
+        self.pending = Some(out[1]);
+        Some(out[0])
+    }
+}
+
+impl<I> Source for SpatialSource<I>
+where
+    I: Source,
+    I::Item: Sample,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        // The doppler effect changes how many input frames make up an output frame, so the
+        // format of the input is read once.
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        2
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.input.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.input.total_duration()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rodio::buffer::SamplesBuffer;
+
+    use super::*;
+
+    fn params(emitter: Point3<f32>, mode: SpatialMode) -> Arc<Mutex<SpatialParams>> {
+        Arc::new(Mutex::new(SpatialParams {
+            emitter,
+            left_ear: Point3::new(-0.1, 0.0, 0.0),
+            right_ear: Point3::new(0.1, 0.0, 0.0),
+            rolloff: Rolloff::default(),
+            mode,
+            head_radius: 0.0875,
+            pitch: 1.0,
+        }))
+    }
+
+    fn render(emitter: Point3<f32>, mode: SpatialMode) -> (Vec<f32>, Vec<f32>) {
+        let mut impulse = vec![0.0f32; 512];
+        impulse[0] = 1.0;
+        let source =
+            SpatialSource::new(SamplesBuffer::new(1, 48000, impulse), params(emitter, mode));
+        let out: Vec<f32> = source.collect();
+        assert_eq!(out.len(), 1024);
+        (
+            out.iter().step_by(2).copied().collect(),
+            out.iter().skip(1).step_by(2).copied().collect(),
+        )
+    }
+
+    fn peak(samples: &[f32]) -> usize {
+        (0..samples.len())
+            .max_by(|&a, &b| samples[a].abs().partial_cmp(&samples[b].abs()).unwrap())
+            .unwrap()
+    }
+
+    #[test]
+    fn rolloff_curves() {
+        let mut rolloff = Rolloff::default();
+        assert!((rolloff.gain(0.5) - 1.0).abs() < 1e-6);
+        assert!((rolloff.gain(2.0) - 0.5).abs() < 1e-6);
+        assert!((rolloff.gain(1000.0) - rolloff.gain(100.0)).abs() < 1e-6);
+
+        rolloff.model = DistanceModel::Linear;
+        assert!((rolloff.gain(50.5) - 0.5).abs() < 1e-6);
+        assert!(rolloff.gain(100.0).abs() < 1e-6);
+
+        rolloff.model = DistanceModel::Exponential;
+        rolloff.factor = 2.0;
+        assert!((rolloff.gain(2.0) - 0.25).abs() < 1e-6);
+    }
+
+    #[test]
+    fn interaural_delays_and_shadows_far_ear() {
+        let (left, right) = render(Point3::new(2.0, 0.0, 0.0), SpatialMode::Interaural);
+        assert_eq!(peak(&right), 0);
+        assert!(peak(&left) > 10);
+        let energy = |s: &[f32]| s.iter().map(|x| x * x).sum::<f32>();
+        assert!(energy(&right) > energy(&left));
+
+        let (left, right) = render(Point3::new(0.0, 0.0, -2.0), SpatialMode::Interaural);
+        assert_eq!(peak(&left), peak(&right));
+        assert!((left[0] - right[0]).abs() < 1e-6);
+    }
+
+    #[test]
+    fn panning_has_no_delay() {
+        let (left, right) = render(Point3::new(-2.0, 0.0, 0.0), SpatialMode::Panning);
+        assert_eq!(peak(&left), 0);
+        assert!(left[0] > 0.4);
+        assert!(right.iter().all(|s| s.abs() < 1e-6));
+    }
+
+    #[test]
+    fn doppler_raises_pitch_of_approaching_sounds() {
+        let listener = Point3::new(0.0, 0.0, 0.0);
+        let emitter = Point3::new(10.0, 0.0, 0.0);
+        let still = Vector3::zeros();
+        let approaching = Vector3::new(-34.3, 0.0, 0.0);
+
+        let pitch = doppler_pitch(listener, still, emitter, approaching, 1.0);
+        assert!((pitch - 1.0 / 0.9).abs() < 1e-4);
+        let pitch = doppler_pitch(listener, still, emitter, -approaching, 1.0);
+        assert!((pitch - 1.0 / 1.1).abs() < 1e-4);
+        assert_eq!(
+            doppler_pitch(listener, still, emitter, approaching, 0.0),
+            1.0
+        );
+
+        // Moving along with the sound cancels the shift.
+        let pitch = doppler_pitch(listener, approaching, emitter, approaching, 1.0);
+        assert!((pitch - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn pitch_changes_playback_speed() {
+        let params = params(Point3::new(0.0, 0.0, -2.0), SpatialMode::Panning);
+        params.lock().unwrap().pitch = 2.0;
+        let source = SpatialSource::new(SamplesBuffer::new(1, 48000, vec![0.5f32; 512]), params);
+        assert_eq!(source.count(), 2 * 256);
+    }
+}
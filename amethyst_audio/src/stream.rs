@@ -0,0 +1,285 @@
+//! Playback of long sounds, like music, without decoding them up front.
+
+use std::{
+    io::Cursor,
+    sync::{
+        atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use rodio::{Decoder, Source as RSource};
+
+use crate::{source::Source, DecoderError};
+
+/// Playback state shared between an `AudioStream` and its `StreamControl`s.
+#[derive(Debug, Default)]
+struct StreamState {
+    looping: AtomicBool,
+    seek_pending: AtomicBool,
+    seek_target: Mutex<Duration>,
+    frames_played: AtomicU64,
+    sample_rate: AtomicU32,
+    loops: AtomicU64,
+}
+
+/// Controls an `AudioStream` after it was handed to a sink or emitter.
+#[derive(Clone, Debug)]
+pub struct StreamControl {
+    state: Arc<StreamState>,
+}
+
+impl StreamControl {
+    /// Returns true if the stream starts over once it reaches the end.
+    pub fn is_looping(&self) -> bool {
+        self.state.looping.load(Ordering::Relaxed)
+    }
+
+    /// Changes whether the stream starts over once it reaches the end.
+    pub fn set_looping(&self, looping: bool) {
+        self.state.looping.store(looping, Ordering::Relaxed);
+    }
+
+    /// Jumps to the given offset from the start of the sound.
+    ///
+    /// Seeking past the end ends the stream, or wraps around if it is looping.
+    pub fn seek(&self, position: Duration) {
+        *self
+            .state
+            .seek_target
+            .lock()
+            .expect("Stream state poisoned") = position;
+        self.state.seek_pending.store(true, Ordering::Release);
+    }
+
+    /// Returns the offset from the start of the sound that is currently being decoded.
+    pub fn position(&self) -> Duration {
+        let rate = self.state.sample_rate.load(Ordering::Relaxed);
+        if rate == 0 {
+            return Duration::from_secs(0);
+        }
+        let frames = self.state.frames_played.load(Ordering::Relaxed);
+        Duration::from_secs(frames / u64::from(rate))
+            + Duration::from_secs_f64((frames % u64::from(rate)) as f64 / f64::from(rate))
+    }
+
+    pub(crate) fn frames_played(&self) -> u64 {
+        self.state.frames_played.load(Ordering::Relaxed)
+    }
+
+    /// Returns how many times the stream wrapped around to the start.
+    pub fn loop_count(&self) -> u64 {
+        self.state.loops.load(Ordering::Relaxed)
+    }
+}
+
+/// A sound which is decoded in small chunks while it plays.
+///
+/// Playing a `Source` through an `AudioStream` only keeps the encoded file in memory,
+/// which makes it the better choice for long music tracks. Looping restarts the decoder
+/// without leaving a gap, and the stream can be repositioned through its `StreamControl`.
+///
+/// Seeking decodes the sound up to the requested offset, so seeking far into a long track
+/// takes a moment to complete.
+#[allow(missing_debug_implementations)]
+pub struct AudioStream {
+    bytes: Arc<[u8]>,
+    decoder: Decoder<Cursor<Arc<[u8]>>>,
+    state: Arc<StreamState>,
+    channels: u16,
+    /// Samples of the current frame that were already returned.
+    sample_in_frame: u16,
+}
+
+impl AudioStream {
+    /// Creates a stream playing the given source once.
+    pub fn new(source: &Source) -> Result<AudioStream, DecoderError> {
+        AudioStream::from_bytes(Arc::from(source.bytes.as_slice()), false)
+    }
+
+    /// Creates a stream playing the given source over and over.
+    pub fn looping(source: &Source) -> Result<AudioStream, DecoderError> {
+        AudioStream::from_bytes(Arc::from(source.bytes.as_slice()), true)
+    }
+
+    fn from_bytes(bytes: Arc<[u8]>, looping: bool) -> Result<AudioStream, DecoderError> {
+        let decoder = Decoder::new(Cursor::new(bytes.clone()))?;
+        let state = StreamState::default();
+        state.looping.store(looping, Ordering::Relaxed);
+        state
+            .sample_rate
+            .store(decoder.sample_rate(), Ordering::Relaxed);
+        Ok(AudioStream {
+            channels: decoder.channels().max(1),
+            bytes,
+            decoder,
+            state: Arc::new(state),
+            sample_in_frame: 0,
+        })
+    }
+
+    /// Returns a handle to control this stream while it plays.
+    pub fn control(&self) -> StreamControl {
+        StreamControl {
+            state: self.state.clone(),
+        }
+    }
+
+    /// Returns what is needed to continue this stream later with `AudioStream::resume`, even
+    /// once it was handed to a sink.
+    pub(crate) fn resumable(&self) -> (Arc<[u8]>, StreamControl) {
+        (self.bytes.clone(), self.control())
+    }
+
+    /// Creates a new stream of the sound of `bytes`, starting where the stream of `control` is.
+    pub(crate) fn resume(
+        bytes: Arc<[u8]>,
+        control: &StreamControl,
+    ) -> Result<AudioStream, DecoderError> {
+        let stream = AudioStream::from_bytes(bytes, control.is_looping())?;
+        stream.control().seek(control.position());
+        Ok(stream)
+    }
+
+    /// Starts decoding from the beginning of the sound.
+    fn restart(&mut self) -> bool {
+        match Decoder::new(Cursor::new(self.bytes.clone())) {
+            Ok(decoder) => {
+                self.decoder = decoder;
+                self.sample_in_frame = 0;
+                self.state.frames_played.store(0, Ordering::Relaxed);
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    fn apply_seek(&mut self) {
+        let target = *self
+            .state
+            .seek_target
+            .lock()
+            .expect("Stream state poisoned");
+        let rate = u64::from(self.decoder.sample_rate());
+        let mut target_frames = (target.as_secs_f64() * rate as f64) as u64;
+        let played = self.state.frames_played.load(Ordering::Relaxed);
+        if target_frames < played && !self.restart() {
+            return;
+        }
+        let mut played = self.state.frames_played.load(Ordering::Relaxed);
+        'frames: while played < target_frames {
+            for _ in 0..self.channels {
+                if self.decoder.next().is_none() {
+                    if played == 0 || !self.is_looping() || !self.restart() {
+                        self.state.frames_played.store(played, Ordering::Relaxed);
+                        return;
+                    }
+                    self.state.loops.fetch_add(1, Ordering::Relaxed);
+                    target_frames -= played;
+                    played = 0;
+                    continue 'frames;
+                }
+            }
+            played += 1;
+        }
+        self.state.frames_played.store(played, Ordering::Relaxed);
+    }
+
+    fn is_looping(&self) -> bool {
+        self.state.looping.load(Ordering::Relaxed)
+    }
+}
+
+impl Iterator for AudioStream {
+    type Item = i16;
+
+    fn next(&mut self) -> Option<i16> {
+        if self.sample_in_frame == 0 && self.state.seek_pending.swap(false, Ordering::Acquire) {
+            self.apply_seek();
+        }
+
+        let sample = match self.decoder.next() {
+            Some(sample) => sample,
+            None if self.is_looping() && self.restart() => {
+                self.state.loops.fetch_add(1, Ordering::Relaxed);
+                // An empty sound would otherwise loop forever without producing anything.
+                self.decoder.next()?
+            }
+            None => return None,
+        };
+
+        self.sample_in_frame += 1;
+        if self.sample_in_frame == self.channels {
+            self.sample_in_frame = 0;
+            self.state.frames_played.fetch_add(1, Ordering::Relaxed);
+        }
+        Some(sample)
+    }
+}
+
+impl RSource for AudioStream {
+    fn current_frame_len(&self) -> Option<usize> {
+        // A single file never changes its format, even when it loops.
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.decoder.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        if self.is_looping() {
+            None
+        } else {
+            self.decoder.total_duration()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, time::Duration};
+
+    use amethyst_utils::app_root_dir::application_root_dir;
+
+    use crate::{AudioStream, Source};
+
+    fn source() -> Source {
+        let path = application_root_dir().unwrap().join("tests/sound_test.ogg");
+        Source::new(fs::read(path).unwrap())
+    }
+
+    #[test]
+    fn stream_loops_seamlessly() {
+        let source = source();
+        let length = AudioStream::new(&source).unwrap().count();
+        assert!(length > 0);
+
+        let stream = AudioStream::looping(&source).unwrap();
+        let control = stream.control();
+        assert_eq!(stream.take(length * 2 + 10).count(), length * 2 + 10);
+        assert_eq!(control.loop_count(), 2);
+    }
+
+    #[test]
+    fn stream_seeks() {
+        let source = source();
+        let mut stream = AudioStream::new(&source).unwrap();
+        let control = stream.control();
+        let rate = u64::from(rodio::Source::sample_rate(&stream));
+        let length = AudioStream::new(&source).unwrap().count();
+
+        control.seek(Duration::from_millis(10));
+        stream.next();
+        let frames = (control.position().as_secs_f64() * rate as f64).round() as u64;
+        assert!(frames >= rate / 100 && frames <= rate / 100 + 1);
+
+        let remaining = stream.count();
+        assert!(remaining < length);
+    }
+}
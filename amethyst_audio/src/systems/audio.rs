@@ -3,19 +3,29 @@ use std::{
     mem::replace,
     sync::{
         atomic::{AtomicBool, Ordering},
-        Arc,
+        Arc, Mutex,
     },
 };
 
-use amethyst_core::{ecs::*, math::convert, transform::Transform};
-use rodio::SpatialSink;
+use amethyst_core::{
+    ecs::*,
+    math::{center, Point3, Vector3},
+    shrev::EventChannel,
+    transform::Transform,
+    Time,
+};
+use rodio::Sink;
 #[cfg(feature = "profiler")]
 use thread_profiler::profile_scope;
 
 use crate::{
-    components::{AudioEmitter, AudioListener},
+    components::{AudioEmitter, AudioListener, AudioVelocity, EmitterSink, ReverbZone},
+    effects::{Reverb, ZoneReverb},
     end_signal::EndSignalSource,
+    event::{AudioEvent, EventQueue},
+    mixer::{MixSource, Mixer},
     output::OutputWrapper,
+    spatial::{doppler_pitch, DopplerFactor, SpatialParams, SpatialSource},
 };
 
 /// Syncs 3D transform data with the audio engine to provide 3D audio.
@@ -30,19 +40,42 @@ pub struct SelectedListener(pub Option<Entity>);
 
 impl System for AudioSystem {
     fn build(self) -> Box<dyn ParallelRunnable> {
+        let zone_reverb = Arc::new(ZoneReverb::default());
+        let events = EventQueue::default();
+        // Listener position in the last frame, for listeners without an `AudioVelocity`.
+        let mut last_listener: Option<(Entity, Point3<f32>)> = None;
+
         Box::new(
             SystemBuilder::new("AudioSystem")
                 .read_resource::<OutputWrapper>()
                 .read_resource::<SelectedListener>()
+                .read_resource::<Mixer>()
+                .write_resource::<EventChannel<AudioEvent>>()
+                .read_resource::<Time>()
+                .read_resource::<DopplerFactor>()
                 .with_query(<(Entity, Read<AudioListener>)>::query())
-                .with_query(<(Write<AudioEmitter>, Read<Transform>)>::query())
+                .with_query(<(
+                    Entity,
+                    Write<AudioEmitter>,
+                    Read<Transform>,
+                    TryRead<AudioVelocity>,
+                )>::query())
+                .with_query(<(Read<ReverbZone>, Read<Transform>)>::query())
                 .build(
                     move |_commands,
                           world,
-                          (wrapper, select_listener),
-                          (q_audio_listener, q_audio_emitter)| {
+                          (wrapper, select_listener, mixer, audio_events, time, doppler),
+                          (q_audio_listener, q_audio_emitter, q_reverb_zone)| {
                         #[cfg(feature = "profiler")]
                         profile_scope!("audio_system");
+                        mixer.set_time_scale(time.time_scale());
+
+                        // Send what happened since the last frame.
+                        audio_events.iter_write(events.drain());
+                        if let Some(sink) = &wrapper.audio_sink {
+                            audio_events.iter_write(sink.events.drain());
+                        }
+
                         // Process emitters and listener.
                         if let Some((entity, listener)) = if let Some(entity) = select_listener.0 {
                             // Find entity refered by SelectedListener resource
@@ -64,37 +97,91 @@ impl System for AudioSystem {
                                 .and_then(|entry| entry.into_component::<Transform>().ok())
                             {
                                 let listener_transform = listener_transform.global_matrix();
-                                let left_ear_position: [f32; 3] = {
-                                    let pos = listener_transform
-                                        .transform_point(&listener.left_ear)
-                                        .to_homogeneous()
-                                        .xyz();
-                                    [convert(pos.x), convert(pos.y), convert(pos.z)]
-                                };
-                                let right_ear_position: [f32; 3] = {
-                                    let pos = listener_transform
-                                        .transform_point(&listener.right_ear)
-                                        .to_homogeneous()
-                                        .xyz();
-                                    [convert(pos.x), convert(pos.y), convert(pos.z)]
+                                let left_ear =
+                                    listener_transform.transform_point(&listener.left_ear);
+                                let right_ear =
+                                    listener_transform.transform_point(&listener.right_ear);
+
+                                // Use the reverb zone the listener is deepest in.
+                                let listener_position = center(&left_ear, &right_ear);
+                                let mut has_zones = false;
+                                let mut zone = Reverb {
+                                    mix: 0.0,
+                                    ..Default::default()
                                 };
+                                let mut zone_weight = 0.0;
+                                for (reverb_zone, transform) in q_reverb_zone.iter(world) {
+                                    has_zones = true;
+                                    let zone_center =
+                                        Point3::from(transform.global_matrix().column(3).xyz());
+                                    let weight = reverb_zone
+                                        .weight((zone_center - listener_position).norm());
+                                    if weight > zone_weight {
+                                        zone_weight = weight;
+                                        zone = reverb_zone.reverb;
+                                        zone.mix *= weight;
+                                    }
+                                }
+                                zone_reverb.set(zone);
+
+                                let delta = time.delta_seconds();
+                                let velocity =
+                                    |position: Point3<f32>, last: Option<Point3<f32>>| {
+                                        match last {
+                                            Some(last) if delta > 0.0 => (position - last) / delta,
+                                            _ => Vector3::zeros(),
+                                        }
+                                    };
+                                let listener_velocity = world
+                                    .entry_ref(entity)
+                                    .ok()
+                                    .and_then(|entry| {
+                                        entry.into_component::<AudioVelocity>().ok().map(|v| v.0)
+                                    })
+                                    .unwrap_or_else(|| {
+                                        let last = last_listener
+                                            .filter(|(last, _)| *last == entity)
+                                            .map(|(_, position)| position);
+                                        velocity(listener_position, last)
+                                    });
+                                last_listener = Some((entity, listener_position));
+
+                                let mode = listener.mode;
+                                let head_radius = listener.head_radius;
                                 q_audio_emitter.for_each_mut(
                                     world,
-                                    |(mut audio_emitter, transform)| {
-                                        let emitter_position: [f32; 3] = {
-                                            let x = transform.global_matrix()[(0, 3)];
-                                            let y = transform.global_matrix()[(1, 3)];
-                                            let z = transform.global_matrix()[(2, 3)];
-                                            [convert(x), convert(y), convert(z)]
+                                    |(entity, mut audio_emitter, transform, emitter_velocity)| {
+                                        let position =
+                                            Point3::from(transform.global_matrix().column(3).xyz());
+                                        let emitter_velocity =
+                                            emitter_velocity.map(|v| v.0).unwrap_or_else(|| {
+                                                velocity(position, audio_emitter.last_position)
+                                            });
+                                        audio_emitter.last_position = Some(position);
+                                        let params = SpatialParams {
+                                            emitter: position,
+                                            left_ear,
+                                            right_ear,
+                                            rolloff: audio_emitter.rolloff,
+                                            mode,
+                                            head_radius,
+                                            pitch: doppler_pitch(
+                                                listener_position,
+                                                listener_velocity,
+                                                position,
+                                                emitter_velocity,
+                                                doppler.0,
+                                            ),
                                         };
                                         // Remove all sinks whose sounds have ended.
                                         audio_emitter
                                             .sinks
-                                            .retain(|s| !s.1.load(Ordering::Relaxed));
-                                        for &mut (ref mut sink, _) in &mut audio_emitter.sinks {
-                                            sink.set_emitter_position(emitter_position);
-                                            sink.set_left_ear_position(left_ear_position);
-                                            sink.set_right_ear_position(right_ear_position);
+                                            .retain(|s| !s.ended.load(Ordering::Relaxed));
+                                        for sink in &audio_emitter.sinks {
+                                            *sink
+                                                .params
+                                                .lock()
+                                                .expect("Spatial parameters poisoned") = params;
                                         }
                                         if audio_emitter.sinks.is_empty() {
                                             if let Some(mut picker) =
@@ -105,23 +192,40 @@ impl System for AudioSystem {
                                                 }
                                             }
                                         }
-                                        while let Some(source) = audio_emitter.sound_queue.pop() {
+                                        while let Some(sound) = audio_emitter.sound_queue.pop() {
                                             if let Some(output) = &wrapper.output {
-                                                let sink = SpatialSink::new(
-                                                    &output.device,
-                                                    emitter_position,
-                                                    left_ear_position,
-                                                    right_ear_position,
-                                                );
+                                                let sink = Sink::new(&output.device);
+                                                let shared = Arc::new(Mutex::new(params));
                                                 let atomic_bool = Arc::new(AtomicBool::new(false));
                                                 let clone = atomic_bool.clone();
+                                                let mut source: MixSource =
+                                                    Box::new(SpatialSource::new(
+                                                        sound.track(Some(*entity), events.clone()),
+                                                        shared.clone(),
+                                                    ));
+                                                // Attached even while occlusion is disabled, so
+                                                // enabling it later reaches this sound too.
+                                                source =
+                                                    audio_emitter.occlusion_state.apply(source);
+                                                for effect in &audio_emitter.effects {
+                                                    source = effect.apply(source);
+                                                }
+                                                if has_zones {
+                                                    source = zone_reverb.apply(source);
+                                                }
+                                                let bus = mixer.bus_or_master(&audio_emitter.bus);
+                                                // Signal the end after effect tails played out.
                                                 sink.append(EndSignalSource::new(
-                                                    source,
+                                                    bus.route(source),
                                                     move || {
                                                         clone.store(true, Ordering::Relaxed);
                                                     },
                                                 ));
-                                                audio_emitter.sinks.push((sink, atomic_bool));
+                                                audio_emitter.sinks.push(EmitterSink {
+                                                    sink,
+                                                    params: shared,
+                                                    ended: atomic_bool,
+                                                });
                                             }
                                         }
                                     },
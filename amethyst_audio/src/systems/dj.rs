@@ -1,4 +1,4 @@
-use std::marker::PhantomData;
+use std::{marker::PhantomData, time::Duration};
 
 use amethyst_assets::AssetStorage;
 use amethyst_core::ecs::*;
@@ -8,6 +8,8 @@ use log::error;
 use thread_profiler::profile_scope;
 
 use crate::{
+    mixer::Mixer,
+    music::{MusicController, MusicTrack, Transition},
     output::{init_output, OutputWrapper},
     source::{Source, SourceHandle},
 };
@@ -20,6 +22,7 @@ where
     R: Send + Sync + 'static,
 {
     f: F,
+    crossfade: Option<Duration>,
     _marker: PhantomData<R>,
 }
 
@@ -32,9 +35,20 @@ where
     pub fn new(f: F) -> Self {
         Self {
             f,
+            crossfade: None,
             _marker: PhantomData,
         }
     }
+
+    /// Plays the music through the `MusicController`, fading from one track into the next
+    /// over the given duration.
+    ///
+    /// The next track is picked when the current one is about to end, which is only known
+    /// for preloaded sources. Other tracks are followed by the next one without a fade.
+    pub fn with_crossfade(mut self, fade: Duration) -> Self {
+        self.crossfade = Some(fade);
+        self
+    }
 }
 
 impl<F, R> SystemBundle for DjSystemBundle<F, R>
@@ -49,8 +63,12 @@ where
         builder: &mut DispatcherBuilder,
     ) -> Result<(), Error> {
         init_output(resources);
+        if self.crossfade.is_some() {
+            resources.get_or_default::<MusicController>();
+        }
         builder.add_system(DjSystem {
             f: self.f,
+            crossfade: self.crossfade,
             _phantom: PhantomData,
         });
         Ok(())
@@ -58,6 +76,9 @@ where
 }
 
 /// Calls a closure if the `AudioSink` is empty.
+///
+/// With a crossfade, the closure is called when the track of the `MusicController` is about
+/// to end instead.
 #[derive(Debug, Clone)]
 pub struct DjSystem<F, R>
 where
@@ -65,6 +86,7 @@ where
     R: Send + Sync,
 {
     f: F,
+    crossfade: Option<Duration>,
     _phantom: std::marker::PhantomData<R>,
 }
 
@@ -74,6 +96,9 @@ where
     R: Send + Sync + 'static,
 {
     fn build(mut self) -> Box<dyn ParallelRunnable + 'static> {
+        if let Some(fade) = self.crossfade {
+            return build_crossfading(self.f, fade);
+        }
         Box::new(
             SystemBuilder::new("DjSystem")
                 .read_resource::<AssetStorage<Source>>()
@@ -98,3 +123,42 @@ where
         )
     }
 }
+
+fn build_crossfading<F, R>(mut f: F, fade: Duration) -> Box<dyn ParallelRunnable + 'static>
+where
+    F: FnMut(&mut R) -> Option<SourceHandle> + Send + Sync + 'static,
+    R: Send + Sync + 'static,
+{
+    Box::new(
+        SystemBuilder::new("DjSystem")
+            .read_resource::<AssetStorage<Source>>()
+            .read_resource::<OutputWrapper>()
+            .read_resource::<Mixer>()
+            .write_resource::<MusicController>()
+            .write_resource::<R>()
+            .build(
+                move |_commands, _world, (storage, wrapper, mixer, controller, res), _queries| {
+                    #[cfg(feature = "profiler")]
+                    profile_scope!("dj_system");
+
+                    if let Some(output) = &wrapper.output {
+                        controller.attach(output, mixer);
+                    }
+                    if controller.is_transitioning() {
+                        return;
+                    }
+                    let due = !controller.is_playing()
+                        || controller.remaining().map_or(false, |left| left <= fade);
+                    if !due {
+                        return;
+                    }
+                    if let Some(source) = f(res).and_then(|h| storage.get(&h)) {
+                        match MusicTrack::new(source) {
+                            Ok(track) => controller.play(track, Transition::crossfade(fade)),
+                            Err(e) => error!("DJ Cannot play source. {}", e),
+                        }
+                    }
+                },
+            ),
+    )
+}
@@ -3,7 +3,11 @@
 pub use self::{
     audio::{AudioSystem, SelectedListener},
     dj::{DjSystem, DjSystemBundle},
+    music::MusicSystem,
+    output::OutputSystem,
 };
 
 mod audio;
 mod dj;
+mod music;
+mod output;
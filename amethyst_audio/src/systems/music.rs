@@ -0,0 +1,30 @@
+use amethyst_core::ecs::*;
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+
+use crate::{mixer::Mixer, music::MusicController, output::OutputWrapper};
+
+/// Connects the `MusicController` to the audio output once there is one.
+#[derive(Debug)]
+pub struct MusicSystem;
+
+impl System for MusicSystem {
+    fn build(self) -> Box<dyn ParallelRunnable> {
+        Box::new(
+            SystemBuilder::new("MusicSystem")
+                .read_resource::<OutputWrapper>()
+                .read_resource::<Mixer>()
+                .write_resource::<MusicController>()
+                .build(
+                    move |_commands, _world, (wrapper, mixer, controller), _queries| {
+                        #[cfg(feature = "profiler")]
+                        profile_scope!("music_system");
+
+                        if let Some(output) = &wrapper.output {
+                            controller.attach(output, mixer);
+                        }
+                    },
+                ),
+        )
+    }
+}
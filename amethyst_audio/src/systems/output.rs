@@ -0,0 +1,190 @@
+use std::time::Instant;
+
+use amethyst_core::{ecs::*, shrev::EventChannel};
+use log::info;
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+
+use crate::{
+    components::AudioEmitter,
+    event::AudioEvent,
+    mixer::Mixer,
+    music::MusicController,
+    output::{default_output, find_output, Output, OutputSelection, OutputWrapper},
+};
+
+/// Keeps playing on the output selected by the `OutputSelection` resource.
+///
+/// The system switches outputs right away when the selection changes, and periodically checks
+/// whether the selected or default device changed, e.g. when headphones are unplugged.
+/// The music continues on the new output where it was. Sounds of `AudioEmitter`s playing on
+/// the previous output are stopped, play them again on `AudioEvent::OutputChanged` if needed.
+#[derive(Debug)]
+pub struct OutputSystem;
+
+impl System for OutputSystem {
+    fn build(self) -> Box<dyn ParallelRunnable> {
+        let mut applied: Option<OutputSelection> = None;
+        let mut last_check = Instant::now();
+
+        Box::new(
+            SystemBuilder::new("OutputSystem")
+                .read_resource::<OutputSelection>()
+                .read_resource::<Mixer>()
+                .write_resource::<OutputWrapper>()
+                .write_resource::<MusicController>()
+                .write_resource::<EventChannel<AudioEvent>>()
+                .with_query(<Write<AudioEmitter>>::query())
+                .build(
+                    move |_commands,
+                          world,
+                          (selection, mixer, wrapper, music, audio_events),
+                          q_audio_emitter| {
+                        #[cfg(feature = "profiler")]
+                        profile_scope!("output_system");
+
+                        let changed = applied.as_ref() != Some(&**selection);
+                        if !changed && last_check.elapsed() < selection.check_interval {
+                            return;
+                        }
+                        applied = Some((**selection).clone());
+                        last_check = Instant::now();
+
+                        let current = wrapper.output.as_ref().map(Output::name);
+                        let (output, name) = match choose_output(
+                            selection.device.as_deref(),
+                            current.as_deref(),
+                            find_output,
+                            default_output,
+                            Output::name,
+                        ) {
+                            Some(switch) => switch,
+                            None => return,
+                        };
+
+                        info!("Switching audio output to `{}`", name);
+                        wrapper.switch_output(output, mixer);
+                        music.detach();
+                        if let Some(output) = &wrapper.output {
+                            music.attach(output, mixer);
+                        }
+                        for emitter in q_audio_emitter.iter_mut(world) {
+                            emitter.sinks.clear();
+                        }
+                        audio_events.single_write(AudioEvent::OutputChanged { name });
+                    },
+                ),
+        )
+    }
+}
+
+// The output to switch to, with its name: the selected device, or the default device if it's
+// missing or none is selected. Returns `None` to keep the current output, when it's already the
+// one to play on, or when there is nothing to switch to, in case the current output still works.
+fn choose_output<D>(
+    selected: Option<&str>,
+    current: Option<&str>,
+    find: impl FnOnce(&str) -> Option<D>,
+    default: impl FnOnce() -> Option<D>,
+    name: impl Fn(&D) -> String,
+) -> Option<(D, String)> {
+    let output = selected.and_then(find).or_else(default)?;
+    let output_name = name(&output);
+    if current == Some(output_name.as_str()) {
+        return None;
+    }
+    Some((output, output_name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Chooses among the devices with the given names, the first one being the default.
+    fn choose(
+        selected: Option<&str>,
+        current: Option<&str>,
+        devices: &[&'static str],
+    ) -> Option<&'static str> {
+        choose_output(
+            selected,
+            current,
+            |name| devices.iter().copied().find(|device| *device == name),
+            || devices.first().copied(),
+            |device| device.to_string(),
+        )
+        .map(|(device, name)| {
+            assert_eq!(device, name);
+            device
+        })
+    }
+
+    #[test]
+    fn follows_the_default_device() {
+        assert_eq!(
+            choose(None, None, &["speakers", "headphones"]),
+            Some("speakers")
+        );
+        assert_eq!(
+            choose(None, Some("speakers"), &["speakers", "headphones"]),
+            None
+        );
+        assert_eq!(
+            choose(None, Some("speakers"), &["headphones", "speakers"]),
+            Some("headphones")
+        );
+    }
+
+    #[test]
+    fn plays_on_the_selected_device() {
+        let devices = ["speakers", "headphones"];
+        assert_eq!(
+            choose(Some("headphones"), None, &devices),
+            Some("headphones")
+        );
+        assert_eq!(
+            choose(Some("headphones"), Some("speakers"), &devices),
+            Some("headphones")
+        );
+        assert_eq!(
+            choose(Some("headphones"), Some("headphones"), &devices),
+            None
+        );
+    }
+
+    #[test]
+    fn falls_back_to_the_default_device() {
+        // the selected device is unplugged
+        assert_eq!(
+            choose(Some("headphones"), Some("headphones"), &["speakers"]),
+            Some("speakers")
+        );
+        assert_eq!(
+            choose(Some("headphones"), None, &["speakers"]),
+            Some("speakers")
+        );
+        assert_eq!(
+            choose(Some("headphones"), Some("speakers"), &["speakers"]),
+            None
+        );
+    }
+
+    #[test]
+    fn default_device_disappearing_switches_to_the_new_default() {
+        assert_eq!(
+            choose(None, Some("headphones"), &["speakers"]),
+            Some("speakers")
+        );
+        assert_eq!(
+            choose(Some("usb"), Some("headphones"), &["speakers"]),
+            Some("speakers")
+        );
+    }
+
+    #[test]
+    fn keeps_the_current_output_without_devices() {
+        assert_eq!(choose(None, Some("speakers"), &[]), None);
+        assert_eq!(choose(Some("headphones"), Some("headphones"), &[]), None);
+        assert_eq!(choose(None, None, &[]), None);
+    }
+}
@@ -0,0 +1,327 @@
+//! Configurations merged from several layers.
+
+use std::{fmt, marker::PhantomData, path::PathBuf};
+
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+
+use crate::{Config, ConfigError};
+
+/// A layer overriding the values of a configuration.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ConfigLayer {
+    /// The default values of the configuration
+    Defaults,
+    /// A configuration file
+    File(PathBuf),
+    /// An environment variable, with its name
+    Environment(String),
+    /// A command line argument
+    Argument,
+}
+
+impl fmt::Display for ConfigLayer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            ConfigLayer::Defaults => write!(f, "default values"),
+            ConfigLayer::File(ref path) => write!(f, "file {}", path.display()),
+            ConfigLayer::Environment(ref name) => write!(f, "environment variable {}", name),
+            ConfigLayer::Argument => write!(f, "command line argument"),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Override {
+    key: String,
+    value: Value,
+    layer: ConfigLayer,
+}
+
+/// Builds a configuration from layers, each overriding the values of the previous one:
+///
+/// 1. the `Default` values of the configuration,
+/// 2. a configuration file, which may only contain some of the values if the configuration
+///    uses `#[serde(default)]`,
+/// 3. environment variables, such as `GAME_DISPLAY__TITLE` for the `display.title` key with the
+///    `GAME_` prefix, double underscores separating the keys of nested values,
+/// 4. command line arguments and explicit values, as `display.title=Game`.
+///
+/// The layers apply in this order whatever the order they are added in. Values are parsed as
+/// JSON, or used as strings if they aren't valid JSON, so `800`, `true`, `[800, 600]`, `null`
+/// and enum variants such as `Windowed` can be given as they are.
+///
+/// # Example
+///
+/// ```no_run
+/// use amethyst_config::ConfigLayers;
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Debug, Default, Deserialize, Serialize)]
+/// #[serde(default)]
+/// struct GameConfig {
+///     difficulty: u32,
+///     player_name: String,
+/// }
+///
+/// let config: GameConfig = ConfigLayers::new()
+///     .file_if_exists("config/game.ron")?
+///     .env("GAME_")
+///     .args(std::env::args().skip(1).filter(|arg| arg.contains('=')))?
+///     .build()?;
+/// # Ok::<(), amethyst_config::ConfigError>(())
+/// ```
+#[derive(Debug)]
+pub struct ConfigLayers<T> {
+    base: Value,
+    base_layer: ConfigLayer,
+    overrides: Vec<Override>,
+    marker: PhantomData<T>,
+}
+
+impl<T> ConfigLayers<T>
+where
+    T: Config + Default + Serialize + DeserializeOwned,
+{
+    /// Starts from the default values of the configuration.
+    pub fn new() -> Self {
+        Self::from_value(&T::default(), ConfigLayer::Defaults)
+    }
+
+    fn from_value(config: &T, layer: ConfigLayer) -> Self {
+        let base = serde_json::to_value(config).expect("Failed to serialize the configuration");
+        ConfigLayers {
+            base,
+            base_layer: layer,
+            overrides: Vec::new(),
+            marker: PhantomData,
+        }
+    }
+
+    /// Loads the configuration file, replacing the default values.
+    pub fn file<P: Into<PathBuf>>(self, path: P) -> Result<Self, ConfigError> {
+        let path = path.into();
+        let config = T::load(&path)?;
+        Ok(ConfigLayers {
+            overrides: self.overrides,
+            ..Self::from_value(&config, ConfigLayer::File(path))
+        })
+    }
+
+    /// Loads the configuration file if it exists, keeping the default values otherwise.
+    pub fn file_if_exists<P: Into<PathBuf>>(self, path: P) -> Result<Self, ConfigError> {
+        let path = path.into();
+        if path.exists() {
+            self.file(path)
+        } else {
+            Ok(self)
+        }
+    }
+
+    /// Overrides values with the environment variables starting with the prefix.
+    pub fn env(self, prefix: &str) -> Self {
+        self.env_vars(prefix, std::env::vars())
+    }
+
+    /// Overrides values with the given variables starting with the prefix, in the way of `env`.
+    pub fn env_vars<I>(mut self, prefix: &str, vars: I) -> Self
+    where
+        I: IntoIterator<Item = (String, String)>,
+    {
+        let mut vars = vars
+            .into_iter()
+            .filter(|(name, _)| name.starts_with(prefix) && name.len() > prefix.len())
+            .collect::<Vec<_>>();
+        // the order of the environment is unspecified
+        vars.sort();
+        for (name, value) in vars {
+            let key = name[prefix.len()..].to_lowercase().replace("__", ".");
+            self.overrides.push(Override {
+                key,
+                value: parse_value(&value),
+                layer: ConfigLayer::Environment(name),
+            });
+        }
+        self
+    }
+
+    /// Overrides values with arguments of the `key=value` form, e.g. `display.title=Game`.
+    pub fn args<I, S>(mut self, args: I) -> Result<Self, ConfigError>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        for arg in args {
+            let arg = arg.as_ref();
+            let mut split = arg.splitn(2, '=');
+            match (split.next(), split.next()) {
+                (Some(key), Some(value)) if !key.is_empty() => {
+                    self.overrides.push(Override {
+                        key: key.to_string(),
+                        value: parse_value(value),
+                        layer: ConfigLayer::Argument,
+                    });
+                }
+                _ => {
+                    return Err(ConfigError::Key {
+                        key: arg.to_string(),
+                        layer: ConfigLayer::Argument,
+                        message: "expected an argument of the form `key=value`".to_string(),
+                    })
+                }
+            }
+        }
+        Ok(self)
+    }
+
+    /// Overrides a value, with the precedence of command line arguments.
+    pub fn set<K: Into<String>>(mut self, key: K, value: &str) -> Self {
+        self.overrides.push(Override {
+            key: key.into(),
+            value: parse_value(value),
+            layer: ConfigLayer::Argument,
+        });
+        self
+    }
+
+    /// Merges the layers into the configuration.
+    ///
+    /// Fails with a `ConfigError::Key` naming the key and layer of the first value which doesn't
+    /// exist in the configuration, or doesn't have the type of the key.
+    pub fn build(self) -> Result<T, ConfigError> {
+        let mut overrides = self.overrides;
+        // stable, so later overrides of the same layer still win
+        overrides.sort_by_key(|o| layer_rank(&o.layer));
+
+        let mut value = self.base;
+        for Override {
+            key,
+            value: new_value,
+            layer,
+        } in overrides
+        {
+            match set_key(&mut value, &key) {
+                Some(slot) => *slot = new_value,
+                None => {
+                    return Err(ConfigError::Key {
+                        key,
+                        layer,
+                        message: "the configuration has no such key".to_string(),
+                    });
+                }
+            }
+            // checked after every override, to know which key has a wrong value
+            if let Err(err) = serde_json::from_value::<T>(value.clone()) {
+                return Err(ConfigError::Key {
+                    key,
+                    layer,
+                    message: err.to_string(),
+                });
+            }
+        }
+        serde_json::from_value(value).map_err(|err| {
+            ConfigError::Key {
+                key: String::new(),
+                layer: self.base_layer,
+                message: err.to_string(),
+            }
+        })
+    }
+}
+
+fn layer_rank(layer: &ConfigLayer) -> u8 {
+    match *layer {
+        ConfigLayer::Defaults => 0,
+        ConfigLayer::File(_) => 1,
+        ConfigLayer::Environment(_) => 2,
+        ConfigLayer::Argument => 3,
+    }
+}
+
+fn parse_value(value: &str) -> Value {
+    serde_json::from_str(value).unwrap_or_else(|_| Value::String(value.to_string()))
+}
+
+// The value at a dot separated key, which must exist, array elements being indexed by number.
+fn set_key<'a>(value: &'a mut Value, key: &str) -> Option<&'a mut Value> {
+    key.split('.').try_fold(value, |value, part| {
+        match value {
+            Value::Object(map) => map.get_mut(part),
+            Value::Array(values) => {
+                part.parse::<usize>()
+                    .ok()
+                    .and_then(move |i| values.get_mut(i))
+            }
+            _ => None,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use super::*;
+
+    #[derive(Debug, Default, PartialEq, Deserialize, Serialize)]
+    #[serde(default)]
+    struct Display {
+        title: String,
+        dimensions: Option<(u32, u32)>,
+    }
+
+    #[derive(Debug, Default, PartialEq, Deserialize, Serialize)]
+    #[serde(default)]
+    struct TestConfig {
+        display: Display,
+        max_fps: u32,
+    }
+
+    #[test]
+    fn later_layers_override_earlier_ones() {
+        let vars = vec![
+            ("GAME_MAX_FPS".to_string(), "30".to_string()),
+            ("GAME_DISPLAY__TITLE".to_string(), "from env".to_string()),
+            ("OTHER_MAX_FPS".to_string(), "10".to_string()),
+        ];
+        let config: TestConfig = ConfigLayers::new()
+            .args(vec!["display.title=from args", "display.dimensions=[800, 600]"])
+            .unwrap()
+            .env_vars("GAME_", vars)
+            .build()
+            .unwrap();
+        assert_eq!(config.max_fps, 30);
+        assert_eq!(config.display.title, "from args");
+        assert_eq!(config.display.dimensions, Some((800, 600)));
+    }
+
+    #[test]
+    fn errors_name_the_key_and_layer() {
+        let error = ConfigLayers::<TestConfig>::new()
+            .set("display.width", "800")
+            .build()
+            .unwrap_err();
+        match error {
+            ConfigError::Key { key, layer, .. } => {
+                assert_eq!(key, "display.width");
+                assert_eq!(layer, ConfigLayer::Argument);
+            }
+            _ => panic!("Unexpected error {}", error),
+        }
+
+        let vars = vec![("GAME_MAX_FPS".to_string(), "many".to_string())];
+        let error = ConfigLayers::<TestConfig>::new()
+            .env_vars("GAME_", vars)
+            .build()
+            .unwrap_err();
+        match error {
+            ConfigError::Key { key, layer, .. } => {
+                assert_eq!(key, "max_fps");
+                assert_eq!(layer, ConfigLayer::Environment("GAME_MAX_FPS".to_string()));
+            }
+            _ => panic!("Unexpected error {}", error),
+        }
+
+        assert!(ConfigLayers::<TestConfig>::new().args(vec!["max_fps"]).is_err());
+    }
+}
@@ -27,6 +27,10 @@ use serde::{Deserialize, Serialize};
 #[cfg(feature = "json")]
 use serde_json::error::Error as SerJsonError;
 
+pub use crate::layers::{ConfigLayer, ConfigLayers};
+
+mod layers;
+
 /// Error related to anything that manages/creates configurations as well as
 /// "workspace"-related things.
 #[derive(Debug)]
@@ -45,6 +49,15 @@ pub enum ConfigError {
     /// Forward to bincode's errors
     #[cfg(feature = "binary")]
     BincodeError(BincodeError),
+    /// A value of a `ConfigLayers` layer couldn't be set.
+    Key {
+        /// The dot separated key of the value
+        key: String,
+        /// The layer of the value
+        layer: ConfigLayer,
+        /// What is wrong with the value
+        message: String,
+    },
 }
 
 /// Config file format for serde
@@ -83,6 +96,17 @@ impl fmt::Display for ConfigError {
             ConfigError::SerdeJsonError(ref msg) => write!(f, "{}", msg),
             #[cfg(feature = "binary")]
             ConfigError::BincodeError(ref msg) => write!(f, "{}", msg),
+            ConfigError::Key {
+                ref key,
+                ref layer,
+                ref message,
+            } => {
+                write!(
+                    f,
+                    "Invalid value for `{}` from the {}: {}",
+                    key, layer, message
+                )
+            }
         }
     }
 }
@@ -124,6 +148,7 @@ impl Error for ConfigError {
             ConfigError::SerdeJsonError(_) => "Serialization or deserialization error (serde_json)",
             #[cfg(feature = "binary")]
             ConfigError::BincodeError(_) => "Serialization or deserialization error (bincode)",
+            ConfigError::Key { .. } => "Invalid configuration value",
         }
     }
 
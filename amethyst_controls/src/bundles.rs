@@ -1,6 +1,10 @@
 use std::borrow::Cow;
 
-use amethyst_core::{ecs::*, math::one, shrev::EventChannel};
+use amethyst_core::{
+    ecs::*,
+    math::one,
+    shrev::{EventChannel, ReaderId},
+};
 use amethyst_error::Error;
 use winit::event::Event;
 
@@ -21,12 +25,12 @@ use super::*;
 ///
 /// * `FlyMovementSystem`
 /// * `FreeRotationSystem`
+/// * `MouseLookInputSystem`, when the mouse look has an aim or a recenter action
 /// * `MouseFocusUpdateSystem`
 /// * `CursorHideSystem`
 #[derive(Debug)]
 pub struct FlyControlBundle {
-    sensitivity_x: f32,
-    sensitivity_y: f32,
+    mouse_look: MouseLookConfig,
     speed: f32,
     horizontal_axis: Option<Cow<'static, str>>,
     vertical_axis: Option<Cow<'static, str>>,
@@ -41,8 +45,7 @@ impl FlyControlBundle {
         longitudinal_axis: Option<Cow<'static, str>>,
     ) -> Self {
         FlyControlBundle {
-            sensitivity_x: 1.0,
-            sensitivity_y: 1.0,
+            mouse_look: MouseLookConfig::default(),
             speed: one(),
             horizontal_axis,
             vertical_axis,
@@ -52,8 +55,13 @@ impl FlyControlBundle {
 
     /// Alters the mouse sensitivy on this `FlyControlBundle`
     pub fn with_sensitivity(mut self, x: f32, y: f32) -> Self {
-        self.sensitivity_x = x;
-        self.sensitivity_y = y;
+        self.mouse_look.look = Sensitivity::new(x, y);
+        self
+    }
+
+    /// Sets the settings of the mouse look, replacing the sensitivity.
+    pub fn with_mouse_look(mut self, config: MouseLookConfig) -> Self {
+        self.mouse_look = config;
         self
     }
 
@@ -83,11 +91,7 @@ impl SystemBundle for FlyControlBundle {
             .expect("Window event channel not found in resources")
             .register_reader();
 
-        builder.add_system(FreeRotationSystem {
-            sensitivity_x: self.sensitivity_x,
-            sensitivity_y: self.sensitivity_y,
-            reader,
-        });
+        add_mouse_look(&self.mouse_look, reader, resources, builder);
 
         resources.insert(WindowFocus::new());
 
@@ -111,26 +115,62 @@ impl SystemBundle for FlyControlBundle {
 /// You might want to add "fly_movement" and "free_rotation" as dependencies of the TransformSystem.
 /// Adding this bundle will grab the mouse, hide it and keep it centered.
 ///
+/// The `ControlCollision` resource is inserted if it's missing, set its query to keep the
+/// cameras with a collision radius out of geometry.
+///
 /// See the `arc_ball_camera` example to see how to use the arc ball camera.
 #[derive(Debug)]
 pub struct ArcBallControlBundle {
-    sensitivity_x: f32,
-    sensitivity_y: f32,
+    mouse_look: MouseLookConfig,
+    zoom_axis: Option<Cow<'static, str>>,
+    zoom_speed: f32,
+    pan_horizontal_axis: Option<Cow<'static, str>>,
+    pan_vertical_axis: Option<Cow<'static, str>>,
+    pan_speed: f32,
 }
 
 impl ArcBallControlBundle {
     /// Builds a new `ArcBallControlBundle` with a default sensitivity of 1.0
     pub fn new() -> Self {
         ArcBallControlBundle {
-            sensitivity_x: 1.0,
-            sensitivity_y: 1.0,
+            mouse_look: MouseLookConfig::default(),
+            zoom_axis: None,
+            zoom_speed: 1.0,
+            pan_horizontal_axis: None,
+            pan_vertical_axis: None,
+            pan_speed: 1.0,
         }
     }
 
     /// Builds a new `ArcBallControlBundle` with the provided mouse sensitivity values.
     pub fn with_sensitivity(mut self, x: f32, y: f32) -> Self {
-        self.sensitivity_x = x;
-        self.sensitivity_y = y;
+        self.mouse_look.look = Sensitivity::new(x, y);
+        self
+    }
+
+    /// Sets the settings of the mouse look, replacing the sensitivity.
+    pub fn with_mouse_look(mut self, config: MouseLookConfig) -> Self {
+        self.mouse_look = config;
+        self
+    }
+
+    /// Zooms the cameras with the given input axis, by `speed` units per second.
+    pub fn with_zoom_axis(mut self, axis: Cow<'static, str>, speed: f32) -> Self {
+        self.zoom_axis = Some(axis);
+        self.zoom_speed = speed;
+        self
+    }
+
+    /// Pans the cameras with the given input axes, by `speed` units per second.
+    pub fn with_pan_axes(
+        mut self,
+        horizontal_axis: Option<Cow<'static, str>>,
+        vertical_axis: Option<Cow<'static, str>>,
+        speed: f32,
+    ) -> Self {
+        self.pan_horizontal_axis = horizontal_axis;
+        self.pan_vertical_axis = vertical_axis;
+        self.pan_speed = speed;
         self
     }
 }
@@ -153,12 +193,24 @@ impl SystemBundle for ArcBallControlBundle {
             .expect("Window event channel not found in resources")
             .register_reader();
 
-        builder.add_system(FreeRotationSystem {
-            sensitivity_x: self.sensitivity_x,
-            sensitivity_y: self.sensitivity_y,
-            reader,
-        });
+        add_mouse_look(&self.mouse_look, reader, resources, builder);
 
+        if self.zoom_axis.is_some()
+            || self.pan_horizontal_axis.is_some()
+            || self.pan_vertical_axis.is_some()
+        {
+            builder.add_system(ArcBallInputSystem {
+                zoom_axis: self.zoom_axis.clone(),
+                zoom_speed: self.zoom_speed,
+                pan_horizontal_axis: self.pan_horizontal_axis.clone(),
+                pan_vertical_axis: self.pan_vertical_axis.clone(),
+                pan_speed: self.pan_speed,
+            });
+        }
+
+        if !resources.contains::<ControlCollision>() {
+            resources.insert(ControlCollision::default());
+        }
         builder.add_system(ArcBallRotationSystem);
 
         resources.insert(WindowFocus::new());
@@ -176,3 +228,199 @@ impl SystemBundle for ArcBallControlBundle {
         Ok(())
     }
 }
+
+/// The bundle that creates a character movement system, for first person games.
+///
+/// Note: Will not actually create a moving entity. It will only register the needed resources and
+/// systems. The `ControlCollision` resource is inserted if it's missing, set its query for the
+/// characters to collide with the geometry of the game.
+///
+/// # Systems
+///
+/// This bundle adds the following systems:
+///
+/// * `CharacterMovementSystem`
+/// * `FreeRotationSystem`
+/// * `MouseLookInputSystem`, when the mouse look has an aim or a recenter action
+/// * `MouseFocusUpdateSystem`
+/// * `CursorHideSystem`
+#[derive(Debug)]
+pub struct CharacterControlBundle {
+    mouse_look: MouseLookConfig,
+    horizontal_axis: Option<Cow<'static, str>>,
+    longitudinal_axis: Option<Cow<'static, str>>,
+    jump_action: Option<Cow<'static, str>>,
+    crouch_action: Option<Cow<'static, str>>,
+}
+
+impl CharacterControlBundle {
+    /// Builds a new character control bundle using the provided axes as controls.
+    pub fn new(
+        horizontal_axis: Option<Cow<'static, str>>,
+        longitudinal_axis: Option<Cow<'static, str>>,
+    ) -> Self {
+        CharacterControlBundle {
+            mouse_look: MouseLookConfig::default(),
+            horizontal_axis,
+            longitudinal_axis,
+            jump_action: None,
+            crouch_action: None,
+        }
+    }
+
+    /// Alters the mouse sensitivy on this `CharacterControlBundle`
+    pub fn with_sensitivity(mut self, x: f32, y: f32) -> Self {
+        self.mouse_look.look = Sensitivity::new(x, y);
+        self
+    }
+
+    /// Sets the settings of the mouse look, replacing the sensitivity.
+    pub fn with_mouse_look(mut self, config: MouseLookConfig) -> Self {
+        self.mouse_look = config;
+        self
+    }
+
+    /// Jumps with the given input action.
+    pub fn with_jump_action(mut self, action: Cow<'static, str>) -> Self {
+        self.jump_action = Some(action);
+        self
+    }
+
+    /// Crouches while the given input action is down.
+    pub fn with_crouch_action(mut self, action: Cow<'static, str>) -> Self {
+        self.crouch_action = Some(action);
+        self
+    }
+}
+
+impl SystemBundle for CharacterControlBundle {
+    fn load(
+        &mut self,
+        _world: &mut World,
+        resources: &mut Resources,
+        builder: &mut DispatcherBuilder,
+    ) -> Result<(), Error> {
+        if !resources.contains::<ControlCollision>() {
+            resources.insert(ControlCollision::default());
+        }
+        builder.add_system(CharacterMovementSystem {
+            horizontal_axis: self.horizontal_axis.clone(),
+            longitudinal_axis: self.longitudinal_axis.clone(),
+            jump_action: self.jump_action.clone(),
+            crouch_action: self.crouch_action.clone(),
+        });
+
+        let reader = resources
+            .get_mut::<EventChannel<Event<'static, ()>>>()
+            .expect("Window event channel not found in resources")
+            .register_reader();
+
+        add_mouse_look(&self.mouse_look, reader, resources, builder);
+
+        resources.insert(WindowFocus::new());
+
+        let reader = resources
+            .get_mut::<EventChannel<Event<'static, ()>>>()
+            .expect("Window event channel not found in resources")
+            .register_reader();
+
+        builder.add_system(MouseFocusUpdateSystem { reader });
+
+        resources.insert(HideCursor::default());
+        builder.add_thread_local(CursorHideSystem);
+
+        Ok(())
+    }
+}
+
+/// The bundle that creates the system moving the follow cameras.
+///
+/// The `ControlCollision` resource is inserted if it's missing, set its query for the cameras to
+/// zoom in when geometry is in the way.
+///
+/// # Systems
+///
+/// This bundle adds the following systems:
+///
+/// * `FollowCameraSystem`
+#[derive(Debug, Default)]
+pub struct FollowCameraBundle;
+
+impl SystemBundle for FollowCameraBundle {
+    fn load(
+        &mut self,
+        _world: &mut World,
+        resources: &mut Resources,
+        builder: &mut DispatcherBuilder,
+    ) -> Result<(), Error> {
+        if !resources.contains::<ControlCollision>() {
+            resources.insert(ControlCollision::default());
+        }
+        builder.add_system(FollowCameraSystem);
+        Ok(())
+    }
+}
+
+/// The bundle that creates the system shaking the cameras.
+///
+/// # Systems
+///
+/// This bundle adds the following systems:
+///
+/// * `CameraShakeSystem`
+#[derive(Debug, Default)]
+pub struct CameraShakeBundle;
+
+impl SystemBundle for CameraShakeBundle {
+    fn load(
+        &mut self,
+        _world: &mut World,
+        _resources: &mut Resources,
+        builder: &mut DispatcherBuilder,
+    ) -> Result<(), Error> {
+        builder.add_system(CameraShakeSystem);
+        Ok(())
+    }
+}
+
+/// The bundle that creates the system moving entities along their camera rails.
+///
+/// The `RailEvent`s are sent to the `EventChannel<RailEvent>` resource, which is inserted if
+/// it's missing.
+///
+/// # Systems
+///
+/// This bundle adds the following systems:
+///
+/// * `CameraRailSystem`
+#[derive(Debug, Default)]
+pub struct CameraRailBundle;
+
+impl SystemBundle for CameraRailBundle {
+    fn load(
+        &mut self,
+        _world: &mut World,
+        resources: &mut Resources,
+        builder: &mut DispatcherBuilder,
+    ) -> Result<(), Error> {
+        if !resources.contains::<EventChannel<RailEvent>>() {
+            resources.insert(EventChannel::<RailEvent>::new());
+        }
+        builder.add_system(CameraRailSystem);
+        Ok(())
+    }
+}
+
+/// Adds the systems rotating the view with the mouse.
+fn add_mouse_look(
+    config: &MouseLookConfig,
+    reader: ReaderId<Event<'static, ()>>,
+    resources: &mut Resources,
+    builder: &mut DispatcherBuilder,
+) {
+    resources.insert(MouseLook::new(config.clone()));
+    if config.aim_action.is_some() || config.recenter_action.is_some() {
+        builder.add_system(MouseLookInputSystem);
+    }
+    builder.add_system(FreeRotationSystem { reader });
+}
@@ -0,0 +1,329 @@
+use std::borrow::Cow;
+
+use amethyst_core::{
+    ecs::*,
+    math::{Unit, Vector3},
+    timing::Time,
+    transform::Transform,
+};
+use amethyst_input::{get_action_simple, get_input_axis_simple, InputHandler};
+use derive_new::new;
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+
+use crate::collision::ControlCollision;
+
+/// Gap kept between the character and the geometry it collides with.
+const SKIN: f32 = 0.01;
+
+/// How the horizontal velocity of a character reaches the velocity of its input.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum AccelerationCurve {
+    /// The velocity changes instantly
+    Instant,
+    /// The velocity changes by the acceleration, in units per second squared
+    Linear,
+    /// The velocity closes the gap to the input velocity by the acceleration, as a fraction
+    /// per second, quickly at first and slowly at the end
+    Exponential,
+}
+
+impl AccelerationCurve {
+    fn apply(self, current: f32, target: f32, rate: f32, delta_sec: f32) -> f32 {
+        match self {
+            AccelerationCurve::Instant => target,
+            AccelerationCurve::Linear => {
+                let step = rate * delta_sec;
+                current + (target - current).max(-step).min(step)
+            }
+            AccelerationCurve::Exponential => {
+                current + (target - current) * (1.0 - (-rate * delta_sec).exp())
+            }
+        }
+    }
+}
+
+/// Add this to a camera to walk it around like the player of a first person game.
+/// You need to add the `CharacterControlBundle` for it to work.
+///
+/// The transform of the entity is at the eyes of the character, `height` above its feet. The
+/// character collides with the geometry found by the `ControlCollision` resource, which needs
+/// a query for the character to stand on the ground instead of falling.
+#[derive(Debug, Clone, new)]
+pub struct CharacterControl {
+    /// Walking speed, in units per second
+    #[new(value = "4.0")]
+    pub speed: f32,
+    /// Speed while crouching, in units per second
+    #[new(value = "2.0")]
+    pub crouch_speed: f32,
+    /// Rate at which the character speeds up towards its input, see `curve`
+    #[new(value = "30.0")]
+    pub acceleration: f32,
+    /// Rate at which the character slows down without input, see `curve`
+    #[new(value = "40.0")]
+    pub deceleration: f32,
+    /// Fraction of the acceleration available while in the air
+    #[new(value = "0.3")]
+    pub air_control: f32,
+    /// How the velocity reaches the velocity of the input
+    #[new(value = "AccelerationCurve::Linear")]
+    pub curve: AccelerationCurve,
+    /// Downwards acceleration, in units per second squared
+    #[new(value = "9.81")]
+    pub gravity: f32,
+    /// Upwards speed of a jump, in units per second
+    #[new(value = "5.0")]
+    pub jump_speed: f32,
+    /// Height of the eyes above the feet while standing
+    #[new(value = "1.7")]
+    pub height: f32,
+    /// Height of the eyes above the feet while crouching
+    #[new(value = "1.0")]
+    pub crouch_height: f32,
+    /// Time in seconds to crouch or to stand up
+    #[new(value = "0.15")]
+    pub crouch_time: f32,
+    /// Radius of the body of the character
+    #[new(value = "0.3")]
+    pub radius: f32,
+    /// Height of the highest step the character walks onto
+    #[new(value = "0.3")]
+    pub step_height: f32,
+    /// Steepest slope the character stands on, in radians
+    #[new(value = "std::f32::consts::FRAC_PI_4")]
+    pub max_slope: f32,
+    #[new(default)]
+    pub(crate) velocity: Vector3<f32>,
+    #[new(default)]
+    pub(crate) grounded: bool,
+    #[new(default)]
+    pub(crate) crouching: bool,
+    #[new(default)]
+    pub(crate) jump_held: bool,
+    #[new(default)]
+    pub(crate) current_height: Option<f32>,
+}
+
+impl Default for CharacterControl {
+    fn default() -> Self {
+        CharacterControl::new()
+    }
+}
+
+impl CharacterControl {
+    /// The velocity of the character.
+    pub fn velocity(&self) -> &Vector3<f32> {
+        &self.velocity
+    }
+
+    /// Sets the velocity of the character, e.g. when it's pushed by an explosion.
+    pub fn set_velocity(&mut self, velocity: Vector3<f32>) {
+        self.velocity = velocity;
+    }
+
+    /// Returns true if the character stands on the ground.
+    pub fn is_grounded(&self) -> bool {
+        self.grounded
+    }
+
+    /// Returns true if the character is crouching.
+    pub fn is_crouching(&self) -> bool {
+        self.crouching
+    }
+}
+
+/// The input of a character for a frame.
+struct CharacterInput {
+    x: f32,
+    z: f32,
+    jump: bool,
+    crouch: bool,
+}
+
+/// The system that walks the characters with the input.
+///
+/// The horizontal and longitudinal axes move the character relative to the direction it looks
+/// at, flattened on the ground, like the axes of the `FlyMovementSystem`.
+#[derive(Debug)]
+pub struct CharacterMovementSystem {
+    pub(crate) horizontal_axis: Option<Cow<'static, str>>,
+    pub(crate) longitudinal_axis: Option<Cow<'static, str>>,
+    pub(crate) jump_action: Option<Cow<'static, str>>,
+    pub(crate) crouch_action: Option<Cow<'static, str>>,
+}
+
+impl System for CharacterMovementSystem {
+    fn build(self) -> Box<dyn systems::ParallelRunnable> {
+        Box::new(
+            SystemBuilder::new("CharacterMovementSystem")
+                .read_resource::<Time>()
+                .read_resource::<InputHandler>()
+                .read_resource::<ControlCollision>()
+                .with_query(<(&mut CharacterControl, &mut Transform)>::query())
+                .build(
+                    move |_commands, world, (time, input, collision), controls| {
+                        #[cfg(feature = "profiler")]
+                        profile_scope!("character_movement_system");
+
+                        let input = CharacterInput {
+                            x: get_input_axis_simple(&self.horizontal_axis, &input),
+                            z: get_input_axis_simple(&self.longitudinal_axis, &input),
+                            jump: get_action_simple(&self.jump_action, &input),
+                            crouch: get_action_simple(&self.crouch_action, &input),
+                        };
+                        let delta_sec = time.delta_seconds();
+                        for (control, transform) in controls.iter_mut(world) {
+                            move_character(control, transform, &input, delta_sec, &collision);
+                        }
+                    },
+                ),
+        )
+    }
+}
+
+fn move_character(
+    control: &mut CharacterControl,
+    transform: &mut Transform,
+    input: &CharacterInput,
+    delta_sec: f32,
+    collision: &ControlCollision,
+) {
+    let up = Vector3::y_axis();
+    let down = Unit::new_unchecked(-Vector3::y());
+    let radius = control.radius;
+    let height = control.current_height.unwrap_or(control.height);
+    let mut feet = transform.translation() - up.into_inner() * height;
+
+    // stand up only when there is room above the head
+    let mut crouching = input.crouch;
+    if !crouching && control.crouching {
+        let origin = feet + up.into_inner() * radius;
+        let room = control.height - radius;
+        crouching = collision.sphere_cast(&origin, &up, radius, room).is_some();
+    }
+    control.crouching = crouching;
+    let target_height = if crouching {
+        control.crouch_height
+    } else {
+        control.height
+    };
+    let height = if control.crouch_time > 0.0 {
+        height + (target_height - height) * (1.0 - (-delta_sec / control.crouch_time).exp())
+    } else {
+        target_height
+    };
+    control.current_height = Some(height);
+
+    // accelerate towards the input, relative to where the character looks
+    let mut wish = transform.rotation() * Vector3::new(input.x, 0.0, input.z);
+    wish.y = 0.0;
+    if wish.norm() > 1.0 {
+        wish.normalize_mut();
+    }
+    wish *= if crouching {
+        control.crouch_speed
+    } else {
+        control.speed
+    };
+    let rate = if wish.norm_squared() > 0.0 {
+        control.acceleration
+    } else {
+        control.deceleration
+    };
+    let rate = if control.grounded {
+        rate
+    } else {
+        rate * control.air_control
+    };
+    control.velocity.x = control
+        .curve
+        .apply(control.velocity.x, wish.x, rate, delta_sec);
+    control.velocity.z = control
+        .curve
+        .apply(control.velocity.z, wish.z, rate, delta_sec);
+
+    if input.jump && !control.jump_held && control.grounded {
+        control.velocity.y = control.jump_speed;
+        control.grounded = false;
+    }
+    control.jump_held = input.jump;
+    if !control.grounded {
+        control.velocity.y -= control.gravity * delta_sec;
+    }
+
+    // move horizontally, sliding along walls; the body starts above the steps
+    let body_offset = up.into_inner() * (control.step_height + radius);
+    let mut remaining = Vector3::new(control.velocity.x, 0.0, control.velocity.z) * delta_sec;
+    for _ in 0..2 {
+        let distance = remaining.norm();
+        let direction = match Unit::try_new(remaining, 1.0e-6) {
+            Some(direction) => direction,
+            None => break,
+        };
+        match collision.sphere_cast(&(feet + body_offset), &direction, radius, distance) {
+            Some(hit) => {
+                let allowed = (hit.distance - SKIN).max(0.0);
+                feet += direction.into_inner() * allowed;
+                let mut normal = hit.normal;
+                normal.y = 0.0;
+                let normal = match Unit::try_new(normal, 1.0e-6) {
+                    Some(normal) => normal.into_inner(),
+                    None => break,
+                };
+                remaining = direction.into_inner() * (distance - allowed);
+                remaining -= normal * remaining.dot(&normal);
+                let into_wall = control.velocity.dot(&normal);
+                if into_wall < 0.0 {
+                    control.velocity -= normal * into_wall;
+                }
+            }
+            None => {
+                feet += remaining;
+                break;
+            }
+        }
+    }
+
+    // move vertically, stopping at ceilings and at the ground, so falling fast doesn't go through
+    // it; the fall is swept from above the steps, like the ground check below
+    let mut rise = control.velocity.y * delta_sec;
+    if rise > 0.0 {
+        let head = feet + up.into_inner() * (height - radius);
+        if let Some(hit) = collision.sphere_cast(&head, &up, radius, rise) {
+            rise = (hit.distance - SKIN).max(0.0);
+            control.velocity.y = 0.0;
+        }
+    } else if rise < 0.0 {
+        let origin = feet + body_offset;
+        let reach = -rise + control.step_height;
+        if let Some(hit) = collision.sphere_cast(&origin, &down, radius, reach) {
+            rise = control.step_height - hit.distance;
+            control.velocity.y = 0.0;
+        }
+    }
+    feet.y += rise;
+
+    // stand on the ground below the feet, climbing steps and following slopes down
+    let was_grounded = control.grounded;
+    control.grounded = false;
+    if control.velocity.y <= 0.0 {
+        let origin = feet + body_offset;
+        let snap = if was_grounded {
+            control.step_height
+        } else {
+            0.0
+        };
+        let reach = control.step_height + snap + SKIN;
+        if let Some(hit) = collision.sphere_cast(&origin, &down, radius, reach) {
+            if hit.normal.y >= control.max_slope.cos() {
+                feet.y = origin.y - radius - hit.distance;
+                control.velocity.y = 0.0;
+                control.grounded = true;
+            }
+        }
+    }
+
+    transform.set_translation(feet + up.into_inner() * height);
+}
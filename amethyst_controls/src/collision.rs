@@ -0,0 +1,90 @@
+use std::fmt;
+
+use amethyst_core::math::{Unit, Vector3};
+
+/// The first hit of a collision query.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CollisionHit {
+    /// Distance travelled before the hit
+    pub distance: f32,
+    /// Normal of the surface which was hit
+    pub normal: Vector3<f32>,
+}
+
+/// Collision queries used by the controllers, implemented on top of the physics or collision
+/// library of the game.
+///
+/// It is implemented for closures taking the same parameters as `sphere_cast`.
+pub trait CollisionQuery: Send + Sync {
+    /// Sweeps a sphere of the given radius from `origin` along `direction`, returning the first
+    /// hit closer than `max_distance`.
+    fn sphere_cast(
+        &self,
+        origin: &Vector3<f32>,
+        direction: &Unit<Vector3<f32>>,
+        radius: f32,
+        max_distance: f32,
+    ) -> Option<CollisionHit>;
+}
+
+impl<F> CollisionQuery for F
+where
+    F: Fn(&Vector3<f32>, &Unit<Vector3<f32>>, f32, f32) -> Option<CollisionHit> + Send + Sync,
+{
+    fn sphere_cast(
+        &self,
+        origin: &Vector3<f32>,
+        direction: &Unit<Vector3<f32>>,
+        radius: f32,
+        max_distance: f32,
+    ) -> Option<CollisionHit> {
+        self(origin, direction, radius, max_distance)
+    }
+}
+
+/// Resource holding the collision queries of the controllers. Nothing collides until a query
+/// is set.
+#[derive(Default)]
+pub struct ControlCollision {
+    query: Option<Box<dyn CollisionQuery>>,
+}
+
+impl fmt::Debug for ControlCollision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ControlCollision")
+            .field("enabled", &self.query.is_some())
+            .finish()
+    }
+}
+
+impl ControlCollision {
+    /// Creates the resource with the given query.
+    pub fn new<Q: CollisionQuery + 'static>(query: Q) -> Self {
+        ControlCollision {
+            query: Some(Box::new(query)),
+        }
+    }
+
+    /// Sets the query, replacing the previous one.
+    pub fn set<Q: CollisionQuery + 'static>(&mut self, query: Q) {
+        self.query = Some(Box::new(query));
+    }
+
+    /// Returns true if a query is set.
+    pub fn is_enabled(&self) -> bool {
+        self.query.is_some()
+    }
+
+    /// Sweeps a sphere with the query, see [CollisionQuery::sphere_cast].
+    pub fn sphere_cast(
+        &self,
+        origin: &Vector3<f32>,
+        direction: &Unit<Vector3<f32>>,
+        radius: f32,
+        max_distance: f32,
+    ) -> Option<CollisionHit> {
+        self.query
+            .as_ref()
+            .and_then(|query| query.sphere_cast(origin, direction, radius, max_distance))
+    }
+}
@@ -1,4 +1,7 @@
-use amethyst_core::ecs::*;
+use amethyst_core::{
+    ecs::*,
+    math::{UnitQuaternion, Vector3},
+};
 use derive_new::new;
 use serde::{Deserialize, Serialize};
 
@@ -14,4 +17,111 @@ pub struct ArcBallControl {
     pub target: Entity,
     /// The distance from the target entity that the camera should orbit at.
     pub distance: f32,
+    /// The lowest and highest pitch of the camera, in radians.
+    #[new(value = "(-1.5, 1.5)")]
+    pub pitch_limits: (f32, f32),
+    /// The closest and farthest distance the camera can be zoomed to.
+    #[new(value = "(0.5, 1000.0)")]
+    pub distance_limits: (f32, f32),
+    /// Time in seconds the camera takes to catch up with the target and the zoom, 0 to follow
+    /// them instantly.
+    #[new(value = "0.0")]
+    pub smoothing: f32,
+    /// Time in seconds the rotation takes to slow down once the mouse stops, 0 to stop the
+    /// rotation instantly.
+    #[new(value = "0.0")]
+    pub inertia: f32,
+    /// Offset of the orbited point from the target, moved by panning.
+    #[new(value = "Vector3::zeros()")]
+    pub pan: Vector3<f32>,
+    /// Radius of the sphere around the camera which is kept out of geometry with the
+    /// `ControlCollision` resource, or `None` to go through geometry.
+    #[new(default)]
+    pub collision_radius: Option<f32>,
+    #[new(default)]
+    pub(crate) input: (f32, f32),
+    #[new(default)]
+    pub(crate) state: Option<ArcBallState>,
+}
+
+impl ArcBallControl {
+    /// Sets the lowest and highest pitch of the camera, in radians.
+    pub fn with_pitch_limits(mut self, min: f32, max: f32) -> Self {
+        self.pitch_limits = (min, max);
+        self
+    }
+
+    /// Sets the closest and farthest distance the camera can be zoomed to.
+    pub fn with_distance_limits(mut self, min: f32, max: f32) -> Self {
+        self.distance_limits = (min, max);
+        self
+    }
+
+    /// Sets the time in seconds the camera takes to catch up with the target and the zoom.
+    pub fn with_smoothing(mut self, smoothing: f32) -> Self {
+        self.smoothing = smoothing;
+        self
+    }
+
+    /// Sets the time in seconds the rotation takes to slow down once the mouse stops.
+    pub fn with_inertia(mut self, inertia: f32) -> Self {
+        self.inertia = inertia;
+        self
+    }
+
+    /// Keeps a sphere of the given radius around the camera out of geometry.
+    pub fn with_collision_radius(mut self, radius: f32) -> Self {
+        self.collision_radius = Some(radius);
+        self
+    }
+
+    /// Moves the camera closer to the target by `amount`, within the distance limits.
+    pub fn zoom(&mut self, amount: f32) {
+        self.distance = clamp(
+            self.distance - amount,
+            self.distance_limits.0,
+            self.distance_limits.1,
+        );
+    }
+
+    /// Rotates the camera around the target, in radians.
+    pub fn rotate(&mut self, yaw: f32, pitch: f32) {
+        self.input.0 += yaw;
+        self.input.1 += pitch;
+    }
+}
+
+/// The orientation of an arc ball camera, and the smoothed values it follows.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ArcBallState {
+    pub(crate) yaw: f32,
+    pub(crate) pitch: f32,
+    pub(crate) yaw_speed: f32,
+    pub(crate) pitch_speed: f32,
+    pub(crate) center: Vector3<f32>,
+    pub(crate) distance: f32,
+}
+
+impl ArcBallState {
+    /// Takes the yaw and pitch of the camera looking along `-Z` with the given rotation.
+    pub(crate) fn new(rotation: &UnitQuaternion<f32>, center: Vector3<f32>, distance: f32) -> Self {
+        let forward = rotation * -Vector3::z();
+        ArcBallState {
+            yaw: (-forward.x).atan2(-forward.z),
+            pitch: clamp(forward.y, -1.0, 1.0).asin(),
+            yaw_speed: 0.0,
+            pitch_speed: 0.0,
+            center,
+            distance,
+        }
+    }
+
+    pub(crate) fn rotation(&self) -> UnitQuaternion<f32> {
+        UnitQuaternion::from_axis_angle(&Vector3::y_axis(), self.yaw)
+            * UnitQuaternion::from_axis_angle(&Vector3::x_axis(), self.pitch)
+    }
+}
+
+pub(crate) fn clamp(value: f32, min: f32, max: f32) -> f32 {
+    value.max(min).min(max)
 }
@@ -0,0 +1,171 @@
+use std::collections::HashMap;
+
+use amethyst_core::{
+    ecs::*,
+    math::{Unit, UnitQuaternion, Vector3},
+    timing::Time,
+    transform::Transform,
+};
+use derive_new::new;
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+
+use crate::collision::ControlCollision;
+
+/// Add this to a camera to follow a target entity from behind, like the camera of a third
+/// person game. You need to add the `FollowCameraBundle` for it to work.
+///
+/// The camera sits at the end of an arm starting at the target, and looks at the start of the
+/// arm. It lags behind the target by its damping, looks ahead in the direction the target
+/// moves, and zooms in when geometry found by the `ControlCollision` resource is in the way.
+#[derive(Debug, Clone, new)]
+pub struct FollowCamera {
+    /// The entity which is followed
+    pub target: Entity,
+    /// The distance from the target to the camera
+    pub arm_length: f32,
+    /// The direction of the arm, in the space of the target when `follow_rotation` is true
+    #[new(value = "Vector3::new(0.0, 0.5, 1.0)")]
+    pub arm_direction: Vector3<f32>,
+    /// Whether the arm turns with the target
+    #[new(value = "true")]
+    pub follow_rotation: bool,
+    /// Offset of the start of the arm from the target, e.g. the height of the head
+    #[new(value = "Vector3::new(0.0, 1.5, 0.0)")]
+    pub offset: Vector3<f32>,
+    /// Time in seconds the camera takes to catch up with the target, 0 to follow instantly
+    #[new(value = "0.2")]
+    pub damping: f32,
+    /// Time in seconds the camera looks ahead of a moving target
+    #[new(value = "0.3")]
+    pub look_ahead: f32,
+    /// Time in seconds the look ahead takes to follow a change of the velocity of the target
+    #[new(value = "0.5")]
+    pub look_ahead_damping: f32,
+    /// Radius of the sphere around the camera which is kept out of geometry
+    #[new(value = "0.2")]
+    pub collision_radius: f32,
+    /// Shortest arm the camera zooms in to when the view is occluded
+    #[new(value = "0.5")]
+    pub min_arm_length: f32,
+    /// Time in seconds the camera takes to zoom back out once the view is clear
+    #[new(value = "0.5")]
+    pub zoom_out_damping: f32,
+    #[new(default)]
+    pub(crate) state: Option<FollowState>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct FollowState {
+    last_target: Vector3<f32>,
+    velocity: Vector3<f32>,
+    pivot: Vector3<f32>,
+    arm: f32,
+}
+
+/// The system that moves the `FollowCamera`s after their targets.
+#[derive(Debug)]
+pub struct FollowCameraSystem;
+
+impl System for FollowCameraSystem {
+    fn build(self) -> Box<dyn systems::ParallelRunnable> {
+        Box::new(
+            SystemBuilder::new("FollowCameraSystem")
+                .read_resource::<Time>()
+                .read_resource::<ControlCollision>()
+                .with_query(<&FollowCamera>::query())
+                .with_query(<(&mut FollowCamera, &mut Transform)>::query())
+                .read_component::<Transform>()
+                .build(move |_commands, world, (time, collision), queries| {
+                    #[cfg(feature = "profiler")]
+                    profile_scope!("follow_camera_system");
+
+                    let targets: HashMap<Entity, Transform> = queries
+                        .0
+                        .iter(world)
+                        .filter_map(|camera| {
+                            world
+                                .entry_ref(camera.target)
+                                .ok()
+                                .and_then(|e| e.into_component::<Transform>().ok())
+                                .map(|transform| (camera.target, *transform))
+                        })
+                        .collect();
+
+                    let delta_sec = time.delta_seconds();
+                    for (camera, transform) in queries.1.iter_mut(world) {
+                        if let Some(target) = targets.get(&camera.target) {
+                            follow(camera, transform, target, delta_sec, &collision);
+                        }
+                    }
+                }),
+        )
+    }
+}
+
+/// Fraction of the way to the goal covered in a frame, for a damping time in seconds.
+fn blend(damping: f32, delta_sec: f32) -> f32 {
+    if damping > 0.0 {
+        1.0 - (-delta_sec / damping).exp()
+    } else {
+        1.0
+    }
+}
+
+fn follow(
+    camera: &mut FollowCamera,
+    transform: &mut Transform,
+    target: &Transform,
+    delta_sec: f32,
+    collision: &ControlCollision,
+) {
+    let position = *target.translation();
+    let state = camera.state.get_or_insert(FollowState {
+        last_target: position,
+        velocity: Vector3::zeros(),
+        pivot: position + camera.offset,
+        arm: camera.arm_length,
+    });
+
+    if delta_sec > 0.0 {
+        let velocity = (position - state.last_target) / delta_sec;
+        state.velocity += (velocity - state.velocity) * blend(camera.look_ahead_damping, delta_sec);
+    }
+    state.last_target = position;
+
+    let goal = position + camera.offset + state.velocity * camera.look_ahead;
+    state.pivot += (goal - state.pivot) * blend(camera.damping, delta_sec);
+
+    let direction = if camera.follow_rotation {
+        // only the heading of the target turns the arm
+        let forward = target.rotation() * Vector3::z();
+        let yaw = forward.x.atan2(forward.z);
+        UnitQuaternion::from_axis_angle(&Vector3::y_axis(), yaw) * camera.arm_direction
+    } else {
+        camera.arm_direction
+    };
+    let direction = match Unit::try_new(direction, 1.0e-6) {
+        Some(direction) => direction,
+        None => return,
+    };
+
+    // zoom in at once when the view is occluded, and back out smoothly
+    let clear = collision
+        .sphere_cast(
+            &state.pivot,
+            &direction,
+            camera.collision_radius,
+            camera.arm_length,
+        )
+        .map_or(camera.arm_length, |hit| {
+            hit.distance.max(camera.min_arm_length)
+        });
+    if clear < state.arm {
+        state.arm = clear;
+    } else {
+        state.arm += (clear - state.arm) * blend(camera.zoom_out_damping, delta_sec);
+    }
+
+    transform.set_translation(state.pivot + direction.into_inner() * state.arm);
+    transform.face_towards(state.pivot, Vector3::y());
+}
@@ -14,16 +14,32 @@
 #![allow(clippy::new_without_default)]
 
 pub use self::{
-    bundles::{ArcBallControlBundle, FlyControlBundle},
+    bundles::{
+        ArcBallControlBundle, CameraRailBundle, CameraShakeBundle, CharacterControlBundle,
+        FlyControlBundle, FollowCameraBundle,
+    },
+    character::{AccelerationCurve, CharacterControl, CharacterMovementSystem},
+    collision::{CollisionHit, CollisionQuery, ControlCollision},
     components::{ArcBallControl, FlyControl},
-    resources::{HideCursor, WindowFocus},
+    follow::{FollowCamera, FollowCameraSystem},
+    rail::{
+        CameraRail, CameraRailSystem, RailCurve, RailEase, RailEvent, RailFollower, RailLook,
+        RailSpeed,
+    },
+    resources::{AimMode, HideCursor, MouseLook, MouseLookConfig, Sensitivity, WindowFocus},
+    shake::{CameraShake, CameraShakeSystem},
     systems::{
-        ArcBallRotationSystem, CursorHideSystem, FlyMovementSystem, FreeRotationSystem,
-        MouseFocusUpdateSystem,
+        ArcBallInputSystem, ArcBallRotationSystem, CursorHideSystem, FlyMovementSystem,
+        FreeRotationSystem, MouseFocusUpdateSystem, MouseLookInputSystem,
     },
 };
 
 mod bundles;
+mod character;
+mod collision;
 mod components;
+mod follow;
+mod rail;
 mod resources;
+mod shake;
 mod systems;
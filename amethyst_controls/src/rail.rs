@@ -0,0 +1,412 @@
+use std::collections::HashMap;
+
+use amethyst_assets::{
+    distill_importer::{typetag, SerdeImportable},
+    register_asset_type, Asset, AssetProcessorSystem, AssetStorage, Handle,
+};
+use amethyst_core::{
+    ecs::*, math::Vector3, shrev::EventChannel, timing::Time, transform::Transform,
+};
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+use type_uuid::TypeUuid;
+
+/// Number of samples per segment of the table mapping distances along a rail to its curve.
+const SAMPLES_PER_SEGMENT: usize = 16;
+
+/// The curve going through the control points of a `CameraRail`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RailCurve {
+    /// A Catmull-Rom spline, passing through every control point
+    CatmullRom,
+    /// Cubic Bézier segments, where each segment is made of a point, two handles and the point
+    /// starting the next segment, so a rail of `n` segments has `3n + 1` control points
+    Bezier,
+}
+
+impl Default for RailCurve {
+    fn default() -> Self {
+        RailCurve::CatmullRom
+    }
+}
+
+/// A path for cameras and other entities to move along, e.g. for cutscenes and intros.
+/// Add a `RailFollower` to an entity to move it along the rail.
+///
+/// ```ron
+/// (
+///     curve: CatmullRom,
+///     points: [[0.0, 2.0, 10.0], [5.0, 3.0, 5.0], [10.0, 2.0, 0.0], [5.0, 1.0, -5.0]],
+///     closed: false,
+/// )
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TypeUuid)]
+#[uuid = "3b7a8c1e-63d2-4a8b-9f0e-5a2c6d41b7e9"]
+pub struct CameraRail {
+    /// The curve going through the points
+    #[serde(default)]
+    pub curve: RailCurve,
+    /// The control points, in world space
+    pub points: Vec<Vector3<f32>>,
+    /// Loops back from the last point to the first one, for Catmull-Rom rails
+    #[serde(default)]
+    pub closed: bool,
+}
+
+impl Asset for CameraRail {
+    fn name() -> &'static str {
+        "controls::CameraRail"
+    }
+    type Data = Self;
+}
+
+#[typetag::serde]
+impl SerdeImportable for CameraRail {}
+register_asset_type!(CameraRail => CameraRail; AssetProcessorSystem<CameraRail>);
+
+impl CameraRail {
+    /// Creates a rail through the given points.
+    pub fn new(curve: RailCurve, points: Vec<Vector3<f32>>) -> Self {
+        CameraRail {
+            curve,
+            points,
+            closed: false,
+        }
+    }
+
+    /// The number of segments of the curve.
+    pub fn segments(&self) -> usize {
+        let n = self.points.len();
+        match self.curve {
+            RailCurve::CatmullRom if n < 2 => 0,
+            RailCurve::CatmullRom if self.closed => n,
+            RailCurve::CatmullRom => n - 1,
+            RailCurve::Bezier => n.saturating_sub(1) / 3,
+        }
+    }
+
+    /// The point of the curve at `t`, from 0 at the start to the number of segments at the end.
+    pub fn point(&self, t: f32) -> Vector3<f32> {
+        let segments = self.segments();
+        if segments == 0 {
+            return self.points.first().copied().unwrap_or_else(Vector3::zeros);
+        }
+        let t = t.max(0.0).min(segments as f32);
+        let segment = (t.floor() as usize).min(segments - 1);
+        let f = t - segment as f32;
+        match self.curve {
+            RailCurve::CatmullRom => {
+                let p = |i: isize| self.catmull_rom_point(i);
+                let i = segment as isize;
+                catmull_rom(p(i - 1), p(i), p(i + 1), p(i + 2), f)
+            }
+            RailCurve::Bezier => {
+                let p = &self.points[segment * 3..segment * 3 + 4];
+                bezier(p[0], p[1], p[2], p[3], f)
+            }
+        }
+    }
+
+    /// The length of the curve, measured along the samples of the distance table.
+    pub fn length(&self) -> f32 {
+        self.distance_table().last().map_or(0.0, |d| d.1)
+    }
+
+    fn catmull_rom_point(&self, i: isize) -> Vector3<f32> {
+        let n = self.points.len() as isize;
+        let i = if self.closed {
+            i.rem_euclid(n)
+        } else {
+            i.max(0).min(n - 1)
+        };
+        self.points[i as usize]
+    }
+
+    /// Samples of the curve as `(t, distance from the start)`.
+    fn distance_table(&self) -> Vec<(f32, f32)> {
+        let samples = self.segments() * SAMPLES_PER_SEGMENT;
+        let mut table = Vec::with_capacity(samples + 1);
+        let mut previous = self.point(0.0);
+        let mut distance = 0.0;
+        table.push((0.0, 0.0));
+        for i in 1..=samples {
+            let t = i as f32 / SAMPLES_PER_SEGMENT as f32;
+            let point = self.point(t);
+            distance += (point - previous).norm();
+            previous = point;
+            table.push((t, distance));
+        }
+        table
+    }
+}
+
+fn catmull_rom(
+    p0: Vector3<f32>,
+    p1: Vector3<f32>,
+    p2: Vector3<f32>,
+    p3: Vector3<f32>,
+    t: f32,
+) -> Vector3<f32> {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    (p1 * 2.0
+        + (p2 - p0) * t
+        + (p0 * 2.0 - p1 * 5.0 + p2 * 4.0 - p3) * t2
+        + (p1 * 3.0 - p0 - p2 * 3.0 + p3) * t3)
+        * 0.5
+}
+
+fn bezier(
+    p0: Vector3<f32>,
+    p1: Vector3<f32>,
+    p2: Vector3<f32>,
+    p3: Vector3<f32>,
+    t: f32,
+) -> Vector3<f32> {
+    let u = 1.0 - t;
+    p0 * (u * u * u) + p1 * (3.0 * u * u * t) + p2 * (3.0 * u * t * t) + p3 * (t * t * t)
+}
+
+/// How fast a `RailFollower` moves along its rail.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum RailSpeed {
+    /// Moves at a constant speed, in units per second
+    Constant(f32),
+    /// Travels the whole rail in the given seconds, with the given easing
+    Duration {
+        /// Time to travel the rail, in seconds
+        seconds: f32,
+        /// Easing of the travel
+        ease: RailEase,
+    },
+}
+
+/// Easing of the travel of a `RailFollower`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RailEase {
+    /// Constant speed
+    Linear,
+    /// Starts slowly
+    In,
+    /// Ends slowly
+    Out,
+    /// Starts and ends slowly
+    InOut,
+}
+
+impl RailEase {
+    fn apply(self, x: f32) -> f32 {
+        match self {
+            RailEase::Linear => x,
+            RailEase::In => x * x,
+            RailEase::Out => x * (2.0 - x),
+            RailEase::InOut => x * x * (3.0 - 2.0 * x),
+        }
+    }
+}
+
+/// Where a `RailFollower` looks while it moves.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RailLook {
+    /// Keeps the rotation of the transform
+    Free,
+    /// Looks in the direction of the travel
+    Forward,
+    /// Looks at a point, in world space
+    Point(Vector3<f32>),
+    /// Looks at an entity
+    Entity(Entity),
+}
+
+/// Event sent when a `RailFollower` reaches the end of its rail.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RailEvent {
+    /// The entity reached the end of its rail and stopped
+    Finished(Entity),
+    /// The entity reached the end of its rail and started over
+    Looped(Entity),
+}
+
+/// Add this to move an entity along a `CameraRail`. You need to add the `CameraRailBundle` for
+/// it to work.
+#[derive(Debug, Clone)]
+pub struct RailFollower {
+    /// The rail to move along
+    pub rail: Handle<CameraRail>,
+    /// How fast the entity moves
+    pub speed: RailSpeed,
+    /// Where the entity looks
+    pub look: RailLook,
+    /// Starts over at the end of the rail
+    pub looping: bool,
+    /// Moves the entity while true
+    pub playing: bool,
+    elapsed: f32,
+    distance: f32,
+    table: Option<Vec<(f32, f32)>>,
+}
+
+impl RailFollower {
+    /// Moves along the rail at the given speed, looking forward.
+    pub fn new(rail: Handle<CameraRail>, speed: RailSpeed) -> Self {
+        RailFollower {
+            rail,
+            speed,
+            look: RailLook::Forward,
+            looping: false,
+            playing: true,
+            elapsed: 0.0,
+            distance: 0.0,
+            table: None,
+        }
+    }
+
+    /// Sets where the entity looks.
+    pub fn with_look(mut self, look: RailLook) -> Self {
+        self.look = look;
+        self
+    }
+
+    /// Starts over at the end of the rail.
+    pub fn looping(mut self) -> Self {
+        self.looping = true;
+        self
+    }
+
+    /// Distance travelled along the rail.
+    pub fn distance(&self) -> f32 {
+        self.distance
+    }
+
+    /// Moves back to the start of the rail and plays.
+    pub fn restart(&mut self) {
+        self.elapsed = 0.0;
+        self.distance = 0.0;
+        self.playing = true;
+    }
+
+    /// Advances the follower, returning its position and direction, and whether it reached the
+    /// end of the rail.
+    fn advance(&mut self, rail: &CameraRail, delta_sec: f32) -> (Vector3<f32>, Vector3<f32>, bool) {
+        let table = self.table.get_or_insert_with(|| rail.distance_table());
+        let length = table.last().map_or(0.0, |d| d.1);
+
+        self.elapsed += delta_sec;
+        let progress = match self.speed {
+            RailSpeed::Constant(speed) if length > 0.0 => self.elapsed * speed / length,
+            RailSpeed::Duration { seconds, .. } if seconds > 0.0 => self.elapsed / seconds,
+            _ => 1.0,
+        };
+        let ended = progress >= 1.0;
+        let progress = progress.min(1.0);
+        let eased = match self.speed {
+            RailSpeed::Duration { ease, .. } => ease.apply(progress),
+            RailSpeed::Constant(_) => progress,
+        };
+        self.distance = eased * length;
+
+        let t = parameter_at(table, self.distance);
+        let position = rail.point(t);
+        let ahead = rail.point(t + 1.0 / SAMPLES_PER_SEGMENT as f32);
+        let behind = rail.point(t - 1.0 / SAMPLES_PER_SEGMENT as f32);
+        (position, ahead - behind, ended)
+    }
+}
+
+/// Finds the curve parameter at a distance from the start of the rail.
+fn parameter_at(table: &[(f32, f32)], distance: f32) -> f32 {
+    let i = table
+        .iter()
+        .position(|sample| sample.1 >= distance)
+        .unwrap_or_else(|| table.len());
+    if i == 0 {
+        return 0.0;
+    }
+    match table.get(i) {
+        Some(&(t1, d1)) => {
+            let (t0, d0) = table[i - 1];
+            if d1 > d0 {
+                t0 + (t1 - t0) * (distance - d0) / (d1 - d0)
+            } else {
+                t1
+            }
+        }
+        None => table[i - 1].0,
+    }
+}
+
+/// The system that moves the `RailFollower`s along their rails.
+#[derive(Debug)]
+pub struct CameraRailSystem;
+
+impl System for CameraRailSystem {
+    fn build(self) -> Box<dyn systems::ParallelRunnable> {
+        Box::new(
+            SystemBuilder::new("CameraRailSystem")
+                .read_resource::<Time>()
+                .read_resource::<AssetStorage<CameraRail>>()
+                .write_resource::<EventChannel<RailEvent>>()
+                .with_query(<&RailFollower>::query())
+                .with_query(<(Entity, &mut RailFollower, &mut Transform)>::query())
+                .read_component::<Transform>()
+                .build(move |_commands, world, (time, rails, events), queries| {
+                    #[cfg(feature = "profiler")]
+                    profile_scope!("camera_rail_system");
+
+                    let targets: HashMap<Entity, Vector3<f32>> = queries
+                        .0
+                        .iter(world)
+                        .filter_map(|follower| {
+                            match follower.look {
+                                RailLook::Entity(target) => Some(target),
+                                _ => None,
+                            }
+                        })
+                        .filter_map(|target| {
+                            world
+                                .entry_ref(target)
+                                .ok()
+                                .and_then(|e| e.into_component::<Transform>().ok())
+                                .map(|transform| (target, *transform.translation()))
+                        })
+                        .collect();
+
+                    let delta_sec = time.delta_seconds();
+                    for (entity, follower, transform) in queries.1.iter_mut(world) {
+                        if !follower.playing {
+                            continue;
+                        }
+                        let rail = match rails.get(&follower.rail) {
+                            Some(rail) => rail,
+                            None => continue,
+                        };
+                        let (position, direction, ended) = follower.advance(rail, delta_sec);
+                        transform.set_translation(position);
+
+                        let look = match follower.look {
+                            RailLook::Free => None,
+                            RailLook::Forward => Some(position + direction),
+                            RailLook::Point(point) => Some(point),
+                            RailLook::Entity(target) => targets.get(&target).copied(),
+                        };
+                        if let Some(look) = look {
+                            if (look - position).norm_squared() > 1.0e-8 {
+                                transform.face_towards(look, Vector3::y());
+                            }
+                        }
+
+                        if ended {
+                            if follower.looping {
+                                follower.elapsed = 0.0;
+                                events.single_write(RailEvent::Looped(*entity));
+                            } else {
+                                follower.playing = false;
+                                events.single_write(RailEvent::Finished(*entity));
+                            }
+                        }
+                    }
+                }),
+        )
+    }
+}
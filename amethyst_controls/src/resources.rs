@@ -1,3 +1,5 @@
+use std::borrow::Cow;
+
 use serde::{Deserialize, Serialize};
 
 /// Struct which holds information about whether the window is focused.
@@ -22,3 +24,137 @@ pub struct HideCursor {
     /// If true this system will take control of the cursor.
     pub hide: bool,
 }
+
+/// Mouse sensitivity, in degrees per pixel of mouse motion.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Sensitivity {
+    /// Sensitivity of the yaw, for horizontal motion
+    pub x: f32,
+    /// Sensitivity of the pitch, for vertical motion
+    pub y: f32,
+}
+
+impl Sensitivity {
+    /// Creates a sensitivity with the given values per axis.
+    pub fn new(x: f32, y: f32) -> Self {
+        Sensitivity { x, y }
+    }
+}
+
+impl Default for Sensitivity {
+    fn default() -> Self {
+        Sensitivity::new(1.0, 1.0)
+    }
+}
+
+/// How the aim action switches to the aim sensitivity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AimMode {
+    /// Aims while the action is held
+    Hold,
+    /// Each press of the action starts or stops aiming
+    Toggle,
+}
+
+impl Default for AimMode {
+    fn default() -> Self {
+        AimMode::Hold
+    }
+}
+
+/// Settings of the mouse look, passed to the control bundles and usually loaded with the
+/// other settings of the game.
+///
+/// ```ron
+/// (
+///     look: (x: 1.0, y: 0.8),
+///     aim: (x: 0.4, y: 0.4),
+///     invert_y: true,
+///     aim_action: Some("aim"),
+///     aim_mode: Toggle,
+///     recenter_action: Some("recenter"),
+///     recenter_time: 0.2,
+/// )
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MouseLookConfig {
+    /// Sensitivity while not aiming
+    pub look: Sensitivity,
+    /// Sensitivity while aiming
+    pub aim: Sensitivity,
+    /// Looks down when the mouse moves up
+    pub invert_y: bool,
+    /// Input action to aim with
+    pub aim_action: Option<Cow<'static, str>>,
+    /// How the aim action works
+    pub aim_mode: AimMode,
+    /// Input action bringing the view back to level
+    pub recenter_action: Option<Cow<'static, str>>,
+    /// Time in seconds the view takes to come back to level, 0 to level instantly
+    pub recenter_time: f32,
+}
+
+impl Default for MouseLookConfig {
+    fn default() -> Self {
+        MouseLookConfig {
+            look: Sensitivity::default(),
+            aim: Sensitivity::new(0.5, 0.5),
+            invert_y: false,
+            aim_action: None,
+            aim_mode: AimMode::default(),
+            recenter_action: None,
+            recenter_time: 0.15,
+        }
+    }
+}
+
+/// Resource holding the settings and the state of the mouse look, inserted by the control
+/// bundles. Change its config to apply new settings while the game runs.
+#[derive(Debug, Clone, Default)]
+pub struct MouseLook {
+    /// The settings
+    pub config: MouseLookConfig,
+    pub(crate) aiming: bool,
+    pub(crate) recentering: bool,
+}
+
+impl MouseLook {
+    /// Creates the resource with the given settings.
+    pub fn new(config: MouseLookConfig) -> Self {
+        MouseLook {
+            config,
+            aiming: false,
+            recentering: false,
+        }
+    }
+
+    /// Returns true while aiming.
+    pub fn is_aiming(&self) -> bool {
+        self.aiming
+    }
+
+    /// Starts or stops aiming, e.g. when the weapon changes.
+    pub fn set_aiming(&mut self, aiming: bool) {
+        self.aiming = aiming;
+    }
+
+    /// Starts bringing the view back to level.
+    pub fn recenter(&mut self) {
+        self.recentering = true;
+    }
+
+    /// The sensitivity in use, signed by the inversion of the y axis.
+    pub fn sensitivity(&self) -> Sensitivity {
+        let sensitivity = if self.aiming {
+            self.config.aim
+        } else {
+            self.config.look
+        };
+        if self.config.invert_y {
+            Sensitivity::new(sensitivity.x, -sensitivity.y)
+        } else {
+            sensitivity
+        }
+    }
+}
@@ -0,0 +1,165 @@
+use amethyst_core::{
+    ecs::*,
+    math::{Vector2, Vector3},
+    timing::Time,
+    transform::Transform,
+};
+use derive_new::new;
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+
+/// Add this to shake a camera, e.g. on explosions and hits, and to kick it up on recoil.
+/// You need to add the `CameraShakeBundle` for it to work.
+///
+/// The shake replaces the local transform of the entity, so it composes with any camera
+/// controller when it's added to a child of the controlled entity, holding the camera:
+///
+/// ```rust,ignore
+/// let rig = world.push((Transform::default(), ArcBallControl::new(target, 10.0)));
+/// let camera = world.push((
+///     Transform::default(),
+///     Parent(rig),
+///     Camera::standard_3d(width, height),
+///     CameraShake::new(),
+/// ));
+/// ```
+///
+/// The shake is driven by the trauma of the camera, between 0 and 1, which is added by
+/// gameplay events and decays over time. The shake grows with the trauma raised to
+/// `exponent`, so small hits are subtle and big ones are violent.
+#[derive(Debug, Clone, new)]
+pub struct CameraShake {
+    /// Rotation at full trauma around the x, y and z axes, in radians
+    #[new(value = "Vector3::new(0.1, 0.1, 0.05)")]
+    pub max_angles: Vector3<f32>,
+    /// Translation at full trauma along the x, y and z axes
+    #[new(value = "Vector3::new(0.1, 0.1, 0.0)")]
+    pub max_offset: Vector3<f32>,
+    /// Speed of the shake, in noise periods per second
+    #[new(value = "15.0")]
+    pub frequency: f32,
+    /// Trauma removed per second
+    #[new(value = "1.0")]
+    pub decay: f32,
+    /// Power the trauma is raised to before shaking
+    #[new(value = "2.0")]
+    pub exponent: f32,
+    /// Rate at which the recoil returns to rest, as a fraction per second
+    #[new(value = "8.0")]
+    pub recoil_recovery: f32,
+    /// Seed of the noise, give different seeds to cameras which shake together
+    #[new(default)]
+    pub seed: u32,
+    #[new(default)]
+    pub(crate) trauma: f32,
+    #[new(default)]
+    pub(crate) recoil: Vector2<f32>,
+    #[new(default)]
+    pub(crate) time: f32,
+}
+
+impl Default for CameraShake {
+    fn default() -> Self {
+        CameraShake::new()
+    }
+}
+
+impl CameraShake {
+    /// The current trauma, between 0 and 1.
+    pub fn trauma(&self) -> f32 {
+        self.trauma
+    }
+
+    /// Adds trauma, e.g. 0.3 for a hit and 1.0 for an explosion next to the camera.
+    pub fn add_trauma(&mut self, amount: f32) {
+        self.set_trauma(self.trauma + amount);
+    }
+
+    /// Sets the trauma, clamped between 0 and 1.
+    pub fn set_trauma(&mut self, trauma: f32) {
+        self.trauma = trauma.max(0.0).min(1.0);
+    }
+
+    /// Kicks the camera by the given pitch and yaw, in radians. The kick returns to rest by the
+    /// `recoil_recovery`.
+    pub fn recoil(&mut self, pitch: f32, yaw: f32) {
+        self.recoil += Vector2::new(pitch, yaw);
+    }
+
+    /// Stops the shake and the recoil.
+    pub fn reset(&mut self) {
+        self.trauma = 0.0;
+        self.recoil = Vector2::zeros();
+    }
+}
+
+/// The system that shakes the cameras.
+#[derive(Debug)]
+pub struct CameraShakeSystem;
+
+impl System for CameraShakeSystem {
+    fn build(self) -> Box<dyn systems::ParallelRunnable> {
+        Box::new(
+            SystemBuilder::new("CameraShakeSystem")
+                .read_resource::<Time>()
+                .with_query(<(&mut CameraShake, &mut Transform)>::query())
+                .build(move |_commands, world, time, shakes| {
+                    #[cfg(feature = "profiler")]
+                    profile_scope!("camera_shake_system");
+
+                    let delta_sec = time.delta_seconds();
+                    for (shake, transform) in shakes.iter_mut(world) {
+                        shake_camera(shake, transform, delta_sec);
+                    }
+                }),
+        )
+    }
+}
+
+fn shake_camera(shake: &mut CameraShake, transform: &mut Transform, delta_sec: f32) {
+    shake.time += delta_sec * shake.frequency;
+    let amount = shake.trauma.powf(shake.exponent);
+    let t = shake.time;
+    let seed = shake.seed.wrapping_mul(6);
+    let channel = |i: u32| amount * noise(seed.wrapping_add(i), t);
+
+    let angles = Vector3::new(
+        shake.max_angles.x * channel(0) + shake.recoil.x,
+        shake.max_angles.y * channel(1) + shake.recoil.y,
+        shake.max_angles.z * channel(2),
+    );
+    let offset = Vector3::new(
+        shake.max_offset.x * channel(3),
+        shake.max_offset.y * channel(4),
+        shake.max_offset.z * channel(5),
+    );
+    transform.set_rotation_euler(angles.x, angles.y, angles.z);
+    transform.set_translation(offset);
+
+    shake.set_trauma(shake.trauma - shake.decay * delta_sec);
+    shake.recoil *= (-shake.recoil_recovery * delta_sec).exp();
+}
+
+/// Hashes a lattice point of a noise channel to a gradient between -1 and 1.
+fn gradient(channel: u32, point: i32) -> f32 {
+    let mut h = (point as u32)
+        .wrapping_mul(0x9E37_79B9)
+        .wrapping_add(channel.wrapping_mul(0x85EB_CA6B));
+    h ^= h >> 16;
+    h = h.wrapping_mul(0x7FEB_352D);
+    h ^= h >> 15;
+    h = h.wrapping_mul(0x846C_A68B);
+    h ^= h >> 16;
+    (h as f32 / u32::MAX as f32) * 2.0 - 1.0
+}
+
+/// Smooth one dimensional gradient noise, between -1 and 1.
+fn noise(channel: u32, t: f32) -> f32 {
+    let cell = t.floor();
+    let f = t - cell;
+    let point = cell as i32;
+    let a = gradient(channel, point) * f;
+    let b = gradient(channel, point.wrapping_add(1)) * (f - 1.0);
+    let u = f * f * (3.0 - 2.0 * f);
+    (a + (b - a) * u) * 2.0
+}
@@ -8,17 +8,17 @@ use amethyst_core::{
     timing::Time,
     transform::Transform,
 };
-use amethyst_input::{get_input_axis_simple, InputHandler};
+use amethyst_input::{get_action_simple, get_input_axis_simple, InputHandler};
+use amethyst_window::CursorMode;
 #[cfg(feature = "profiler")]
 use thread_profiler::profile_scope;
-use winit::{
-    event::{DeviceEvent, Event, WindowEvent},
-    window::Window,
-};
+use winit::event::{DeviceEvent, Event, WindowEvent};
 
 use crate::{
-    components::{ArcBallControl, FlyControl},
-    resources::{HideCursor, WindowFocus},
+    character::CharacterControl,
+    collision::ControlCollision,
+    components::{clamp, ArcBallControl, ArcBallState, FlyControl},
+    resources::{AimMode, HideCursor, MouseLook, WindowFocus},
 };
 
 /// The system that manages the fly movement.
@@ -60,6 +60,11 @@ impl System for FlyMovementSystem {
 /// In essence, the system will align the camera with its target while keeping the distance to it
 /// and while keeping the orientation of the camera.
 ///
+/// The rotation is limited to the pitch limits of the `ArcBallControl`, keeps going for its
+/// inertia, and the camera catches up with the target and the zoom over its smoothing time. When
+/// the control has a collision radius, the camera is pulled in front of the geometry between it
+/// and the target, as found by the `ControlCollision` resource.
+///
 /// To modify the orientation of the camera in accordance with the mouse input, please use the
 /// `FreeRotationSystem`.
 #[derive(Debug)]
@@ -69,10 +74,12 @@ impl System for ArcBallRotationSystem {
     fn build(self) -> Box<dyn systems::ParallelRunnable> {
         Box::new(
             SystemBuilder::new("ArcBallRotationSystem")
+                .read_resource::<Time>()
+                .read_resource::<ControlCollision>()
                 .with_query(<&ArcBallControl>::query())
-                .with_query(<(&ArcBallControl, &mut Transform)>::query())
+                .with_query(<(&mut ArcBallControl, &mut Transform)>::query())
                 .read_component::<Transform>()
-                .build(move |_commands, world, (), queries| {
+                .build(move |_commands, world, (time, collision), queries| {
                     #[cfg(feature = "profiler")]
                     profile_scope!("arc_ball_rotation_system");
 
@@ -93,13 +100,114 @@ impl System for ArcBallRotationSystem {
                         .map(|t| t.unwrap())
                         .collect();
 
+                    let delta_sec = time.delta_seconds();
                     for (control, transform) in queries.1.iter_mut(world) {
-                        let pos_vec = transform.rotation() * -Vector3::z() * control.distance;
-                        match targets.get(&control.target) {
-                            Some(target_trans) => {
-                                *transform.translation_mut() = target_trans.translation() - pos_vec;
-                            }
+                        let center = match targets.get(&control.target) {
+                            Some(target_trans) => target_trans.translation() + control.pan,
                             None => continue,
+                        };
+                        update_arc_ball(control, transform, center, delta_sec, &collision);
+                    }
+                }),
+        )
+    }
+}
+
+fn update_arc_ball(
+    control: &mut ArcBallControl,
+    transform: &mut Transform,
+    center: Vector3<f32>,
+    delta_sec: f32,
+    collision: &ControlCollision,
+) {
+    control.distance = clamp(
+        control.distance,
+        control.distance_limits.0,
+        control.distance_limits.1,
+    );
+    let (yaw, pitch) = std::mem::take(&mut control.input);
+    let target_distance = control.distance;
+    let state = control
+        .state
+        .get_or_insert_with(|| ArcBallState::new(transform.rotation(), center, target_distance));
+
+    if yaw != 0.0 || pitch != 0.0 {
+        state.yaw += yaw;
+        state.pitch += pitch;
+        if delta_sec > 0.0 {
+            state.yaw_speed = yaw / delta_sec;
+            state.pitch_speed = pitch / delta_sec;
+        }
+    } else if control.inertia > 0.0 {
+        state.yaw += state.yaw_speed * delta_sec;
+        state.pitch += state.pitch_speed * delta_sec;
+        let decay = (-delta_sec / control.inertia).exp();
+        state.yaw_speed *= decay;
+        state.pitch_speed *= decay;
+    } else {
+        state.yaw_speed = 0.0;
+        state.pitch_speed = 0.0;
+    }
+    let (min_pitch, max_pitch) = control.pitch_limits;
+    if state.pitch <= min_pitch || state.pitch >= max_pitch {
+        state.pitch = clamp(state.pitch, min_pitch, max_pitch);
+        state.pitch_speed = 0.0;
+    }
+
+    let blend = if control.smoothing > 0.0 {
+        1.0 - (-delta_sec / control.smoothing).exp()
+    } else {
+        1.0
+    };
+    state.center += (center - state.center) * blend;
+    state.distance += (target_distance - state.distance) * blend;
+
+    let rotation = state.rotation();
+    let back = Unit::new_normalize(rotation * Vector3::z());
+    let mut distance = state.distance;
+    if let Some(radius) = control.collision_radius {
+        if let Some(hit) = collision.sphere_cast(&state.center, &back, radius, distance) {
+            distance = hit.distance.max(0.0);
+        }
+    }
+    transform.set_rotation(rotation);
+    transform.set_translation(state.center + back.into_inner() * distance);
+}
+
+/// The system that zooms and pans arc ball cameras with input axes.
+#[derive(Debug)]
+pub struct ArcBallInputSystem {
+    pub(crate) zoom_axis: Option<Cow<'static, str>>,
+    pub(crate) zoom_speed: f32,
+    pub(crate) pan_horizontal_axis: Option<Cow<'static, str>>,
+    pub(crate) pan_vertical_axis: Option<Cow<'static, str>>,
+    pub(crate) pan_speed: f32,
+}
+
+impl System for ArcBallInputSystem {
+    fn build(self) -> Box<dyn systems::ParallelRunnable> {
+        Box::new(
+            SystemBuilder::new("ArcBallInputSystem")
+                .read_resource::<Time>()
+                .read_resource::<InputHandler>()
+                .with_query(<(&mut ArcBallControl, &Transform)>::query())
+                .build(move |_commands, world, (time, input), controls| {
+                    #[cfg(feature = "profiler")]
+                    profile_scope!("arc_ball_input_system");
+
+                    let delta_sec = time.delta_seconds();
+                    let zoom = get_input_axis_simple(&self.zoom_axis, &input);
+                    let x = get_input_axis_simple(&self.pan_horizontal_axis, &input);
+                    let y = get_input_axis_simple(&self.pan_vertical_axis, &input);
+
+                    for (control, transform) in controls.iter_mut(world) {
+                        if zoom != 0.0 {
+                            control.zoom(zoom * self.zoom_speed * delta_sec);
+                        }
+                        if x != 0.0 || y != 0.0 {
+                            control.pan += transform.rotation()
+                                * Vector3::new(x, y, 0.0)
+                                * (self.pan_speed * delta_sec);
                         }
                     }
                 }),
@@ -109,15 +217,13 @@ impl System for ArcBallRotationSystem {
 
 /// The system that manages the view rotation.
 ///
-/// Controlled by the mouse.
+/// Controlled by the mouse, with the settings of the `MouseLook` resource.
 /// Goes into an inactive state if the window is not focused (`WindowFocus` resource).
 ///
 /// Can be manually disabled by making the mouse visible using the `HideCursor` resource:
 /// `HideCursor.hide = false`
 #[derive(Debug)]
 pub struct FreeRotationSystem {
-    pub(crate) sensitivity_x: f32,
-    pub(crate) sensitivity_y: f32,
     pub(crate) reader: ReaderId<Event<'static, ()>>,
 }
 
@@ -128,31 +234,106 @@ impl System for FreeRotationSystem {
                 .read_resource::<EventChannel<Event<'static, ()>>>()
                 .read_resource::<WindowFocus>()
                 .read_resource::<HideCursor>()
+                .read_resource::<Time>()
+                .write_resource::<MouseLook>()
                 .with_query(
-                    <&mut Transform>::query()
-                        .filter(component::<FlyControl>() | component::<ArcBallControl>()),
+                    <(&mut Transform, Option<&mut ArcBallControl>)>::query().filter(
+                        component::<FlyControl>()
+                            | component::<ArcBallControl>()
+                            | component::<CharacterControl>(),
+                    ),
                 )
-                .build(move |_commands, world, (events, focus, hide), controls| {
-                    #[cfg(feature = "profiler")]
-                    profile_scope!("free_rotation_system");
+                .build(
+                    move |_commands, world, (events, focus, hide, time, look), controls| {
+                        #[cfg(feature = "profiler")]
+                        profile_scope!("free_rotation_system");
 
-                    let focused = focus.is_focused;
-                    for event in events.read(&mut self.reader) {
-                        if focused && hide.hide {
-                            if let Event::DeviceEvent { ref event, .. } = *event {
-                                if let DeviceEvent::MouseMotion { delta: (x, y) } = *event {
-                                    for transform in controls.iter_mut(world) {
-                                        transform.append_rotation_x_axis(
-                                            (-(y as f32) * self.sensitivity_y).to_radians(),
-                                        );
-                                        transform.prepend_rotation_y_axis(
-                                            (-(x as f32) * self.sensitivity_x).to_radians(),
-                                        );
+                        let focused = focus.is_focused;
+                        let sensitivity = look.sensitivity();
+                        for event in events.read(&mut self.reader) {
+                            if focused && hide.hide {
+                                if let Event::DeviceEvent { ref event, .. } = *event {
+                                    if let DeviceEvent::MouseMotion { delta: (x, y) } = *event {
+                                        let pitch = (-(y as f32) * sensitivity.y).to_radians();
+                                        let yaw = (-(x as f32) * sensitivity.x).to_radians();
+                                        for (transform, arc_ball) in controls.iter_mut(world) {
+                                            match arc_ball {
+                                                // applied within the limits of the arc ball
+                                                Some(arc_ball) => arc_ball.rotate(yaw, pitch),
+                                                None => {
+                                                    transform.append_rotation_x_axis(pitch);
+                                                    transform.prepend_rotation_y_axis(yaw);
+                                                }
+                                            }
+                                        }
                                     }
                                 }
                             }
                         }
+
+                        if look.recentering {
+                            let blend = if look.config.recenter_time > 0.0 {
+                                1.0 - (-time.delta_seconds() / look.config.recenter_time).exp()
+                            } else {
+                                1.0
+                            };
+                            let mut level = true;
+                            for (transform, arc_ball) in controls.iter_mut(world) {
+                                let pitch = match arc_ball {
+                                    Some(arc_ball) => {
+                                        let pitch = arc_ball.state.map_or(0.0, |s| s.pitch)
+                                            + arc_ball.input.1;
+                                        arc_ball.rotate(0.0, -pitch * blend);
+                                        pitch
+                                    }
+                                    None => {
+                                        let forward = transform.rotation() * -Vector3::z();
+                                        let pitch = forward.y.max(-1.0).min(1.0).asin();
+                                        transform.append_rotation_x_axis(-pitch * blend);
+                                        pitch
+                                    }
+                                };
+                                level &= (pitch * (1.0 - blend)).abs() < 1.0e-3;
+                            }
+                            look.recentering = !level;
+                        }
+                    },
+                ),
+        )
+    }
+}
+
+/// The system that aims and recenters the view with the input actions of the `MouseLook`
+/// resource.
+#[derive(Debug)]
+pub struct MouseLookInputSystem;
+
+impl System for MouseLookInputSystem {
+    fn build(self) -> Box<dyn systems::ParallelRunnable> {
+        let mut aim_held = false;
+        let mut recenter_held = false;
+
+        Box::new(
+            SystemBuilder::new("MouseLookInputSystem")
+                .read_resource::<InputHandler>()
+                .write_resource::<MouseLook>()
+                .build(move |_commands, _world, (input, look), ()| {
+                    #[cfg(feature = "profiler")]
+                    profile_scope!("mouse_look_input_system");
+
+                    let aim = get_action_simple(&look.config.aim_action, &input);
+                    match look.config.aim_mode {
+                        AimMode::Hold if aim != aim_held => look.aiming = aim,
+                        AimMode::Toggle if aim && !aim_held => look.aiming = !look.aiming,
+                        _ => {}
                     }
+                    aim_held = aim;
+
+                    let recenter = get_action_simple(&look.config.recenter_action, &input);
+                    if recenter && !recenter_held {
+                        look.recenter();
+                    }
+                    recenter_held = recenter;
                 }),
         )
     }
@@ -188,8 +369,11 @@ impl System for MouseFocusUpdateSystem {
     }
 }
 
-/// System which hides the cursor when the window is focused.
+/// System which captures the cursor when the window is focused.
 /// Requires the usage MouseFocusUpdateSystem at the same time.
+///
+/// The cursor itself is updated through the `CursorMode` resource of the window bundle, which is
+/// only written when the desired state changes, so other systems can still switch modes.
 #[derive(Debug)]
 pub struct CursorHideSystem;
 
@@ -201,18 +385,19 @@ impl ThreadLocalSystem<'_> for CursorHideSystem {
             SystemBuilder::new("CursorHideSystem")
                 .read_resource::<HideCursor>()
                 .read_resource::<WindowFocus>()
-                .read_resource::<Window>()
-                .build(move |_commands, _world, (hide, focus, window), ()| {
+                .write_resource::<CursorMode>()
+                .build(move |_commands, _world, (hide, focus, mode), ()| {
                     #[cfg(feature = "profiler")]
                     profile_scope!("cursor_hide_system");
 
                     let should_be_hidden = focus.is_focused && hide.hide;
 
                     if should_be_hidden != is_hidden {
-                        window
-                            .set_cursor_grab(should_be_hidden)
-                            .expect("Failed to set cursor grab state.");
-                        window.set_cursor_visible(!should_be_hidden);
+                        **mode = if should_be_hidden {
+                            CursorMode::Captured
+                        } else {
+                            CursorMode::Normal
+                        };
                         is_hidden = should_be_hidden;
                     }
                 }),
@@ -0,0 +1,205 @@
+//! Catching the panics of systems.
+//!
+//! Insert a [CrashReporter] resource before building a dispatcher, and the dispatcher catches
+//! the panics of the systems it runs, instead of unwinding through the thread pool. Each panic
+//! is turned into an `Error` naming the system, and passed to the handler of the reporter at
+//! the end of the frame, e.g. to upload a crash report or show a dialog. The application then
+//! stops its states and shuts down as if it was closed, unless the reporter is told to keep
+//! running.
+
+use std::{
+    any::Any,
+    fmt,
+    panic::{catch_unwind, AssertUnwindSafe},
+    sync::{Arc, Mutex},
+};
+
+use amethyst_error::Error;
+use legion::{
+    storage::ComponentTypeId,
+    systems::{CommandBuffer, ResourceTypeId, Runnable, SystemId, UnsafeResources},
+    world::{ArchetypeAccess, WorldId},
+    Resources, World,
+};
+use log::error;
+
+type CrashHandler = Box<dyn FnMut(&Error) + Send + Sync>;
+
+/// Collects the panics of the systems running on any thread.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct CrashRecorder(Arc<Mutex<Vec<Error>>>);
+
+impl CrashRecorder {
+    fn record(&self, error: Error) {
+        self.0.lock().expect("poisoned crash recorder").push(error);
+    }
+
+    fn drain(&self) -> Vec<Error> {
+        std::mem::take(&mut *self.0.lock().expect("poisoned crash recorder"))
+    }
+}
+
+/// Resource receiving the panics of the systems of the dispatcher.
+///
+/// Panics are caught when the resource is present while the dispatcher is built. The
+/// application inserts it by default, set its handler with
+/// `ApplicationBuilder::with_crash_handler`.
+pub struct CrashReporter {
+    recorder: CrashRecorder,
+    handler: Option<CrashHandler>,
+    crashes: Vec<Error>,
+    shutdown_on_crash: bool,
+}
+
+impl fmt::Debug for CrashReporter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CrashReporter")
+            .field("crashes", &self.crashes)
+            .field("shutdown_on_crash", &self.shutdown_on_crash)
+            .finish()
+    }
+}
+
+impl Default for CrashReporter {
+    fn default() -> Self {
+        CrashReporter {
+            recorder: CrashRecorder::default(),
+            handler: None,
+            crashes: Vec::new(),
+            shutdown_on_crash: true,
+        }
+    }
+}
+
+impl CrashReporter {
+    /// Creates a reporter logging the crashes, and shutting down the application after one.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the function called with every crash, instead of logging it.
+    pub fn set_handler<F>(&mut self, handler: F)
+    where
+        F: FnMut(&Error) + Send + Sync + 'static,
+    {
+        self.handler = Some(Box::new(handler));
+    }
+
+    /// Sets whether the application shuts down after a crash, true by default. The systems
+    /// which panicked keep running in the next frames otherwise, although their state may be
+    /// broken.
+    pub fn set_shutdown_on_crash(&mut self, shutdown: bool) {
+        self.shutdown_on_crash = shutdown;
+    }
+
+    /// Returns true if the application should shut down because of a crash.
+    pub fn should_shutdown(&self) -> bool {
+        self.shutdown_on_crash && !self.crashes.is_empty()
+    }
+
+    /// The crashes which happened so far.
+    pub fn crashes(&self) -> &[Error] {
+        &self.crashes
+    }
+
+    /// Reports an error as a crash, e.g. from a state.
+    pub fn report(&mut self, error: Error) {
+        match &mut self.handler {
+            Some(handler) => handler(&error),
+            None => error!("Crash: {}", error),
+        }
+        self.crashes.push(error);
+    }
+
+    pub(crate) fn recorder(&self) -> CrashRecorder {
+        self.recorder.clone()
+    }
+
+    /// Reports the crashes caught by the systems.
+    pub(crate) fn finish_frame(&mut self) {
+        for error in self.recorder.drain() {
+            self.report(error);
+        }
+    }
+}
+
+/// Turns the payload of a panic into an error.
+fn panic_error(name: &str, payload: Box<dyn Any + Send>) -> Error {
+    let message = match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => {
+            match payload.downcast::<&'static str>() {
+                Ok(message) => (*message).to_string(),
+                Err(_) => "unknown panic".to_string(),
+            }
+        }
+    };
+    Error::from_string(format!("{} panicked: {}", name, message))
+}
+
+/// A system whose panics are caught into a `CrashReporter`.
+pub(crate) struct CrashGuarded<S: ?Sized> {
+    name: String,
+    recorder: CrashRecorder,
+    system: Box<S>,
+}
+
+impl<S: Runnable + ?Sized> CrashGuarded<S> {
+    pub(crate) fn new(system: Box<S>, recorder: CrashRecorder) -> Self {
+        let name = system
+            .name()
+            .map_or_else(|| "unnamed system".to_string(), |name| name.to_string());
+        CrashGuarded {
+            name,
+            recorder,
+            system,
+        }
+    }
+}
+
+impl<S: Runnable + ?Sized> Runnable for CrashGuarded<S> {
+    // Default passthrough impls
+    fn name(&self) -> Option<&SystemId> {
+        self.system.name()
+    }
+
+    fn reads(&self) -> (&[ResourceTypeId], &[ComponentTypeId]) {
+        self.system.reads()
+    }
+
+    fn writes(&self) -> (&[ResourceTypeId], &[ComponentTypeId]) {
+        self.system.writes()
+    }
+
+    fn prepare(&mut self, world: &World) {
+        self.system.prepare(world)
+    }
+
+    fn accesses_archetypes(&self) -> &ArchetypeAccess {
+        self.system.accesses_archetypes()
+    }
+
+    unsafe fn run_unsafe(&mut self, world: &World, resources: &UnsafeResources) {
+        let system = &mut self.system;
+        if let Err(payload) = catch_unwind(AssertUnwindSafe(|| system.run_unsafe(world, resources)))
+        {
+            self.recorder.record(panic_error(&self.name, payload));
+        }
+    }
+
+    fn command_buffer_mut(&mut self, world: WorldId) -> Option<&mut CommandBuffer> {
+        self.system.command_buffer_mut(world)
+    }
+}
+
+/// Catches the panics of a thread local function.
+pub(crate) fn guard_fn(
+    mut f: Box<dyn FnMut(&mut World, &mut Resources)>,
+    recorder: CrashRecorder,
+) -> Box<dyn FnMut(&mut World, &mut Resources)> {
+    Box::new(move |world, resources| {
+        if let Err(payload) = catch_unwind(AssertUnwindSafe(|| f(world, resources))) {
+            recorder.record(panic_error("thread local function", payload));
+        }
+    })
+}
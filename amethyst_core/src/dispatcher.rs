@@ -1,8 +1,14 @@
 use amethyst_error::Error;
 
-use crate::ecs::{
-    systems::{Executor, ParallelRunnable, Step},
-    *,
+use crate::{
+    crash::{guard_fn, CrashGuarded, CrashRecorder, CrashReporter},
+    dispatcher_graph::{DispatcherGraph, DispatcherStep, SystemInfo},
+    ecs::{
+        systems::{Executor, ParallelRunnable, Step},
+        *,
+    },
+    state_stack::{gate, StateCondition},
+    system_profile::{ProfileRecorder, Profiled, SystemProfile},
 };
 
 /// A SystemBundle is a structure that adds multiple systems to the [Dispatcher] and loads/unloads all required resources.
@@ -65,19 +71,75 @@ pub struct DispatcherData<'a> {
     steps: Vec<Step>,
     /// Temporarily holds systems which are later combined into [Executor].
     accumulator: Vec<Box<dyn ParallelRunnable + 'static>>,
+    /// Holds the systems executed at the fixed update rate, see [Dispatcher::execute_fixed].
+    fixed_systems: Vec<Box<dyn ParallelRunnable + 'static>>,
     /// Bundles that can be later used for cleanup by calling [SystemBundle::unload].
     bundles: Vec<Box<dyn SystemBundle + 'a>>,
+    /// Description of the steps, inserted as a resource by [DispatcherBuilder::build].
+    graph: DispatcherGraph,
+    /// Records the times of the systems when there is a [SystemProfile].
+    profile: Option<ProfileRecorder>,
+    /// Catches the panics of the systems when there is a [CrashReporter].
+    crash: Option<CrashRecorder>,
 }
 
 impl<'a> DispatcherData<'a> {
+    fn profiled(&self, system: Box<dyn ParallelRunnable>) -> Box<dyn ParallelRunnable> {
+        let system: Box<dyn ParallelRunnable> = match &self.crash {
+            Some(recorder) => Box::new(CrashGuarded::new(system, recorder.clone())),
+            None => system,
+        };
+        match &self.profile {
+            Some(recorder) => Box::new(Profiled::new(system, recorder.clone())),
+            None => system,
+        }
+    }
+
+    fn profiled_thread_local(&self, system: Box<dyn Runnable>) -> Box<dyn Runnable> {
+        let system: Box<dyn Runnable> = match &self.crash {
+            Some(recorder) => Box::new(CrashGuarded::new(system, recorder.clone())),
+            None => system,
+        };
+        match &self.profile {
+            Some(recorder) => Box::new(Profiled::new(system, recorder.clone())),
+            None => system,
+        }
+    }
+
+    fn guarded_fn(
+        &self,
+        f: Box<dyn FnMut(&mut World, &mut Resources)>,
+    ) -> Box<dyn FnMut(&mut World, &mut Resources)> {
+        match &self.crash {
+            Some(recorder) => guard_fn(f, recorder.clone()),
+            None => f,
+        }
+    }
+
     fn finalize_executor(&mut self) {
         if !self.accumulator.is_empty() {
             let mut systems = Vec::new();
             std::mem::swap(&mut self.accumulator, &mut systems);
+            self.graph.steps.push(DispatcherStep::Systems(
+                systems.iter().map(|s| SystemInfo::of(s.as_ref())).collect(),
+            ));
             let executor = Executor::new(systems);
             self.steps.push(Step::Systems(executor));
         }
     }
+
+    fn fixed_steps(&mut self) -> Vec<Step> {
+        if self.fixed_systems.is_empty() {
+            return Vec::new();
+        }
+        let mut systems = Vec::new();
+        std::mem::swap(&mut self.fixed_systems, &mut systems);
+        self.graph.fixed_steps = vec![
+            DispatcherStep::Systems(systems.iter().map(|s| SystemInfo::of(s.as_ref())).collect()),
+            DispatcherStep::FlushCmdBuffers,
+        ];
+        vec![Step::Systems(Executor::new(systems)), Step::FlushCmdBuffers]
+    }
 }
 
 /// A builder which is used to construct [Dispatcher] from multiple systems and system bundles.
@@ -95,6 +157,17 @@ impl<'a> DispatcherBuilder {
         self
     }
 
+    /// Adds a system to the fixed update schedule. This system is executed at the fixed rate of
+    /// [Time](crate::Time), zero or more times per frame, instead of once per frame.
+    ///
+    /// Use it for simulation such as physics or gameplay logic which needs a stable delta time,
+    /// and read [Time::fixed_seconds](crate::Time::fixed_seconds) in the system.
+    pub fn add_fixed_system<S: System + 'a>(&mut self, system: S) -> &mut Self {
+        log::debug!("Building fixed system");
+        self.items.push(DispatcherItem::FixedSystem(system.build()));
+        self
+    }
+
     /// Adds a thread local system to the schedule. This system will be executed on the main thread.
     pub fn add_thread_local<T: ThreadLocalSystem<'a> + 'a>(&mut self, system: T) -> &mut Self {
         self.items
@@ -128,6 +201,29 @@ impl<'a> DispatcherBuilder {
         self
     }
 
+    /// Adds the systems and bundles added by `f` as a group, which only runs while the
+    /// condition on the [StateStack](crate::state_stack::StateStack) holds, e.g. while the game state is on top of the stack.
+    /// Command buffers are still flushed when the group doesn't run.
+    pub fn add_state_group<F>(&mut self, condition: StateCondition, f: F) -> &mut Self
+    where
+        F: FnOnce(&mut DispatcherBuilder),
+    {
+        let mut group = DispatcherBuilder::default();
+        f(&mut group);
+        for item in group.take_items() {
+            self.items.push(gate(item, condition));
+        }
+        self
+    }
+
+    pub(crate) fn add_item(&mut self, item: DispatcherItem) {
+        self.items.push(item);
+    }
+
+    pub(crate) fn take_items(&mut self) -> Vec<DispatcherItem> {
+        std::mem::take(&mut self.items)
+    }
+
     /// Evaluates all system bundles (recursively). Resulting systems and unpacked bundles are put into [DispatcherData].
     pub fn load(
         &'a mut self,
@@ -137,17 +233,33 @@ impl<'a> DispatcherBuilder {
     ) -> Result<(), Error> {
         for item in self.items.drain(..) {
             match item {
-                DispatcherItem::System(s) => data.accumulator.push(s),
+                DispatcherItem::System(s) => {
+                    let s = data.profiled(s);
+                    data.accumulator.push(s);
+                }
+                DispatcherItem::FixedSystem(s) => {
+                    let s = data.profiled(s);
+                    data.fixed_systems.push(s);
+                }
                 DispatcherItem::FlushCmdBuffers => {
                     data.finalize_executor();
+                    data.graph.steps.push(DispatcherStep::FlushCmdBuffers);
                     data.steps.push(Step::FlushCmdBuffers);
                 }
                 DispatcherItem::ThreadLocalFn(f) => {
+                    let f = data.guarded_fn(f);
                     data.finalize_executor();
+                    data.graph.steps.push(DispatcherStep::ThreadLocalFn);
                     data.steps.push(Step::ThreadLocalFn(f));
                 }
                 DispatcherItem::ThreadLocalSystem(s) => {
+                    let s = data.profiled_thread_local(s);
                     data.finalize_executor();
+                    data.graph
+                        .steps
+                        .push(DispatcherStep::ThreadLocalSystem(SystemInfo::of(
+                            s.as_ref(),
+                        )));
                     data.steps.push(Step::ThreadLocalSystem(s));
                 }
                 DispatcherItem::SystemBundle(mut bundle) => {
@@ -165,18 +277,34 @@ impl<'a> DispatcherBuilder {
     }
 
     /// Finalizes the builder into a [Dispatcher]. This also evaluates all system bundles by calling [SystemBundle::load].
+    ///
+    /// The [DispatcherGraph] of the dispatcher is inserted as a resource, replacing the graph of
+    /// any dispatcher built before. If there is a [SystemProfile] resource, the systems of the
+    /// dispatcher are timed into it. If there is a [CrashReporter] resource, the panics of the
+    /// systems are caught and reported to it.
     pub fn build(
         &mut self,
         world: &mut World,
         resources: &mut Resources,
     ) -> Result<Dispatcher, Error> {
-        let mut data = DispatcherData::default();
+        let mut data = DispatcherData {
+            profile: resources.get::<SystemProfile>().map(|p| p.recorder()),
+            crash: resources.get::<CrashReporter>().map(|c| c.recorder()),
+            ..Default::default()
+        };
+        let profiled = data.profile.is_some();
+        let crash_guarded = data.crash.is_some();
 
         self.flush().load(world, resources, &mut data)?;
 
+        let fixed_steps = data.fixed_steps();
+        resources.insert(data.graph.clone());
         Ok(Dispatcher {
             schedule: Schedule::from(data.steps),
+            fixed_schedule: Schedule::from(fixed_steps),
             bundles: data.bundles,
+            profiled,
+            crash_guarded,
         })
     }
 }
@@ -186,6 +314,8 @@ impl<'a> DispatcherBuilder {
 pub enum DispatcherItem {
     /// A simple system.
     System(Box<dyn ParallelRunnable + 'static>),
+    /// A system executed at the fixed update rate.
+    FixedSystem(Box<dyn ParallelRunnable + 'static>),
     /// Flush system command buffers.
     FlushCmdBuffers,
     /// A thread local function.
@@ -202,13 +332,37 @@ pub struct Dispatcher {
     // Used to execute unload on system bundles once dispatcher is disposed.
     bundles: Vec<Box<dyn SystemBundle>>,
     schedule: Schedule,
+    fixed_schedule: Schedule,
+    profiled: bool,
+    crash_guarded: bool,
 }
 
 impl Dispatcher {
     /// Executes systems according to the [Schedule].
     pub fn execute(&mut self, world: &mut World, resources: &mut Resources) {
         // TODO: use ArcThreadPool from resources to dispatch legion
+        if self.profiled {
+            if let Some(mut profile) = resources.get_mut::<SystemProfile>() {
+                profile.start_frame();
+            }
+        }
         self.schedule.execute(world, resources);
+        if self.profiled {
+            if let Some(mut profile) = resources.get_mut::<SystemProfile>() {
+                profile.finish_frame();
+            }
+        }
+        if self.crash_guarded {
+            if let Some(mut reporter) = resources.get_mut::<CrashReporter>() {
+                reporter.finish_frame();
+            }
+        }
+    }
+
+    /// Executes the systems added with [DispatcherBuilder::add_fixed_system]. This is called for
+    /// every fixed update step, which may happen zero or more times per frame.
+    pub fn execute_fixed(&mut self, world: &mut World, resources: &mut Resources) {
+        self.fixed_schedule.execute(world, resources);
     }
 
     /// Unloads any resources by calling [SystemBundle::unload] for stored system bundles and returns [DispatcherBuilder]
@@ -302,4 +456,90 @@ pub mod tests {
 
         assert_eq!(resources.get::<MyResource>().unwrap().0, true);
     }
+
+    #[test]
+    fn dispatcher_inserts_graph() {
+        let mut world = World::default();
+        let mut resources = Resources::default();
+
+        DispatcherBuilder::default()
+            .add_system(MySystem)
+            .add_fixed_system(MySystem)
+            .build(&mut world, &mut resources)
+            .unwrap();
+
+        let graph = resources.get::<DispatcherGraph>().unwrap();
+        assert_eq!(graph.systems().count(), 2);
+        let system = graph.system("test").unwrap();
+        assert_eq!(system.resource_writes.len(), 1);
+        assert_eq!(graph.fixed_steps.len(), 2);
+    }
+
+    #[test]
+    fn dispatcher_profiles_systems() {
+        let mut world = World::default();
+        let mut resources = Resources::default();
+
+        resources.insert(MyResource(false));
+        resources.insert(SystemProfile::default());
+
+        let mut dispatcher = DispatcherBuilder::default()
+            .add_system(MySystem)
+            .build(&mut world, &mut resources)
+            .unwrap();
+
+        dispatcher.execute(&mut world, &mut resources);
+        dispatcher.execute(&mut world, &mut resources);
+
+        let profile = resources.get::<SystemProfile>().unwrap();
+        assert_eq!(profile.system("test").unwrap().runs(), 2);
+        assert_eq!(profile.frame().runs(), 2);
+    }
+
+    #[test]
+    fn dispatcher_reports_panics() {
+        let mut world = World::default();
+        let mut resources = Resources::default();
+
+        resources.insert(MyResource(false));
+        resources.insert(CrashReporter::default());
+
+        let mut dispatcher = DispatcherBuilder::default()
+            .add_system(|| {
+                SystemBuilder::new("panicking").build(|_, _, _, _| panic!("out of cheese"))
+            })
+            .add_system(MySystem)
+            .build(&mut world, &mut resources)
+            .unwrap();
+
+        dispatcher.execute(&mut world, &mut resources);
+
+        assert_eq!(resources.get::<MyResource>().unwrap().0, true);
+        let reporter = resources.get::<CrashReporter>().unwrap();
+        assert!(reporter.should_shutdown());
+        assert_eq!(
+            reporter.crashes()[0].to_string(),
+            "panicking panicked: out of cheese"
+        );
+    }
+
+    #[test]
+    fn dispatcher_fixed_system() {
+        let mut world = World::default();
+        let mut resources = Resources::default();
+
+        resources.insert(MyResource(false));
+
+        let mut dispatcher = DispatcherBuilder::default()
+            .add_fixed_system(MySystem)
+            .build(&mut world, &mut resources)
+            .unwrap();
+
+        // fixed systems don't run on the frame schedule
+        dispatcher.execute(&mut world, &mut resources);
+        assert_eq!(resources.get::<MyResource>().unwrap().0, false);
+
+        dispatcher.execute_fixed(&mut world, &mut resources);
+        assert_eq!(resources.get::<MyResource>().unwrap().0, true);
+    }
 }
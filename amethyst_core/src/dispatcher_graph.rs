@@ -0,0 +1,269 @@
+//! Introspection of the systems of a built [Dispatcher](crate::ecs::Dispatcher).
+//!
+//! Building a dispatcher inserts a [DispatcherGraph] resource, describing the order the systems
+//! run in and the resources and components they access. Systems of the same group run in
+//! parallel, unless they access the same data and one of them writes it, in which case they run
+//! in the order they were added. Use [DispatcherGraph::to_dot] to draw the graph with graphviz.
+
+use std::fmt::Write;
+
+use crate::ecs::systems::Runnable;
+
+/// Name and data accesses of a system.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SystemInfo {
+    /// Name of the system
+    pub name: String,
+    /// Resources the system reads
+    pub resource_reads: Vec<String>,
+    /// Resources the system writes
+    pub resource_writes: Vec<String>,
+    /// Components the system reads
+    pub component_reads: Vec<String>,
+    /// Components the system writes
+    pub component_writes: Vec<String>,
+}
+
+impl SystemInfo {
+    pub(crate) fn of<R: Runnable + ?Sized>(system: &R) -> Self {
+        let (resource_reads, component_reads) = system.reads();
+        let (resource_writes, component_writes) = system.writes();
+        SystemInfo {
+            name: system
+                .name()
+                .map_or_else(|| "unnamed system".to_string(), |name| name.to_string()),
+            resource_reads: resource_reads.iter().map(|r| r.to_string()).collect(),
+            resource_writes: resource_writes.iter().map(|r| r.to_string()).collect(),
+            component_reads: component_reads.iter().map(|c| c.to_string()).collect(),
+            component_writes: component_writes.iter().map(|c| c.to_string()).collect(),
+        }
+    }
+
+    /// Returns true if the system reads or writes the resource or component with the given name.
+    pub fn accesses(&self, name: &str) -> bool {
+        self.reads(name) || self.writes(name)
+    }
+
+    /// Returns true if the system reads the resource or component with the given name.
+    pub fn reads(&self, name: &str) -> bool {
+        contains(&self.resource_reads, name) || contains(&self.component_reads, name)
+    }
+
+    /// Returns true if the system writes the resource or component with the given name.
+    pub fn writes(&self, name: &str) -> bool {
+        contains(&self.resource_writes, name) || contains(&self.component_writes, name)
+    }
+
+    /// Returns the resources and components which keep both systems from running in parallel.
+    pub fn conflicts(&self, other: &SystemInfo) -> Vec<String> {
+        let mut conflicts = Vec::new();
+        let writes = self.resource_writes.iter().chain(&self.component_writes);
+        let other_writes = other.resource_writes.iter().chain(&other.component_writes);
+        for name in writes.filter(|name| other.accesses(name)) {
+            conflicts.push(name.clone());
+        }
+        for name in other_writes.filter(|name| self.reads(name)) {
+            if !conflicts.contains(name) {
+                conflicts.push(name.clone());
+            }
+        }
+        conflicts
+    }
+}
+
+fn contains(names: &[String], name: &str) -> bool {
+    names.iter().any(|n| n == name)
+}
+
+/// A step of the schedule of a dispatcher.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DispatcherStep {
+    /// A group of systems running in parallel where their accesses allow it
+    Systems(Vec<SystemInfo>),
+    /// Waits for the systems before it, and flushes their command buffers
+    FlushCmdBuffers,
+    /// A thread local function
+    ThreadLocalFn,
+    /// A thread local system
+    ThreadLocalSystem(SystemInfo),
+}
+
+/// Resource describing the schedule of the last built dispatcher.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DispatcherGraph {
+    /// Steps run every frame
+    pub steps: Vec<DispatcherStep>,
+    /// Steps run at the fixed update rate
+    pub fixed_steps: Vec<DispatcherStep>,
+}
+
+impl DispatcherGraph {
+    /// Iterate over all systems, the frame systems first.
+    pub fn systems(&self) -> impl Iterator<Item = &SystemInfo> {
+        self.steps.iter().chain(&self.fixed_steps).flat_map(|step| {
+            match step {
+                DispatcherStep::Systems(systems) => systems.iter().collect(),
+                DispatcherStep::ThreadLocalSystem(system) => vec![system],
+                _ => Vec::new(),
+            }
+        })
+    }
+
+    /// Find a system by name.
+    pub fn system(&self, name: &str) -> Option<&SystemInfo> {
+        self.systems().find(|system| system.name == name)
+    }
+
+    /// Iterate over the systems which read or write the resource or component with the given
+    /// name, e.g. to find out which systems contend for it.
+    pub fn accessing<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a SystemInfo> + 'a {
+        self.systems().filter(move |system| system.accesses(name))
+    }
+
+    /// Get the systems each system of a group has to wait for, as indices into the group,
+    /// along with the data causing the wait.
+    pub fn dependencies(systems: &[SystemInfo]) -> Vec<(usize, usize, Vec<String>)> {
+        let mut dependencies = Vec::new();
+        for (i, system) in systems.iter().enumerate() {
+            for (j, later) in systems.iter().enumerate().skip(i + 1) {
+                let conflicts = system.conflicts(later);
+                if !conflicts.is_empty() {
+                    dependencies.push((i, j, conflicts));
+                }
+            }
+        }
+        dependencies
+    }
+
+    /// Export the graph in the DOT format of graphviz, with a cluster for every group of
+    /// parallel systems, and edges between systems which have to wait for each other.
+    pub fn to_dot(&self) -> String {
+        let mut dot =
+            String::from("digraph dispatcher {\n    rankdir=LR;\n    node [shape=box];\n");
+        let mut previous = None;
+        for (prefix, steps) in &[("frame", &self.steps), ("fixed", &self.fixed_steps)] {
+            for (index, step) in steps.iter().enumerate() {
+                let id = format!("{}_{}", prefix, index);
+                let first = write_step(&mut dot, &id, step);
+                if let Some(previous) = &previous {
+                    writeln!(dot, "    \"{}\" -> \"{}\" [style=dashed];", previous, first).unwrap();
+                }
+                previous = Some(first);
+            }
+            previous = None;
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+/// Writes a step, and returns the id of its first node.
+fn write_step(dot: &mut String, id: &str, step: &DispatcherStep) -> String {
+    match step {
+        DispatcherStep::Systems(systems) => {
+            writeln!(
+                dot,
+                "    subgraph cluster_{} {{\n        style=rounded;",
+                id
+            )
+            .unwrap();
+            for (index, system) in systems.iter().enumerate() {
+                writeln!(
+                    dot,
+                    "        \"{}_{}\" [label=\"{}\"];",
+                    id,
+                    index,
+                    escape(&system.name)
+                )
+                .unwrap();
+            }
+            for (from, to, conflicts) in DispatcherGraph::dependencies(systems) {
+                writeln!(
+                    dot,
+                    "        \"{}_{}\" -> \"{}_{}\" [label=\"{}\"];",
+                    id,
+                    from,
+                    id,
+                    to,
+                    escape(&conflicts.join(", "))
+                )
+                .unwrap();
+            }
+            dot.push_str("    }\n");
+            format!("{}_0", id)
+        }
+        DispatcherStep::FlushCmdBuffers => {
+            writeln!(dot, "    \"{}\" [label=\"flush\", shape=point];", id).unwrap();
+            id.to_string()
+        }
+        DispatcherStep::ThreadLocalFn => {
+            writeln!(
+                dot,
+                "    \"{}\" [label=\"thread local fn\", shape=ellipse];",
+                id
+            )
+            .unwrap();
+            id.to_string()
+        }
+        DispatcherStep::ThreadLocalSystem(system) => {
+            writeln!(
+                dot,
+                "    \"{}\" [label=\"{}\", shape=ellipse];",
+                id,
+                escape(&system.name)
+            )
+            .unwrap();
+            id.to_string()
+        }
+    }
+}
+
+fn escape(label: &str) -> String {
+    label.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn system(name: &str, reads: &[&str], writes: &[&str]) -> SystemInfo {
+        SystemInfo {
+            name: name.to_string(),
+            resource_reads: reads.iter().map(|r| r.to_string()).collect(),
+            resource_writes: writes.iter().map(|w| w.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn dependencies_follow_conflicting_accesses() {
+        let systems = vec![
+            system("input", &[], &["Input"]),
+            system("movement", &["Input", "Time"], &[]),
+            system("audio", &["Time"], &[]),
+        ];
+        assert_eq!(
+            DispatcherGraph::dependencies(&systems),
+            vec![(0, 1, vec!["Input".to_string()])]
+        );
+    }
+
+    #[test]
+    fn dot_contains_systems_and_edges() {
+        let graph = DispatcherGraph {
+            steps: vec![
+                DispatcherStep::Systems(vec![
+                    system("input", &[], &["Input"]),
+                    system("movement", &["Input"], &[]),
+                ]),
+                DispatcherStep::FlushCmdBuffers,
+            ],
+            fixed_steps: Vec::new(),
+        };
+        let dot = graph.to_dot();
+        assert!(dot.contains("\"frame_0_0\" [label=\"input\"]"));
+        assert!(dot.contains("\"frame_0_0\" -> \"frame_0_1\" [label=\"Input\"]"));
+        assert!(dot.contains("\"frame_0_0\" -> \"frame_1\""));
+        assert_eq!(graph.accessing("Input").count(), 2);
+    }
+}
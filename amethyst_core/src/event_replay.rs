@@ -0,0 +1,291 @@
+//! Replay buffers for event channels.
+//!
+//! A reader of an [EventChannel] only receives the events written after it was registered, and
+//! has to read them before the channel grows past its capacity. Systems registering their reader
+//! late, or skipping frames, silently lose events that way.
+//!
+//! The [EventReplayBundle] copies the events of a channel into an [EventReplay] resource, which
+//! keeps the most recent ones. Systems read it through an [EventCursor], which needs no
+//! registration: it is a plain value owned by the system, starting at the oldest buffered
+//! event, so late systems catch up on what they missed, and it counts the events which fell
+//! out of the buffer before they were read.
+
+use std::{collections::VecDeque, marker::PhantomData};
+
+use amethyst_error::Error;
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+
+use crate::{ecs::*, shrev::ReaderId, EventChannel};
+
+/// Statistics of the events of a channel.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct EventStats {
+    /// Number of events written since the replay buffer was created
+    pub total: u64,
+    /// Number of events written in the last frame
+    pub last_frame: usize,
+    /// Largest number of events written in a single frame
+    pub peak_frame: usize,
+    /// Number of frames recorded
+    pub frames: u64,
+}
+
+impl EventStats {
+    /// Average number of events written per frame.
+    pub fn average(&self) -> f32 {
+        if self.frames == 0 {
+            0.0
+        } else {
+            self.total as f32 / self.frames as f32
+        }
+    }
+}
+
+/// Resource keeping the most recent events of an `EventChannel<E>`.
+#[derive(Debug)]
+pub struct EventReplay<E> {
+    events: VecDeque<E>,
+    capacity: usize,
+    /// Sequence number of the oldest buffered event
+    first: u64,
+    stats: EventStats,
+}
+
+impl<E> EventReplay<E> {
+    /// Creates a buffer keeping up to `capacity` events.
+    pub fn new(capacity: usize) -> Self {
+        EventReplay {
+            events: VecDeque::with_capacity(capacity),
+            capacity: capacity.max(1),
+            first: 0,
+            stats: EventStats::default(),
+        }
+    }
+
+    /// Maximum number of buffered events.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Number of buffered events.
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    /// Returns true if no event is buffered.
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    /// Get the statistics of the channel.
+    pub fn stats(&self) -> &EventStats {
+        &self.stats
+    }
+
+    /// Iterate over the buffered events, from the oldest.
+    pub fn recent(&self) -> impl Iterator<Item = &E> {
+        self.events.iter()
+    }
+
+    /// Read the events the cursor didn't read yet, and move it past them.
+    pub fn read<'a>(&'a self, cursor: &mut EventCursor<E>) -> impl Iterator<Item = &'a E> + 'a {
+        let end = self.first + self.events.len() as u64;
+        let mut next = match cursor.next {
+            Some(next) => next,
+            None if cursor.replay => self.first,
+            None => end,
+        };
+        if next < self.first {
+            cursor.missed += self.first - next;
+            next = self.first;
+        }
+        cursor.next = Some(end);
+        self.events.iter().skip((next - self.first) as usize)
+    }
+
+    /// Records the events written in a frame.
+    pub fn push_frame(&mut self, events: impl IntoIterator<Item = E>) {
+        let mut count = 0;
+        for event in events {
+            if self.events.len() == self.capacity {
+                self.events.pop_front();
+                self.first += 1;
+            }
+            self.events.push_back(event);
+            count += 1;
+        }
+        self.stats.total += count as u64;
+        self.stats.last_frame = count;
+        self.stats.peak_frame = self.stats.peak_frame.max(count);
+        self.stats.frames += 1;
+    }
+}
+
+/// Position of a reader in an [EventReplay].
+///
+/// Unlike a `ReaderId`, a cursor doesn't have to be registered, so it can be created when the
+/// system is built and dropped along with it.
+#[derive(Debug)]
+pub struct EventCursor<E> {
+    next: Option<u64>,
+    replay: bool,
+    missed: u64,
+    marker: PhantomData<fn() -> E>,
+}
+
+impl<E> Default for EventCursor<E> {
+    fn default() -> Self {
+        EventCursor::new()
+    }
+}
+
+impl<E> EventCursor<E> {
+    /// Creates a cursor whose first read returns all the buffered events.
+    pub fn new() -> Self {
+        EventCursor {
+            next: None,
+            replay: true,
+            missed: 0,
+            marker: PhantomData,
+        }
+    }
+
+    /// Creates a cursor whose first read skips the buffered events, like a newly registered
+    /// `ReaderId`.
+    pub fn from_now() -> Self {
+        EventCursor {
+            replay: false,
+            ..EventCursor::new()
+        }
+    }
+
+    /// Number of events which were dropped from the buffer before the cursor read them.
+    pub fn missed(&self) -> u64 {
+        self.missed
+    }
+}
+
+/// System copying the events of an `EventChannel<E>` into its [EventReplay].
+#[allow(missing_debug_implementations)]
+pub struct EventReplaySystem<E: 'static> {
+    reader: ReaderId<E>,
+}
+
+impl<E> EventReplaySystem<E>
+where
+    E: Clone + Send + Sync + 'static,
+{
+    /// Creates the system, registering its reader in the channel.
+    pub fn new(channel: &mut EventChannel<E>) -> Self {
+        EventReplaySystem {
+            reader: channel.register_reader(),
+        }
+    }
+}
+
+impl<E> System for EventReplaySystem<E>
+where
+    E: Clone + Send + Sync + 'static,
+{
+    fn build(mut self) -> Box<dyn ParallelRunnable> {
+        Box::new(
+            SystemBuilder::new("EventReplaySystem")
+                .read_resource::<EventChannel<E>>()
+                .write_resource::<EventReplay<E>>()
+                .build(move |_commands, _world, (channel, replay), _queries| {
+                    #[cfg(feature = "profiler")]
+                    profile_scope!("event_replay_system");
+
+                    replay.push_frame(channel.read(&mut self.reader).cloned());
+                }),
+        )
+    }
+}
+
+/// Bundle keeping the recent events of an `EventChannel<E>` in an [EventReplay].
+///
+/// This inserts the channel if it's missing, and the `EventReplay<E>` resource. Add it after
+/// the bundles writing the events, so they are buffered in the same frame.
+#[derive(Debug)]
+pub struct EventReplayBundle<E> {
+    capacity: usize,
+    marker: PhantomData<fn() -> E>,
+}
+
+impl<E> EventReplayBundle<E> {
+    /// Creates a bundle keeping up to `capacity` events.
+    pub fn new(capacity: usize) -> Self {
+        EventReplayBundle {
+            capacity,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<E> SystemBundle for EventReplayBundle<E>
+where
+    E: Clone + Send + Sync + 'static,
+{
+    fn load(
+        &mut self,
+        _world: &mut World,
+        resources: &mut Resources,
+        builder: &mut DispatcherBuilder,
+    ) -> Result<(), Error> {
+        if !resources.contains::<EventChannel<E>>() {
+            resources.insert(EventChannel::<E>::new());
+        }
+        let system = EventReplaySystem::new(&mut resources.get_mut::<EventChannel<E>>().unwrap());
+        resources.insert(EventReplay::<E>::new(self.capacity));
+        builder.add_system(system);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cursors_replay_and_count_missed_events() {
+        let mut replay = EventReplay::new(3);
+        replay.push_frame(vec![1, 2]);
+
+        let mut late = EventCursor::new();
+        let mut now = EventCursor::from_now();
+        assert_eq!(replay.read(&mut late).copied().collect::<Vec<_>>(), [1, 2]);
+        assert_eq!(replay.read(&mut now).count(), 0);
+
+        replay.push_frame(vec![3, 4, 5, 6]);
+        assert_eq!(
+            replay.read(&mut late).copied().collect::<Vec<_>>(),
+            [4, 5, 6]
+        );
+        assert_eq!(late.missed(), 1);
+        assert_eq!(replay.read(&mut now).count(), 3);
+
+        let stats = replay.stats();
+        assert_eq!((stats.total, stats.last_frame, stats.peak_frame), (6, 4, 4));
+        assert_eq!(stats.average(), 3.0);
+    }
+
+    #[test]
+    fn bundle_buffers_channel_events() {
+        let mut world = World::default();
+        let mut resources = Resources::default();
+        let mut dispatcher = DispatcherBuilder::default()
+            .add_bundle(EventReplayBundle::<u32>::new(8))
+            .build(&mut world, &mut resources)
+            .unwrap();
+
+        resources
+            .get_mut::<EventChannel<u32>>()
+            .unwrap()
+            .iter_write(vec![7, 8]);
+        dispatcher.execute(&mut world, &mut resources);
+
+        let replay = resources.get::<EventReplay<u32>>().unwrap();
+        assert_eq!(replay.recent().copied().collect::<Vec<_>>(), [7, 8]);
+    }
+}
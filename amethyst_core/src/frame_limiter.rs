@@ -58,6 +58,16 @@
 //! `SleepAndYield` can potentially be as accurate as `Yield` while using less CPU time, but you
 //! will have to test different grace period timings to determine how much time needs to be left
 //! to ensure that the main thread doesn't sleep too long and miss the start of the next frame.
+//! `Adaptive` does the same with a grace period it tunes itself, from how late the sleeps of
+//! the previous frames woke up, starting from a default suited to the platform.
+//!
+//! # Vsync and unfocused windows
+//!
+//! When the renderer waits for vsync, tell the limiter the refresh rate with
+//! [`FrameLimiter::set_vsync`]: it then leaves the pacing to the presentation when the frame
+//! rate limit is above the refresh rate, and rounds lower limits to a whole number of refresh
+//! intervals, so frames don't judder. A separate, usually lower, limit can be set for when the
+//! window is unfocused or minimized, which the application tracks from the window events.
 //!
 //! [`Application`]: ../../amethyst/type.Application.html
 //! [`FrameRateLimitStrategy`]: ./enum.FrameRateLimitStrategy.html
@@ -65,15 +75,29 @@
 //! [`thread::sleep`]: https://doc.rust-lang.org/stable/std/thread/fn.sleep.html
 
 use std::{
+    hint::spin_loop,
     thread::{sleep, yield_now},
-    time::{Duration, Instant},
+    time::Duration,
 };
 
 use derive_new::new;
+use instant::Instant;
 use serde::{Deserialize, Serialize};
 
 const ZERO: Duration = Duration::from_millis(0);
 
+/// Bounds of the spin margin of the `Adaptive` strategy.
+const MIN_SPIN_MARGIN: Duration = Duration::from_micros(200);
+const MAX_SPIN_MARGIN: Duration = Duration::from_millis(8);
+
+/// Initial spin margin of the `Adaptive` strategy, covering the usual timer resolution.
+#[cfg(target_os = "windows")]
+const DEFAULT_SPIN_MARGIN: Duration = Duration::from_millis(3);
+#[cfg(target_os = "macos")]
+const DEFAULT_SPIN_MARGIN: Duration = Duration::from_millis(1);
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+const DEFAULT_SPIN_MARGIN: Duration = Duration::from_micros(500);
+
 /// Frame rate limiting strategy.
 ///
 /// See the [module documentation] on the difference between sleeping and yielding, and when
@@ -96,6 +120,12 @@ pub enum FrameRateLimitStrategy {
     /// Will sleep repeatedly until the given duration remains, and then will yield repeatedly
     /// for the remaining frame time.
     SleepAndYield(Duration),
+
+    /// Sleep until a margin remains, then spin for the remaining frame time.
+    ///
+    /// The margin is tuned every frame from how late the sleep woke up, so it stays as short
+    /// as the platform timer allows.
+    Adaptive,
 }
 
 impl Default for FrameRateLimitStrategy {
@@ -125,6 +155,19 @@ pub struct FrameRateLimitConfig {
     pub strategy: FrameRateLimitStrategy,
     /// The FPS to limit the game loop execution.
     pub fps: u32,
+    /// The FPS to limit the game loop execution to while the window is unfocused or minimized.
+    #[serde(default)]
+    #[new(default)]
+    pub unfocused_fps: Option<u32>,
+    /// The FPS to limit the game loop execution to while the window is minimized, instead of
+    /// `unfocused_fps`. Defaults to 10, as nothing is shown.
+    #[serde(default = "default_minimized_fps")]
+    #[new(value = "default_minimized_fps()")]
+    pub minimized_fps: Option<u32>,
+}
+
+fn default_minimized_fps() -> Option<u32> {
+    Some(10)
 }
 
 impl Default for FrameRateLimitConfig {
@@ -132,6 +175,60 @@ impl Default for FrameRateLimitConfig {
         FrameRateLimitConfig {
             fps: 144,
             strategy: Default::default(),
+            unfocused_fps: None,
+            minimized_fps: default_minimized_fps(),
+        }
+    }
+}
+
+/// Smoothed statistics of the frame times measured by a `FrameLimiter`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct FrameTimeStats {
+    /// Exponential moving average of the frame time
+    pub smoothed: Duration,
+    /// Exponential moving average of the deviation of the frame time from `smoothed`
+    pub jitter: Duration,
+    /// Shortest frame time
+    pub min: Duration,
+    /// Longest frame time
+    pub max: Duration,
+    /// Number of measured frames
+    pub frames: u64,
+}
+
+impl FrameTimeStats {
+    /// Weight of a new frame in the moving averages.
+    const SMOOTHING: f64 = 0.1;
+
+    fn record(&mut self, frame_time: Duration) {
+        if self.frames == 0 {
+            *self = FrameTimeStats {
+                smoothed: frame_time,
+                jitter: ZERO,
+                min: frame_time,
+                max: frame_time,
+                frames: 1,
+            };
+            return;
+        }
+        let smoothed = self.smoothed.as_secs_f64();
+        let time = frame_time.as_secs_f64();
+        let deviation = (time - smoothed).abs();
+        self.jitter = Duration::from_secs_f64(
+            self.jitter.as_secs_f64() + (deviation - self.jitter.as_secs_f64()) * Self::SMOOTHING,
+        );
+        self.smoothed = Duration::from_secs_f64(smoothed + (time - smoothed) * Self::SMOOTHING);
+        self.min = self.min.min(frame_time);
+        self.max = self.max.max(frame_time);
+        self.frames += 1;
+    }
+
+    /// Smoothed frames per second.
+    pub fn fps(&self) -> f32 {
+        if self.smoothed == ZERO {
+            0.0
+        } else {
+            1.0 / self.smoothed.as_secs_f32()
         }
     }
 }
@@ -146,6 +243,13 @@ pub struct FrameLimiter {
     frame_duration: Duration,
     strategy: FrameRateLimitStrategy,
     last_call: Instant,
+    unfocused_frame_duration: Option<Duration>,
+    minimized_frame_duration: Option<Duration>,
+    refresh_interval: Option<Duration>,
+    focused: bool,
+    minimized: bool,
+    spin_margin: Duration,
+    stats: FrameTimeStats,
 }
 
 impl Default for FrameLimiter {
@@ -161,6 +265,13 @@ impl FrameLimiter {
             frame_duration: Duration::from_secs(0),
             strategy: Default::default(),
             last_call: Instant::now(),
+            unfocused_frame_duration: None,
+            minimized_frame_duration: None,
+            refresh_interval: None,
+            focused: true,
+            minimized: false,
+            spin_margin: DEFAULT_SPIN_MARGIN,
+            stats: FrameTimeStats::default(),
         };
         s.set_rate(strategy, fps);
         s
@@ -178,7 +289,79 @@ impl FrameLimiter {
 
     /// Creates a new frame limiter with the given config.
     pub fn from_config(config: FrameRateLimitConfig) -> Self {
-        Self::new(config.strategy, config.fps)
+        let mut limiter = Self::new(config.strategy, config.fps);
+        limiter.set_unfocused_fps(config.unfocused_fps);
+        limiter.set_minimized_fps(config.minimized_fps);
+        limiter
+    }
+
+    /// Sets the maximum fps while the window is unfocused or minimized, or `None` to keep the
+    /// normal limit. The limiter sleeps in that case, whatever the strategy.
+    pub fn set_unfocused_fps(&mut self, fps: Option<u32>) {
+        self.unfocused_frame_duration = fps
+            .filter(|fps| *fps > 0)
+            .map(|fps| Duration::from_secs(1) / fps);
+    }
+
+    /// Sets the maximum fps while the window is minimized, or `None` to use the limit for
+    /// unfocused windows.
+    pub fn set_minimized_fps(&mut self, fps: Option<u32>) {
+        self.minimized_frame_duration = fps
+            .filter(|fps| *fps > 0)
+            .map(|fps| Duration::from_secs(1) / fps);
+    }
+
+    /// Sets whether the window has the focus.
+    pub fn set_focused(&mut self, focused: bool) {
+        self.focused = focused;
+    }
+
+    /// Sets whether the window is minimized.
+    pub fn set_minimized(&mut self, minimized: bool) {
+        self.minimized = minimized;
+    }
+
+    /// Returns true if the limit for unfocused or minimized windows applies.
+    pub fn is_background(&self) -> bool {
+        self.background_frame_duration().is_some()
+    }
+
+    fn background_frame_duration(&self) -> Option<Duration> {
+        match self.minimized_frame_duration {
+            Some(duration) if self.minimized => Some(duration),
+            _ if !self.focused || self.minimized => self.unfocused_frame_duration,
+            _ => None,
+        }
+    }
+
+    /// Sets the refresh rate the renderer waits for with vsync, or `None` if vsync is off.
+    pub fn set_vsync(&mut self, refresh_rate: Option<u32>) {
+        self.refresh_interval = refresh_rate
+            .filter(|rate| *rate > 0)
+            .map(|rate| Duration::from_secs(1) / rate);
+    }
+
+    /// Get the statistics of the measured frame times.
+    pub fn stats(&self) -> &FrameTimeStats {
+        &self.stats
+    }
+
+    /// The duration frames are paced to, or `None` if the limiter leaves the pacing to vsync.
+    pub fn target_frame_duration(&self) -> Option<Duration> {
+        if let Some(duration) = self.background_frame_duration() {
+            return Some(duration);
+        }
+        match self.refresh_interval {
+            Some(interval) if self.frame_duration <= interval => None,
+            Some(interval) => {
+                let intervals = (self.frame_duration.as_secs_f64() / interval.as_secs_f64())
+                    .round()
+                    .max(1.0);
+                // present half an interval early, vsync holds the frame until the right refresh
+                Some(interval * intervals as u32 - interval / 2)
+            }
+            None => Some(self.frame_duration),
+        }
     }
 
     /// Resets the frame start time to the current instant.
@@ -195,32 +378,70 @@ impl FrameLimiter {
     /// `wait` is used internally by [`Application`] to limit the frame rate of the game
     /// to the configured rate. This should likely never be called directly by game logic.
     ///
+    /// On `wasm32` this never blocks, as the browser paces the frames and the page can't sleep.
+    ///
     /// [`Application`]: ../../amethyst/type.Application.html
     pub fn wait(&mut self) {
+        #[cfg(not(target_arch = "wasm32"))]
+        self.pace();
+        let now = Instant::now();
+        self.stats.record(now - self.last_call);
+        self.last_call = now;
+    }
+
+    #[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+    fn pace(&mut self) {
         use self::FrameRateLimitStrategy::*;
-        match self.strategy {
-            Unlimited => yield_now(),
+        match (self.target_frame_duration(), self.strategy.clone()) {
+            (Some(frame_duration), _) if self.is_background() => {
+                self.do_sleep(frame_duration, ZERO)
+            }
+
+            (None, _) | (_, Unlimited) => yield_now(),
 
-            Yield => self.do_yield(),
+            (Some(frame_duration), Yield) => self.do_yield(frame_duration),
 
-            Sleep => self.do_sleep(ZERO),
+            (Some(frame_duration), Sleep) => self.do_sleep(frame_duration, ZERO),
 
-            SleepAndYield(dur) => {
-                self.do_sleep(dur);
-                self.do_yield();
+            (Some(frame_duration), SleepAndYield(dur)) => {
+                self.do_sleep(frame_duration, dur);
+                self.do_yield(frame_duration);
             }
+
+            (Some(frame_duration), Adaptive) => self.do_adaptive(frame_duration),
         }
-        self.last_call = Instant::now();
     }
 
-    fn do_yield(&self) {
-        while Instant::now() - self.last_call < self.frame_duration {
+    fn do_yield(&self, frame_duration: Duration) {
+        while Instant::now() - self.last_call < frame_duration {
             yield_now();
         }
     }
 
-    fn do_sleep(&self, stop_on_remaining: Duration) {
-        let frame_duration = self.frame_duration - stop_on_remaining;
+    fn do_adaptive(&mut self, frame_duration: Duration) {
+        let deadline = self.last_call + frame_duration;
+        let now = Instant::now();
+        let remaining = deadline.saturating_duration_since(now);
+        if let Some(sleep_duration) = remaining.checked_sub(self.spin_margin) {
+            sleep(sleep_duration);
+            let overshoot = Instant::now().saturating_duration_since(now + sleep_duration);
+            // grow the margin right away when a sleep was late, and shrink it slowly
+            let target = (overshoot * 2).max(MIN_SPIN_MARGIN).min(MAX_SPIN_MARGIN);
+            self.spin_margin = if target > self.spin_margin {
+                target
+            } else {
+                (self.spin_margin * 15 + target) / 16
+            };
+        }
+        while Instant::now() < deadline {
+            spin_loop();
+        }
+    }
+
+    fn do_sleep(&self, frame_duration: Duration, stop_on_remaining: Duration) {
+        let frame_duration = frame_duration
+            .checked_sub(stop_on_remaining)
+            .unwrap_or(ZERO);
         loop {
             let elapsed = Instant::now() - self.last_call;
             if elapsed >= frame_duration {
@@ -231,3 +452,56 @@ impl FrameLimiter {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn target_follows_vsync_and_focus() {
+        let mut limiter = FrameLimiter::new(FrameRateLimitStrategy::Adaptive, 30);
+        assert_eq!(
+            limiter.target_frame_duration(),
+            Some(Duration::from_secs(1) / 30)
+        );
+
+        limiter.set_vsync(Some(60));
+        let interval = Duration::from_secs(1) / 60;
+        assert_eq!(
+            limiter.target_frame_duration(),
+            Some(interval * 2 - interval / 2)
+        );
+        limiter.set_rate(FrameRateLimitStrategy::Adaptive, 144);
+        assert_eq!(limiter.target_frame_duration(), None);
+
+        limiter.set_unfocused_fps(Some(10));
+        limiter.set_focused(false);
+        assert_eq!(
+            limiter.target_frame_duration(),
+            Some(Duration::from_millis(100))
+        );
+
+        limiter.set_minimized_fps(Some(4));
+        assert_eq!(
+            limiter.target_frame_duration(),
+            Some(Duration::from_millis(100))
+        );
+        limiter.set_minimized(true);
+        assert_eq!(
+            limiter.target_frame_duration(),
+            Some(Duration::from_millis(250))
+        );
+    }
+
+    #[test]
+    fn stats_smooth_frame_times() {
+        let mut stats = FrameTimeStats::default();
+        stats.record(Duration::from_millis(10));
+        stats.record(Duration::from_millis(20));
+        assert_eq!(stats.frames, 2);
+        assert_eq!(stats.min, Duration::from_millis(10));
+        assert_eq!(stats.max, Duration::from_millis(20));
+        assert!((stats.smoothed.as_secs_f64() - 0.011).abs() < 1e-9);
+        assert!((stats.jitter.as_secs_f64() - 0.001).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,438 @@
+//! Background jobs for gameplay work which doesn't fit in a frame.
+//!
+//! The [Jobs] resource runs closures on a thread pool of its own, highest [JobPriority] first,
+//! so long jobs don't hold up the systems of the dispatcher. A job returns a [JobHandle] to poll
+//! for its result, or sends it as a [JobCompleted] event at the end of the frame it finished in,
+//! when spawned with [Jobs::spawn_event]. Cancelling a job skips it if it hasn't started yet, and
+//! long jobs can check [JobContext::is_cancelled] to stop early.
+
+use std::{
+    cmp::Ordering,
+    collections::BinaryHeap,
+    panic::{catch_unwind, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering as AtomicOrdering},
+        Arc, Mutex,
+    },
+};
+
+use amethyst_error::Error;
+use log::error;
+use rayon::ThreadPoolBuilder;
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+
+use crate::{ecs::*, ArcThreadPool, EventChannel};
+
+/// Identifier of a job, unique for a `Jobs` resource.
+pub type JobId = u64;
+
+/// Priority of a job. Queued jobs of a higher priority start first, and jobs of the same
+/// priority start in the order they were spawned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum JobPriority {
+    /// Work whose result isn't needed soon, like prefetching
+    Low,
+    /// Default priority
+    Normal,
+    /// Work the game is waiting for, like the path of the unit the player just ordered around
+    High,
+}
+
+impl Default for JobPriority {
+    fn default() -> Self {
+        JobPriority::Normal
+    }
+}
+
+/// Passed to a running job.
+#[derive(Debug, Clone)]
+pub struct JobContext {
+    id: JobId,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl JobContext {
+    /// The id of the job.
+    pub fn id(&self) -> JobId {
+        self.id
+    }
+
+    /// Returns true if the job was cancelled, so it can stop early.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(AtomicOrdering::Relaxed)
+    }
+}
+
+#[derive(Debug)]
+struct JobState<T> {
+    result: Mutex<Option<T>>,
+    finished: AtomicBool,
+    cancelled: Arc<AtomicBool>,
+}
+
+/// Handle to the result of a job.
+#[derive(Debug)]
+pub struct JobHandle<T> {
+    id: JobId,
+    state: Arc<JobState<T>>,
+}
+
+impl<T> JobHandle<T> {
+    /// The id of the job.
+    pub fn id(&self) -> JobId {
+        self.id
+    }
+
+    /// Returns true if the job finished, was cancelled before it started, or panicked.
+    pub fn is_finished(&self) -> bool {
+        self.state.finished.load(AtomicOrdering::Acquire)
+    }
+
+    /// Takes the result of the job, if it finished with one.
+    pub fn try_take(&self) -> Option<T> {
+        if !self.is_finished() {
+            return None;
+        }
+        self.state
+            .result
+            .lock()
+            .expect("poisoned job result")
+            .take()
+    }
+
+    /// Cancels the job.
+    pub fn cancel(&self) {
+        self.state.cancelled.store(true, AtomicOrdering::Relaxed);
+    }
+
+    /// Returns true if the job was cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.state.cancelled.load(AtomicOrdering::Relaxed)
+    }
+}
+
+/// Event sent at the end of the frame in which a job spawned with [Jobs::spawn_event]
+/// finished.
+#[derive(Debug, Clone)]
+pub struct JobCompleted<T> {
+    /// The id of the job
+    pub id: JobId,
+    /// The result of the job
+    pub result: T,
+}
+
+struct QueuedJob {
+    priority: JobPriority,
+    id: JobId,
+    run: Box<dyn FnOnce() + Send>,
+}
+
+impl PartialEq for QueuedJob {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for QueuedJob {}
+
+impl PartialOrd for QueuedJob {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for QueuedJob {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // the heap pops the greatest job: the highest priority, then the lowest id
+        self.priority
+            .cmp(&other.priority)
+            .then_with(|| other.id.cmp(&self.id))
+    }
+}
+
+type Completion = Box<dyn FnOnce(&Resources) + Send>;
+
+/// Resource spawning jobs on the thread pool.
+#[allow(missing_debug_implementations)]
+pub struct Jobs {
+    pool: ArcThreadPool,
+    queue: Arc<Mutex<BinaryHeap<QueuedJob>>>,
+    completions: Arc<Mutex<Vec<Completion>>>,
+    next_id: AtomicU64,
+}
+
+impl Jobs {
+    /// Creates the resource running the jobs on the given pool.
+    pub fn new(pool: ArcThreadPool) -> Self {
+        Jobs {
+            pool,
+            queue: Default::default(),
+            completions: Default::default(),
+            next_id: AtomicU64::new(0),
+        }
+    }
+
+    /// Spawns a job, whose result can be polled with the returned handle.
+    pub fn spawn<T, F>(&self, priority: JobPriority, job: F) -> JobHandle<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&JobContext) -> T + Send + 'static,
+    {
+        self.spawn_inner(priority, job, |_, _| {})
+    }
+
+    /// Spawns a job whose result is sent as a `JobCompleted<T>` event at the end of the frame
+    /// it finished in. The event is dropped if there's no `EventChannel<JobCompleted<T>>`, see
+    /// [insert_job_events].
+    pub fn spawn_event<T, F>(&self, priority: JobPriority, job: F) -> JobId
+    where
+        T: Clone + Send + Sync + 'static,
+        F: FnOnce(&JobContext) -> T + Send + 'static,
+    {
+        let completions = self.completions.clone();
+        let handle = self.spawn_inner(priority, job, move |id, result: &mut Option<T>| {
+            if let Some(result) = result.take() {
+                let send: Completion = Box::new(move |resources| {
+                    if let Some(mut events) = resources.get_mut::<EventChannel<JobCompleted<T>>>() {
+                        events.single_write(JobCompleted { id, result });
+                    }
+                });
+                completions
+                    .lock()
+                    .expect("poisoned job completions")
+                    .push(send);
+            }
+        });
+        handle.id
+    }
+
+    fn spawn_inner<T, F, C>(&self, priority: JobPriority, job: F, complete: C) -> JobHandle<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&JobContext) -> T + Send + 'static,
+        C: FnOnce(JobId, &mut Option<T>) + Send + 'static,
+    {
+        let id = self.next_id.fetch_add(1, AtomicOrdering::Relaxed);
+        let state = Arc::new(JobState {
+            result: Mutex::new(None),
+            finished: AtomicBool::new(false),
+            cancelled: Arc::new(AtomicBool::new(false)),
+        });
+        let context = JobContext {
+            id,
+            cancelled: state.cancelled.clone(),
+        };
+        let job_state = state.clone();
+        let run = Box::new(move || {
+            let mut result = None;
+            if !context.is_cancelled() {
+                match catch_unwind(AssertUnwindSafe(|| job(&context))) {
+                    Ok(value) => result = Some(value),
+                    Err(_) => error!("Job {} panicked", id),
+                }
+            }
+            complete(id, &mut result);
+            *job_state.result.lock().expect("poisoned job result") = result;
+            job_state.finished.store(true, AtomicOrdering::Release);
+        });
+
+        self.queue
+            .lock()
+            .expect("poisoned job queue")
+            .push(QueuedJob { priority, id, run });
+        // every spawn runs one job from the queue, the one with the highest priority
+        let queue = self.queue.clone();
+        self.pool.spawn(move || {
+            let next = queue.lock().expect("poisoned job queue").pop();
+            if let Some(job) = next {
+                (job.run)();
+            }
+        });
+        JobHandle { id, state }
+    }
+
+    /// Number of jobs which didn't start yet.
+    pub fn queued(&self) -> usize {
+        self.queue.lock().expect("poisoned job queue").len()
+    }
+
+    /// Sends the events of the jobs which finished since the last call.
+    pub fn send_completed(&self, resources: &Resources) {
+        let completions =
+            std::mem::take(&mut *self.completions.lock().expect("poisoned job completions"));
+        for completion in completions {
+            completion(resources);
+        }
+    }
+}
+
+fn jobs_tick(_world: &mut World, resources: &mut Resources) {
+    #[cfg(feature = "profiler")]
+    profile_scope!("jobs_tick");
+
+    if let Some(jobs) = resources.get::<Jobs>() {
+        jobs.send_completed(resources);
+    }
+}
+
+/// Bundle inserting the [Jobs] resource, and sending the events of finished jobs at the end
+/// of the frame.
+///
+/// The jobs run on a pool created for them, so they don't compete with the systems for the
+/// threads of the dispatcher. [JobsBundle::with_dispatcher_pool] runs them on the
+/// `ArcThreadPool` resource of the application instead.
+#[derive(Debug, Default)]
+pub struct JobsBundle {
+    threads: Option<usize>,
+    dispatcher_pool: bool,
+}
+
+impl JobsBundle {
+    /// Creates the bundle with a pool of as many threads as there are CPUs.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the number of threads of the pool created for the jobs.
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = Some(threads);
+        self
+    }
+
+    /// Runs the jobs on the `ArcThreadPool` resource, shared with the systems of the dispatcher,
+    /// instead of creating a pool for them. A pool is still created if there is no such resource.
+    pub fn with_dispatcher_pool(mut self) -> Self {
+        self.dispatcher_pool = true;
+        self
+    }
+
+    fn create_pool(&self) -> Result<ArcThreadPool, Error> {
+        let mut pool = ThreadPoolBuilder::new().thread_name(|index| format!("job-{}", index));
+        if let Some(threads) = self.threads {
+            pool = pool.num_threads(threads);
+        }
+        Ok(Arc::new(pool.build().map_err(Error::new)?))
+    }
+}
+
+impl SystemBundle for JobsBundle {
+    fn load(
+        &mut self,
+        _world: &mut World,
+        resources: &mut Resources,
+        builder: &mut DispatcherBuilder,
+    ) -> Result<(), Error> {
+        let shared = if self.dispatcher_pool {
+            resources.get::<ArcThreadPool>().map(|pool| pool.clone())
+        } else {
+            None
+        };
+        let pool = match shared {
+            Some(pool) => pool,
+            None => self.create_pool()?,
+        };
+        resources.insert(Jobs::new(pool));
+        builder.add_thread_local_fn(jobs_tick);
+        Ok(())
+    }
+}
+
+/// Inserts the event channel of a job result type, for the systems reading the events of
+/// [Jobs::spawn_event] to register their readers.
+pub fn insert_job_events<T: Clone + Send + Sync + 'static>(resources: &mut Resources) {
+    if !resources.contains::<EventChannel<JobCompleted<T>>>() {
+        resources.insert(EventChannel::<JobCompleted<T>>::new());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::mpsc,
+        time::{Duration, Instant},
+    };
+
+    use super::*;
+
+    fn wait_for<T>(handle: &JobHandle<T>) {
+        let start = Instant::now();
+        while !handle.is_finished() {
+            assert!(
+                start.elapsed() < Duration::from_secs(5),
+                "job didn't finish"
+            );
+            std::thread::yield_now();
+        }
+    }
+
+    #[test]
+    fn jobs_run_by_priority_and_send_events() {
+        let pool = Arc::new(ThreadPoolBuilder::new().num_threads(1).build().unwrap());
+        let jobs = Jobs::new(pool);
+
+        // block the only thread, so the next jobs queue up
+        let (unblock, blocked) = mpsc::channel::<()>();
+        let blocker = jobs.spawn(JobPriority::High, move |_| blocked.recv().unwrap());
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let spawn = |priority, name: &'static str| {
+            let order = order.clone();
+            jobs.spawn(priority, move |_| order.lock().unwrap().push(name))
+        };
+        let low = spawn(JobPriority::Low, "low");
+        let cancelled = spawn(JobPriority::Normal, "cancelled");
+        let high = spawn(JobPriority::High, "high");
+        cancelled.cancel();
+        unblock.send(()).unwrap();
+        wait_for(&blocker);
+        wait_for(&low);
+        wait_for(&high);
+        wait_for(&cancelled);
+        assert_eq!(*order.lock().unwrap(), vec!["high", "low"]);
+        assert!(cancelled.try_take().is_none());
+
+        let mut resources = Resources::default();
+        insert_job_events::<u32>(&mut resources);
+        let mut reader = resources
+            .get_mut::<EventChannel<JobCompleted<u32>>>()
+            .unwrap()
+            .register_reader();
+        let id = jobs.spawn_event(JobPriority::Normal, |_| 7u32);
+        let handle = jobs.spawn(JobPriority::Low, |_| ());
+        wait_for(&handle);
+        jobs.send_completed(&resources);
+        let events: Vec<_> = resources
+            .get::<EventChannel<JobCompleted<u32>>>()
+            .unwrap()
+            .read(&mut reader)
+            .map(|event| (event.id, event.result))
+            .collect();
+        assert_eq!(events, vec![(id, 7)]);
+    }
+
+    #[test]
+    fn bundle_shares_the_dispatcher_pool_only_on_request() {
+        let dispatcher_pool: ArcThreadPool =
+            Arc::new(ThreadPoolBuilder::new().num_threads(1).build().unwrap());
+        let jobs_pool = |mut bundle: JobsBundle| {
+            let mut world = World::default();
+            let mut resources = Resources::default();
+            resources.insert(dispatcher_pool.clone());
+            bundle
+                .load(
+                    &mut world,
+                    &mut resources,
+                    &mut DispatcherBuilder::default(),
+                )
+                .unwrap();
+            let pool = resources.get::<Jobs>().unwrap().pool.clone();
+            pool
+        };
+
+        let own = jobs_pool(JobsBundle::new().with_threads(2));
+        assert!(!Arc::ptr_eq(&own, &dispatcher_pool));
+        assert_eq!(own.current_num_threads(), 2);
+        let shared = jobs_pool(JobsBundle::new().with_dispatcher_pool());
+        assert!(Arc::ptr_eq(&shared, &dispatcher_pool));
+    }
+}
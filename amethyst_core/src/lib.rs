@@ -34,6 +34,7 @@ pub use self::{
     event::EventReader,
     hidden::{Hidden, HiddenPropagate},
     logger::{start_logger, LevelFilter as LogLevelFilter, Logger, LoggerConfig, StdoutLog},
+    name_index::{NameIndex, NameIndexSystem},
     named::Named,
     shrev::EventChannel,
     timing::*,
@@ -51,15 +52,39 @@ pub mod ecs {
     pub use crate::dispatcher::{Dispatcher, DispatcherBuilder, System, SystemBundle};
 }
 
+/// Catching the panics of systems.
+pub mod crash;
+
 /// Dispatcher module.
 pub mod dispatcher;
 
+/// Introspection of the dispatcher.
+pub mod dispatcher_graph;
+
+/// Replay buffers for event channels.
+pub mod event_replay;
+
 /// The frame limiter module.
 pub mod frame_limiter;
 
 /// The geometry module.
 pub mod geometry;
 
+/// Background jobs for gameplay work.
+pub mod jobs;
+
+/// Spawning entities from blueprints, with entity pools.
+pub mod spawner;
+
+/// Systems running only while given states are active.
+pub mod state_stack;
+
+/// Per-system timing of the dispatcher.
+pub mod system_profile;
+
+/// Deterministic random numbers.
+pub mod random;
+
 /// The timing module.
 pub mod timing;
 
@@ -75,5 +100,6 @@ pub mod logger;
 mod axis;
 mod event;
 mod hidden;
+mod name_index;
 mod named;
 pub mod system_ext;
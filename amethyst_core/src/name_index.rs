@@ -0,0 +1,206 @@
+use std::collections::HashMap;
+
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+
+use crate::{ecs::*, transform::Parent, Named};
+
+/// Resource looking up entities by their [Named](struct.Named.html) component.
+///
+/// Names don't have to be unique. An entity is scoped by its closest ancestor which has a name,
+/// so entities can be found by a path of names separated by `/` like `"Level/Boss/Turret1"`,
+/// where the ancestors without a name are skipped. The index is kept up to date by the
+/// [NameIndexSystem](struct.NameIndexSystem.html), registered by the `TransformBundle`.
+#[derive(Debug, Default, Clone)]
+pub struct NameIndex {
+    by_name: HashMap<String, Vec<Entity>>,
+    by_scope: HashMap<(Option<Entity>, String), Vec<Entity>>,
+    names: HashMap<Entity, (String, Option<Entity>)>,
+}
+
+impl NameIndex {
+    /// Find the first entity with the given name.
+    pub fn find(&self, name: &str) -> Option<Entity> {
+        self.find_all(name).first().copied()
+    }
+
+    /// Find all entities with the given name.
+    pub fn find_all(&self, name: &str) -> &[Entity] {
+        self.by_name.get(name).map_or(&[], |entities| &entities[..])
+    }
+
+    /// Find the first entity with the given name scoped by `scope`, or without a named ancestor
+    /// if `scope` is `None`.
+    pub fn find_child(&self, scope: Option<Entity>, name: &str) -> Option<Entity> {
+        self.children(scope, name).first().copied()
+    }
+
+    fn children(&self, scope: Option<Entity>, name: &str) -> &[Entity] {
+        self.by_scope
+            .get(&(scope, name.to_string()))
+            .map_or(&[], |entities| &entities[..])
+    }
+
+    /// Find the first entity at a path of names, starting from the entities without a named
+    /// ancestor.
+    pub fn find_path(&self, path: &str) -> Option<Entity> {
+        self.find_all_path(None, path).into_iter().next()
+    }
+
+    /// Find all entities at a path of names relative to `scope`, or starting from the entities
+    /// without a named ancestor if `scope` is `None`. Every segment of the path can match
+    /// several entities with the same name.
+    pub fn find_all_path(&self, scope: Option<Entity>, path: &str) -> Vec<Entity> {
+        let mut scopes = vec![scope];
+        for segment in path.split('/').filter(|segment| !segment.is_empty()) {
+            scopes = scopes
+                .iter()
+                .flat_map(|scope| self.children(*scope, segment))
+                .map(|entity| Some(*entity))
+                .collect();
+            if scopes.is_empty() {
+                break;
+            }
+        }
+        scopes.into_iter().flatten().collect()
+    }
+
+    /// Get the name of an entity.
+    pub fn name(&self, entity: Entity) -> Option<&str> {
+        self.names.get(&entity).map(|(name, _)| name.as_str())
+    }
+
+    /// Get the path of names of an entity, which finds it with `find_path` unless one of the
+    /// names on the path is shared by several entities.
+    pub fn path(&self, entity: Entity) -> Option<String> {
+        let mut segments = Vec::new();
+        let mut current = Some(entity);
+        // bounded, in case of a cycle in the hierarchy
+        while let Some(entity) = current.filter(|_| segments.len() < 256) {
+            let (name, scope) = self.names.get(&entity)?;
+            segments.push(name.as_str());
+            current = *scope;
+        }
+        segments.reverse();
+        Some(segments.join("/"))
+    }
+
+    /// Number of named entities.
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    /// Returns true if no entity is named.
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    fn rebuild(&mut self, named: Vec<(Entity, String, Option<Entity>)>) {
+        self.by_name.clear();
+        self.by_scope.clear();
+        self.names.clear();
+        for (entity, name, scope) in named {
+            self.by_name.entry(name.clone()).or_default().push(entity);
+            self.by_scope
+                .entry((scope, name.clone()))
+                .or_default()
+                .push(entity);
+            self.names.insert(entity, (name, scope));
+        }
+    }
+}
+
+/// System keeping the [NameIndex](struct.NameIndex.html) up to date.
+///
+/// The index is rebuilt when a `Named` or `Parent` component changed, or named entities were
+/// added or removed.
+#[derive(Debug)]
+pub struct NameIndexSystem;
+
+impl System for NameIndexSystem {
+    fn build(self) -> Box<dyn ParallelRunnable> {
+        Box::new(
+            SystemBuilder::new("NameIndexSystem")
+                .write_resource::<NameIndex>()
+                .read_component::<Parent>()
+                .with_query(<(Entity, &Named)>::query())
+                .with_query(
+                    <Entity>::query().filter(maybe_changed::<Named>() | maybe_changed::<Parent>()),
+                )
+                .build(move |_commands, world, index, (named, changed)| {
+                    #[cfg(feature = "profiler")]
+                    profile_scope!("name_index_system");
+
+                    let count = named.iter(world).count();
+                    if count == index.len() && changed.iter(world).next().is_none() {
+                        return;
+                    }
+
+                    let names: HashMap<Entity, String> = named
+                        .iter(world)
+                        .map(|(entity, name)| (*entity, name.0.to_string()))
+                        .collect();
+                    let entries = named
+                        .iter(world)
+                        .map(|(entity, name)| {
+                            let scope = named_ancestor(world, &names, *entity);
+                            (*entity, name.0.to_string(), scope)
+                        })
+                        .collect();
+                    index.rebuild(entries);
+                }),
+        )
+    }
+}
+
+/// Find the closest ancestor of an entity which has a name.
+fn named_ancestor(
+    world: &SubWorld<'_>,
+    names: &HashMap<Entity, String>,
+    mut entity: Entity,
+) -> Option<Entity> {
+    // bounded, in case of a cycle in the hierarchy
+    for _ in 0..256 {
+        let parent = world
+            .entry_ref(entity)
+            .ok()
+            .and_then(|entry| entry.into_component::<Parent>().ok().map(|p| p.0))?;
+        if names.contains_key(&parent) {
+            return Some(parent);
+        }
+        entity = parent;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_entities_by_path() {
+        let mut world = World::default();
+        let mut resources = Resources::default();
+        resources.insert(NameIndex::default());
+        let mut dispatcher = DispatcherBuilder::default()
+            .add_system(NameIndexSystem)
+            .build(&mut world, &mut resources)
+            .unwrap();
+
+        let level = world.push((Named::new("Level"),));
+        let group = world.push((Parent(level),));
+        let boss = world.push((Named::new("Boss"), Parent(group)));
+        let turret1 = world.push((Named::new("Turret"), Parent(boss)));
+        let turret2 = world.push((Named::new("Turret"), Parent(boss)));
+
+        dispatcher.execute(&mut world, &mut resources);
+
+        let index = resources.get::<NameIndex>().unwrap();
+        assert_eq!(index.find_path("Level/Boss"), Some(boss));
+        let turrets = index.find_all_path(None, "Level/Boss/Turret");
+        assert!(turrets.len() == 2 && turrets.contains(&turret1) && turrets.contains(&turret2));
+        assert_eq!(index.find_all_path(Some(boss), "Turret").len(), 2);
+        assert_eq!(index.find_path("Boss"), None);
+        assert_eq!(index.path(turret2).as_deref(), Some("Level/Boss/Turret"));
+    }
+}
@@ -0,0 +1,173 @@
+//! Deterministic random numbers.
+//!
+//! The [Random] resource is seeded once, and hands out independent [RandomStream]s: named
+//! streams for systems, and split streams which can be stored on entities. A stream only
+//! depends on the seed and its name or split index, so systems get the same numbers whatever
+//! order they run in, which keeps replays and lockstep simulations reproducible. Both types
+//! are serializable, and can be saved in snapshots along with the rest of the game state.
+
+use std::collections::BTreeMap;
+
+use rand::{Error, RngCore};
+use rand_pcg::Pcg32;
+use serde::{Deserialize, Serialize};
+
+/// A stream of random numbers, to be used through the `rand::Rng` trait.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RandomStream(Pcg32);
+
+impl RandomStream {
+    /// Creates the stream with the given index for a seed.
+    pub fn new(seed: u64, stream: u64) -> Self {
+        RandomStream(Pcg32::new(splitmix(seed), stream))
+    }
+}
+
+impl RngCore for RandomStream {
+    fn next_u32(&mut self) -> u32 {
+        self.0.next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0.next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.0.fill_bytes(dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.0.try_fill_bytes(dest)
+    }
+}
+
+/// Resource of seeded random numbers.
+///
+/// The resource can be used as a random number generator itself, but its numbers then depend
+/// on the order of the systems using it. Use a named stream per system instead:
+///
+/// ```rust
+/// # use amethyst::core::random::Random;
+/// use rand::Rng;
+///
+/// let mut random = Random::new(42);
+/// let damage = random.stream("combat").gen_range(1..=6);
+/// # assert!((1..=6).contains(&damage));
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Random {
+    seed: u64,
+    main: RandomStream,
+    streams: BTreeMap<String, RandomStream>,
+    splits: u64,
+}
+
+impl Default for Random {
+    /// Seeds the generator with 0, to be reseeded when a game starts.
+    fn default() -> Self {
+        Random::new(0)
+    }
+}
+
+impl Random {
+    /// Creates a generator with the given seed.
+    pub fn new(seed: u64) -> Self {
+        Random {
+            seed,
+            main: RandomStream::new(seed, 0),
+            streams: BTreeMap::new(),
+            splits: 0,
+        }
+    }
+
+    /// The seed of the generator.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Restarts the generator and all its streams with a new seed. Streams split off before
+    /// keep their numbers.
+    pub fn reseed(&mut self, seed: u64) {
+        *self = Random::new(seed);
+    }
+
+    /// Get a stream by name, e.g. the name of the system using it. The stream starts with
+    /// the same numbers for the same seed and name, whenever it's first used.
+    pub fn stream(&mut self, name: &str) -> &mut RandomStream {
+        let seed = self.seed;
+        self.streams
+            .entry(name.to_string())
+            .or_insert_with(|| RandomStream::new(seed, fnv1a(name) << 1))
+    }
+
+    /// Split off a new stream, e.g. to store on an entity so it has its own numbers. The
+    /// streams are numbered, so the same splits give the same streams.
+    pub fn split(&mut self) -> RandomStream {
+        self.splits += 1;
+        RandomStream::new(self.seed, (self.splits << 1) | 1)
+    }
+}
+
+impl RngCore for Random {
+    fn next_u32(&mut self) -> u32 {
+        self.main.next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.main.next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.main.fill_bytes(dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.main.try_fill_bytes(dest)
+    }
+}
+
+/// Scrambles a seed, so close seeds give unrelated states.
+fn splitmix(seed: u64) -> u64 {
+    let mut z = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// Hash of a stream name which is stable across platforms and compiler versions.
+fn fnv1a(name: &str) -> u64 {
+    name.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn streams_only_depend_on_seed_and_name() {
+        let mut first = Random::new(7);
+        let mut second = Random::new(7);
+        let a = first.stream("a").next_u64();
+        let b = first.stream("b").next_u64();
+        assert_eq!(second.stream("b").next_u64(), b);
+        assert_eq!(second.stream("a").next_u64(), a);
+        assert_ne!(a, b);
+        assert_eq!(first.split(), second.split());
+        assert_ne!(Random::new(8).stream("a").next_u64(), a);
+    }
+
+    #[test]
+    fn restored_generator_continues_the_sequence() {
+        let mut random = Random::new(3);
+        random.stream("ai").next_u64();
+        let saved = ron::ser::to_string(&random).unwrap();
+        let mut restored: Random = ron::de::from_str(&saved).unwrap();
+        assert_eq!(
+            restored.stream("ai").next_u64(),
+            random.stream("ai").next_u64()
+        );
+        assert_eq!(restored.next_u32(), random.next_u32());
+    }
+}
@@ -0,0 +1,408 @@
+//! Spawning entities from registered blueprints, with pooling of the despawned entities.
+//!
+//! Register blueprints in the [Spawner] resource, and request entities from any system. The
+//! requests are processed at the end of the frame by the thread local function added by the
+//! [SpawnerBundle], which sends a [SpawnEvent] for every spawned and despawned entity.
+//!
+//! Entities of pooled blueprints aren't deleted when they are despawned, but get the [Pooled]
+//! and [Hidden](crate::Hidden) components until they are spawned again, after running the reset
+//! hook of the pool. Gameplay systems should skip them with a `!component::<Pooled>()` filter.
+
+use std::{collections::HashMap, sync::Arc};
+
+use amethyst_error::Error;
+use log::warn;
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+
+use crate::{
+    ecs::{storage::IntoComponentSource, world::Entry, *},
+    shrev::EventChannel,
+    Hidden,
+};
+
+type SpawnFn = Box<dyn Fn(&mut World) -> Entity + Send + Sync>;
+type EntryFn = Box<dyn Fn(&mut Entry<'_>) + Send + Sync>;
+type InitFn = Box<dyn FnOnce(&mut Entry<'_>) + Send + Sync>;
+
+/// Marks a despawned entity waiting in the pool of its blueprint.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Pooled;
+
+/// Event sent by the [Spawner] when it spawned or despawned an entity.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SpawnEvent {
+    /// An entity was spawned from a blueprint.
+    Spawned {
+        /// Name of the blueprint
+        blueprint: Arc<str>,
+        /// The spawned entity
+        entity: Entity,
+        /// True if the entity was taken from the pool of the blueprint
+        reused: bool,
+    },
+    /// An entity was despawned.
+    Despawned {
+        /// Name of the blueprint, `None` if the entity wasn't spawned by the `Spawner`
+        blueprint: Option<Arc<str>>,
+        /// The despawned entity
+        entity: Entity,
+        /// True if the entity was put into the pool of its blueprint instead of being deleted
+        pooled: bool,
+    },
+}
+
+struct Pool {
+    capacity: usize,
+    reset: EntryFn,
+    entities: Vec<Entity>,
+}
+
+struct Blueprint {
+    name: Arc<str>,
+    spawn: SpawnFn,
+    pool: Option<Pool>,
+}
+
+enum Request {
+    Spawn {
+        blueprint: String,
+        init: Option<InitFn>,
+    },
+    Prewarm {
+        blueprint: String,
+        count: usize,
+    },
+    Despawn(Entity),
+}
+
+/// Resource spawning entities from registered blueprints.
+///
+/// ```rust,ignore
+/// spawner
+///     .register_components("bullet", (Transform::default(), Bullet { speed: 20.0 }))
+///     .set_pool("bullet", 1000, |entry| {
+///         *entry.get_component_mut::<Bullet>().unwrap() = Bullet { speed: 20.0 };
+///     });
+///
+/// // in a system
+/// spawner.spawn_with("bullet", move |entry| {
+///     *entry.get_component_mut::<Transform>().unwrap() = muzzle;
+/// });
+/// ```
+#[derive(Default)]
+#[allow(missing_debug_implementations)]
+pub struct Spawner {
+    blueprints: HashMap<String, Blueprint>,
+    spawned: HashMap<Entity, Arc<str>>,
+    requests: Vec<Request>,
+}
+
+impl Spawner {
+    /// Creates a spawner without blueprints.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Registers a blueprint spawning an entity with the given function, e.g. loading a prefab
+    /// by adding its handle.
+    pub fn register<F>(&mut self, name: &str, spawn: F) -> &mut Self
+    where
+        F: Fn(&mut World) -> Entity + Send + Sync + 'static,
+    {
+        let blueprint = Blueprint {
+            name: name.into(),
+            spawn: Box::new(spawn),
+            pool: None,
+        };
+        if self
+            .blueprints
+            .insert(name.to_string(), blueprint)
+            .is_some()
+        {
+            warn!("Replaced the blueprint {:?} of the spawner", name);
+        }
+        self
+    }
+
+    /// Registers a blueprint spawning an entity with a copy of the given components.
+    pub fn register_components<T>(&mut self, name: &str, components: T) -> &mut Self
+    where
+        T: Clone + Send + Sync + 'static,
+        Option<T>: IntoComponentSource,
+    {
+        self.register(name, move |world| world.push(components.clone()))
+    }
+
+    /// Pools up to `capacity` despawned entities of a blueprint, which are reused by the next
+    /// spawns after running `reset` on them.
+    pub fn set_pool<F>(&mut self, name: &str, capacity: usize, reset: F) -> &mut Self
+    where
+        F: Fn(&mut Entry<'_>) + Send + Sync + 'static,
+    {
+        match self.blueprints.get_mut(name) {
+            Some(blueprint) => {
+                blueprint.pool = Some(Pool {
+                    capacity,
+                    reset: Box::new(reset),
+                    entities: Vec::new(),
+                });
+            }
+            None => warn!("Can't pool the unknown blueprint {:?}", name),
+        }
+        self
+    }
+
+    /// Returns true if a blueprint is registered with the given name.
+    pub fn has_blueprint(&self, name: &str) -> bool {
+        self.blueprints.contains_key(name)
+    }
+
+    /// Spawns an entity from a blueprint at the end of the frame.
+    pub fn spawn(&mut self, blueprint: &str) {
+        self.requests.push(Request::Spawn {
+            blueprint: blueprint.to_string(),
+            init: None,
+        });
+    }
+
+    /// Spawns an entity from a blueprint at the end of the frame, and runs `init` on it, e.g.
+    /// to place it.
+    pub fn spawn_with<F>(&mut self, blueprint: &str, init: F)
+    where
+        F: FnOnce(&mut Entry<'_>) + Send + Sync + 'static,
+    {
+        self.requests.push(Request::Spawn {
+            blueprint: blueprint.to_string(),
+            init: Some(Box::new(init)),
+        });
+    }
+
+    /// Fills the pool of a blueprint with `count` entities at the end of the frame, so they
+    /// don't have to be created while playing.
+    pub fn prewarm(&mut self, blueprint: &str, count: usize) {
+        self.requests.push(Request::Prewarm {
+            blueprint: blueprint.to_string(),
+            count,
+        });
+    }
+
+    /// Despawns an entity at the end of the frame, putting it into the pool of its blueprint
+    /// if there is room, or deleting it otherwise.
+    pub fn despawn(&mut self, entity: Entity) {
+        self.requests.push(Request::Despawn(entity));
+    }
+
+    /// Number of entities in the pool of a blueprint.
+    pub fn pooled(&self, blueprint: &str) -> usize {
+        self.blueprints
+            .get(blueprint)
+            .and_then(|blueprint| blueprint.pool.as_ref())
+            .map_or(0, |pool| pool.entities.len())
+    }
+
+    /// Get the name of the blueprint an active entity was spawned from.
+    pub fn blueprint_of(&self, entity: Entity) -> Option<&str> {
+        self.spawned.get(&entity).map(|name| &**name)
+    }
+
+    /// Processes the requests, in the order they were made.
+    pub fn process(&mut self, world: &mut World, events: &mut EventChannel<SpawnEvent>) {
+        for request in std::mem::take(&mut self.requests) {
+            match request {
+                Request::Spawn { blueprint, init } => {
+                    if let Some(event) = self.spawn_now(world, &blueprint, init) {
+                        events.single_write(event);
+                    }
+                }
+                Request::Prewarm { blueprint, count } => self.prewarm_now(world, &blueprint, count),
+                Request::Despawn(entity) => {
+                    if let Some(event) = self.despawn_now(world, entity) {
+                        events.single_write(event);
+                    }
+                }
+            }
+        }
+    }
+
+    fn spawn_now(
+        &mut self,
+        world: &mut World,
+        name: &str,
+        init: Option<InitFn>,
+    ) -> Option<SpawnEvent> {
+        let blueprint = match self.blueprints.get_mut(name) {
+            Some(blueprint) => blueprint,
+            None => {
+                warn!("Can't spawn the unknown blueprint {:?}", name);
+                return None;
+            }
+        };
+        let reused = blueprint.pool.as_mut().and_then(|pool| {
+            let entity = pool.entities.pop()?;
+            let mut entry = world.entry(entity)?;
+            entry.remove_component::<Pooled>();
+            entry.remove_component::<Hidden>();
+            (pool.reset)(&mut entry);
+            Some(entity)
+        });
+        let entity = reused.unwrap_or_else(|| (blueprint.spawn)(world));
+        if let (Some(init), Some(mut entry)) = (init, world.entry(entity)) {
+            init(&mut entry);
+        }
+        self.spawned.insert(entity, blueprint.name.clone());
+        Some(SpawnEvent::Spawned {
+            blueprint: blueprint.name.clone(),
+            entity,
+            reused: reused.is_some(),
+        })
+    }
+
+    fn prewarm_now(&mut self, world: &mut World, name: &str, count: usize) {
+        let blueprint = match self.blueprints.get_mut(name) {
+            Some(blueprint) => blueprint,
+            None => {
+                warn!("Can't prewarm the unknown blueprint {:?}", name);
+                return;
+            }
+        };
+        let pool = match &mut blueprint.pool {
+            Some(pool) => pool,
+            None => {
+                warn!("Can't prewarm the blueprint {:?} without a pool", name);
+                return;
+            }
+        };
+        let count = count.min(pool.capacity.saturating_sub(pool.entities.len()));
+        for _ in 0..count {
+            let entity = (blueprint.spawn)(world);
+            if let Some(mut entry) = world.entry(entity) {
+                entry.add_component(Pooled);
+                entry.add_component(Hidden);
+            }
+            pool.entities.push(entity);
+        }
+    }
+
+    fn despawn_now(&mut self, world: &mut World, entity: Entity) -> Option<SpawnEvent> {
+        let name = self.spawned.remove(&entity);
+        let pool = name
+            .as_ref()
+            .and_then(|name| self.blueprints.get_mut(&**name))
+            .and_then(|blueprint| blueprint.pool.as_mut())
+            .filter(|pool| pool.entities.len() < pool.capacity);
+        let pooled = match (pool, world.entry(entity)) {
+            (Some(pool), Some(mut entry)) => {
+                entry.add_component(Pooled);
+                entry.add_component(Hidden);
+                pool.entities.push(entity);
+                true
+            }
+            (_, Some(_)) => {
+                world.remove(entity);
+                false
+            }
+            // already deleted
+            (_, None) => return None,
+        };
+        Some(SpawnEvent::Despawned {
+            blueprint: name,
+            entity,
+            pooled,
+        })
+    }
+}
+
+/// Processes the requests of the [Spawner] at the end of the frame.
+fn spawner_tick(world: &mut World, resources: &mut Resources) {
+    #[cfg(feature = "profiler")]
+    profile_scope!("spawner_tick");
+
+    let spawner = resources.get_mut::<Spawner>();
+    let events = resources.get_mut::<EventChannel<SpawnEvent>>();
+    if let (Some(mut spawner), Some(mut events)) = (spawner, events) {
+        spawner.process(world, &mut events);
+    }
+}
+
+/// Bundle for the [Spawner]
+///
+/// This inserts the `Spawner` and `EventChannel<SpawnEvent>` resources, and adds a thread local
+/// function processing the requests. Add it after the bundles of the systems spawning
+/// entities, so they are spawned in the same frame.
+#[derive(Debug, Default)]
+pub struct SpawnerBundle;
+
+impl SystemBundle for SpawnerBundle {
+    fn load(
+        &mut self,
+        _world: &mut World,
+        resources: &mut Resources,
+        builder: &mut DispatcherBuilder,
+    ) -> Result<(), Error> {
+        if !resources.contains::<Spawner>() {
+            resources.insert(Spawner::new());
+        }
+        resources.insert(EventChannel::<SpawnEvent>::new());
+        builder.add_thread_local_fn(spawner_tick);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct Health(u32);
+
+    #[test]
+    fn despawned_entities_are_pooled_and_reset() {
+        let mut world = World::default();
+        let mut events = EventChannel::<SpawnEvent>::new();
+        let mut reader = events.register_reader();
+        let mut spawner = Spawner::new();
+        spawner
+            .register_components("enemy", (Health(10),))
+            .set_pool("enemy", 1, |entry| {
+                *entry.get_component_mut::<Health>().unwrap() = Health(10);
+            });
+
+        spawner.spawn("enemy");
+        spawner.process(&mut world, &mut events);
+        let entity = match events.read(&mut reader).next() {
+            Some(SpawnEvent::Spawned { entity, .. }) => *entity,
+            event => panic!("expected a spawn event, got {:?}", event),
+        };
+
+        world
+            .entry(entity)
+            .unwrap()
+            .get_component_mut::<Health>()
+            .unwrap()
+            .0 = 0;
+        spawner.despawn(entity);
+        spawner.process(&mut world, &mut events);
+        assert_eq!(spawner.pooled("enemy"), 1);
+        assert!(world
+            .entry(entity)
+            .unwrap()
+            .get_component::<Pooled>()
+            .is_ok());
+
+        spawner.spawn("enemy");
+        spawner.process(&mut world, &mut events);
+        let events: Vec<_> = events.read(&mut reader).cloned().collect();
+        assert_eq!(
+            events.last(),
+            Some(&SpawnEvent::Spawned {
+                blueprint: "enemy".into(),
+                entity,
+                reused: true,
+            })
+        );
+        let entry = world.entry(entity).unwrap();
+        assert_eq!(entry.get_component::<Health>().ok(), Some(&Health(10)));
+        assert!(entry.get_component::<Pooled>().is_err());
+    }
+}
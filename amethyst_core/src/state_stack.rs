@@ -0,0 +1,257 @@
+//! Systems running only while given states are active.
+//!
+//! The application keeps the [StateStack] resource up to date with the names of the states on
+//! its stack. Systems added with [DispatcherBuilder::add_state_group] only run while their
+//! [StateCondition] holds, e.g. gameplay systems pause while a menu state is pushed on top of
+//! the game state, without the states having to run the systems themselves.
+
+use std::any::type_name;
+
+use amethyst_error::Error;
+use legion::{
+    storage::ComponentTypeId,
+    systems::{CommandBuffer, ResourceSet, ResourceTypeId, Runnable, SystemId, UnsafeResources},
+    world::{ArchetypeAccess, WorldId},
+    Read, Resources, World,
+};
+
+use crate::dispatcher::{DispatcherBuilder, DispatcherItem, SystemBundle};
+
+/// Resource holding the names of the states on the stack of the state machine, from the
+/// bottom to the top. The name of a state is its type name, unless it overrides `State::name`.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct StateStack {
+    states: Vec<&'static str>,
+}
+
+impl StateStack {
+    /// Replaces the states on the stack.
+    pub fn set(&mut self, states: impl IntoIterator<Item = &'static str>) {
+        self.states.clear();
+        self.states.extend(states);
+    }
+
+    /// The names of the states, from the bottom of the stack to the top.
+    pub fn states(&self) -> &[&'static str] {
+        &self.states
+    }
+
+    /// The name of the active state.
+    pub fn top(&self) -> Option<&'static str> {
+        self.states.last().copied()
+    }
+
+    /// Returns true if the state with the given name is on the stack.
+    pub fn contains(&self, name: &str) -> bool {
+        self.states.iter().any(|state| *state == name)
+    }
+
+    /// Returns true if the state of type `S` is the active state.
+    pub fn is_on_top<S: ?Sized>(&self) -> bool {
+        self.top() == Some(type_name::<S>())
+    }
+
+    /// Returns true if the state of type `S` is on the stack, active or paused.
+    pub fn is_active<S: ?Sized>(&self) -> bool {
+        self.contains(type_name::<S>())
+    }
+
+    /// Returns true if the condition holds for the states of the stack.
+    pub fn matches(&self, condition: &StateCondition) -> bool {
+        match condition {
+            StateCondition::OnTop(name) => self.top() == Some(*name),
+            StateCondition::OnStack(name) => self.contains(name),
+            StateCondition::NotOnStack(name) => !self.contains(name),
+        }
+    }
+}
+
+/// Condition on the [StateStack] for a group of systems to run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StateCondition {
+    /// The state with the name is the active state
+    OnTop(&'static str),
+    /// The state with the name is on the stack, active or paused
+    OnStack(&'static str),
+    /// The state with the name isn't on the stack
+    NotOnStack(&'static str),
+}
+
+impl StateCondition {
+    /// The state of type `S` is the active state.
+    pub fn on_top<S: ?Sized>() -> Self {
+        StateCondition::OnTop(type_name::<S>())
+    }
+
+    /// The state of type `S` is on the stack, active or paused.
+    pub fn on_stack<S: ?Sized>() -> Self {
+        StateCondition::OnStack(type_name::<S>())
+    }
+
+    /// The state of type `S` isn't on the stack.
+    pub fn not_on_stack<S: ?Sized>() -> Self {
+        StateCondition::NotOnStack(type_name::<S>())
+    }
+}
+
+/// A system running only while a `StateCondition` holds.
+pub(crate) struct StateGated<S: ?Sized> {
+    condition: StateCondition,
+    resource_reads: Vec<ResourceTypeId>,
+    system: Box<S>,
+}
+
+impl<S: Runnable + ?Sized> StateGated<S> {
+    pub(crate) fn new(system: Box<S>, condition: StateCondition) -> Self {
+        let resource_reads = system
+            .reads()
+            .0
+            .iter()
+            .copied()
+            .chain(std::iter::once(ResourceTypeId::of::<StateStack>()))
+            .collect();
+        StateGated {
+            condition,
+            resource_reads,
+            system,
+        }
+    }
+}
+
+impl<S: Runnable + ?Sized> Runnable for StateGated<S> {
+    // Default passthrough impls
+    fn name(&self) -> Option<&SystemId> {
+        self.system.name()
+    }
+
+    fn reads(&self) -> (&[ResourceTypeId], &[ComponentTypeId]) {
+        let (_, components) = self.system.reads();
+        // Our local copy of the resources of the system, with Read<StateStack> appended
+        (&self.resource_reads[..], components)
+    }
+
+    fn writes(&self) -> (&[ResourceTypeId], &[ComponentTypeId]) {
+        self.system.writes()
+    }
+
+    fn prepare(&mut self, world: &World) {
+        self.system.prepare(world)
+    }
+
+    fn accesses_archetypes(&self) -> &ArchetypeAccess {
+        self.system.accesses_archetypes()
+    }
+
+    unsafe fn run_unsafe(&mut self, world: &World, resources: &UnsafeResources) {
+        let resources_static = &*(resources as *const UnsafeResources);
+        let stack = Read::<StateStack>::fetch_unchecked(resources_static);
+
+        if !stack.matches(&self.condition) {
+            return;
+        }
+
+        self.system.run_unsafe(world, resources);
+    }
+
+    fn command_buffer_mut(&mut self, world: WorldId) -> Option<&mut CommandBuffer> {
+        self.system.command_buffer_mut(world)
+    }
+}
+
+/// Makes an item of a dispatcher run only while the condition holds.
+pub(crate) fn gate(item: DispatcherItem, condition: StateCondition) -> DispatcherItem {
+    match item {
+        DispatcherItem::System(system) => {
+            DispatcherItem::System(Box::new(StateGated::new(system, condition)))
+        }
+        DispatcherItem::FixedSystem(system) => {
+            DispatcherItem::FixedSystem(Box::new(StateGated::new(system, condition)))
+        }
+        DispatcherItem::ThreadLocalSystem(system) => {
+            DispatcherItem::ThreadLocalSystem(Box::new(StateGated::new(system, condition)))
+        }
+        DispatcherItem::ThreadLocalFn(mut f) => {
+            DispatcherItem::ThreadLocalFn(Box::new(move |world, resources| {
+                let matches = resources
+                    .get::<StateStack>()
+                    .map_or(false, |stack| stack.matches(&condition));
+                if matches {
+                    f(world, resources);
+                }
+            }))
+        }
+        DispatcherItem::SystemBundle(bundle) => {
+            DispatcherItem::SystemBundle(Box::new(StateGroupBundle { condition, bundle }))
+        }
+        DispatcherItem::FlushCmdBuffers => DispatcherItem::FlushCmdBuffers,
+    }
+}
+
+/// A bundle whose systems run only while the condition holds.
+struct StateGroupBundle {
+    condition: StateCondition,
+    bundle: Box<dyn SystemBundle>,
+}
+
+impl SystemBundle for StateGroupBundle {
+    fn load(
+        &mut self,
+        world: &mut World,
+        resources: &mut Resources,
+        builder: &mut DispatcherBuilder,
+    ) -> Result<(), Error> {
+        let mut group = DispatcherBuilder::default();
+        self.bundle.load(world, resources, &mut group)?;
+        for item in group.take_items() {
+            builder.add_item(gate(item, self.condition));
+        }
+        Ok(())
+    }
+
+    fn unload(&mut self, world: &mut World, resources: &mut Resources) -> Result<(), Error> {
+        self.bundle.unload(world, resources)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use legion::SystemBuilder;
+
+    use super::*;
+
+    struct Game;
+    struct Menu;
+
+    #[test]
+    fn group_pauses_while_menu_is_on_top() {
+        let mut world = World::default();
+        let mut resources = Resources::default();
+        resources.insert(0u32);
+        resources.insert(StateStack::default());
+
+        let mut dispatcher = DispatcherBuilder::default()
+            .add_state_group(StateCondition::on_top::<Game>(), |group| {
+                group.add_system(|| {
+                    SystemBuilder::new("GameplaySystem")
+                        .write_resource::<u32>()
+                        .build(|_, _, frames, _| **frames += 1)
+                });
+            })
+            .build(&mut world, &mut resources)
+            .unwrap();
+
+        resources
+            .get_mut::<StateStack>()
+            .unwrap()
+            .set(vec![type_name::<Game>()]);
+        dispatcher.execute(&mut world, &mut resources);
+        resources
+            .get_mut::<StateStack>()
+            .unwrap()
+            .set(vec![type_name::<Game>(), type_name::<Menu>()]);
+        dispatcher.execute(&mut world, &mut resources);
+
+        assert_eq!(*resources.get::<u32>().unwrap(), 1);
+        assert!(resources.get::<StateStack>().unwrap().is_active::<Game>());
+    }
+}
@@ -0,0 +1,294 @@
+//! Per-system timing of the dispatcher.
+//!
+//! Insert a [SystemProfile] resource before building a dispatcher, and the dispatcher times
+//! every system it runs. The profile keeps a rolling window of the times of each system, and
+//! can record a trace of the frames in the chrome tracing format, to be opened with
+//! `chrome://tracing` or similar viewers. This doesn't need the `profiler` feature.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt::Write,
+    sync::{Arc, Mutex},
+    thread::{self, ThreadId},
+    time::Duration,
+};
+
+use instant::Instant;
+use legion::{
+    storage::ComponentTypeId,
+    systems::{CommandBuffer, ResourceTypeId, Runnable, SystemId, UnsafeResources},
+    world::{ArchetypeAccess, WorldId},
+    World,
+};
+
+/// Times of a single system over the window of a `SystemProfile`.
+#[derive(Clone, Debug, Default)]
+pub struct SystemTiming {
+    samples: VecDeque<Duration>,
+}
+
+impl SystemTiming {
+    fn push(&mut self, duration: Duration, window: usize) {
+        if self.samples.len() >= window {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(duration);
+    }
+
+    /// The time of the last run
+    pub fn last(&self) -> Duration {
+        self.samples.back().copied().unwrap_or_default()
+    }
+
+    /// The average time over the window
+    pub fn average(&self) -> Duration {
+        if self.samples.is_empty() {
+            return Duration::default();
+        }
+        self.samples.iter().sum::<Duration>() / self.samples.len() as u32
+    }
+
+    /// The longest time in the window
+    pub fn max(&self) -> Duration {
+        self.samples.iter().max().copied().unwrap_or_default()
+    }
+
+    /// The time which `percentile` percent of the runs in the window took at most, e.g. 99.0
+    /// for the time of the slowest runs but the worst outliers.
+    pub fn percentile(&self, percentile: f32) -> Duration {
+        if self.samples.is_empty() {
+            return Duration::default();
+        }
+        let mut sorted: Vec<_> = self.samples.iter().copied().collect();
+        sorted.sort();
+        let rank = (percentile.max(0.).min(100.) / 100. * (sorted.len() - 1) as f32).round();
+        sorted[rank as usize]
+    }
+
+    /// Number of runs in the window
+    pub fn runs(&self) -> usize {
+        self.samples.len()
+    }
+}
+
+/// A run of a system.
+#[derive(Clone, Debug)]
+struct Span {
+    name: Arc<str>,
+    start: Instant,
+    duration: Duration,
+    thread: ThreadId,
+}
+
+/// Collects the spans of the systems running on any thread.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct ProfileRecorder(Arc<Mutex<Vec<Span>>>);
+
+impl ProfileRecorder {
+    fn record(&self, span: Span) {
+        self.0.lock().expect("poisoned profile recorder").push(span);
+    }
+
+    fn drain(&self) -> Vec<Span> {
+        std::mem::take(&mut *self.0.lock().expect("poisoned profile recorder"))
+    }
+}
+
+/// Resource holding the times of the systems of the dispatcher.
+///
+/// Systems are timed when the resource is present while the dispatcher is built. Systems
+/// running several times per frame, such as fixed systems, have one sample per run.
+#[derive(Debug)]
+pub struct SystemProfile {
+    window: usize,
+    systems: Vec<(Arc<str>, SystemTiming)>,
+    frame: SystemTiming,
+    frame_start: Option<Instant>,
+    recorder: ProfileRecorder,
+    epoch: Instant,
+    trace: Option<Vec<Span>>,
+}
+
+impl Default for SystemProfile {
+    fn default() -> Self {
+        SystemProfile::new(120)
+    }
+}
+
+impl SystemProfile {
+    /// Creates a profile keeping the times of the given number of runs, 120 by default.
+    pub fn new(window: usize) -> Self {
+        SystemProfile {
+            window: window.max(1),
+            systems: Vec::new(),
+            frame: SystemTiming::default(),
+            frame_start: None,
+            recorder: ProfileRecorder::default(),
+            epoch: Instant::now(),
+            trace: None,
+        }
+    }
+
+    /// Get the times of a system by name.
+    pub fn system(&self, name: &str) -> Option<&SystemTiming> {
+        self.systems
+            .iter()
+            .find(|(n, _)| &**n == name)
+            .map(|(_, timing)| timing)
+    }
+
+    /// Iterate over the times of all systems, in the order they first ran.
+    pub fn systems(&self) -> impl Iterator<Item = (&str, &SystemTiming)> {
+        self.systems.iter().map(|(name, timing)| (&**name, timing))
+    }
+
+    /// Get the times of the frame schedule of the dispatcher as a whole.
+    pub fn frame(&self) -> &SystemTiming {
+        &self.frame
+    }
+
+    /// Start recording a chrome trace, discarding the one being recorded.
+    pub fn start_trace(&mut self) {
+        self.trace = Some(Vec::new());
+    }
+
+    /// Return true if a trace is being recorded.
+    pub fn is_tracing(&self) -> bool {
+        self.trace.is_some()
+    }
+
+    /// Stop recording the trace, and return it in the chrome tracing JSON format.
+    pub fn finish_trace(&mut self) -> Option<String> {
+        let spans = self.trace.take()?;
+        let mut threads = HashMap::new();
+        let mut json = String::from("[");
+        for (index, span) in spans.iter().enumerate() {
+            let next_thread = threads.len();
+            let thread = *threads.entry(span.thread).or_insert(next_thread);
+            if index > 0 {
+                json.push(',');
+            }
+            write!(
+                json,
+                "\n{{\"name\":\"{}\",\"ph\":\"X\",\"ts\":{},\"dur\":{},\"pid\":1,\"tid\":{}}}",
+                escape(&span.name),
+                span.start.saturating_duration_since(self.epoch).as_micros(),
+                span.duration.as_micros(),
+                thread,
+            )
+            .unwrap();
+        }
+        json.push_str("\n]\n");
+        Some(json)
+    }
+
+    pub(crate) fn recorder(&self) -> ProfileRecorder {
+        self.recorder.clone()
+    }
+
+    pub(crate) fn start_frame(&mut self) {
+        self.frame_start = Some(Instant::now());
+    }
+
+    /// Moves the spans recorded by the systems into the timings.
+    pub(crate) fn finish_frame(&mut self) {
+        if let Some(start) = self.frame_start.take() {
+            self.frame.push(start.elapsed(), self.window);
+        }
+        let spans = self.recorder.drain();
+        for span in &spans {
+            let window = self.window;
+            match self.systems.iter_mut().find(|(name, _)| *name == span.name) {
+                Some((_, timing)) => timing.push(span.duration, window),
+                None => {
+                    let mut timing = SystemTiming::default();
+                    timing.push(span.duration, window);
+                    self.systems.push((span.name.clone(), timing));
+                }
+            }
+        }
+        if let Some(trace) = &mut self.trace {
+            trace.extend(spans);
+        }
+    }
+}
+
+fn escape(name: &str) -> String {
+    name.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// A system timed into a `SystemProfile`.
+pub(crate) struct Profiled<S: ?Sized> {
+    name: Arc<str>,
+    recorder: ProfileRecorder,
+    system: Box<S>,
+}
+
+impl<S: Runnable + ?Sized> Profiled<S> {
+    pub(crate) fn new(system: Box<S>, recorder: ProfileRecorder) -> Self {
+        let name = system
+            .name()
+            .map_or_else(|| "unnamed system".to_string(), |name| name.to_string());
+        Profiled {
+            name: name.into(),
+            recorder,
+            system,
+        }
+    }
+}
+
+impl<S: Runnable + ?Sized> Runnable for Profiled<S> {
+    // Default passthrough impls
+    fn name(&self) -> Option<&SystemId> {
+        self.system.name()
+    }
+
+    fn reads(&self) -> (&[ResourceTypeId], &[ComponentTypeId]) {
+        self.system.reads()
+    }
+
+    fn writes(&self) -> (&[ResourceTypeId], &[ComponentTypeId]) {
+        self.system.writes()
+    }
+
+    fn prepare(&mut self, world: &World) {
+        self.system.prepare(world)
+    }
+
+    fn accesses_archetypes(&self) -> &ArchetypeAccess {
+        self.system.accesses_archetypes()
+    }
+
+    unsafe fn run_unsafe(&mut self, world: &World, resources: &UnsafeResources) {
+        let start = Instant::now();
+        self.system.run_unsafe(world, resources);
+        self.recorder.record(Span {
+            name: self.name.clone(),
+            start,
+            duration: start.elapsed(),
+            thread: thread::current().id(),
+        });
+    }
+
+    fn command_buffer_mut(&mut self, world: WorldId) -> Option<&mut CommandBuffer> {
+        self.system.command_buffer_mut(world)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timing_statistics() {
+        let mut timing = SystemTiming::default();
+        for millis in 1..=10 {
+            timing.push(Duration::from_millis(millis), 8);
+        }
+        assert_eq!(timing.runs(), 8);
+        assert_eq!(timing.last(), Duration::from_millis(10));
+        assert_eq!(timing.max(), Duration::from_millis(10));
+        assert_eq!(timing.percentile(0.), Duration::from_millis(3));
+        assert_eq!(timing.average(), Duration::from_micros(6500));
+    }
+}
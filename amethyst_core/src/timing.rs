@@ -1,6 +1,62 @@
 //! Utilities for working with time.
 
-use std::time::{Duration, Instant};
+use std::time::Duration;
+
+pub use instant::Instant;
+
+/// A clock of `Time`, which can be scaled and paused independently of the others.
+///
+/// Systems consuming time read it with `Time::delta_seconds_for` and document the clock they
+/// follow, so e.g. a system animating menus can follow the `Ui` clock while the game is paused.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Clock {
+    /// Time of the game world, the clock of `Time::delta_seconds` and `Time::absolute_time`.
+    /// Follows the global scale and pause, and the slow motion.
+    Game,
+    /// Real time, which is never scaled or paused.
+    Real,
+    /// Time of the user interface. Follows the global scale and pause, but not the slow motion.
+    Ui,
+}
+
+impl Clock {
+    fn index(self) -> usize {
+        match self {
+            Clock::Game => 0,
+            Clock::Real => 1,
+            Clock::Ui => 2,
+        }
+    }
+}
+
+/// Scale, pause and elapsed time of a `Clock`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct ClockState {
+    scale: f32,
+    paused: bool,
+    delta: Duration,
+    delta_seconds: f32,
+    elapsed: Duration,
+}
+
+impl Default for ClockState {
+    fn default() -> Self {
+        ClockState {
+            scale: 1.0,
+            paused: false,
+            delta: Duration::default(),
+            delta_seconds: 0.0,
+            elapsed: Duration::default(),
+        }
+    }
+}
+
+/// A temporary scale of the game clock.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct SlowMotion {
+    scale: f32,
+    remaining: Option<Duration>,
+}
 
 /// Frame timing values.
 #[derive(Clone, Copy, Debug, PartialEq)]
@@ -29,6 +85,14 @@ pub struct Time {
     fixed_time_accumulator: f32,
     /// Fixed update interpolation alpha
     interpolation_alpha: f32,
+    /// Maximum number of fixed updates per frame if fixed updates follow the game clock's scale.
+    scaled_fixed_updates: Option<u32>,
+    /// Pauses all clocks but the real clock.
+    paused: bool,
+    /// The state of each `Clock`.
+    clocks: [ClockState; 3],
+    /// Slow motion of the game clock.
+    slow_motion: Option<SlowMotion>,
 }
 
 impl Time {
@@ -103,13 +167,131 @@ impl Time {
         self.interpolation_alpha
     }
 
+    /// Gets the time difference between frames of a clock in seconds.
+    ///
+    /// Systems should read their time with this, from the clock they follow.
+    /// `delta_seconds` is the same as `delta_seconds_for(Clock::Game)`.
+    pub fn delta_seconds_for(&self, clock: Clock) -> f32 {
+        self.clocks[clock.index()].delta_seconds
+    }
+
+    /// Gets the time difference between frames of a clock.
+    pub fn delta_time_for(&self, clock: Clock) -> Duration {
+        self.clocks[clock.index()].delta
+    }
+
+    /// Gets the time a clock has run since the start of the game.
+    pub fn clock_elapsed(&self, clock: Clock) -> Duration {
+        self.clocks[clock.index()].elapsed
+    }
+
+    /// Gets the scale of a clock, the factor of its own speed on top of the global scale.
+    pub fn clock_scale(&self, clock: Clock) -> f32 {
+        self.clocks[clock.index()].scale
+    }
+
+    /// Gets the speed a clock runs at, taking the global scale, pauses and slow motion into
+    /// account.
+    pub fn effective_scale(&self, clock: Clock) -> f32 {
+        let state = &self.clocks[clock.index()];
+        match clock {
+            Clock::Real => 1.0,
+            _ if self.paused || state.paused => 0.0,
+            Clock::Game => self.time_scale * self.slow_motion_scale() * state.scale,
+            Clock::Ui => self.time_scale * state.scale,
+        }
+    }
+
+    /// Sets the scale of a clock, on top of the global scale. The real clock can't be scaled.
+    ///
+    /// ## Panics
+    /// This will panic if the scale is NaN, Infinity, or less than 0.
+    pub fn set_clock_scale(&mut self, clock: Clock, scale: f32) {
+        assert!(scale >= 0.0 && scale.is_finite());
+        if clock != Clock::Real {
+            self.clocks[clock.index()].scale = scale;
+        }
+    }
+
+    /// Returns true if a clock is paused, by itself or by the global pause.
+    pub fn is_clock_paused(&self, clock: Clock) -> bool {
+        clock != Clock::Real && (self.paused || self.clocks[clock.index()].paused)
+    }
+
+    /// Pauses or resumes a clock. The real clock can't be paused.
+    pub fn set_clock_paused(&mut self, clock: Clock, paused: bool) {
+        if clock != Clock::Real {
+            self.clocks[clock.index()].paused = paused;
+        }
+    }
+
+    /// Returns true if all clocks but the real clock are paused.
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Pauses or resumes all clocks but the real clock.
+    ///
+    /// Fixed updates are paused as well while the game clock is paused.
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    /// Slows down, or speeds up, the game clock by `scale` on top of the other scales, for
+    /// `duration` of real time or until `stop_slow_motion` is called.
+    ///
+    /// ## Panics
+    /// This will panic if the scale is NaN, Infinity, or less than 0.
+    pub fn start_slow_motion(&mut self, scale: f32, duration: Option<Duration>) {
+        assert!(scale >= 0.0 && scale.is_finite());
+        self.slow_motion = Some(SlowMotion {
+            scale,
+            remaining: duration,
+        });
+    }
+
+    /// Returns the game clock to normal speed.
+    pub fn stop_slow_motion(&mut self) {
+        self.slow_motion = None;
+    }
+
+    /// Gets the scale of the current slow motion, 1.0 if there is none.
+    pub fn slow_motion_scale(&self) -> f32 {
+        self.slow_motion
+            .map_or(1.0, |slow_motion| slow_motion.scale)
+    }
+
+    /// Advances all clocks by the real time of the frame.
+    fn advance_clocks(&mut self, real: Duration) {
+        let secs = duration_to_secs(real);
+        for clock in &[Clock::Game, Clock::Real, Clock::Ui] {
+            let scale = self.effective_scale(*clock);
+            let state = &mut self.clocks[clock.index()];
+            state.delta_seconds = secs * scale;
+            state.delta = secs_to_duration(secs * scale);
+            state.elapsed += state.delta;
+        }
+        if let Some(SlowMotion {
+            remaining: Some(remaining),
+            ..
+        }) = &mut self.slow_motion
+        {
+            match remaining.checked_sub(real) {
+                Some(left) if left > Duration::default() => *remaining = left,
+                _ => self.slow_motion = None,
+            }
+        }
+    }
+
     /// Sets both `delta_seconds` and `delta_time` based on the seconds given.
     ///
     /// This should only be called by the engine.  Bad things might happen if you call this in
     /// your game.
     pub fn set_delta_seconds(&mut self, secs: f32) {
-        self.delta_seconds = secs * self.time_scale;
-        self.delta_time = secs_to_duration(secs * self.time_scale);
+        self.advance_clocks(secs_to_duration(secs));
+        let game = self.clocks[Clock::Game.index()];
+        self.delta_seconds = game.delta_seconds;
+        self.delta_time = game.delta;
         self.delta_real_seconds = secs;
         self.delta_real_time = secs_to_duration(secs);
 
@@ -122,8 +304,10 @@ impl Time {
     /// This should only be called by the engine.  Bad things might happen if you call this in
     /// your game.
     pub fn set_delta_time(&mut self, time: Duration) {
-        self.delta_seconds = duration_to_secs(time) * self.time_scale;
-        self.delta_time = secs_to_duration(duration_to_secs(time) * self.time_scale);
+        self.advance_clocks(time);
+        let game = self.clocks[Clock::Game.index()];
+        self.delta_seconds = game.delta_seconds;
+        self.delta_time = game.delta;
         self.delta_real_seconds = duration_to_secs(time);
         self.delta_real_time = time;
 
@@ -152,7 +336,8 @@ impl Time {
     }
 
     /// Sets the time multiplier that affects how time values are computed,
-    /// effectively slowing or speeding up your game.
+    /// effectively slowing or speeding up your game. This is the global scale of all clocks but
+    /// the real clock.
     ///
     /// ## Panics
     /// This will panic if multiplier is NaN, Infinity, or less than 0.
@@ -163,12 +348,43 @@ impl Time {
         self.time_scale = multiplier;
     }
 
+    /// Gets the maximum number of fixed updates per frame if fixed updates are scaled, see
+    /// `set_scaled_fixed_updates`.
+    pub fn scaled_fixed_updates(&self) -> Option<u32> {
+        self.scaled_fixed_updates
+    }
+
+    /// Makes fixed updates follow the scale and slow motion of the game clock, running at most
+    /// `max_steps_per_frame` of them each frame, or keeps them at real time with `None`, the
+    /// default.
+    ///
+    /// Scaled fixed updates which don't fit in a frame are dropped, so a high scale can't make
+    /// the game fall further and further behind.
+    pub fn set_scaled_fixed_updates(&mut self, max_steps_per_frame: Option<u32>) {
+        self.scaled_fixed_updates = max_steps_per_frame;
+    }
+
     /// Restarts the internal fixed update accumulator to the desired fixed update delta time.
     ///
+    /// Fixed updates run at real time, unless `set_scaled_fixed_updates` is used, and are paused
+    /// while the game clock is paused.
+    ///
     /// This should only be called by the engine.  Bad things might happen if you call this in
     /// your game.
     pub fn start_fixed_update(&mut self) {
-        self.fixed_time_accumulator += self.delta_real_seconds;
+        if self.is_clock_paused(Clock::Game) {
+            return;
+        }
+        match self.scaled_fixed_updates {
+            None => self.fixed_time_accumulator += self.delta_real_seconds,
+            Some(max_steps) => {
+                self.fixed_time_accumulator +=
+                    self.delta_real_seconds * self.effective_scale(Clock::Game);
+                self.fixed_time_accumulator = self
+                    .fixed_time_accumulator
+                    .min(max_steps as f32 * self.fixed_seconds);
+            }
+        }
     }
 
     /// Checks to see if we should perform another fixed update iteration, and if so, returns true
@@ -206,13 +422,25 @@ impl Default for Time {
             fixed_time_accumulator: 0.0,
             frame_number: 0,
             interpolation_alpha: 0.0,
+            scaled_fixed_updates: None,
             absolute_real_time: Duration::default(),
             absolute_time: Duration::default(),
             time_scale: 1.0,
+            paused: false,
+            clocks: [ClockState::default(); 3],
+            slow_motion: None,
         }
     }
 }
 
+/// The time at which an event was delivered to the game, e.g. by the window event loop.
+///
+/// Written to an `EventChannel<DeliveryTime>` in lockstep with the channel of the events it
+/// stamps, one per event and in the same order, so a reader registered along with the reader of
+/// the events sees the time of each of them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DeliveryTime(pub Instant);
+
 /// A stopwatch which accurately measures elapsed time.
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub enum Stopwatch {
@@ -439,6 +667,126 @@ mod tests {
         }
         assert_eq!(fixed_count, 2);
     }
+
+    #[test]
+    fn clocks_scale_and_pause_independently() {
+        use super::{Clock, Time};
+
+        let mut time = Time::default();
+        time.set_time_scale(2.0);
+        time.set_clock_scale(Clock::Ui, 0.5);
+        time.start_slow_motion(0.25, Some(Duration::from_millis(1500)));
+        time.set_delta_seconds(1.0);
+
+        assert_eq!(time.delta_seconds(), 0.5);
+        assert_eq!(time.delta_seconds_for(Clock::Game), 0.5);
+        assert_eq!(time.delta_seconds_for(Clock::Real), 1.0);
+        assert_eq!(time.delta_seconds_for(Clock::Ui), 1.0);
+
+        // the slow motion ends after 1.5 seconds of real time
+        time.set_delta_seconds(1.0);
+        time.set_delta_seconds(1.0);
+        assert_eq!(time.slow_motion_scale(), 1.0);
+        assert_eq!(time.delta_seconds(), 2.0);
+
+        time.set_clock_paused(Clock::Game, true);
+        time.set_delta_seconds(1.0);
+        assert_eq!(time.delta_seconds(), 0.0);
+        assert_eq!(time.delta_seconds_for(Clock::Ui), 1.0);
+
+        time.set_paused(true);
+        time.set_delta_seconds(1.0);
+        assert_eq!(time.delta_seconds_for(Clock::Ui), 0.0);
+        assert_eq!(time.clock_elapsed(Clock::Real), Duration::from_secs(5));
+    }
+
+    // Fixed updates are paused with the game clock, but not scaled unless asked to
+    #[test]
+    fn fixed_update_follows_game_clock() {
+        use super::{Clock, Time};
+
+        fn fixed_steps(time: &mut Time, frames: usize) -> usize {
+            let mut fixed_count = 0;
+            for _ in 0..frames {
+                time.set_delta_seconds(0.25);
+                time.start_fixed_update();
+                while time.step_fixed_update() {
+                    fixed_count += 1;
+                }
+                time.finish_fixed_update();
+            }
+            fixed_count
+        }
+
+        let mut time = Time::default();
+        time.set_fixed_seconds(0.5);
+        assert_eq!(fixed_steps(&mut time, 8), 4);
+
+        time.set_time_scale(2.0);
+        time.start_slow_motion(0.25, None);
+        assert_eq!(fixed_steps(&mut time, 8), 4);
+        time.stop_slow_motion();
+
+        time.set_clock_paused(Clock::Game, true);
+        assert_eq!(fixed_steps(&mut time, 8), 0);
+        time.set_clock_paused(Clock::Game, false);
+
+        time.set_paused(true);
+        assert_eq!(fixed_steps(&mut time, 8), 0);
+    }
+
+    // Scaled fixed updates follow the game clock, but run a limited number of times per frame
+    #[test]
+    fn scaled_fixed_updates_are_clamped() {
+        use super::{Clock, Time};
+
+        let mut time = Time::default();
+        time.set_fixed_seconds(0.25);
+        time.set_scaled_fixed_updates(Some(4));
+
+        let fixed_steps = |time: &mut Time| {
+            time.set_delta_seconds(0.5);
+            time.start_fixed_update();
+            let mut fixed_count = 0;
+            while time.step_fixed_update() {
+                fixed_count += 1;
+            }
+            time.finish_fixed_update();
+            fixed_count
+        };
+
+        assert_eq!(fixed_steps(&mut time), 2);
+
+        time.start_slow_motion(0.5, None);
+        assert_eq!(fixed_steps(&mut time), 1);
+        time.stop_slow_motion();
+
+        time.set_time_scale(3.0);
+        assert_eq!(fixed_steps(&mut time), 4);
+        assert_eq!(time.interpolation_alpha(), 0.0);
+
+        time.set_clock_paused(Clock::Game, true);
+        assert_eq!(fixed_steps(&mut time), 0);
+    }
+
+    // Systems read the time of the clock they follow
+    #[test]
+    fn delta_seconds_for_clock() {
+        use super::{Clock, Time};
+
+        let mut time = Time::default();
+        time.set_time_scale(0.5);
+        time.set_clock_scale(Clock::Ui, 2.0);
+        time.start_slow_motion(0.5, None);
+        time.set_delta_seconds(1.0);
+
+        assert_eq!(time.delta_seconds_for(Clock::Game), time.delta_seconds());
+        assert_eq!(time.delta_seconds_for(Clock::Game), 0.25);
+        assert_eq!(time.delta_seconds_for(Clock::Real), 1.0);
+        assert_eq!(time.delta_seconds_for(Clock::Ui), 1.0);
+        assert_eq!(time.delta_time_for(Clock::Game), time.delta_time());
+        assert_eq!(time.delta_time_for(Clock::Real), Duration::from_secs(1));
+    }
 }
 
 /// Converts a Duration to the time in seconds.
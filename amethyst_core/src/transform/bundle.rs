@@ -2,9 +2,12 @@
 
 use amethyst_error::Error;
 
-use crate::{ecs::*, transform::*};
+use crate::{ecs::*, transform::*, NameIndex, NameIndexSystem};
 
 /// Transform bundle
+///
+/// This also registers the `NameIndexSystem` and inserts its `NameIndex`, since names are
+/// scoped by the hierarchy.
 #[derive(Default)]
 #[allow(missing_debug_implementations)]
 pub struct TransformBundle;
@@ -13,13 +16,17 @@ impl SystemBundle for TransformBundle {
     fn load(
         &mut self,
         _world: &mut World,
-        _resources: &mut Resources,
+        resources: &mut Resources,
         builder: &mut DispatcherBuilder,
     ) -> Result<(), Error> {
+        resources.insert(NameIndex::default());
         builder
             .add_system(MissingPreviousParentSystem)
             .add_system(ParentUpdateSystem)
-            .add_system(TransformSystem);
+            .add_system(TransformSystem)
+            .add_system(NameIndexSystem)
+            .add_system(TransformInterpolationSystem)
+            .add_fixed_system(TransformSnapshotSystem);
 
         Ok(())
     }
@@ -0,0 +1,76 @@
+//! Render interpolation of transforms simulated at the fixed update rate.
+
+use super::Transform;
+
+/// Interpolates the rendered transform of an entity between its last two fixed updates.
+///
+/// Entities which are moved by fixed systems only change position once or a few times per
+/// frame, which looks jerky when the frame rate isn't a multiple of the fixed rate. With this
+/// component, the `TransformSnapshotSystem` records the local `Transform` after each fixed
+/// update, and the `TransformInterpolationSystem` sets the global matrix to the interpolation
+/// of the last two records by [Time::interpolation_alpha](crate::Time::interpolation_alpha).
+/// The rendered transform lags behind the simulation by up to one fixed update.
+///
+/// Only the entity's own local transform is interpolated, so changes made to it outside of
+/// fixed systems are overridden, and children of the entity follow its simulated transform.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TransformInterpolation {
+    previous: Option<Transform>,
+    current: Option<Transform>,
+}
+
+impl TransformInterpolation {
+    /// Creates an interpolation which starts with the next fixed update.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Record the local transform at the end of a fixed update.
+    pub fn record(&mut self, transform: &Transform) {
+        self.previous = self.current.or_else(|| Some(*transform));
+        self.current = Some(*transform);
+    }
+
+    /// Forget the recorded transforms, e.g. after teleporting the entity.
+    pub fn reset(&mut self) {
+        self.previous = None;
+        self.current = None;
+    }
+
+    /// Get the transform between the last two records, by `alpha` from 0.0 (the previous
+    /// record) to 1.0 (the last record), `None` if nothing was recorded yet.
+    pub fn interpolate(&self, alpha: f32) -> Option<Transform> {
+        let (previous, current) = (self.previous?, self.current?);
+        // exact at rest, blending equal values can round differently
+        if previous == current {
+            return Some(current);
+        }
+        let mut transform = current;
+        *transform.translation_mut() = previous.translation().lerp(current.translation(), alpha);
+        *transform.rotation_mut() = previous.rotation().slerp(current.rotation(), alpha);
+        *transform.scale_mut() = previous.scale().lerp(current.scale(), alpha);
+        Some(transform)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::Vector3;
+
+    #[test]
+    fn interpolates_between_last_two_records() {
+        let mut interpolation = TransformInterpolation::new();
+        assert_eq!(interpolation.interpolate(0.5), None);
+
+        let mut transform = Transform::default();
+        interpolation.record(&transform);
+        transform.set_translation_x(2.);
+        interpolation.record(&transform);
+        transform.set_translation_x(4.);
+        interpolation.record(&transform);
+
+        let interpolated = interpolation.interpolate(0.25).unwrap();
+        assert_eq!(*interpolated.translation(), Vector3::new(2.5, 0., 0.));
+    }
+}
@@ -2,10 +2,12 @@
 
 pub use self::{
     children::Children,
+    interpolation::TransformInterpolation,
     parent::{Parent, PreviousParent},
     transform::{Transform, TransformValues},
 };
 
 mod children;
+mod interpolation;
 mod parent;
 mod transform;
@@ -0,0 +1,265 @@
+//! Helpers to query and edit the hierarchy of [Parent] components.
+//!
+//! These work directly on the `World`, outside of the transform systems. The world transforms
+//! they compute are derived from the local transforms, so they are up to date even before
+//! the `TransformSystem` ran. Iterating descendants follows the [Children] components, which
+//! are only updated by the `ParentUpdateSystem`.
+
+use std::collections::{HashMap, HashSet};
+
+use amethyst_error::Error;
+
+use super::components::*;
+use crate::{
+    ecs::*,
+    math::{Matrix4, Rotation3, Translation3, UnitQuaternion, Vector3, U3},
+};
+
+/// Iterator over the ancestors of an entity, from its parent to the root.
+#[allow(missing_debug_implementations)]
+pub struct Ancestors<'a, S> {
+    world: &'a S,
+    current: Option<Entity>,
+    remaining: usize,
+}
+
+impl<'a, S: EntityStore> Iterator for Ancestors<'a, S> {
+    type Item = Entity;
+
+    fn next(&mut self) -> Option<Entity> {
+        // bounded, in case of a cycle in the hierarchy
+        self.remaining = self.remaining.checked_sub(1)?;
+        let parent = parent(self.world, self.current?);
+        self.current = parent;
+        parent
+    }
+}
+
+/// Iterate over the ancestors of an entity, from its parent to the root.
+pub fn ancestors<S: EntityStore>(world: &S, entity: Entity) -> Ancestors<'_, S> {
+    Ancestors {
+        world,
+        current: Some(entity),
+        remaining: 256,
+    }
+}
+
+/// Iterator over the descendants of an entity, depth first.
+#[allow(missing_debug_implementations)]
+pub struct Descendants<'a, S> {
+    world: &'a S,
+    stack: Vec<Entity>,
+    visited: HashSet<Entity>,
+}
+
+impl<'a, S: EntityStore> Iterator for Descendants<'a, S> {
+    type Item = Entity;
+
+    fn next(&mut self) -> Option<Entity> {
+        let entity = self.stack.pop()?;
+        if let Some(children) = self
+            .world
+            .entry_ref(entity)
+            .ok()
+            .and_then(|entry| entry.into_component::<Children>().ok())
+        {
+            let visited = &mut self.visited;
+            self.stack.extend(
+                children
+                    .0
+                    .iter()
+                    .rev()
+                    .filter(|child| visited.insert(**child)),
+            );
+        }
+        Some(entity)
+    }
+}
+
+/// Iterate over the descendants of an entity depth first, not including the entity itself.
+pub fn descendants<S: EntityStore>(world: &S, entity: Entity) -> Descendants<'_, S> {
+    let mut descendants = Descendants {
+        world,
+        stack: vec![entity],
+        visited: HashSet::new(),
+    };
+    descendants.visited.insert(entity);
+    descendants.next();
+    descendants
+}
+
+fn parent<S: EntityStore>(world: &S, entity: Entity) -> Option<Entity> {
+    world
+        .entry_ref(entity)
+        .ok()
+        .and_then(|entry| entry.into_component::<Parent>().ok().map(|p| p.0))
+}
+
+fn local_matrix<S: EntityStore>(world: &S, entity: Entity) -> Matrix4<f32> {
+    world
+        .entry_ref(entity)
+        .ok()
+        .and_then(|entry| entry.into_component::<Transform>().ok().map(|t| t.matrix()))
+        .unwrap_or_else(Matrix4::identity)
+}
+
+/// Computes the world matrix of an entity from the local transforms of it and its ancestors.
+///
+/// Entities without a `Transform` count as the identity.
+pub fn global_matrix<S: EntityStore>(world: &S, entity: Entity) -> Matrix4<f32> {
+    ancestors(world, entity).fold(local_matrix(world, entity), |matrix, ancestor| {
+        local_matrix(world, ancestor) * matrix
+    })
+}
+
+/// Computes the world transform of an entity from the local transforms of it and its ancestors.
+///
+/// The result is only exact if no ancestor combines a non uniform scale with a rotation, which
+/// can't be represented by a `Transform`.
+pub fn global_transform<S: EntityStore>(world: &S, entity: Entity) -> Transform {
+    let matrix = global_matrix(world, entity);
+    decompose(&matrix)
+}
+
+/// Sets the local `Transform` of an entity so that it ends up with the given world matrix,
+/// adding the component if it's missing. The global matrix of the entity is updated as well,
+/// the ones of its descendants are updated by the next run of the `TransformSystem`.
+pub fn set_global_matrix(world: &mut World, entity: Entity, global: &Matrix4<f32>) {
+    let parent_matrix = parent(world, entity)
+        .map(|parent| global_matrix(world, parent))
+        .unwrap_or_else(Matrix4::identity);
+    let local = parent_matrix
+        .try_inverse()
+        .map_or(*global, |inverse| inverse * global);
+    let mut transform = decompose(&local);
+    transform.parent_matrix = parent_matrix;
+    transform.global_matrix = parent_matrix * transform.matrix();
+
+    if let Some(mut entry) = world.entry(entity) {
+        match entry.get_component_mut::<Transform>() {
+            Ok(existing) => *existing = transform,
+            Err(_) => entry.add_component(transform),
+        }
+    }
+}
+
+/// Sets the local `Transform` of an entity so that it ends up with the given world transform.
+///
+/// See [set_global_matrix].
+pub fn set_global_transform(world: &mut World, entity: Entity, global: &Transform) {
+    set_global_matrix(world, entity, &global.matrix());
+}
+
+/// Changes the parent of an entity, or makes it a root when `parent` is `None`, keeping its
+/// world transform.
+///
+/// Fails without changing anything if the new parent is the entity or one of its descendants.
+pub fn reparent(world: &mut World, entity: Entity, parent: Option<Entity>) -> Result<(), Error> {
+    if let Some(parent) = parent {
+        if parent == entity || ancestors(world, parent).any(|ancestor| ancestor == entity) {
+            return Err(Error::from_string(format!(
+                "Can't reparent {:?} to {:?}, which would create a cycle",
+                entity, parent
+            )));
+        }
+    }
+
+    let global = global_matrix(world, entity);
+    let mut entry = world
+        .entry(entity)
+        .ok_or_else(|| Error::from_string(format!("Can't reparent missing {:?}", entity)))?;
+    match parent {
+        Some(parent) => entry.add_component(Parent(parent)),
+        None => entry.remove_component::<Parent>(),
+    }
+    set_global_matrix(world, entity, &global);
+    Ok(())
+}
+
+/// Removes an entity and all of its descendants from the world at once, and returns them.
+///
+/// The descendants are found by their `Parent` components, so this doesn't depend on the
+/// `Children` components being up to date.
+pub fn despawn_subtree(world: &mut World, entity: Entity) -> Vec<Entity> {
+    let mut children = HashMap::<Entity, Vec<Entity>>::new();
+    for (child, parent) in <(Entity, &Parent)>::query().iter(world) {
+        children.entry(parent.0).or_default().push(*child);
+    }
+
+    let mut removed = Vec::new();
+    let mut visited = HashSet::new();
+    let mut stack = vec![entity];
+    while let Some(entity) = stack.pop() {
+        if !visited.insert(entity) {
+            continue;
+        }
+        if let Some(children) = children.get(&entity) {
+            stack.extend(children);
+        }
+        if world.remove(entity) {
+            removed.push(entity);
+        }
+    }
+
+    // drop the entity from the children of its parent right away
+    for parent_children in <&mut Children>::query().iter_mut(world) {
+        parent_children.0.retain(|child| !visited.contains(child));
+    }
+    removed
+}
+
+/// Splits a matrix into translation, rotation and scale.
+fn decompose(matrix: &Matrix4<f32>) -> Transform {
+    let translation = matrix.column(3).xyz();
+    let mut basis = matrix.fixed_slice::<U3, U3>(0, 0).into_owned();
+    let mut scale = Vector3::new(
+        basis.column(0).norm(),
+        basis.column(1).norm(),
+        basis.column(2).norm(),
+    );
+    if basis.determinant() < 0.0 {
+        scale.x = -scale.x;
+    }
+    for (mut column, scale) in basis.column_iter_mut().zip(scale.iter()) {
+        if *scale != 0.0 {
+            column /= *scale;
+        }
+    }
+    let rotation = UnitQuaternion::from_rotation_matrix(&Rotation3::from_matrix_unchecked(basis));
+    Transform::new(Translation3::from(translation), rotation, scale)
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use super::*;
+
+    #[test]
+    fn reparent_keeps_world_transform() {
+        let mut world = World::default();
+        let mut old_parent = Transform::default();
+        old_parent.set_translation_xyz(1.0, 2.0, 3.0);
+        old_parent.set_rotation_2d(1.0);
+        let old_parent = world.push((old_parent,));
+        let mut new_parent = Transform::default();
+        new_parent.set_translation_xyz(-4.0, 0.0, 1.0);
+        new_parent.set_scale(Vector3::new(2.0, 2.0, 2.0));
+        let new_parent = world.push((new_parent,));
+        let mut child = Transform::default();
+        child.set_translation_xyz(0.5, 0.0, 0.0);
+        let child = world.push((child, Parent(old_parent)));
+        let grandchild = world.push((Transform::default(), Parent(child)));
+
+        let before = global_matrix(&world, child);
+        reparent(&mut world, child, Some(new_parent)).unwrap();
+        assert_relative_eq!(global_matrix(&world, child), before, epsilon = 1e-5);
+        assert_eq!(ancestors(&world, grandchild).last(), Some(new_parent));
+        assert!(reparent(&mut world, new_parent, Some(grandchild)).is_err());
+
+        let removed = despawn_subtree(&mut world, child);
+        assert_eq!(removed.len(), 2);
+        assert!(world.entry(grandchild).is_none());
+        assert!(world.entry(new_parent).is_some());
+    }
+}
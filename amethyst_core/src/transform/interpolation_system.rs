@@ -0,0 +1,115 @@
+//! Systems interpolating the rendered transforms between fixed updates.
+
+use super::components::*;
+use crate::{ecs::*, Time};
+
+/// System recording the local transforms of the entities with a `TransformInterpolation`.
+///
+/// Added to the fixed schedule by the `TransformBundle`, after the fixed systems of the
+/// bundles added before it.
+#[derive(Debug, Default)]
+pub struct TransformSnapshotSystem;
+
+impl System for TransformSnapshotSystem {
+    fn build(self) -> Box<dyn ParallelRunnable> {
+        Box::new(
+            SystemBuilder::new("TransformSnapshotSystem")
+                .with_query(<(&Transform, &mut TransformInterpolation)>::query())
+                .build(move |_commands, world, _resource, query| {
+                    for (transform, interpolation) in query.iter_mut(world) {
+                        interpolation.record(transform);
+                    }
+                }),
+        )
+    }
+}
+
+/// System setting the global matrices of the entities with a `TransformInterpolation` to the
+/// interpolation of their last two fixed updates.
+///
+/// Needs to run after `TransformSystem`. Only the global matrices which change are written, so
+/// the chunks of entities at rest don't show up as changed to the `TransformSystem`.
+#[derive(Debug, Default)]
+pub struct TransformInterpolationSystem;
+
+impl System for TransformInterpolationSystem {
+    fn build(self) -> Box<dyn ParallelRunnable> {
+        Box::new(
+            SystemBuilder::new("TransformInterpolationSystem")
+                .read_resource::<Time>()
+                .with_query(<(Entity, &Transform, &TransformInterpolation)>::query())
+                .write_component::<Transform>()
+                .build(move |_commands, world, time, query| {
+                    let alpha = time.interpolation_alpha();
+                    let updates: Vec<_> = query
+                        .iter(world)
+                        .filter_map(|(entity, transform, interpolation)| {
+                            let interpolated = interpolation.interpolate(alpha)?;
+                            let global_matrix = transform.parent_matrix * interpolated.matrix();
+                            if global_matrix == transform.global_matrix {
+                                None
+                            } else {
+                                Some((*entity, global_matrix))
+                            }
+                        })
+                        .collect();
+                    for (entity, global_matrix) in updates {
+                        if let Some(transform) = world
+                            .entry_mut(entity)
+                            .ok()
+                            .and_then(|entry| entry.into_component_mut::<Transform>().ok())
+                        {
+                            transform.global_matrix = global_matrix;
+                        }
+                    }
+                }),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transform::TransformBundle;
+
+    /// Number of entities whose transform maybe changed since the last frame.
+    struct Changed(usize);
+
+    fn count_changed(builder: &mut DispatcherBuilder) {
+        builder.add_system(|| {
+            SystemBuilder::new("CountChanged")
+                .write_resource::<Changed>()
+                .with_query(<&Transform>::query().filter(maybe_changed::<Transform>()))
+                .build(|_, world, changed, query| changed.0 = query.iter(world).count())
+        });
+    }
+
+    #[test]
+    fn transforms_at_rest_are_not_written() {
+        let mut resources = Resources::default();
+        resources.insert(Time::default());
+        resources.insert(Changed(0));
+        let mut world = World::default();
+        let mut builder = DispatcherBuilder::default();
+        builder.add_bundle(TransformBundle);
+        count_changed(&mut builder);
+        let mut dispatcher = builder.build(&mut world, &mut resources).unwrap();
+
+        let mut transform = Transform::default();
+        transform.set_translation_xyz(1.0, 2.0, 3.0);
+        let mut interpolation = TransformInterpolation::new();
+        interpolation.record(&transform);
+        interpolation.record(&transform);
+        let entity = world.push((transform, interpolation));
+
+        dispatcher.execute(&mut world, &mut resources);
+        assert_eq!(resources.get::<Changed>().unwrap().0, 1);
+        dispatcher.execute(&mut world, &mut resources);
+        dispatcher.execute(&mut world, &mut resources);
+        assert_eq!(resources.get::<Changed>().unwrap().0, 0);
+
+        let entry = world.entry(entity).unwrap();
+        let transform = entry.get_component::<Transform>().unwrap();
+        assert_eq!(*transform.global_matrix(), transform.matrix());
+    }
+}
@@ -1,13 +1,18 @@
 //! `amethyst` transform ecs module
 
 pub use self::{
-    bundle::TransformBundle, components::*,
+    bundle::TransformBundle,
+    components::*,
+    interpolation_system::{TransformInterpolationSystem, TransformSnapshotSystem},
     missing_previous_parent_system::MissingPreviousParentSystem,
-    parent_update_system::ParentUpdateSystem, transform_system::TransformSystem,
+    parent_update_system::ParentUpdateSystem,
+    transform_system::TransformSystem,
 };
 
 pub mod bundle;
 pub mod components;
+pub mod hierarchy;
+pub mod interpolation_system;
 pub mod missing_previous_parent_system;
 pub mod parent_update_system;
 pub mod transform_system;
@@ -1,9 +1,24 @@
 //! System that updates global transform matrices based on hierarchy relations.
 
+use std::collections::HashSet;
+
+use rayon::prelude::*;
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+
 use super::components::*;
-use crate::ecs::*;
+use crate::{ecs::*, math::Matrix4};
+
+/// Global matrices computed for an entity: the entity, its parent matrix and its global matrix.
+type Update = (Entity, Matrix4<f32>, Matrix4<f32>);
 
 /// System that updates global transform matrices based on hierarchy relations.
+///
+/// Only the chunks of entities whose `Transform` or `Parent` changed are looked at, and only
+/// the entities whose global matrix is out of date are updated, along with their descendants
+/// found through `Children`. Entities which don't move cost nothing, and the subtrees of
+/// different roots are computed in parallel. Global matrices which didn't change aren't written,
+/// so their chunks don't show up as changed in the next frame.
 #[derive(Debug)]
 pub struct TransformSystem;
 
@@ -13,73 +28,61 @@ impl System for TransformSystem {
             SystemBuilder::new("TransformSystem")
                 // Entities at the hierarchy root (no parent component)
                 .with_query(
-                    <(Entity, &mut Transform)>::query()
+                    <(Entity, &Transform)>::query()
                         .filter(maybe_changed::<Transform>() & !component::<Parent>()),
                 )
                 // Entities that are children of some entity
                 .with_query(
-                    <(Entity, &mut Transform)>::query()
-                        .filter(maybe_changed::<Transform>() & component::<Parent>()),
+                    <(Entity, &Transform, &Parent)>::query()
+                        .filter(maybe_changed::<Transform>() | maybe_changed::<Parent>()),
                 )
-                .with_query(<(Entity, &Parent)>::query())
+                .read_component::<Parent>()
+                .read_component::<Children>()
                 .write_component::<Transform>()
                 .build(
-                    move |_commands,
-                          world,
-                          _resource,
-                          (query_root, query_children, query_parent)| {
-                        // Update global transform for entities that are root of the hierarchy
-                        for (entity, transform) in query_root.iter_mut(world) {
-                            transform.global_matrix = transform.matrix();
-                            debug_assert!(
-                                transform.is_finite(),
-                                format!(
-                                    "Entity {:?} had a non-finite `Transform` {:?}",
-                                    entity, transform
-                                )
-                            );
+                    move |_commands, world, _resource, (query_root, query_children)| {
+                        #[cfg(feature = "profiler")]
+                        profile_scope!("transform_system");
+
+                        let roots: Vec<Entity> = query_root
+                            .iter(world)
+                            .filter(|(_, transform)| {
+                                transform.global_matrix != transform.matrix()
+                                    || transform.parent_matrix != Matrix4::identity()
+                            })
+                            .map(|(entity, _)| *entity)
+                            .collect();
+                        let mut children: Vec<(Entity, Entity)> = query_children
+                            .iter(world)
+                            .filter(|(_, transform, parent)| {
+                                let parent_matrix = global_matrix(world, parent.0);
+                                transform.global_matrix != parent_matrix * transform.matrix()
+                            })
+                            .map(|(entity, _, parent)| (*entity, parent.0))
+                            .collect();
+
+                        // Update the subtrees of the roots in parallel
+                        let updates: Vec<Vec<Update>> = {
+                            let world: &SubWorld<'_> = world;
+                            roots
+                                .par_iter()
+                                .map(|root| subtree(world, *root, Matrix4::identity()))
+                                .collect()
+                        };
+                        let mut visited = HashSet::new();
+                        for updates in updates {
+                            apply(world, updates, &mut visited);
                         }
 
-                        // Update parent transforms for entities in the hierarchy
-                        // Ideally we would check if the linked parent entity exists before
-                        // accessing it's Transform component, but as the parent_update_system panics
-                        // in both cases it is ok to just unwrap/expect here.
-                        let (left, mut right) = world.split_for_query(query_parent);
-                        for (entity, parent) in query_parent.iter(&left) {
-                            let parent_has_transform = right
-                                .entry_ref(parent.0)
-                                .expect("Invalid entity in Parent component")
-                                .into_component::<Transform>()
-                                .is_ok();
-
-                            if parent_has_transform {
-                                let parent_matrix = right
-                                    .entry_ref(parent.0)
-                                    .expect("Invalid entity in Parent component")
-                                    .into_component::<Transform>()
-                                    .unwrap()
-                                    .global_matrix;
-
-                                if let Some(transform) = right
-                                    .entry_mut(*entity)
-                                    .ok()
-                                    .and_then(|entry| entry.into_component_mut::<Transform>().ok())
-                                {
-                                    transform.parent_matrix = parent_matrix;
-                                }
+                        // Update the remaining subtrees of children, parents first
+                        children.retain(|(entity, _)| !visited.contains(entity));
+                        children.sort_by_cached_key(|(entity, _)| depth(world, *entity));
+                        for (entity, parent) in children {
+                            if visited.contains(&entity) {
+                                continue;
                             }
-                        }
-
-                        // Update global transform for entities that are children of some entity
-                        for (entity, transform) in query_children.iter_mut(world) {
-                            transform.global_matrix = transform.parent_matrix * transform.matrix();
-                            debug_assert!(
-                                transform.is_finite(),
-                                format!(
-                                    "Entity {:?} had a non-finite `Transform` {:?}",
-                                    entity, transform
-                                )
-                            );
+                            let updates = subtree(world, entity, global_matrix(world, parent));
+                            apply(world, updates, &mut visited);
                         }
                     },
                 ),
@@ -87,12 +90,105 @@ impl System for TransformSystem {
     }
 }
 
+/// Get the global matrix of an entity, the identity if it doesn't have a `Transform`.
+fn global_matrix(world: &SubWorld<'_>, entity: Entity) -> Matrix4<f32> {
+    world
+        .entry_ref(entity)
+        .ok()
+        .and_then(|entry| {
+            entry
+                .into_component::<Transform>()
+                .ok()
+                .map(|t| t.global_matrix)
+        })
+        .unwrap_or_else(Matrix4::identity)
+}
+
+/// Number of ancestors of an entity.
+fn depth(world: &SubWorld<'_>, mut entity: Entity) -> usize {
+    // bounded, in case of a cycle in the hierarchy
+    for depth in 0..256 {
+        let parent = world
+            .entry_ref(entity)
+            .ok()
+            .and_then(|entry| entry.into_component::<Parent>().ok().map(|p| p.0));
+        match parent {
+            Some(parent) => entity = parent,
+            None => return depth,
+        }
+    }
+    256
+}
+
+/// Computes the global matrices of an entity and its descendants.
+fn subtree(world: &SubWorld<'_>, entity: Entity, parent_matrix: Matrix4<f32>) -> Vec<Update> {
+    let mut updates = Vec::new();
+    // in case of a cycle in the hierarchy, every entity is only visited once
+    let mut visited = HashSet::new();
+    let mut stack = vec![(entity, parent_matrix)];
+    while let Some((entity, parent_matrix)) = stack.pop() {
+        if !visited.insert(entity) {
+            continue;
+        }
+        let entry = match world.entry_ref(entity) {
+            Ok(entry) => entry,
+            Err(_) => continue,
+        };
+        let transform = match entry.get_component::<Transform>() {
+            Ok(transform) => transform,
+            Err(_) => continue,
+        };
+        let global_matrix = parent_matrix * transform.matrix();
+        debug_assert!(
+            global_matrix.iter().all(|f| f.is_finite()),
+            format!(
+                "Entity {:?} had a non-finite `Transform` {:?}",
+                entity, transform
+            )
+        );
+        if let Ok(children) = entry.get_component::<Children>() {
+            stack.extend(children.0.iter().map(|child| (*child, global_matrix)));
+        }
+        updates.push((entity, parent_matrix, global_matrix));
+    }
+    updates
+}
+
+/// Writes the global matrices which changed.
+fn apply(world: &mut SubWorld<'_>, updates: Vec<Update>, visited: &mut HashSet<Entity>) {
+    for (entity, parent_matrix, global_matrix) in updates {
+        visited.insert(entity);
+        let unchanged = world
+            .entry_ref(entity)
+            .ok()
+            .and_then(|entry| {
+                entry.into_component::<Transform>().ok().map(|transform| {
+                    transform.global_matrix == global_matrix
+                        && transform.parent_matrix == parent_matrix
+                })
+            })
+            .unwrap_or(true);
+        if unchanged {
+            continue;
+        }
+        if let Some(transform) = world
+            .entry_mut(entity)
+            .ok()
+            .and_then(|entry| entry.into_component_mut::<Transform>().ok())
+        {
+            transform.parent_matrix = parent_matrix;
+            transform.global_matrix = global_matrix;
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::{
         ecs::*,
         math::{Matrix4, Quaternion, Unit, Vector3},
-        transform::{Parent, Transform, TransformBundle},
+        transform::{Children, Parent, Transform, TransformBundle},
+        Time,
     };
 
     // If this works, then all other tests should work.
@@ -112,6 +208,7 @@ mod tests {
 
     fn transform_world() -> (Resources, World, Dispatcher) {
         let mut resources = Resources::default();
+        resources.insert(Time::default());
         let mut world = World::default();
 
         let dispatcher = DispatcherBuilder::default()
@@ -301,6 +398,51 @@ mod tests {
         };
     }
 
+    // Moving the root of a hierarchy updates all of its descendants, which didn't move themselves
+    #[test]
+    fn moved_root_updates_descendants() {
+        let (mut res, mut world, mut dispatcher) = transform_world();
+
+        let e1 = world.push((Transform::default(),));
+        let e2 = world.push((Transform::default(), Parent(e1)));
+        let mut local3 = Transform::default();
+        local3.set_translation_xyz(0.0, 1.0, 0.0);
+        let e3 = world.push((local3, Parent(e2)));
+
+        dispatcher.execute(&mut world, &mut res);
+        dispatcher.execute(&mut world, &mut res);
+
+        let mut entry = world.entry(e1).unwrap();
+        entry
+            .get_component_mut::<Transform>()
+            .unwrap()
+            .set_translation_xyz(2.0, 0.0, 0.0);
+        dispatcher.execute(&mut world, &mut res);
+
+        let e3_transform = *world
+            .entry(e3)
+            .unwrap()
+            .into_component::<Transform>()
+            .unwrap();
+        let expected = Matrix4::new_translation(&Vector3::new(2.0, 1.0, 0.0));
+        assert_eq!(*e3_transform.global_matrix(), expected);
+    }
+
+    #[test]
+    fn cyclic_hierarchy_does_not_hang() {
+        let (mut res, mut world, mut dispatcher) = transform_world();
+
+        let mut transform = Transform::default();
+        transform.set_translation_xyz(1.0, 0.0, 0.0);
+        let e1 = world.push((transform,));
+        let e2 = world.push((Transform::default(), Parent(e1), Children::with(&[e1])));
+        let mut e1_entry = world.entry(e1).unwrap();
+        e1_entry.add_component(Parent(e2));
+        e1_entry.add_component(Children::with(&[e2]));
+
+        dispatcher.execute(&mut world, &mut res);
+    }
+
     #[test]
     #[should_panic]
     #[cfg(debug_assertions)]
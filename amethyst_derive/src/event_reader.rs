@@ -98,10 +98,13 @@ pub enum SomeEvent {{
         {
             type Event = #event_name #type_generics;
 
+            // the event may have no variants, e.g. with its features disabled
+            #[allow(unused_variables)]
             fn read(&mut self, resources: &mut Resources, events: &mut Vec<#event_name #type_generics>) {
                 #(#reads)*
             }
 
+            #[allow(unused_variables)]
             fn setup(&mut self, resources: &mut Resources) {
                 #(#setups)*
             }
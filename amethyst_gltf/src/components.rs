@@ -0,0 +1,393 @@
+//! Components of the prefabs of glTF scenes.
+//!
+//! The prefabs hold the components of this crate, which are spawned as the components of the
+//! engine they stand for, such as `Handle<Mesh>` for `GltfMesh`.
+
+use amethyst_animation::{Animation, AnimationHierarchy, AnimationSet, Joint, Skin};
+use amethyst_assets::{
+    prefab::{legion_prefab, register_component_type, serde_diff, SerdeDiff},
+    register_spawn_mapping, Handle,
+};
+use amethyst_core::{
+    ecs::Entity,
+    math::{Matrix4, Point3},
+    transform::Transform,
+    Named,
+};
+use amethyst_rendy::{
+    camera::Camera,
+    light::{Light, PointLight},
+    morph::{MorphTargets, MorphWeights},
+    rendy::mesh::MeshBuilder,
+    skinning::JointTransforms,
+    transparent::Transparent,
+    types::Mesh,
+    visibility::BoundingSphere,
+    Material,
+};
+use serde::{Deserialize, Serialize};
+use type_uuid::TypeUuid;
+
+/// Name of a node, spawned as `Named`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, SerdeDiff, TypeUuid)]
+#[uuid = "e3ca2e99-da2a-44ae-8649-b5c6338fdaf1"]
+pub struct GltfName(pub String);
+
+register_component_type!(GltfName);
+register_spawn_mapping!(GltfName => Named);
+
+impl From<GltfName> for Named {
+    fn from(name: GltfName) -> Self {
+        Named::new(name.0)
+    }
+}
+
+/// Mesh of a primitive, spawned as `Handle<Mesh>`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, SerdeDiff, TypeUuid)]
+#[uuid = "45b123c3-0956-4ca2-87fe-b856a032cc84"]
+pub struct GltfMesh(#[serde_diff(opaque)] pub Handle<Mesh>);
+
+impl Default for GltfMesh {
+    fn default() -> Self {
+        unimplemented!()
+    }
+}
+
+register_component_type!(GltfMesh);
+register_spawn_mapping!(GltfMesh => Handle<Mesh>);
+
+impl From<GltfMesh> for Handle<Mesh> {
+    fn from(mesh: GltfMesh) -> Self {
+        mesh.0
+    }
+}
+
+/// Material of a primitive, spawned as `Handle<Material>`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, SerdeDiff, TypeUuid)]
+#[uuid = "bf56ffbc-28d3-46cc-b21e-a5975a6eebcf"]
+pub struct GltfMaterial(#[serde_diff(opaque)] pub Handle<Material>);
+
+impl Default for GltfMaterial {
+    fn default() -> Self {
+        unimplemented!()
+    }
+}
+
+register_component_type!(GltfMaterial);
+register_spawn_mapping!(GltfMaterial => Handle<Material>);
+
+impl From<GltfMaterial> for Handle<Material> {
+    fn from(material: GltfMaterial) -> Self {
+        material.0
+    }
+}
+
+/// Marks the primitives with a blended material, spawned as `Transparent`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, SerdeDiff, TypeUuid)]
+#[uuid = "20fbef95-049d-4e0b-b9e4-fa64247b2bbd"]
+pub struct GltfTransparent;
+
+register_component_type!(GltfTransparent);
+register_spawn_mapping!(GltfTransparent => Transparent);
+
+impl From<GltfTransparent> for Transparent {
+    fn from(_: GltfTransparent) -> Self {
+        Transparent
+    }
+}
+
+/// Projection of a `GltfCamera`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum GltfProjection {
+    /// An orthographic projection
+    Orthographic {
+        /// Left of the view volume
+        left: f32,
+        /// Right of the view volume
+        right: f32,
+        /// Bottom of the view volume
+        bottom: f32,
+        /// Top of the view volume
+        top: f32,
+        /// Distance to the near plane
+        znear: f32,
+        /// Distance to the far plane
+        zfar: f32,
+    },
+    /// A perspective projection
+    Perspective {
+        /// Aspect ratio of the view
+        aspect: f32,
+        /// Vertical field of view, in radians
+        fovy: f32,
+        /// Distance to the near plane
+        znear: f32,
+    },
+}
+
+/// Camera of a node, spawned as `Camera`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, SerdeDiff, TypeUuid)]
+#[uuid = "1f13a5d1-daee-4c30-a40a-1d22f7e08f26"]
+pub struct GltfCamera(#[serde_diff(opaque)] pub GltfProjection);
+
+impl Default for GltfCamera {
+    fn default() -> Self {
+        GltfCamera(GltfProjection::Perspective {
+            aspect: 1.0,
+            fovy: std::f32::consts::FRAC_PI_3,
+            znear: 0.125,
+        })
+    }
+}
+
+register_component_type!(GltfCamera);
+register_spawn_mapping!(GltfCamera => Camera);
+
+impl From<GltfCamera> for Camera {
+    fn from(camera: GltfCamera) -> Self {
+        match camera.0 {
+            GltfProjection::Orthographic {
+                left,
+                right,
+                bottom,
+                top,
+                znear,
+                zfar,
+            } => Camera::orthographic(left, right, bottom, top, znear, zfar),
+            GltfProjection::Perspective {
+                aspect,
+                fovy,
+                znear,
+            } => Camera::perspective(aspect, fovy, znear),
+        }
+    }
+}
+
+/// Light of a node, spawned as `Light`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, SerdeDiff, TypeUuid)]
+#[uuid = "1d6c143d-4f82-4067-b91e-2512051af9bc"]
+pub struct GltfLight(#[serde_diff(opaque)] pub Light);
+
+impl Default for GltfLight {
+    fn default() -> Self {
+        GltfLight(PointLight::default().into())
+    }
+}
+
+register_component_type!(GltfLight);
+register_spawn_mapping!(GltfLight => Light);
+
+impl From<GltfLight> for Light {
+    fn from(light: GltfLight) -> Self {
+        light.0
+    }
+}
+
+/// Bounds of a node and its children, spawned as `BoundingSphere`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, SerdeDiff, TypeUuid)]
+#[uuid = "69814892-db85-498d-9751-620617501f65"]
+pub struct GltfBounds {
+    /// Center of the bounding sphere
+    pub center: [f32; 3],
+    /// Radius of the bounding sphere
+    pub radius: f32,
+}
+
+register_component_type!(GltfBounds);
+register_spawn_mapping!(GltfBounds => BoundingSphere);
+
+impl From<GltfBounds> for BoundingSphere {
+    fn from(bounds: GltfBounds) -> Self {
+        BoundingSphere::new(Point3::from(bounds.center), bounds.radius)
+    }
+}
+
+/// Morph targets of a primitive, spawned as `MorphTargets`.
+#[derive(Debug, Clone, Serialize, Deserialize, SerdeDiff, TypeUuid)]
+#[uuid = "5a6b8081-ddeb-4854-85d9-97fe3a262e89"]
+pub struct GltfMorphTargets(#[serde_diff(skip)] pub MorphTargets);
+
+impl Default for GltfMorphTargets {
+    fn default() -> Self {
+        GltfMorphTargets(MorphTargets {
+            positions: Vec::new(),
+            normals: None,
+            tangents: None,
+            attributes: MeshBuilder::new(),
+            targets: Vec::new(),
+        })
+    }
+}
+
+register_component_type!(GltfMorphTargets);
+register_spawn_mapping!(GltfMorphTargets => MorphTargets);
+
+impl From<GltfMorphTargets> for MorphTargets {
+    fn from(targets: GltfMorphTargets) -> Self {
+        targets.0
+    }
+}
+
+/// Initial weights of the morph targets of a primitive, spawned as `MorphWeights`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, SerdeDiff, TypeUuid)]
+#[uuid = "08a2268c-a489-40d4-a737-3e775325cc92"]
+pub struct GltfMorphWeights(pub Vec<f32>);
+
+register_component_type!(GltfMorphWeights);
+register_spawn_mapping!(GltfMorphWeights => MorphWeights);
+
+impl From<GltfMorphWeights> for MorphWeights {
+    fn from(weights: GltfMorphWeights) -> Self {
+        MorphWeights::from(weights.0)
+    }
+}
+
+/// Skin of a node, spawned as `Skin`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, SerdeDiff, TypeUuid)]
+#[uuid = "e24d184f-8015-4916-b57a-e4439c9213f8"]
+pub struct GltfSkin {
+    /// Joint entities of the skin
+    #[serde_diff(opaque)]
+    pub joints: Vec<Entity>,
+    /// Mesh entities using the skin
+    #[serde_diff(opaque)]
+    pub meshes: Vec<Entity>,
+    /// Inverse bind matrix of every joint
+    pub inverse_bind_matrices: Vec<[[f32; 4]; 4]>,
+}
+
+register_component_type!(GltfSkin);
+register_spawn_mapping!(GltfSkin => Skin);
+
+impl From<GltfSkin> for Skin {
+    fn from(skin: GltfSkin) -> Self {
+        Skin {
+            joint_matrices: Vec::with_capacity(skin.joints.len()),
+            joints: skin.joints,
+            meshes: skin.meshes,
+            bind_shape_matrix: Matrix4::identity(),
+            inverse_bind_matrices: skin
+                .inverse_bind_matrices
+                .into_iter()
+                .map(Matrix4::from)
+                .collect(),
+        }
+    }
+}
+
+/// Joint of one or more skins, spawned as `Joint`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, SerdeDiff, TypeUuid)]
+#[uuid = "82077cdc-bc45-439b-9177-88c8674aeab0"]
+pub struct GltfJoint {
+    /// Skin entities the joint is part of
+    #[serde_diff(opaque)]
+    pub skins: Vec<Entity>,
+}
+
+register_component_type!(GltfJoint);
+register_spawn_mapping!(GltfJoint => Joint);
+
+impl From<GltfJoint> for Joint {
+    fn from(joint: GltfJoint) -> Self {
+        Joint { skins: joint.skins }
+    }
+}
+
+/// Skinned primitive, spawned as the `JointTransforms` of its skin.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, SerdeDiff, TypeUuid)]
+#[uuid = "147b2d54-827b-489f-b2d8-3ae501338e35"]
+pub struct GltfSkinnedMesh {
+    /// Skin entity
+    #[serde_diff(opaque)]
+    pub skin: Entity,
+    /// Number of joints of the skin
+    pub joints: usize,
+}
+
+impl Default for GltfSkinnedMesh {
+    fn default() -> Self {
+        unimplemented!()
+    }
+}
+
+register_component_type!(GltfSkinnedMesh);
+register_spawn_mapping!(GltfSkinnedMesh => JointTransforms);
+
+impl From<GltfSkinnedMesh> for JointTransforms {
+    fn from(mesh: GltfSkinnedMesh) -> Self {
+        JointTransforms {
+            skin: mesh.skin,
+            matrices: vec![Matrix4::identity(); mesh.joints],
+        }
+    }
+}
+
+/// Nodes animated by the animations of the root, by glTF node index, spawned as
+/// `AnimationHierarchy<Transform>`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, SerdeDiff, TypeUuid)]
+#[uuid = "86c9069b-edcc-462c-abea-b06b29c4d495"]
+pub struct GltfAnimationHierarchy(#[serde_diff(opaque)] pub Vec<(usize, Entity)>);
+
+register_component_type!(GltfAnimationHierarchy);
+register_spawn_mapping!(GltfAnimationHierarchy => AnimationHierarchy<Transform>);
+
+impl From<GltfAnimationHierarchy> for AnimationHierarchy<Transform> {
+    fn from(hierarchy: GltfAnimationHierarchy) -> Self {
+        AnimationHierarchy::new_many(hierarchy.0.into_iter().collect())
+    }
+}
+
+/// Animation clips of the root, by glTF animation index, spawned as
+/// `AnimationSet<usize, Transform>`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, SerdeDiff, TypeUuid)]
+#[uuid = "6dad0739-5275-477c-ad42-a1fcb99f8769"]
+pub struct GltfAnimations(#[serde_diff(opaque)] pub Vec<(usize, Handle<Animation<Transform>>)>);
+
+register_component_type!(GltfAnimations);
+register_spawn_mapping!(GltfAnimations => AnimationSet<usize, Transform>);
+
+impl From<GltfAnimations> for AnimationSet<usize, Transform> {
+    fn from(animations: GltfAnimations) -> Self {
+        let mut set = AnimationSet::new();
+        for (index, animation) in animations.0 {
+            set.insert(index, animation);
+        }
+        set
+    }
+}
+
+/// Primitives whose morph target weights are animated by the animations of the root, by glTF
+/// node index, spawned as `AnimationHierarchy<MorphWeights>`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, SerdeDiff, TypeUuid)]
+#[uuid = "a381a5c5-c9c3-42a9-a73f-80a2c95032d1"]
+pub struct GltfMorphHierarchy(#[serde_diff(opaque)] pub Vec<(usize, Entity)>);
+
+register_component_type!(GltfMorphHierarchy);
+register_spawn_mapping!(GltfMorphHierarchy => AnimationHierarchy<MorphWeights>);
+
+impl From<GltfMorphHierarchy> for AnimationHierarchy<MorphWeights> {
+    fn from(hierarchy: GltfMorphHierarchy) -> Self {
+        AnimationHierarchy::new_many(hierarchy.0.into_iter().collect())
+    }
+}
+
+/// Morph target weight animation clips of the root, by glTF animation index, spawned as
+/// `AnimationSet<usize, MorphWeights>`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, SerdeDiff, TypeUuid)]
+#[uuid = "0aa66308-4502-4342-9c08-a9e1fa6009e1"]
+pub struct GltfMorphAnimations(
+    #[serde_diff(opaque)] pub Vec<(usize, Handle<Animation<MorphWeights>>)>,
+);
+
+register_component_type!(GltfMorphAnimations);
+register_spawn_mapping!(GltfMorphAnimations => AnimationSet<usize, MorphWeights>);
+
+impl From<GltfMorphAnimations> for AnimationSet<usize, MorphWeights> {
+    fn from(animations: GltfMorphAnimations) -> Self {
+        let mut set = AnimationSet::new();
+        for (index, animation) in animations.0 {
+            set.insert(index, animation);
+        }
+        set
+    }
+}
@@ -33,4 +33,27 @@ pub enum Error {
     /// A loaded glTF buffer is not of the required length.
     #[error(display = "Loaded buffer does not match required length")]
     BufferLength(gltf::json::Path),
+
+    /// No scene or node has the name given in the options.
+    #[error(display = "Gltf has no scene or node named {}", _0)]
+    MissingName(String),
+
+    /// A joint of a loaded skin is not part of the loaded nodes.
+    #[error(display = "Skin joint {} is not part of the loaded nodes", _0)]
+    MissingJoint(usize),
+
+    /// A compressed glTF buffer view could not be decoded.
+    #[error(display = "Failed to decode compressed buffer view {}", _0)]
+    InvalidCompression(usize),
+
+    /// A buffer or image is stored in another file, which the importer can't read.
+    #[error(
+        display = "External file {} is not supported, embed it or export a .glb instead",
+        _0
+    )]
+    ExternalReference(String),
+
+    /// A glTF extension required by the file is not supported.
+    #[error(display = "Required extension is not supported: {}", _0)]
+    UnsupportedExtension(&'static str),
 }
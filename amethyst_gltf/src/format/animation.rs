@@ -1,47 +1,173 @@
 use std::collections::HashMap;
 
 use amethyst_animation::{
-    AnimationPrefab, AnimationSetPrefab, InterpolationFunction, InterpolationPrimitive, Sampler,
-    SamplerPrimitive, TransformChannel,
+    Animation, AnimationSampling, InterpolationFunction, InterpolationPrimitive, MorphChannel,
+    Sampler, SamplerPrimitive, TransformChannel,
 };
+use amethyst_assets::{distill_importer::SerdeObj, Handle};
 use amethyst_core::{
+    ecs::Entity,
     math::{convert, Vector3, Vector4},
     Transform,
 };
 use amethyst_error::Error;
+use amethyst_rendy::morph::MorphWeights;
+use gltf::animation::Property;
 
-use super::Buffers;
-use crate::error;
+use super::{assets::ImportedAssets, Buffers};
+use crate::{error, GltfAnimationNames};
+
+type Samplers<T> = Vec<(usize, T, Sampler<SamplerPrimitive<f32>>)>;
 
 pub fn load_animations(
     gltf: &gltf::Gltf,
     buffers: &Buffers,
-    node_map: &HashMap<usize, usize>,
-) -> Result<AnimationSetPrefab<usize, Transform>, Error> {
-    let mut prefab = AnimationSetPrefab::default();
+    node_map: &HashMap<usize, Entity>,
+    assets: &mut ImportedAssets<'_>,
+) -> Result<Vec<(usize, Handle<Animation<Transform>>)>, Error> {
+    let mut animations = Vec::new();
     for animation in gltf.animations() {
-        let anim = load_animation(&animation, buffers)?;
-        if anim
-            .samplers
+        let samplers = load_animation(&animation, buffers)?;
+        if samplers
             .iter()
             .any(|sampler| node_map.contains_key(&sampler.0))
         {
-            prefab.animations.push((animation.index(), anim));
+            let key = format!("animation/{}", animation.index());
+            let handle = add_animation(assets, key, samplers, |node| vec![node])?;
+            animations.push((animation.index(), handle));
+        }
+    }
+    Ok(animations)
+}
+
+// Adds the samplers of an animation as assets, and the animation applying them to the hierarchy
+// indices of their node
+fn add_animation<T, F>(
+    assets: &mut ImportedAssets<'_>,
+    key: String,
+    samplers: Samplers<T::Channel>,
+    targets: F,
+) -> Result<Handle<Animation<T>>, Error>
+where
+    T: AnimationSampling<Primitive = SamplerPrimitive<f32>>,
+    Animation<T>: SerdeObj,
+    F: Fn(usize) -> Vec<usize>,
+{
+    let mut nodes = Vec::with_capacity(samplers.len());
+    for (index, (node_index, channel, sampler)) in samplers.into_iter().enumerate() {
+        let handle = assets.get_or_insert(format!("{}/sampler/{}", key, index), |_| Ok(sampler))?;
+        for target in targets(node_index) {
+            nodes.push((target, channel.clone(), handle.clone()));
+        }
+    }
+    assets.get_or_insert(key, |_| {
+        Ok(Animation {
+            nodes,
+            events: Vec::new(),
+        })
+    })
+}
+
+/// Map the names of the animations to their ids in the animation sets.
+pub fn load_animation_names(gltf: &gltf::Gltf) -> GltfAnimationNames {
+    let mut names = GltfAnimationNames::default();
+    for animation in gltf.animations() {
+        if let Some(name) = animation.name() {
+            names.names.insert(name.to_string(), animation.index());
         }
     }
-    Ok(prefab)
+    names
 }
 
 fn load_animation(
     animation: &gltf::Animation<'_>,
     buffers: &Buffers,
-) -> Result<AnimationPrefab<Transform>, Error> {
-    let mut a = AnimationPrefab::default();
-    a.samplers = animation
+) -> Result<Samplers<TransformChannel>, Error> {
+    animation
         .channels()
+        .filter(|channel| channel.target().property() != Property::MorphTargetWeights)
         .map(|ref channel| load_channel(channel, buffers))
-        .collect::<Result<Vec<_>, Error>>()?;
-    Ok(a)
+        .collect()
+}
+
+/// Loads the morph target weight animations, for the morph hierarchy indices of the primitives
+/// of every node.
+pub fn load_morph_animations(
+    gltf: &gltf::Gltf,
+    buffers: &Buffers,
+    morph_indices: &HashMap<usize, Vec<usize>>,
+    assets: &mut ImportedAssets<'_>,
+) -> Result<Vec<(usize, Handle<Animation<MorphWeights>>)>, Error> {
+    let mut animations = Vec::new();
+    for animation in gltf.animations() {
+        let mut samplers = Vec::new();
+        for channel in animation
+            .channels()
+            .filter(|channel| channel.target().property() == Property::MorphTargetWeights)
+        {
+            samplers.extend(load_morph_channel(&channel, buffers)?);
+        }
+        if samplers
+            .iter()
+            .any(|sampler| morph_indices.contains_key(&sampler.0))
+        {
+            let key = format!("morph_animation/{}", animation.index());
+            let handle = add_animation(assets, key, samplers, |node| {
+                morph_indices.get(&node).cloned().unwrap_or_default()
+            })?;
+            animations.push((animation.index(), handle));
+        }
+    }
+    Ok(animations)
+}
+
+/// Split the weights of all morph targets of a channel into one sampler per target.
+fn load_morph_channel(
+    channel: &gltf::animation::Channel<'_>,
+    buffers: &Buffers,
+) -> Result<Samplers<MorphChannel>, Error> {
+    use gltf::animation::util::ReadOutputs::*;
+    let sampler = channel.sampler();
+    let node_index = channel.target().node().index();
+
+    let reader = channel.reader(|buffer| buffers.buffer(&buffer));
+    let input = reader
+        .read_inputs()
+        .ok_or(error::Error::MissingInputs)?
+        .collect::<Vec<_>>();
+    let weights = match reader.read_outputs().ok_or(error::Error::MissingOutputs)? {
+        MorphTargetWeights(weights) => weights.into_f32().collect::<Vec<_>>(),
+        _ => return Err(error::Error::MissingOutputs.into()),
+    };
+
+    // cubic splines have an in tangent, a value and an out tangent for every keyframe
+    let function = map_interpolation_type(sampler.interpolation());
+    let values_per_key = if function == InterpolationFunction::CubicSpline {
+        3
+    } else {
+        1
+    };
+    let targets = match input.len() * values_per_key {
+        0 => 0,
+        values => weights.len() / values,
+    };
+
+    Ok((0..targets)
+        .map(|target| {
+            (
+                node_index,
+                MorphChannel::Weight(target),
+                Sampler {
+                    input: input.clone(),
+                    function: function.clone(),
+                    output: weights
+                        .chunks(targets)
+                        .map(|values| SamplerPrimitive::Scalar(values[target]))
+                        .collect(),
+                },
+            )
+        })
+        .collect())
 }
 
 fn load_channel(
@@ -60,18 +186,20 @@ fn load_channel(
     let node_index = target.node().index();
 
     match reader.read_outputs().ok_or(error::Error::MissingOutputs)? {
-        Translations(translations) => Ok((
-            node_index,
-            TransformChannel::Translation,
-            Sampler {
-                input,
-                function: map_interpolation_type(sampler.interpolation()),
-                output: translations
-                    .map(Vector3::from)
-                    .map(|t| convert::<_, Vector3<f32>>(t).into())
-                    .collect(),
-            },
-        )),
+        Translations(translations) => {
+            Ok((
+                node_index,
+                TransformChannel::Translation,
+                Sampler {
+                    input,
+                    function: map_interpolation_type(sampler.interpolation()),
+                    output: translations
+                        .map(Vector3::from)
+                        .map(|t| convert::<_, Vector3<f32>>(t).into())
+                        .collect(),
+                },
+            ))
+        }
         Rotations(rotations) => {
             let ty = map_interpolation_type(sampler.interpolation());
             let ty = if ty == InterpolationFunction::Linear {
@@ -93,19 +221,21 @@ fn load_channel(
                 },
             ))
         }
-        Scales(scales) => Ok((
-            node_index,
-            TransformChannel::Scale,
-            Sampler {
-                input,
-                function: map_interpolation_type(sampler.interpolation()),
-                output: scales
-                    .map(Vector3::from)
-                    .map(|s| convert::<_, Vector3<f32>>(s).into())
-                    .collect(),
-            },
-        )),
-        MorphTargetWeights(_) => Err(error::Error::NotImplemented.into()),
+        Scales(scales) => {
+            Ok((
+                node_index,
+                TransformChannel::Scale,
+                Sampler {
+                    input,
+                    function: map_interpolation_type(sampler.interpolation()),
+                    output: scales
+                        .map(Vector3::from)
+                        .map(|s| convert::<_, Vector3<f32>>(s).into())
+                        .collect(),
+                },
+            ))
+        }
+        MorphTargetWeights(_) => unreachable!("Morph target weights are loaded separately"),
     }
 }
 
@@ -121,3 +251,64 @@ where
         CubicSpline => InterpolationFunction::CubicSpline,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use amethyst_animation::{MorphChannel, SamplerPrimitive};
+
+    use super::{super::import, load_morph_channel};
+
+    // two keyframes of the weights of two morph targets, [0, 1] then [1, 0]
+    const WEIGHTS: &str = r#"{
+        "asset": { "version": "2.0" },
+        "buffers": [{
+            "byteLength": 24,
+            "uri": "data:application/octet-stream;base64,AAAAAAAAgD8AAAAAAACAPwAAgD8AAAAA"
+        }],
+        "bufferViews": [
+            { "buffer": 0, "byteOffset": 0, "byteLength": 8 },
+            { "buffer": 0, "byteOffset": 8, "byteLength": 16 }
+        ],
+        "accessors": [
+            {
+                "bufferView": 0, "componentType": 5126, "count": 2, "type": "SCALAR",
+                "min": [0.0], "max": [1.0]
+            },
+            { "bufferView": 1, "componentType": 5126, "count": 4, "type": "SCALAR" }
+        ],
+        "nodes": [{}],
+        "animations": [{
+            "samplers": [{ "input": 0, "output": 1 }],
+            "channels": [{ "sampler": 0, "target": { "node": 0, "path": "weights" } }]
+        }]
+    }"#;
+
+    fn scalars(output: &[SamplerPrimitive<f32>]) -> Vec<f32> {
+        output
+            .iter()
+            .map(|value| {
+                match value {
+                    SamplerPrimitive::Scalar(value) => *value,
+                    value => panic!("Expected a scalar, got {:?}", value),
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn morph_channels_are_split_by_target() {
+        let (gltf, buffers) = import(WEIGHTS.as_bytes()).unwrap();
+        let animation = gltf.animations().next().unwrap();
+        let channel = animation.channels().next().unwrap();
+
+        let samplers = load_morph_channel(&channel, &buffers).unwrap();
+
+        assert_eq!(samplers.len(), 2);
+        for (target, expected) in [vec![0.0, 1.0], vec![1.0, 0.0]].iter().enumerate() {
+            let (node, channel, sampler) = &samplers[target];
+            assert_eq!((*node, *channel), (0, MorphChannel::Weight(target)));
+            assert_eq!(sampler.input, vec![0.0, 1.0]);
+            assert_eq!(&scalars(&sampler.output), expected);
+        }
+    }
+}
@@ -0,0 +1,83 @@
+//! The assets imported from a glTF file next to its prefab, such as its meshes and textures.
+
+use std::collections::HashMap;
+
+use amethyst_assets::{
+    distill_importer::{ImportedAsset, SerdeObj},
+    make_handle, Asset, AssetUuid, Handle,
+};
+use amethyst_error::Error;
+use serde::{Deserialize, Serialize};
+use type_uuid::TypeUuid;
+
+/// The ids of the assets imported from a glTF file, kept between imports so the handles to them
+/// stay valid when the file changes.
+#[derive(Debug, Default, Serialize, Deserialize, TypeUuid)]
+#[uuid = "1df0998c-c873-4d56-b3ae-b4fad13c0b6c"]
+pub struct GltfImporterState {
+    ids: HashMap<String, AssetUuid>,
+}
+
+/// The assets of an import, by a key naming the part of the file they are imported from.
+pub struct ImportedAssets<'a> {
+    state: &'a mut GltfImporterState,
+    imported: HashMap<String, AssetUuid>,
+    assets: Vec<ImportedAsset>,
+}
+
+impl<'a> ImportedAssets<'a> {
+    pub fn new(state: &'a mut GltfImporterState) -> Self {
+        ImportedAssets {
+            state,
+            imported: HashMap::new(),
+            assets: Vec::new(),
+        }
+    }
+
+    /// The id of the asset with the given key, the one it had in the previous imports. The asset
+    /// must be added with `insert` before the import finishes, or its id is forgotten.
+    pub fn id(&mut self, key: String) -> AssetUuid {
+        let id = *self
+            .state
+            .ids
+            .entry(key.clone())
+            .or_insert_with(|| AssetUuid(*uuid::Uuid::new_v4().as_bytes()));
+        self.imported.insert(key, id);
+        id
+    }
+
+    /// Adds an asset, with the id it had in the previous imports.
+    pub fn insert<T: SerdeObj>(&mut self, key: String, data: T) -> AssetUuid {
+        let id = self.id(key);
+        self.assets.push(ImportedAsset {
+            id,
+            search_tags: Vec::new(),
+            build_deps: Vec::new(),
+            load_deps: Vec::new(),
+            asset_data: Box::new(data),
+            build_pipeline: None,
+        });
+        id
+    }
+
+    /// A handle to the asset with the given key, created by `data` if it isn't imported yet.
+    pub fn get_or_insert<A, F>(&mut self, key: String, data: F) -> Result<Handle<A>, Error>
+    where
+        A: Asset,
+        A::Data: SerdeObj,
+        F: FnOnce(&mut Self) -> Result<A::Data, Error>,
+    {
+        if let Some(id) = self.imported.get(&key) {
+            return Ok(make_handle(*id));
+        }
+        let data = data(self)?;
+        Ok(make_handle(self.insert(key, data)))
+    }
+
+    /// The imported assets, forgetting the ids of the assets which are gone from the file.
+    pub fn finish(self) -> Vec<ImportedAsset> {
+        let imported = self.imported;
+        self.state.ids.retain(|key, _| imported.contains_key(key));
+        self.assets
+    }
+}
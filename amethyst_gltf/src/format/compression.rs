@@ -0,0 +1,652 @@
+//! Compressed buffer views and primitives.
+//!
+//! `EXT_meshopt_compression` buffer views are decoded into their fallback buffer when importing,
+//! so the rest of the loader reads them like any other buffer view. With the `draco` feature,
+//! `KHR_draco_mesh_compression` primitives are decoded the same way, into a new buffer holding
+//! the accessors of the primitive. `gltf` doesn't parse these extensions, so they are read from
+//! the json document directly.
+
+#[cfg(feature = "draco")]
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+use amethyst_error::Error;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::error;
+
+const MESHOPT: &str = "EXT_meshopt_compression";
+const DRACO: &str = "KHR_draco_mesh_compression";
+const DECODE_DRACO: bool = cfg!(feature = "draco");
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MeshoptView {
+    buffer: usize,
+    #[serde(default)]
+    byte_offset: usize,
+    byte_length: usize,
+    byte_stride: usize,
+    count: usize,
+    mode: MeshoptMode,
+    #[serde(default)]
+    filter: MeshoptFilter,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+enum MeshoptMode {
+    Attributes,
+    Triangles,
+    Indices,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+enum MeshoptFilter {
+    None,
+    Octahedral,
+    Quaternion,
+    Exponential,
+}
+
+impl Default for MeshoptFilter {
+    fn default() -> Self {
+        MeshoptFilter::None
+    }
+}
+
+/// A Draco compressed primitive, and where its accessors are decoded.
+#[cfg(feature = "draco")]
+#[derive(Debug)]
+struct DracoPrimitive {
+    view: usize,
+    buffer: usize,
+    accessors: Vec<DracoAccessor>,
+}
+
+/// An accessor of a Draco compressed primitive, `attribute` being `None` for the indices.
+#[cfg(feature = "draco")]
+#[derive(Debug)]
+struct DracoAccessor {
+    attribute: Option<u32>,
+    component_type: u64,
+    components: usize,
+    count: usize,
+    offset: usize,
+    length: usize,
+}
+
+/// Compressed buffer views found in a json document.
+#[derive(Debug, Default)]
+pub struct Compression {
+    views: Vec<(usize, MeshoptView)>,
+    #[cfg(feature = "draco")]
+    primitives: Vec<DracoPrimitive>,
+    fallback_buffers: HashSet<usize>,
+}
+
+impl Compression {
+    #[cfg(feature = "draco")]
+    fn is_empty(&self) -> bool {
+        self.views.is_empty() && self.primitives.is_empty()
+    }
+
+    #[cfg(not(feature = "draco"))]
+    fn is_empty(&self) -> bool {
+        self.views.is_empty()
+    }
+
+    /// Returns true if the buffer only holds decoded data, and doesn't need to be loaded.
+    pub fn is_fallback(&self, buffer: usize) -> bool {
+        self.fallback_buffers.contains(&buffer)
+    }
+
+    /// Decode the compressed buffer views into their fallback buffers.
+    pub fn decompress(&self, gltf: &gltf::Gltf, buffers: &mut [Vec<u8>]) -> Result<(), Error> {
+        for (index, compressed) in &self.views {
+            let view = gltf
+                .views()
+                .nth(*index)
+                .expect("Unreachable: compressed views are read from the `Gltf` document");
+            let begin = compressed.byte_offset;
+            let source = buffers
+                .get(compressed.buffer)
+                .and_then(|data| data.get(begin..begin + compressed.byte_length))
+                .ok_or(error::Error::InvalidCompression(*index))?;
+            let length = compressed.count * compressed.byte_stride;
+            if length > view.length() {
+                return Err(error::Error::InvalidCompression(*index).into());
+            }
+            let mut data = vec![0; length];
+            decode(compressed, source, &mut data)
+                .ok_or(error::Error::InvalidCompression(*index))?;
+
+            let target = buffers
+                .get_mut(view.buffer().index())
+                .and_then(|target| target.get_mut(view.offset()..view.offset() + data.len()))
+                .ok_or(error::Error::InvalidCompression(*index))?;
+            target.copy_from_slice(&data);
+        }
+
+        #[cfg(feature = "draco")]
+        for primitive in &self.primitives {
+            let error = || error::Error::InvalidCompression(primitive.view);
+            let decoded = {
+                let view = gltf.views().nth(primitive.view).ok_or_else(error)?;
+                let source = buffers
+                    .get(view.buffer().index())
+                    .and_then(|data| data.get(view.offset()..view.offset() + view.length()))
+                    .ok_or_else(error)?;
+                draco_decoder::decode_mesh_with_config_sync(source).ok_or_else(error)?
+            };
+            let target = &mut buffers[primitive.buffer];
+            for accessor in &primitive.accessors {
+                let data = target
+                    .get_mut(accessor.offset..accessor.offset + accessor.length)
+                    .ok_or_else(error)?;
+                write_draco_accessor(&decoded, accessor, data).ok_or_else(error)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Reads the compressed buffer views of a json document, and removes the decoded extensions from
+/// the required extensions so `gltf` accepts the document.
+pub fn prepare(json: &[u8]) -> Result<(Vec<u8>, Compression), Error> {
+    let mut root: Value = serde_json::from_slice(json)?;
+    let mut compression = Compression::default();
+
+    if let Some(Value::Array(required)) = root.get_mut("extensionsRequired") {
+        if !DECODE_DRACO && required.iter().any(|extension| extension == DRACO) {
+            return Err(error::Error::UnsupportedExtension(DRACO).into());
+        }
+        required.retain(|extension| extension != MESHOPT && !(DECODE_DRACO && extension == DRACO));
+    }
+
+    if let Some(Value::Array(views)) = root.get("bufferViews") {
+        for (index, view) in views.iter().enumerate() {
+            if let Some(extension) = view.pointer(&format!("/extensions/{}", MESHOPT)) {
+                let compressed = MeshoptView::deserialize(extension)?;
+                compression.views.push((index, compressed));
+            }
+        }
+    }
+
+    if let Some(Value::Array(buffers)) = root.get("buffers") {
+        for (index, buffer) in buffers.iter().enumerate() {
+            let fallback = buffer
+                .pointer(&format!("/extensions/{}/fallback", MESHOPT))
+                .and_then(Value::as_bool)
+                .unwrap_or(false);
+            if fallback {
+                compression.fallback_buffers.insert(index);
+            }
+        }
+    }
+
+    #[cfg(feature = "draco")]
+    prepare_draco(&mut root, &mut compression)?;
+
+    if compression.is_empty() {
+        Ok((json.to_vec(), compression))
+    } else {
+        Ok((serde_json::to_vec(&root)?, compression))
+    }
+}
+
+/// Points the accessors of the Draco compressed primitives to new buffer views of a new buffer
+/// for every primitive, which the primitive is decoded into. The uncompressed fallback of the
+/// primitives, if any, isn't used.
+#[cfg(feature = "draco")]
+fn prepare_draco(root: &mut Value, compression: &mut Compression) -> Result<(), Error> {
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct DracoExtension {
+        buffer_view: usize,
+        attributes: HashMap<String, u32>,
+    }
+
+    // the accessors of every compressed primitive, collected before the document is changed
+    let mut primitives = Vec::new();
+    let meshes = root.get("meshes").and_then(Value::as_array);
+    for mesh in meshes.into_iter().flatten() {
+        let mesh_primitives = mesh.get("primitives").and_then(Value::as_array);
+        for primitive in mesh_primitives.into_iter().flatten() {
+            let extension = match primitive.pointer(&format!("/extensions/{}", DRACO)) {
+                Some(extension) => DracoExtension::deserialize(extension)?,
+                None => continue,
+            };
+            let invalid = || error::Error::InvalidCompression(extension.buffer_view);
+            let mut accessors = Vec::new();
+            if let Some(indices) = primitive.get("indices") {
+                accessors.push((None, indices.as_u64().ok_or_else(invalid)?));
+            }
+            for (name, attribute) in &extension.attributes {
+                let accessor = primitive
+                    .pointer(&format!("/attributes/{}", name))
+                    .and_then(Value::as_u64)
+                    .ok_or_else(invalid)?;
+                accessors.push((Some(*attribute), accessor));
+            }
+            primitives.push((extension.buffer_view, accessors));
+        }
+    }
+
+    for (view, accessors) in primitives {
+        let invalid = || error::Error::InvalidCompression(view);
+        let buffer = array_len(root, "buffers");
+        let mut decoded = Vec::with_capacity(accessors.len());
+        let mut offset = 0;
+        for (attribute, index) in accessors {
+            let buffer_view = array_len(root, "bufferViews");
+            let accessor = root
+                .pointer_mut(&format!("/accessors/{}", index))
+                .and_then(Value::as_object_mut)
+                .ok_or_else(invalid)?;
+            let component_type = accessor
+                .get("componentType")
+                .and_then(Value::as_u64)
+                .ok_or_else(invalid)?;
+            let count = accessor
+                .get("count")
+                .and_then(Value::as_u64)
+                .ok_or_else(invalid)? as usize;
+            let components = match accessor.get("type").and_then(Value::as_str) {
+                Some("SCALAR") => 1,
+                Some("VEC2") => 2,
+                Some("VEC3") => 3,
+                Some("VEC4") => 4,
+                _ => return Err(invalid().into()),
+            };
+            let length = count * components * component_size(component_type).ok_or_else(invalid)?;
+            accessor.insert("bufferView".to_string(), buffer_view.into());
+            accessor.remove("byteOffset");
+            push(
+                root,
+                "bufferViews",
+                serde_json::json!({
+                    "buffer": buffer,
+                    "byteOffset": offset,
+                    "byteLength": length,
+                }),
+            );
+            decoded.push(DracoAccessor {
+                attribute,
+                component_type,
+                components,
+                count,
+                offset,
+                length,
+            });
+            // accessors are aligned to 4 bytes
+            offset += (length + 3) & !3;
+        }
+        push(root, "buffers", serde_json::json!({ "byteLength": offset }));
+        compression.fallback_buffers.insert(buffer);
+        compression.primitives.push(DracoPrimitive {
+            view,
+            buffer,
+            accessors: decoded,
+        });
+    }
+    Ok(())
+}
+
+#[cfg(feature = "draco")]
+fn array_len(root: &Value, field: &str) -> usize {
+    root.get(field)
+        .and_then(Value::as_array)
+        .map_or(0, Vec::len)
+}
+
+#[cfg(feature = "draco")]
+fn push(root: &mut Value, field: &str, value: Value) {
+    if let Some(root) = root.as_object_mut() {
+        let array = root
+            .entry(field)
+            .or_insert_with(|| Value::Array(Vec::new()));
+        if let Value::Array(array) = array {
+            array.push(value);
+        }
+    }
+}
+
+/// The size of a component of an accessor, by glTF component type.
+#[cfg(feature = "draco")]
+fn component_size(component_type: u64) -> Option<usize> {
+    match component_type {
+        5120 | 5121 => Some(1),
+        5122 | 5123 => Some(2),
+        5125 | 5126 => Some(4),
+        _ => None,
+    }
+}
+
+/// Writes an accessor of a decoded Draco primitive, which must have the type of the accessor.
+#[cfg(feature = "draco")]
+fn write_draco_accessor(
+    decoded: &draco_decoder::MeshDecodeResult,
+    accessor: &DracoAccessor,
+    data: &mut [u8],
+) -> Option<()> {
+    use draco_decoder::AttributeDataType::*;
+
+    let config = &decoded.config;
+    let attribute = match accessor.attribute {
+        Some(attribute) => attribute,
+        None => {
+            if config.index_count() as usize != accessor.count {
+                return None;
+            }
+            // the decoder writes 16 bit indices when there are few enough of them
+            let width = if config.index_count() <= u32::from(u16::MAX) {
+                2
+            } else {
+                4
+            };
+            let indices = decoded.data.get(..config.index_length() as usize)?;
+            let size = component_size(accessor.component_type)?;
+            for (index, target) in indices.chunks_exact(width).zip(data.chunks_exact_mut(size)) {
+                let mut bytes = [0; 4];
+                bytes[..width].copy_from_slice(index);
+                let index = u32::from_le_bytes(bytes);
+                if size < 4 && index >> (size * 8) != 0 {
+                    return None;
+                }
+                target.copy_from_slice(&index.to_le_bytes()[..size]);
+            }
+            return Some(());
+        }
+    };
+
+    let decoded_attribute = config
+        .attributes()
+        .into_iter()
+        .find(|decoded| decoded.unique_id() == attribute)?;
+    let component_type = match decoded_attribute.data_type() {
+        Int8 => 5120,
+        UInt8 => 5121,
+        Int16 => 5122,
+        UInt16 => 5123,
+        UInt32 => 5125,
+        Float32 => 5126,
+        Int32 => return None,
+    };
+    if component_type != accessor.component_type
+        || decoded_attribute.dim() as usize != accessor.components
+        || config.vertex_count() as usize != accessor.count
+    {
+        return None;
+    }
+    let begin = decoded_attribute.offset() as usize;
+    let values = decoded
+        .data
+        .get(begin..begin + decoded_attribute.lenght() as usize)?;
+    if values.len() != data.len() {
+        return None;
+    }
+    data.copy_from_slice(values);
+    Some(())
+}
+
+fn decode(compressed: &MeshoptView, source: &[u8], data: &mut [u8]) -> Option<()> {
+    use meshopt::ffi;
+
+    let count = compressed.count;
+    let stride = compressed.byte_stride;
+    // SAFETY: `data` holds `count` elements of `stride` bytes, the decoders fail instead of
+    // reading past the end of `source`
+    let result = unsafe {
+        let destination = data.as_mut_ptr() as *mut std::ffi::c_void;
+        match compressed.mode {
+            MeshoptMode::Attributes => {
+                ffi::meshopt_decodeVertexBuffer(
+                    destination,
+                    count,
+                    stride,
+                    source.as_ptr(),
+                    source.len(),
+                )
+            }
+            MeshoptMode::Triangles => {
+                ffi::meshopt_decodeIndexBuffer(
+                    destination,
+                    count,
+                    stride,
+                    source.as_ptr(),
+                    source.len(),
+                )
+            }
+            MeshoptMode::Indices => {
+                ffi::meshopt_decodeIndexSequence(
+                    destination,
+                    count,
+                    stride,
+                    source.as_ptr(),
+                    source.len(),
+                )
+            }
+        }
+    };
+    if result != 0 {
+        return None;
+    }
+
+    match (&compressed.filter, stride) {
+        (MeshoptFilter::None, _) => {}
+        (MeshoptFilter::Octahedral, 4) => {
+            for vertex in data.chunks_exact_mut(4) {
+                let mut normal = [0.0; 3];
+                for (n, byte) in normal.iter_mut().zip(vertex.iter()) {
+                    *n = f32::from(*byte as i8);
+                }
+                let normal = decode_octahedral(normal, 127.0);
+                for (byte, n) in vertex.iter_mut().zip(normal.iter()) {
+                    *byte = *n as i8 as u8;
+                }
+            }
+        }
+        (MeshoptFilter::Octahedral, 8) => {
+            for vertex in data.chunks_exact_mut(8) {
+                let components = read_i16(vertex);
+                let normal = decode_octahedral(
+                    [
+                        f32::from(components[0]),
+                        f32::from(components[1]),
+                        f32::from(components[2]),
+                    ],
+                    32767.0,
+                );
+                write_i16(
+                    vertex,
+                    [
+                        normal[0] as i16,
+                        normal[1] as i16,
+                        normal[2] as i16,
+                        components[3],
+                    ],
+                );
+            }
+        }
+        (MeshoptFilter::Quaternion, 8) => {
+            for vertex in data.chunks_exact_mut(8) {
+                let quaternion = decode_quaternion(read_i16(vertex));
+                write_i16(vertex, quaternion);
+            }
+        }
+        (MeshoptFilter::Exponential, stride) if stride % 4 == 0 => {
+            for value in data.chunks_exact_mut(4) {
+                let mut bytes = [0; 4];
+                bytes.copy_from_slice(value);
+                let decoded = decode_exponential(u32::from_le_bytes(bytes));
+                value.copy_from_slice(&decoded.to_le_bytes());
+            }
+        }
+        _ => return None,
+    }
+    Some(())
+}
+
+fn read_i16(vertex: &[u8]) -> [i16; 4] {
+    let mut components = [0; 4];
+    for (component, bytes) in components.iter_mut().zip(vertex.chunks_exact(2)) {
+        *component = i16::from_le_bytes([bytes[0], bytes[1]]);
+    }
+    components
+}
+
+fn write_i16(vertex: &mut [u8], components: [i16; 4]) {
+    for (bytes, component) in vertex.chunks_exact_mut(2).zip(components.iter()) {
+        bytes.copy_from_slice(&component.to_le_bytes());
+    }
+}
+
+/// Rebuild a normal from its octahedral encoding, scaled to `max`.
+fn decode_octahedral([mut x, mut y, z]: [f32; 3], max: f32) -> [f32; 3] {
+    let z = z - x.abs() - y.abs();
+    // fold the lower hemisphere back
+    let t = z.min(0.0);
+    x += if x >= 0.0 { t } else { -t };
+    y += if y >= 0.0 { t } else { -t };
+    let scale = max / (x * x + y * y + z * z).sqrt();
+    [
+        (x * scale).round(),
+        (y * scale).round(),
+        (z * scale).round(),
+    ]
+}
+
+/// Rebuild a quaternion from its three smallest components, the last component holding the index
+/// of the largest one in its low bits.
+fn decode_quaternion(components: [i16; 4]) -> [i16; 4] {
+    let scale = std::f32::consts::FRAC_1_SQRT_2 / f32::from(components[3] | 3);
+    let x = f32::from(components[0]) * scale;
+    let y = f32::from(components[1]) * scale;
+    let z = f32::from(components[2]) * scale;
+    let w = (1.0 - x * x - y * y - z * z).max(0.0).sqrt();
+
+    let largest = (components[3] & 3) as usize;
+    let mut decoded = [0; 4];
+    decoded[(largest + 1) & 3] = (x * 32767.0).round() as i16;
+    decoded[(largest + 2) & 3] = (y * 32767.0).round() as i16;
+    decoded[(largest + 3) & 3] = (z * 32767.0).round() as i16;
+    decoded[largest] = (w * 32767.0).round() as i16;
+    decoded
+}
+
+/// Decode a float stored as a 24 bit mantissa and an 8 bit exponent.
+fn decode_exponential(value: u32) -> f32 {
+    let mantissa = ((value << 8) as i32) >> 8;
+    let exponent = (value as i32) >> 24;
+    mantissa as f32 * 2f32.powi(exponent)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filters_decode_reference_values() {
+        assert_eq!(
+            decode_octahedral([0.0, 0.0, 127.0], 127.0),
+            [0.0, 0.0, 127.0]
+        );
+        assert_eq!(
+            decode_octahedral([127.0, 0.0, 127.0], 127.0),
+            [127.0, 0.0, 0.0]
+        );
+        // an identity quaternion, with w as the largest component
+        assert_eq!(decode_quaternion([0, 0, 0, 0x7ffc | 3]), [0, 0, 0, 32767]);
+        assert_eq!(decode_exponential(0xff00_0003), 1.5);
+        assert_eq!(decode_exponential(0x00ff_ffff), -1.0);
+    }
+
+    #[test]
+    fn meshopt_is_removed_from_the_required_extensions() {
+        let json = br#"{
+            "asset": { "version": "2.0" },
+            "extensionsRequired": ["EXT_meshopt_compression"],
+            "buffers": [
+                { "byteLength": 8 },
+                { "byteLength": 24, "extensions": { "EXT_meshopt_compression": { "fallback": true } } }
+            ],
+            "bufferViews": [{
+                "buffer": 1,
+                "byteLength": 24,
+                "extensions": { "EXT_meshopt_compression": {
+                    "buffer": 0, "byteLength": 8, "byteStride": 4, "count": 6, "mode": "TRIANGLES"
+                } }
+            }]
+        }"#;
+        let (json, compression) = prepare(json).unwrap();
+        let root: Value = serde_json::from_slice(&json).unwrap();
+        assert_eq!(root["extensionsRequired"], Value::Array(vec![]));
+        assert_eq!(compression.views.len(), 1);
+        assert!(compression.is_fallback(1));
+        assert!(!compression.is_fallback(0));
+
+        let draco = br#"{ "asset": { "version": "2.0" },
+            "extensionsRequired": ["KHR_draco_mesh_compression"] }"#;
+        assert_eq!(prepare(draco).is_err(), !DECODE_DRACO);
+    }
+
+    #[test]
+    fn meshopt_views_longer_than_their_target_are_rejected() {
+        let json = br#"{
+            "asset": { "version": "2.0" },
+            "buffers": [
+                { "byteLength": 8 },
+                { "byteLength": 24, "extensions": { "EXT_meshopt_compression": { "fallback": true } } }
+            ],
+            "bufferViews": [{
+                "buffer": 1,
+                "byteLength": 8,
+                "extensions": { "EXT_meshopt_compression": {
+                    "buffer": 0, "byteLength": 8, "byteStride": 4, "count": 6, "mode": "TRIANGLES"
+                } }
+            }]
+        }"#;
+        let (json, compression) = prepare(json).unwrap();
+        let gltf = gltf::Gltf::from_slice(&json).unwrap();
+        let mut buffers = vec![vec![0; 8], vec![0; 24]];
+        assert!(compression.decompress(&gltf, &mut buffers).is_err());
+        assert_eq!(buffers[1], vec![0; 24]);
+    }
+
+    #[cfg(feature = "draco")]
+    #[test]
+    fn draco_accessors_point_to_the_decoded_buffer() {
+        let json = br#"{
+            "asset": { "version": "2.0" },
+            "extensionsRequired": ["KHR_draco_mesh_compression"],
+            "buffers": [{ "byteLength": 16 }],
+            "bufferViews": [{ "buffer": 0, "byteLength": 16 }],
+            "accessors": [
+                { "componentType": 5123, "count": 3, "type": "SCALAR" },
+                { "componentType": 5126, "count": 3, "type": "VEC3", "byteOffset": 4 }
+            ],
+            "meshes": [{ "primitives": [{
+                "indices": 0,
+                "attributes": { "POSITION": 1 },
+                "extensions": { "KHR_draco_mesh_compression": {
+                    "bufferView": 0, "attributes": { "POSITION": 0 }
+                } }
+            }] }]
+        }"#;
+        let (json, compression) = prepare(json).unwrap();
+        let root: Value = serde_json::from_slice(&json).unwrap();
+        assert_eq!(root["extensionsRequired"], Value::Array(vec![]));
+        assert!(compression.is_fallback(1));
+
+        // the indices take 6 bytes, aligned to 8
+        assert_eq!(root["buffers"][1]["byteLength"], 44);
+        assert_eq!(root["accessors"][0]["bufferView"], 1);
+        assert_eq!(root["accessors"][1]["bufferView"], 2);
+        assert_eq!(root["accessors"][1].get("byteOffset"), None);
+        assert_eq!(root["bufferViews"][2]["byteOffset"], 8);
+        assert_eq!(root["bufferViews"][2]["byteLength"], 36);
+    }
+}
@@ -1,9 +1,7 @@
-use std::{path::Path, sync::Arc};
-
-use amethyst_assets::Source as AssetSource;
 use amethyst_error::Error;
 use gltf::{self, json, Gltf};
 
+use super::compression::{prepare, Compression};
 use crate::error;
 
 #[derive(Debug)]
@@ -48,58 +46,46 @@ impl Buffers {
     }
 }
 
-/// Imports glTF 2.0
-pub fn import<P>(source: Arc<dyn AssetSource>, path: P) -> Result<(Gltf, Buffers), Error>
-where
-    P: AsRef<Path>,
-{
-    let path = path.as_ref();
-    let data = read_to_end(source.clone(), path)?;
+/// Imports glTF 2.0, from a `.glb` file or a `.gltf` file with embedded buffers and images
+pub fn import(data: &[u8]) -> Result<(Gltf, Buffers), Error> {
     if data.starts_with(b"glTF") {
-        import_binary(&data, source, path)
+        import_binary(data)
     } else {
-        import_standard(&data, source, path)
+        import_standard(data)
     }
 }
 
-fn read_to_end<P: AsRef<Path>>(source: Arc<dyn AssetSource>, path: P) -> Result<Vec<u8>, Error> {
-    let path = path.as_ref();
-    source.load(
-        path.to_str()
-            .expect("Path contains invalid UTF-8 charcters"),
-    )
-}
-
 fn parse_data_uri(uri: &str) -> Result<Vec<u8>, Error> {
     let encoded = uri.split(',').nth(1).expect("URI does not contain ','");
     let decoded = base64::decode(&encoded)?;
     Ok(decoded)
 }
 
-fn load_external_buffers(
-    source: Arc<dyn AssetSource>,
-    base_path: &Path,
+fn load_buffers(
     gltf: &Gltf,
+    compression: &Compression,
     mut bin: Option<Vec<u8>>,
 ) -> Result<Vec<Vec<u8>>, Error> {
     use gltf::buffer::Source;
     let mut buffers = vec![];
     for (index, buffer) in gltf.buffers().enumerate() {
+        if compression.is_fallback(index) {
+            // filled when decoding the compressed buffer views
+            buffers.push(vec![0; buffer.length()]);
+            continue;
+        }
         let data = match buffer.source() {
             Source::Uri(uri) => {
                 if uri.starts_with("data:") {
                     parse_data_uri(uri)?
                 } else {
-                    let path = base_path
-                        .parent()
-                        .unwrap_or_else(|| Path::new("./"))
-                        .join(uri);
-                    read_to_end(source.clone(), &path)?
+                    return Err(error::Error::ExternalReference(uri.to_string()).into());
                 }
             }
-            Source::Bin => bin
-                .take()
-                .expect("`BIN` section of binary glTF file is empty or used by another buffer"),
+            Source::Bin => {
+                bin.take()
+                    .expect("`BIN` section of binary glTF file is empty or used by another buffer")
+            }
         };
 
         if data.len() < buffer.length() {
@@ -108,36 +94,29 @@ fn load_external_buffers(
         }
         buffers.push(data);
     }
+    compression.decompress(gltf, &mut buffers)?;
     Ok(buffers)
 }
 
-fn import_standard(
-    data: &[u8],
-    source: Arc<dyn AssetSource>,
-    base_path: &Path,
-) -> Result<(Gltf, Buffers), Error> {
-    let gltf = Gltf::from_slice(data)?;
-    let buffers = Buffers(load_external_buffers(source, base_path, &gltf, None)?);
+fn import_standard(data: &[u8]) -> Result<(Gltf, Buffers), Error> {
+    let (json, compression) = prepare(data)?;
+    let gltf = Gltf::from_slice(&json)?;
+    let buffers = Buffers(load_buffers(&gltf, &compression, None)?);
     Ok((gltf, buffers))
 }
 
-fn import_binary(
-    data: &[u8],
-    source: Arc<dyn AssetSource>,
-    base_path: &Path,
-) -> Result<(Gltf, Buffers), Error> {
+fn import_binary(data: &[u8]) -> Result<(Gltf, Buffers), Error> {
     let gltf::binary::Glb { json, bin, .. } = gltf::binary::Glb::from_slice(data)?;
+    let (json, compression) = prepare(&json)?;
     let gltf = Gltf::from_slice(&json)?;
     let bin = bin.map(|x| x.to_vec());
-    let buffers = Buffers(load_external_buffers(source, base_path, &gltf, bin)?);
+    let buffers = Buffers(load_buffers(&gltf, &compression, bin)?);
     Ok((gltf, buffers))
 }
 
 pub fn get_image_data(
     image: &gltf::Image<'_>,
     buffers: &Buffers,
-    source: Arc<dyn AssetSource>,
-    base_path: &Path,
 ) -> Result<(Vec<u8>, ImageFormat), Error> {
     use gltf::image::Source;
     match image.source() {
@@ -167,28 +146,7 @@ pub fn get_image_data(
                     Ok((data, ImageFormat::from_mime_type(mimetype)))
                 }
             } else {
-                let path = base_path
-                    .parent()
-                    .unwrap_or_else(|| Path::new("./"))
-                    .join(uri);
-                let data = source.load(
-                    path.to_str()
-                        .expect("Path contains invalid UTF-8 characters"),
-                )?;
-                if let Some(ty) = mime_type {
-                    Ok((data, ImageFormat::from_mime_type(ty)))
-                } else {
-                    let ext = path
-                        .extension()
-                        .and_then(|s| s.to_str())
-                        .map_or("".to_string(), |s| s.to_ascii_lowercase());
-                    let format = match &ext[..] {
-                        "jpg" | "jpeg" => ImageFormat::Jpeg,
-                        "png" => ImageFormat::Png,
-                        _ => unreachable!(),
-                    };
-                    Ok((data, format))
-                }
+                Err(error::Error::ExternalReference(uri.to_string()).into())
             }
         }
     }
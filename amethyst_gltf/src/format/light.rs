@@ -0,0 +1,109 @@
+use amethyst_core::math::{UnitQuaternion, Vector3};
+use amethyst_rendy::{
+    light::{DirectionalLight, Light, PointLight, SpotLight},
+    palette::Srgb,
+};
+use gltf::khr_lights_punctual::Kind;
+
+// Load a KHR_lights_punctual light. Lights point down the -Z axis of their node, the direction
+// only takes the rotation of the node itself into account, not the one of its parents.
+pub fn load_light(
+    light: &gltf::khr_lights_punctual::Light<'_>,
+    rotation: &UnitQuaternion<f32>,
+) -> Light {
+    let [r, g, b] = light.color();
+    let color = Srgb::new(r, g, b);
+    let intensity = light.intensity();
+    let direction = rotation * -Vector3::z();
+    match light.kind() {
+        Kind::Directional => {
+            DirectionalLight {
+                color,
+                intensity,
+                direction,
+            }
+            .into()
+        }
+        Kind::Point => {
+            let default = PointLight::default();
+            PointLight {
+                color,
+                intensity,
+                radius: light.range().unwrap_or(default.radius),
+                ..default
+            }
+            .into()
+        }
+        Kind::Spot {
+            outer_cone_angle, ..
+        } => {
+            let default = SpotLight::default();
+            SpotLight {
+                angle: outer_cone_angle,
+                color,
+                direction,
+                intensity,
+                range: light.range().unwrap_or(default.range),
+                ..default
+            }
+            .into()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use amethyst_core::math::{UnitQuaternion, Vector3};
+    use amethyst_rendy::light::{Light, PointLight};
+
+    use super::load_light;
+
+    const LIGHTS: &str = r#"{
+        "asset": { "version": "2.0" },
+        "extensionsUsed": ["KHR_lights_punctual"],
+        "extensions": {
+            "KHR_lights_punctual": {
+                "lights": [
+                    {
+                        "type": "spot",
+                        "color": [1.0, 0.5, 0.0],
+                        "intensity": 2.0,
+                        "range": 5.0,
+                        "spot": { "outerConeAngle": 0.5 }
+                    },
+                    { "type": "point" }
+                ]
+            }
+        }
+    }"#;
+
+    #[test]
+    fn lights_point_down_their_node() {
+        let gltf = gltf::Gltf::from_slice(LIGHTS.as_bytes()).unwrap();
+        let lights = gltf.lights().unwrap().collect::<Vec<_>>();
+        let rotation =
+            UnitQuaternion::from_axis_angle(&Vector3::y_axis(), std::f32::consts::FRAC_PI_2);
+
+        match load_light(&lights[0], &rotation) {
+            Light::Spot(spot) => {
+                assert!((spot.direction + Vector3::x()).norm() < 1e-6);
+                assert_eq!(spot.angle, 0.5);
+                assert_eq!(spot.intensity, 2.0);
+                assert_eq!(spot.range, 5.0);
+                assert_eq!((spot.color.red, spot.color.green), (1.0, 0.5));
+            }
+            light => panic!("Expected a spot light, got {:?}", light),
+        }
+    }
+
+    #[test]
+    fn lights_without_range_keep_the_default() {
+        let gltf = gltf::Gltf::from_slice(LIGHTS.as_bytes()).unwrap();
+        let lights = gltf.lights().unwrap().collect::<Vec<_>>();
+
+        match load_light(&lights[1], &UnitQuaternion::identity()) {
+            Light::Point(point) => assert_eq!(point.radius, PointLight::default().radius),
+            light => panic!("Expected a point light, got {:?}", light),
+        }
+    }
+}
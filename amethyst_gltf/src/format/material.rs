@@ -1,9 +1,7 @@
-use std::sync::Arc;
-
-use amethyst_assets::Source;
+use amethyst_assets::Handle;
 use amethyst_error::Error;
 use amethyst_rendy::{
-    formats::{mtl::MaterialPrefab, texture::TexturePrefab},
+    mtl::{Material, TextureOffset},
     palette::{LinSrgba, Srgba},
     rendy::{
         hal,
@@ -13,33 +11,91 @@ use amethyst_rendy::{
             MipLevels, TextureBuilder,
         },
     },
+    types::{Texture, TextureData},
 };
 use gltf::{self, material::AlphaMode};
 
-use super::{get_image_data, Buffers, ImageFormat as ImportDataFormat};
+use super::{assets::ImportedAssets, get_image_data, Buffers, ImageFormat as ImportDataFormat};
 
-// Load a single material, and transform into a format usable by the engine
+// Load a single material as an asset, with its textures, and whether it is blended
 pub fn load_material(
     material: &gltf::Material<'_>,
     buffers: &Buffers,
-    source: Arc<dyn Source>,
-    name: &str,
-) -> Result<MaterialPrefab, Error> {
-    let mut prefab = MaterialPrefab::default();
+    assets: &mut ImportedAssets<'_>,
+) -> Result<(Handle<Material>, bool), Error> {
+    let key = match material.index() {
+        Some(index) => format!("material/{}", index),
+        None => "material/default".to_string(),
+    };
+    let handle = assets.get_or_insert(key.clone(), |assets| {
+        import_material(material, &key, buffers, assets)
+    })?;
+    Ok((handle, material.alpha_mode() == AlphaMode::Blend))
+}
 
+fn import_material(
+    material: &gltf::Material<'_>,
+    key: &str,
+    buffers: &Buffers,
+    assets: &mut ImportedAssets<'_>,
+) -> Result<Material, Error> {
     let pbr = material.pbr_metallic_roughness();
 
-    prefab.albedo = Some(
-        load_texture_with_factor(
-            pbr.base_color_texture(),
-            pbr.base_color_factor(),
-            buffers,
-            source.clone(),
-            name,
-            true,
-        )
-        .map(|(texture, _)| TexturePrefab::Data(texture.into()))?,
-    );
+    let uv_offset = texture_offset(material);
+    let alpha_cutoff = match material.alpha_mode() {
+        AlphaMode::Mask => material.alpha_cutoff(),
+        AlphaMode::Blend | AlphaMode::Opaque => 0.0,
+    };
+
+    let normal = match material.normal_texture() {
+        Some(normal_texture) => load_texture(&normal_texture.texture(), buffers, assets, false)?,
+        None => load_color(assets, key, "normal", LinSrgba::new(0.5, 0.5, 1.0, 1.0))?,
+    };
+    let ambient_occlusion = match material.occlusion_texture() {
+        Some(occlusion_texture) => {
+            load_texture(&occlusion_texture.texture(), buffers, assets, false)?
+        }
+        None => load_color(assets, key, "occlusion", LinSrgba::new(1.0, 1.0, 1.0, 1.0))?,
+    };
+    let cavity = load_color(assets, key, "cavity", LinSrgba::new(1.0, 1.0, 1.0, 1.0))?;
+
+    // KHR_materials_unlit: the base color is emitted instead of lit, over a black rough surface
+    if material.unlit() {
+        let [_, _, _, alpha] = pbr.base_color_factor();
+        return Ok(Material {
+            alpha_cutoff,
+            albedo: load_srgb_color(assets, key, "albedo", Srgba::new(0.0, 0.0, 0.0, alpha))?,
+            emission: load_texture_with_factor(
+                pbr.base_color_texture(),
+                pbr.base_color_factor(),
+                buffers,
+                assets,
+                key,
+                "emission",
+                true,
+            )?,
+            normal,
+            metallic_roughness: load_color(
+                assets,
+                key,
+                "metallic_roughness",
+                LinSrgba::new(1.0, 1.0, 0.0, 1.0),
+            )?,
+            ambient_occlusion,
+            cavity,
+            uv_offset,
+        });
+    }
+
+    let albedo = load_texture_with_factor(
+        pbr.base_color_texture(),
+        pbr.base_color_factor(),
+        buffers,
+        assets,
+        key,
+        "albedo",
+        true,
+    )?;
 
     // metallic from B channel
     // roughness from G channel
@@ -47,127 +103,125 @@ pub fn load_material(
         pbr.metallic_roughness_texture(),
         [1.0, pbr.roughness_factor(), pbr.metallic_factor(), 1.0],
         buffers,
-        source.clone(),
-        name,
+        assets,
+        key,
+        "metallic_roughness",
         false,
-    )?
-    .0;
-
-    prefab.metallic_roughness = Some(TexturePrefab::Data(metallic_roughness.into()));
+    )?;
 
     let em_factor = material.emissive_factor();
-    prefab.emission = Some(TexturePrefab::Data(
-        load_texture_with_factor(
-            material.emissive_texture(),
-            [em_factor[0], em_factor[1], em_factor[2], 1.0],
-            buffers,
-            source.clone(),
-            name,
-            true,
-        )?
-        .0
-        .into(),
-    ));
-
-    // Can't use map/and_then because of Result returning from the load_texture function
-    prefab.normal = match material.normal_texture() {
-        Some(normal_texture) => {
-            Some(
-                load_texture(
-                    &normal_texture.texture(),
-                    buffers,
-                    source.clone(),
-                    name,
-                    false,
-                )
-                .map(|data| TexturePrefab::Data(data.into()))?,
-            )
-        }
-
-        None => None,
-    };
-
-    // Can't use map/and_then because of Result returning from the load_texture function
-    prefab.ambient_occlusion = match material.occlusion_texture() {
-        Some(occlusion_texture) => {
-            Some(
-                load_texture(
-                    &occlusion_texture.texture(),
-                    buffers,
-                    source.clone(),
-                    name,
-                    false,
-                )
-                .map(|data| TexturePrefab::Data(data.into()))?,
-            )
-        }
+    let emission = load_texture_with_factor(
+        material.emissive_texture(),
+        [em_factor[0], em_factor[1], em_factor[2], 1.0],
+        buffers,
+        assets,
+        key,
+        "emission",
+        true,
+    )?;
 
-        None => None,
-    };
+    Ok(Material {
+        alpha_cutoff,
+        albedo,
+        emission,
+        normal,
+        metallic_roughness,
+        ambient_occlusion,
+        cavity,
+        uv_offset,
+    })
+}
 
-    match material.alpha_mode() {
-        AlphaMode::Blend => {
-            prefab.transparent = true;
-        }
-        AlphaMode::Mask => {
-            prefab.alpha_cutoff = material.alpha_cutoff();
-        }
-        AlphaMode::Opaque => {
-            prefab.alpha_cutoff = 0.0;
-        }
-    }
-    Ok(prefab)
+// KHR_texture_transform: materials only have one texture offset, taken from the base color
+// texture, and the rotation is not supported
+fn texture_offset(material: &gltf::Material<'_>) -> TextureOffset {
+    material
+        .pbr_metallic_roughness()
+        .base_color_texture()
+        .and_then(|info| info.texture_transform())
+        .map(|transform| {
+            let [u, v] = transform.offset();
+            let [width, height] = transform.scale();
+            TextureOffset {
+                u: (u, u + width),
+                v: (v, v + height),
+            }
+        })
+        .unwrap_or_default()
 }
 
+// The texture, or a single texel of the factor if there is none. The factor is not applied to
+// the texture, as materials have no factors.
 fn load_texture_with_factor(
     texture: Option<gltf::texture::Info<'_>>,
     factor: [f32; 4],
     buffers: &Buffers,
-    source: Arc<dyn Source>,
+    assets: &mut ImportedAssets<'_>,
+    key: &str,
     name: &str,
     srgb: bool,
-) -> Result<(TextureBuilder<'static>, [f32; 4]), Error> {
+) -> Result<Handle<Texture>, Error> {
+    let [r, g, b, a] = factor;
     match texture {
-        Some(info) => {
-            Ok((
-                load_texture(&info.texture(), buffers, source, name, srgb)?
-                    .with_mip_levels(MipLevels::GenerateAuto),
-                factor,
-            ))
-        }
-        None => {
-            Ok((
-                if srgb {
-                    load_from_srgba(Srgba::new(factor[0], factor[1], factor[2], factor[3]))
-                } else {
-                    load_from_linear_rgba(LinSrgba::new(factor[0], factor[1], factor[2], factor[3]))
-                },
-                [1.0, 1.0, 1.0, 1.0],
-            ))
-        }
+        Some(info) => load_texture(&info.texture(), buffers, assets, srgb),
+        None if srgb => load_srgb_color(assets, key, name, Srgba::new(r, g, b, a)),
+        None => load_color(assets, key, name, LinSrgba::new(r, g, b, a)),
     }
 }
 
+fn load_srgb_color(
+    assets: &mut ImportedAssets<'_>,
+    key: &str,
+    name: &str,
+    color: Srgba,
+) -> Result<Handle<Texture>, Error> {
+    assets.get_or_insert(format!("{}/{}", key, name), |_| {
+        Ok(TextureData::from(load_from_srgba(color)))
+    })
+}
+
+fn load_color(
+    assets: &mut ImportedAssets<'_>,
+    key: &str,
+    name: &str,
+    color: LinSrgba,
+) -> Result<Handle<Texture>, Error> {
+    assets.get_or_insert(format!("{}/{}", key, name), |_| {
+        Ok(TextureData::from(load_from_linear_rgba(color)))
+    })
+}
+
+// Textures are shared by the materials using them, but the color ones are loaded as sRGB
 fn load_texture(
     texture: &gltf::Texture<'_>,
     buffers: &Buffers,
-    source: Arc<dyn Source>,
-    name: &str,
+    assets: &mut ImportedAssets<'_>,
     srgb: bool,
-) -> Result<TextureBuilder<'static>, Error> {
-    let (data, format) = get_image_data(&texture.source(), buffers, source, name.as_ref())?;
-
-    let metadata = ImageTextureConfig {
-        repr: if srgb { Repr::Srgb } else { Repr::Unorm },
-        format: match format {
-            ImportDataFormat::Png => Some(DataFormat::PNG),
-            ImportDataFormat::Jpeg => Some(DataFormat::JPEG),
-        },
-        sampler_info: load_sampler_info(&texture.sampler()),
-        ..Default::default()
-    };
+) -> Result<Handle<Texture>, Error> {
+    let key = format!(
+        "texture/{}/{}",
+        texture.index(),
+        if srgb { "srgb" } else { "unorm" }
+    );
+    assets.get_or_insert(key, |_| {
+        let (data, format) = get_image_data(&texture.source(), buffers)?;
+
+        let metadata = ImageTextureConfig {
+            repr: if srgb { Repr::Srgb } else { Repr::Unorm },
+            format: match format {
+                ImportDataFormat::Png => Some(DataFormat::PNG),
+                ImportDataFormat::Jpeg => Some(DataFormat::JPEG),
+            },
+            sampler_info: load_sampler_info(&texture.sampler()),
+            ..Default::default()
+        };
 
-    load_from_image(std::io::Cursor::new(&data), metadata).map_err(|e| e.into())
+        let builder: TextureBuilder<'static> =
+            load_from_image(std::io::Cursor::new(&data), metadata)?;
+        Ok(TextureData::from(
+            builder.with_mip_levels(MipLevels::GenerateAuto),
+        ))
+    })
 }
 
 fn load_sampler_info(sampler: &gltf::texture::Sampler<'_>) -> hal::image::SamplerDesc {
@@ -207,3 +261,49 @@ fn map_wrapping(gltf_wrap: gltf::texture::WrappingMode) -> hal::image::WrapMode
         gltf::texture::WrappingMode::Repeat => hal::image::WrapMode::Tile,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use amethyst_rendy::mtl::TextureOffset;
+
+    use super::texture_offset;
+
+    #[test]
+    fn texture_transform_sets_the_offset() {
+        let gltf = gltf::Gltf::from_slice(
+            br#"{
+                "asset": { "version": "2.0" },
+                "extensionsUsed": ["KHR_texture_transform"],
+                "images": [{ "uri": "data:image/png;base64," }],
+                "textures": [{ "source": 0 }],
+                "materials": [
+                    {
+                        "pbrMetallicRoughness": {
+                            "baseColorTexture": {
+                                "index": 0,
+                                "extensions": {
+                                    "KHR_texture_transform": {
+                                        "offset": [0.25, 0.5],
+                                        "scale": [0.5, 0.25]
+                                    }
+                                }
+                            }
+                        }
+                    },
+                    {}
+                ]
+            }"#,
+        )
+        .unwrap();
+        let materials = gltf.materials().collect::<Vec<_>>();
+
+        assert_eq!(
+            texture_offset(&materials[0]),
+            TextureOffset {
+                u: (0.25, 0.75),
+                v: (0.5, 0.75),
+            }
+        );
+        assert_eq!(texture_offset(&materials[1]), TextureOffset::default());
+    }
+}
@@ -3,11 +3,13 @@ use std::{iter::repeat, ops::Range};
 use amethyst_core::math::{zero, Vector3};
 use amethyst_error::Error;
 use amethyst_rendy::{
+    morph::{MorphTarget, MorphTargets},
     rendy::mesh::{Color, MeshBuilder, Normal, Position, Tangent, TexCoord},
     skinning::JointCombined,
 };
 use log::{trace, warn};
 use mikktspace::{generate_tangents, Geometry};
+use serde::Deserialize;
 
 use super::Buffers;
 use crate::{error, GltfSceneOptions};
@@ -52,13 +54,31 @@ impl Indices {
     }
 }
 
+/// The names of the morph targets of a mesh, which glTF exporters store in the `targetNames`
+/// extras of the mesh.
+fn load_target_names(mesh: &gltf::Mesh<'_>) -> Vec<String> {
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct MeshExtras {
+        #[serde(default)]
+        target_names: Vec<String>,
+    }
+
+    mesh.extras()
+        .as_ref()
+        .and_then(|extras| serde_json::from_str::<MeshExtras>(extras.get()).ok())
+        .map(|extras| extras.target_names)
+        .unwrap_or_default()
+}
+
 pub fn load_mesh(
     mesh: &gltf::Mesh<'_>,
     buffers: &Buffers,
     options: &GltfSceneOptions,
-) -> Result<Vec<(MeshBuilder<'static>, Option<usize>, Range<[f32; 3]>)>, Error> {
+) -> Result<Vec<(MeshBuilder<'static>, Range<[f32; 3]>, Option<MorphTargets>)>, Error> {
     trace!("Loading mesh");
     let mut primitives = vec![];
+    let target_names = load_target_names(mesh);
 
     for primitive in mesh.primitives() {
         trace!("Loading mesh primitive");
@@ -150,6 +170,27 @@ pub fn load_mesh(
             }
         });
 
+        let targets = try_compute_if(options.load_animations, || {
+            trace!("Loading morph targets");
+            let targets = reader
+                .read_morph_targets()
+                .enumerate()
+                .map(|(index, (positions, normals, tangents))| {
+                    MorphTarget {
+                        name: target_names.get(index).cloned(),
+                        positions: positions.map(Iterator::collect).unwrap_or_default(),
+                        normals: normals.map(Iterator::collect).unwrap_or_default(),
+                        tangents: tangents.map(Iterator::collect).unwrap_or_default(),
+                    }
+                })
+                .collect::<Vec<_>>();
+            if targets.is_empty() {
+                None
+            } else {
+                Some(targets)
+            }
+        });
+
         match indices {
             Indices::U16(vec) => {
                 builder.set_indices(vec);
@@ -160,19 +201,30 @@ pub fn load_mesh(
             Indices::None => {}
         };
 
-        builder.add_vertices(positions);
-        normals.map(|v| builder.add_vertices(v));
-        tangents.map(|v| builder.add_vertices(v));
         tex_coords.map(|v| builder.add_vertices(v));
         colors.map(|v| builder.add_vertices(v));
         joints.map(|v| builder.add_vertices(v));
 
+        // the morphed attributes are kept apart from the rest of the mesh, to rebuild it
+        let morph_targets = targets.map(|targets| {
+            MorphTargets {
+                positions: positions.clone(),
+                normals: normals.clone(),
+                tangents: tangents.clone(),
+                attributes: builder.clone(),
+                targets,
+            }
+        });
+
+        builder.add_vertices(positions);
+        normals.map(|v| builder.add_vertices(v));
+        tangents.map(|v| builder.add_vertices(v));
+
         trace!("Loading bounding box");
         let bounds = primitive.bounding_box();
         let bounds = bounds.min..bounds.max;
-        let material = primitive.material().index();
 
-        primitives.push((builder, material, bounds));
+        primitives.push((builder, bounds, morph_targets));
     }
     trace!("Loaded mesh");
     Ok(primitives)
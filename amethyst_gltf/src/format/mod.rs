@@ -1,98 +1,134 @@
 //! GLTF format
 
-use std::{cmp::Ordering, collections::HashMap, sync::Arc};
+use std::{cmp::Ordering, collections::HashMap, io::Read};
 
-use amethyst_animation::AnimationHierarchyPrefab;
-use amethyst_assets::{Format, FormatValue, Prefab, Source};
+use amethyst_assets::{
+    distill_importer::{self, ImportOp, Importer, ImporterValue},
+    inventory,
+    prefab::Prefab,
+    SourceFileImporter,
+};
 use amethyst_core::{
+    ecs::{Entity, World},
     math::{convert, Quaternion, Unit, Vector3, Vector4},
-    transform::Transform,
+    transform::{Parent, Transform},
 };
 use amethyst_error::{format_err, Error, ResultExt};
-use amethyst_rendy::{camera::CameraPrefab, light::LightPrefab};
+use amethyst_rendy::{morph::MorphTargets, rendy::mesh::MeshBuilder, types::MeshData};
 use gltf::{self, Gltf};
 use log::debug;
-use serde::{Deserialize, Serialize};
+use type_uuid::TypeUuid;
 
+pub use self::assets::GltfImporterState;
 use self::{
-    animation::load_animations,
+    animation::{load_animation_names, load_animations, load_morph_animations},
+    assets::ImportedAssets,
     importer::{get_image_data, import, Buffers, ImageFormat},
+    light::load_light,
     material::load_material,
     mesh::load_mesh,
     skin::load_skin,
 };
-use crate::{error, GltfMaterialSet, GltfNodeExtent, GltfPrefab, GltfSceneOptions, Named};
+use crate::{
+    components::{
+        GltfAnimationHierarchy, GltfAnimations, GltfBounds, GltfCamera, GltfLight, GltfMaterial,
+        GltfMesh, GltfMorphAnimations, GltfMorphHierarchy, GltfMorphTargets, GltfMorphWeights,
+        GltfName, GltfProjection, GltfTransparent,
+    },
+    error, GltfAnimationNames, GltfNodeExtent, GltfSceneOptions,
+};
 
 mod animation;
+mod assets;
+mod compression;
 mod importer;
+mod light;
 mod material;
 mod mesh;
 mod skin;
 
-/// Gltf scene format, will load a single scene from a Gltf file.
+/// Imports a single scene of a `.gltf` or `.glb` file as a `Prefab`.
 ///
-/// Using the `GltfSceneLoaderSystem` a `Handle<GltfSceneAsset>` from this format can be attached
-/// to an entity in ECS, and the system will then load the full scene using the given entity
-/// as the root node of the scene hierarchy.
+/// The meshes, materials, textures and animations of the scene are imported as assets of their
+/// own next to the prefab, which refers to them by handle. The entities of the prefab hold the
+/// components of `amethyst_gltf::components`, spawned as the engine components they stand for.
+/// Buffers and images must be embedded in the file, as external files can't be read.
 ///
-/// See `GltfSceneOptions` for more information about the load options.
-#[derive(Clone, Debug, Default, Serialize, Deserialize)]
-#[serde(transparent)]
-pub struct GltfSceneFormat(pub GltfSceneOptions);
-
-impl Format<Prefab<GltfPrefab>> for GltfSceneFormat {
-    fn name(&self) -> &'static str {
-        "GLTFScene"
+/// See `GltfSceneOptions` for the options of the importer, set in the `.meta` file of the scene.
+#[derive(Debug, Default, TypeUuid)]
+#[uuid = "3686a285-bbb1-4e94-bb54-638a7ec727dc"]
+pub struct GltfImporter;
+
+inventory::submit! {
+    #![crate = amethyst_assets]
+    SourceFileImporter {
+        extension: "gltf",
+        instantiator: || Box::new(GltfImporter),
     }
+}
 
-    fn import(
-        &self,
-        name: String,
-        source: Arc<dyn Source>,
-        _create_reload: Option<Box<dyn Format<Prefab<GltfPrefab>>>>,
-    ) -> Result<FormatValue<Prefab<GltfPrefab>>, Error> {
-        Ok(FormatValue::data(
-            load_gltf(source, &name, &self.0)
-                .with_context(|_| format_err!("Failed to import gltf scene '{:?}'", name))?,
-        ))
+inventory::submit! {
+    #![crate = amethyst_assets]
+    SourceFileImporter {
+        extension: "glb",
+        instantiator: || Box::new(GltfImporter),
     }
 }
 
-fn load_gltf(
-    source: Arc<dyn Source>,
-    name: &str,
-    options: &GltfSceneOptions,
-) -> Result<Prefab<GltfPrefab>, Error> {
-    debug!("Loading GLTF scene '{}'", name);
-    import(source.clone(), name)
-        .with_context(|_| error::Error::GltfImporterError)
-        .and_then(|(gltf, buffers)| {
-            load_data(&gltf, &buffers, options, source, name).map_err(Into::into)
+impl Importer for GltfImporter {
+    type State = GltfImporterState;
+    type Options = GltfSceneOptions;
+
+    fn version_static() -> u32 {
+        1
+    }
+
+    fn version(&self) -> u32 {
+        Self::version_static()
+    }
+
+    fn import(
+        &self,
+        _op: &mut ImportOp,
+        source: &mut dyn Read,
+        options: &Self::Options,
+        state: &mut Self::State,
+    ) -> distill_importer::Result<ImporterValue> {
+        let mut bytes = Vec::new();
+        source.read_to_end(&mut bytes)?;
+        let mut assets = ImportedAssets::new(state);
+        load_gltf(&bytes, options, &mut assets)
+            .with_context(|_| format_err!("Failed to import gltf scene"))
+            .map_err(|e| distill_importer::Error::Boxed(e.into_error()))?;
+        Ok(ImporterValue {
+            assets: assets.finish(),
         })
+    }
 }
 
-fn load_data(
-    gltf: &Gltf,
-    buffers: &Buffers,
+fn load_gltf(
+    bytes: &[u8],
     options: &GltfSceneOptions,
-    source: Arc<dyn Source>,
-    name: &str,
-) -> Result<Prefab<GltfPrefab>, Error> {
-    let scene_index = get_scene_index(gltf, options)?;
-    let mut prefab = Prefab::<GltfPrefab>::new();
-    load_scene(
-        gltf,
-        scene_index,
-        buffers,
-        options,
-        source,
-        name,
-        &mut prefab,
-    )?;
-    Ok(prefab)
+    assets: &mut ImportedAssets<'_>,
+) -> Result<(), Error> {
+    debug!("Loading GLTF scene");
+    let (gltf, buffers) = import(bytes).with_context(|_| error::Error::GltfImporterError)?;
+    let scene_index = get_scene_index(&gltf, options)?;
+    let world = load_scene(&gltf, scene_index, &buffers, options, assets)?;
+    // other prefabs refer to this one by its prefab id, so it's the id of the asset
+    let id = assets.id("prefab".to_string());
+    assets.insert("prefab".to_string(), Prefab::with_id(world, id));
+    Ok(())
 }
 
 fn get_scene_index(gltf: &Gltf, options: &GltfSceneOptions) -> Result<usize, Error> {
+    if let Some(ref scene_name) = options.scene_name {
+        return gltf
+            .scenes()
+            .find(|scene| scene.name() == Some(scene_name.as_str()))
+            .map(|scene| scene.index())
+            .ok_or_else(|| error::Error::MissingName(scene_name.clone()).into());
+    }
     let num_scenes = gltf.scenes().len();
     match (options.scene_index, gltf.default_scene()) {
         (Some(index), _) if index >= num_scenes => {
@@ -110,236 +146,512 @@ fn load_scene(
     scene_index: usize,
     buffers: &Buffers,
     options: &GltfSceneOptions,
-    source: Arc<dyn Source>,
-    name: &str,
-    prefab: &mut Prefab<GltfPrefab>,
-) -> Result<(), Error> {
+    assets: &mut ImportedAssets<'_>,
+) -> Result<World, Error> {
     let scene = gltf
         .scenes()
         .nth(scene_index)
         .expect("Tried to load a scene which does not exist");
-    let mut node_map = HashMap::new();
-    let mut skin_map = HashMap::new();
+    let mut world = World::default();
+
+    // Prefabs are spawned from their first entity, so the root is pushed first with all of its
+    // components, which are filled in once the nodes are loaded. It remaps the coordinate system,
+    // so animations of the nodes keep working.
+    let transform = root_transform(options).unwrap_or_default();
+    let root = if options.load_animations {
+        world.push((
+            transform,
+            GltfBounds::default(),
+            GltfAnimationNames::default(),
+            GltfAnimationHierarchy::default(),
+            GltfAnimations::default(),
+            GltfMorphHierarchy::default(),
+            GltfMorphAnimations::default(),
+        ))
+    } else {
+        world.push((transform, GltfBounds::default()))
+    };
+
+    let mut loader = NodeLoader {
+        gltf,
+        buffers,
+        options,
+        assets,
+        world,
+        node_map: HashMap::new(),
+        morph_map: HashMap::new(),
+        skins: Vec::new(),
+    };
     let mut bounding_box = GltfNodeExtent::default();
-    let mut material_set = GltfMaterialSet::default();
-    for node in scene.nodes() {
-        let index = prefab.add(Some(0), None);
-        load_node(
-            gltf,
-            &node,
-            index,
-            buffers,
-            options,
-            source.clone(),
-            name,
-            prefab,
-            &mut node_map,
-            &mut skin_map,
-            &mut bounding_box,
-            &mut material_set,
-        )?;
+    for node in root_nodes(&scene, options)? {
+        loader.load_node(&node, root, &mut bounding_box)?;
     }
+    let NodeLoader {
+        mut world,
+        node_map,
+        morph_map,
+        skins,
+        assets,
+        ..
+    } = loader;
+
+    let mut root_entry = world.entry(root).expect("The root was just pushed");
     if bounding_box.valid() {
-        prefab.data_or_default(0).extent = Some(bounding_box);
+        *root_entry.get_component_mut::<GltfBounds>().unwrap() = bounding_box.into();
+    }
+
+    // load animations, if applicable
+    if options.load_animations {
+        let mut nodes = node_map
+            .iter()
+            .map(|(node, entity)| (*node, *entity))
+            .collect::<Vec<_>>();
+        nodes.sort_by_key(|(node, _)| *node);
+        *root_entry.get_component_mut::<GltfAnimations>().unwrap() =
+            GltfAnimations(load_animations(gltf, buffers, &node_map, assets)?);
+        let (morph_nodes, morph_indices) = morph_hierarchy(morph_map);
+        *root_entry
+            .get_component_mut::<GltfMorphAnimations>()
+            .unwrap() = GltfMorphAnimations(load_morph_animations(
+            gltf,
+            buffers,
+            &morph_indices,
+            assets,
+        )?);
+        *root_entry
+            .get_component_mut::<GltfAnimationNames>()
+            .unwrap() = load_animation_names(gltf);
+        *root_entry
+            .get_component_mut::<GltfMorphHierarchy>()
+            .unwrap() = GltfMorphHierarchy(morph_nodes);
+        *root_entry
+            .get_component_mut::<GltfAnimationHierarchy>()
+            .unwrap() = GltfAnimationHierarchy(nodes);
     }
-    prefab.data_or_default(0).materials = Some(material_set);
 
     // load skins
-    for (node_index, skin_info) in skin_map {
+    for (skin_index, skin_entity, meshes) in skins {
         load_skin(
-            &gltf.skins().nth(skin_info.skin_index).expect(
-                "Unreachable: `skin_map` is initialized with indexes from the `Gltf` object",
-            ),
+            &gltf
+                .skins()
+                .nth(skin_index)
+                .expect("Unreachable: `skins` is initialized with indexes from the `Gltf` object"),
             buffers,
-            *node_map
-                .get(&node_index)
-                .expect("Unreachable: `node_map` should contain all nodes present in `skin_map`"),
+            skin_entity,
             &node_map,
-            skin_info.mesh_indices,
-            prefab,
+            meshes,
+            &mut world,
         )?;
     }
 
-    // load animations, if applicable
-    if options.load_animations {
-        let mut hierarchy_prefab = AnimationHierarchyPrefab::default();
-        hierarchy_prefab.nodes = node_map
-            .iter()
-            .map(|(node, entity)| (*node, *entity))
-            .collect();
-        prefab
-            .data_or_default(0)
-            .animatable
-            .get_or_insert_with(Default::default)
-            .hierarchy = Some(hierarchy_prefab);
-
-        prefab
-            .data_or_default(0)
-            .animatable
-            .get_or_insert_with(Default::default)
-            .animation_set = Some(load_animations(gltf, buffers, &node_map)?);
-    }
+    Ok(world)
+}
 
-    Ok(())
+fn node_transform(node: &gltf::Node<'_>) -> Transform {
+    let (translation, rotation, scale) = node.transform().decomposed();
+    let mut transform = Transform::default();
+    *transform.translation_mut() = convert::<_, Vector3<f32>>(Vector3::from(translation));
+    *transform.rotation_mut() = Unit::new_normalize(convert::<_, Quaternion<f32>>(
+        Quaternion::from(Vector4::from(rotation)),
+    ));
+    *transform.scale_mut() = convert::<_, Vector3<f32>>(Vector3::from(scale));
+    transform
 }
 
-#[derive(Debug)]
-struct SkinInfo {
-    skin_index: usize,
-    mesh_indices: Vec<usize>,
+// The transform of the root converting the coordinate system, if the options change it
+fn root_transform(options: &GltfSceneOptions) -> Option<Transform> {
+    if !options.z_up && (options.scale - 1.0).abs() <= std::f32::EPSILON {
+        return None;
+    }
+    let mut transform = Transform::default();
+    transform.set_scale(Vector3::from_element(options.scale));
+    if options.z_up {
+        transform.set_rotation_x_axis(-std::f32::consts::FRAC_PI_2);
+    }
+    Some(transform)
 }
 
-fn load_node(
-    gltf: &Gltf,
-    node: &gltf::Node<'_>,
-    entity_index: usize,
-    buffers: &Buffers,
+// The nodes loaded at the root of the prefab
+fn root_nodes<'a>(
+    scene: &gltf::Scene<'a>,
     options: &GltfSceneOptions,
-    source: Arc<dyn Source>,
-    name: &str,
-    prefab: &mut Prefab<GltfPrefab>,
-    node_map: &mut HashMap<usize, usize>,
-    skin_map: &mut HashMap<usize, SkinInfo>,
-    parent_bounding_box: &mut GltfNodeExtent,
-    material_set: &mut GltfMaterialSet,
-) -> Result<(), Error> {
-    node_map.insert(node.index(), entity_index);
-
-    // Load node name.
-    if let Some(name) = node.name() {
-        prefab.data_or_default(entity_index).name = Some(Named::new(name.to_string()));
+) -> Result<Vec<gltf::Node<'a>>, Error> {
+    match options.node_name {
+        Some(ref node_name) => {
+            let node = find_node(scene.nodes(), node_name)
+                .ok_or_else(|| error::Error::MissingName(node_name.clone()))?;
+            Ok(vec![node])
+        }
+        None => Ok(scene.nodes().collect()),
     }
+}
 
-    // Load transformation data, default will be identity
-    let (translation, rotation, scale) = node.transform().decomposed();
-    let mut local_transform = Transform::default();
-    *local_transform.translation_mut() = convert::<_, Vector3<f32>>(Vector3::from(translation));
-    *local_transform.rotation_mut() = Unit::new_normalize(convert::<_, Quaternion<f32>>(
-        Quaternion::from(Vector4::from(rotation)),
-    ));
-    *local_transform.scale_mut() = convert::<_, Vector3<f32>>(Vector3::from(scale));
-    prefab.data_or_default(entity_index).transform = Some(local_transform);
-
-    // Load camera
-    if let Some(camera) = node.camera() {
-        prefab.data_or_default(entity_index).camera = Some(match camera.projection() {
-            gltf::camera::Projection::Orthographic(proj) => CameraPrefab::Orthographic {
-                left: -proj.xmag(),
-                right: proj.xmag(),
-                bottom: -proj.ymag(),
-                top: proj.ymag(),
-                znear: proj.znear(),
-                zfar: proj.zfar(),
-            },
-            gltf::camera::Projection::Perspective(proj) => CameraPrefab::Perspective {
-                aspect: proj.aspect_ratio().ok_or_else(|| {
-                    format_err!(
-                        "Camera {} is a perspective projection, but has no aspect ratio",
-                        camera.index()
-                    )
-                })?,
-                fovy: proj.yfov(),
-                znear: proj.znear(),
-            },
-        });
+// Depth first search of a node by name
+fn find_node<'a, I>(nodes: I, name: &str) -> Option<gltf::Node<'a>>
+where
+    I: Iterator<Item = gltf::Node<'a>>,
+{
+    for node in nodes {
+        if node.name() == Some(name) {
+            return Some(node);
+        }
+        if let Some(found) = find_node(node.children(), name) {
+            return Some(found);
+        }
     }
+    None
+}
+
+fn initial_weights(
+    targets: &Option<MorphTargets>,
+    weights: Option<&[f32]>,
+) -> Option<GltfMorphWeights> {
+    targets.as_ref().map(|targets| {
+        match weights {
+            Some(weights) => GltfMorphWeights(weights.to_vec()),
+            None => GltfMorphWeights(vec![0.0; targets.targets.len()]),
+        }
+    })
+}
 
-    // Load lights
-    if let Some(light) = node.light() {
-        prefab.data_or_default(entity_index).light = Some(LightPrefab::from(light));
+// The morph weights are on the primitives, which are children of their node when it has several,
+// so the morph hierarchy has an index for every primitive, and the samplers of a node are
+// applied to all of its primitives
+fn morph_hierarchy(
+    morph_map: HashMap<usize, Vec<Entity>>,
+) -> (Vec<(usize, Entity)>, HashMap<usize, Vec<usize>>) {
+    let mut nodes = morph_map.into_iter().collect::<Vec<_>>();
+    nodes.sort_by_key(|(node, _)| *node);
+    let mut hierarchy = Vec::new();
+    let mut indices = HashMap::new();
+    for (node, entities) in nodes {
+        let node_indices = indices.entry(node).or_insert_with(Vec::new);
+        for entity in entities {
+            node_indices.push(hierarchy.len());
+            hierarchy.push((hierarchy.len(), entity));
+        }
     }
+    (hierarchy, indices)
+}
 
-    // check for skinning
-    let mut skin = node.skin().map(|skin| SkinInfo {
-        skin_index: skin.index(),
-        mesh_indices: Vec::default(),
-    });
+// Pushes the nodes of a scene into the world of its prefab
+struct NodeLoader<'a, 'b> {
+    gltf: &'a Gltf,
+    buffers: &'a Buffers,
+    options: &'a GltfSceneOptions,
+    assets: &'a mut ImportedAssets<'b>,
+    world: World,
+    node_map: HashMap<usize, Entity>,
+    // the primitive entities with morph targets of every node
+    morph_map: HashMap<usize, Vec<Entity>>,
+    // the skin index, skin entity and skinned mesh entities of every skinned node
+    skins: Vec<(usize, Entity, Vec<Entity>)>,
+}
 
-    let mut bounding_box = GltfNodeExtent::default();
+impl NodeLoader<'_, '_> {
+    fn load_node(
+        &mut self,
+        node: &gltf::Node<'_>,
+        parent: Entity,
+        parent_bounding_box: &mut GltfNodeExtent,
+    ) -> Result<(), Error> {
+        // Load transformation data, default will be identity
+        let local_transform = node_transform(node);
+        let rotation = *local_transform.rotation();
+        let entity = self.world.push((local_transform, Parent(parent)));
+        self.node_map.insert(node.index(), entity);
+        let mut entry = self.world.entry(entity).unwrap();
 
-    // load graphics
-    if let Some(mesh) = node.mesh() {
-        let mut graphics = load_mesh(&mesh, buffers, options)?;
-        match graphics.len().cmp(&1) {
-            Ordering::Equal => {
-                // single primitive can be loaded directly onto the node
-                let (mesh, material_index, bounds) = graphics.remove(0);
-                bounding_box.extend_range(&bounds);
-                let prefab_data = prefab.data_or_default(entity_index);
-                prefab_data.mesh = Some(mesh);
-                if let Some((material_id, material)) =
-                    material_index.and_then(|index| gltf.materials().nth(index).map(|m| (index, m)))
-                {
-                    material_set
-                        .materials
-                        .entry(material_id)
-                        .or_insert(load_material(&material, buffers, source.clone(), name)?);
-                    prefab_data.material_id = Some(material_id);
-                }
-                // if we have a skin we need to track the mesh entities
-                if let Some(ref mut skin) = skin {
-                    skin.mesh_indices.push(entity_index);
+        // Load node name.
+        if let Some(name) = node.name() {
+            entry.add_component(GltfName(name.to_string()));
+        }
+
+        // Load camera
+        if let Some(camera) = node.camera().filter(|_| self.options.load_cameras) {
+            entry.add_component(GltfCamera(match camera.projection() {
+                gltf::camera::Projection::Orthographic(proj) => {
+                    GltfProjection::Orthographic {
+                        left: -proj.xmag(),
+                        right: proj.xmag(),
+                        bottom: -proj.ymag(),
+                        top: proj.ymag(),
+                        znear: proj.znear(),
+                        zfar: proj.zfar(),
+                    }
                 }
-            }
-            Ordering::Greater => {
-                // if we have multiple primitives,
-                // we need to add each primitive as a child entity to the node
-                for (mesh, material_index, bounds) in graphics {
-                    let mesh_entity = prefab.add(Some(entity_index), None);
-                    let prefab_data = prefab.data_or_default(mesh_entity);
-                    prefab_data.transform = Some(Transform::default());
-                    prefab_data.mesh = Some(mesh);
-                    if let Some((material_id, material)) = material_index
-                        .and_then(|index| gltf.materials().nth(index).map(|m| (index, m)))
-                    {
-                        material_set
-                            .materials
-                            .entry(material_id)
-                            .or_insert(load_material(&material, buffers, source.clone(), name)?);
-                        prefab_data.material_id = Some(material_id);
+                gltf::camera::Projection::Perspective(proj) => {
+                    GltfProjection::Perspective {
+                        aspect: proj.aspect_ratio().ok_or_else(|| {
+                            format_err!(
+                                "Camera {} is a perspective projection, but has no aspect ratio",
+                                camera.index()
+                            )
+                        })?,
+                        fovy: proj.yfov(),
+                        znear: proj.znear(),
                     }
+                }
+            }));
+        }
 
-                    // if we have a skin we need to track the mesh entities
-                    if let Some(ref mut skin) = skin {
-                        skin.mesh_indices.push(mesh_entity);
-                    }
+        // Load lights
+        if let Some(light) = node.light().filter(|_| self.options.load_lights) {
+            entry.add_component(GltfLight(load_light(&light, &rotation)));
+        }
+
+        // if we have a skin we need to track the mesh entities
+        let mut skinned_meshes = Vec::new();
+        let mut bounding_box = GltfNodeExtent::default();
 
-                    // extent
-                    bounding_box.extend_range(&bounds);
-                    prefab_data.extent = Some(bounds.into());
+        // load graphics
+        if let Some(mesh) = node.mesh() {
+            let graphics = load_mesh(&mesh, self.buffers, self.options)?;
+            // initial weights of the morph targets, the node ones override the mesh ones
+            let weights = node.weights().or_else(|| mesh.weights());
+            let primitives = mesh.primitives().zip(graphics);
+            match mesh.primitives().len().cmp(&1) {
+                Ordering::Equal => {
+                    // single primitive can be loaded directly onto the node
+                    for (primitive, (builder, bounds, morph_targets)) in primitives {
+                        bounding_box.extend_range(&bounds);
+                        if morph_targets.is_some() {
+                            self.morph_map.entry(node.index()).or_default().push(entity);
+                        }
+                        self.load_primitive(
+                            entity,
+                            &mesh,
+                            &primitive,
+                            builder,
+                            morph_targets,
+                            weights,
+                        )?;
+                        skinned_meshes.push(entity);
+                    }
+                }
+                Ordering::Greater => {
+                    // if we have multiple primitives,
+                    // we need to add each primitive as a child entity to the node
+                    for (primitive, (builder, bounds, morph_targets)) in primitives {
+                        bounding_box.extend_range(&bounds);
+                        let mesh_entity = self.world.push((
+                            Transform::default(),
+                            Parent(entity),
+                            GltfBounds::from(GltfNodeExtent::from(bounds)),
+                        ));
+                        if morph_targets.is_some() {
+                            self.morph_map
+                                .entry(node.index())
+                                .or_default()
+                                .push(mesh_entity);
+                        }
+                        self.load_primitive(
+                            mesh_entity,
+                            &mesh,
+                            &primitive,
+                            builder,
+                            morph_targets,
+                            weights,
+                        )?;
+                        skinned_meshes.push(mesh_entity);
+                    }
                 }
+                Ordering::Less => {}
             }
-            Ordering::Less => {}
         }
+
+        // load children
+        for child in node.children() {
+            self.load_node(&child, entity, &mut bounding_box)?;
+        }
+        if bounding_box.valid() {
+            parent_bounding_box.extend(&bounding_box);
+            self.world
+                .entry(entity)
+                .unwrap()
+                .add_component(GltfBounds::from(bounding_box));
+        }
+
+        // propagate skin information
+        if let Some(skin) = node.skin() {
+            self.skins.push((skin.index(), entity, skinned_meshes));
+        }
+
+        Ok(())
     }
 
-    // load children
-    for child in node.children() {
-        let index = prefab.add(Some(entity_index), None);
-        load_node(
-            gltf,
-            &child,
-            index,
-            buffers,
-            options,
-            source.clone(),
-            name,
-            prefab,
-            node_map,
-            skin_map,
-            &mut bounding_box,
-            material_set,
-        )?;
+    fn load_primitive(
+        &mut self,
+        entity: Entity,
+        mesh: &gltf::Mesh<'_>,
+        primitive: &gltf::mesh::Primitive<'_>,
+        builder: MeshBuilder<'static>,
+        morph_targets: Option<MorphTargets>,
+        weights: Option<&[f32]>,
+    ) -> Result<(), Error> {
+        let key = format!("mesh/{}/{}", mesh.index(), primitive.index());
+        let handle = self
+            .assets
+            .get_or_insert(key, |_| Ok(MeshData::from(builder)))?;
+        let (material, transparent) =
+            load_material(&primitive.material(), self.buffers, self.assets)?;
+
+        let mut entry = self.world.entry(entity).unwrap();
+        entry.add_component(GltfMesh(handle));
+        entry.add_component(GltfMaterial(material));
+        if transparent {
+            entry.add_component(GltfTransparent);
+        }
+        if let Some(weights) = initial_weights(&morph_targets, weights) {
+            entry.add_component(weights);
+        }
+        if let Some(targets) = morph_targets {
+            entry.add_component(GltfMorphTargets(targets));
+        }
+        Ok(())
     }
-    if bounding_box.valid() {
-        parent_bounding_box.extend(&bounding_box);
-        prefab.data_or_default(entity_index).extent = Some(bounding_box);
+}
+
+#[cfg(test)]
+mod tests {
+    use amethyst_rendy::morph::MorphTarget;
+
+    use super::*;
+
+    fn targets(count: usize) -> MorphTargets {
+        MorphTargets {
+            positions: Vec::new(),
+            normals: None,
+            tangents: None,
+            attributes: MeshBuilder::new(),
+            targets: (0..count)
+                .map(|_| {
+                    MorphTarget {
+                        name: None,
+                        positions: Vec::new(),
+                        normals: Vec::new(),
+                        tangents: Vec::new(),
+                    }
+                })
+                .collect(),
+        }
     }
 
-    // propagate skin information
-    if let Some(skin) = skin {
-        skin_map.insert(node.index(), skin);
+    const SCENES: &str = r#"{
+        "asset": { "version": "2.0" },
+        "scenes": [{ "name": "menu", "nodes": [0] }, { "name": "level", "nodes": [1, 2] }],
+        "nodes": [
+            { "name": "camera" },
+            { "name": "ground" },
+            { "name": "house", "children": [3] },
+            { "name": "door" }
+        ]
+    }"#;
+
+    fn options() -> GltfSceneOptions {
+        GltfSceneOptions::default()
     }
 
-    Ok(())
+    #[test]
+    fn scenes_are_selected_by_name_or_index() {
+        let gltf = Gltf::from_slice(SCENES.as_bytes()).unwrap();
+
+        let by_name = GltfSceneOptions {
+            scene_name: Some("level".to_string()),
+            scene_index: Some(0),
+            ..options()
+        };
+        assert_eq!(get_scene_index(&gltf, &by_name).unwrap(), 1);
+        let by_index = GltfSceneOptions {
+            scene_index: Some(0),
+            ..options()
+        };
+        assert_eq!(get_scene_index(&gltf, &by_index).unwrap(), 0);
+
+        // no default scene, and more than one
+        assert!(get_scene_index(&gltf, &options()).is_err());
+        let missing = GltfSceneOptions {
+            scene_name: Some("credits".to_string()),
+            ..options()
+        };
+        assert!(get_scene_index(&gltf, &missing).is_err());
+        let out_of_range = GltfSceneOptions {
+            scene_index: Some(2),
+            ..options()
+        };
+        assert!(get_scene_index(&gltf, &out_of_range).is_err());
+    }
+
+    #[test]
+    fn node_subtrees_are_found_in_the_scene() {
+        let gltf = Gltf::from_slice(SCENES.as_bytes()).unwrap();
+        let level = gltf.scenes().nth(1).unwrap();
+
+        let names = |options: &GltfSceneOptions| {
+            root_nodes(&level, options)
+                .unwrap()
+                .iter()
+                .map(|node| node.name().unwrap().to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names(&options()), vec!["ground", "house"]);
+        let door = GltfSceneOptions {
+            node_name: Some("door".to_string()),
+            ..options()
+        };
+        assert_eq!(names(&door), vec!["door"]);
+
+        // only the nodes of the loaded scene are searched
+        let camera = GltfSceneOptions {
+            node_name: Some("camera".to_string()),
+            ..options()
+        };
+        assert!(root_nodes(&level, &camera).is_err());
+    }
+
+    #[test]
+    fn root_transform_remaps_the_coordinates() {
+        assert!(root_transform(&options()).is_none());
+
+        let z_up = GltfSceneOptions {
+            z_up: true,
+            scale: 0.01,
+            ..options()
+        };
+        let transform = root_transform(&z_up).unwrap();
+        assert_eq!(*transform.scale(), Vector3::from_element(0.01));
+        // Z up becomes Y up
+        let up = transform.rotation() * Vector3::z();
+        assert!((up - Vector3::y()).norm() < 1e-6);
+    }
+
+    #[test]
+    fn initial_weights_default_to_zero() {
+        assert_eq!(initial_weights(&None, Some(&[1.0][..])), None);
+        assert_eq!(
+            initial_weights(&Some(targets(2)), None),
+            Some(GltfMorphWeights(vec![0.0, 0.0]))
+        );
+        assert_eq!(
+            initial_weights(&Some(targets(2)), Some(&[0.5, 1.0][..])),
+            Some(GltfMorphWeights(vec![0.5, 1.0]))
+        );
+    }
+
+    #[test]
+    fn morph_hierarchy_has_an_index_per_primitive() {
+        let mut world = World::default();
+        let single = world.push((Transform::default(),));
+        let first = world.push((Transform::default(),));
+        let second = world.push((Transform::default(),));
+        let mut morph_map = HashMap::new();
+        morph_map.insert(3, vec![first, second]);
+        morph_map.insert(1, vec![single]);
+
+        let (hierarchy, indices) = morph_hierarchy(morph_map);
+
+        assert_eq!(hierarchy, vec![(0, single), (1, first), (2, second)]);
+        assert_eq!(indices[&1], vec![0]);
+        assert_eq!(indices[&3], vec![1, 2]);
+    }
 }
@@ -1,73 +1,73 @@
 use std::collections::HashMap;
 
-use amethyst_animation::{JointPrefab, SkinPrefab, SkinnablePrefab};
-use amethyst_assets::Prefab;
-use amethyst_core::math::{convert, Matrix4};
+use amethyst_core::{
+    ecs::{Entity, World},
+    math::Matrix4,
+};
 use amethyst_error::Error;
-use amethyst_rendy::skinning::JointTransformsPrefab;
 
 use super::Buffers;
-use crate::GltfPrefab;
+use crate::{
+    components::{GltfJoint, GltfSkin, GltfSkinnedMesh},
+    error,
+};
 
 pub fn load_skin(
     skin: &gltf::Skin<'_>,
     buffers: &Buffers,
-    skin_entity: usize,
-    node_map: &HashMap<usize, usize>,
-    meshes: Vec<usize>,
-    prefab: &mut Prefab<GltfPrefab>,
+    skin_entity: Entity,
+    node_map: &HashMap<usize, Entity>,
+    meshes: Vec<Entity>,
+    world: &mut World,
 ) -> Result<(), Error> {
     let joints = skin
         .joints()
         .map(|j| {
-            node_map.get(&j.index()).cloned().expect(
-                "Unreachable: `node_map` is initialized with the indexes from the `Gltf` object",
-            )
+            // the joints may be outside of the loaded nodes when only loading a subtree
+            node_map
+                .get(&j.index())
+                .cloned()
+                .ok_or_else(|| error::Error::MissingJoint(j.index()).into())
         })
-        .collect::<Vec<_>>();
+        .collect::<Result<Vec<_>, Error>>()?;
 
     let reader = skin.reader(|buffer| buffers.buffer(&buffer));
 
     let inverse_bind_matrices = reader
         .read_inverse_bind_matrices()
-        .map(|matrices| {
-            matrices
-                .map(Matrix4::from)
-                .map(convert::<_, Matrix4<f32>>)
-                .collect()
-        })
-        .unwrap_or_else(|| vec![Matrix4::identity(); joints.len()]);
+        .map(|matrices| matrices.collect())
+        .unwrap_or_else(|| vec![Matrix4::<f32>::identity().into(); joints.len()]);
 
-    for (_bind_index, joint_index) in joints.iter().enumerate() {
-        prefab
-            .data_or_default(*joint_index)
-            .skinnable
-            .get_or_insert_with(SkinnablePrefab::default)
-            .joint
-            .get_or_insert_with(JointPrefab::default)
-            .skins
-            .push(skin_entity);
+    for joint in &joints {
+        let mut entry = world.entry(*joint).expect("Joints are loaded nodes");
+        // a joint can be part of several skins
+        match entry.get_component_mut::<GltfJoint>() {
+            Ok(joint) => joint.skins.push(skin_entity),
+            Err(_) => {
+                entry.add_component(GltfJoint {
+                    skins: vec![skin_entity],
+                })
+            }
+        }
     }
-    let joint_transforms = JointTransformsPrefab::new(skin_entity, joints.len());
-    for mesh_index in &meshes {
-        prefab
-            .data_or_default(*mesh_index)
-            .skinnable
-            .get_or_insert_with(SkinnablePrefab::default)
-            .joint_transforms = Some(joint_transforms.clone());
+    for mesh in &meshes {
+        world
+            .entry(*mesh)
+            .expect("Skinned meshes are loaded entities")
+            .add_component(GltfSkinnedMesh {
+                skin: skin_entity,
+                joints: joints.len(),
+            });
     }
 
-    let skin_prefab = SkinPrefab {
-        joints,
-        meshes,
-        bind_shape_matrix: Matrix4::identity(),
-        inverse_bind_matrices,
-    };
-    prefab
-        .data_or_default(skin_entity)
-        .skinnable
-        .get_or_insert_with(SkinnablePrefab::default)
-        .skin = Some(skin_prefab);
+    world
+        .entry(skin_entity)
+        .expect("Skins are loaded nodes")
+        .add_component(GltfSkin {
+            joints,
+            meshes,
+            inverse_bind_matrices,
+        });
 
     Ok(())
 }
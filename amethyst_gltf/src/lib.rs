@@ -15,20 +15,16 @@
 
 use std::{collections::HashMap, ops::Range};
 
-use amethyst_assets::{AssetStorage, Handle, Loader, ProgressCounter};
-use amethyst_core::{
-    ecs::{Entity, Read, ReadExpect, Write, WriteStorage},
-    math::{convert, Point3, Vector3},
-    transform::Transform,
-    Named,
-};
-use amethyst_error::Error;
-use amethyst_rendy::{rendy::mesh::MeshBuilder, types::Mesh, visibility::BoundingSphere};
+use amethyst_assets::prefab::{legion_prefab, register_component_type, serde_diff, SerdeDiff};
+use amethyst_core::math::{convert, Point3, Vector3};
+use amethyst_rendy::visibility::BoundingSphere;
 use derivative::Derivative;
 use serde::{Deserialize, Serialize};
+use type_uuid::TypeUuid;
 
-pub use crate::format::GltfSceneFormat;
+pub use crate::format::{GltfImporter, GltfImporterState};
 
+pub mod components;
 mod error;
 mod format;
 
@@ -105,6 +101,15 @@ impl Into<BoundingSphere> for GltfNodeExtent {
     }
 }
 
+impl From<GltfNodeExtent> for components::GltfBounds {
+    fn from(extent: GltfNodeExtent) -> Self {
+        components::GltfBounds {
+            center: extent.centroid().coords.into(),
+            radius: extent.distance().magnitude() * 0.5,
+        }
+    }
+}
+
 impl From<Range<[f32; 3]>> for GltfNodeExtent {
     fn from(range: Range<[f32; 3]>) -> Self {
         GltfNodeExtent {
@@ -114,15 +119,32 @@ impl From<Range<[f32; 3]>> for GltfNodeExtent {
     }
 }
 
-/// Used during gltf loading to contain the materials used from scenes in the file
-#[derive(Debug, Derivative)]
-#[derivative(Default(bound = ""))]
-pub struct GltfMaterialSet {
-    pub(crate) materials: HashMap<usize, MaterialPrefab>,
+/// Names of the animation clips of a GLTF scene, mapping to the ids of the clips in the
+/// animation sets of the root entity of the scene. Unnamed clips are only found by id.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, SerdeDiff, TypeUuid)]
+#[uuid = "8e0b2f5a-3c4b-4f4e-9a52-0d6c2b1f7e93"]
+pub struct GltfAnimationNames {
+    #[serde_diff(opaque)]
+    pub(crate) names: HashMap<String, usize>,
+}
+
+register_component_type!(GltfAnimationNames);
+
+impl GltfAnimationNames {
+    /// Id of the animation clip with the given name
+    pub fn get(&self, name: &str) -> Option<usize> {
+        self.names.get(name).copied()
+    }
+
+    /// Iterate over the names of the animation clips and their ids
+    pub fn iter(&self) -> impl Iterator<Item = (&str, usize)> {
+        self.names.iter().map(|(name, id)| (name.as_str(), *id))
+    }
 }
 
 /// Options used when loading a GLTF file
-#[derive(Debug, Clone, Derivative, Serialize, Deserialize)]
+#[derive(Debug, Clone, Derivative, Serialize, Deserialize, TypeUuid)]
+#[uuid = "d0bd7d05-6a5b-4c7e-8f0e-3b9f61c2a4d8"]
 #[derivative(Default)]
 #[serde(default)]
 pub struct GltfSceneOptions {
@@ -141,11 +163,27 @@ pub struct GltfSceneOptions {
     /// Load vertex tangent data from the Gltf file
     pub load_tangents: bool,
     #[derivative(Default(value = "true"))]
-    /// Load animation data from the Gltf file
+    /// Load animation and skin data from the Gltf file, as animation sets of the root entity
     pub load_animations: bool,
     /// Flip the v coordinate for all texture coordinates
     pub flip_v_coord: bool,
     /// Load the given scene index, if not supplied will either load the default scene (if set),
     /// or the first scene (only if there is only one scene, otherwise an `Error` will be returned).
     pub scene_index: Option<usize>,
+    /// Load the scene with the given name, takes precedence over `scene_index`
+    pub scene_name: Option<String>,
+    /// Only load the subtree of the node with the given name, searched in the loaded scene
+    pub node_name: Option<String>,
+    #[derivative(Default(value = "true"))]
+    /// Load the cameras of the Gltf file
+    pub load_cameras: bool,
+    #[derivative(Default(value = "true"))]
+    /// Load the lights of the Gltf file
+    pub load_lights: bool,
+    #[derivative(Default(value = "1.0"))]
+    /// Uniform scale of the loaded scene, e.g. 0.01 for scenes exported in centimeters
+    pub scale: f32,
+    /// Convert the scene from a Z up coordinate system, for exports which kept the one of the
+    /// authoring tool instead of the Y up one of Gltf
+    pub z_up: bool,
 }
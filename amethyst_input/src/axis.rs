@@ -35,6 +35,20 @@ pub enum Axis {
         /// Zone to which the movement is relative
         radius: f32,
     },
+    /// Represents the unaccelerated relative motion of a mouse, independent of the cursor
+    /// position.
+    ///
+    /// The value is the motion accumulated during the current frame in device units multiplied
+    /// by `sensitivity`, so it is not limited to [-1..1].
+    RawMouse {
+        /// The axis being bound
+        axis: MouseAxis,
+        /// Factor applied to the raw motion.
+        sensitivity: f32,
+        /// Whether to pass the motion through the `MouseFilters` of the `InputHandler` first.
+        #[serde(default)]
+        filtered: bool,
+    },
     /// Represents the wheel on a PC mouse.
     MouseWheel {
         /// If this value is true then this axis is for the horizontal mouse wheel rather than the vertical mouse wheel.
@@ -109,6 +123,15 @@ impl Axis {
                     }
                 }
             }
+            Axis::RawMouse {
+                axis: self_axis, ..
+            } => {
+                if let Axis::RawMouse { axis, .. } = other {
+                    if self_axis == axis {
+                        return Some(Conflict::MouseAxis);
+                    }
+                }
+            }
             Axis::MouseWheel {
                 horizontal: self_horizontal,
             } => {
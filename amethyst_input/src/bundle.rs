@@ -3,16 +3,17 @@
 use std::{error, fmt, path::Path};
 
 use amethyst_config::{Config, ConfigError};
-use amethyst_core::{ecs::*, shrev::EventChannel};
+use amethyst_core::{ecs::*, shrev::EventChannel, timing::DeliveryTime};
 use amethyst_error::Error;
 use derivative::Derivative;
 use winit::event::Event;
 
 #[cfg(feature = "sdl_controller")]
 use crate::sdl_events_system::ControllerMappings;
-#[cfg(feature = "sdl_controller")]
-use crate::InputEvent;
-use crate::{BindingError, Bindings, InputHandler, InputSystem};
+use crate::{
+    BindingError, Bindings, InputHandler, InputSystem, MouseFilters, PlayerAssignments,
+    TimedInputEvent,
+};
 
 /// Bundle for adding the `InputHandler`.
 ///
@@ -31,6 +32,9 @@ use crate::{BindingError, Bindings, InputHandler, InputSystem};
 #[derive(Debug, Default)]
 pub struct InputBundle {
     bindings: Option<Bindings>,
+    players: Option<PlayerAssignments>,
+    text_input: bool,
+    mouse_filters: Option<MouseFilters>,
     #[cfg(feature = "sdl_controller")]
     controller_mappings: Option<ControllerMappings>,
 }
@@ -57,6 +61,34 @@ impl InputBundle {
         Ok(self.with_bindings(bindings))
     }
 
+    /// Use the provided device to player assignments with the `InputHandler`.
+    ///
+    /// This is mostly useful to select an `AssignmentPolicy` for local multiplayer.
+    pub fn with_player_assignments(mut self, players: PlayerAssignments) -> Self {
+        self.players = Some(players);
+        self
+    }
+
+    /// Enable `TextEntered` events from the start, see `InputHandler::set_text_input_enabled`.
+    pub fn with_text_input(mut self, enabled: bool) -> Self {
+        self.text_input = enabled;
+        self
+    }
+
+    /// Use the provided mouse filters with the `InputHandler`.
+    pub fn with_mouse_filters(mut self, mouse_filters: MouseFilters) -> Self {
+        self.mouse_filters = Some(mouse_filters);
+        self
+    }
+
+    /// Load mouse filters from file
+    pub fn with_mouse_filters_from_file<P: AsRef<Path>>(
+        self,
+        file: P,
+    ) -> Result<Self, ConfigError> {
+        Ok(self.with_mouse_filters(MouseFilters::load(file)?))
+    }
+
     /// Load SDL controller mappings from file
     #[cfg(feature = "sdl_controller")]
     pub fn with_sdl_controller_mappings(mut self, mappings: String) -> Self {
@@ -86,31 +118,40 @@ impl SystemBundle for InputBundle {
             .get_mut::<EventChannel<Event<'_, ()>>>()
             .expect("Window event channel not found in resources")
             .register_reader();
+        resources.get_or_default::<EventChannel<DeliveryTime>>();
+        let time_reader = resources
+            .get_mut::<EventChannel<DeliveryTime>>()
+            .unwrap()
+            .register_reader();
 
         let mut handler = InputHandler::new();
         if let Some(bindings) = self.bindings.as_ref() {
             handler.bindings = bindings.clone();
         }
+        if let Some(players) = self.players.as_ref() {
+            handler.players = players.clone();
+        }
+        handler.set_text_input_enabled(self.text_input);
+        if let Some(mouse_filters) = self.mouse_filters.as_ref() {
+            handler.mouse_filters = mouse_filters.clone();
+        }
 
         #[cfg(feature = "sdl_controller")]
         {
             use super::SdlEventsSystem;
             builder.add_thread_local(
                 // TODO: improve errors when migrating to failure
-                Box::new(
-                    SdlEventsSystem::new(
-                        &mut handler,
-                        &mut resources.get_mut::<EventChannel<InputEvent>>().unwrap(),
-                        &self.controller_mappings,
-                    )
-                    .unwrap(),
-                ),
+                Box::new(SdlEventsSystem::new(&mut handler, &self.controller_mappings).unwrap()),
             );
         }
 
         resources.insert(handler);
+        resources.insert(EventChannel::<TimedInputEvent>::new());
 
-        builder.add_system(InputSystem { reader });
+        builder.add_system(InputSystem {
+            reader,
+            time_reader,
+        });
 
         Ok(())
     }
@@ -17,6 +17,57 @@ pub enum ControllerAxis {
     LeftTrigger,
     /// The analog right trigger, not to be confused with the right bumper.
     RightTrigger,
+    /// Horizontal position of the first finger on the touchpad, from 0 (left) to 1 (right).
+    ///
+    /// Keeps the last position after the finger is lifted.
+    TouchpadX,
+    /// Vertical position of the first finger on the touchpad, from 0 (top) to 1 (bottom).
+    ///
+    /// Keeps the last position after the finger is lifted.
+    TouchpadY,
+    /// Angular velocity around the X axis of the gyroscope in radians per second.
+    GyroX,
+    /// Angular velocity around the Y axis of the gyroscope in radians per second.
+    GyroY,
+    /// Angular velocity around the Z axis of the gyroscope in radians per second.
+    GyroZ,
+    /// Acceleration along the X axis of the accelerometer in meters per second squared.
+    AccelX,
+    /// Acceleration along the Y axis of the accelerometer in meters per second squared.
+    AccelY,
+    /// Acceleration along the Z axis of the accelerometer in meters per second squared.
+    AccelZ,
+}
+
+/// Motion sensors found in some controllers.
+#[derive(Eq, PartialEq, Debug, Copy, Clone, Hash, Serialize, Deserialize)]
+pub enum ControllerSensor {
+    /// Measures angular velocity, updates the `Gyro` axes.
+    Gyroscope,
+    /// Measures acceleration, updates the `Accel` axes.
+    Accelerometer,
+}
+
+impl ControllerSensor {
+    /// Returns the X, Y and Z axes this sensor updates.
+    pub fn axes(self) -> [ControllerAxis; 3] {
+        match self {
+            ControllerSensor::Gyroscope => {
+                [
+                    ControllerAxis::GyroX,
+                    ControllerAxis::GyroY,
+                    ControllerAxis::GyroZ,
+                ]
+            }
+            ControllerSensor::Accelerometer => {
+                [
+                    ControllerAxis::AccelX,
+                    ControllerAxis::AccelY,
+                    ControllerAxis::AccelZ,
+                ]
+            }
+        }
+    }
 }
 
 /// Controller buttons matching SDL controller model
@@ -56,6 +107,8 @@ pub enum ControllerButton {
     Start,
     /// The centermost button on the controller. Large and green on an Xbox controller.
     Guide,
+    /// Clicking the touchpad on controllers which have one, like the DualShock 4.
+    Touchpad,
 }
 
 /// Controller events generated by the SDL events system.
@@ -96,6 +149,47 @@ pub enum ControllerEvent {
         /// The controller button.
         button: ControllerButton,
     },
+    /// A finger touched or moved on the touchpad of a controller.
+    ///
+    /// Corresponds to [`SDL_CONTROLLERTOUCHPADMOTION`].
+    ///
+    /// [`SDL_CONTROLLERTOUCHPADMOTION`]: https://wiki.libsdl.org/SDL_ControllerTouchpadEvent
+    ControllerTouchpadMoved {
+        /// The joystick instance id.
+        which: u32,
+        /// The index of the finger on the touchpad.
+        finger: u32,
+        /// The horizontal position, from 0 (left) to 1 (right).
+        x: f32,
+        /// The vertical position, from 0 (top) to 1 (bottom).
+        y: f32,
+        /// The pressure of the finger, from 0 to 1.
+        pressure: f32,
+    },
+    /// A finger was lifted from the touchpad of a controller.
+    ///
+    /// Corresponds to [`SDL_CONTROLLERTOUCHPADUP`].
+    ///
+    /// [`SDL_CONTROLLERTOUCHPADUP`]: https://wiki.libsdl.org/SDL_ControllerTouchpadEvent
+    ControllerTouchpadReleased {
+        /// The joystick instance id.
+        which: u32,
+        /// The index of the finger on the touchpad.
+        finger: u32,
+    },
+    /// A motion sensor of a controller reported new data.
+    ///
+    /// Corresponds to [`SDL_CONTROLLERSENSORUPDATE`].
+    ///
+    /// [`SDL_CONTROLLERSENSORUPDATE`]: https://wiki.libsdl.org/SDL_ControllerSensorEvent
+    ControllerSensorUpdated {
+        /// The joystick instance id.
+        which: u32,
+        /// The sensor that was updated.
+        sensor: ControllerSensor,
+        /// The X, Y and Z values of the sensor.
+        data: [f32; 3],
+    },
     /// Controller disconnect event.
     ///
     /// Corresponds to [`SDL_CONTROLLERDEVICEREMOVED`].
@@ -131,6 +225,35 @@ impl From<&ControllerEvent> for InputEvent {
             ControllerButtonReleased { which, button } => {
                 InputEvent::ControllerButtonReleased { which, button }
             }
+            ControllerTouchpadMoved {
+                which,
+                finger,
+                x,
+                y,
+                pressure,
+            } => {
+                InputEvent::ControllerTouchpadMoved {
+                    which,
+                    finger,
+                    x,
+                    y,
+                    pressure,
+                }
+            }
+            ControllerTouchpadReleased { which, finger } => {
+                InputEvent::ControllerTouchpadReleased { which, finger }
+            }
+            ControllerSensorUpdated {
+                which,
+                sensor,
+                data,
+            } => {
+                InputEvent::ControllerSensorUpdated {
+                    which,
+                    sensor,
+                    data,
+                }
+            }
             ControllerConnected { which } => InputEvent::ControllerConnected { which },
             ControllerDisconnected { which } => InputEvent::ControllerDisconnected { which },
         }
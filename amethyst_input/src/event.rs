@@ -1,11 +1,13 @@
-use std::borrow::Cow;
+use std::{borrow::Cow, time::Duration};
 
+use amethyst_core::timing::Instant;
 use serde::{Deserialize, Serialize};
 use winit::event::{MouseButton, VirtualKeyCode};
 
 use super::{
     button::Button,
-    controller::{ControllerAxis, ControllerButton},
+    controller::{ControllerAxis, ControllerButton, ControllerSensor},
+    player::InputDevice,
     scroll_direction::ScrollDirection,
 };
 
@@ -31,6 +33,13 @@ pub enum InputEvent {
     },
     /// A unicode character was received by the window.  Good for typing.
     KeyTyped(char),
+    /// A printable character was entered as text.
+    ///
+    /// Unlike `KeyTyped`, this is only sent while text input is enabled on the `InputHandler`,
+    /// and never contains control characters like backspace or escape. Characters composed through
+    /// an input method arrive here as well, so this is the event to use for chat boxes and other
+    /// text fields.
+    TextEntered(char),
     /// A mouse button was pressed down, sent exactly once per press.
     MouseButtonPressed(MouseButton),
     /// A mouse button was released, sent exactly once per release.
@@ -90,6 +99,35 @@ pub enum InputEvent {
         /// The button that was released.
         button: ControllerButton,
     },
+    /// A finger touched or moved on the touchpad of a controller.
+    ControllerTouchpadMoved {
+        /// The id for the controller whose touchpad was touched.
+        which: u32,
+        /// The index of the finger on the touchpad.
+        finger: u32,
+        /// The horizontal position, from 0 (left) to 1 (right).
+        x: f32,
+        /// The vertical position, from 0 (top) to 1 (bottom).
+        y: f32,
+        /// The pressure of the finger, from 0 to 1.
+        pressure: f32,
+    },
+    /// A finger was lifted from the touchpad of a controller.
+    ControllerTouchpadReleased {
+        /// The id for the controller whose touchpad was released.
+        which: u32,
+        /// The index of the finger on the touchpad.
+        finger: u32,
+    },
+    /// A motion sensor of a controller reported new data.
+    ControllerSensorUpdated {
+        /// The id for the controller whose sensor was updated.
+        which: u32,
+        /// The sensor that was updated.
+        sensor: ControllerSensor,
+        /// The X, Y and Z values of the sensor.
+        data: [f32; 3],
+    },
     /// New controller was connected.
     ControllerConnected {
         /// The id for the controller connected.
@@ -100,6 +138,20 @@ pub enum InputEvent {
         /// The id for the controller disconnected.
         which: u32,
     },
+    /// A device was assigned to a local player by the `InputHandler`.
+    PlayerDeviceAssigned {
+        /// The player the device now belongs to.
+        player: u32,
+        /// The assigned device.
+        device: InputDevice,
+    },
+    /// A device was removed from a local player because it was disconnected.
+    PlayerDeviceUnassigned {
+        /// The player the device belonged to.
+        player: u32,
+        /// The removed device.
+        device: InputDevice,
+    },
     /// The associated action had any related button or combination pressed.
     ///
     /// If a combination is bound to an action, it will be pressed
@@ -113,3 +165,30 @@ pub enum InputEvent {
     /// The associated action has its mouse wheel moved.
     ActionWheelMoved(Cow<'static, str>),
 }
+
+/// An `InputEvent` together with the time it was generated and its position among all events.
+///
+/// The `InputSystem` and the `SdlEventsSystem` write these to `EventChannel<TimedInputEvent>`
+/// alongside the plain events. All events produced by the same window or controller event share
+/// a timestamp, which is taken when the event loop or SDL delivers that event, not when the
+/// systems process it.
+#[derive(Clone, PartialEq, Debug)]
+pub struct TimedInputEvent {
+    /// The event itself.
+    pub event: InputEvent,
+    /// The time the event was delivered to the game.
+    pub timestamp: Instant,
+    /// Strictly increasing counter, giving the order in which events were generated even if
+    /// they share a timestamp.
+    pub sequence: u64,
+}
+
+impl TimedInputEvent {
+    /// Returns the time elapsed between an earlier event and this one, zero if `earlier` is
+    /// actually newer.
+    pub fn duration_since(&self, earlier: &TimedInputEvent) -> Duration {
+        self.timestamp
+            .checked_duration_since(earlier.timestamp)
+            .unwrap_or_default()
+    }
+}
@@ -0,0 +1,184 @@
+//! Identifiers for the symbols printed on input devices, used to show bindings in the UI.
+
+use std::borrow::Cow;
+
+use serde::{Deserialize, Serialize};
+use winit::event::MouseButton;
+
+use super::{
+    axis::Axis,
+    button::Button,
+    controller::{ControllerAxis, ControllerButton},
+    scroll_direction::ScrollDirection,
+};
+
+/// The family of a controller, which decides the symbols printed on its buttons.
+#[derive(Eq, PartialEq, Debug, Copy, Clone, Hash, Serialize, Deserialize)]
+pub enum ControllerKind {
+    /// Xbox controllers and other XInput devices.
+    Xbox,
+    /// DualShock and DualSense controllers.
+    PlayStation,
+    /// Nintendo Switch Pro controllers and Joy-Cons.
+    Switch,
+    /// Any controller that couldn't be identified.
+    Generic,
+}
+
+impl Default for ControllerKind {
+    fn default() -> Self {
+        ControllerKind::Generic
+    }
+}
+
+impl ControllerKind {
+    /// Guesses the kind of a controller from the name reported by the backend.
+    pub fn from_name(name: &str) -> Self {
+        let name = name.to_lowercase();
+        let any = |patterns: &[&str]| patterns.iter().any(|p| name.contains(p));
+        if any(&["xbox", "xinput", "x-box"]) {
+            ControllerKind::Xbox
+        } else if any(&[
+            "playstation",
+            "dualshock",
+            "dualsense",
+            "ps3",
+            "ps4",
+            "ps5",
+            "sony",
+        ]) {
+            ControllerKind::PlayStation
+        } else if any(&["nintendo", "switch", "joy-con", "joycon"]) {
+            ControllerKind::Switch
+        } else {
+            ControllerKind::Generic
+        }
+    }
+
+    fn prefix(self) -> &'static str {
+        match self {
+            ControllerKind::Xbox => "xbox",
+            ControllerKind::PlayStation => "ps",
+            ControllerKind::Switch => "switch",
+            ControllerKind::Generic => "gamepad",
+        }
+    }
+}
+
+/// Returns the glyph identifier of a controller button, e.g. `"xbox_a"` or `"ps_cross"`.
+///
+/// Buttons are named after the symbol printed on them, so the face buttons of a Switch
+/// controller are mirrored compared to an Xbox controller even though they are in the same spot.
+pub fn controller_button_glyph(
+    button: ControllerButton,
+    kind: ControllerKind,
+) -> Cow<'static, str> {
+    use self::{ControllerButton::*, ControllerKind::*};
+
+    let name = match (kind, button) {
+        (Xbox, A) | (Switch, B) => "a",
+        (Xbox, B) | (Switch, A) => "b",
+        (Xbox, X) | (Switch, Y) => "x",
+        (Xbox, Y) | (Switch, X) => "y",
+        (PlayStation, A) => "cross",
+        (PlayStation, B) => "circle",
+        (PlayStation, X) => "square",
+        (PlayStation, Y) => "triangle",
+        (Generic, A) => "south",
+        (Generic, B) => "east",
+        (Generic, X) => "west",
+        (Generic, Y) => "north",
+        (_, DPadDown) => "dpad_down",
+        (_, DPadLeft) => "dpad_left",
+        (_, DPadRight) => "dpad_right",
+        (_, DPadUp) => "dpad_up",
+        (Xbox, LeftShoulder) => "lb",
+        (Xbox, RightShoulder) => "rb",
+        (PlayStation, LeftShoulder) => "l1",
+        (PlayStation, RightShoulder) => "r1",
+        (Switch, LeftShoulder) => "l",
+        (Switch, RightShoulder) => "r",
+        (Generic, LeftShoulder) => "left_shoulder",
+        (Generic, RightShoulder) => "right_shoulder",
+        (PlayStation, LeftStick) => "l3",
+        (PlayStation, RightStick) => "r3",
+        (_, LeftStick) => "left_stick_press",
+        (_, RightStick) => "right_stick_press",
+        (Xbox, Back) => "view",
+        (PlayStation, Back) => "share",
+        (Switch, Back) => "minus",
+        (Generic, Back) => "back",
+        (Xbox, Start) => "menu",
+        (PlayStation, Start) => "options",
+        (Switch, Start) => "plus",
+        (Generic, Start) => "start",
+        (Xbox, Guide) => "guide",
+        (_, Guide) => "home",
+        (_, Touchpad) => "touchpad",
+    };
+    format!("{}_{}", kind.prefix(), name).into()
+}
+
+/// Returns the glyph identifier of a controller axis, e.g. `"xbox_left_stick"` or `"ps_l2"`.
+pub fn controller_axis_glyph(axis: ControllerAxis, kind: ControllerKind) -> Cow<'static, str> {
+    use self::{ControllerAxis::*, ControllerKind::*};
+
+    let name = match (kind, axis) {
+        (_, LeftX) | (_, LeftY) => "left_stick",
+        (_, RightX) | (_, RightY) => "right_stick",
+        (Xbox, LeftTrigger) => "lt",
+        (Xbox, RightTrigger) => "rt",
+        (PlayStation, LeftTrigger) => "l2",
+        (PlayStation, RightTrigger) => "r2",
+        (Switch, LeftTrigger) => "zl",
+        (Switch, RightTrigger) => "zr",
+        (Generic, LeftTrigger) => "left_trigger",
+        (Generic, RightTrigger) => "right_trigger",
+        (_, TouchpadX) | (_, TouchpadY) => "touchpad",
+        (_, GyroX) | (_, GyroY) | (_, GyroZ) | (_, AccelX) | (_, AccelY) | (_, AccelZ) => "motion",
+    };
+    format!("{}_{}", kind.prefix(), name).into()
+}
+
+/// Returns the glyph identifier of any button.
+///
+/// Keys are named `"key_"` followed by the `VirtualKeyCode`, e.g. `"key_Space"`, and mouse
+/// buttons `"mouse_"` followed by the button. Controller buttons use `kind` to select the symbol.
+pub fn button_glyph(button: Button, kind: ControllerKind) -> Cow<'static, str> {
+    match button {
+        Button::Key(key) => format!("key_{:?}", key).into(),
+        Button::ScanCode(scancode) => format!("scancode_{}", scancode).into(),
+        Button::Mouse(MouseButton::Left) => "mouse_left".into(),
+        Button::Mouse(MouseButton::Right) => "mouse_right".into(),
+        Button::Mouse(MouseButton::Middle) => "mouse_middle".into(),
+        Button::Mouse(MouseButton::Other(n)) => format!("mouse_{}", n).into(),
+        Button::MouseWheel(ScrollDirection::ScrollUp) => "mouse_wheel_up".into(),
+        Button::MouseWheel(ScrollDirection::ScrollDown) => "mouse_wheel_down".into(),
+        Button::MouseWheel(ScrollDirection::ScrollLeft) => "mouse_wheel_left".into(),
+        Button::MouseWheel(ScrollDirection::ScrollRight) => "mouse_wheel_right".into(),
+        Button::Controller(_, b) => controller_button_glyph(b, kind),
+    }
+}
+
+/// Returns the glyph identifiers of all inputs bound to an axis.
+pub fn axis_glyphs(axis: &Axis, kind: ControllerKind) -> Vec<Cow<'static, str>> {
+    match axis {
+        Axis::Emulated { pos, neg } => vec![button_glyph(*pos, kind), button_glyph(*neg, kind)],
+        Axis::Controller { axis, .. } => vec![controller_axis_glyph(*axis, kind)],
+        Axis::Mouse { .. } | Axis::RawMouse { .. } => vec!["mouse_move".into()],
+        Axis::MouseWheel { horizontal: false } => vec!["mouse_wheel".into()],
+        Axis::MouseWheel { horizontal: true } => vec!["mouse_wheel_horizontal".into()],
+        Axis::Multiple(axes) => axes.iter().flat_map(|a| axis_glyphs(a, kind)).collect(),
+    }
+}
+
+pub(crate) fn axis_uses_controller(axis: &Axis) -> bool {
+    match axis {
+        Axis::Emulated { pos, neg } => {
+            matches!(pos, Button::Controller(..)) || matches!(neg, Button::Controller(..))
+        }
+        Axis::Controller { .. } => true,
+        Axis::Multiple(axes) => axes.iter().any(axis_uses_controller),
+        _ => false,
+    }
+}
@@ -1,5 +1,7 @@
 //! World resource that handles all user input.
 
+use std::borrow::Cow;
+
 use amethyst_core::shrev::EventChannel;
 use smallvec::SmallVec;
 use winit::{
@@ -13,6 +15,7 @@ use winit::{
 use super::{
     controller::{ControllerButton, ControllerEvent},
     event::InputEvent::{self, *},
+    glyph::{axis_glyphs, axis_uses_controller, button_glyph},
     scroll_direction::ScrollDirection,
     *,
 };
@@ -58,20 +61,38 @@ pub struct InputHandler {
     pub bindings: Bindings,
     /// Keeps the current state of keyboard modifiers
     pub modifiers: KeyboardModifiersState,
+    /// Smoothing, acceleration and scaling applied to filtered mouse motion.
+    pub mouse_filters: MouseFilters,
+    /// Maps input devices to local players.
+    ///
+    /// Events are only emitted for changes made by the handler itself, i.e. automatic assignment
+    /// and removal of disconnected controllers.
+    pub players: PlayerAssignments,
     /// Encodes the VirtualKeyCode and corresponding scancode.
     pressed_keys: SmallVec<[(VirtualKeyCode, u32); 12]>,
     pressed_mouse_buttons: SmallVec<[MouseButton; 12]>,
     pressed_controller_buttons: SmallVec<[(u32, ControllerButton); 12]>,
     /// Holds current state of all connected controller axes
     controller_axes: SmallVec<[(u32, ControllerAxis, f32); 24]>,
+    /// Fingers currently on a controller touchpad as controller id, finger, x, y and pressure.
+    controller_touches: SmallVec<[(u32, u32, f32, f32, f32); 4]>,
     /// A list of raw and mapped ids for currently connected controllers.
     /// First number represents mapped ID visible to the user code,
     /// while second is the ID used by incoming events.
     connected_controllers: SmallVec<[(u32, u32); 8]>,
+    /// Kinds of connected controllers, by controller id. Missing entries are `Generic`.
+    controller_kinds: SmallVec<[(u32, ControllerKind); 8]>,
+    /// The device which most recently had a button pressed.
+    active_device: Option<InputDevice>,
     mouse_last_position: Option<(f32, f32)>,
     mouse_position: Option<(f32, f32)>,
+    /// Raw mouse motion accumulated during the current frame.
+    raw_mouse_delta: (f32, f32),
     mouse_wheel_vertical: f32,
     mouse_wheel_horizontal: f32,
+    text_input_enabled: bool,
+    /// Sequence number of the next `TimedInputEvent`, shared by all the input sources.
+    next_sequence: u64,
 }
 
 impl InputHandler {
@@ -100,6 +121,9 @@ impl InputHandler {
                     }
                     WindowEvent::ReceivedCharacter(c) => {
                         event_handler.single_write(KeyTyped(c));
+                        if self.text_input_enabled && is_text_char(c) {
+                            event_handler.single_write(TextEntered(c));
+                        }
                     }
                     WindowEvent::KeyboardInput {
                         input:
@@ -113,6 +137,7 @@ impl InputHandler {
                     } => {
                         if self.pressed_keys.iter().all(|&k| k.0 != key_code) {
                             self.pressed_keys.push((key_code, scancode));
+                            self.device_used(InputDevice::KeyboardMouse, event_handler);
                             event_handler.iter_write(
                                 [
                                     KeyPressed { key_code, scancode },
@@ -195,6 +220,7 @@ impl InputHandler {
                             .all(|&b| b != mouse_button)
                         {
                             self.pressed_mouse_buttons.push(mouse_button);
+                            self.device_used(InputDevice::KeyboardMouse, event_handler);
                             event_handler.iter_write(
                                 [
                                     MouseButtonPressed(mouse_button),
@@ -279,6 +305,8 @@ impl InputHandler {
                     DeviceEvent::MouseMotion {
                         delta: (delta_x, delta_y),
                     } => {
+                        self.raw_mouse_delta.0 += delta_x as f32;
+                        self.raw_mouse_delta.1 += delta_y as f32;
                         event_handler.single_write(MouseMoved {
                             delta_x: delta_x as f32,
                             delta_y: delta_y as f32,
@@ -328,13 +356,45 @@ impl InputHandler {
         match *event {
             ControllerAxisMoved { which, axis, value } => {
                 if let Some(controller_id) = self.controller_idx_to_id(which) {
-                    self.controller_axes
-                        .iter_mut()
-                        .find(|(id, a, _)| *id == controller_id && *a == axis)
-                        .map(|entry| entry.2 = value)
-                        .unwrap_or_else(|| {
-                            self.controller_axes.push((controller_id, axis, value));
-                        });
+                    self.set_controller_axis(controller_id, axis, value);
+                    event_handler.single_write(event.into());
+                }
+            }
+            ControllerTouchpadMoved {
+                which,
+                finger,
+                x,
+                y,
+                pressure,
+            } => {
+                if let Some(controller_id) = self.controller_idx_to_id(which) {
+                    self.controller_touches
+                        .retain(|t| t.0 != controller_id || t.1 != finger);
+                    self.controller_touches
+                        .push((controller_id, finger, x, y, pressure));
+                    if finger == 0 {
+                        self.set_controller_axis(controller_id, ControllerAxis::TouchpadX, x);
+                        self.set_controller_axis(controller_id, ControllerAxis::TouchpadY, y);
+                    }
+                    event_handler.single_write(event.into());
+                }
+            }
+            ControllerTouchpadReleased { which, finger } => {
+                if let Some(controller_id) = self.controller_idx_to_id(which) {
+                    self.controller_touches
+                        .retain(|t| t.0 != controller_id || t.1 != finger);
+                    event_handler.single_write(event.into());
+                }
+            }
+            ControllerSensorUpdated {
+                which,
+                sensor,
+                data,
+            } => {
+                if let Some(controller_id) = self.controller_idx_to_id(which) {
+                    for (&axis, &value) in sensor.axes().iter().zip(data.iter()) {
+                        self.set_controller_axis(controller_id, axis, value);
+                    }
                     event_handler.single_write(event.into());
                 }
             }
@@ -347,6 +407,7 @@ impl InputHandler {
                     {
                         self.pressed_controller_buttons
                             .push((controller_id, button));
+                        self.device_used(InputDevice::Controller(controller_id), event_handler);
                         event_handler.iter_write(
                             [
                                 event.into(),
@@ -415,6 +476,13 @@ impl InputHandler {
                         .all(|&ids| ids.0 != controller_id)
                     {
                         self.connected_controllers.push((controller_id, which));
+                        event_handler.single_write(event.into());
+                        if self.players.policy() == AssignmentPolicy::OnConnect {
+                            let device = InputDevice::Controller(controller_id);
+                            if let Some(player) = self.players.auto_assign(device) {
+                                event_handler.single_write(PlayerDeviceAssigned { player, device });
+                            }
+                        }
                     }
                 }
             }
@@ -427,14 +495,47 @@ impl InputHandler {
                     if let Some(i) = index {
                         self.connected_controllers.swap_remove(i);
                         self.controller_axes.retain(|a| a.0 != controller_id);
+                        self.controller_kinds.retain(|k| k.0 != controller_id);
+                        self.controller_touches.retain(|t| t.0 != controller_id);
+                        if self.active_device == Some(InputDevice::Controller(controller_id)) {
+                            self.active_device = None;
+                        }
                         self.pressed_controller_buttons
                             .retain(|b| b.0 != controller_id);
+                        event_handler.single_write(event.into());
+                        let device = InputDevice::Controller(controller_id);
+                        if let Some(player) = self.players.unassign(device) {
+                            event_handler.single_write(PlayerDeviceUnassigned { player, device });
+                        }
                     }
                 }
             }
         }
     }
 
+    /// Updates the input handler with text from a source other than the window, for example an
+    /// input method or an on-screen keyboard.
+    ///
+    /// Sends a `TextEntered` event for every printable character if text input is enabled.
+    pub fn send_text(&mut self, text: &str, event_handler: &mut EventChannel<InputEvent>) {
+        if self.text_input_enabled {
+            event_handler.iter_write(text.chars().filter(|&c| is_text_char(c)).map(TextEntered));
+        }
+    }
+
+    /// Enables or disables `TextEntered` events.
+    ///
+    /// Enable this while a text field has focus. Key and action events are sent regardless of
+    /// this setting.
+    pub fn set_text_input_enabled(&mut self, enabled: bool) {
+        self.text_input_enabled = enabled;
+    }
+
+    /// Returns true if `TextEntered` events are sent.
+    pub fn text_input_enabled(&self) -> bool {
+        self.text_input_enabled
+    }
+
     /// This function is to be called whenever a frame begins. It resets some input values.
     ///
     /// The `InputSystem` will call this automatically. If you're using that system, you
@@ -443,6 +544,15 @@ impl InputHandler {
         self.mouse_wheel_vertical = 0.0;
         self.mouse_wheel_horizontal = 0.0;
         self.mouse_last_position = self.mouse_position;
+        self.mouse_filters.push_frame(self.raw_mouse_delta);
+        self.raw_mouse_delta = (0.0, 0.0);
+    }
+
+    /// Returns the sequence number of the next `TimedInputEvent` and advances it.
+    pub(crate) fn take_sequence(&mut self) -> u64 {
+        let sequence = self.next_sequence;
+        self.next_sequence += 1;
+        sequence
     }
 
     /// Returns an iterator over all keys that are down.
@@ -507,6 +617,25 @@ impl InputHandler {
             .any(|&(id, b)| id == controller_id && b == controller_button)
     }
 
+    /// Returns the current value of a controller axis, without any dead zone applied.
+    ///
+    /// This includes the analog triggers, the touchpad and the motion sensors.
+    pub fn controller_axis_value(&self, controller_id: u32, axis: ControllerAxis) -> Option<f32> {
+        self.controller_axes
+            .iter()
+            .find(|&&(id, a, _)| id == controller_id && a == axis)
+            .map(|&(_, _, value)| value)
+    }
+
+    /// Returns the position and pressure of a finger on the touchpad of a controller, if that
+    /// finger is currently touching it.
+    pub fn controller_touch(&self, controller_id: u32, finger: u32) -> Option<(f32, f32, f32)> {
+        self.controller_touches
+            .iter()
+            .find(|t| t.0 == controller_id && t.1 == finger)
+            .map(|t| (t.2, t.3, t.4))
+    }
+
     /// List controller ids of all currently connected controllers.
     /// IDs are assigned sequentially in the order of connection
     /// starting from 0, always taking the lowest next free number.
@@ -529,6 +658,19 @@ impl InputHandler {
         self.mouse_position
     }
 
+    /// Returns the unaccelerated mouse motion accumulated during the current frame.
+    ///
+    /// Unlike the cursor position, this keeps reporting motion while the cursor is grabbed or
+    /// sits at the edge of the window, which makes it the right choice for mouse look.
+    pub fn raw_mouse_delta(&self) -> (f32, f32) {
+        self.raw_mouse_delta
+    }
+
+    /// Returns the mouse motion of the current frame after applying the active `MouseFilter`.
+    pub fn filtered_mouse_delta(&self) -> (f32, f32) {
+        self.mouse_filters.apply(self.raw_mouse_delta)
+    }
+
     /// Returns an iterator over all buttons that are down.
     pub fn buttons_that_are_down(&self) -> impl Iterator<Item = Button> + '_ {
         let mouse_buttons = self
@@ -559,10 +701,37 @@ impl InputHandler {
         }
     }
 
-    fn axis_value_impl(&self, a: &Axis) -> f32 {
+    /// Checks if a button is down on a device assigned to the given player.
+    ///
+    /// Keyboard and mouse buttons are only considered if the player owns
+    /// `InputDevice::KeyboardMouse`. The controller id of `Button::Controller` is interpreted
+    /// relative to the player, so `Button::Controller(0, ..)` refers to the first controller
+    /// assigned to that player.
+    pub fn player_button_is_down(&self, player: u32, button: Button) -> bool {
+        match button {
+            Button::Controller(index, b) => {
+                self.players
+                    .controller_of(player, index)
+                    .map(|id| self.controller_button_is_down(id, b))
+                    .unwrap_or(false)
+            }
+            _ => {
+                self.players.owns(player, InputDevice::KeyboardMouse) && self.button_is_down(button)
+            }
+        }
+    }
+
+    fn is_down(&self, player: Option<u32>, button: Button) -> bool {
+        match player {
+            Some(player) => self.player_button_is_down(player, button),
+            None => self.button_is_down(button),
+        }
+    }
+
+    fn axis_value_impl(&self, a: &Axis, player: Option<u32>) -> f32 {
         match a {
             Axis::Emulated { pos, neg, .. } => {
-                match (self.button_is_down(*pos), self.button_is_down(*neg)) {
+                match (self.is_down(player, *pos), self.is_down(player, *neg)) {
                     (true, false) => 1.0,
                     (false, true) => -1.0,
                     _ => 0.0,
@@ -575,9 +744,18 @@ impl InputHandler {
                 dead_zone,
                 ..
             } => {
+                let controller_id = match player {
+                    Some(player) => {
+                        match self.players.controller_of(player, *controller_id) {
+                            Some(id) => id,
+                            None => return 0.0,
+                        }
+                    }
+                    None => *controller_id,
+                };
                 self.controller_axes
                     .iter()
-                    .find(|&&(id, a, _)| id == *controller_id && a == *axis)
+                    .find(|&&(id, a, _)| id == controller_id && a == *axis)
                     .map(|&(_, _, val)| if *invert { -val } else { val })
                     .map(|val| {
                         let dead_zone = *dead_zone as f32;
@@ -591,6 +769,11 @@ impl InputHandler {
                     })
                     .unwrap_or(0.0)
             }
+            Axis::Mouse { .. } | Axis::RawMouse { .. } | Axis::MouseWheel { .. }
+                if player.map_or(false, |p| !self.players.owns(p, InputDevice::KeyboardMouse)) =>
+            {
+                0.0
+            }
             Axis::Mouse {
                 axis,
                 over_extendable,
@@ -616,10 +799,26 @@ impl InputHandler {
                     rel_delta
                 }
             }
+            Axis::RawMouse {
+                axis,
+                sensitivity,
+                filtered,
+            } => {
+                let (x, y) = if *filtered {
+                    self.filtered_mouse_delta()
+                } else {
+                    self.raw_mouse_delta
+                };
+                let delta = match axis {
+                    MouseAxis::X => x,
+                    MouseAxis::Y => y,
+                };
+                delta * sensitivity
+            }
             Axis::MouseWheel { horizontal } => self.mouse_wheel_value(*horizontal),
             Axis::Multiple(axes) => {
                 axes.iter()
-                    .map(|a| self.axis_value_impl(a))
+                    .map(|a| self.axis_value_impl(a, player))
                     .max_by(|x, y| x.abs().partial_cmp(&y.abs()).unwrap())
                     .unwrap_or(0.0)
             }
@@ -628,7 +827,21 @@ impl InputHandler {
 
     /// Returns the value of an axis by the id, if the id doesn't exist this returns None.
     pub fn axis_value(&self, id: &str) -> Option<f32> {
-        self.bindings.axes.get(id).map(|a| self.axis_value_impl(a))
+        self.bindings
+            .axes
+            .get(id)
+            .map(|a| self.axis_value_impl(a, None))
+    }
+
+    /// Returns the value of an axis by the id, only taking devices assigned to the given player
+    /// into account. If the id doesn't exist this returns None.
+    ///
+    /// See `player_button_is_down` for how bindings are matched against the player's devices.
+    pub fn player_axis_value(&self, player: u32, id: &str) -> Option<f32> {
+        self.bindings
+            .axes
+            .get(id)
+            .map(|a| self.axis_value_impl(a, Some(player)))
     }
 
     /// Returns true if any of the actions bindings is down.
@@ -644,6 +857,122 @@ impl InputHandler {
         })
     }
 
+    /// Returns true if any of the actions bindings is down on a device assigned to the given player.
+    ///
+    /// See `player_button_is_down` for how bindings are matched against the player's devices.
+    pub fn player_action_is_down(&self, player: u32, action: &str) -> Option<bool> {
+        self.bindings.actions.get(action).map(|combinations| {
+            combinations.iter().any(|combination| {
+                combination
+                    .iter()
+                    .all(|button| self.player_button_is_down(player, *button))
+            })
+        })
+    }
+
+    /// Sets the kind of a connected controller, which selects the glyphs used for its buttons.
+    ///
+    /// `which` is the id used by incoming controller events, as with `send_controller_event`.
+    /// Backends should call this right after sending `ControllerConnected`, for example with
+    /// `ControllerKind::from_name`.
+    pub fn set_controller_kind(&mut self, which: u32, kind: ControllerKind) {
+        if let Some(controller_id) = self.controller_idx_to_id(which) {
+            self.controller_kinds.retain(|k| k.0 != controller_id);
+            self.controller_kinds.push((controller_id, kind));
+        }
+    }
+
+    /// Returns the kind of a controller, `Generic` if it is unknown.
+    pub fn controller_kind(&self, controller_id: u32) -> ControllerKind {
+        self.controller_kinds
+            .iter()
+            .find(|k| k.0 == controller_id)
+            .map(|k| k.1)
+            .unwrap_or_default()
+    }
+
+    /// Returns the device which most recently had a button pressed.
+    pub fn active_device(&self) -> Option<InputDevice> {
+        self.active_device
+    }
+
+    /// Returns the glyph identifiers of the buttons to press for an action on the active device.
+    ///
+    /// Picks the first combination bound to the action which only uses the active device, and falls
+    /// back to the first combination if there is none. Returns None if the action doesn't exist.
+    /// See `button_glyph` for the format of the identifiers.
+    pub fn action_glyphs(&self, action: &str) -> Option<Vec<Cow<'static, str>>> {
+        let combinations = self.bindings.actions.get(action)?;
+        let uses_controller = self.active_controller().is_some();
+        let combination = combinations
+            .iter()
+            .find(|c| {
+                c.iter()
+                    .all(|b| matches!(b, Button::Controller(..)) == uses_controller)
+            })
+            .or_else(|| combinations.first())?;
+        let kind = self.glyph_kind();
+        Some(combination.iter().map(|&b| button_glyph(b, kind)).collect())
+    }
+
+    /// Returns the glyph identifiers of the inputs bound to an axis on the active device.
+    ///
+    /// For `Axis::Multiple` only the alternatives using the active device are considered, unless
+    /// there are none. Returns None if the axis doesn't exist.
+    pub fn axis_glyphs(&self, id: &str) -> Option<Vec<Cow<'static, str>>> {
+        let axis = self.bindings.axes.get(id)?;
+        let kind = self.glyph_kind();
+        let uses_controller = self.active_controller().is_some();
+        if let Axis::Multiple(axes) = axis {
+            let glyphs = axes
+                .iter()
+                .filter(|a| axis_uses_controller(a) == uses_controller)
+                .flat_map(|a| axis_glyphs(a, kind))
+                .collect::<Vec<_>>();
+            if !glyphs.is_empty() {
+                return Some(glyphs);
+            }
+        }
+        Some(axis_glyphs(axis, kind))
+    }
+
+    fn active_controller(&self) -> Option<u32> {
+        match self.active_device {
+            Some(InputDevice::Controller(id)) => Some(id),
+            _ => None,
+        }
+    }
+
+    /// The controller kind used for glyphs, either of the active or the first connected controller.
+    fn glyph_kind(&self) -> ControllerKind {
+        self.active_controller()
+            .or_else(|| self.connected_controllers().next())
+            .map(|id| self.controller_kind(id))
+            .unwrap_or_default()
+    }
+
+    /// Records the device as active and assigns it to the next free player if the policy
+    /// asks for it.
+    fn device_used(&mut self, device: InputDevice, event_handler: &mut EventChannel<InputEvent>) {
+        self.active_device = Some(device);
+        if self.players.policy() == AssignmentPolicy::OnFirstInput {
+            if let Some(player) = self.players.auto_assign(device) {
+                event_handler.single_write(PlayerDeviceAssigned { player, device });
+            }
+        }
+    }
+
+    fn set_controller_axis(&mut self, controller_id: u32, axis: ControllerAxis, value: f32) {
+        match self
+            .controller_axes
+            .iter_mut()
+            .find(|(id, a, _)| *id == controller_id && *a == axis)
+        {
+            Some(entry) => entry.2 = value,
+            None => self.controller_axes.push((controller_id, axis, value)),
+        }
+    }
+
     /// Retrieve next free controller number to allocate new controller to
     fn alloc_controller_id(&self) -> u32 {
         let mut i = 0u32;
@@ -815,7 +1144,7 @@ impl InputHandler {
 
 #[cfg(test)]
 mod tests {
-    use std::{borrow::Cow, fmt::Debug};
+    use std::fmt::Debug;
 
     use winit::{
         event::{DeviceId, ModifiersState, ScanCode},
@@ -1283,6 +1612,276 @@ mod tests {
         assert_ulps_eq!(handler.mouse_wheel_value(true), -1.0);
     }
 
+    #[test]
+    fn player_routing() {
+        // Connect two controllers with automatic assignment, bind an action and an axis
+        // relative to the player and check each player only sees their own controller.
+
+        let mut handler = InputHandler::new();
+        handler.players = PlayerAssignments::new(AssignmentPolicy::OnConnect, 2);
+        let mut events = EventChannel::<InputEvent>::new();
+        let mut reader = events.register_reader();
+
+        const JUMP: Cow<'static, str> = Cow::Borrowed("jump");
+        const MOVE: Cow<'static, str> = Cow::Borrowed("move");
+
+        handler
+            .bindings
+            .insert_action_binding(
+                JUMP,
+                [Button::Controller(0, ControllerButton::A)].iter().cloned(),
+            )
+            .unwrap();
+        handler
+            .bindings
+            .insert_axis(
+                MOVE,
+                Axis::Controller {
+                    controller_id: 0,
+                    axis: ControllerAxis::LeftX,
+                    invert: false,
+                    dead_zone: 0.0,
+                },
+            )
+            .unwrap();
+
+        handler.send_controller_event(
+            &ControllerEvent::ControllerConnected { which: 7 },
+            &mut events,
+        );
+        handler.send_controller_event(
+            &ControllerEvent::ControllerConnected { which: 9 },
+            &mut events,
+        );
+        let event_vec = events.read(&mut reader).cloned().collect::<Vec<_>>();
+        sets_are_equal(
+            &event_vec,
+            &[
+                InputEvent::ControllerConnected { which: 7 },
+                InputEvent::ControllerConnected { which: 9 },
+                InputEvent::PlayerDeviceAssigned {
+                    player: 0,
+                    device: InputDevice::Controller(0),
+                },
+                InputEvent::PlayerDeviceAssigned {
+                    player: 1,
+                    device: InputDevice::Controller(1),
+                },
+            ],
+        );
+
+        handler.send_controller_event(
+            &ControllerEvent::ControllerButtonPressed {
+                which: 9,
+                button: ControllerButton::A,
+            },
+            &mut events,
+        );
+        handler.send_controller_event(
+            &ControllerEvent::ControllerAxisMoved {
+                which: 7,
+                axis: ControllerAxis::LeftX,
+                value: 0.5,
+            },
+            &mut events,
+        );
+        assert_eq!(handler.player_action_is_down(0, &JUMP), Some(false));
+        assert_eq!(handler.player_action_is_down(1, &JUMP), Some(true));
+        assert_eq!(handler.player_axis_value(0, &MOVE), Some(0.5));
+        assert_eq!(handler.player_axis_value(1, &MOVE), Some(0.0));
+        assert_eq!(handler.player_action_is_down(2, &JUMP), Some(false));
+
+        events.read(&mut reader).for_each(drop);
+        handler.send_controller_event(
+            &ControllerEvent::ControllerDisconnected { which: 9 },
+            &mut events,
+        );
+        let event_vec = events.read(&mut reader).cloned().collect::<Vec<_>>();
+        sets_are_equal(
+            &event_vec,
+            &[
+                InputEvent::ControllerDisconnected { which: 9 },
+                InputEvent::PlayerDeviceUnassigned {
+                    player: 1,
+                    device: InputDevice::Controller(1),
+                },
+            ],
+        );
+        assert_eq!(handler.player_action_is_down(1, &JUMP), Some(false));
+    }
+
+    #[test]
+    fn text_input_toggle() {
+        let mut handler = InputHandler::new();
+        let mut events = EventChannel::<InputEvent>::new();
+        let mut reader = events.register_reader();
+
+        handler.send_event(&received_character('a'), &mut events);
+        let event_vec = events.read(&mut reader).cloned().collect::<Vec<_>>();
+        assert_eq!(event_vec, vec![InputEvent::KeyTyped('a')]);
+
+        handler.set_text_input_enabled(true);
+        handler.send_event(&received_character('a'), &mut events);
+        handler.send_event(&received_character('\u{8}'), &mut events);
+        let event_vec = events.read(&mut reader).cloned().collect::<Vec<_>>();
+        assert_eq!(
+            event_vec,
+            vec![
+                InputEvent::KeyTyped('a'),
+                InputEvent::TextEntered('a'),
+                InputEvent::KeyTyped('\u{8}'),
+            ]
+        );
+
+        handler.send_text("hé\r", &mut events);
+        let event_vec = events.read(&mut reader).cloned().collect::<Vec<_>>();
+        assert_eq!(
+            event_vec,
+            vec![InputEvent::TextEntered('h'), InputEvent::TextEntered('é')]
+        );
+    }
+
+    #[test]
+    fn glyphs_follow_active_device() {
+        let mut handler = InputHandler::new();
+        let mut events = EventChannel::<InputEvent>::new();
+
+        const JUMP: Cow<'static, str> = Cow::Borrowed("jump");
+
+        handler
+            .bindings
+            .insert_action_binding(JUMP, [Button::Key(VirtualKeyCode::Space)].iter().cloned())
+            .unwrap();
+        handler
+            .bindings
+            .insert_action_binding(
+                JUMP,
+                [Button::Controller(0, ControllerButton::A)].iter().cloned(),
+            )
+            .unwrap();
+        handler.send_controller_event(
+            &ControllerEvent::ControllerConnected { which: 3 },
+            &mut events,
+        );
+        handler.set_controller_kind(
+            3,
+            ControllerKind::from_name("Sony DualSense Wireless Controller"),
+        );
+
+        handler.send_event(&key_press(57, VirtualKeyCode::Space), &mut events);
+        assert_eq!(handler.active_device(), Some(InputDevice::KeyboardMouse));
+        assert_eq!(
+            handler.action_glyphs(&JUMP),
+            Some(vec![Cow::from("key_Space")])
+        );
+
+        handler.send_controller_event(
+            &ControllerEvent::ControllerButtonPressed {
+                which: 3,
+                button: ControllerButton::A,
+            },
+            &mut events,
+        );
+        assert_eq!(handler.active_device(), Some(InputDevice::Controller(0)));
+        assert_eq!(
+            handler.action_glyphs(&JUMP),
+            Some(vec![Cow::from("ps_cross")])
+        );
+        assert_eq!(handler.action_glyphs("missing"), None);
+    }
+
+    #[test]
+    fn sensor_and_touchpad_axes() {
+        let mut handler = InputHandler::new();
+        let mut events = EventChannel::<InputEvent>::new();
+
+        const TILT: Cow<'static, str> = Cow::Borrowed("tilt");
+
+        handler
+            .bindings
+            .insert_axis(
+                TILT,
+                Axis::Controller {
+                    controller_id: 0,
+                    axis: ControllerAxis::GyroY,
+                    invert: true,
+                    dead_zone: 0.0,
+                },
+            )
+            .unwrap();
+        handler.send_controller_event(
+            &ControllerEvent::ControllerConnected { which: 1 },
+            &mut events,
+        );
+        handler.send_controller_event(
+            &ControllerEvent::ControllerSensorUpdated {
+                which: 1,
+                sensor: ControllerSensor::Gyroscope,
+                data: [0.25, 0.5, 0.75],
+            },
+            &mut events,
+        );
+        assert_eq!(handler.axis_value(&TILT), Some(-0.5));
+        assert_eq!(
+            handler.controller_axis_value(0, ControllerAxis::GyroZ),
+            Some(0.75)
+        );
+
+        handler.send_controller_event(
+            &ControllerEvent::ControllerTouchpadMoved {
+                which: 1,
+                finger: 0,
+                x: 0.2,
+                y: 0.8,
+                pressure: 1.0,
+            },
+            &mut events,
+        );
+        assert_eq!(handler.controller_touch(0, 0), Some((0.2, 0.8, 1.0)));
+        assert_eq!(
+            handler.controller_axis_value(0, ControllerAxis::TouchpadX),
+            Some(0.2)
+        );
+        handler.send_controller_event(
+            &ControllerEvent::ControllerTouchpadReleased {
+                which: 1,
+                finger: 0,
+            },
+            &mut events,
+        );
+        assert_eq!(handler.controller_touch(0, 0), None);
+    }
+
+    #[test]
+    fn mouse_filter_smoothing() {
+        let mut handler = InputHandler::new();
+        let mut events = EventChannel::<InputEvent>::new();
+        handler.mouse_filters = MouseFilters::new(MouseFilter {
+            smoothing_window: 2,
+            scale: (1.0, -1.0),
+            ..Default::default()
+        });
+        handler.mouse_filters.insert_context(
+            "zoomed",
+            MouseFilter {
+                scale: (0.5, 0.5),
+                ..Default::default()
+            },
+        );
+
+        handler.send_event(&mouse_motion(4.0, 2.0), &mut events);
+        assert_eq!(handler.raw_mouse_delta(), (4.0, 2.0));
+        assert_eq!(handler.filtered_mouse_delta(), (4.0, -2.0));
+
+        handler.send_frame_begin();
+        handler.send_event(&mouse_motion(2.0, 0.0), &mut events);
+        assert_eq!(handler.raw_mouse_delta(), (2.0, 0.0));
+        assert_eq!(handler.filtered_mouse_delta(), (3.0, -1.0));
+
+        handler.mouse_filters.set_active_context(Some("zoomed"));
+        assert_eq!(handler.filtered_mouse_delta(), (1.0, 0.0));
+    }
+
     /// Compares two sets for equality, but not the order
     fn sets_are_equal<T>(a: &[T], b: &[T])
     where
@@ -1340,6 +1939,13 @@ right: `{:?}`",
         }
     }
 
+    fn received_character(c: char) -> Event<'static, ()> {
+        Event::WindowEvent {
+            window_id: unsafe { WindowId::dummy() },
+            event: WindowEvent::ReceivedCharacter(c),
+        }
+    }
+
     fn mouse_press(button: MouseButton) -> Event<'static, ()> {
         mouse_event(button, ElementState::Pressed)
     }
@@ -1360,6 +1966,13 @@ right: `{:?}`",
         }
     }
 
+    fn mouse_motion(x: f64, y: f64) -> Event<'static, ()> {
+        Event::DeviceEvent {
+            device_id: unsafe { DeviceId::dummy() },
+            event: DeviceEvent::MouseMotion { delta: (x, y) },
+        }
+    }
+
     fn mouse_wheel(x: f32, y: f32) -> Event<'static, ()> {
         Event::DeviceEvent {
             device_id: unsafe { DeviceId::dummy() },
@@ -24,15 +24,20 @@ pub use self::{
     bindings::{BindingError, Bindings},
     bundle::{BindingsFileError, InputBundle},
     button::Button,
-    controller::{ControllerAxis, ControllerButton, ControllerEvent},
-    event::InputEvent,
+    controller::{ControllerAxis, ControllerButton, ControllerEvent, ControllerSensor},
+    event::{InputEvent, TimedInputEvent},
+    glyph::{
+        axis_glyphs, button_glyph, controller_axis_glyph, controller_button_glyph, ControllerKind,
+    },
     input_handler::{InputHandler, KeyboardModifiersState},
     mouse::MouseAxis,
+    mouse_filter::{MouseAcceleration, MouseFilter, MouseFilters, MAX_SMOOTHING_WINDOW},
+    player::{AssignmentPolicy, InputDevice, PlayerAssignments},
     scroll_direction::ScrollDirection,
     system::InputSystem,
     util::{
         get_action_simple, get_input_axis_simple, get_key, get_mouse_button, is_close_requested,
-        is_key_down, is_key_up, is_mouse_button_down,
+        is_key_down, is_key_up, is_mouse_button_down, is_text_char,
     },
 };
 
@@ -42,8 +47,11 @@ mod bundle;
 mod button;
 mod controller;
 mod event;
+mod glyph;
 mod input_handler;
 mod mouse;
+mod mouse_filter;
+mod player;
 mod scroll_direction;
 mod system;
 mod util;
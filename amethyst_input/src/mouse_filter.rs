@@ -0,0 +1,157 @@
+//! Smoothing, acceleration and scaling of raw mouse motion.
+
+use std::borrow::Cow;
+
+use fnv::FnvHashMap as HashMap;
+use serde::{Deserialize, Serialize};
+use smallvec::SmallVec;
+
+/// The maximum number of frames raw mouse motion can be averaged over.
+pub const MAX_SMOOTHING_WINDOW: usize = 16;
+
+/// Acceleration applied to mouse motion after smoothing.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum MouseAcceleration {
+    /// Motion is passed through unchanged.
+    None,
+    /// Motion is multiplied by `1 + factor * speed.powf(exponent)`, where speed is the length of
+    /// the motion during the frame, but never by more than `max_gain`.
+    Power {
+        /// How strongly speed increases the gain.
+        factor: f32,
+        /// How quickly the gain grows with speed, 1 is linear.
+        exponent: f32,
+        /// The maximum gain.
+        max_gain: f32,
+    },
+}
+
+impl Default for MouseAcceleration {
+    fn default() -> Self {
+        MouseAcceleration::None
+    }
+}
+
+impl MouseAcceleration {
+    /// Returns the gain applied for the given speed.
+    pub fn gain(&self, speed: f32) -> f32 {
+        match *self {
+            MouseAcceleration::None => 1.0,
+            MouseAcceleration::Power {
+                factor,
+                exponent,
+                max_gain,
+            } => (1.0 + factor * speed.powf(exponent)).min(max_gain),
+        }
+    }
+}
+
+/// Settings for turning raw mouse motion into filtered mouse motion.
+///
+/// The motion is first averaged over the last `smoothing_window` frames, then accelerated and
+/// finally scaled per axis.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MouseFilter {
+    /// Number of frames to average over, 1 disables smoothing.
+    ///
+    /// Values above `MAX_SMOOTHING_WINDOW` are treated as `MAX_SMOOTHING_WINDOW`.
+    pub smoothing_window: usize,
+    /// Acceleration curve applied to the smoothed motion.
+    pub acceleration: MouseAcceleration,
+    /// Factor applied to the horizontal and vertical motion. Use negative values to invert an axis.
+    pub scale: (f32, f32),
+}
+
+impl Default for MouseFilter {
+    fn default() -> Self {
+        MouseFilter {
+            smoothing_window: 1,
+            acceleration: MouseAcceleration::None,
+            scale: (1.0, 1.0),
+        }
+    }
+}
+
+impl MouseFilter {
+    /// Applies this filter to the motion of the current frame and the motion of previous frames,
+    /// most recent first.
+    pub fn apply(&self, current: (f32, f32), history: &[(f32, f32)]) -> (f32, f32) {
+        let window = self.smoothing_window.max(1).min(MAX_SMOOTHING_WINDOW);
+        let frames = 1 + history.len().min(window - 1);
+        let (sum_x, sum_y) = history
+            .iter()
+            .take(frames - 1)
+            .fold(current, |(x, y), &(dx, dy)| (x + dx, y + dy));
+        let (x, y) = (sum_x / frames as f32, sum_y / frames as f32);
+        let gain = self.acceleration.gain((x * x + y * y).sqrt());
+        (x * gain * self.scale.0, y * gain * self.scale.1)
+    }
+}
+
+/// Mouse filters used by the `InputHandler`, with overrides for named contexts.
+///
+/// Contexts allow e.g. a vehicle camera to use a different feel than on-foot controls,
+/// by switching the active context when the player enters the vehicle.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MouseFilters {
+    /// Filter used when no context is active, or the active context has no override.
+    pub default: MouseFilter,
+    /// Overrides for named contexts.
+    pub contexts: HashMap<Cow<'static, str>, MouseFilter>,
+    #[serde(skip)]
+    active_context: Option<Cow<'static, str>>,
+    /// Motion of previous frames, most recent first.
+    #[serde(skip)]
+    history: SmallVec<[(f32, f32); MAX_SMOOTHING_WINDOW]>,
+}
+
+impl MouseFilters {
+    /// Creates filters which use `default` everywhere.
+    pub fn new(default: MouseFilter) -> Self {
+        MouseFilters {
+            default,
+            ..Default::default()
+        }
+    }
+
+    /// Adds an override for a context, returning the previous override if there was one.
+    pub fn insert_context<C: Into<Cow<'static, str>>>(
+        &mut self,
+        context: C,
+        filter: MouseFilter,
+    ) -> Option<MouseFilter> {
+        self.contexts.insert(context.into(), filter)
+    }
+
+    /// Activates a context, or goes back to the default filter with `None`.
+    ///
+    /// Activating a context without an override uses the default filter.
+    pub fn set_active_context<C: Into<Cow<'static, str>>>(&mut self, context: Option<C>) {
+        self.active_context = context.map(Into::into);
+    }
+
+    /// Returns the active context.
+    pub fn active_context(&self) -> Option<&str> {
+        self.active_context.as_deref()
+    }
+
+    /// Returns the filter currently in use.
+    pub fn active(&self) -> &MouseFilter {
+        self.active_context
+            .as_ref()
+            .and_then(|c| self.contexts.get(c))
+            .unwrap_or(&self.default)
+    }
+
+    /// Returns the filtered motion for the raw motion of the current frame.
+    pub fn apply(&self, current: (f32, f32)) -> (f32, f32) {
+        self.active().apply(current, &self.history)
+    }
+
+    /// Records the raw motion of a finished frame.
+    pub(crate) fn push_frame(&mut self, delta: (f32, f32)) {
+        self.history.insert(0, delta);
+        self.history.truncate(MAX_SMOOTHING_WINDOW - 1);
+    }
+}
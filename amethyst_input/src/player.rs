@@ -0,0 +1,153 @@
+//! Assignment of input devices to local players.
+
+use serde::{Deserialize, Serialize};
+use smallvec::SmallVec;
+
+/// An input device which can be assigned to a local player.
+#[derive(Eq, PartialEq, Debug, Copy, Clone, Hash, Serialize, Deserialize)]
+pub enum InputDevice {
+    /// The keyboard together with the mouse.
+    KeyboardMouse,
+    /// A controller, identified by the controller id assigned by the `InputHandler`.
+    Controller(u32),
+}
+
+/// Decides how the `InputHandler` assigns devices to players without user intervention.
+#[derive(Eq, PartialEq, Debug, Copy, Clone, Serialize, Deserialize)]
+pub enum AssignmentPolicy {
+    /// Devices are only assigned through `PlayerAssignments::assign`.
+    Manual,
+    /// Every newly connected controller is assigned to the lowest numbered player
+    /// that doesn't have a device yet.
+    ///
+    /// The keyboard is never connected, so it still needs to be assigned manually.
+    OnConnect,
+    /// A device is assigned to the lowest numbered player that doesn't have a device yet
+    /// the first time one of its buttons is pressed, e.g. for "press start to join" screens.
+    OnFirstInput,
+}
+
+impl Default for AssignmentPolicy {
+    fn default() -> Self {
+        AssignmentPolicy::Manual
+    }
+}
+
+/// Keeps track of which input devices belong to which local player.
+///
+/// Players are identified by a sequential number starting from 0. A device belongs to at most
+/// one player, while a player can own several devices (e.g. keyboard and a controller).
+#[derive(Debug, Clone)]
+pub struct PlayerAssignments {
+    policy: AssignmentPolicy,
+    max_players: u32,
+    assignments: SmallVec<[(u32, InputDevice); 8]>,
+}
+
+impl Default for PlayerAssignments {
+    fn default() -> Self {
+        PlayerAssignments::new(AssignmentPolicy::default(), 4)
+    }
+}
+
+impl PlayerAssignments {
+    /// Creates assignments without any devices, using the given policy for at most
+    /// `max_players` players.
+    pub fn new(policy: AssignmentPolicy, max_players: u32) -> Self {
+        PlayerAssignments {
+            policy,
+            max_players,
+            assignments: SmallVec::new(),
+        }
+    }
+
+    /// Returns the policy used for automatic assignment.
+    pub fn policy(&self) -> AssignmentPolicy {
+        self.policy
+    }
+
+    /// Changes the policy used for automatic assignment, existing assignments are kept.
+    pub fn set_policy(&mut self, policy: AssignmentPolicy) {
+        self.policy = policy;
+    }
+
+    /// Returns the maximum number of players devices get automatically assigned to.
+    pub fn max_players(&self) -> u32 {
+        self.max_players
+    }
+
+    /// Changes the maximum number of players devices get automatically assigned to.
+    pub fn set_max_players(&mut self, max_players: u32) {
+        self.max_players = max_players;
+    }
+
+    /// Assigns a device to a player.
+    ///
+    /// If the device already belonged to another player, that player is returned.
+    pub fn assign(&mut self, player: u32, device: InputDevice) -> Option<u32> {
+        let previous = self.unassign(device);
+        self.assignments.push((player, device));
+        previous
+    }
+
+    /// Removes a device from its player, returning the player it belonged to.
+    pub fn unassign(&mut self, device: InputDevice) -> Option<u32> {
+        let index = self.assignments.iter().position(|&(_, d)| d == device)?;
+        Some(self.assignments.remove(index).0)
+    }
+
+    /// Removes all devices of a player.
+    pub fn unassign_player(&mut self, player: u32) {
+        self.assignments.retain(|&mut (p, _)| p != player);
+    }
+
+    /// Returns the player the device is assigned to, if any.
+    pub fn player_of(&self, device: InputDevice) -> Option<u32> {
+        self.assignments
+            .iter()
+            .find(|&&(_, d)| d == device)
+            .map(|&(p, _)| p)
+    }
+
+    /// Returns an iterator over all devices assigned to a player, in order of assignment.
+    pub fn devices_of(&self, player: u32) -> impl Iterator<Item = InputDevice> + '_ {
+        self.assignments
+            .iter()
+            .filter(move |&&(p, _)| p == player)
+            .map(|&(_, d)| d)
+    }
+
+    /// Returns true if the player owns the given device.
+    pub fn owns(&self, player: u32, device: InputDevice) -> bool {
+        self.player_of(device) == Some(player)
+    }
+
+    /// Returns the id of the `index`th controller assigned to a player.
+    pub fn controller_of(&self, player: u32, index: u32) -> Option<u32> {
+        self.devices_of(player)
+            .filter_map(|d| {
+                match d {
+                    InputDevice::Controller(id) => Some(id),
+                    InputDevice::KeyboardMouse => None,
+                }
+            })
+            .nth(index as usize)
+    }
+
+    /// Returns the lowest numbered player that doesn't own any device yet.
+    pub fn next_free_player(&self) -> Option<u32> {
+        (0..self.max_players).find(|&p| self.devices_of(p).next().is_none())
+    }
+
+    /// Assigns the device to the next free player, if it isn't assigned already.
+    ///
+    /// Returns the player the device was assigned to.
+    pub(crate) fn auto_assign(&mut self, device: InputDevice) -> Option<u32> {
+        if self.player_of(device).is_some() {
+            return None;
+        }
+        let player = self.next_free_player()?;
+        self.assignments.push((player, device));
+        Some(player)
+    }
+}
@@ -3,7 +3,8 @@ use std::{fmt, marker::PhantomData, path::PathBuf};
 use amethyst_core::{
     dispatcher::ThreadLocalSystem,
     ecs::{ParallelRunnable, Runnable, System, SystemBuilder, World},
-    shrev::EventChannel,
+    shrev::{EventChannel, ReaderId},
+    timing::Instant,
 };
 use sdl2::{
     self,
@@ -14,7 +15,9 @@ use sdl2::{
 
 use super::{
     controller::{ControllerAxis, ControllerButton, ControllerEvent},
-    InputEvent, InputHandler,
+    glyph::ControllerKind,
+    system::write_timed,
+    InputEvent, InputHandler, TimedInputEvent,
 };
 
 /// A collection of errors that can occur in the SDL system.
@@ -52,12 +55,21 @@ pub enum ControllerMappings {
 }
 
 /// A system that pumps SDL events into the `amethyst_input` APIs.
+///
+/// The events are written to `EventChannel<InputEvent>` and, with the time SDL delivered them at,
+/// to `EventChannel<TimedInputEvent>`. The controllers connected when the system is created are
+/// announced during its first run.
 pub struct SdlEventsSystem {
     sdl_context: Sdl,
     event_pump: Option<EventPump>,
     controller_subsystem: GameControllerSubsystem,
     /// Vector of opened controllers and their corresponding joystick indices
     opened_controllers: Vec<(u32, GameController)>,
+    /// Events generated by the `InputHandler`, before they are timed
+    scratch: EventChannel<InputEvent>,
+    scratch_reader: ReaderId<InputEvent>,
+    /// Time the events in `scratch` were delivered at
+    delivered: Instant,
 }
 
 impl ThreadLocalSystem<'static> for SdlEventsSystem {
@@ -66,14 +78,20 @@ impl ThreadLocalSystem<'static> for SdlEventsSystem {
             SystemBuilder::new("SdlEventsSystem")
                 .write_resource::<InputHandler>()
                 .write_resource::<EventChannel<InputEvent>>()
-                .build(move |_, _, (handler, output), _| {
+                .write_resource::<EventChannel<TimedInputEvent>>()
+                .build(move |_, _, (handler, output, timed), _| {
+                    // the connections made while creating the system
+                    self.write_events(handler, output, timed);
+
                     let mut event_pump = self
                         .event_pump
                         .take()
                         .expect("Unreachable: `event_pump` is always reinserted after `take`");
                     for event in event_pump.poll_iter() {
+                        self.delivered = Instant::now();
                         // handle appropriate events locally
-                        self.handle_sdl_event(&event, handler, output);
+                        self.handle_sdl_event(&event, handler);
+                        self.write_events(handler, output, timed);
                     }
                     self.event_pump = Some(event_pump);
                 }),
@@ -85,7 +103,6 @@ impl SdlEventsSystem {
     /// Creates a new instance of this system with the provided controller mappings.
     pub fn new(
         handler: &mut InputHandler,
-        output: &mut EventChannel<InputEvent>,
         mappings: &Option<ControllerMappings>,
     ) -> Result<Self, SdlSystemError> {
         let sdl_context = sdl2::init().map_err(SdlSystemError::ContextInit)?;
@@ -111,22 +128,38 @@ impl SdlEventsSystem {
             None => {}
         };
 
+        let mut scratch = EventChannel::new();
+        let scratch_reader = scratch.register_reader();
         let mut sys = SdlEventsSystem {
             sdl_context,
             event_pump: Some(event_pump),
             controller_subsystem,
             opened_controllers: vec![],
+            scratch,
+            scratch_reader,
+            delivered: Instant::now(),
         };
-        sys.initialize_controllers(handler, output);
+        sys.initialize_controllers(handler);
         Ok(sys)
     }
 
-    fn handle_sdl_event(
+    fn write_events(
         &mut self,
-        event: &Event,
         handler: &mut InputHandler,
         output: &mut EventChannel<InputEvent>,
+        timed: &mut EventChannel<TimedInputEvent>,
     ) {
+        write_timed(
+            &mut self.scratch,
+            &mut self.scratch_reader,
+            self.delivered,
+            handler,
+            output,
+            timed,
+        );
+    }
+
+    fn handle_sdl_event(&mut self, event: &Event, handler: &mut InputHandler) {
         use self::ControllerEvent::*;
 
         match *event {
@@ -143,7 +176,7 @@ impl SdlEventsSystem {
                             f32::from(value) / 32768.0
                         },
                     },
-                    output,
+                    &mut self.scratch,
                 );
             }
             Event::ControllerButtonDown { which, button, .. } => {
@@ -152,7 +185,7 @@ impl SdlEventsSystem {
                         which: which as u32,
                         button: button.into(),
                     },
-                    output,
+                    &mut self.scratch,
                 );
             }
             Event::ControllerButtonUp { which, button, .. } => {
@@ -161,7 +194,7 @@ impl SdlEventsSystem {
                         which: which as u32,
                         button: button.into(),
                     },
-                    output,
+                    &mut self.scratch,
                 );
             }
             Event::ControllerDeviceRemoved { which, .. } => {
@@ -170,24 +203,29 @@ impl SdlEventsSystem {
                     &ControllerDisconnected {
                         which: which as u32,
                     },
-                    output,
+                    &mut self.scratch,
                 );
             }
             Event::ControllerDeviceAdded { which, .. } => {
-                if let Some(idx) = self.open_controller(which) {
-                    handler.send_controller_event(&ControllerConnected { which: idx }, output);
+                if let Some((idx, kind)) = self.open_controller(which) {
+                    handler.send_controller_event(
+                        &ControllerConnected { which: idx },
+                        &mut self.scratch,
+                    );
+                    handler.set_controller_kind(idx, kind);
                 }
             }
             _ => {}
         }
     }
 
-    fn open_controller(&mut self, which: u32) -> Option<u32> {
+    fn open_controller(&mut self, which: u32) -> Option<(u32, ControllerKind)> {
         if self.controller_subsystem.is_game_controller(which) {
             self.controller_subsystem.open(which).ok().map(|c| {
                 let id = c.instance_id() as u32;
+                let kind = ControllerKind::from_name(&c.name());
                 self.opened_controllers.push((which, c));
-                id
+                (id, kind)
             })
         } else {
             None
@@ -204,17 +242,17 @@ impl SdlEventsSystem {
         }
     }
 
-    fn initialize_controllers(
-        &mut self,
-        handler: &mut InputHandler,
-        output: &mut EventChannel<InputEvent>,
-    ) {
+    fn initialize_controllers(&mut self, handler: &mut InputHandler) {
         use crate::controller::ControllerEvent::ControllerConnected;
 
         if let Ok(available) = self.controller_subsystem.num_joysticks() {
             for id in 0..available {
-                if let Some(idx) = self.open_controller(id) {
-                    handler.send_controller_event(&ControllerConnected { which: idx }, output);
+                if let Some((idx, kind)) = self.open_controller(id) {
+                    handler.send_controller_event(
+                        &ControllerConnected { which: idx },
+                        &mut self.scratch,
+                    );
+                    handler.set_controller_kind(idx, kind);
                 }
             }
         }
@@ -2,37 +2,84 @@
 use amethyst_core::{
     ecs::*,
     shrev::{EventChannel, ReaderId},
+    timing::{DeliveryTime, Instant},
 };
 #[cfg(feature = "profiler")]
 use thread_profiler::profile_scope;
 use winit::event::Event;
 
-use crate::{InputEvent, InputHandler};
+use crate::{InputEvent, InputHandler, TimedInputEvent};
 
 /// Will read `winit::Event` from `EventHandler<winit::Event>`, process them with `InputHandler`,
 /// and push the results in `EventHandler<InputEvent>`.
+///
+/// The same events are also pushed with a timestamp and sequence number in
+/// `EventHandler<TimedInputEvent>`. The timestamp is the time the window event was delivered at,
+/// read from `EventHandler<DeliveryTime>`, or the time it's processed at for window events
+/// written without one.
 #[derive(Debug)]
 pub struct InputSystem {
     // reads input events from winit
     pub(crate) reader: ReaderId<Event<'static, ()>>,
+    // reads the times the events were delivered at
+    pub(crate) time_reader: ReaderId<DeliveryTime>,
 }
 
 impl System for InputSystem {
     fn build(mut self) -> Box<dyn systems::ParallelRunnable> {
+        let mut scratch = EventChannel::<InputEvent>::new();
+        let mut scratch_reader = scratch.register_reader();
+
         Box::new(
             SystemBuilder::new("InputSystem")
                 .read_resource::<EventChannel<Event<'static, ()>>>()
+                .read_resource::<EventChannel<DeliveryTime>>()
                 .write_resource::<InputHandler>()
                 .write_resource::<EventChannel<InputEvent>>()
-                .build(move |_commands, _world, (input, handler, output), _query| {
-                    #[cfg(feature = "profiler")]
-                    profile_scope!("input_system");
+                .write_resource::<EventChannel<TimedInputEvent>>()
+                .build(
+                    move |_commands, _world, (input, times, handler, output, timed), _query| {
+                        #[cfg(feature = "profiler")]
+                        profile_scope!("input_system");
 
-                    handler.send_frame_begin();
-                    for event in input.read(&mut self.reader) {
-                        handler.send_event(event, output);
-                    }
-                }),
+                        handler.send_frame_begin();
+                        let mut times = times.read(&mut self.time_reader);
+                        for event in input.read(&mut self.reader) {
+                            let timestamp = times
+                                .next()
+                                .map_or_else(Instant::now, |delivered| delivered.0);
+                            handler.send_event(event, &mut scratch);
+                            write_timed(
+                                &mut scratch,
+                                &mut scratch_reader,
+                                timestamp,
+                                handler,
+                                output,
+                                timed,
+                            );
+                        }
+                    },
+                ),
         )
     }
 }
+
+/// Moves the events generated into `scratch` to `output`, and to `timed` with `timestamp` and
+/// the next sequence numbers of `handler`.
+pub(crate) fn write_timed(
+    scratch: &mut EventChannel<InputEvent>,
+    scratch_reader: &mut ReaderId<InputEvent>,
+    timestamp: Instant,
+    handler: &mut InputHandler,
+    output: &mut EventChannel<InputEvent>,
+    timed: &mut EventChannel<TimedInputEvent>,
+) {
+    for event in scratch.read(scratch_reader) {
+        output.single_write(event.clone());
+        timed.single_write(TimedInputEvent {
+            event: event.clone(),
+            timestamp,
+            sequence: handler.take_sequence(),
+        });
+    }
+}
@@ -46,6 +46,18 @@ pub fn is_key_up(event: &Event<'_, ()>, key_code: VirtualKeyCode) -> bool {
     false
 }
 
+/// Returns true if the character should be inserted when the user types text.
+///
+/// This filters out control characters, the delete character and the unicode private use areas,
+/// which some systems emit alongside regular key presses.
+pub fn is_text_char(input: char) -> bool {
+    !(input < '\u{20}'
+        || input == '\u{7F}'
+        || ('\u{E000}'..='\u{F8FF}').contains(&input)
+        || ('\u{F0000}'..='\u{FFFFF}').contains(&input)
+        || ('\u{100000}'..='\u{10FFFF}').contains(&input))
+}
+
 /// Returns true if the event passed in is a request to close the game window.
 pub fn is_close_requested(event: &Event<'_, ()>) -> bool {
     match *event {
@@ -0,0 +1,158 @@
+//! The active language, and the locales of every language.
+
+use amethyst_assets::{AssetStorage, Handle};
+use fluent::FluentArgs;
+use fnv::FnvHashMap;
+use unic_langid::{langid, LanguageIdentifier};
+
+use crate::Locale;
+
+/// The language of the game, a resource which can be changed at runtime.
+///
+/// Strings are resolved along the fallback chain of the language, e.g. `de-AT`, `de`, then the
+/// default language.
+#[derive(Clone, Debug)]
+pub struct Language {
+    current: LanguageIdentifier,
+    default: LanguageIdentifier,
+    generation: u64,
+}
+
+impl Default for Language {
+    fn default() -> Self {
+        Language::new(langid!("en"), langid!("en"))
+    }
+}
+
+impl Language {
+    /// Create the resource with the current language, and the default language used for the
+    /// strings missing from the current one.
+    pub fn new(current: LanguageIdentifier, default: LanguageIdentifier) -> Self {
+        Language {
+            current,
+            default,
+            generation: 0,
+        }
+    }
+
+    /// The current language.
+    pub fn current(&self) -> &LanguageIdentifier {
+        &self.current
+    }
+
+    /// The default language, last of the fallback chain.
+    pub fn default_language(&self) -> &LanguageIdentifier {
+        &self.default
+    }
+
+    /// Change the current language, the `LocaleSystem` then sends a
+    /// `LocaleEvent::LanguageChanged`.
+    pub fn set(&mut self, language: LanguageIdentifier) {
+        if language != self.current {
+            self.current = language;
+            self.generation += 1;
+        }
+    }
+
+    /// Changes every time the current language changes.
+    pub(crate) fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// The languages strings are looked up in, from the most specific to the default language.
+    pub fn fallback_chain(&self) -> Vec<LanguageIdentifier> {
+        let mut chain = Vec::new();
+        for language in &[&self.current, &self.default] {
+            let mut language = (*language).clone();
+            push_unique(&mut chain, language.clone());
+            language.clear_variants();
+            push_unique(&mut chain, language.clone());
+            language.region = None;
+            push_unique(&mut chain, language.clone());
+            language.script = None;
+            push_unique(&mut chain, language);
+        }
+        chain
+    }
+}
+
+fn push_unique(chain: &mut Vec<LanguageIdentifier>, language: LanguageIdentifier) {
+    if !chain.contains(&language) {
+        chain.push(language);
+    }
+}
+
+/// The locale of every language, a resource in which locales are registered once their loading
+/// starts. Locales are looked up in the `AssetStorage` when resolving strings, so reloaded
+/// locales are used right away.
+#[derive(Debug, Default)]
+pub struct Locales {
+    locales: FnvHashMap<LanguageIdentifier, Handle<Locale>>,
+    revision: u64,
+}
+
+impl Locales {
+    /// Register the locale of a language, returning the previous one.
+    pub fn insert(
+        &mut self,
+        language: LanguageIdentifier,
+        locale: Handle<Locale>,
+    ) -> Option<Handle<Locale>> {
+        self.revision += 1;
+        self.locales.insert(language, locale)
+    }
+
+    /// Unregister the locale of a language.
+    pub fn remove(&mut self, language: &LanguageIdentifier) -> Option<Handle<Locale>> {
+        self.revision += 1;
+        self.locales.remove(language)
+    }
+
+    /// The locale of a language.
+    pub fn get(&self, language: &LanguageIdentifier) -> Option<&Handle<Locale>> {
+        self.locales.get(language)
+    }
+
+    /// Changes every time locales are inserted or removed.
+    pub(crate) fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    /// The first loaded locale of the fallback chain of the language with the given message.
+    pub fn resolve<'a>(
+        &self,
+        language: &Language,
+        storage: &'a AssetStorage<Locale>,
+        id: &str,
+    ) -> Option<&'a Locale> {
+        language
+            .fallback_chain()
+            .iter()
+            .filter_map(|language| self.locales.get(language))
+            .filter_map(|handle| storage.get(handle))
+            .find(|locale| locale.bundle.has_message(id))
+    }
+
+    /// Format the message with the given id in the current language, or its fallbacks.
+    ///
+    /// Returns `None` if no loaded locale of the fallback chain has the message, or if the
+    /// message has no value. Formatting errors are logged, and leave a placeholder in the string.
+    pub fn format(
+        &self,
+        language: &Language,
+        storage: &AssetStorage<Locale>,
+        id: &str,
+        args: Option<&FluentArgs<'_>>,
+    ) -> Option<String> {
+        let bundle = &self.resolve(language, storage, id)?.bundle;
+        let pattern = bundle.get_message(id)?.value?;
+        let mut errors = Vec::new();
+        let value = bundle
+            .format_pattern(pattern, args, &mut errors)
+            .into_owned();
+        for error in errors {
+            log::warn!("Failed to format message `{}`: {:?}", id, error);
+        }
+        Some(value)
+    }
+}
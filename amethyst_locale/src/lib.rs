@@ -1,6 +1,11 @@
 //! # amethyst_locale
 //!
 //! Localisation binding a `Fluent` file to an Asset<Locale> via the use of amethyst_assets.
+//!
+//! The locale of each language is registered in the `Locales` resource, and strings are resolved
+//! in the current `Language`, which can change at runtime, falling back to more generic languages
+//! and then to the default one. The `LocaleBundle` sends a `LocaleEvent` whenever the strings in
+//! use may have changed.
 
 #![doc(
     html_logo_url = "https://amethyst.rs/brand/logo-standard.svg",
@@ -19,10 +24,24 @@ use amethyst_assets::{
     LoadHandle, ProcessableAsset, ProcessingState,
 };
 use amethyst_error::Error;
-pub use fluent::{concurrent::FluentBundle, FluentResource};
+pub use fluent::{concurrent::FluentBundle, FluentArgs, FluentResource, FluentValue};
 use serde::{Deserialize, Serialize};
 use type_uuid::*;
-use unic_langid::langid;
+pub use unic_langid::{langid, LanguageIdentifier};
+
+#[cfg(feature = "ui")]
+pub use crate::ui::LocalizedTextSystem;
+pub use crate::{
+    language::{Language, Locales},
+    system::{LocaleBundle, LocaleEvent, LocaleSystem},
+    text::{LocaleArg, LocalizedText},
+};
+
+mod language;
+mod system;
+mod text;
+#[cfg(feature = "ui")]
+mod ui;
 
 /// Internal representation of a Locale
 #[derive(Clone, Debug, Serialize, Deserialize, TypeUuid)]
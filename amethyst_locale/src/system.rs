@@ -0,0 +1,100 @@
+//! Events for the changes of language and locales.
+
+use amethyst_assets::AssetStorage;
+use amethyst_core::{
+    dispatcher::{DispatcherBuilder, System, SystemBundle},
+    ecs::{systems::ParallelRunnable, Resources, SystemBuilder, World},
+    shrev::EventChannel,
+};
+use amethyst_error::Error;
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+use unic_langid::LanguageIdentifier;
+
+use crate::{Language, Locale, Locales};
+
+/// Sent by the `LocaleSystem` when the strings of the current language may have changed, to
+/// resolve them again.
+#[derive(Clone, Debug, PartialEq)]
+pub enum LocaleEvent {
+    /// The current language changed to the given one.
+    LanguageChanged(LanguageIdentifier),
+    /// A locale of the fallback chain of the current language was loaded, reloaded or
+    /// registered.
+    LocalesChanged,
+}
+
+/// Sends `LocaleEvent`s when the `Language` or the locales of its fallback chain change.
+#[derive(Debug, Default)]
+pub struct LocaleSystem;
+
+impl System for LocaleSystem {
+    fn build(self) -> Box<dyn ParallelRunnable> {
+        let mut generation = 0;
+        let mut revision = None;
+        let mut versions = Vec::new();
+        Box::new(
+            SystemBuilder::new("LocaleSystem")
+                .read_resource::<Language>()
+                .read_resource::<Locales>()
+                .read_resource::<AssetStorage<Locale>>()
+                .write_resource::<EventChannel<LocaleEvent>>()
+                .build(move |_, _, (language, locales, storage, events), _| {
+                    #[cfg(feature = "profiler")]
+                    profile_scope!("locale_system");
+
+                    if language.generation() != generation {
+                        generation = language.generation();
+                        events
+                            .single_write(LocaleEvent::LanguageChanged(language.current().clone()));
+                    }
+
+                    let current = language
+                        .fallback_chain()
+                        .iter()
+                        .map(|language| {
+                            locales
+                                .get(language)
+                                .and_then(|handle| storage.get_version(handle))
+                        })
+                        .collect::<Vec<_>>();
+                    if revision != Some(locales.revision()) || current != versions {
+                        // the first run has no strings to resolve again
+                        if revision.is_some() {
+                            events.single_write(LocaleEvent::LocalesChanged);
+                        }
+                        revision = Some(locales.revision());
+                        versions = current;
+                    }
+                }),
+        )
+    }
+}
+
+/// Inserts the `Language`, `Locales` and `EventChannel<LocaleEvent>` resources if missing, and
+/// adds the `LocaleSystem`, followed by the `LocalizedTextSystem` with the `ui` feature.
+#[derive(Debug, Default)]
+pub struct LocaleBundle;
+
+impl SystemBundle for LocaleBundle {
+    fn load(
+        &mut self,
+        _world: &mut World,
+        resources: &mut Resources,
+        builder: &mut DispatcherBuilder,
+    ) -> Result<(), Error> {
+        resources.get_or_default::<Language>();
+        resources.get_or_default::<Locales>();
+        resources.get_or_default::<EventChannel<LocaleEvent>>();
+        builder.add_system(LocaleSystem);
+        #[cfg(feature = "ui")]
+        {
+            let locale_reader = resources
+                .get_mut::<EventChannel<LocaleEvent>>()
+                .unwrap()
+                .register_reader();
+            builder.add_system(crate::LocalizedTextSystem::new(locale_reader));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,110 @@
+//! Localised strings with named arguments.
+
+use std::collections::BTreeMap;
+
+use amethyst_assets::AssetStorage;
+use fluent::{FluentArgs, FluentValue};
+
+use crate::{Language, Locale, Locales};
+
+/// The value of an argument of a localised string.
+#[derive(Clone, Debug, PartialEq)]
+pub enum LocaleArg {
+    /// A string, e.g. the name of a player
+    String(String),
+    /// A number, e.g. a count selecting the plural form of the string
+    Number(f64),
+}
+
+impl From<String> for LocaleArg {
+    fn from(value: String) -> Self {
+        LocaleArg::String(value)
+    }
+}
+
+impl From<&str> for LocaleArg {
+    fn from(value: &str) -> Self {
+        LocaleArg::String(value.to_string())
+    }
+}
+
+macro_rules! number_arg {
+    ($($ty:ty),*) => {
+        $(
+            impl From<$ty> for LocaleArg {
+                fn from(value: $ty) -> Self {
+                    LocaleArg::Number(f64::from(value))
+                }
+            }
+        )*
+    };
+}
+
+number_arg!(i8, i16, i32, u8, u16, u32, f32, f64);
+
+/// A component holding the id of a localised message and its named arguments.
+///
+/// With the `ui` feature, the `LocalizedTextSystem` keeps the `UiText` of the entity set to the
+/// message, formatted in the current `Language`, whenever the arguments or the language change.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LocalizedText {
+    /// The id of the message
+    pub id: String,
+    args: BTreeMap<String, LocaleArg>,
+}
+
+impl LocalizedText {
+    /// Create a text showing the message with the given id.
+    pub fn new<S: Into<String>>(id: S) -> Self {
+        LocalizedText {
+            id: id.into(),
+            args: BTreeMap::new(),
+        }
+    }
+
+    /// Set the value of an argument, when building the text.
+    pub fn with_arg<S: Into<String>, V: Into<LocaleArg>>(mut self, name: S, value: V) -> Self {
+        self.args.insert(name.into(), value.into());
+        self
+    }
+
+    /// Set the value of an argument, returning true if it changed.
+    pub fn set_arg<S: Into<String>, V: Into<LocaleArg>>(&mut self, name: S, value: V) -> bool {
+        let value = value.into();
+        let name = name.into();
+        if self.args.get(&name) == Some(&value) {
+            return false;
+        }
+        self.args.insert(name, value);
+        true
+    }
+
+    /// The value of an argument.
+    pub fn arg(&self, name: &str) -> Option<&LocaleArg> {
+        self.args.get(name)
+    }
+
+    /// The arguments, for formatting with a `FluentBundle`.
+    pub fn fluent_args(&self) -> FluentArgs<'_> {
+        self.args
+            .iter()
+            .map(|(name, value)| {
+                let value = match value {
+                    LocaleArg::String(value) => FluentValue::from(value.as_str()),
+                    LocaleArg::Number(value) => FluentValue::from(*value),
+                };
+                (name.as_str(), value)
+            })
+            .collect()
+    }
+
+    /// Format the message in the current language, see `Locales::format`.
+    pub fn format(
+        &self,
+        locales: &Locales,
+        language: &Language,
+        storage: &AssetStorage<Locale>,
+    ) -> Option<String> {
+        locales.format(language, storage, &self.id, Some(&self.fluent_args()))
+    }
+}
@@ -0,0 +1,65 @@
+//! Localised `UiText`s.
+
+use amethyst_assets::AssetStorage;
+use amethyst_core::{
+    dispatcher::System,
+    ecs::{maybe_changed, systems::ParallelRunnable, IntoQuery, SystemBuilder},
+    shrev::{EventChannel, ReaderId},
+};
+use amethyst_ui::UiText;
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+
+use crate::{Language, Locale, LocaleEvent, Locales, LocalizedText};
+
+/// Sets the `UiText` of entities with a `LocalizedText` to the formatted message, when the text
+/// changes and when a `LocaleEvent` is received. Texts whose message isn't loaded yet are left
+/// unchanged until their locale is loaded.
+#[derive(Debug)]
+pub struct LocalizedTextSystem {
+    event_reader: ReaderId<LocaleEvent>,
+}
+
+impl LocalizedTextSystem {
+    /// Constructs the system reading the `LocaleEvent`s with the given reader.
+    pub fn new(event_reader: ReaderId<LocaleEvent>) -> Self {
+        Self { event_reader }
+    }
+}
+
+impl System for LocalizedTextSystem {
+    fn build(mut self) -> Box<dyn ParallelRunnable> {
+        Box::new(
+            SystemBuilder::new("LocalizedTextSystem")
+                .read_resource::<Language>()
+                .read_resource::<Locales>()
+                .read_resource::<AssetStorage<Locale>>()
+                .read_resource::<EventChannel<LocaleEvent>>()
+                .with_query(<(&LocalizedText, &mut UiText)>::query())
+                .with_query(
+                    <(&LocalizedText, &mut UiText)>::query()
+                        .filter(maybe_changed::<LocalizedText>()),
+                )
+                .build(
+                    move |_, world, (language, locales, storage, events), (all, changed)| {
+                        #[cfg(feature = "profiler")]
+                        profile_scope!("localized_text_system");
+
+                        let mut update = |(text, ui_text): (&LocalizedText, &mut UiText)| {
+                            if let Some(value) = text.format(locales, language, storage) {
+                                if ui_text.text != value {
+                                    ui_text.text = value;
+                                }
+                            }
+                        };
+                        // read all the events, so they don't pile up
+                        if events.read(&mut self.event_reader).count() > 0 {
+                            all.for_each_mut(world, &mut update);
+                        } else {
+                            changed.for_each_mut(world, &mut update);
+                        }
+                    },
+                ),
+        )
+    }
+}
@@ -2,14 +2,52 @@
 //! more utilities to make their way into this module. e.g. "Component synchronization",
 //! "Matchmaking", etc.
 
+mod channels;
+mod conditioner;
+mod delta;
+mod discovery;
 mod events;
+mod interpolation;
+mod lockstep;
 mod message;
+mod metrics;
+mod packet;
+mod prediction;
+mod replication;
 mod requirements;
+mod rpc;
+mod session;
 mod timing;
 mod transport;
 
+pub use channels::DeliveryChannels;
+pub use conditioner::{LinkConditioner, LinkConditionerBundle, LinkConditionerSystem};
+pub use delta::{BitReader, BitWriter, ChangeMask, Quantization};
+#[cfg(feature = "master-server")]
+pub use discovery::master_server::{MasterServerBundle, MasterServerClient, MasterServerSystem};
+pub use discovery::{
+    DiscoveredServer, DiscoverySource, LanDiscovery, LanDiscoveryBundle, LanDiscoverySystem,
+    ServerInfo, ServerList,
+};
 pub use events::NetworkSimulationEvent;
+pub use interpolation::{SnapshotInterpolation, SnapshotInterpolationSystem, TransformSnapshots};
+pub use lockstep::{ChecksumHasher, Lockstep, LockstepBundle, LockstepEvent, LockstepModel};
 pub use message::Message;
+pub use metrics::{
+    NetworkMetrics, NetworkMetricsBundle, NetworkMetricsEvent, NetworkMetricsSystem, PeerMetrics,
+};
+pub use prediction::{
+    PredictionBundle, PredictionClient, PredictionModel, PredictionServer, TransformPrediction,
+};
+pub use replication::{
+    Interest, NetworkId, Replicated, ReplicationBundle, ReplicationClient, ReplicationServer,
+};
 pub use requirements::{DeliveryRequirement, UrgencyRequirement};
+pub use rpc::{Rpc, RpcBundle, RpcEvent};
+pub use session::{DisconnectReason, SessionBundle, SessionEvent, SessionSystem, Sessions};
 pub use timing::NetworkSimulationTime;
+#[cfg(all(feature = "webrtc", target_arch = "wasm32"))]
+pub use transport::webrtc;
+#[cfg(feature = "websocket")]
+pub use transport::websocket;
 pub use transport::{laminar, tcp, udp, TransportResource};
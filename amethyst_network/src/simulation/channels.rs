@@ -0,0 +1,649 @@
+//! Delivery guarantees for transports which can only send unreliable datagrams.
+//!
+//! `DeliveryChannels` prefixes every datagram with a small header describing how it has to be
+//! delivered. Reliable messages are resent until the peer acknowledges them, sequenced messages
+//! drop anything older than what was already received and ordered messages are held back until
+//! every message before them arrived. Each of the `DeliveryRequirement`s with a stream id keeps
+//! its own sequence per stream, so unrelated traffic doesn't wait on each other.
+//!
+//! A peer whose reliable messages aren't acknowledged within the timeout is considered lost and
+//! forgotten, as are peers nothing was exchanged with for as long. State is only kept for a
+//! limited number of peers, so datagrams from spoofed addresses can't use up memory.
+
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    io,
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+
+use bytes::Bytes;
+
+use crate::simulation::{
+    message::Message, metrics::NetworkMetrics, requirements::DeliveryRequirement,
+};
+
+/// Size in bytes of the header written in front of every payload, without acknowledgements.
+const HEADER_SIZE: usize = 7;
+
+/// Maximum number of acknowledgements sent in a single datagram.
+const MAX_ACKS: usize = 64;
+
+/// Number of received reliable ids remembered to drop duplicates.
+const RECEIVED_WINDOW: usize = 4096;
+
+/// Default number of peers state is kept for.
+const DEFAULT_MAX_PEERS: usize = 1024;
+
+/// Kinds of datagrams, written as the first byte of the header.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Kind {
+    Unreliable = 0,
+    UnreliableSequenced = 1,
+    Reliable = 2,
+    ReliableSequenced = 3,
+    ReliableOrdered = 4,
+    /// Only carries acknowledgements.
+    Ack = 5,
+}
+
+impl Kind {
+    fn from_byte(byte: u8) -> Option<Self> {
+        Some(match byte {
+            0 => Kind::Unreliable,
+            1 => Kind::UnreliableSequenced,
+            2 => Kind::Reliable,
+            3 => Kind::ReliableSequenced,
+            4 => Kind::ReliableOrdered,
+            5 => Kind::Ack,
+            _ => return None,
+        })
+    }
+
+    fn is_reliable(self) -> bool {
+        matches!(
+            self,
+            Kind::Reliable | Kind::ReliableSequenced | Kind::ReliableOrdered
+        )
+    }
+
+    fn is_sequenced(self) -> bool {
+        matches!(
+            self,
+            Kind::UnreliableSequenced | Kind::ReliableSequenced | Kind::ReliableOrdered
+        )
+    }
+}
+
+/// Delivery guarantees implemented on top of an unreliable transport, kept for every peer.
+#[derive(Debug)]
+pub struct DeliveryChannels {
+    peers: HashMap<SocketAddr, Peer>,
+    default_delivery: DeliveryRequirement,
+    resend_after: Duration,
+    timeout: Duration,
+    max_peers: usize,
+    lost: Vec<SocketAddr>,
+}
+
+impl Default for DeliveryChannels {
+    fn default() -> Self {
+        Self::new(DeliveryRequirement::Unreliable, Duration::from_millis(100))
+    }
+}
+
+impl DeliveryChannels {
+    /// Creates new channels which use `default_delivery` for messages sent with
+    /// `DeliveryRequirement::Default`, and resend lost reliable messages after `resend_after`.
+    ///
+    /// Peers time out after 10 seconds, and state is kept for up to 1024 peers.
+    pub fn new(default_delivery: DeliveryRequirement, resend_after: Duration) -> Self {
+        let default_delivery = match default_delivery {
+            DeliveryRequirement::Default => DeliveryRequirement::Unreliable,
+            delivery => delivery,
+        };
+        Self {
+            peers: HashMap::new(),
+            default_delivery,
+            resend_after,
+            timeout: Duration::from_secs(10),
+            max_peers: DEFAULT_MAX_PEERS,
+            lost: Vec::new(),
+        }
+    }
+
+    /// Returns the delivery used for messages sent with `DeliveryRequirement::Default`.
+    pub fn default_delivery(&self) -> DeliveryRequirement {
+        self.default_delivery
+    }
+
+    /// Returns the duration after which unacknowledged reliable messages are sent again.
+    pub fn resend_after(&self) -> Duration {
+        self.resend_after
+    }
+
+    /// Returns the duration after which a peer whose reliable messages weren't acknowledged is
+    /// lost, or a peer nothing was sent to or received from is forgotten.
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Sets the duration after which a peer whose reliable messages weren't acknowledged is
+    /// lost, or a peer nothing was sent to or received from is forgotten.
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    /// Returns the number of peers state is kept for at most.
+    pub fn max_peers(&self) -> usize {
+        self.max_peers
+    }
+
+    /// Sets the number of peers state is kept for at most. Datagrams from new peers which need
+    /// state, because they are reliable or sequenced, are rejected while there are as many.
+    pub fn set_max_peers(&mut self, max_peers: usize) {
+        self.max_peers = max_peers;
+    }
+
+    /// Returns the datagram to send for the given message, which is also kept to be resent if
+    /// the message has to be delivered reliably.
+    pub fn encode(&mut self, message: &Message, now: Instant) -> Vec<u8> {
+        let delivery = match message.delivery {
+            DeliveryRequirement::Default => self.default_delivery,
+            delivery => delivery,
+        };
+        let (kind, stream) = match delivery {
+            DeliveryRequirement::Unreliable | DeliveryRequirement::Default => (Kind::Unreliable, 0),
+            DeliveryRequirement::UnreliableSequenced(stream) => {
+                (Kind::UnreliableSequenced, stream.unwrap_or(0))
+            }
+            DeliveryRequirement::Reliable => (Kind::Reliable, 0),
+            DeliveryRequirement::ReliableSequenced(stream) => {
+                (Kind::ReliableSequenced, stream.unwrap_or(0))
+            }
+            DeliveryRequirement::ReliableOrdered(stream) => {
+                (Kind::ReliableOrdered, stream.unwrap_or(0))
+            }
+        };
+
+        let peer = self
+            .peers
+            .entry(message.destination)
+            .or_insert_with(|| Peer::new(now));
+        peer.last_active = now;
+        let sequence = if kind.is_sequenced() {
+            let next = peer.send_sequences.entry((kind as u8, stream)).or_insert(0);
+            let sequence = *next;
+            *next = next.wrapping_add(1);
+            sequence
+        } else {
+            0
+        };
+        let id = if kind.is_reliable() {
+            let id = peer.next_id;
+            peer.next_id = peer.next_id.wrapping_add(1);
+            peer.pending.insert(
+                id,
+                Pending {
+                    kind,
+                    stream,
+                    sequence,
+                    payload: message.payload.clone(),
+                    first_sent: now,
+                    sent_at: now,
+                    resent: false,
+                },
+            );
+            id
+        } else {
+            0
+        };
+
+        peer.write(kind, stream, sequence, id, &message.payload)
+    }
+
+    /// Reads a datagram received from `from` at `now` and returns the payloads which can be
+    /// handed to the game, in the order they have to be delivered.
+    pub fn decode(
+        &mut self,
+        from: SocketAddr,
+        now: Instant,
+        datagram: &[u8],
+    ) -> io::Result<Vec<Bytes>> {
+        if datagram.len() < HEADER_SIZE {
+            return Err(invalid_data("datagram is shorter than its header"));
+        }
+        let kind = Kind::from_byte(datagram[0])
+            .ok_or_else(|| invalid_data("datagram has an unknown delivery kind"))?;
+        let stream = datagram[1];
+        let sequence = u16::from_be_bytes([datagram[2], datagram[3]]);
+        let id = u16::from_be_bytes([datagram[4], datagram[5]]);
+        let ack_count = datagram[6] as usize;
+        let acks_end = HEADER_SIZE + ack_count * 2;
+        if datagram.len() < acks_end {
+            return Err(invalid_data(
+                "datagram is shorter than its acknowledgements",
+            ));
+        }
+
+        let payload = Bytes::copy_from_slice(&datagram[acks_end..]);
+        if !self.peers.contains_key(&from) {
+            // Only acknowledgements and sequences need state, so everything else is delivered
+            // without creating a peer for an address which may be spoofed.
+            if kind == Kind::Unreliable {
+                return Ok(vec![payload]);
+            }
+            if kind == Kind::Ack {
+                return Ok(Vec::new());
+            }
+            if self.peers.len() >= self.max_peers {
+                return Err(io::Error::new(
+                    io::ErrorKind::ConnectionRefused,
+                    "too many peers to receive reliable or sequenced datagrams from",
+                ));
+            }
+        }
+        let peer = self.peers.entry(from).or_insert_with(|| Peer::new(now));
+        peer.last_active = now;
+        for ack in datagram[HEADER_SIZE..acks_end].chunks(2) {
+            if let Some(pending) = peer.pending.remove(&u16::from_be_bytes([ack[0], ack[1]])) {
+                // The acknowledgement of a resent message may be for any of its copies.
+                if !pending.resent {
+                    peer.rtt_samples.push(now.duration_since(pending.sent_at));
+                }
+            }
+        }
+
+        if kind == Kind::Ack {
+            return Ok(Vec::new());
+        }
+        if kind.is_reliable() {
+            // Duplicates are acknowledged again, the previous acknowledgement may have been lost.
+            peer.acks.push_back(id);
+            if !peer.mark_received(id) {
+                return Ok(Vec::new());
+            }
+        }
+
+        let key = (kind as u8, stream);
+        Ok(match kind {
+            Kind::UnreliableSequenced | Kind::ReliableSequenced => {
+                match peer.recv_sequences.get(&key) {
+                    Some(&newest) if !is_newer(sequence, newest) => Vec::new(),
+                    _ => {
+                        peer.recv_sequences.insert(key, sequence);
+                        vec![payload]
+                    }
+                }
+            }
+            Kind::ReliableOrdered => {
+                let ordered = peer.ordered.entry(stream).or_default();
+                ordered.receive(sequence, payload)
+            }
+            _ => vec![payload],
+        })
+    }
+
+    /// Returns the datagrams which have to be sent again because they weren't acknowledged in
+    /// time, and datagrams acknowledging what was received from peers nothing else was sent to.
+    ///
+    /// Peers with a reliable message which wasn't acknowledged within the timeout are dropped
+    /// and returned by `lost_peers`, idle peers are forgotten. Call this once per send tick
+    /// after encoding the messages of the tick.
+    pub fn resends(&mut self, now: Instant) -> Vec<(SocketAddr, Vec<u8>)> {
+        let timeout = self.timeout;
+        let lost = &mut self.lost;
+        self.peers.retain(|addr, peer| {
+            let expired = |since: Instant| now.duration_since(since) >= timeout;
+            if peer
+                .pending
+                .values()
+                .any(|pending| expired(pending.first_sent))
+            {
+                lost.push(*addr);
+                return false;
+            }
+            !(peer.pending.is_empty() && peer.acks.is_empty() && expired(peer.last_active))
+        });
+
+        let resend_after = self.resend_after;
+        let mut datagrams = Vec::new();
+        for (addr, peer) in self.peers.iter_mut() {
+            let mut due: Vec<u16> = peer
+                .pending
+                .iter()
+                .filter(|(_, pending)| now.duration_since(pending.sent_at) >= resend_after)
+                .map(|(id, _)| *id)
+                .collect();
+            due.sort_by_key(|id| id.wrapping_sub(peer.next_id));
+            for id in due {
+                let pending = peer.pending.get_mut(&id).expect("Pending message exists");
+                pending.sent_at = now;
+                pending.resent = true;
+                peer.resends += 1;
+                let (kind, stream, sequence, payload) = (
+                    pending.kind,
+                    pending.stream,
+                    pending.sequence,
+                    pending.payload.clone(),
+                );
+                datagrams.push((*addr, peer.write(kind, stream, sequence, id, &payload)));
+            }
+            while !peer.acks.is_empty() {
+                datagrams.push((*addr, peer.write(Kind::Ack, 0, 0, 0, &[])));
+            }
+        }
+        datagrams
+    }
+
+    /// Returns the peers which were dropped since the last call, because a reliable message to
+    /// them wasn't acknowledged within the timeout. The messages which weren't delivered yet
+    /// are dropped with them.
+    pub fn lost_peers(&mut self) -> Vec<SocketAddr> {
+        std::mem::take(&mut self.lost)
+    }
+
+    /// Returns the number of reliable messages sent to `addr` which weren't acknowledged yet.
+    pub fn pending(&self, addr: SocketAddr) -> usize {
+        self.peers.get(&addr).map_or(0, |peer| peer.pending.len())
+    }
+
+    /// Hands the round-trip times measured and the number of messages resent since the last call
+    /// to the metrics.
+    pub(crate) fn report(&mut self, metrics: &mut NetworkMetrics) {
+        for (addr, peer) in self.peers.iter_mut() {
+            if peer.resends > 0 {
+                metrics.record_resends(*addr, peer.resends);
+                peer.resends = 0;
+            }
+            for sample in peer.rtt_samples.drain(..) {
+                metrics.record_rtt(*addr, sample);
+            }
+        }
+    }
+
+    /// Forgets everything about a peer, e.g. after it disconnected.
+    pub fn drop_peer(&mut self, addr: SocketAddr) {
+        self.peers.remove(&addr);
+    }
+}
+
+/// State of the channels of a single peer.
+#[derive(Debug)]
+struct Peer {
+    /// When something was last sent to or received from the peer.
+    last_active: Instant,
+    next_id: u16,
+    pending: HashMap<u16, Pending>,
+    send_sequences: HashMap<(u8, u8), u16>,
+    acks: VecDeque<u16>,
+    received: HashSet<u16>,
+    received_order: VecDeque<u16>,
+    recv_sequences: HashMap<(u8, u8), u16>,
+    ordered: HashMap<u8, OrderedStream>,
+    rtt_samples: Vec<Duration>,
+    resends: u32,
+}
+
+impl Peer {
+    fn new(now: Instant) -> Self {
+        Self {
+            last_active: now,
+            next_id: 0,
+            pending: HashMap::new(),
+            send_sequences: HashMap::new(),
+            acks: VecDeque::new(),
+            received: HashSet::new(),
+            received_order: VecDeque::new(),
+            recv_sequences: HashMap::new(),
+            ordered: HashMap::new(),
+            rtt_samples: Vec::new(),
+            resends: 0,
+        }
+    }
+
+    /// Writes a datagram, acknowledging as many received messages as fit.
+    fn write(&mut self, kind: Kind, stream: u8, sequence: u16, id: u16, payload: &[u8]) -> Vec<u8> {
+        let ack_count = self.acks.len().min(MAX_ACKS);
+        let mut datagram = Vec::with_capacity(HEADER_SIZE + ack_count * 2 + payload.len());
+        datagram.push(kind as u8);
+        datagram.push(stream);
+        datagram.extend_from_slice(&sequence.to_be_bytes());
+        datagram.extend_from_slice(&id.to_be_bytes());
+        datagram.push(ack_count as u8);
+        for ack in self.acks.drain(..ack_count) {
+            datagram.extend_from_slice(&ack.to_be_bytes());
+        }
+        datagram.extend_from_slice(payload);
+        datagram
+    }
+
+    /// Remembers a reliable id, returning false if it was already received.
+    fn mark_received(&mut self, id: u16) -> bool {
+        if !self.received.insert(id) {
+            return false;
+        }
+        self.received_order.push_back(id);
+        if self.received_order.len() > RECEIVED_WINDOW {
+            if let Some(old) = self.received_order.pop_front() {
+                self.received.remove(&old);
+            }
+        }
+        true
+    }
+}
+
+/// A reliable message waiting for its acknowledgement.
+#[derive(Debug)]
+struct Pending {
+    kind: Kind,
+    stream: u8,
+    sequence: u16,
+    payload: Bytes,
+    first_sent: Instant,
+    sent_at: Instant,
+    resent: bool,
+}
+
+/// Messages of an ordered stream received ahead of the next one to deliver.
+#[derive(Debug, Default)]
+struct OrderedStream {
+    expected: u16,
+    early: HashMap<u16, Bytes>,
+}
+
+impl OrderedStream {
+    fn receive(&mut self, sequence: u16, payload: Bytes) -> Vec<Bytes> {
+        if sequence != self.expected {
+            if is_newer(sequence, self.expected) {
+                self.early.insert(sequence, payload);
+            }
+            return Vec::new();
+        }
+        let mut delivered = vec![payload];
+        self.expected = self.expected.wrapping_add(1);
+        while let Some(payload) = self.early.remove(&self.expected) {
+            delivered.push(payload);
+            self.expected = self.expected.wrapping_add(1);
+        }
+        delivered
+    }
+}
+
+/// Returns true if sequence `a` comes after `b`, accounting for wrap around.
+fn is_newer(a: u16, b: u16) -> bool {
+    a != b && a.wrapping_sub(b) < 0x8000
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::requirements::UrgencyRequirement;
+
+    fn message(delivery: DeliveryRequirement, payload: &[u8]) -> Message {
+        Message::new(
+            "127.0.0.1:3000".parse().unwrap(),
+            payload,
+            delivery,
+            UrgencyRequirement::OnTick,
+        )
+    }
+
+    fn sender() -> SocketAddr {
+        "127.0.0.1:3001".parse().unwrap()
+    }
+
+    #[test]
+    fn test_ordered_messages_are_held_back() {
+        let mut server = DeliveryChannels::default();
+        let mut client = DeliveryChannels::default();
+        let now = Instant::now();
+
+        let datagrams: Vec<_> = [b"1", b"2", b"3"]
+            .iter()
+            .map(|payload| {
+                server.encode(
+                    &message(DeliveryRequirement::ReliableOrdered(None), &payload[..]),
+                    now,
+                )
+            })
+            .collect();
+
+        assert!(client
+            .decode(sender(), now, &datagrams[2])
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            client.decode(sender(), now, &datagrams[0]).unwrap(),
+            vec![Bytes::from_static(b"1")]
+        );
+        assert_eq!(
+            client.decode(sender(), now, &datagrams[1]).unwrap(),
+            vec![Bytes::from_static(b"2"), Bytes::from_static(b"3")]
+        );
+        // Duplicates are dropped.
+        assert!(client
+            .decode(sender(), now, &datagrams[1])
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_sequenced_messages_drop_older() {
+        let mut server = DeliveryChannels::default();
+        let mut client = DeliveryChannels::default();
+        let now = Instant::now();
+
+        let first = server.encode(
+            &message(DeliveryRequirement::UnreliableSequenced(Some(1)), b"1"),
+            now,
+        );
+        let second = server.encode(
+            &message(DeliveryRequirement::UnreliableSequenced(Some(1)), b"2"),
+            now,
+        );
+        let other_stream = server.encode(
+            &message(DeliveryRequirement::UnreliableSequenced(Some(2)), b"3"),
+            now,
+        );
+
+        assert_eq!(client.decode(sender(), now, &second).unwrap().len(), 1);
+        assert!(client.decode(sender(), now, &first).unwrap().is_empty());
+        assert_eq!(
+            client.decode(sender(), now, &other_stream).unwrap().len(),
+            1
+        );
+        assert_eq!(server.pending("127.0.0.1:3000".parse().unwrap()), 0);
+    }
+
+    #[test]
+    fn test_reliable_messages_are_resent_until_acknowledged() {
+        let mut server = DeliveryChannels::default();
+        let mut client = DeliveryChannels::default();
+        let client_addr = "127.0.0.1:3000".parse().unwrap();
+        let now = Instant::now();
+
+        // The first datagram is lost.
+        let _ = server.encode(&message(DeliveryRequirement::Reliable, b"lost"), now);
+        assert_eq!(server.pending(client_addr), 1);
+        assert!(server.resends(now).is_empty());
+
+        let resends = server.resends(now + server.resend_after());
+        assert_eq!(resends.len(), 1);
+        assert_eq!(
+            client.decode(sender(), now, &resends[0].1).unwrap(),
+            vec![Bytes::from_static(b"lost")]
+        );
+
+        // The client acknowledges it with its next datagrams.
+        let acks = client.resends(now);
+        assert_eq!(acks.len(), 1);
+        assert!(server
+            .decode(client_addr, now, &acks[0].1)
+            .unwrap()
+            .is_empty());
+        assert_eq!(server.pending(client_addr), 0);
+    }
+
+    #[test]
+    fn test_unacknowledged_peers_are_lost() {
+        let mut server = DeliveryChannels::default();
+        let client_addr = "127.0.0.1:3000".parse().unwrap();
+        let now = Instant::now();
+
+        let _ = server.encode(&message(DeliveryRequirement::Reliable, b"lost"), now);
+        assert_eq!(server.resends(now + server.resend_after()).len(), 1);
+        assert!(server.lost_peers().is_empty());
+
+        let resends = server.resends(now + server.timeout());
+        assert!(resends.is_empty());
+        assert_eq!(server.lost_peers(), vec![client_addr]);
+        assert_eq!(server.pending(client_addr), 0);
+        assert!(server.lost_peers().is_empty());
+    }
+
+    #[test]
+    fn test_peers_are_limited() {
+        let mut server = DeliveryChannels::default();
+        let mut client = DeliveryChannels::default();
+        client.set_max_peers(1);
+        let now = Instant::now();
+        let unreliable = server.encode(&message(DeliveryRequirement::Unreliable, b"1"), now);
+        let reliable = server.encode(&message(DeliveryRequirement::Reliable, b"2"), now);
+
+        // datagrams which don't need state never create a peer
+        for port in 4000..4010 {
+            let from = SocketAddr::from(([127, 0, 0, 1], port));
+            assert_eq!(client.decode(from, now, &unreliable).unwrap().len(), 1);
+        }
+        assert!(client.peers.is_empty());
+
+        assert_eq!(client.decode(sender(), now, &reliable).unwrap().len(), 1);
+        let other = "127.0.0.1:3002".parse().unwrap();
+        assert!(client.decode(other, now, &reliable).is_err());
+
+        // idle peers are forgotten, making room for new ones
+        let _ = client.resends(now);
+        let _ = client.resends(now + client.timeout());
+        assert!(client.peers.is_empty());
+        assert!(client.lost_peers().is_empty());
+        assert_eq!(client.decode(other, now, &reliable).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_malformed_datagrams_are_rejected() {
+        let mut channels = DeliveryChannels::default();
+        let now = Instant::now();
+        assert!(channels.decode(sender(), now, b"abc").is_err());
+        assert!(channels
+            .decode(sender(), now, &[9, 0, 0, 0, 0, 0, 0, 0])
+            .is_err());
+        assert!(channels
+            .decode(sender(), now, &[0, 0, 0, 0, 0, 0, 2, 0])
+            .is_err());
+    }
+}
@@ -0,0 +1,334 @@
+//! Simulation of a bad connection, to test netcode on localhost.
+//!
+//! The `LinkConditioner` holds back the messages sent through the `TransportResource` before the
+//! transport sends them, delaying them by the configured latency and jitter, and dropping or
+//! duplicating unreliable ones. Since only sent messages are conditioned, use it on both ends to
+//! affect both directions of a connection.
+
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+
+use amethyst_core::ecs::*;
+use amethyst_error::Error;
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+
+use crate::simulation::{
+    message::Message,
+    requirements::{DeliveryRequirement, UrgencyRequirement},
+    timing::NetworkSimulationTime,
+    transport::TransportResource,
+};
+
+#[derive(Debug)]
+struct Delayed {
+    release: Instant,
+    message: Message,
+}
+
+/// Resource configuring the latency, jitter, packet loss and duplication applied to sent
+/// messages. It can be toggled and reconfigured at runtime.
+///
+/// Reliable messages are only delayed, and keep their order.
+#[derive(Debug)]
+pub struct LinkConditioner {
+    enabled: bool,
+    latency: Duration,
+    jitter: Duration,
+    packet_loss: f32,
+    duplication: f32,
+    rng: SmallRng,
+    delayed: Vec<Delayed>,
+    // Release time of the last reliable message to every destination
+    last_reliable: HashMap<SocketAddr, Instant>,
+}
+
+impl Default for LinkConditioner {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            latency: Duration::from_millis(0),
+            jitter: Duration::from_millis(0),
+            packet_loss: 0.0,
+            duplication: 0.0,
+            rng: SmallRng::from_entropy(),
+            delayed: Vec::new(),
+            last_reliable: HashMap::new(),
+        }
+    }
+}
+
+impl LinkConditioner {
+    /// Creates an enabled conditioner which doesn't affect messages until configured.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns true if sent messages are conditioned.
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Enables or disables the conditioner. Held back messages are still sent when it's
+    /// disabled.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    /// Returns the delay added to every message.
+    pub fn latency(&self) -> Duration {
+        self.latency
+    }
+
+    /// Sets the delay added to every message.
+    pub fn set_latency(&mut self, latency: Duration) {
+        self.latency = latency;
+    }
+
+    /// Returns how much the delay of a message varies at most from the latency.
+    pub fn jitter(&self) -> Duration {
+        self.jitter
+    }
+
+    /// Sets how much the delay of a message varies at most from the latency.
+    pub fn set_jitter(&mut self, jitter: Duration) {
+        self.jitter = jitter;
+    }
+
+    /// Returns the probability of dropping an unreliable message, in 0.0-1.0.
+    pub fn packet_loss(&self) -> f32 {
+        self.packet_loss
+    }
+
+    /// Sets the probability of dropping an unreliable message, in 0.0-1.0.
+    pub fn set_packet_loss(&mut self, loss: f32) {
+        self.packet_loss = loss;
+    }
+
+    /// Returns the probability of sending an unreliable message twice, in 0.0-1.0.
+    pub fn duplication(&self) -> f32 {
+        self.duplication
+    }
+
+    /// Sets the probability of sending an unreliable message twice, in 0.0-1.0.
+    pub fn set_duplication(&mut self, duplication: f32) {
+        self.duplication = duplication;
+    }
+
+    /// Seeds the random number generator, to reproduce the same losses and delays.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = SmallRng::seed_from_u64(seed);
+    }
+
+    /// Returns the number of messages held back.
+    pub fn delayed(&self) -> usize {
+        self.delayed.len()
+    }
+
+    /// Holds back the given messages, dropping or duplicating unreliable ones.
+    fn condition(&mut self, messages: Vec<Message>, now: Instant) {
+        for message in messages {
+            let unreliable = matches!(
+                message.delivery,
+                DeliveryRequirement::Unreliable | DeliveryRequirement::UnreliableSequenced(_)
+            );
+            if unreliable && self.rng.gen::<f32>() < self.packet_loss {
+                continue;
+            }
+            if unreliable && self.rng.gen::<f32>() < self.duplication {
+                let duplicate = Message {
+                    destination: message.destination,
+                    payload: message.payload.clone(),
+                    delivery: message.delivery,
+                    urgency: message.urgency,
+                };
+                self.delay(duplicate, false, now);
+            }
+            self.delay(message, !unreliable, now);
+        }
+    }
+
+    fn delay(&mut self, message: Message, reliable: bool, now: Instant) {
+        // the latency is added as is, so it isn't rounded through a float
+        let mut release = now + self.latency;
+        if self.jitter > Duration::from_millis(0) {
+            let offset: f32 = self.rng.gen_range(-1.0..=1.0);
+            let jitter = self.jitter.mul_f32(offset.abs());
+            release = if offset < 0.0 {
+                (release - jitter).max(now)
+            } else {
+                release + jitter
+            };
+        }
+        if reliable {
+            let last = self
+                .last_reliable
+                .entry(message.destination)
+                .or_insert(release);
+            release = release.max(*last);
+            *last = release;
+        }
+        self.delayed.push(Delayed { release, message });
+    }
+
+    /// Returns the held back messages due at the given time, in the order they were held back.
+    fn release(&mut self, now: Instant) -> Vec<Message> {
+        let enabled = self.enabled;
+        let (due, delayed): (Vec<_>, Vec<_>) = self
+            .delayed
+            .drain(..)
+            .partition(|delayed| delayed.release <= now || !enabled);
+        self.delayed = delayed;
+        self.last_reliable.retain(|_, release| *release > now);
+        due.into_iter().map(|delayed| delayed.message).collect()
+    }
+}
+
+/// Conditions the messages to send before the transport sends them.
+#[derive(Debug)]
+pub struct LinkConditionerSystem;
+
+impl System for LinkConditionerSystem {
+    fn build(self) -> Box<dyn ParallelRunnable> {
+        Box::new(
+            SystemBuilder::new("LinkConditionerSystem")
+                .write_resource::<LinkConditioner>()
+                .write_resource::<TransportResource>()
+                .read_resource::<NetworkSimulationTime>()
+                .build(
+                    move |_commands, _world, (conditioner, transport, sim_time), _| {
+                        #[cfg(feature = "profiler")]
+                        profile_scope!("link_conditioner_system");
+
+                        let now = Instant::now();
+                        if conditioner.is_enabled() {
+                            let messages = transport
+                                .drain_messages_to_send(|_| sim_time.should_send_message_now());
+                            conditioner.condition(messages, now);
+                        }
+                        // Released messages are sent right away, since they were delayed enough.
+                        for message in conditioner.release(now) {
+                            transport.send_with_requirements(
+                                message.destination,
+                                &message.payload,
+                                message.delivery,
+                                UrgencyRequirement::Immediate,
+                            );
+                        }
+                    },
+                ),
+        )
+    }
+}
+
+/// Adds the `LinkConditioner` resource and the `LinkConditionerSystem`.
+///
+/// Add this bundle before the network bundle of the transport.
+#[derive(Debug)]
+pub struct LinkConditionerBundle {
+    conditioner: Option<LinkConditioner>,
+}
+
+impl LinkConditionerBundle {
+    /// Creates the bundle with the given conditioner.
+    pub fn new(conditioner: LinkConditioner) -> Self {
+        Self {
+            conditioner: Some(conditioner),
+        }
+    }
+}
+
+impl SystemBundle for LinkConditionerBundle {
+    fn load(
+        &mut self,
+        _world: &mut World,
+        resources: &mut Resources,
+        builder: &mut DispatcherBuilder,
+    ) -> Result<(), Error> {
+        resources.get_or_default::<TransportResource>();
+        resources.get_or_default::<NetworkSimulationTime>();
+        if let Some(conditioner) = self.conditioner.take() {
+            resources.insert(conditioner);
+        }
+        builder.add_system(LinkConditionerSystem);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(payload: &'static [u8], delivery: DeliveryRequirement) -> Message {
+        Message::new(
+            "127.0.0.1:3000".parse().unwrap(),
+            payload,
+            delivery,
+            UrgencyRequirement::OnTick,
+        )
+    }
+
+    fn conditioner() -> LinkConditioner {
+        let mut conditioner = LinkConditioner::new();
+        conditioner.set_seed(7);
+        conditioner
+    }
+
+    #[test]
+    fn test_messages_are_delayed_by_latency() {
+        let now = Instant::now();
+        let mut conditioner = conditioner();
+        conditioner.set_latency(Duration::from_millis(100));
+        conditioner.condition(vec![message(b"a", DeliveryRequirement::Unreliable)], now);
+
+        assert!(conditioner
+            .release(now + Duration::from_millis(50))
+            .is_empty());
+        assert_eq!(
+            conditioner.release(now + Duration::from_millis(100)).len(),
+            1
+        );
+        assert_eq!(conditioner.delayed(), 0);
+    }
+
+    #[test]
+    fn test_loss_and_duplication_only_affect_unreliable_messages() {
+        let now = Instant::now();
+        let mut conditioner = conditioner();
+        conditioner.set_packet_loss(1.0);
+        conditioner.condition(
+            vec![
+                message(b"a", DeliveryRequirement::Unreliable),
+                message(b"b", DeliveryRequirement::ReliableOrdered(None)),
+            ],
+            now,
+        );
+        let released = conditioner.release(now);
+        assert_eq!(released.len(), 1);
+        assert_eq!(&released[0].payload[..], b"b");
+
+        conditioner.set_packet_loss(0.0);
+        conditioner.set_duplication(1.0);
+        conditioner.condition(vec![message(b"c", DeliveryRequirement::Unreliable)], now);
+        assert_eq!(conditioner.release(now).len(), 2);
+    }
+
+    #[test]
+    fn test_reliable_messages_keep_their_order() {
+        let now = Instant::now();
+        let mut conditioner = conditioner();
+        conditioner.set_latency(Duration::from_millis(100));
+        conditioner.set_jitter(Duration::from_millis(100));
+        let messages = (0..10)
+            .map(|_| message(b"r", DeliveryRequirement::ReliableOrdered(None)))
+            .collect();
+        conditioner.condition(messages, now);
+
+        let releases: Vec<_> = conditioner.delayed.iter().map(|d| d.release).collect();
+        assert!(releases.windows(2).all(|pair| pair[0] <= pair[1]));
+    }
+}
@@ -0,0 +1,362 @@
+//! Compact serialization helpers, to keep the bandwidth of messages sent every tick down.
+//!
+//! `BitWriter` and `BitReader` pack values into as few bits as they need: varints, quantized
+//! floats and `ChangeMask`s telling which fields of a value changed against a baseline, so only
+//! those have to be sent.
+//!
+//! ```
+//! use amethyst_network::simulation::{BitReader, BitWriter, ChangeMask, Quantization};
+//!
+//! let position = Quantization::new(-512.0, 512.0, 16);
+//! let (baseline, current) = ([1.0, 2.0, 3.0], [1.0, 2.5, 3.0]);
+//!
+//! let mut writer = BitWriter::new();
+//! let mut mask = ChangeMask::new(3);
+//! for field in 0..3 {
+//!     mask.compare(field, &baseline[field], &current[field]);
+//! }
+//! mask.write(&mut writer);
+//! for field in mask.changed() {
+//!     writer.write_quantized(current[field], &position);
+//! }
+//! let bytes = writer.finish();
+//!
+//! let mut reader = BitReader::new(&bytes);
+//! let mask = ChangeMask::read(&mut reader).unwrap();
+//! assert_eq!(mask.changed().collect::<Vec<_>>(), vec![1]);
+//! let y = reader.read_quantized(&position).unwrap();
+//! assert!((y - 2.5).abs() <= position.precision());
+//! ```
+
+/// Largest number of bytes of a varint encoding an `u64`.
+const MAX_VARINT_BYTES: usize = 10;
+
+/// Range and resolution of a quantized float.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Quantization {
+    /// Smallest value, values below it are clamped.
+    pub min: f32,
+    /// Largest value, values above it are clamped.
+    pub max: f32,
+    /// Number of bits of a quantized value, at most 32.
+    pub bits: u32,
+}
+
+impl Quantization {
+    /// Creates a quantization of the range `min..=max` into the given number of bits.
+    pub fn new(min: f32, max: f32, bits: u32) -> Self {
+        assert!((1..=32).contains(&bits), "Quantizing into {} bits", bits);
+        assert!(min < max, "Quantizing an empty range");
+        Self { min, max, bits }
+    }
+
+    /// Returns the largest difference between a value in range and its dequantized value.
+    pub fn precision(&self) -> f32 {
+        (self.range() / self.steps() as f64 / 2.0) as f32
+    }
+
+    /// Maps a value to an integer of `bits` bits.
+    pub fn quantize(&self, value: f32) -> u64 {
+        let normalized = ((f64::from(value) - f64::from(self.min)) / self.range()).clamp(0.0, 1.0);
+        (normalized * self.steps() as f64).round() as u64
+    }
+
+    /// Maps an integer returned by `quantize` back to a value.
+    pub fn dequantize(&self, quantized: u64) -> f32 {
+        let normalized = quantized.min(self.steps()) as f64 / self.steps() as f64;
+        (f64::from(self.min) + normalized * self.range()) as f32
+    }
+
+    // Computed in double precision, so values are restored within `precision`.
+    fn range(&self) -> f64 {
+        f64::from(self.max) - f64::from(self.min)
+    }
+
+    fn steps(&self) -> u64 {
+        (1u64 << self.bits) - 1
+    }
+}
+
+/// Writes values into a buffer bit by bit.
+#[derive(Clone, Debug, Default)]
+pub struct BitWriter {
+    bytes: Vec<u8>,
+    bits: usize,
+}
+
+impl BitWriter {
+    /// Creates an empty writer.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of bits written so far.
+    pub fn len_bits(&self) -> usize {
+        self.bits
+    }
+
+    /// Writes the lowest `count` bits of a value, at most 64.
+    pub fn write_bits(&mut self, value: u64, count: u32) {
+        assert!(count <= 64, "Writing {} bits at once", count);
+        for i in 0..count {
+            if self.bits % 8 == 0 {
+                self.bytes.push(0);
+            }
+            if (value >> i) & 1 == 1 {
+                let last = self.bytes.len() - 1;
+                self.bytes[last] |= 1 << (self.bits % 8);
+            }
+            self.bits += 1;
+        }
+    }
+
+    /// Writes a single bit.
+    pub fn write_bool(&mut self, value: bool) {
+        self.write_bits(u64::from(value), 1);
+    }
+
+    /// Writes an unsigned integer in as few bytes as needed, 7 bits per byte.
+    pub fn write_varint(&mut self, mut value: u64) {
+        loop {
+            let group = value & 0x7f;
+            value >>= 7;
+            if value == 0 {
+                self.write_bits(group, 8);
+                return;
+            }
+            self.write_bits(group | 0x80, 8);
+        }
+    }
+
+    /// Writes a signed integer as a varint, small negative values staying small.
+    pub fn write_signed_varint(&mut self, value: i64) {
+        self.write_varint(((value << 1) ^ (value >> 63)) as u64);
+    }
+
+    /// Writes a float quantized into `quantization.bits` bits.
+    pub fn write_quantized(&mut self, value: f32, quantization: &Quantization) {
+        self.write_bits(quantization.quantize(value), quantization.bits);
+    }
+
+    /// Writes bytes prefixed with their length.
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        self.write_varint(bytes.len() as u64);
+        for byte in bytes {
+            self.write_bits(u64::from(*byte), 8);
+        }
+    }
+
+    /// Returns the written bytes, the last one padded with zeros.
+    pub fn finish(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+/// Reads values written by a `BitWriter`. Every read returns `None` once the data runs out or
+/// is malformed.
+#[derive(Clone, Debug)]
+pub struct BitReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> BitReader<'a> {
+    /// Creates a reader of the given bytes.
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, position: 0 }
+    }
+
+    /// Returns the number of bits left to read, including the padding of the last byte.
+    pub fn remaining_bits(&self) -> usize {
+        self.bytes.len() * 8 - self.position
+    }
+
+    /// Reads a value of `count` bits, at most 64.
+    pub fn read_bits(&mut self, count: u32) -> Option<u64> {
+        if count > 64 || self.remaining_bits() < count as usize {
+            return None;
+        }
+        let mut value = 0;
+        for i in 0..count {
+            let byte = self.bytes[self.position / 8];
+            if (byte >> (self.position % 8)) & 1 == 1 {
+                value |= 1 << i;
+            }
+            self.position += 1;
+        }
+        Some(value)
+    }
+
+    /// Reads a single bit.
+    pub fn read_bool(&mut self) -> Option<bool> {
+        self.read_bits(1).map(|bit| bit == 1)
+    }
+
+    /// Reads an unsigned integer written with `write_varint`.
+    pub fn read_varint(&mut self) -> Option<u64> {
+        let mut value = 0;
+        for i in 0..MAX_VARINT_BYTES {
+            let byte = self.read_bits(8)?;
+            value |= (byte & 0x7f).checked_shl(7 * i as u32)?;
+            if byte & 0x80 == 0 {
+                return Some(value);
+            }
+        }
+        None
+    }
+
+    /// Reads a signed integer written with `write_signed_varint`.
+    pub fn read_signed_varint(&mut self) -> Option<i64> {
+        let value = self.read_varint()?;
+        Some(((value >> 1) as i64) ^ -((value & 1) as i64))
+    }
+
+    /// Reads a float written with `write_quantized` and the same quantization.
+    pub fn read_quantized(&mut self, quantization: &Quantization) -> Option<f32> {
+        self.read_bits(quantization.bits)
+            .map(|quantized| quantization.dequantize(quantized))
+    }
+
+    /// Reads bytes written with `write_bytes`.
+    pub fn read_bytes(&mut self) -> Option<Vec<u8>> {
+        let len = self.read_varint()? as usize;
+        if self.remaining_bits() / 8 < len {
+            return None;
+        }
+        (0..len)
+            .map(|_| self.read_bits(8).map(|b| b as u8))
+            .collect()
+    }
+}
+
+/// The fields of a value which differ from a baseline.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ChangeMask {
+    words: Vec<u64>,
+    fields: usize,
+}
+
+impl ChangeMask {
+    /// Creates a mask of the given number of fields, none of which changed.
+    pub fn new(fields: usize) -> Self {
+        Self {
+            words: vec![0; (fields + 63) / 64],
+            fields,
+        }
+    }
+
+    /// Returns the number of fields of the mask.
+    pub fn fields(&self) -> usize {
+        self.fields
+    }
+
+    /// Marks the field as changed if it differs from the baseline, returning true if it does.
+    pub fn compare<T: PartialEq>(&mut self, field: usize, baseline: &T, current: &T) -> bool {
+        let changed = baseline != current;
+        if changed {
+            self.set(field);
+        }
+        changed
+    }
+
+    /// Marks the field as changed.
+    pub fn set(&mut self, field: usize) {
+        assert!(field < self.fields, "Field {} is out of the mask", field);
+        self.words[field / 64] |= 1 << (field % 64);
+    }
+
+    /// Returns true if the field changed.
+    pub fn is_set(&self, field: usize) -> bool {
+        field < self.fields && self.words[field / 64] & (1 << (field % 64)) != 0
+    }
+
+    /// Returns true if no field changed.
+    pub fn is_empty(&self) -> bool {
+        self.words.iter().all(|word| *word == 0)
+    }
+
+    /// Returns the changed fields in ascending order.
+    pub fn changed(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.fields).filter(move |field| self.is_set(*field))
+    }
+
+    /// Writes the number of fields followed by a bit per field.
+    pub fn write(&self, writer: &mut BitWriter) {
+        writer.write_varint(self.fields as u64);
+        for field in 0..self.fields {
+            writer.write_bool(self.is_set(field));
+        }
+    }
+
+    /// Reads a mask written with `write`.
+    pub fn read(reader: &mut BitReader<'_>) -> Option<Self> {
+        let fields = reader.read_varint()? as usize;
+        if reader.remaining_bits() < fields {
+            return None;
+        }
+        let mut mask = Self::new(fields);
+        for field in 0..fields {
+            if reader.read_bool()? {
+                mask.set(field);
+            }
+        }
+        Some(mask)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_values_round_trip() {
+        let mut writer = BitWriter::new();
+        writer.write_bits(5, 3);
+        writer.write_bool(true);
+        writer.write_varint(300);
+        writer.write_varint(u64::MAX);
+        writer.write_signed_varint(-2);
+        writer.write_bytes(b"abc");
+        assert_eq!(writer.len_bits(), 3 + 1 + 16 + 80 + 8 + 32);
+        let bytes = writer.finish();
+
+        let mut reader = BitReader::new(&bytes);
+        assert_eq!(reader.read_bits(3), Some(5));
+        assert_eq!(reader.read_bool(), Some(true));
+        assert_eq!(reader.read_varint(), Some(300));
+        assert_eq!(reader.read_varint(), Some(u64::MAX));
+        assert_eq!(reader.read_signed_varint(), Some(-2));
+        assert_eq!(reader.read_bytes(), Some(b"abc".to_vec()));
+        assert!(reader.remaining_bits() < 8);
+        assert_eq!(reader.read_bits(8), None);
+    }
+
+    #[test]
+    fn test_quantized_floats_stay_within_precision() {
+        let quantization = Quantization::new(-10.0, 10.0, 12);
+        for value in &[-10.0, -3.3, 0.0, 0.001, 7.25, 10.0] {
+            let restored = quantization.dequantize(quantization.quantize(*value));
+            assert!((restored - value).abs() <= quantization.precision());
+        }
+        assert_eq!(quantization.dequantize(quantization.quantize(50.0)), 10.0);
+    }
+
+    #[test]
+    fn test_change_mask_round_trip() {
+        let mut mask = ChangeMask::new(70);
+        assert!(mask.is_empty());
+        assert!(!mask.compare(0, &1, &1));
+        assert!(mask.compare(3, &1, &2));
+        mask.set(69);
+
+        let mut writer = BitWriter::new();
+        mask.write(&mut writer);
+        let bytes = writer.finish();
+        let read = ChangeMask::read(&mut BitReader::new(&bytes)).unwrap();
+        assert_eq!(read, mask);
+        assert_eq!(read.changed().collect::<Vec<_>>(), vec![3, 69]);
+
+        // Truncated masks are rejected.
+        assert_eq!(ChangeMask::read(&mut BitReader::new(&bytes[..2])), None);
+    }
+}
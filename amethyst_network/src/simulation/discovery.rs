@@ -0,0 +1,360 @@
+//! Discovery of game servers, for server browsers and lobbies.
+//!
+//! Servers on the local network announce themselves by broadcasting their `ServerInfo` with a
+//! `LanDiscovery`, which clients listen for. With the `master-server` feature, servers can also
+//! register with a master server, from which clients fetch the list of servers on the internet.
+//! Servers found either way are listed in the `ServerList` resource.
+
+#[cfg(feature = "master-server")]
+pub mod master_server;
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    io,
+    net::{Ipv4Addr, SocketAddr, UdpSocket},
+    time::{Duration, Instant},
+};
+
+use amethyst_core::ecs::*;
+use amethyst_error::Error;
+use log::warn;
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+
+use crate::simulation::packet;
+
+/// Bytes in front of every LAN announcement.
+const PACKET_MAGIC: [u8; 4] = *b"ADSC";
+
+/// Largest announcement which can be received.
+const MAX_ANNOUNCEMENT_SIZE: usize = 4096;
+
+/// Description of a game server, shown in server browsers.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ServerInfo {
+    /// Name of the server.
+    pub name: String,
+    /// Port the game is served on. The address is the one the server is discovered from.
+    pub port: u16,
+    /// Number of connected players.
+    pub players: u32,
+    /// Maximum number of players.
+    pub max_players: u32,
+    /// Version of the game protocol, to hide incompatible servers.
+    pub version: u32,
+    /// Game specific properties, such as the map or game mode.
+    pub properties: BTreeMap<String, String>,
+}
+
+/// Where a server was discovered.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DiscoverySource {
+    /// Announced on the local network.
+    Lan,
+    /// Listed by the master server.
+    MasterServer,
+}
+
+/// A server found by discovery.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DiscoveredServer {
+    /// Address the game is served on.
+    pub addr: SocketAddr,
+    /// Description of the server.
+    pub info: ServerInfo,
+    /// Where the server was discovered.
+    pub source: DiscoverySource,
+    /// When the server was last heard of.
+    pub last_seen: Instant,
+}
+
+/// Resource listing the discovered servers.
+#[derive(Debug)]
+pub struct ServerList {
+    servers: HashMap<SocketAddr, DiscoveredServer>,
+    timeout: Duration,
+}
+
+impl Default for ServerList {
+    fn default() -> Self {
+        Self {
+            servers: HashMap::new(),
+            timeout: Duration::from_secs(5),
+        }
+    }
+}
+
+impl ServerList {
+    /// Returns all discovered servers.
+    pub fn servers(&self) -> impl Iterator<Item = &DiscoveredServer> {
+        self.servers.values()
+    }
+
+    /// Returns the server with the given game address.
+    pub fn get(&self, addr: SocketAddr) -> Option<&DiscoveredServer> {
+        self.servers.get(&addr)
+    }
+
+    /// Returns the number of discovered servers.
+    pub fn len(&self) -> usize {
+        self.servers.len()
+    }
+
+    /// Returns true if no server was discovered.
+    pub fn is_empty(&self) -> bool {
+        self.servers.is_empty()
+    }
+
+    /// Forgets all discovered servers.
+    pub fn clear(&mut self) {
+        self.servers.clear();
+    }
+
+    /// Sets the time after which servers on the LAN which stopped announcing themselves are
+    /// removed, 5 seconds by default.
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    fn insert(
+        &mut self,
+        addr: SocketAddr,
+        info: ServerInfo,
+        source: DiscoverySource,
+        now: Instant,
+    ) {
+        self.servers.insert(
+            addr,
+            DiscoveredServer {
+                addr,
+                info,
+                source,
+                last_seen: now,
+            },
+        );
+    }
+
+    /// Replaces all servers of the given source.
+    #[cfg_attr(not(feature = "master-server"), allow(dead_code))]
+    fn replace(
+        &mut self,
+        source: DiscoverySource,
+        servers: Vec<(SocketAddr, ServerInfo)>,
+        now: Instant,
+    ) {
+        self.servers.retain(|_, server| server.source != source);
+        for (addr, info) in servers {
+            self.servers.entry(addr).or_insert(DiscoveredServer {
+                addr,
+                info,
+                source,
+                last_seen: now,
+            });
+        }
+    }
+
+    /// Removes the servers on the LAN which timed out.
+    fn expire(&mut self, now: Instant) {
+        let timeout = self.timeout;
+        self.servers.retain(|_, server| {
+            server.source != DiscoverySource::Lan || now.duration_since(server.last_seen) < timeout
+        });
+    }
+}
+
+/// Resource broadcasting the `ServerInfo` of a server on the local network, or listening for the
+/// announcements of servers.
+#[derive(Debug)]
+pub struct LanDiscovery {
+    socket: UdpSocket,
+    port: u16,
+    info: Option<ServerInfo>,
+    interval: Duration,
+    last_announcement: Option<Instant>,
+    buffer: Vec<u8>,
+}
+
+impl LanDiscovery {
+    /// Creates the discovery of a server, announcing it to the given port every second.
+    pub fn announce(port: u16, info: ServerInfo) -> io::Result<Self> {
+        let mut discovery = Self::bind(port, 0)?;
+        discovery.info = Some(info);
+        Ok(discovery)
+    }
+
+    /// Creates the discovery of a client, listening for announcements on the given port.
+    pub fn listen(port: u16) -> io::Result<Self> {
+        Self::bind(port, port)
+    }
+
+    fn bind(port: u16, local_port: u16) -> io::Result<Self> {
+        let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, local_port))?;
+        socket.set_broadcast(true)?;
+        socket.set_nonblocking(true)?;
+        Ok(Self {
+            socket,
+            port,
+            info: None,
+            interval: Duration::from_secs(1),
+            last_announcement: None,
+            buffer: vec![0; MAX_ANNOUNCEMENT_SIZE],
+        })
+    }
+
+    /// Returns the announced description of the server.
+    pub fn info(&self) -> Option<&ServerInfo> {
+        self.info.as_ref()
+    }
+
+    /// Sets the announced description of the server, e.g. when players joined. Nothing is
+    /// announced if it's `None`.
+    pub fn set_info(&mut self, info: Option<ServerInfo>) {
+        self.info = info;
+    }
+
+    /// Sets the interval between announcements.
+    pub fn set_interval(&mut self, interval: Duration) {
+        self.interval = interval;
+    }
+
+    /// Broadcasts the description of the server if the interval elapsed.
+    fn announce_if_due(&mut self, now: Instant) {
+        let info = match &self.info {
+            Some(info) => info,
+            None => return,
+        };
+        if self
+            .last_announcement
+            .map_or(false, |last| now.duration_since(last) < self.interval)
+        {
+            return;
+        }
+        self.last_announcement = Some(now);
+        let announcement = packet::encode(&PACKET_MAGIC, info);
+        if let Err(e) = self
+            .socket
+            .send_to(&announcement, (Ipv4Addr::BROADCAST, self.port))
+        {
+            warn!("Failed to announce the server on the LAN: {}", e);
+        }
+    }
+
+    /// Returns the game addresses and descriptions of the servers announced since last called.
+    fn receive(&mut self) -> Vec<(SocketAddr, ServerInfo)> {
+        let mut announcements = Vec::new();
+        loop {
+            match self.socket.recv_from(&mut self.buffer) {
+                Ok((len, source)) => {
+                    if let Some(announcement) = parse_announcement(source, &self.buffer[..len]) {
+                        announcements.push(announcement);
+                    }
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) => {
+                    warn!("Failed to receive LAN announcements: {}", e);
+                    break;
+                }
+            }
+        }
+        announcements
+    }
+}
+
+/// Returns the game address and description of a server from its announcement.
+fn parse_announcement(source: SocketAddr, bytes: &[u8]) -> Option<(SocketAddr, ServerInfo)> {
+    let info: ServerInfo = packet::decode(&PACKET_MAGIC, bytes)?;
+    Some((SocketAddr::new(source.ip(), info.port), info))
+}
+
+/// Announces the server, or lists the servers announced on the LAN in the `ServerList`.
+#[derive(Debug)]
+pub struct LanDiscoverySystem;
+
+impl System for LanDiscoverySystem {
+    fn build(self) -> Box<dyn ParallelRunnable> {
+        Box::new(
+            SystemBuilder::new("LanDiscoverySystem")
+                .write_resource::<LanDiscovery>()
+                .write_resource::<ServerList>()
+                .build(move |_commands, _world, (discovery, servers), _| {
+                    #[cfg(feature = "profiler")]
+                    profile_scope!("lan_discovery_system");
+
+                    let now = Instant::now();
+                    discovery.announce_if_due(now);
+                    for (addr, info) in discovery.receive() {
+                        servers.insert(addr, info, DiscoverySource::Lan, now);
+                    }
+                    servers.expire(now);
+                }),
+        )
+    }
+}
+
+/// Adds the `LanDiscovery` and `ServerList` resources and the `LanDiscoverySystem`.
+#[derive(Debug)]
+pub struct LanDiscoveryBundle {
+    discovery: Option<LanDiscovery>,
+}
+
+impl LanDiscoveryBundle {
+    /// Creates the bundle with the given discovery.
+    pub fn new(discovery: LanDiscovery) -> Self {
+        Self {
+            discovery: Some(discovery),
+        }
+    }
+}
+
+impl SystemBundle for LanDiscoveryBundle {
+    fn load(
+        &mut self,
+        _world: &mut World,
+        resources: &mut Resources,
+        builder: &mut DispatcherBuilder,
+    ) -> Result<(), Error> {
+        resources.get_or_default::<ServerList>();
+        if let Some(discovery) = self.discovery.take() {
+            resources.insert(discovery);
+        }
+        builder.add_system(LanDiscoverySystem);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_announcements_are_listed_until_they_time_out() {
+        let info = ServerInfo {
+            name: "Arena".into(),
+            port: 4000,
+            max_players: 8,
+            ..Default::default()
+        };
+        let source = "192.168.1.20:52000".parse().unwrap();
+        let (addr, parsed) =
+            parse_announcement(source, &packet::encode(&PACKET_MAGIC, &info)).unwrap();
+        assert_eq!(addr, "192.168.1.20:4000".parse().unwrap());
+        assert_eq!(parsed, info);
+        assert_eq!(parse_announcement(source, b"hello"), None);
+
+        let now = Instant::now();
+        let mut servers = ServerList::default();
+        servers.insert(addr, parsed, DiscoverySource::Lan, now);
+        servers.replace(
+            DiscoverySource::MasterServer,
+            vec![("10.0.0.1:4000".parse().unwrap(), info)],
+            now,
+        );
+        servers.expire(now + Duration::from_secs(1));
+        assert_eq!(servers.len(), 2);
+
+        servers.expire(now + Duration::from_secs(5));
+        assert_eq!(servers.len(), 1);
+        assert!(servers.get(addr).is_none());
+    }
+}
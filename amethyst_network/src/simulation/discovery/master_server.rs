@@ -0,0 +1,373 @@
+//! Client of a master server listing the game servers on the internet.
+//!
+//! The master server is expected to serve a small JSON API over plain HTTP:
+//!
+//! * `POST <url>/servers` registers the `ServerInfo` in the body, under the address the request
+//!   comes from and the port of the info. Servers register again periodically, so the master
+//!   server can drop the ones which stopped.
+//! * `DELETE <url>/servers/<port>` removes the server registered from the address of the request
+//!   with the given port.
+//! * `GET <url>/servers` returns an array of `{ "addr": "<ip>:<port>", "info": <ServerInfo> }`.
+
+use std::{
+    io::{self, Read, Write},
+    net::{SocketAddr, TcpStream, ToSocketAddrs},
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use amethyst_core::ecs::*;
+use amethyst_error::Error;
+use log::warn;
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+
+use super::{DiscoverySource, ServerInfo, ServerList};
+
+/// Time after which requests to the master server are given up.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Largest response read from the master server, longer responses are rejected.
+const MAX_RESPONSE_SIZE: u64 = 4 * 1024 * 1024;
+
+/// A server as listed by the master server.
+#[derive(Debug, Serialize, Deserialize)]
+struct ListedServer {
+    addr: SocketAddr,
+    info: ServerInfo,
+}
+
+/// Result of a request, handed from its thread to the `MasterServerClient`.
+#[derive(Debug)]
+enum Response {
+    Registered(io::Result<()>),
+    Unregistered(io::Result<()>),
+    Servers(io::Result<Vec<ListedServer>>),
+}
+
+/// Resource registering a server with a master server, or fetching the list of servers from it.
+///
+/// Requests are sent from background threads, so they don't block the game.
+#[derive(Debug)]
+pub struct MasterServerClient {
+    host: String,
+    path: String,
+    registration: Option<ServerInfo>,
+    register_interval: Duration,
+    last_registration: Option<Instant>,
+    refreshing: bool,
+    responses: Arc<Mutex<Vec<Response>>>,
+}
+
+impl MasterServerClient {
+    /// Creates a client of the master server at the given `http://host[:port][/path]` URL.
+    pub fn new(url: &str) -> Result<Self, Error> {
+        let (host, path) = parse_url(url)
+            .ok_or_else(|| Error::from_string(format!("Invalid master server URL {}", url)))?;
+        Ok(Self {
+            host,
+            path,
+            registration: None,
+            register_interval: Duration::from_secs(30),
+            last_registration: None,
+            refreshing: false,
+            responses: Arc::new(Mutex::new(Vec::new())),
+        })
+    }
+
+    /// Registers the server with the given description, every register interval until it's
+    /// unregistered. Call it again when the description changes.
+    pub fn register(&mut self, info: ServerInfo) {
+        self.registration = Some(info);
+        self.last_registration = None;
+    }
+
+    /// Removes the server from the master server.
+    pub fn unregister(&mut self) {
+        if let Some(info) = self.registration.take() {
+            let path = format!("{}/servers/{}", self.path, info.port);
+            self.spawn(move |host| {
+                Response::Unregistered(request(&host, "DELETE", &path, None).map(|_| ()))
+            });
+        }
+    }
+
+    /// Sets the interval at which the server registers again, 30 seconds by default.
+    pub fn set_register_interval(&mut self, interval: Duration) {
+        self.register_interval = interval;
+    }
+
+    /// Fetches the list of servers into the `ServerList`, unless it's already being fetched.
+    pub fn refresh(&mut self) {
+        if self.refreshing {
+            return;
+        }
+        self.refreshing = true;
+        let path = format!("{}/servers", self.path);
+        self.spawn(move |host| {
+            Response::Servers(request(&host, "GET", &path, None).and_then(|body| {
+                serde_json::from_slice(&body)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+            }))
+        });
+    }
+
+    /// Returns true while the list of servers is being fetched.
+    pub fn is_refreshing(&self) -> bool {
+        self.refreshing
+    }
+
+    fn spawn<F>(&self, send: F)
+    where
+        F: FnOnce(String) -> Response + Send + 'static,
+    {
+        let host = self.host.clone();
+        let responses = Arc::clone(&self.responses);
+        thread::spawn(move || {
+            let response = send(host);
+            responses
+                .lock()
+                .expect("Master server responses are poisoned")
+                .push(response);
+        });
+    }
+
+    /// Registers the server if the interval elapsed, and lists the fetched servers.
+    fn update(&mut self, now: Instant, servers: &mut ServerList) {
+        if let Some(info) = &self.registration {
+            if self.last_registration.map_or(true, |last| {
+                now.duration_since(last) >= self.register_interval
+            }) {
+                self.last_registration = Some(now);
+                let path = format!("{}/servers", self.path);
+                let body = serde_json::to_vec(info).expect("Serializing server info");
+                self.spawn(move |host| {
+                    Response::Registered(request(&host, "POST", &path, Some(&body)).map(|_| ()))
+                });
+            }
+        }
+
+        let responses = std::mem::take(
+            &mut *self
+                .responses
+                .lock()
+                .expect("Master server responses are poisoned"),
+        );
+        for response in responses {
+            match response {
+                Response::Registered(Err(e)) => {
+                    warn!("Failed to register with the master server: {}", e)
+                }
+                Response::Unregistered(Err(e)) => {
+                    warn!("Failed to unregister from the master server: {}", e)
+                }
+                Response::Servers(result) => {
+                    self.refreshing = false;
+                    match result {
+                        Ok(listed) => {
+                            servers.replace(
+                                DiscoverySource::MasterServer,
+                                listed
+                                    .into_iter()
+                                    .map(|server| (server.addr, server.info))
+                                    .collect(),
+                                now,
+                            )
+                        }
+                        Err(e) => warn!("Failed to fetch the servers of the master server: {}", e),
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Splits an `http://` URL into the host with its port and the path, without a trailing slash.
+fn parse_url(url: &str) -> Option<(String, String)> {
+    let rest = url.strip_prefix("http://")?;
+    let (host, path) = match rest.find('/') {
+        Some(index) => rest.split_at(index),
+        None => (rest, ""),
+    };
+    if host.is_empty() {
+        return None;
+    }
+    // the host of an IPv6 address is bracketed, as in `[::1]:8080`
+    let has_port = match host.rfind(']') {
+        Some(index) => host[index + 1..].starts_with(':'),
+        None => host.contains(':'),
+    };
+    let host = if has_port {
+        host.to_string()
+    } else {
+        format!("{}:80", host)
+    };
+    Some((host, path.trim_end_matches('/').to_string()))
+}
+
+/// Sends an HTTP/1.0 request, returning the body of a successful response.
+fn request(host: &str, method: &str, path: &str, body: Option<&[u8]>) -> io::Result<Vec<u8>> {
+    let addr = host
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "Master server not found"))?;
+    let mut stream = TcpStream::connect_timeout(&addr, REQUEST_TIMEOUT)?;
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+    stream.set_write_timeout(Some(REQUEST_TIMEOUT))?;
+
+    let body = body.unwrap_or(&[]);
+    let path = if path.is_empty() { "/" } else { path };
+    write!(
+        stream,
+        concat!(
+            "{} {} HTTP/1.0\r\n",
+            "Host: {}\r\n",
+            "Content-Type: application/json\r\n",
+            "Content-Length: {}\r\n\r\n",
+        ),
+        method,
+        path,
+        host,
+        body.len()
+    )?;
+    stream.write_all(body)?;
+
+    parse_response(&read_response(stream)?)
+}
+
+/// Reads a whole response, failing if it is longer than `MAX_RESPONSE_SIZE`.
+fn read_response(reader: impl Read) -> io::Result<Vec<u8>> {
+    let mut response = Vec::new();
+    reader
+        .take(MAX_RESPONSE_SIZE + 1)
+        .read_to_end(&mut response)?;
+    if response.len() as u64 > MAX_RESPONSE_SIZE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Master server response is too large",
+        ));
+    }
+    Ok(response)
+}
+
+/// Returns the body of a response with a 2xx status.
+fn parse_response(response: &[u8]) -> io::Result<Vec<u8>> {
+    let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+    let header_end = response
+        .windows(4)
+        .position(|window| window == b"\r\n\r\n")
+        .ok_or_else(|| invalid("Truncated HTTP response".into()))?;
+    let head = String::from_utf8_lossy(&response[..header_end]);
+    let status = head
+        .lines()
+        .next()
+        .and_then(|line| line.split_whitespace().nth(1))
+        .and_then(|status| status.parse::<u16>().ok())
+        .ok_or_else(|| invalid("Malformed HTTP status line".into()))?;
+    if !(200..300).contains(&status) {
+        return Err(invalid(format!("Master server responded with {}", status)));
+    }
+    Ok(response[header_end + 4..].to_vec())
+}
+
+/// Registers the server with the master server, and lists the servers fetched from it in the
+/// `ServerList`.
+#[derive(Debug)]
+pub struct MasterServerSystem;
+
+impl System for MasterServerSystem {
+    fn build(self) -> Box<dyn ParallelRunnable> {
+        Box::new(
+            SystemBuilder::new("MasterServerSystem")
+                .write_resource::<MasterServerClient>()
+                .write_resource::<ServerList>()
+                .build(move |_commands, _world, (client, servers), _| {
+                    #[cfg(feature = "profiler")]
+                    profile_scope!("master_server_system");
+
+                    client.update(Instant::now(), servers);
+                }),
+        )
+    }
+}
+
+/// Adds the `MasterServerClient` and `ServerList` resources and the `MasterServerSystem`.
+#[derive(Debug)]
+pub struct MasterServerBundle {
+    client: Option<MasterServerClient>,
+}
+
+impl MasterServerBundle {
+    /// Creates the bundle with the given client.
+    pub fn new(client: MasterServerClient) -> Self {
+        Self {
+            client: Some(client),
+        }
+    }
+}
+
+impl SystemBundle for MasterServerBundle {
+    fn load(
+        &mut self,
+        _world: &mut World,
+        resources: &mut Resources,
+        builder: &mut DispatcherBuilder,
+    ) -> Result<(), Error> {
+        resources.get_or_default::<ServerList>();
+        if let Some(client) = self.client.take() {
+            resources.insert(client);
+        }
+        builder.add_system(MasterServerSystem);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_urls_are_split_into_host_and_path() {
+        assert_eq!(
+            parse_url("http://example.com/api/"),
+            Some(("example.com:80".into(), "/api".into()))
+        );
+        assert_eq!(
+            parse_url("http://127.0.0.1:8080"),
+            Some(("127.0.0.1:8080".into(), "".into()))
+        );
+        assert_eq!(
+            parse_url("http://[::1]/api"),
+            Some(("[::1]:80".into(), "/api".into()))
+        );
+        assert_eq!(
+            parse_url("http://[::1]:8080"),
+            Some(("[::1]:8080".into(), "".into()))
+        );
+        assert_eq!(parse_url("https://example.com"), None);
+        assert_eq!(parse_url("http:///servers"), None);
+    }
+
+    #[test]
+    fn test_responses_are_parsed() {
+        let body = parse_response(b"HTTP/1.0 200 OK\r\nContent-Length: 2\r\n\r\n[]").unwrap();
+        let listed: Vec<ListedServer> = serde_json::from_slice(&body).unwrap();
+        assert!(listed.is_empty());
+
+        assert!(parse_response(b"HTTP/1.0 404 Not Found\r\n\r\n").is_err());
+        assert!(parse_response(b"HTTP/1.0 200 OK\r\n").is_err());
+    }
+
+    #[test]
+    fn test_responses_are_limited() {
+        let response = vec![0; MAX_RESPONSE_SIZE as usize];
+        assert_eq!(read_response(&response[..]).unwrap().len(), response.len());
+
+        let response = vec![0; MAX_RESPONSE_SIZE as usize + 1];
+        let error = read_response(&response[..]).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,226 @@
+//! Smooth movement of replicated entities, by showing them slightly in the past and
+//! interpolating between the states received from the server.
+
+use std::{collections::VecDeque, time::Duration};
+
+use amethyst_core::{ecs::*, timing::Time, transform::Transform};
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+
+use crate::simulation::{replication::ReplicationClient, timing::NetworkSimulationTime};
+
+/// Difference in seconds between the estimated and received server time at which the estimate
+/// jumps to the received time instead of slowly catching up.
+const RESYNC_THRESHOLD: f64 = 0.25;
+
+/// Fraction of the difference between the estimated and received server time corrected with
+/// every received update.
+const CLOCK_CORRECTION: f64 = 0.1;
+
+/// Maximum number of snapshots buffered per entity.
+const MAX_SNAPSHOTS: usize = 32;
+
+/// Resource configuring the interpolation of `TransformSnapshots`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SnapshotInterpolation {
+    /// How far in the past of the server entities are shown. This should cover the interval
+    /// between two updates plus some jitter, so there is usually a newer snapshot to
+    /// interpolate towards.
+    pub delay: Duration,
+    /// How far entities may move past their newest snapshot when no newer one arrived in time.
+    pub max_extrapolation: Duration,
+}
+
+impl Default for SnapshotInterpolation {
+    fn default() -> Self {
+        Self {
+            delay: Duration::from_millis(100),
+            max_extrapolation: Duration::from_millis(50),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Snapshot {
+    time: f64,
+    transform: Transform,
+}
+
+/// Transforms received for a replicated entity, its `Transform` is interpolated between them.
+///
+/// Add this component to the replicated entities which should move smoothly, but not to the
+/// entity predicted by the client.
+#[derive(Clone, Debug, Default)]
+pub struct TransformSnapshots {
+    snapshots: VecDeque<Snapshot>,
+}
+
+impl TransformSnapshots {
+    /// Creates an empty buffer of snapshots.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of buffered snapshots.
+    pub fn len(&self) -> usize {
+        self.snapshots.len()
+    }
+
+    /// Returns true if no snapshot was received yet.
+    pub fn is_empty(&self) -> bool {
+        self.snapshots.is_empty()
+    }
+
+    /// Adds the transform of the entity at the given server time in seconds. Snapshots older than
+    /// the newest one are ignored.
+    pub fn push(&mut self, time: f64, transform: Transform) {
+        if self
+            .snapshots
+            .back()
+            .map_or(false, |newest| newest.time >= time)
+        {
+            return;
+        }
+        self.snapshots.push_back(Snapshot { time, transform });
+        if self.snapshots.len() > MAX_SNAPSHOTS {
+            self.snapshots.pop_front();
+        }
+    }
+
+    /// Returns the transform of the entity at the given server time in seconds, moving it at
+    /// most `max_extrapolation` seconds past the newest snapshot. Snapshots which are no longer
+    /// needed to sample later times are dropped.
+    pub fn sample(&mut self, time: f64, max_extrapolation: f64) -> Option<Transform> {
+        while self.snapshots.len() > 2 && self.snapshots[1].time <= time {
+            self.snapshots.pop_front();
+        }
+        let first = self.snapshots.front()?;
+        if time <= first.time || self.snapshots.len() == 1 {
+            return Some(first.transform);
+        }
+        let (from, to) = (&self.snapshots[0], &self.snapshots[1]);
+        let span = to.time - from.time;
+        let time = time.min(to.time + max_extrapolation.max(0.0));
+        let factor = ((time - from.time) / span) as f32;
+        Some(blend(&from.transform, &to.transform, factor))
+    }
+}
+
+/// Blends two transforms, extrapolating the translation for factors above 1.
+fn blend(from: &Transform, to: &Transform, factor: f32) -> Transform {
+    let mut transform = *to;
+    transform.set_translation(from.translation().lerp(to.translation(), factor));
+    if factor < 1.0 {
+        let rotation = from
+            .rotation()
+            .try_slerp(to.rotation(), factor, 1.0e-6)
+            .unwrap_or_else(|| *to.rotation());
+        transform.set_rotation(rotation);
+        transform.set_scale(from.scale().lerp(to.scale(), factor));
+    }
+    transform
+}
+
+/// Moves replicated entities with `TransformSnapshots` along the transforms received from the
+/// server, delayed by `SnapshotInterpolation::delay`.
+#[derive(Debug)]
+pub struct SnapshotInterpolationSystem;
+
+impl System for SnapshotInterpolationSystem {
+    fn build(self) -> Box<dyn ParallelRunnable> {
+        // Estimated current time of the server, advanced every frame and corrected with every
+        // update received.
+        let mut server_time: Option<f64> = None;
+        let mut last_frame = None;
+        Box::new(
+            SystemBuilder::new("SnapshotInterpolationSystem")
+                .read_resource::<ReplicationClient>()
+                .read_resource::<NetworkSimulationTime>()
+                .read_resource::<SnapshotInterpolation>()
+                .read_resource::<Time>()
+                .with_query(<(Entity, Write<TransformSnapshots>, Write<Transform>)>::query())
+                .build(
+                    move |_commands, world, (client, sim_time, settings, time), query| {
+                        #[cfg(feature = "profiler")]
+                        profile_scope!("snapshot_interpolation_system");
+
+                        if let Some(clock) = server_time.as_mut() {
+                            *clock += time.delta_time().as_secs_f64();
+                        }
+                        let frame = match client.frame() {
+                            Some(frame) => frame,
+                            None => return,
+                        };
+                        let received =
+                            f64::from(frame) * sim_time.per_frame_duration().as_secs_f64();
+                        if last_frame != Some(frame) {
+                            last_frame = Some(frame);
+                            server_time = Some(match server_time {
+                                Some(clock) if (received - clock).abs() < RESYNC_THRESHOLD => {
+                                    clock + (received - clock) * CLOCK_CORRECTION
+                                }
+                                _ => received,
+                            });
+                        }
+                        let render_time =
+                            server_time.unwrap_or(received) - settings.delay.as_secs_f64();
+                        let max_extrapolation = settings.max_extrapolation.as_secs_f64();
+
+                        for (entity, snapshots, transform) in query.iter_mut(world) {
+                            if client.was_updated::<Transform>(*entity) {
+                                snapshots.push(received, *transform);
+                            }
+                            if let Some(sampled) = snapshots.sample(render_time, max_extrapolation)
+                            {
+                                *transform = sampled;
+                            }
+                        }
+                    },
+                ),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use amethyst_core::math::Vector3;
+
+    use super::*;
+
+    fn at(x: f32) -> Transform {
+        let mut transform = Transform::default();
+        transform.set_translation(Vector3::new(x, 0.0, 0.0));
+        transform
+    }
+
+    fn x_at(snapshots: &mut TransformSnapshots, time: f64) -> f32 {
+        snapshots.sample(time, 0.5).unwrap().translation().x
+    }
+
+    #[test]
+    fn test_interpolates_between_snapshots() {
+        let mut snapshots = TransformSnapshots::new();
+        assert!(snapshots.sample(0.0, 0.5).is_none());
+
+        snapshots.push(1.0, at(0.0));
+        snapshots.push(2.0, at(10.0));
+        snapshots.push(3.0, at(20.0));
+        // Out of order snapshots are ignored.
+        snapshots.push(2.5, at(100.0));
+
+        assert!((x_at(&mut snapshots, 0.5) - 0.0).abs() < 1e-5);
+        assert!((x_at(&mut snapshots, 1.5) - 5.0).abs() < 1e-5);
+        assert!((x_at(&mut snapshots, 2.25) - 12.5).abs() < 1e-5);
+        assert_eq!(snapshots.len(), 2);
+    }
+
+    #[test]
+    fn test_extrapolation_is_bounded() {
+        let mut snapshots = TransformSnapshots::new();
+        snapshots.push(1.0, at(0.0));
+        snapshots.push(2.0, at(10.0));
+
+        assert!((x_at(&mut snapshots, 2.2) - 12.0).abs() < 1e-4);
+        assert!((x_at(&mut snapshots, 10.0) - 15.0).abs() < 1e-4);
+    }
+}
@@ -0,0 +1,695 @@
+//! Deterministic lockstep simulation, for games such as RTSs whose state is too large to
+//! replicate.
+//!
+//! Instead of states, peers only exchange their inputs. The input of every frame is scheduled
+//! `input_delay` frames ahead and sent to all peers, and a frame is only simulated once the inputs
+//! of every player for it arrived, stalling the simulation otherwise. Since every peer runs the
+//! same frames with the same inputs, the simulation has to be deterministic. Peers periodically
+//! exchange checksums of their state to detect when it isn't.
+
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    hash::Hasher,
+    net::SocketAddr,
+    time::Duration,
+};
+
+use amethyst_core::{
+    ecs::*,
+    shrev::{EventChannel, ReaderId},
+};
+use amethyst_error::Error;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+
+use crate::simulation::{
+    events::NetworkSimulationEvent,
+    packet,
+    requirements::{DeliveryRequirement, UrgencyRequirement},
+    timing::NetworkSimulationTime,
+    transport::TransportResource,
+};
+
+/// Bytes in front of every lockstep packet.
+const PACKET_MAGIC: [u8; 4] = *b"ALCK";
+
+/// Maximum number of inputs sent in a single packet.
+const MAX_INPUTS_PER_PACKET: usize = 32;
+
+/// Maximum number of frames ahead of the simulation inputs are accepted for.
+const MAX_FRAMES_AHEAD: u32 = 256;
+
+/// Number of local checksums remembered, to compare them with those of peers arriving later.
+const MAX_CHECKSUMS: usize = 16;
+
+/// Number of the most recent checksums sent in every packet.
+const CHECKSUMS_PER_PACKET: usize = 4;
+
+/// Describes how the inputs of all players advance the simulation. Every peer has to use the
+/// same model.
+pub trait LockstepModel: Send + Sync + 'static {
+    /// Input of a player for a single frame. The default input is used for the first frames,
+    /// before any input was exchanged.
+    type Input: Clone + Default + Serialize + DeserializeOwned + Send + Sync + 'static;
+
+    /// Advances the world by one frame of the given duration, with the inputs of every player
+    /// ordered by player.
+    ///
+    /// This has to be deterministic: the same world and inputs must always give the same result,
+    /// on every peer.
+    fn step(&self, world: &mut World, inputs: &[(u32, Self::Input)], delta: Duration);
+
+    /// Returns a checksum of the simulated state, which is compared between peers to detect
+    /// desyncs. Use a `ChecksumHasher` to compute it.
+    fn checksum(&self, world: &World) -> u64;
+}
+
+/// FNV-1a hasher for computing `LockstepModel::checksum`s.
+///
+/// Unlike the `DefaultHasher`, it hashes the same values to the same checksum on every platform
+/// and build of the game. Floats don't implement `Hash`, hash their `to_bits` instead.
+#[derive(Clone, Copy, Debug)]
+pub struct ChecksumHasher(u64);
+
+impl Default for ChecksumHasher {
+    fn default() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Hasher for ChecksumHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 = (self.0 ^ u64::from(*byte)).wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+
+    // Integers are hashed in native byte order by default.
+    fn write_u16(&mut self, value: u16) {
+        self.write(&value.to_le_bytes());
+    }
+
+    fn write_u32(&mut self, value: u32) {
+        self.write(&value.to_le_bytes());
+    }
+
+    fn write_u64(&mut self, value: u64) {
+        self.write(&value.to_le_bytes());
+    }
+
+    fn write_usize(&mut self, value: usize) {
+        self.write_u64(value as u64);
+    }
+}
+
+/// Event of the lockstep simulation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LockstepEvent {
+    /// The simulation stopped at the given frame, waiting for the inputs of some peers.
+    Stalled {
+        /// The frame which can't be simulated yet.
+        frame: u32,
+        /// The peers whose inputs for the frame are missing.
+        waiting_for: Vec<SocketAddr>,
+    },
+    /// The simulation continued after being stalled.
+    Resumed {
+        /// The frame the simulation stalled at.
+        frame: u32,
+        /// Number of simulation frames the simulation was stalled for.
+        stalled_frames: u32,
+    },
+    /// The state of a peer differs from the local one. The simulation isn't deterministic.
+    Desync {
+        /// The frame after which the checksums were computed.
+        frame: u32,
+        /// The peer whose checksum differs.
+        peer: SocketAddr,
+        /// The local checksum.
+        local: u64,
+        /// The checksum of the peer.
+        remote: u64,
+    },
+}
+
+/// Inputs and checksums sent to a peer.
+#[derive(Serialize, Deserialize)]
+struct LockstepPacket<I> {
+    /// The first frame whose input from the receiver is still missing.
+    next: u32,
+    inputs: Vec<(u32, I)>,
+    checksums: Vec<(u32, u64)>,
+}
+
+#[derive(Debug)]
+struct Peer {
+    player: u32,
+    /// The first frame whose input from the peer is missing.
+    received: u32,
+    /// The first frame whose local input the peer is missing.
+    acknowledged: u32,
+    /// The last frame whose checksum was compared.
+    verified: Option<u32>,
+    /// Checksums of the peer for frames not simulated locally yet.
+    pending: BTreeMap<u32, u64>,
+}
+
+impl Peer {
+    fn verify(
+        &mut self,
+        addr: SocketAddr,
+        frame: u32,
+        remote: u64,
+        checksums: &VecDeque<(u32, u64)>,
+        events: &mut Vec<LockstepEvent>,
+    ) {
+        if self.verified.map_or(false, |verified| frame <= verified) {
+            return;
+        }
+        match checksums
+            .iter()
+            .find(|(local_frame, _)| *local_frame == frame)
+        {
+            Some((_, local)) => {
+                self.verified = Some(frame);
+                if *local != remote {
+                    events.push(LockstepEvent::Desync {
+                        frame,
+                        peer: addr,
+                        local: *local,
+                        remote,
+                    });
+                }
+            }
+            None if self.pending.len() < MAX_CHECKSUMS => {
+                self.pending.insert(frame, remote);
+            }
+            None => {}
+        }
+    }
+}
+
+/// Resource driving the lockstep simulation of a peer.
+///
+/// All peers have to be added before the simulation starts, and have to agree on the player of
+/// every peer, the input delay and the checksum interval.
+pub struct Lockstep<P: LockstepModel> {
+    local_player: u32,
+    peers: HashMap<SocketAddr, Peer>,
+    input: P::Input,
+    input_delay: u32,
+    checksum_interval: u32,
+    delivery: DeliveryRequirement,
+    frame: u32,
+    inputs: BTreeMap<u32, BTreeMap<u32, P::Input>>,
+    // Local inputs not all peers acknowledged yet
+    unacknowledged: VecDeque<(u32, P::Input)>,
+    checksums: VecDeque<(u32, u64)>,
+    stalled_frames: u32,
+}
+
+impl<P: LockstepModel> Lockstep<P> {
+    /// Creates the lockstep simulation of the given local player.
+    pub fn new(local_player: u32) -> Self {
+        Self {
+            local_player,
+            peers: HashMap::new(),
+            input: P::Input::default(),
+            input_delay: 3,
+            checksum_interval: 30,
+            delivery: DeliveryRequirement::Default,
+            frame: 0,
+            inputs: BTreeMap::new(),
+            unacknowledged: VecDeque::new(),
+            checksums: VecDeque::new(),
+            stalled_frames: 0,
+        }
+    }
+
+    /// Returns the player of this peer.
+    pub fn local_player(&self) -> u32 {
+        self.local_player
+    }
+
+    /// Adds a peer controlling the given player.
+    pub fn add_peer(&mut self, addr: SocketAddr, player: u32) {
+        assert_ne!(player, self.local_player, "Peer has the local player");
+        self.peers.insert(
+            addr,
+            Peer {
+                player,
+                received: self.input_delay,
+                acknowledged: self.input_delay,
+                verified: None,
+                pending: BTreeMap::new(),
+            },
+        );
+    }
+
+    /// Removes a peer, so the simulation no longer waits for its inputs. All other peers have to
+    /// remove it at the same frame to stay in sync.
+    pub fn remove_peer(&mut self, addr: SocketAddr) {
+        self.peers.remove(&addr);
+    }
+
+    /// Returns the peers and their players.
+    pub fn peers(&self) -> impl Iterator<Item = (&SocketAddr, u32)> {
+        self.peers.iter().map(|(addr, peer)| (addr, peer.player))
+    }
+
+    /// Sets the input of the local player, scheduled for every frame from now on until it is set
+    /// again.
+    pub fn set_input(&mut self, input: P::Input) {
+        self.input = input;
+    }
+
+    /// Returns the number of frames ahead inputs are scheduled.
+    pub fn input_delay(&self) -> u32 {
+        self.input_delay
+    }
+
+    /// Sets the number of frames ahead inputs are scheduled, 3 by default. It should cover the
+    /// round-trip time to the peers, or the simulation stalls waiting for their inputs.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the delay is zero, or the simulation already started.
+    pub fn set_input_delay(&mut self, frames: u32) {
+        assert!(frames > 0, "Lockstep input delay is zero");
+        assert_eq!(self.frame, 0, "Lockstep simulation already started");
+        self.input_delay = frames;
+        for peer in self.peers.values_mut() {
+            peer.received = frames;
+            peer.acknowledged = frames;
+        }
+    }
+
+    /// Sets the number of frames between checksums, 30 by default.
+    pub fn set_checksum_interval(&mut self, frames: u32) {
+        self.checksum_interval = frames.max(1);
+    }
+
+    /// Sets the delivery used for inputs, `DeliveryRequirement::Default` by default. Inputs which
+    /// weren't acknowledged are sent again with every packet, so they don't have to be reliable.
+    pub fn set_delivery(&mut self, delivery: DeliveryRequirement) {
+        self.delivery = delivery;
+    }
+
+    /// Returns the next frame to simulate.
+    pub fn frame(&self) -> u32 {
+        self.frame
+    }
+
+    /// Returns true if the simulation waits for the inputs of peers.
+    pub fn is_stalled(&self) -> bool {
+        self.stalled_frames > 0
+    }
+
+    fn receive(
+        &mut self,
+        addr: SocketAddr,
+        packet: LockstepPacket<P::Input>,
+        events: &mut Vec<LockstepEvent>,
+    ) {
+        let peer = match self.peers.get_mut(&addr) {
+            Some(peer) => peer,
+            None => return,
+        };
+        peer.acknowledged = peer.acknowledged.max(packet.next);
+        for (frame, input) in packet.inputs {
+            if frame >= peer.received && frame < self.frame + MAX_FRAMES_AHEAD {
+                self.inputs
+                    .entry(frame)
+                    .or_default()
+                    .insert(peer.player, input);
+            }
+        }
+        while self
+            .inputs
+            .get(&peer.received)
+            .map_or(false, |inputs| inputs.contains_key(&peer.player))
+        {
+            peer.received += 1;
+        }
+        for (frame, checksum) in packet.checksums {
+            peer.verify(addr, frame, checksum, &self.checksums, events);
+        }
+
+        let acknowledged = self.peers.values().map(|peer| peer.acknowledged).min();
+        while self.unacknowledged.front().map_or(false, |(frame, _)| {
+            acknowledged.map_or(true, |acknowledged| *frame < acknowledged)
+        }) {
+            self.unacknowledged.pop_front();
+        }
+    }
+
+    /// Simulates the next frame if the inputs of every player arrived, returning true if it did.
+    fn advance(
+        &mut self,
+        world: &mut World,
+        model: &P,
+        delta: Duration,
+        events: &mut Vec<LockstepEvent>,
+    ) -> bool {
+        let frame = self.frame;
+        let scheduled = self.inputs.remove(&frame).unwrap_or_default();
+        let waiting_for: Vec<_> = self
+            .peers
+            .iter()
+            .filter(|(_, peer)| frame >= self.input_delay && !scheduled.contains_key(&peer.player))
+            .map(|(addr, _)| *addr)
+            .collect();
+        if !waiting_for.is_empty() {
+            self.inputs.insert(frame, scheduled);
+            if self.stalled_frames == 0 {
+                events.push(LockstepEvent::Stalled { frame, waiting_for });
+            }
+            self.stalled_frames += 1;
+            return false;
+        }
+        if self.stalled_frames > 0 {
+            events.push(LockstepEvent::Resumed {
+                frame,
+                stalled_frames: self.stalled_frames,
+            });
+            self.stalled_frames = 0;
+        }
+
+        let mut players: Vec<_> = self.peers.values().map(|peer| peer.player).collect();
+        players.push(self.local_player);
+        players.sort_unstable();
+        let inputs: Vec<_> = players
+            .into_iter()
+            .map(|player| (player, scheduled.get(&player).cloned().unwrap_or_default()))
+            .collect();
+        model.step(world, &inputs, delta);
+
+        let input_frame = frame + self.input_delay;
+        self.inputs
+            .entry(input_frame)
+            .or_default()
+            .insert(self.local_player, self.input.clone());
+        if !self.peers.is_empty() {
+            self.unacknowledged
+                .push_back((input_frame, self.input.clone()));
+        }
+
+        if frame % self.checksum_interval == 0 {
+            let checksum = model.checksum(world);
+            self.checksums.push_back((frame, checksum));
+            if self.checksums.len() > MAX_CHECKSUMS {
+                self.checksums.pop_front();
+            }
+            for (addr, peer) in self.peers.iter_mut() {
+                if let Some(remote) = peer.pending.remove(&frame) {
+                    peer.verify(*addr, frame, remote, &self.checksums, events);
+                }
+                peer.pending.retain(|pending, _| *pending > frame);
+            }
+        }
+        self.frame += 1;
+        true
+    }
+
+    fn packets(&self) -> Vec<(SocketAddr, LockstepPacket<P::Input>)> {
+        let skip = self.checksums.len().saturating_sub(CHECKSUMS_PER_PACKET);
+        let checksums: Vec<_> = self.checksums.iter().skip(skip).copied().collect();
+        self.peers
+            .iter()
+            .map(|(addr, peer)| {
+                let inputs = self
+                    .unacknowledged
+                    .iter()
+                    .filter(|(frame, _)| *frame >= peer.acknowledged)
+                    .take(MAX_INPUTS_PER_PACKET)
+                    .cloned()
+                    .collect();
+                let packet = LockstepPacket {
+                    next: peer.received,
+                    inputs,
+                    checksums: checksums.clone(),
+                };
+                (*addr, packet)
+            })
+            .collect()
+    }
+}
+
+/// Adds the lockstep simulation, writing `LockstepEvent`s.
+///
+/// Every simulation frame of the `NetworkSimulationTime` advances the lockstep simulation by a
+/// frame, once the inputs of all players arrived. Inputs are sent through the
+/// `TransportResource`. Set the local input with `Lockstep::set_input` from a system running
+/// before this bundle.
+pub struct LockstepBundle<P: LockstepModel> {
+    model: Option<P>,
+    lockstep: Option<Lockstep<P>>,
+}
+
+impl<P: LockstepModel> LockstepBundle<P> {
+    /// Creates the bundle with the given model and lockstep resource.
+    pub fn new(model: P, lockstep: Lockstep<P>) -> Self {
+        Self {
+            model: Some(model),
+            lockstep: Some(lockstep),
+        }
+    }
+}
+
+impl<P: LockstepModel> SystemBundle for LockstepBundle<P> {
+    fn load(
+        &mut self,
+        _world: &mut World,
+        resources: &mut Resources,
+        builder: &mut DispatcherBuilder,
+    ) -> Result<(), Error> {
+        let model = self
+            .model
+            .take()
+            .expect("LockstepBundle was already loaded");
+        let mut reader = resources
+            .get_mut_or_default::<EventChannel<NetworkSimulationEvent>>()
+            .register_reader();
+        resources.get_or_default::<TransportResource>();
+        resources.get_or_default::<NetworkSimulationTime>();
+        resources.get_or_default::<EventChannel<LockstepEvent>>();
+        if let Some(lockstep) = self.lockstep.take() {
+            resources.insert(lockstep);
+        }
+
+        builder.add_thread_local_fn(move |world, resources| {
+            run_lockstep(world, resources, &model, &mut reader);
+        });
+        Ok(())
+    }
+}
+
+fn run_lockstep<P: LockstepModel>(
+    world: &mut World,
+    resources: &mut Resources,
+    model: &P,
+    reader: &mut ReaderId<NetworkSimulationEvent>,
+) {
+    #[cfg(feature = "profiler")]
+    profile_scope!("run_lockstep");
+
+    let mut lockstep = resources
+        .get_mut::<Lockstep<P>>()
+        .expect("Lockstep resource is missing");
+    let mut events = Vec::new();
+    let channel = resources
+        .get::<EventChannel<NetworkSimulationEvent>>()
+        .expect("NetworkSimulationEvent channel is missing");
+    for event in channel.read(reader) {
+        if let NetworkSimulationEvent::Message(addr, payload) = event {
+            if let Some(packet) = packet::decode(&PACKET_MAGIC, payload) {
+                lockstep.receive(*addr, packet, &mut events);
+            }
+        }
+    }
+
+    let sim_time = *resources
+        .get::<NetworkSimulationTime>()
+        .expect("NetworkSimulationTime resource is missing");
+    for _ in sim_time.sim_frames_to_run() {
+        lockstep.advance(world, model, sim_time.per_frame_duration(), &mut events);
+    }
+
+    if sim_time.should_send_message_now() {
+        let mut transport = resources
+            .get_mut::<TransportResource>()
+            .expect("TransportResource is missing");
+        for (addr, packet) in lockstep.packets() {
+            transport.send_with_requirements(
+                addr,
+                &packet::encode(&PACKET_MAGIC, &packet),
+                lockstep.delivery,
+                UrgencyRequirement::OnTick,
+            );
+        }
+    }
+
+    resources
+        .get_mut::<EventChannel<LockstepEvent>>()
+        .expect("LockstepEvent channel is missing")
+        .iter_write(events);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::hash::Hash;
+
+    use super::*;
+
+    struct Counter(i64);
+
+    /// Adds the inputs of all players to a counter, the first player's twice if `skew` is set.
+    struct Sum {
+        skew: bool,
+    }
+
+    impl LockstepModel for Sum {
+        type Input = i64;
+
+        fn step(&self, world: &mut World, inputs: &[(u32, i64)], _: Duration) {
+            let mut sum: i64 = inputs.iter().map(|(_, input)| input).sum();
+            if self.skew {
+                sum += inputs[0].1;
+            }
+            <&mut Counter>::query().for_each_mut(world, |counter| counter.0 += sum);
+        }
+
+        fn checksum(&self, world: &World) -> u64 {
+            let mut hasher = ChecksumHasher::default();
+            for counter in <&Counter>::query().iter(world) {
+                counter.0.hash(&mut hasher);
+            }
+            hasher.finish()
+        }
+    }
+
+    fn counter(world: &World) -> i64 {
+        <&Counter>::query().iter(world).next().unwrap().0
+    }
+
+    struct Node {
+        addr: SocketAddr,
+        world: World,
+        lockstep: Lockstep<Sum>,
+        model: Sum,
+        events: Vec<LockstepEvent>,
+    }
+
+    fn pair(skew: bool) -> (Node, Node) {
+        let node = |player: u32, skew: bool| {
+            let mut world = World::default();
+            world.push((Counter(0),));
+            let mut lockstep = Lockstep::new(player);
+            lockstep.set_input_delay(2);
+            lockstep.set_checksum_interval(2);
+            Node {
+                addr: format!("127.0.0.1:{}", 3000 + player).parse().unwrap(),
+                world,
+                lockstep,
+                model: Sum { skew },
+                events: Vec::new(),
+            }
+        };
+        let (mut a, mut b) = (node(0, false), node(1, skew));
+        a.lockstep.add_peer(b.addr, 1);
+        b.lockstep.add_peer(a.addr, 0);
+        (a, b)
+    }
+
+    fn advance(node: &mut Node) -> bool {
+        node.lockstep.advance(
+            &mut node.world,
+            &node.model,
+            Duration::from_millis(16),
+            &mut node.events,
+        )
+    }
+
+    fn exchange(from: &Node, to: &mut Node) {
+        for (_, packet) in from.lockstep.packets() {
+            let bytes = packet::encode(&PACKET_MAGIC, &packet);
+            let packet = packet::decode(&PACKET_MAGIC, &bytes).unwrap();
+            to.lockstep.receive(from.addr, packet, &mut to.events);
+        }
+    }
+
+    #[test]
+    fn test_peers_simulate_the_same_inputs() {
+        let (mut a, mut b) = pair(false);
+        a.lockstep.set_input(1);
+        b.lockstep.set_input(10);
+        for _ in 0..20 {
+            advance(&mut a);
+            advance(&mut b);
+            exchange(&a, &mut b);
+            exchange(&b, &mut a);
+        }
+        // The first two frames are simulated with the default inputs.
+        assert_eq!(a.lockstep.frame(), 20);
+        assert_eq!(counter(&a.world), 18 * 11);
+        assert_eq!(counter(&b.world), counter(&a.world));
+        assert!(a.events.is_empty() && b.events.is_empty());
+        assert!(a.lockstep.unacknowledged.len() <= 2);
+    }
+
+    #[test]
+    fn test_simulation_stalls_without_inputs() {
+        let (mut a, mut b) = pair(false);
+        assert!(advance(&mut a));
+        assert!(advance(&mut a));
+        assert!(!advance(&mut a));
+        assert!(!advance(&mut a));
+        assert!(a.lockstep.is_stalled());
+        assert_eq!(
+            a.events,
+            vec![LockstepEvent::Stalled {
+                frame: 2,
+                waiting_for: vec![b.addr],
+            }]
+        );
+
+        advance(&mut b);
+        exchange(&b, &mut a);
+        assert!(advance(&mut a));
+        assert_eq!(
+            a.events[1],
+            LockstepEvent::Resumed {
+                frame: 2,
+                stalled_frames: 2,
+            }
+        );
+    }
+
+    #[test]
+    fn test_diverging_states_are_reported() {
+        let (mut a, mut b) = pair(true);
+        a.lockstep.set_input(1);
+        b.lockstep.set_input(1);
+        for _ in 0..6 {
+            advance(&mut a);
+            advance(&mut b);
+            exchange(&a, &mut b);
+            exchange(&b, &mut a);
+        }
+        assert!(a.events.iter().any(|event| {
+            matches!(
+                event,
+                LockstepEvent::Desync { frame: 2, peer, .. } if *peer == b.addr
+            )
+        }));
+        assert!(b
+            .events
+            .iter()
+            .any(|event| matches!(event, LockstepEvent::Desync { frame: 2, .. })));
+    }
+}
@@ -0,0 +1,320 @@
+//! Statistics of the connection to every peer, for network debug overlays and adapting send
+//! rates.
+//!
+//! Bytes and messages are counted for every transport. Round-trip times and resends are only
+//! known to transports with acknowledgements, currently UDP with `DeliveryChannels`.
+
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+
+use amethyst_core::{
+    ecs::*,
+    shrev::{EventChannel, ReaderId},
+};
+use amethyst_error::Error;
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+
+use crate::simulation::{
+    events::NetworkSimulationEvent, requirements::UrgencyRequirement,
+    timing::NetworkSimulationTime, transport::TransportResource,
+};
+
+/// Weight of a new round-trip time sample in the smoothed round-trip time.
+const RTT_SMOOTHING: f64 = 0.125;
+
+/// Statistics of the connection to a peer over the last report interval.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PeerMetrics {
+    /// Smoothed round-trip time, if the transport measures it.
+    pub rtt: Option<Duration>,
+    /// Estimated fraction of lost messages in 0.0-1.0, from the messages which were resent.
+    pub packet_loss: f32,
+    /// Bytes of the messages sent per second.
+    pub bytes_sent_per_sec: f32,
+    /// Bytes of the messages received per second.
+    pub bytes_received_per_sec: f32,
+    /// Number of messages sent.
+    pub messages_sent: u32,
+    /// Number of messages received.
+    pub messages_received: u32,
+    /// Number of messages resent because they weren't acknowledged in time.
+    pub resends: u32,
+}
+
+/// Periodic report of the metrics of a peer.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NetworkMetricsEvent {
+    /// The peer the metrics are about.
+    pub peer: SocketAddr,
+    /// Metrics of the last report interval.
+    pub metrics: PeerMetrics,
+}
+
+/// What was counted for a peer since the last report.
+#[derive(Debug, Default)]
+struct Counters {
+    bytes_sent: u64,
+    bytes_received: u64,
+    messages_sent: u32,
+    messages_received: u32,
+    resends: u32,
+}
+
+#[derive(Debug, Default)]
+struct Peer {
+    metrics: PeerMetrics,
+    counters: Counters,
+}
+
+/// Resource holding the `PeerMetrics` of every peer, updated every report interval.
+#[derive(Debug)]
+pub struct NetworkMetrics {
+    report_interval: Duration,
+    last_report: Option<Instant>,
+    peers: HashMap<SocketAddr, Peer>,
+}
+
+impl Default for NetworkMetrics {
+    fn default() -> Self {
+        Self::new(Duration::from_secs(1))
+    }
+}
+
+impl NetworkMetrics {
+    /// Creates metrics reported at the given interval.
+    pub fn new(report_interval: Duration) -> Self {
+        Self {
+            report_interval,
+            last_report: None,
+            peers: HashMap::new(),
+        }
+    }
+
+    /// Returns the interval at which metrics are updated and reported.
+    pub fn report_interval(&self) -> Duration {
+        self.report_interval
+    }
+
+    /// Sets the interval at which metrics are updated and reported.
+    pub fn set_report_interval(&mut self, interval: Duration) {
+        self.report_interval = interval;
+    }
+
+    /// Returns the metrics of the given peer as of the last report.
+    pub fn peer(&self, addr: SocketAddr) -> Option<&PeerMetrics> {
+        self.peers.get(&addr).map(|peer| &peer.metrics)
+    }
+
+    /// Returns the metrics of all peers as of the last report.
+    pub fn peers(&self) -> impl Iterator<Item = (&SocketAddr, &PeerMetrics)> {
+        self.peers.iter().map(|(addr, peer)| (addr, &peer.metrics))
+    }
+
+    /// Forgets the metrics of a peer, e.g. after it disconnected.
+    pub fn remove_peer(&mut self, addr: SocketAddr) {
+        self.peers.remove(&addr);
+    }
+
+    /// Counts a message sent to a peer.
+    pub fn record_sent(&mut self, addr: SocketAddr, bytes: usize) {
+        let counters = &mut self.peers.entry(addr).or_default().counters;
+        counters.bytes_sent += bytes as u64;
+        counters.messages_sent += 1;
+    }
+
+    /// Counts a message received from a peer.
+    pub fn record_received(&mut self, addr: SocketAddr, bytes: usize) {
+        let counters = &mut self.peers.entry(addr).or_default().counters;
+        counters.bytes_received += bytes as u64;
+        counters.messages_received += 1;
+    }
+
+    /// Counts messages resent to a peer. This should be called by a transport implementation.
+    pub fn record_resends(&mut self, addr: SocketAddr, count: u32) {
+        self.peers.entry(addr).or_default().counters.resends += count;
+    }
+
+    /// Adds a round-trip time sample of a peer. This should be called by a transport
+    /// implementation.
+    pub fn record_rtt(&mut self, addr: SocketAddr, sample: Duration) {
+        let metrics = &mut self.peers.entry(addr).or_default().metrics;
+        metrics.rtt = Some(match metrics.rtt {
+            Some(rtt) => rtt.mul_f64(1.0 - RTT_SMOOTHING) + sample.mul_f64(RTT_SMOOTHING),
+            None => sample,
+        });
+    }
+
+    /// Updates the metrics of all peers from what was counted since the last report, if the
+    /// report interval elapsed.
+    fn report(&mut self, now: Instant) -> Vec<NetworkMetricsEvent> {
+        let last_report = *self.last_report.get_or_insert(now);
+        let elapsed = now.duration_since(last_report);
+        if elapsed < self.report_interval || elapsed == Duration::from_secs(0) {
+            return Vec::new();
+        }
+        self.last_report = Some(now);
+
+        let secs = elapsed.as_secs_f32();
+        let mut events = Vec::with_capacity(self.peers.len());
+        for (addr, peer) in self.peers.iter_mut() {
+            let counters = std::mem::take(&mut peer.counters);
+            let metrics = &mut peer.metrics;
+            let transmissions = counters.messages_sent + counters.resends;
+            metrics.packet_loss = if transmissions > 0 {
+                counters.resends as f32 / transmissions as f32
+            } else {
+                0.0
+            };
+            metrics.bytes_sent_per_sec = counters.bytes_sent as f32 / secs;
+            metrics.bytes_received_per_sec = counters.bytes_received as f32 / secs;
+            metrics.messages_sent = counters.messages_sent;
+            metrics.messages_received = counters.messages_received;
+            metrics.resends = counters.resends;
+            events.push(NetworkMetricsEvent {
+                peer: *addr,
+                metrics: metrics.clone(),
+            });
+        }
+        events
+    }
+}
+
+/// Counts the messages sent and received, and writes a `NetworkMetricsEvent` for every peer
+/// each report interval.
+#[derive(Debug)]
+pub struct NetworkMetricsSystem {
+    reader: ReaderId<NetworkSimulationEvent>,
+}
+
+impl NetworkMetricsSystem {
+    /// Creates the system reading the given network events.
+    pub fn new(reader: ReaderId<NetworkSimulationEvent>) -> Self {
+        Self { reader }
+    }
+}
+
+impl System for NetworkMetricsSystem {
+    fn build(mut self) -> Box<dyn ParallelRunnable> {
+        Box::new(
+            SystemBuilder::new("NetworkMetricsSystem")
+                .read_resource::<TransportResource>()
+                .read_resource::<NetworkSimulationTime>()
+                .read_resource::<EventChannel<NetworkSimulationEvent>>()
+                .write_resource::<NetworkMetrics>()
+                .write_resource::<EventChannel<NetworkMetricsEvent>>()
+                .build(
+                    move |_commands,
+                          _world,
+                          (transport, sim_time, network_events, metrics, events),
+                          _| {
+                        #[cfg(feature = "profiler")]
+                        profile_scope!("network_metrics_system");
+
+                        // These are the messages the transport sends later in this frame.
+                        let send_now = sim_time.should_send_message_now();
+                        for message in transport.get_messages() {
+                            if send_now || message.urgency == UrgencyRequirement::Immediate {
+                                metrics.record_sent(message.destination, message.payload.len());
+                            }
+                        }
+                        for event in network_events.read(&mut self.reader) {
+                            match event {
+                                NetworkSimulationEvent::Message(addr, payload) => {
+                                    metrics.record_received(*addr, payload.len());
+                                }
+                                NetworkSimulationEvent::Disconnect(addr) => {
+                                    metrics.remove_peer(*addr);
+                                }
+                                _ => {}
+                            }
+                        }
+                        events.iter_write(metrics.report(Instant::now()));
+                    },
+                ),
+        )
+    }
+}
+
+/// Adds the `NetworkMetrics` resource and the `NetworkMetricsSystem`.
+///
+/// Add this bundle before the network bundle of the transport, so sent messages are counted
+/// before the transport sends them.
+#[derive(Debug)]
+pub struct NetworkMetricsBundle {
+    report_interval: Duration,
+}
+
+impl NetworkMetricsBundle {
+    /// Creates the bundle reporting metrics at the given interval.
+    pub fn new(report_interval: Duration) -> Self {
+        Self { report_interval }
+    }
+}
+
+impl Default for NetworkMetricsBundle {
+    fn default() -> Self {
+        Self::new(Duration::from_secs(1))
+    }
+}
+
+impl SystemBundle for NetworkMetricsBundle {
+    fn load(
+        &mut self,
+        _world: &mut World,
+        resources: &mut Resources,
+        builder: &mut DispatcherBuilder,
+    ) -> Result<(), Error> {
+        let reader = resources
+            .get_mut_or_default::<EventChannel<NetworkSimulationEvent>>()
+            .register_reader();
+        resources.get_or_default::<TransportResource>();
+        resources.get_or_default::<NetworkSimulationTime>();
+        resources.get_or_default::<EventChannel<NetworkMetricsEvent>>();
+        resources
+            .get_mut_or_default::<NetworkMetrics>()
+            .set_report_interval(self.report_interval);
+
+        builder.add_system(NetworkMetricsSystem::new(reader));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report_computes_rates_per_interval() {
+        let peer = "127.0.0.1:3000".parse().unwrap();
+        let now = Instant::now();
+        let mut metrics = NetworkMetrics::new(Duration::from_secs(2));
+        assert!(metrics.report(now).is_empty());
+
+        for _ in 0..3 {
+            metrics.record_sent(peer, 100);
+        }
+        metrics.record_resends(peer, 1);
+        metrics.record_received(peer, 50);
+        metrics.record_rtt(peer, Duration::from_millis(80));
+        metrics.record_rtt(peer, Duration::from_millis(160));
+        assert!(metrics.report(now + Duration::from_secs(1)).is_empty());
+
+        let events = metrics.report(now + Duration::from_secs(2));
+        assert_eq!(events.len(), 1);
+        let reported = &events[0].metrics;
+        assert!((reported.bytes_sent_per_sec - 150.0).abs() < 1e-3);
+        assert!((reported.bytes_received_per_sec - 25.0).abs() < 1e-3);
+        assert!((reported.packet_loss - 0.25).abs() < 1e-5);
+        let rtt = reported.rtt.unwrap().as_secs_f64();
+        assert!((rtt - 0.09).abs() < 1e-6);
+        assert_eq!(metrics.peer(peer), Some(reported));
+
+        // Counters start over with every interval.
+        let events = metrics.report(now + Duration::from_secs(4));
+        assert_eq!(events[0].metrics.messages_sent, 0);
+    }
+}
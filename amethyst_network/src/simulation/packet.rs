@@ -0,0 +1,52 @@
+//! Framing of the messages sent by the higher level utilities of the simulation, so they
+//! can share a transport with the messages of the game.
+
+use log::warn;
+use serde::{de::DeserializeOwned, Serialize};
+
+/// Serializes a value behind the given magic bytes.
+pub(crate) fn encode<T: Serialize>(magic: &[u8; 4], value: &T) -> Vec<u8> {
+    let mut bytes = magic.to_vec();
+    bincode::serialize_into(&mut bytes, value).expect("Serializing network packet");
+    bytes
+}
+
+/// Deserializes a value encoded with `encode`, returning `None` if the bytes don't start with
+/// the given magic bytes or are malformed.
+pub(crate) fn decode<T: DeserializeOwned>(magic: &[u8; 4], bytes: &[u8]) -> Option<T> {
+    if !bytes.starts_with(magic) {
+        return None;
+    }
+    deserialize(&bytes[magic.len()..])
+}
+
+/// Serializes a value behind the given magic bytes and a tag telling its type apart from the
+/// other types sent with the same magic bytes.
+pub(crate) fn encode_tagged<T: Serialize>(magic: &[u8; 4], tag: u32, value: &T) -> Vec<u8> {
+    let mut bytes = magic.to_vec();
+    bytes.extend_from_slice(&tag.to_le_bytes());
+    bincode::serialize_into(&mut bytes, value).expect("Serializing network packet");
+    bytes
+}
+
+/// Returns the tag and the serialized value of bytes encoded with `encode_tagged`, or `None` if
+/// they don't start with the given magic bytes.
+pub(crate) fn split_tagged<'a>(magic: &[u8; 4], bytes: &'a [u8]) -> Option<(u32, &'a [u8])> {
+    if !bytes.starts_with(magic) || bytes.len() < magic.len() + 4 {
+        return None;
+    }
+    let mut tag = [0; 4];
+    tag.copy_from_slice(&bytes[magic.len()..magic.len() + 4]);
+    Some((u32::from_le_bytes(tag), &bytes[magic.len() + 4..]))
+}
+
+/// Deserializes a value, returning `None` if the bytes are malformed.
+pub(crate) fn deserialize<T: DeserializeOwned>(bytes: &[u8]) -> Option<T> {
+    match bincode::deserialize(bytes) {
+        Ok(value) => Some(value),
+        Err(e) => {
+            warn!("Received a malformed network packet: {}", e);
+            None
+        }
+    }
+}
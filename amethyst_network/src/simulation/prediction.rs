@@ -0,0 +1,715 @@
+//! Client-side prediction of locally controlled entities, reconciled with the authoritative
+//! simulation of the server.
+//!
+//! The client applies its inputs to the entity it controls right away, keeps them in a buffer
+//! and sends every input the server didn't process yet. The server applies the inputs of each
+//! client to the entity the client controls and answers with the resulting state, along with
+//! the last input it processed. When that state doesn't match what the client predicted for
+//! the same input, the client rewinds to the state of the server and replays the inputs which
+//! were sent since.
+
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    marker::PhantomData,
+    net::SocketAddr,
+    time::Duration,
+};
+
+use amethyst_core::{
+    ecs::*,
+    shrev::{EventChannel, ReaderId},
+    transform::Transform,
+};
+use amethyst_error::Error;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+
+use crate::simulation::{
+    events::NetworkSimulationEvent,
+    packet,
+    requirements::{DeliveryRequirement, UrgencyRequirement},
+    timing::NetworkSimulationTime,
+    transport::TransportResource,
+};
+
+/// Bytes in front of every packet of inputs.
+const INPUT_MAGIC: [u8; 4] = *b"AINP";
+
+/// Bytes in front of every packet of authoritative state.
+const STATE_MAGIC: [u8; 4] = *b"ASTA";
+
+/// Maximum number of predicted frames remembered by the client.
+const MAX_HISTORY: usize = 256;
+
+/// Maximum number of inputs sent in a single packet.
+const MAX_INPUTS_PER_PACKET: usize = 32;
+
+/// Maximum number of inputs the server keeps waiting for a client.
+const MAX_QUEUED_INPUTS: usize = 64;
+
+/// Describes how inputs change the predicted state of an entity. The same model has to be used
+/// by the client and the server, so that predictions match the authoritative simulation.
+pub trait PredictionModel: Send + Sync + 'static {
+    /// Input command of a single simulation frame.
+    type Input: Clone + Serialize + DeserializeOwned + Send + Sync + 'static;
+    /// Part of the state of an entity which is predicted.
+    type State: Clone + Serialize + DeserializeOwned + Send + Sync + 'static;
+
+    /// Reads the state of an entity.
+    fn capture(&self, world: &World, entity: Entity) -> Option<Self::State>;
+
+    /// Writes the state of an entity.
+    fn restore(&self, world: &mut World, entity: Entity, state: &Self::State);
+
+    /// Advances the state by one simulation frame of the given duration.
+    fn step(&self, state: &mut Self::State, input: &Self::Input, delta: Duration);
+
+    /// Returns true if a predicted state is close enough to the state of the server that no
+    /// correction is needed.
+    fn matches(&self, predicted: &Self::State, authoritative: &Self::State) -> bool;
+}
+
+/// Prediction of the `Transform` of an entity, moved by a function of the input.
+pub struct TransformPrediction<I, F> {
+    step: F,
+    tolerance: f32,
+    marker: PhantomData<fn(I)>,
+}
+
+impl<I, F> TransformPrediction<I, F>
+where
+    F: Fn(&mut Transform, &I, Duration),
+{
+    /// Creates a prediction moving transforms with the given function.
+    pub fn new(step: F) -> Self {
+        Self {
+            step,
+            tolerance: 0.01,
+            marker: PhantomData,
+        }
+    }
+
+    /// Sets the distance and angle in radians predictions may be off by without being corrected.
+    pub fn with_tolerance(mut self, tolerance: f32) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+}
+
+impl<I, F> PredictionModel for TransformPrediction<I, F>
+where
+    I: Clone + Serialize + DeserializeOwned + Send + Sync + 'static,
+    F: Fn(&mut Transform, &I, Duration) + Send + Sync + 'static,
+{
+    type Input = I;
+    type State = Transform;
+
+    fn capture(&self, world: &World, entity: Entity) -> Option<Transform> {
+        world
+            .entry_ref(entity)
+            .ok()?
+            .get_component::<Transform>()
+            .ok()
+            .copied()
+    }
+
+    fn restore(&self, world: &mut World, entity: Entity, state: &Transform) {
+        if let Some(mut entry) = world.entry(entity) {
+            if let Ok(transform) = entry.get_component_mut::<Transform>() {
+                *transform = *state;
+            }
+        }
+    }
+
+    fn step(&self, state: &mut Transform, input: &I, delta: Duration) {
+        (self.step)(state, input, delta);
+    }
+
+    fn matches(&self, predicted: &Transform, authoritative: &Transform) -> bool {
+        (predicted.translation() - authoritative.translation()).norm() <= self.tolerance
+            && predicted.rotation().angle_to(authoritative.rotation()) <= self.tolerance
+    }
+}
+
+/// Inputs sent by a client, the oldest first.
+#[derive(Serialize, Deserialize)]
+struct InputPacket<I> {
+    inputs: Vec<(u32, I)>,
+}
+
+/// State of the controlled entity of a client after the server processed the input of `frame`.
+#[derive(Serialize, Deserialize)]
+struct StatePacket<S> {
+    frame: u32,
+    state: S,
+}
+
+/// A simulation frame predicted by the client.
+struct PredictedFrame<I, S> {
+    frame: u32,
+    input: I,
+    state: S,
+}
+
+/// Resource of the client predicting the entity it controls.
+pub struct PredictionClient<P: PredictionModel> {
+    server: Option<SocketAddr>,
+    entity: Option<Entity>,
+    input: Option<P::Input>,
+    delivery: DeliveryRequirement,
+    history: VecDeque<PredictedFrame<P::Input, P::State>>,
+    acknowledged: Option<u32>,
+    corrections: u64,
+}
+
+impl<P: PredictionModel> Default for PredictionClient<P> {
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
+
+impl<P: PredictionModel> PredictionClient<P> {
+    /// Creates a client sending its inputs to the given server.
+    pub fn new(server: Option<SocketAddr>) -> Self {
+        Self {
+            server,
+            entity: None,
+            input: None,
+            delivery: DeliveryRequirement::Default,
+            history: VecDeque::new(),
+            acknowledged: None,
+            corrections: 0,
+        }
+    }
+
+    /// Sets the server inputs are sent to and states are accepted from.
+    pub fn set_server(&mut self, server: Option<SocketAddr>) {
+        self.server = server;
+    }
+
+    /// Returns the entity controlled by this client.
+    pub fn entity(&self) -> Option<Entity> {
+        self.entity
+    }
+
+    /// Sets the entity controlled by this client, forgetting previous predictions.
+    pub fn set_entity(&mut self, entity: Option<Entity>) {
+        self.entity = entity;
+        self.history.clear();
+    }
+
+    /// Sets the input applied in the coming simulation frames, until it is set again.
+    pub fn set_input(&mut self, input: P::Input) {
+        self.input = Some(input);
+    }
+
+    /// Sets the delivery used for inputs, `DeliveryRequirement::Default` by default. Inputs which
+    /// weren't acknowledged are sent again with every packet, so they don't have to be reliable.
+    pub fn set_delivery(&mut self, delivery: DeliveryRequirement) {
+        self.delivery = delivery;
+    }
+
+    /// Returns the number of inputs the server didn't acknowledge yet.
+    pub fn pending_inputs(&self) -> usize {
+        self.history.len()
+    }
+
+    /// Returns the last input frame processed by the server.
+    pub fn acknowledged(&self) -> Option<u32> {
+        self.acknowledged
+    }
+
+    /// Returns how often the prediction had to be corrected.
+    pub fn corrections(&self) -> u64 {
+        self.corrections
+    }
+
+    /// Applies the current input to the controlled entity for the given frame.
+    fn predict(&mut self, world: &mut World, model: &P, frame: u32, delta: Duration) {
+        let (entity, input) = match (self.entity, self.input.clone()) {
+            (Some(entity), Some(input)) => (entity, input),
+            _ => return,
+        };
+        let mut state = match model.capture(world, entity) {
+            Some(state) => state,
+            None => return,
+        };
+        model.step(&mut state, &input, delta);
+        model.restore(world, entity, &state);
+        self.history.push_back(PredictedFrame {
+            frame,
+            input,
+            state,
+        });
+        if self.history.len() > MAX_HISTORY {
+            self.history.pop_front();
+        }
+    }
+
+    /// Compares the state of the server with the prediction for the same frame, replaying the
+    /// inputs since if they differ.
+    fn reconcile(
+        &mut self,
+        world: &mut World,
+        model: &P,
+        packet: StatePacket<P::State>,
+        delta: Duration,
+    ) {
+        if self
+            .acknowledged
+            .map_or(false, |acknowledged| !is_after(packet.frame, acknowledged))
+        {
+            return;
+        }
+        self.acknowledged = Some(packet.frame);
+        while self
+            .history
+            .front()
+            .map_or(false, |predicted| is_after(packet.frame, predicted.frame))
+        {
+            self.history.pop_front();
+        }
+        let matches = match self.history.front() {
+            Some(predicted) if predicted.frame == packet.frame => {
+                model.matches(&predicted.state, &packet.state)
+            }
+            _ => true,
+        };
+        if self
+            .history
+            .front()
+            .map_or(false, |predicted| predicted.frame == packet.frame)
+        {
+            self.history.pop_front();
+        }
+        if matches {
+            return;
+        }
+
+        self.corrections += 1;
+        let mut state = packet.state;
+        for predicted in self.history.iter_mut() {
+            model.step(&mut state, &predicted.input, delta);
+            predicted.state = state.clone();
+        }
+        if let Some(entity) = self.entity {
+            model.restore(world, entity, &state);
+        }
+    }
+
+    fn input_packet(&self) -> InputPacket<P::Input> {
+        let skip = self.history.len().saturating_sub(MAX_INPUTS_PER_PACKET);
+        InputPacket {
+            inputs: self
+                .history
+                .iter()
+                .skip(skip)
+                .map(|predicted| (predicted.frame, predicted.input.clone()))
+                .collect(),
+        }
+    }
+}
+
+/// Entity of a client simulated by the server.
+struct ControlledEntity<I> {
+    entity: Entity,
+    inputs: BTreeMap<u32, I>,
+    processed: Option<u32>,
+    unsent: bool,
+}
+
+/// Resource of the server applying the inputs of its clients.
+pub struct PredictionServer<P: PredictionModel> {
+    clients: HashMap<SocketAddr, ControlledEntity<P::Input>>,
+    delivery: DeliveryRequirement,
+}
+
+impl<P: PredictionModel> Default for PredictionServer<P> {
+    fn default() -> Self {
+        Self {
+            clients: HashMap::new(),
+            delivery: DeliveryRequirement::Default,
+        }
+    }
+}
+
+impl<P: PredictionModel> PredictionServer<P> {
+    /// Applies the inputs received from a client to the given entity.
+    pub fn add_client(&mut self, addr: SocketAddr, entity: Entity) {
+        self.clients.insert(
+            addr,
+            ControlledEntity {
+                entity,
+                inputs: BTreeMap::new(),
+                processed: None,
+                unsent: false,
+            },
+        );
+    }
+
+    /// Stops applying the inputs of a client.
+    pub fn remove_client(&mut self, addr: SocketAddr) {
+        self.clients.remove(&addr);
+    }
+
+    /// Returns the entity controlled by a client.
+    pub fn entity(&self, addr: SocketAddr) -> Option<Entity> {
+        self.clients.get(&addr).map(|client| client.entity)
+    }
+
+    /// Returns the last input frame of a client which was processed.
+    pub fn processed(&self, addr: SocketAddr) -> Option<u32> {
+        self.clients.get(&addr).and_then(|client| client.processed)
+    }
+
+    /// Sets the delivery used for states sent to clients, `DeliveryRequirement::Default` by
+    /// default.
+    pub fn set_delivery(&mut self, delivery: DeliveryRequirement) {
+        self.delivery = delivery;
+    }
+
+    fn receive(&mut self, addr: SocketAddr, packet: InputPacket<P::Input>) {
+        if let Some(client) = self.clients.get_mut(&addr) {
+            for (frame, input) in packet.inputs {
+                if client
+                    .processed
+                    .map_or(true, |processed| is_after(frame, processed))
+                {
+                    client.inputs.insert(frame, input);
+                }
+            }
+            while client.inputs.len() > MAX_QUEUED_INPUTS {
+                let oldest = *client.inputs.keys().next().expect("Inputs are not empty");
+                client.inputs.remove(&oldest);
+            }
+        }
+    }
+
+    /// Applies the next input of every client.
+    fn simulate(&mut self, world: &mut World, model: &P, delta: Duration) {
+        for client in self.clients.values_mut() {
+            let frame = match client.inputs.keys().next() {
+                Some(frame) => *frame,
+                None => continue,
+            };
+            let input = client.inputs.remove(&frame).expect("Input exists");
+            if let Some(mut state) = model.capture(world, client.entity) {
+                model.step(&mut state, &input, delta);
+                model.restore(world, client.entity, &state);
+            }
+            client.processed = Some(frame);
+            client.unsent = true;
+        }
+    }
+
+    /// Returns the states to send to the clients whose inputs were processed since.
+    fn states(&mut self, world: &World, model: &P) -> Vec<(SocketAddr, StatePacket<P::State>)> {
+        let mut states = Vec::new();
+        for (addr, client) in self.clients.iter_mut() {
+            if !client.unsent {
+                continue;
+            }
+            if let (Some(frame), Some(state)) =
+                (client.processed, model.capture(world, client.entity))
+            {
+                client.unsent = false;
+                states.push((*addr, StatePacket { frame, state }));
+            }
+        }
+        states
+    }
+}
+
+/// Returns true if frame `a` comes after `b`, accounting for wrap around.
+fn is_after(a: u32, b: u32) -> bool {
+    a != b && a.wrapping_sub(b) < u32::MAX / 2
+}
+
+enum Role<P: PredictionModel> {
+    Server(PredictionServer<P>),
+    Client(PredictionClient<P>),
+}
+
+/// Adds the prediction of the entities controlled by clients.
+///
+/// Inputs and states are sent through the `TransportResource`. Set the input of every frame
+/// with `PredictionClient::set_input`, from a system running before this bundle.
+pub struct PredictionBundle<P: PredictionModel> {
+    model: Option<P>,
+    role: Option<Role<P>>,
+}
+
+impl<P: PredictionModel> PredictionBundle<P> {
+    /// Creates the bundle of a server.
+    pub fn server(model: P) -> Self {
+        Self {
+            model: Some(model),
+            role: Some(Role::Server(PredictionServer::default())),
+        }
+    }
+
+    /// Creates the bundle of a client.
+    pub fn client(model: P, client: PredictionClient<P>) -> Self {
+        Self {
+            model: Some(model),
+            role: Some(Role::Client(client)),
+        }
+    }
+}
+
+impl<P: PredictionModel> SystemBundle for PredictionBundle<P> {
+    fn load(
+        &mut self,
+        _world: &mut World,
+        resources: &mut Resources,
+        builder: &mut DispatcherBuilder,
+    ) -> Result<(), Error> {
+        let model = self
+            .model
+            .take()
+            .expect("PredictionBundle was already loaded");
+        let mut reader = resources
+            .get_mut_or_default::<EventChannel<NetworkSimulationEvent>>()
+            .register_reader();
+        resources.get_or_default::<TransportResource>();
+        resources.get_or_default::<NetworkSimulationTime>();
+
+        match self.role.take() {
+            Some(Role::Server(server)) => {
+                resources.insert(server);
+                builder.add_thread_local_fn(move |world, resources| {
+                    simulate_clients(world, resources, &model, &mut reader);
+                });
+            }
+            Some(Role::Client(client)) => {
+                resources.insert(client);
+                builder.add_thread_local_fn(move |world, resources| {
+                    predict_locally(world, resources, &model, &mut reader);
+                });
+            }
+            None => {}
+        }
+        Ok(())
+    }
+}
+
+fn simulate_clients<P: PredictionModel>(
+    world: &mut World,
+    resources: &mut Resources,
+    model: &P,
+    reader: &mut ReaderId<NetworkSimulationEvent>,
+) {
+    #[cfg(feature = "profiler")]
+    profile_scope!("simulate_clients");
+
+    let mut server = resources
+        .get_mut::<PredictionServer<P>>()
+        .expect("PredictionServer resource is missing");
+    let channel = resources
+        .get::<EventChannel<NetworkSimulationEvent>>()
+        .expect("NetworkSimulationEvent channel is missing");
+    for event in channel.read(reader) {
+        match event {
+            NetworkSimulationEvent::Message(addr, payload) => {
+                if let Some(packet) = packet::decode(&INPUT_MAGIC, payload) {
+                    server.receive(*addr, packet);
+                }
+            }
+            NetworkSimulationEvent::Disconnect(addr) => server.remove_client(*addr),
+            _ => {}
+        }
+    }
+
+    let sim_time = *resources
+        .get::<NetworkSimulationTime>()
+        .expect("NetworkSimulationTime resource is missing");
+    for _ in sim_time.sim_frames_to_run() {
+        server.simulate(world, model, sim_time.per_frame_duration());
+    }
+
+    if sim_time.should_send_message_now() {
+        let delivery = server.delivery;
+        let mut transport = resources
+            .get_mut::<TransportResource>()
+            .expect("TransportResource is missing");
+        for (addr, state) in server.states(world, model) {
+            transport.send_with_requirements(
+                addr,
+                &packet::encode(&STATE_MAGIC, &state),
+                delivery,
+                UrgencyRequirement::OnTick,
+            );
+        }
+    }
+}
+
+fn predict_locally<P: PredictionModel>(
+    world: &mut World,
+    resources: &mut Resources,
+    model: &P,
+    reader: &mut ReaderId<NetworkSimulationEvent>,
+) {
+    #[cfg(feature = "profiler")]
+    profile_scope!("predict_locally");
+
+    let mut client = resources
+        .get_mut::<PredictionClient<P>>()
+        .expect("PredictionClient resource is missing");
+    let sim_time = *resources
+        .get::<NetworkSimulationTime>()
+        .expect("NetworkSimulationTime resource is missing");
+    let delta = sim_time.per_frame_duration();
+
+    let channel = resources
+        .get::<EventChannel<NetworkSimulationEvent>>()
+        .expect("NetworkSimulationEvent channel is missing");
+    for event in channel.read(reader) {
+        if let NetworkSimulationEvent::Message(addr, payload) = event {
+            if client.server.map_or(false, |server| server != *addr) {
+                continue;
+            }
+            if let Some(packet) = packet::decode(&STATE_MAGIC, payload) {
+                client.reconcile(world, model, packet, delta);
+            }
+        }
+    }
+
+    let frames = sim_time.sim_frames_to_run();
+    if frames.is_empty() {
+        return;
+    }
+    for frame in frames {
+        client.predict(world, model, frame, delta);
+    }
+
+    if let Some(server) = client.server {
+        if !client.history.is_empty() {
+            let mut transport = resources
+                .get_mut::<TransportResource>()
+                .expect("TransportResource is missing");
+            transport.send_with_requirements(
+                server,
+                &packet::encode(&INPUT_MAGIC, &client.input_packet()),
+                client.delivery,
+                UrgencyRequirement::OnTick,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use amethyst_core::math::Vector3;
+
+    use super::*;
+
+    fn model() -> TransformPrediction<f32, impl Fn(&mut Transform, &f32, Duration)> {
+        TransformPrediction::new(|transform: &mut Transform, speed: &f32, _| {
+            transform.prepend_translation_x(*speed);
+        })
+    }
+
+    fn x(world: &World, entity: Entity) -> f32 {
+        world
+            .entry_ref(entity)
+            .unwrap()
+            .get_component::<Transform>()
+            .unwrap()
+            .translation()
+            .x
+    }
+
+    #[test]
+    fn test_matching_state_keeps_prediction() {
+        let model = model();
+        let mut world = World::default();
+        let entity = world.push((Transform::default(),));
+        let mut client = PredictionClient::<_>::new(None);
+        client.set_entity(Some(entity));
+        client.set_input(1.0);
+
+        for frame in 0..3 {
+            client.predict(&mut world, &model, frame, Duration::from_millis(10));
+        }
+        assert_eq!(x(&world, entity), 3.0);
+        assert_eq!(client.pending_inputs(), 3);
+
+        let mut state = Transform::default();
+        state.set_translation(Vector3::new(1.0, 0.0, 0.0));
+        client.reconcile(
+            &mut world,
+            &model,
+            StatePacket { frame: 0, state },
+            Duration::from_millis(10),
+        );
+        assert_eq!(client.pending_inputs(), 2);
+        assert_eq!(client.corrections(), 0);
+        assert_eq!(x(&world, entity), 3.0);
+    }
+
+    #[test]
+    fn test_mismatching_state_replays_inputs() {
+        let model = model();
+        let mut world = World::default();
+        let entity = world.push((Transform::default(),));
+        let mut client = PredictionClient::<_>::new(None);
+        client.set_entity(Some(entity));
+        client.set_input(1.0);
+
+        for frame in 0..4 {
+            client.predict(&mut world, &model, frame, Duration::from_millis(10));
+        }
+
+        // The server was blocked by a wall and only moved half the way.
+        let mut state = Transform::default();
+        state.set_translation(Vector3::new(0.5, 0.0, 0.0));
+        client.reconcile(
+            &mut world,
+            &model,
+            StatePacket { frame: 1, state },
+            Duration::from_millis(10),
+        );
+        assert_eq!(client.corrections(), 1);
+        assert_eq!(client.pending_inputs(), 2);
+        assert_eq!(x(&world, entity), 2.5);
+        assert_eq!(client.acknowledged(), Some(1));
+    }
+
+    #[test]
+    fn test_server_applies_inputs_in_order() {
+        let model = model();
+        let mut world = World::default();
+        let entity = world.push((Transform::default(),));
+        let addr = "127.0.0.1:3000".parse().unwrap();
+        let mut server = PredictionServer::default();
+        server.add_client(addr, entity);
+
+        server.receive(
+            addr,
+            InputPacket {
+                inputs: vec![(1, 2.0), (0, 1.0)],
+            },
+        );
+        server.simulate(&mut world, &model, Duration::from_millis(10));
+        assert_eq!(server.processed(addr), Some(0));
+        assert_eq!(x(&world, entity), 1.0);
+
+        // Inputs which were already processed are ignored when they are sent again.
+        server.receive(
+            addr,
+            InputPacket {
+                inputs: vec![(0, 1.0), (1, 2.0)],
+            },
+        );
+        server.simulate(&mut world, &model, Duration::from_millis(10));
+        server.simulate(&mut world, &model, Duration::from_millis(10));
+        assert_eq!(server.processed(addr), Some(1));
+        assert_eq!(x(&world, entity), 3.0);
+
+        let states = server.states(&world, &model);
+        assert_eq!(states.len(), 1);
+        assert_eq!(states[0].1.frame, 1);
+        assert!(server.states(&world, &model).is_empty());
+    }
+}
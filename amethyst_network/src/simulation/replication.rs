@@ -0,0 +1,892 @@
+//! Replication of entities from a server to its clients.
+//!
+//! The server sends the registered components of every entity with a `Replicated` component to
+//! each client interested in it, at a fixed simulation frame interval. Only components which
+//! changed since the last packet a client received are sent again. Entities entering the
+//! interest of a client are spawned on it with a `NetworkId`, and despawned again when they
+//! leave it or are removed on the server.
+//!
+//! Both ends have to register the same components in the same order, since components are
+//! identified by their registration index.
+
+use std::{
+    any::TypeId,
+    collections::{HashMap, HashSet},
+    convert::TryFrom,
+    marker::PhantomData,
+    net::SocketAddr,
+};
+
+use amethyst_core::{
+    ecs::{storage::Component, *},
+    math::{distance, Point3},
+    shrev::{EventChannel, ReaderId},
+    transform::Transform,
+};
+use amethyst_error::Error;
+use log::{error, warn};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+
+use crate::simulation::{
+    delta::{BitReader, BitWriter, ChangeMask},
+    events::NetworkSimulationEvent,
+    interpolation::{SnapshotInterpolation, SnapshotInterpolationSystem},
+    requirements::{DeliveryRequirement, UrgencyRequirement},
+    timing::NetworkSimulationTime,
+    transport::TransportResource,
+};
+
+/// Bytes in front of every replication packet, used to tell them apart from other messages.
+const PACKET_MAGIC: [u8; 4] = *b"AREP";
+
+/// Identifier of a replicated entity, shared by the server and all of its clients.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct NetworkId(pub u32);
+
+/// Marks an entity of the server to be replicated to clients.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Replicated {
+    /// Zone of the entity, used by clients with `Interest::Zones`. Entities without a zone are
+    /// replicated to all of those clients.
+    pub zone: Option<u32>,
+}
+
+impl Replicated {
+    /// Marks an entity to be replicated to clients interested in the given zone.
+    pub fn in_zone(zone: u32) -> Self {
+        Self { zone: Some(zone) }
+    }
+}
+
+/// Which entities are replicated to a client.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Interest {
+    /// All replicated entities.
+    Everything,
+    /// Entities whose `Transform` is within `radius` of `center`. Entities without a
+    /// `Transform` are always replicated.
+    Area {
+        /// Center of the area, usually the position of the player.
+        center: Point3<f32>,
+        /// Distance from the center at which entities are no longer replicated.
+        radius: f32,
+    },
+    /// Entities in one of the given zones, or without a zone.
+    Zones(Vec<u32>),
+}
+
+impl Default for Interest {
+    fn default() -> Self {
+        Interest::Everything
+    }
+}
+
+impl Interest {
+    /// Returns true if an entity in the given zone and at the given position is replicated.
+    pub fn contains(&self, zone: Option<u32>, position: Option<&Point3<f32>>) -> bool {
+        match self {
+            Interest::Everything => true,
+            Interest::Area { center, radius } => {
+                position.map_or(true, |position| distance(center, position) <= *radius)
+            }
+            Interest::Zones(zones) => zone.map_or(true, |zone| zones.contains(&zone)),
+        }
+    }
+}
+
+/// Reads and writes a single component type of replicated entities.
+trait Replicator: Send + Sync {
+    fn serialize(&self, world: &World, entity: Entity) -> Option<Vec<u8>>;
+    fn apply(&self, world: &mut World, entity: Entity, bytes: &[u8]) -> bincode::Result<()>;
+    fn remove(&self, world: &mut World, entity: Entity);
+    fn component_type(&self) -> TypeId;
+}
+
+struct ComponentReplicator<T>(PhantomData<fn() -> T>);
+
+impl<T> Replicator for ComponentReplicator<T>
+where
+    T: Component + Serialize + DeserializeOwned,
+{
+    fn serialize(&self, world: &World, entity: Entity) -> Option<Vec<u8>> {
+        let entry = world.entry_ref(entity).ok()?;
+        let component = entry.get_component::<T>().ok()?;
+        match bincode::serialize(component) {
+            Ok(bytes) => Some(bytes),
+            Err(e) => {
+                error!(
+                    "Failed to serialize replicated {}: {}",
+                    std::any::type_name::<T>(),
+                    e
+                );
+                None
+            }
+        }
+    }
+
+    fn apply(&self, world: &mut World, entity: Entity, bytes: &[u8]) -> bincode::Result<()> {
+        let component: T = bincode::deserialize(bytes)?;
+        if let Some(mut entry) = world.entry(entity) {
+            entry.add_component(component);
+        }
+        Ok(())
+    }
+
+    fn remove(&self, world: &mut World, entity: Entity) {
+        if let Some(mut entry) = world.entry(entity) {
+            entry.remove_component::<T>();
+        }
+    }
+
+    fn component_type(&self) -> TypeId {
+        TypeId::of::<T>()
+    }
+}
+
+/// Components replicated by a `ReplicationBundle`, in registration order.
+#[derive(Default)]
+struct ReplicationRegistry {
+    replicators: Vec<Box<dyn Replicator>>,
+}
+
+impl ReplicationRegistry {
+    fn register<T>(&mut self)
+    where
+        T: Component + Serialize + DeserializeOwned,
+    {
+        self.replicators
+            .push(Box::new(ComponentReplicator::<T>(PhantomData)));
+    }
+
+    fn serialize(&self, world: &World, entity: Entity) -> Vec<Option<Vec<u8>>> {
+        self.replicators
+            .iter()
+            .map(|replicator| replicator.serialize(world, entity))
+            .collect()
+    }
+}
+
+/// Change to a replicated entity.
+#[derive(Debug, PartialEq)]
+enum EntityUpdate {
+    /// Sets the given components, spawning the entity if the client doesn't know it yet.
+    Update {
+        id: NetworkId,
+        changed: Vec<(u16, Vec<u8>)>,
+        removed: Vec<u16>,
+    },
+    /// Despawns the entity.
+    Despawn { id: NetworkId },
+}
+
+/// Updates sent to a client in one simulation frame.
+#[derive(Debug, PartialEq)]
+struct ReplicationPacket {
+    frame: u32,
+    updates: Vec<EntityUpdate>,
+}
+
+impl ReplicationPacket {
+    /// Encodes the packet with varints, and a mask of the components each update touches.
+    fn encode(&self) -> Vec<u8> {
+        let mut writer = BitWriter::new();
+        writer.write_varint(u64::from(self.frame));
+        writer.write_varint(self.updates.len() as u64);
+        for update in &self.updates {
+            match update {
+                EntityUpdate::Update {
+                    id,
+                    changed,
+                    removed,
+                } => {
+                    writer.write_bool(false);
+                    writer.write_varint(u64::from(id.0));
+                    let fields = changed
+                        .iter()
+                        .map(|(index, _)| *index)
+                        .chain(removed.iter().copied())
+                        .max()
+                        .map_or(0, |index| usize::from(index) + 1);
+                    let mut touched = ChangeMask::new(fields);
+                    for index in changed.iter().map(|(index, _)| index).chain(removed) {
+                        touched.set(usize::from(*index));
+                    }
+                    touched.write(&mut writer);
+                    // Every touched component is either removed, or followed by its new value.
+                    for field in touched.changed() {
+                        match changed
+                            .iter()
+                            .find(|(index, _)| usize::from(*index) == field)
+                        {
+                            Some((_, bytes)) => {
+                                writer.write_bool(false);
+                                writer.write_bytes(bytes);
+                            }
+                            None => writer.write_bool(true),
+                        }
+                    }
+                }
+                EntityUpdate::Despawn { id } => {
+                    writer.write_bool(true);
+                    writer.write_varint(u64::from(id.0));
+                }
+            }
+        }
+        let mut bytes = PACKET_MAGIC.to_vec();
+        bytes.extend(writer.finish());
+        bytes
+    }
+
+    /// Decodes a packet, returning `None` if the bytes aren't a replication packet.
+    fn decode(bytes: &[u8]) -> Option<Self> {
+        if !bytes.starts_with(&PACKET_MAGIC) {
+            return None;
+        }
+        let packet = Self::read(&mut BitReader::new(&bytes[PACKET_MAGIC.len()..]));
+        if packet.is_none() {
+            warn!("Received a malformed replication packet");
+        }
+        packet
+    }
+
+    fn read(reader: &mut BitReader<'_>) -> Option<Self> {
+        let frame = u32::try_from(reader.read_varint()?).ok()?;
+        let count = reader.read_varint()? as usize;
+        // Every update takes at least two bits, which bounds what a malformed count allocates.
+        let mut updates = Vec::with_capacity(count.min(reader.remaining_bits() / 2));
+        for _ in 0..count {
+            let despawn = reader.read_bool()?;
+            let id = NetworkId(u32::try_from(reader.read_varint()?).ok()?);
+            if despawn {
+                updates.push(EntityUpdate::Despawn { id });
+                continue;
+            }
+            let touched = ChangeMask::read(reader)?;
+            let mut changed = Vec::new();
+            let mut removed = Vec::new();
+            for field in touched.changed() {
+                let index = u16::try_from(field).ok()?;
+                if reader.read_bool()? {
+                    removed.push(index);
+                } else {
+                    changed.push((index, reader.read_bytes()?));
+                }
+            }
+            updates.push(EntityUpdate::Update {
+                id,
+                changed,
+                removed,
+            });
+        }
+        Some(Self { frame, updates })
+    }
+}
+
+/// What a client was sent so far.
+#[derive(Debug, Default)]
+struct ClientView {
+    interest: Interest,
+    known: HashMap<NetworkId, Vec<Option<Vec<u8>>>>,
+}
+
+/// Resource of the server keeping track of the clients entities are replicated to.
+#[derive(Debug)]
+pub struct ReplicationServer {
+    clients: HashMap<SocketAddr, ClientView>,
+    send_interval: u32,
+    delivery: DeliveryRequirement,
+    last_send: Option<u32>,
+    next_id: u32,
+}
+
+impl Default for ReplicationServer {
+    fn default() -> Self {
+        Self::new(1)
+    }
+}
+
+impl ReplicationServer {
+    /// Creates a server sending updates every `send_interval` simulation frames.
+    pub fn new(send_interval: u32) -> Self {
+        Self {
+            clients: HashMap::new(),
+            send_interval: send_interval.max(1),
+            delivery: DeliveryRequirement::ReliableOrdered(None),
+            last_send: None,
+            next_id: 0,
+        }
+    }
+
+    /// Returns the number of simulation frames between two updates.
+    pub fn send_interval(&self) -> u32 {
+        self.send_interval
+    }
+
+    /// Sets the number of simulation frames between two updates.
+    pub fn set_send_interval(&mut self, send_interval: u32) {
+        self.send_interval = send_interval.max(1);
+    }
+
+    /// Sets the delivery used for updates, `ReliableOrdered(None)` by default.
+    ///
+    /// Updates only contain what changed since the previous one, so they have to be delivered
+    /// reliably and in order.
+    pub fn set_delivery(&mut self, delivery: DeliveryRequirement) {
+        self.delivery = delivery;
+    }
+
+    /// Starts replicating entities to a client.
+    pub fn add_client(&mut self, addr: SocketAddr, interest: Interest) {
+        self.clients.insert(
+            addr,
+            ClientView {
+                interest,
+                known: HashMap::new(),
+            },
+        );
+    }
+
+    /// Changes which entities are replicated to a client. Entities leaving its interest are
+    /// despawned on the client with the next update.
+    pub fn set_interest(&mut self, addr: SocketAddr, interest: Interest) {
+        if let Some(client) = self.clients.get_mut(&addr) {
+            client.interest = interest;
+        }
+    }
+
+    /// Stops replicating entities to a client.
+    pub fn remove_client(&mut self, addr: SocketAddr) {
+        self.clients.remove(&addr);
+    }
+
+    /// Returns the addresses of all clients.
+    pub fn clients(&self) -> impl Iterator<Item = &SocketAddr> {
+        self.clients.keys()
+    }
+
+    /// Returns true if updates are sent in the given frame, remembering that they were.
+    fn should_send(&mut self, frame: u32) -> bool {
+        let due = self
+            .last_send
+            .map_or(true, |last| frame.wrapping_sub(last) >= self.send_interval);
+        if due {
+            self.last_send = Some(frame);
+        }
+        due
+    }
+
+    /// Assigns ids to new replicated entities and returns the packets to send to every client.
+    fn collect(
+        &mut self,
+        world: &mut World,
+        registry: &ReplicationRegistry,
+        frame: u32,
+    ) -> Vec<(SocketAddr, ReplicationPacket)> {
+        let unassigned: Vec<Entity> = <Entity>::query()
+            .filter(component::<Replicated>() & !component::<NetworkId>())
+            .iter(world)
+            .copied()
+            .collect();
+        for entity in unassigned {
+            let id = NetworkId(self.next_id);
+            self.next_id = self.next_id.wrapping_add(1);
+            if let Some(mut entry) = world.entry(entity) {
+                entry.add_component(id);
+            }
+        }
+
+        let entities: Vec<_> = <(
+            Entity,
+            Read<NetworkId>,
+            Read<Replicated>,
+            TryRead<Transform>,
+        )>::query()
+        .iter(world)
+        .map(|(entity, id, replicated, transform)| {
+            let position =
+                transform.map(|transform| Point3::from(transform.global_matrix().column(3).xyz()));
+            (*entity, *id, replicated.zone, position)
+        })
+        .collect();
+        let mut states = HashMap::with_capacity(entities.len());
+
+        let mut packets = Vec::with_capacity(self.clients.len());
+        for (addr, client) in self.clients.iter_mut() {
+            let mut updates = Vec::new();
+            let mut visible = HashSet::new();
+            for (entity, id, zone, position) in &entities {
+                if !client.interest.contains(*zone, position.as_ref()) {
+                    continue;
+                }
+                visible.insert(*id);
+                let state = states
+                    .entry(*id)
+                    .or_insert_with(|| registry.serialize(world, *entity));
+                let is_new = !client.known.contains_key(id);
+                let known = client
+                    .known
+                    .entry(*id)
+                    .or_insert_with(|| vec![None; state.len()]);
+                let mut changed = Vec::new();
+                let mut removed = Vec::new();
+                for (index, (current, sent)) in state.iter().zip(known.iter_mut()).enumerate() {
+                    if current == sent {
+                        continue;
+                    }
+                    match current {
+                        Some(bytes) => changed.push((index as u16, bytes.clone())),
+                        None => removed.push(index as u16),
+                    }
+                    *sent = current.clone();
+                }
+                if is_new || !changed.is_empty() || !removed.is_empty() {
+                    updates.push(EntityUpdate::Update {
+                        id: *id,
+                        changed,
+                        removed,
+                    });
+                }
+            }
+            client.known.retain(|id, _| {
+                let keep = visible.contains(id);
+                if !keep {
+                    updates.push(EntityUpdate::Despawn { id: *id });
+                }
+                keep
+            });
+            if !updates.is_empty() {
+                packets.push((*addr, ReplicationPacket { frame, updates }));
+            }
+        }
+        packets
+    }
+}
+
+/// Resource of a client keeping track of the entities replicated from the server.
+#[derive(Debug, Default)]
+pub struct ReplicationClient {
+    server: Option<SocketAddr>,
+    entities: HashMap<NetworkId, Entity>,
+    frame: Option<u32>,
+    updated: HashSet<(Entity, TypeId)>,
+}
+
+impl ReplicationClient {
+    /// Creates a client accepting updates from the given server, or from anyone if `None`.
+    pub fn new(server: Option<SocketAddr>) -> Self {
+        Self {
+            server,
+            entities: HashMap::new(),
+            frame: None,
+            updated: HashSet::new(),
+        }
+    }
+
+    /// Returns the server updates are accepted from.
+    pub fn server(&self) -> Option<SocketAddr> {
+        self.server
+    }
+
+    /// Sets the server updates are accepted from.
+    pub fn set_server(&mut self, server: Option<SocketAddr>) {
+        self.server = server;
+    }
+
+    /// Returns the local entity of a replicated entity.
+    pub fn entity(&self, id: NetworkId) -> Option<Entity> {
+        self.entities.get(&id).copied()
+    }
+
+    /// Returns true if a component of an entity was set by the packets received in the current
+    /// frame.
+    pub fn was_updated<T: Component>(&self, entity: Entity) -> bool {
+        self.updated.contains(&(entity, TypeId::of::<T>()))
+    }
+
+    /// Returns the simulation frame of the server the last update was sent in.
+    pub fn frame(&self) -> Option<u32> {
+        self.frame
+    }
+
+    fn apply(
+        &mut self,
+        world: &mut World,
+        registry: &ReplicationRegistry,
+        packet: ReplicationPacket,
+    ) {
+        self.frame = Some(packet.frame);
+        for update in packet.updates {
+            match update {
+                EntityUpdate::Update {
+                    id,
+                    changed,
+                    removed,
+                } => {
+                    let entity = *self.entities.entry(id).or_insert_with(|| world.push((id,)));
+                    for (index, bytes) in changed {
+                        match registry.replicators.get(index as usize) {
+                            Some(replicator) => {
+                                match replicator.apply(world, entity, &bytes) {
+                                    Ok(()) => {
+                                        self.updated.insert((entity, replicator.component_type()));
+                                    }
+                                    Err(e) => warn!("Failed to apply replicated component: {}", e),
+                                }
+                            }
+                            None => warn!("Received unregistered replicated component {}", index),
+                        }
+                    }
+                    for index in removed {
+                        if let Some(replicator) = registry.replicators.get(index as usize) {
+                            replicator.remove(world, entity);
+                        }
+                    }
+                }
+                EntityUpdate::Despawn { id } => {
+                    if let Some(entity) = self.entities.remove(&id) {
+                        world.remove(entity);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Despawns all replicated entities, e.g. after losing the connection to the server.
+    pub fn clear(&mut self, world: &mut World) {
+        for (_, entity) in self.entities.drain() {
+            world.remove(entity);
+        }
+        self.updated.clear();
+        self.frame = None;
+    }
+}
+
+enum Role {
+    Server(ReplicationServer),
+    Client(ReplicationClient),
+}
+
+/// Adds replication of entities from a server to its clients.
+///
+/// Clients also get the `SnapshotInterpolationSystem`, smoothing the movement of the replicated
+/// entities with a `TransformSnapshots` component.
+///
+/// Updates are sent through the `TransportResource`, so add the bundle of a transport which
+/// supports `DeliveryRequirement::ReliableOrdered`, like the laminar or TCP transports or UDP
+/// with delivery channels.
+pub struct ReplicationBundle {
+    role: Option<Role>,
+    registry: Option<ReplicationRegistry>,
+}
+
+impl ReplicationBundle {
+    /// Creates the bundle of a server.
+    pub fn server(server: ReplicationServer) -> Self {
+        Self {
+            role: Some(Role::Server(server)),
+            registry: Some(ReplicationRegistry::default()),
+        }
+    }
+
+    /// Creates the bundle of a client.
+    pub fn client(client: ReplicationClient) -> Self {
+        Self {
+            role: Some(Role::Client(client)),
+            registry: Some(ReplicationRegistry::default()),
+        }
+    }
+
+    /// Replicates the given component type.
+    pub fn with_component<T>(mut self) -> Self
+    where
+        T: Component + Serialize + DeserializeOwned,
+    {
+        if let Some(registry) = self.registry.as_mut() {
+            registry.register::<T>();
+        }
+        self
+    }
+}
+
+impl SystemBundle for ReplicationBundle {
+    fn load(
+        &mut self,
+        _world: &mut World,
+        resources: &mut Resources,
+        builder: &mut DispatcherBuilder,
+    ) -> Result<(), Error> {
+        let registry = self.registry.take().unwrap_or_default();
+        let mut reader = resources
+            .get_mut_or_default::<EventChannel<NetworkSimulationEvent>>()
+            .register_reader();
+        resources.get_or_default::<TransportResource>();
+        resources.get_or_default::<NetworkSimulationTime>();
+
+        match self.role.take() {
+            Some(Role::Server(server)) => {
+                resources.insert(server);
+                builder.add_thread_local_fn(move |world, resources| {
+                    replicate_to_clients(world, resources, &registry, &mut reader);
+                });
+            }
+            Some(Role::Client(client)) => {
+                resources.insert(client);
+                resources.get_or_default::<SnapshotInterpolation>();
+                builder
+                    .add_thread_local_fn(move |world, resources| {
+                        replicate_from_server(world, resources, &registry, &mut reader);
+                    })
+                    .add_system(SnapshotInterpolationSystem);
+            }
+            None => {}
+        }
+        Ok(())
+    }
+}
+
+fn replicate_to_clients(
+    world: &mut World,
+    resources: &mut Resources,
+    registry: &ReplicationRegistry,
+    reader: &mut ReaderId<NetworkSimulationEvent>,
+) {
+    #[cfg(feature = "profiler")]
+    profile_scope!("replicate_to_clients");
+
+    let mut server = resources
+        .get_mut::<ReplicationServer>()
+        .expect("ReplicationServer resource is missing");
+    let channel = resources
+        .get::<EventChannel<NetworkSimulationEvent>>()
+        .expect("NetworkSimulationEvent channel is missing");
+    for event in channel.read(reader) {
+        if let NetworkSimulationEvent::Disconnect(addr) = event {
+            server.remove_client(*addr);
+        }
+    }
+
+    let frame = resources
+        .get::<NetworkSimulationTime>()
+        .expect("NetworkSimulationTime resource is missing")
+        .frame_number();
+    if !server.should_send(frame) {
+        return;
+    }
+    let delivery = server.delivery;
+    let packets = server.collect(world, registry, frame);
+    let mut transport = resources
+        .get_mut::<TransportResource>()
+        .expect("TransportResource is missing");
+    for (addr, packet) in packets {
+        transport.send_with_requirements(
+            addr,
+            &packet.encode(),
+            delivery,
+            UrgencyRequirement::OnTick,
+        );
+    }
+}
+
+fn replicate_from_server(
+    world: &mut World,
+    resources: &mut Resources,
+    registry: &ReplicationRegistry,
+    reader: &mut ReaderId<NetworkSimulationEvent>,
+) {
+    #[cfg(feature = "profiler")]
+    profile_scope!("replicate_from_server");
+
+    let mut client = resources
+        .get_mut::<ReplicationClient>()
+        .expect("ReplicationClient resource is missing");
+    client.updated.clear();
+    let channel = resources
+        .get::<EventChannel<NetworkSimulationEvent>>()
+        .expect("NetworkSimulationEvent channel is missing");
+    for event in channel.read(reader) {
+        match event {
+            NetworkSimulationEvent::Message(addr, payload) => {
+                if client.server.map_or(false, |server| server != *addr) {
+                    continue;
+                }
+                if let Some(packet) = ReplicationPacket::decode(payload) {
+                    client.apply(world, registry, packet);
+                }
+            }
+            NetworkSimulationEvent::Disconnect(addr) if client.server == Some(*addr) => {
+                client.clear(world);
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    struct Health(u32);
+
+    fn registry() -> ReplicationRegistry {
+        let mut registry = ReplicationRegistry::default();
+        registry.register::<Transform>();
+        registry.register::<Health>();
+        registry
+    }
+
+    fn transfer(
+        server: &mut ReplicationServer,
+        server_world: &mut World,
+        client: &mut ReplicationClient,
+        client_world: &mut World,
+        frame: u32,
+    ) -> usize {
+        let registry = registry();
+        let packets = server.collect(server_world, &registry, frame);
+        let mut updates = 0;
+        for (_, packet) in packets {
+            let packet = ReplicationPacket::decode(&packet.encode()).unwrap();
+            updates += packet.updates.len();
+            client.apply(client_world, &registry, packet);
+        }
+        updates
+    }
+
+    #[test]
+    fn test_entities_are_spawned_updated_and_despawned() {
+        let mut server = ReplicationServer::new(1);
+        let mut client = ReplicationClient::default();
+        let mut server_world = World::default();
+        let mut client_world = World::default();
+        server.add_client("127.0.0.1:3000".parse().unwrap(), Interest::Everything);
+
+        let entity = server_world.push((Replicated::default(), Health(10)));
+        assert_eq!(
+            transfer(
+                &mut server,
+                &mut server_world,
+                &mut client,
+                &mut client_world,
+                0
+            ),
+            1
+        );
+        let replica = client.entity(NetworkId(0)).unwrap();
+        assert_eq!(
+            client_world
+                .entry_ref(replica)
+                .unwrap()
+                .get_component::<Health>()
+                .unwrap(),
+            &Health(10)
+        );
+
+        // Nothing changed, so nothing is sent.
+        assert_eq!(
+            transfer(
+                &mut server,
+                &mut server_world,
+                &mut client,
+                &mut client_world,
+                1
+            ),
+            0
+        );
+
+        server_world.entry(entity).unwrap().add_component(Health(5));
+        transfer(
+            &mut server,
+            &mut server_world,
+            &mut client,
+            &mut client_world,
+            2,
+        );
+        assert_eq!(
+            client_world
+                .entry_ref(replica)
+                .unwrap()
+                .get_component::<Health>()
+                .unwrap(),
+            &Health(5)
+        );
+
+        server_world.remove(entity);
+        transfer(
+            &mut server,
+            &mut server_world,
+            &mut client,
+            &mut client_world,
+            3,
+        );
+        assert_eq!(client.entity(NetworkId(0)), None);
+        assert!(client_world.entry_ref(replica).is_err());
+    }
+
+    #[test]
+    fn test_interest_limits_replicated_entities() {
+        let mut server = ReplicationServer::new(1);
+        let mut client = ReplicationClient::default();
+        let mut server_world = World::default();
+        let mut client_world = World::default();
+        let addr = "127.0.0.1:3000".parse().unwrap();
+        server.add_client(addr, Interest::Zones(vec![1]));
+
+        server_world.push((Replicated::in_zone(1), Health(1)));
+        server_world.push((Replicated::in_zone(2), Health(2)));
+        transfer(
+            &mut server,
+            &mut server_world,
+            &mut client,
+            &mut client_world,
+            0,
+        );
+        assert!(client.entity(NetworkId(0)).is_some());
+        assert!(client.entity(NetworkId(1)).is_none());
+
+        server.set_interest(addr, Interest::Zones(vec![2]));
+        transfer(
+            &mut server,
+            &mut server_world,
+            &mut client,
+            &mut client_world,
+            1,
+        );
+        assert!(client.entity(NetworkId(0)).is_none());
+        assert!(client.entity(NetworkId(1)).is_some());
+    }
+
+    #[test]
+    fn test_send_interval() {
+        let mut server = ReplicationServer::new(3);
+        assert!(server.should_send(0));
+        assert!(!server.should_send(1));
+        assert!(!server.should_send(2));
+        assert!(server.should_send(3));
+    }
+
+    #[test]
+    fn test_other_messages_are_not_packets() {
+        assert_eq!(ReplicationPacket::decode(b"hello"), None);
+    }
+
+    #[test]
+    fn test_packets_round_trip() {
+        let packet = ReplicationPacket {
+            frame: 300,
+            updates: vec![
+                EntityUpdate::Update {
+                    id: NetworkId(7),
+                    changed: vec![(0, vec![1, 2, 3]), (2, vec![])],
+                    removed: vec![1],
+                },
+                EntityUpdate::Despawn { id: NetworkId(8) },
+            ],
+        };
+        let bytes = packet.encode();
+        assert_eq!(ReplicationPacket::decode(&bytes), Some(packet));
+        assert_eq!(ReplicationPacket::decode(&bytes[..bytes.len() - 2]), None);
+    }
+}
@@ -0,0 +1,374 @@
+//! Typed messages sent over the transport of the simulation.
+//!
+//! Register every message type with the `RpcBundle` once, send messages with the `Rpc`
+//! resource, and read the received ones from the `EventChannel<RpcEvent<T>>` of their type
+//! instead of slicing the bytes of `NetworkSimulationEvent::Message` by hand.
+//!
+//! Message types are registered under a name, and identified on the wire by a hash of it, so
+//! both ends have to register them under the same names, but may do so in any order. Names
+//! don't depend on how the game is built, so peers built with different compilers or versions
+//! of the game can talk to each other as long as the messages are the same.
+
+use std::{
+    any::{type_name, TypeId},
+    collections::{HashMap, HashSet},
+    marker::PhantomData,
+    net::SocketAddr,
+};
+
+use amethyst_core::{
+    ecs::*,
+    shrev::{EventChannel, ReaderId},
+};
+use amethyst_error::Error;
+use log::warn;
+use serde::{de::DeserializeOwned, Serialize};
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+
+use crate::simulation::{
+    events::NetworkSimulationEvent,
+    packet,
+    requirements::{DeliveryRequirement, UrgencyRequirement},
+    transport::TransportResource,
+};
+
+/// Bytes in front of every RPC packet, used to tell them apart from other messages.
+const PACKET_MAGIC: [u8; 4] = *b"ARPC";
+
+/// A message received from a peer.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RpcEvent<T> {
+    /// Address of the peer which sent the message.
+    pub source: SocketAddr,
+    /// The received message.
+    pub message: T,
+}
+
+/// Identifies a message type on the wire, with the 32 bit FNV-1a hash of its name.
+fn message_id(name: &str) -> u32 {
+    name.bytes().fold(0x811c_9dc5, |hash, byte| {
+        (hash ^ u32::from(byte)).wrapping_mul(0x0100_0193)
+    })
+}
+
+/// Resource to send registered message types to peers.
+///
+/// Peers are known once they connected or sent an RPC, and forgotten when they disconnect.
+#[derive(Debug, Default)]
+pub struct Rpc {
+    types: HashMap<TypeId, (u32, DeliveryRequirement)>,
+    peers: HashSet<SocketAddr>,
+    outgoing: Vec<(SocketAddr, DeliveryRequirement, Vec<u8>)>,
+}
+
+impl Rpc {
+    /// Sends a message to the given peer.
+    ///
+    /// Fails if the message type wasn't registered with the `RpcBundle`.
+    pub fn send<T>(&mut self, destination: SocketAddr, message: &T) -> Result<(), Error>
+    where
+        T: Serialize + 'static,
+    {
+        let (id, delivery) = self.registration::<T>()?;
+        self.outgoing.push((
+            destination,
+            delivery,
+            packet::encode_tagged(&PACKET_MAGIC, id, message),
+        ));
+        Ok(())
+    }
+
+    /// Sends a message to all known peers.
+    ///
+    /// Fails if the message type wasn't registered with the `RpcBundle`.
+    pub fn broadcast<T>(&mut self, message: &T) -> Result<(), Error>
+    where
+        T: Serialize + 'static,
+    {
+        let (id, delivery) = self.registration::<T>()?;
+        let payload = packet::encode_tagged(&PACKET_MAGIC, id, message);
+        for peer in &self.peers {
+            self.outgoing.push((*peer, delivery, payload.clone()));
+        }
+        Ok(())
+    }
+
+    /// Returns the peers messages are broadcast to.
+    pub fn peers(&self) -> impl Iterator<Item = &SocketAddr> {
+        self.peers.iter()
+    }
+
+    /// Adds a peer messages are broadcast to, e.g. a server which wasn't heard from yet.
+    pub fn add_peer(&mut self, addr: SocketAddr) {
+        self.peers.insert(addr);
+    }
+
+    /// Stops broadcasting messages to the given peer.
+    pub fn remove_peer(&mut self, addr: SocketAddr) {
+        self.peers.remove(&addr);
+    }
+
+    fn registration<T: 'static>(&self) -> Result<(u32, DeliveryRequirement), Error> {
+        self.types.get(&TypeId::of::<T>()).copied().ok_or_else(|| {
+            Error::from_string(format!(
+                "{} is not registered with the RpcBundle",
+                type_name::<T>()
+            ))
+        })
+    }
+}
+
+/// Deserializes the messages of one type into its event channel.
+trait RpcHandler {
+    fn dispatch(&self, resources: &Resources, source: SocketAddr, bytes: &[u8]);
+}
+
+struct MessageHandler<T>(PhantomData<T>);
+
+impl<T> RpcHandler for MessageHandler<T>
+where
+    T: DeserializeOwned + Send + Sync + 'static,
+{
+    fn dispatch(&self, resources: &Resources, source: SocketAddr, bytes: &[u8]) {
+        if let Some(message) = packet::deserialize::<T>(bytes) {
+            resources
+                .get_mut::<EventChannel<RpcEvent<T>>>()
+                .expect("RpcEvent channel is missing")
+                .single_write(RpcEvent { source, message });
+        }
+    }
+}
+
+/// Message types registered with an `RpcBundle`.
+#[derive(Default)]
+struct RpcRegistry {
+    rpc: Rpc,
+    handlers: HashMap<u32, Box<dyn RpcHandler>>,
+    names: HashMap<u32, &'static str>,
+}
+
+impl RpcRegistry {
+    fn register<T>(&mut self, name: &'static str, delivery: DeliveryRequirement)
+    where
+        T: Serialize + DeserializeOwned + Send + Sync + 'static,
+    {
+        let id = message_id(name);
+        if let Some(other) = self.names.insert(id, name) {
+            if other != name {
+                panic!(
+                    "The ids of the message names {} and {} collide",
+                    other, name
+                );
+            }
+        }
+        self.rpc.types.insert(TypeId::of::<T>(), (id, delivery));
+        self.handlers
+            .insert(id, Box::new(MessageHandler::<T>(PhantomData)));
+    }
+}
+
+/// Adds the `Rpc` resource, and an `EventChannel<RpcEvent<T>>` for every registered message
+/// type.
+///
+/// This bundle only adds the RPC layer, add one of the network bundles for the transport too.
+#[derive(Default)]
+pub struct RpcBundle {
+    registry: Option<RpcRegistry>,
+    channels: Vec<fn(&mut Resources)>,
+}
+
+impl RpcBundle {
+    /// Creates a bundle without any message types.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a message type under the given name, sent with the given delivery.
+    ///
+    /// The name identifies the message type on the wire, so it has to be the same on all peers
+    /// and unique among the registered messages, e.g. `"chat"`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the hash of the name collides with the one of another registered name.
+    pub fn with_message<T>(mut self, name: &'static str, delivery: DeliveryRequirement) -> Self
+    where
+        T: Serialize + DeserializeOwned + Send + Sync + 'static,
+    {
+        self.registry
+            .get_or_insert_with(RpcRegistry::default)
+            .register::<T>(name, delivery);
+        self.channels.push(|resources| {
+            resources.get_or_default::<EventChannel<RpcEvent<T>>>();
+        });
+        self
+    }
+}
+
+impl SystemBundle for RpcBundle {
+    fn load(
+        &mut self,
+        _world: &mut World,
+        resources: &mut Resources,
+        builder: &mut DispatcherBuilder,
+    ) -> Result<(), Error> {
+        let RpcRegistry { rpc, handlers, .. } = self.registry.take().unwrap_or_default();
+        for insert_channel in self.channels.drain(..) {
+            insert_channel(resources);
+        }
+        let mut reader = resources
+            .get_mut_or_default::<EventChannel<NetworkSimulationEvent>>()
+            .register_reader();
+        resources.get_or_default::<TransportResource>();
+        resources.insert(rpc);
+
+        builder.add_thread_local_fn(move |_world, resources| {
+            exchange_rpcs(resources, &handlers, &mut reader);
+        });
+        Ok(())
+    }
+}
+
+/// Dispatches the received messages to their event channels and hands the sent ones to the
+/// transport.
+fn exchange_rpcs(
+    resources: &Resources,
+    handlers: &HashMap<u32, Box<dyn RpcHandler>>,
+    reader: &mut ReaderId<NetworkSimulationEvent>,
+) {
+    #[cfg(feature = "profiler")]
+    profile_scope!("exchange_rpcs");
+
+    let mut rpc = resources.get_mut::<Rpc>().expect("Rpc resource is missing");
+    let channel = resources
+        .get::<EventChannel<NetworkSimulationEvent>>()
+        .expect("NetworkSimulationEvent channel is missing");
+    for event in channel.read(reader) {
+        match event {
+            NetworkSimulationEvent::Connect(addr) => rpc.add_peer(*addr),
+            NetworkSimulationEvent::Disconnect(addr) => rpc.remove_peer(*addr),
+            NetworkSimulationEvent::Message(addr, payload) => {
+                if let Some((id, bytes)) = packet::split_tagged(&PACKET_MAGIC, payload) {
+                    match handlers.get(&id) {
+                        Some(handler) => {
+                            rpc.add_peer(*addr);
+                            handler.dispatch(resources, *addr, bytes);
+                        }
+                        None => {
+                            warn!(
+                                "Received an unregistered message {:#010x} from {}",
+                                id, addr
+                            )
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut transport = resources
+        .get_mut::<TransportResource>()
+        .expect("TransportResource is missing");
+    for (addr, delivery, payload) in rpc.outgoing.drain(..) {
+        transport.send_with_requirements(addr, &payload, delivery, UrgencyRequirement::OnTick);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    struct Chat(String);
+
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    struct Ready;
+
+    #[test]
+    fn test_messages_are_received_as_typed_events() {
+        let mut registry = RpcRegistry::default();
+        registry.register::<Ready>("ready", DeliveryRequirement::ReliableOrdered(None));
+        registry.register::<Chat>("chat", DeliveryRequirement::ReliableOrdered(None));
+        let RpcRegistry {
+            mut rpc, handlers, ..
+        } = registry;
+
+        let peer = "127.0.0.1:3000".parse().unwrap();
+        rpc.send(peer, &Chat("hello".into())).unwrap();
+        rpc.send(peer, &Ready).unwrap();
+        let sent: Vec<_> = rpc
+            .outgoing
+            .drain(..)
+            .map(|(_, _, payload)| payload)
+            .collect();
+
+        let mut resources = Resources::default();
+        resources.insert(rpc);
+        resources.insert(TransportResource::new());
+        resources.insert(EventChannel::<RpcEvent<Chat>>::new());
+        resources.insert(EventChannel::<RpcEvent<Ready>>::new());
+        let mut network_events = EventChannel::<NetworkSimulationEvent>::new();
+        let mut reader = network_events.register_reader();
+        for payload in sent {
+            network_events
+                .single_write(NetworkSimulationEvent::Message(peer, Bytes::from(payload)));
+        }
+        network_events.single_write(NetworkSimulationEvent::Message(peer, Bytes::from("raw")));
+        resources.insert(network_events);
+
+        let mut chat_reader = resources
+            .get_mut::<EventChannel<RpcEvent<Chat>>>()
+            .unwrap()
+            .register_reader();
+        exchange_rpcs(&resources, &handlers, &mut reader);
+
+        let chats = resources.get::<EventChannel<RpcEvent<Chat>>>().unwrap();
+        let received: Vec<_> = chats.read(&mut chat_reader).cloned().collect();
+        assert_eq!(
+            received,
+            vec![RpcEvent {
+                source: peer,
+                message: Chat("hello".into())
+            }]
+        );
+        assert!(resources.get::<Rpc>().unwrap().peers().any(|p| *p == peer));
+    }
+
+    #[test]
+    fn test_broadcast_reaches_all_peers() {
+        let mut registry = RpcRegistry::default();
+        registry.register::<Ready>("ready", DeliveryRequirement::Reliable);
+        let mut rpc = registry.rpc;
+        rpc.add_peer("127.0.0.1:3000".parse().unwrap());
+        rpc.add_peer("127.0.0.1:3001".parse().unwrap());
+
+        rpc.broadcast(&Ready).unwrap();
+        assert_eq!(rpc.outgoing.len(), 2);
+        assert!(rpc
+            .outgoing
+            .iter()
+            .all(|(_, delivery, _)| *delivery == DeliveryRequirement::Reliable));
+    }
+
+    #[test]
+    fn test_messages_are_identified_by_their_name() {
+        let mut registry = RpcRegistry::default();
+        registry.register::<Chat>("chat", DeliveryRequirement::Reliable);
+        let mut rpc = registry.rpc;
+        let peer = "127.0.0.1:3000".parse().unwrap();
+
+        rpc.send(peer, &Chat("hello".into())).unwrap();
+        let (_, _, payload) = &rpc.outgoing[0];
+        let (id, _) = packet::split_tagged(&PACKET_MAGIC, payload).unwrap();
+        assert_eq!(id, message_id("chat"));
+
+        assert!(rpc.send(peer, &Ready).is_err());
+        assert!(rpc.broadcast(&Ready).is_err());
+        assert_eq!(rpc.outgoing.len(), 1);
+    }
+}
@@ -0,0 +1,541 @@
+//! Sessions between peers, established with a handshake checking the protocol version of both
+//! ends and kept alive until either end disconnects or stops responding.
+//!
+//! A client calls `Sessions::connect`, which sends connection requests until the server accepts
+//! or rejects it. Both ends send keep-alives while a session is idle, and drop it once nothing
+//! was received for the configured timeout. Sessions starting and ending are surfaced as
+//! `SessionEvent`s with the reason a session ended.
+
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+
+use amethyst_core::{
+    ecs::*,
+    shrev::{EventChannel, ReaderId},
+};
+use amethyst_error::Error;
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+
+use crate::simulation::{
+    events::NetworkSimulationEvent,
+    packet,
+    requirements::{DeliveryRequirement, UrgencyRequirement},
+    transport::TransportResource,
+};
+
+/// Bytes in front of every session packet, used to tell them apart from other messages.
+const PACKET_MAGIC: [u8; 4] = *b"ASES";
+
+/// Why a session ended or couldn't be established.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DisconnectReason {
+    /// One of the peers disconnected on purpose.
+    Requested,
+    /// The server ended the session, for the given reason.
+    Kicked(String),
+    /// Nothing was received from the peer for longer than the timeout.
+    TimedOut,
+    /// The transport reported the connection to the peer as lost.
+    ConnectionLost,
+    /// The server doesn't accept incoming sessions.
+    Refused,
+    /// The server already has as many sessions as it accepts.
+    ServerFull,
+    /// The protocol version of the client isn't supported by the server.
+    VersionMismatch {
+        /// Protocol version of the server.
+        server: u32,
+        /// Protocol version of the client.
+        client: u32,
+    },
+}
+
+/// Events written by the `SessionSystem` when sessions start and end.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SessionEvent {
+    /// A session with the peer was established.
+    Connected(SocketAddr),
+    /// The session with the peer ended, or couldn't be established.
+    Disconnected(SocketAddr, DisconnectReason),
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+enum SessionPacket {
+    Connect { version: u32 },
+    Accept { version: u32 },
+    Reject { reason: DisconnectReason },
+    KeepAlive,
+    Disconnect { reason: DisconnectReason },
+}
+
+impl SessionPacket {
+    fn encode(&self) -> Vec<u8> {
+        packet::encode(&PACKET_MAGIC, self)
+    }
+
+    /// Decodes a packet, returning `None` if the bytes aren't a session packet.
+    fn decode(bytes: &[u8]) -> Option<Self> {
+        packet::decode(&PACKET_MAGIC, bytes)
+    }
+}
+
+#[derive(Debug)]
+struct Session {
+    connected: bool,
+    remote_version: Option<u32>,
+    last_received: Instant,
+    last_sent: Instant,
+}
+
+impl Session {
+    fn new(connected: bool, now: Instant) -> Self {
+        Self {
+            connected,
+            remote_version: None,
+            last_received: now,
+            last_sent: now,
+        }
+    }
+}
+
+/// Resource holding the sessions with all peers.
+#[derive(Debug)]
+pub struct Sessions {
+    protocol_version: u32,
+    min_protocol_version: u32,
+    accept_incoming: bool,
+    max_sessions: Option<usize>,
+    keep_alive_interval: Duration,
+    timeout: Duration,
+    delivery: DeliveryRequirement,
+    sessions: HashMap<SocketAddr, Session>,
+    outgoing: Vec<(SocketAddr, SessionPacket)>,
+    events: Vec<SessionEvent>,
+}
+
+impl Sessions {
+    /// Creates the sessions of a peer speaking the given protocol version. Incoming sessions
+    /// aren't accepted until `set_accept_incoming` is called, e.g. by servers.
+    pub fn new(protocol_version: u32) -> Self {
+        Self {
+            protocol_version,
+            min_protocol_version: protocol_version,
+            accept_incoming: false,
+            max_sessions: None,
+            keep_alive_interval: Duration::from_secs(1),
+            timeout: Duration::from_secs(5),
+            delivery: DeliveryRequirement::Default,
+            sessions: HashMap::new(),
+            outgoing: Vec::new(),
+            events: Vec::new(),
+        }
+    }
+
+    /// Returns the protocol version of this peer.
+    pub fn protocol_version(&self) -> u32 {
+        self.protocol_version
+    }
+
+    /// Accepts clients speaking any protocol version from the given one up to the own one.
+    pub fn set_min_protocol_version(&mut self, version: u32) {
+        self.min_protocol_version = version;
+    }
+
+    /// Sets whether sessions requested by other peers are accepted.
+    pub fn set_accept_incoming(&mut self, accept: bool) {
+        self.accept_incoming = accept;
+    }
+
+    /// Limits the number of established sessions, further requests are rejected.
+    pub fn set_max_sessions(&mut self, max: Option<usize>) {
+        self.max_sessions = max;
+    }
+
+    /// Sets the interval at which connection requests and keep-alives are sent.
+    pub fn set_keep_alive_interval(&mut self, interval: Duration) {
+        self.keep_alive_interval = interval;
+    }
+
+    /// Sets how long a peer may not be heard from before its session ends.
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    /// Sets the delivery of session packets, which has to be supported by the transport.
+    pub fn set_delivery(&mut self, delivery: DeliveryRequirement) {
+        self.delivery = delivery;
+    }
+
+    /// Requests a session with the given server.
+    pub fn connect(&mut self, addr: SocketAddr) {
+        self.connect_at(addr, Instant::now());
+    }
+
+    /// Ends the session with the given peer.
+    pub fn disconnect(&mut self, addr: SocketAddr) {
+        self.end(addr, DisconnectReason::Requested);
+    }
+
+    /// Ends the session with the given peer, telling it why.
+    pub fn kick(&mut self, addr: SocketAddr, reason: impl Into<String>) {
+        self.end(addr, DisconnectReason::Kicked(reason.into()));
+    }
+
+    /// Returns true if a session with the given peer is established.
+    pub fn is_connected(&self, addr: SocketAddr) -> bool {
+        self.sessions
+            .get(&addr)
+            .map_or(false, |session| session.connected)
+    }
+
+    /// Returns the peers a session is established with.
+    pub fn connected(&self) -> impl Iterator<Item = &SocketAddr> {
+        self.sessions
+            .iter()
+            .filter(|(_, session)| session.connected)
+            .map(|(addr, _)| addr)
+    }
+
+    /// Returns the protocol version of the given peer, once a session is established.
+    pub fn remote_version(&self, addr: SocketAddr) -> Option<u32> {
+        self.sessions
+            .get(&addr)
+            .and_then(|session| session.remote_version)
+    }
+
+    fn connect_at(&mut self, addr: SocketAddr, now: Instant) {
+        if self.sessions.contains_key(&addr) {
+            return;
+        }
+        self.sessions.insert(addr, Session::new(false, now));
+        self.outgoing.push((
+            addr,
+            SessionPacket::Connect {
+                version: self.protocol_version,
+            },
+        ));
+    }
+
+    fn end(&mut self, addr: SocketAddr, reason: DisconnectReason) {
+        if self.sessions.remove(&addr).is_some() {
+            self.outgoing.push((
+                addr,
+                SessionPacket::Disconnect {
+                    reason: reason.clone(),
+                },
+            ));
+            self.events.push(SessionEvent::Disconnected(addr, reason));
+        }
+    }
+
+    /// Handles a message received from a peer. Any message keeps an established session alive.
+    fn receive(&mut self, addr: SocketAddr, payload: &[u8], now: Instant) {
+        let packet = match SessionPacket::decode(payload) {
+            Some(packet) => packet,
+            None => {
+                if let Some(session) = self.sessions.get_mut(&addr) {
+                    if session.connected {
+                        session.last_received = now;
+                    }
+                }
+                return;
+            }
+        };
+        match packet {
+            SessionPacket::Connect { version } => self.accept(addr, version, now),
+            SessionPacket::Accept { version } => {
+                if let Some(session) = self.sessions.get_mut(&addr) {
+                    session.last_received = now;
+                    if !session.connected {
+                        session.connected = true;
+                        session.remote_version = Some(version);
+                        self.events.push(SessionEvent::Connected(addr));
+                    }
+                }
+            }
+            SessionPacket::Reject { reason } => {
+                if self.sessions.get(&addr).map_or(false, |s| !s.connected) {
+                    self.sessions.remove(&addr);
+                    self.events.push(SessionEvent::Disconnected(addr, reason));
+                }
+            }
+            SessionPacket::KeepAlive => {
+                if let Some(session) = self.sessions.get_mut(&addr) {
+                    session.last_received = now;
+                }
+            }
+            SessionPacket::Disconnect { reason } => {
+                if self.sessions.remove(&addr).is_some() {
+                    self.events.push(SessionEvent::Disconnected(addr, reason));
+                }
+            }
+        }
+    }
+
+    fn accept(&mut self, addr: SocketAddr, version: u32, now: Instant) {
+        let accept = SessionPacket::Accept {
+            version: self.protocol_version,
+        };
+        if let Some(session) = self.sessions.get_mut(&addr) {
+            if session.connected {
+                // The client didn't receive the previous answer yet.
+                session.last_received = now;
+                self.outgoing.push((addr, accept));
+                return;
+            }
+        }
+
+        let connected = self.sessions.values().filter(|s| s.connected).count();
+        let rejection = if !self.accept_incoming {
+            Some(DisconnectReason::Refused)
+        } else if version < self.min_protocol_version || version > self.protocol_version {
+            Some(DisconnectReason::VersionMismatch {
+                server: self.protocol_version,
+                client: version,
+            })
+        } else if self.max_sessions.map_or(false, |max| connected >= max) {
+            Some(DisconnectReason::ServerFull)
+        } else {
+            None
+        };
+        match rejection {
+            Some(reason) => self.outgoing.push((addr, SessionPacket::Reject { reason })),
+            None => {
+                let mut session = Session::new(true, now);
+                session.remote_version = Some(version);
+                self.sessions.insert(addr, session);
+                self.outgoing.push((addr, accept));
+                self.events.push(SessionEvent::Connected(addr));
+            }
+        }
+    }
+
+    /// Ends the session with a peer whose connection the transport reported as lost.
+    fn lost(&mut self, addr: SocketAddr) {
+        if self.sessions.remove(&addr).is_some() {
+            self.events.push(SessionEvent::Disconnected(
+                addr,
+                DisconnectReason::ConnectionLost,
+            ));
+        }
+    }
+
+    /// Sends connection requests and keep-alives, and ends the sessions which timed out.
+    fn tick(&mut self, now: Instant) {
+        let mut timed_out = Vec::new();
+        for (addr, session) in self.sessions.iter_mut() {
+            if now.duration_since(session.last_received) >= self.timeout {
+                timed_out.push(*addr);
+            } else if now.duration_since(session.last_sent) >= self.keep_alive_interval {
+                let packet = if session.connected {
+                    SessionPacket::KeepAlive
+                } else {
+                    SessionPacket::Connect {
+                        version: self.protocol_version,
+                    }
+                };
+                self.outgoing.push((*addr, packet));
+                session.last_sent = now;
+            }
+        }
+        for addr in timed_out {
+            self.sessions.remove(&addr);
+            self.events
+                .push(SessionEvent::Disconnected(addr, DisconnectReason::TimedOut));
+        }
+    }
+}
+
+/// Adds the `Sessions` resource and the `SessionSystem` maintaining it.
+///
+/// Add this bundle after the network bundle of the transport.
+#[derive(Debug)]
+pub struct SessionBundle {
+    sessions: Option<Sessions>,
+}
+
+impl SessionBundle {
+    /// Creates the bundle with the given sessions.
+    pub fn new(sessions: Sessions) -> Self {
+        Self {
+            sessions: Some(sessions),
+        }
+    }
+}
+
+impl SystemBundle for SessionBundle {
+    fn load(
+        &mut self,
+        _world: &mut World,
+        resources: &mut Resources,
+        builder: &mut DispatcherBuilder,
+    ) -> Result<(), Error> {
+        let reader = resources
+            .get_mut_or_default::<EventChannel<NetworkSimulationEvent>>()
+            .register_reader();
+        resources.get_or_default::<TransportResource>();
+        resources.get_or_default::<EventChannel<SessionEvent>>();
+        if let Some(sessions) = self.sessions.take() {
+            resources.insert(sessions);
+        }
+
+        builder.add_system(SessionSystem::new(reader));
+        Ok(())
+    }
+}
+
+/// Performs the handshakes, keep-alives and timeouts of the `Sessions`, and writes
+/// `SessionEvent`s.
+#[derive(Debug)]
+pub struct SessionSystem {
+    reader: ReaderId<NetworkSimulationEvent>,
+}
+
+impl SessionSystem {
+    /// Creates the system reading the given network events.
+    pub fn new(reader: ReaderId<NetworkSimulationEvent>) -> Self {
+        Self { reader }
+    }
+}
+
+impl System for SessionSystem {
+    fn build(mut self) -> Box<dyn ParallelRunnable> {
+        Box::new(
+            SystemBuilder::new("SessionSystem")
+                .read_resource::<EventChannel<NetworkSimulationEvent>>()
+                .write_resource::<Sessions>()
+                .write_resource::<TransportResource>()
+                .write_resource::<EventChannel<SessionEvent>>()
+                .build(
+                    move |_commands, _world, (network_events, sessions, transport, events), _| {
+                        #[cfg(feature = "profiler")]
+                        profile_scope!("session_system");
+
+                        let now = Instant::now();
+                        for event in network_events.read(&mut self.reader) {
+                            match event {
+                                NetworkSimulationEvent::Message(addr, payload) => {
+                                    sessions.receive(*addr, payload, now);
+                                }
+                                NetworkSimulationEvent::Disconnect(addr) => sessions.lost(*addr),
+                                _ => {}
+                            }
+                        }
+                        sessions.tick(now);
+
+                        let delivery = sessions.delivery;
+                        for (addr, packet) in sessions.outgoing.drain(..) {
+                            transport.send_with_requirements(
+                                addr,
+                                &packet.encode(),
+                                delivery,
+                                UrgencyRequirement::OnTick,
+                            );
+                        }
+                        events.iter_write(sessions.events.drain(..));
+                    },
+                ),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addr(port: u16) -> SocketAddr {
+        SocketAddr::from(([127, 0, 0, 1], port))
+    }
+
+    /// Delivers the packets sent by one peer to the other.
+    fn deliver(from: &mut Sessions, from_addr: SocketAddr, to: &mut Sessions, now: Instant) {
+        for (_, packet) in from.outgoing.drain(..) {
+            to.receive(from_addr, &packet.encode(), now);
+        }
+    }
+
+    #[test]
+    fn test_handshake_and_disconnect() {
+        let now = Instant::now();
+        let mut server = Sessions::new(2);
+        server.set_accept_incoming(true);
+        let mut client = Sessions::new(2);
+
+        client.connect_at(addr(1), now);
+        deliver(&mut client, addr(2), &mut server, now);
+        deliver(&mut server, addr(1), &mut client, now);
+
+        assert!(server.is_connected(addr(2)));
+        assert!(client.is_connected(addr(1)));
+        assert_eq!(client.remote_version(addr(1)), Some(2));
+        assert_eq!(client.events, vec![SessionEvent::Connected(addr(1))]);
+
+        client.disconnect(addr(1));
+        deliver(&mut client, addr(2), &mut server, now);
+        assert_eq!(
+            server.events.last(),
+            Some(&SessionEvent::Disconnected(
+                addr(2),
+                DisconnectReason::Requested
+            ))
+        );
+        assert!(!server.is_connected(addr(2)));
+    }
+
+    #[test]
+    fn test_version_mismatch_is_rejected() {
+        let now = Instant::now();
+        let mut server = Sessions::new(3);
+        server.set_accept_incoming(true);
+        server.set_min_protocol_version(2);
+        let mut client = Sessions::new(1);
+
+        client.connect_at(addr(1), now);
+        deliver(&mut client, addr(2), &mut server, now);
+        deliver(&mut server, addr(1), &mut client, now);
+
+        assert!(server.events.is_empty());
+        assert_eq!(
+            client.events,
+            vec![SessionEvent::Disconnected(
+                addr(1),
+                DisconnectReason::VersionMismatch {
+                    server: 3,
+                    client: 1
+                }
+            )]
+        );
+    }
+
+    #[test]
+    fn test_idle_sessions_keep_alive_until_timeout() {
+        let now = Instant::now();
+        let mut server = Sessions::new(1);
+        server.set_accept_incoming(true);
+        server.receive(
+            addr(2),
+            &SessionPacket::Connect { version: 1 }.encode(),
+            now,
+        );
+        server.outgoing.clear();
+
+        server.tick(now + Duration::from_secs(1));
+        assert_eq!(server.outgoing, vec![(addr(2), SessionPacket::KeepAlive)]);
+        assert!(server.is_connected(addr(2)));
+
+        server.tick(now + Duration::from_secs(5));
+        assert!(!server.is_connected(addr(2)));
+        assert_eq!(
+            server.events.last(),
+            Some(&SessionEvent::Disconnected(
+                addr(2),
+                DisconnectReason::TimedOut
+            ))
+        );
+    }
+}
@@ -2,9 +2,15 @@
 //! protocols. One important thing to note if you're implementing your own, the underlying sockets
 //! MUST be non-blocking in order to play nicely with the ECS scheduler.
 
+#[cfg(feature = "encryption")]
+mod encryption;
 pub mod laminar;
 pub mod tcp;
 pub mod udp;
+#[cfg(all(feature = "webrtc", target_arch = "wasm32"))]
+pub mod webrtc;
+#[cfg(feature = "websocket")]
+pub mod websocket;
 
 use std::{collections::VecDeque, net::SocketAddr};
 
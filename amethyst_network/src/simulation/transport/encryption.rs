@@ -0,0 +1,329 @@
+//! Encryption and authentication of packets with a pre-shared key.
+
+use std::{collections::HashMap, convert::TryInto};
+
+use chacha20poly1305::{
+    aead::{Aead, NewAead, Payload},
+    Key, XChaCha20Poly1305, XNonce,
+};
+use rand::{rngs::OsRng, RngCore};
+
+/// Size of the header in front of every sealed packet, holding the role and id of the sender.
+const HEADER_SIZE: usize = 9;
+
+/// Size of the random nonce following the header.
+const NONCE_SIZE: usize = 24;
+
+/// Size of the packet counter in front of every encrypted payload.
+const COUNTER_SIZE: usize = 8;
+
+/// Number of packet counters below the highest one received from a sender which are remembered.
+const WINDOW_SIZE: u64 = 64;
+
+/// Most senders whose received packets are remembered at once.
+const MAX_SENDERS: usize = 1024;
+
+/// The side of the connection a `PacketCipher` seals packets for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CipherRole {
+    /// The server, opening the packets of the clients
+    Server,
+    /// A client, opening the packets of the server
+    Client,
+}
+
+impl CipherRole {
+    fn to_byte(self) -> u8 {
+        match self {
+            CipherRole::Server => 0,
+            CipherRole::Client => 1,
+        }
+    }
+}
+
+/// Encrypts and authenticates packets with XChaCha20-Poly1305 and a key shared by all peers.
+///
+/// Every packet carries the role of its sender and a random id chosen by each new cipher, both
+/// authenticated as associated data. Packets which were tampered with, sent with another key,
+/// sent by a peer with the same role, such as a server packet reflected back to the server, or
+/// replayed, even from another address, are rejected.
+///
+/// The packets received from up to 1024 sender ids are remembered, forgetting the sender heard
+/// from least recently first, or a sender passed to `forget` once its peer disconnected. A
+/// sender which isn't remembered is only accepted again with one of its first 64 packets, so a
+/// peer reconnecting has to use a new cipher, which picks a new id. Anyone knowing the key can
+/// still send packets in the name of any other peer, so every game server should have its own
+/// key, handed out to its clients e.g. by a trusted lobby service.
+pub struct PacketCipher {
+    cipher: XChaCha20Poly1305,
+    role: CipherRole,
+    id: u64,
+    counter: u64,
+    windows: HashMap<u64, ReplayWindow>,
+    // Incremented with every accepted packet, to find the sender heard from least recently
+    tick: u64,
+}
+
+impl PacketCipher {
+    /// Creates a cipher with the given 256 bit key, sealing packets as the server.
+    pub fn server(key: [u8; 32]) -> Self {
+        Self::new(key, CipherRole::Server)
+    }
+
+    /// Creates a cipher with the given 256 bit key, sealing packets as a client.
+    pub fn client(key: [u8; 32]) -> Self {
+        Self::new(key, CipherRole::Client)
+    }
+
+    /// Creates a cipher with the given 256 bit key, sealing packets with the given role.
+    pub fn new(key: [u8; 32], role: CipherRole) -> Self {
+        Self {
+            cipher: XChaCha20Poly1305::new(Key::from_slice(&key)),
+            role,
+            id: OsRng.next_u64(),
+            counter: 0,
+            windows: HashMap::new(),
+            tick: 0,
+        }
+    }
+
+    /// The role packets are sealed with.
+    pub fn role(&self) -> CipherRole {
+        self.role
+    }
+
+    /// The random id packets are sealed with, authenticating their sender.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Returns the id of the sender of a sealed packet, without checking that it's authentic.
+    pub fn sender_id(sealed: &[u8]) -> Option<u64> {
+        sealed
+            .get(1..HEADER_SIZE)
+            .and_then(|id| id.try_into().ok())
+            .map(u64::from_le_bytes)
+    }
+
+    /// Forgets the packets received from the sender with the given id, e.g. once its peer
+    /// disconnected. Its old packets are still rejected, unless they are among its first 64.
+    pub fn forget(&mut self, sender: u64) {
+        self.windows.remove(&sender);
+    }
+
+    /// Returns a new random key.
+    pub fn generate_key() -> [u8; 32] {
+        let mut key = [0; 32];
+        OsRng.fill_bytes(&mut key);
+        key
+    }
+
+    /// Encrypts a payload to send.
+    ///
+    /// ## Panics
+    /// This will panic after 2^64 - 1 packets, as the counter would repeat.
+    pub fn seal(&mut self, payload: &[u8]) -> Vec<u8> {
+        self.counter = self
+            .counter
+            .checked_add(1)
+            .expect("Packet counter exhausted, create a new cipher");
+        let mut plaintext = Vec::with_capacity(COUNTER_SIZE + payload.len());
+        plaintext.extend_from_slice(&self.counter.to_le_bytes());
+        plaintext.extend_from_slice(payload);
+
+        let mut sealed = Vec::with_capacity(HEADER_SIZE + NONCE_SIZE + plaintext.len() + 16);
+        sealed.push(self.role.to_byte());
+        sealed.extend_from_slice(&self.id.to_le_bytes());
+        let mut nonce = [0; NONCE_SIZE];
+        OsRng.fill_bytes(&mut nonce);
+        let ciphertext = self
+            .cipher
+            .encrypt(
+                XNonce::from_slice(&nonce),
+                Payload {
+                    msg: &plaintext,
+                    aad: &sealed,
+                },
+            )
+            .expect("Encrypting packet");
+        sealed.extend_from_slice(&nonce);
+        sealed.extend(ciphertext);
+        sealed
+    }
+
+    /// Decrypts a received payload, returning `None` if it isn't authentic, was sent with the
+    /// role of this cipher or was received before.
+    pub fn open(&mut self, sealed: &[u8]) -> Option<Vec<u8>> {
+        if sealed.len() < HEADER_SIZE + NONCE_SIZE {
+            return None;
+        }
+        let (header, rest) = sealed.split_at(HEADER_SIZE);
+        if header[0] == self.role.to_byte() {
+            return None;
+        }
+        let sender = u64::from_le_bytes(header[1..].try_into().ok()?);
+        let (nonce, ciphertext) = rest.split_at(NONCE_SIZE);
+        let mut plaintext = self
+            .cipher
+            .decrypt(
+                XNonce::from_slice(nonce),
+                Payload {
+                    msg: ciphertext,
+                    aad: header,
+                },
+            )
+            .ok()?;
+        if plaintext.len() < COUNTER_SIZE {
+            return None;
+        }
+        let counter = u64::from_le_bytes(plaintext[..COUNTER_SIZE].try_into().ok()?);
+        if !self.accept(sender, counter) {
+            return None;
+        }
+        plaintext.drain(..COUNTER_SIZE);
+        Some(plaintext)
+    }
+
+    /// Returns true if the counter wasn't received from the sender before.
+    fn accept(&mut self, sender: u64, counter: u64) -> bool {
+        self.tick += 1;
+        if let Some(window) = self.windows.get_mut(&sender) {
+            window.last_used = self.tick;
+            return window.accept(counter);
+        }
+        // the later packets of a forgotten sender could be replays
+        if counter > WINDOW_SIZE {
+            return false;
+        }
+        if self.windows.len() >= MAX_SENDERS {
+            let oldest = self
+                .windows
+                .iter()
+                .min_by_key(|(_, window)| window.last_used)
+                .map(|(sender, _)| *sender);
+            if let Some(oldest) = oldest {
+                self.windows.remove(&oldest);
+            }
+        }
+        let mut window = ReplayWindow {
+            last_used: self.tick,
+            ..ReplayWindow::default()
+        };
+        let accepted = window.accept(counter);
+        self.windows.insert(sender, window);
+        accepted
+    }
+}
+
+/// The packet counters received recently from a peer.
+#[derive(Debug, Default)]
+struct ReplayWindow {
+    highest: u64,
+    // Bit `i` is set if `highest - i` was received
+    received: u64,
+    last_used: u64,
+}
+
+impl ReplayWindow {
+    /// Returns true if the counter wasn't received before and isn't too old to tell.
+    fn accept(&mut self, counter: u64) -> bool {
+        if counter > self.highest {
+            let shift = counter - self.highest;
+            self.received = if shift >= WINDOW_SIZE {
+                0
+            } else {
+                self.received << shift
+            };
+            self.received |= 1;
+            self.highest = counter;
+            return true;
+        }
+        let offset = self.highest - counter;
+        if offset >= WINDOW_SIZE || self.received & (1 << offset) != 0 {
+            return false;
+        }
+        self.received |= 1 << offset;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sealed_packets_are_opened_once() {
+        let key = PacketCipher::generate_key();
+        let (mut client, mut server) = (PacketCipher::client(key), PacketCipher::server(key));
+
+        let first = client.seal(b"hello");
+        let second = client.seal(b"world");
+        assert_eq!(server.open(&second), Some(b"world".to_vec()));
+        assert_eq!(server.open(&first), Some(b"hello".to_vec()));
+        assert_eq!(server.open(&first), None);
+
+        // a reconnecting client has a new id, while the old packets stay rejected
+        let mut client = PacketCipher::client(key);
+        assert_eq!(server.open(&client.seal(b"again")), Some(b"again".to_vec()));
+        assert_eq!(server.open(&first), None);
+    }
+
+    #[test]
+    fn test_reflected_packets_are_rejected() {
+        let key = PacketCipher::generate_key();
+        let (mut client, mut server) = (PacketCipher::client(key), PacketCipher::server(key));
+
+        let sealed = server.seal(b"hello");
+        assert_eq!(server.open(&sealed), None);
+        assert_eq!(client.open(&sealed), Some(b"hello".to_vec()));
+        let sealed = client.seal(b"hello");
+        assert_eq!(client.open(&sealed), None);
+    }
+
+    #[test]
+    fn test_senders_are_forgotten() {
+        let key = PacketCipher::generate_key();
+        let mut server = PacketCipher::server(key);
+
+        let mut client = PacketCipher::client(key);
+        let first = client.seal(b"hello");
+        assert_eq!(PacketCipher::sender_id(&first), Some(client.id()));
+        assert_eq!(server.open(&first), Some(b"hello".to_vec()));
+        let late: Vec<_> = (0..WINDOW_SIZE).map(|_| client.seal(b"late")).collect();
+        assert_eq!(server.open(&late[63]), Some(b"late".to_vec()));
+
+        // only the first packets of a forgotten sender are accepted again
+        server.forget(client.id());
+        assert_eq!(server.open(&late[63]), None);
+        assert_eq!(server.open(&first), Some(b"hello".to_vec()));
+
+        // the sender heard from least recently is forgotten to make room for new ones
+        for _ in 0..MAX_SENDERS {
+            let sealed = PacketCipher::client(key).seal(b"new");
+            assert_eq!(server.open(&sealed), Some(b"new".to_vec()));
+        }
+        assert_eq!(server.windows.len(), MAX_SENDERS);
+        assert!(!server.windows.contains_key(&client.id()));
+    }
+
+    #[test]
+    fn test_forged_packets_are_rejected() {
+        let mut sender = PacketCipher::client(PacketCipher::generate_key());
+        let mut receiver = PacketCipher::server(PacketCipher::generate_key());
+
+        let sealed = sender.seal(b"hello");
+        assert_eq!(receiver.open(&sealed), None);
+
+        let mut receiver = PacketCipher::server([7; 32]);
+        let mut sealed = PacketCipher::client([7; 32]).seal(b"hello");
+        let last = sealed.len() - 1;
+        sealed[last] ^= 1;
+        assert_eq!(receiver.open(&sealed), None);
+        assert_eq!(receiver.open(b"short"), None);
+
+        // the role and id are authenticated
+        let mut sealed = PacketCipher::client([7; 32]).seal(b"hello");
+        sealed[1] ^= 1;
+        assert_eq!(receiver.open(&sealed), None);
+    }
+}
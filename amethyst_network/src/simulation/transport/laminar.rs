@@ -1,14 +1,21 @@
 //! Network systems implementation backed by the Laminar network protocol.
+//!
+//! With the `encryption` feature, payloads can be encrypted and authenticated with a
+//! `PacketCipher`. The headers laminar adds to them, such as acknowledgements, aren't.
 
-use std::time::Instant;
+#[cfg(feature = "encryption")]
+use std::collections::HashMap;
+use std::{net::SocketAddr, time::Instant};
 
 use amethyst_core::{ecs::*, EventChannel};
 use amethyst_error::Error;
 use bytes::Bytes;
 pub use laminar::{Config as LaminarConfig, ErrorKind, Socket as LaminarSocket};
 use laminar::{Packet, SocketEvent};
-use log::error;
+use log::{error, warn};
 
+#[cfg(feature = "encryption")]
+pub use crate::simulation::transport::encryption::{CipherRole, PacketCipher};
 use crate::simulation::{
     events::NetworkSimulationEvent,
     requirements::DeliveryRequirement,
@@ -19,11 +26,26 @@ use crate::simulation::{
 /// Use this network bundle to add the laminar transport layer to your game.
 pub struct LaminarNetworkBundle {
     socket: Option<LaminarSocket>,
+    #[cfg(feature = "encryption")]
+    cipher: Option<PacketCipher>,
 }
 
 impl LaminarNetworkBundle {
     pub fn new(socket: Option<LaminarSocket>) -> Self {
-        Self { socket }
+        Self {
+            socket,
+            #[cfg(feature = "encryption")]
+            cipher: None,
+        }
+    }
+
+    /// Encrypts and authenticates all payloads with the given cipher. Every peer has to use the
+    /// same key, the server with `PacketCipher::server` and the clients with
+    /// `PacketCipher::client`.
+    #[cfg(feature = "encryption")]
+    pub fn with_cipher(mut self, cipher: PacketCipher) -> Self {
+        self.cipher = Some(cipher);
+        self
     }
 }
 
@@ -34,7 +56,13 @@ impl SystemBundle for LaminarNetworkBundle {
         resources: &mut Resources,
         builder: &mut DispatcherBuilder,
     ) -> Result<(), Error> {
-        resources.insert(LaminarSocketResource::new(self.socket.take()));
+        resources.insert(LaminarSocketResource {
+            socket: self.socket.take(),
+            #[cfg(feature = "encryption")]
+            cipher: self.cipher.take(),
+            #[cfg(feature = "encryption")]
+            senders: HashMap::new(),
+        });
 
         builder
             .add_system(NetworkSimulationTimeSystem)
@@ -59,55 +87,50 @@ impl System for LaminarNetworkSendSystem {
                 .write_resource::<EventChannel<NetworkSimulationEvent>>()
                 .build(
                     move |_commands, _world, (transport, socket, sim_time, event_channel), _| {
-                        if let Some(socket) = socket.get_mut() {
+                        if socket.get().is_some() {
                             let messages = transport
                                 .drain_messages_to_send(|_| sim_time.should_send_message_now());
 
                             for message in messages {
+                                let payload = socket.seal(&message.payload);
                                 let packet = match message.delivery {
                                     DeliveryRequirement::Unreliable => {
-                                        Packet::unreliable(
-                                            message.destination,
-                                            message.payload.to_vec(),
-                                        )
+                                        Packet::unreliable(message.destination, payload)
                                     }
                                     DeliveryRequirement::UnreliableSequenced(stream_id) => {
                                         Packet::unreliable_sequenced(
                                             message.destination,
-                                            message.payload.to_vec(),
+                                            payload,
                                             stream_id,
                                         )
                                     }
                                     DeliveryRequirement::Reliable => {
-                                        Packet::reliable_unordered(
-                                            message.destination,
-                                            message.payload.to_vec(),
-                                        )
+                                        Packet::reliable_unordered(message.destination, payload)
                                     }
                                     DeliveryRequirement::ReliableSequenced(stream_id) => {
                                         Packet::reliable_sequenced(
                                             message.destination,
-                                            message.payload.to_vec(),
+                                            payload,
                                             stream_id,
                                         )
                                     }
                                     DeliveryRequirement::ReliableOrdered(stream_id) => {
                                         Packet::reliable_ordered(
                                             message.destination,
-                                            message.payload.to_vec(),
+                                            payload,
                                             stream_id,
                                         )
                                     }
                                     DeliveryRequirement::Default => {
-                                        Packet::reliable_ordered(
-                                            message.destination,
-                                            message.payload.to_vec(),
-                                            None,
-                                        )
+                                        Packet::reliable_ordered(message.destination, payload, None)
                                     }
                                 };
 
-                                match socket.send(packet) {
+                                let result = match socket.get_mut() {
+                                    Some(socket) => socket.send(packet),
+                                    None => return,
+                                };
+                                match result {
                                     Err(ErrorKind::IOError(e)) => {
                                         event_channel.single_write(
                                             NetworkSimulationEvent::SendError(e, message),
@@ -153,25 +176,29 @@ impl System for LaminarNetworkRecvSystem {
                 .write_resource::<LaminarSocketResource>()
                 .write_resource::<EventChannel<NetworkSimulationEvent>>()
                 .build(move |_commands, _world, (socket, event_channel), _| {
-                    if let Some(socket) = socket.get_mut() {
-                        while let Some(event) = socket.recv() {
-                            let event = match event {
-                                SocketEvent::Packet(packet) => {
-                                    NetworkSimulationEvent::Message(
-                                        packet.addr(),
-                                        Bytes::copy_from_slice(packet.payload()),
-                                    )
-                                }
-                                SocketEvent::Disconnect(addr) => {
-                                    NetworkSimulationEvent::Disconnect(addr)
-                                }
-                                SocketEvent::Connect(addr) => NetworkSimulationEvent::Connect(addr),
-                                SocketEvent::Timeout(addr) => {
-                                    NetworkSimulationEvent::Disconnect(addr)
+                    while let Some(event) = socket.get_mut().and_then(LaminarSocket::recv) {
+                        let event = match event {
+                            SocketEvent::Packet(packet) => {
+                                match socket.open(packet.addr(), packet.payload()) {
+                                    Some(payload) => {
+                                        NetworkSimulationEvent::Message(packet.addr(), payload)
+                                    }
+                                    None => {
+                                        warn!(
+                                            "Dropped a packet from {} which isn't authentic",
+                                            packet.addr()
+                                        );
+                                        continue;
+                                    }
                                 }
-                            };
-                            event_channel.single_write(event);
-                        }
+                            }
+                            SocketEvent::Disconnect(addr) | SocketEvent::Timeout(addr) => {
+                                socket.forget(addr);
+                                NetworkSimulationEvent::Disconnect(addr)
+                            }
+                            SocketEvent::Connect(addr) => NetworkSimulationEvent::Connect(addr),
+                        };
+                        event_channel.single_write(event);
                     }
                 }),
         )
@@ -181,18 +208,29 @@ impl System for LaminarNetworkRecvSystem {
 /// Resource that owns the Laminar socket.
 pub struct LaminarSocketResource {
     socket: Option<LaminarSocket>,
+    #[cfg(feature = "encryption")]
+    cipher: Option<PacketCipher>,
+    // The id each peer seals its packets with, to forget them once the peer disconnects
+    #[cfg(feature = "encryption")]
+    senders: HashMap<SocketAddr, u64>,
 }
 
 impl Default for LaminarSocketResource {
     fn default() -> Self {
-        Self { socket: None }
+        Self::new(None)
     }
 }
 
 impl LaminarSocketResource {
     /// Creates a new instance of the `UdpSocketResource`.
     pub fn new(socket: Option<LaminarSocket>) -> Self {
-        Self { socket }
+        Self {
+            socket,
+            #[cfg(feature = "encryption")]
+            cipher: None,
+            #[cfg(feature = "encryption")]
+            senders: HashMap::new(),
+        }
     }
 
     /// Returns a reference to the socket if there is one configured.
@@ -214,4 +252,50 @@ impl LaminarSocketResource {
     pub fn drop_socket(&mut self) {
         self.socket = None;
     }
+
+    /// Sets the cipher encrypting and authenticating payloads, or sends them in plain text if
+    /// it's `None`.
+    #[cfg(feature = "encryption")]
+    pub fn set_cipher(&mut self, cipher: Option<PacketCipher>) {
+        self.cipher = cipher;
+        self.senders.clear();
+    }
+
+    /// Encrypts a payload to send, if there is a cipher.
+    fn seal(&mut self, payload: &[u8]) -> Vec<u8> {
+        #[cfg(feature = "encryption")]
+        {
+            if let Some(cipher) = &mut self.cipher {
+                return cipher.seal(payload);
+            }
+        }
+        payload.to_vec()
+    }
+
+    /// Decrypts a received payload if there is a cipher, returning `None` if it isn't authentic.
+    #[cfg_attr(not(feature = "encryption"), allow(unused_variables))]
+    fn open(&mut self, addr: SocketAddr, payload: &[u8]) -> Option<Bytes> {
+        #[cfg(feature = "encryption")]
+        {
+            if let Some(cipher) = &mut self.cipher {
+                let opened = cipher.open(payload)?;
+                if let Some(sender) = PacketCipher::sender_id(payload) {
+                    self.senders.insert(addr, sender);
+                }
+                return Some(Bytes::from(opened));
+            }
+        }
+        Some(Bytes::copy_from_slice(payload))
+    }
+
+    /// Forgets the packets the cipher received from a peer which disconnected.
+    #[cfg_attr(not(feature = "encryption"), allow(unused_variables))]
+    fn forget(&mut self, addr: SocketAddr) {
+        #[cfg(feature = "encryption")]
+        {
+            if let (Some(cipher), Some(sender)) = (&mut self.cipher, self.senders.remove(&addr)) {
+                cipher.forget(sender);
+            }
+        }
+    }
 }
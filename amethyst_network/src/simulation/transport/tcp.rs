@@ -1,4 +1,9 @@
 //! Network systems implementation backed by the TCP network protocol.
+//!
+//! TCP suits turn-based games and lobby traffic, where every message has to arrive anyway and
+//! NATs or firewalls make UDP troublesome. Since TCP is a stream, every message is prefixed with
+//! its length in bytes as a big-endian `u32`, so it is received whole and separately from the
+//! messages sent before and after it.
 
 use std::{
     collections::HashMap,
@@ -20,6 +25,16 @@ use crate::simulation::{
     transport::TransportResource,
 };
 
+/// Size of the length prefix written before every message.
+const FRAME_HEADER_SIZE: usize = 4;
+
+/// Largest message accepted, longer frames are treated as a corrupted stream.
+const MAX_FRAME_SIZE: usize = 16 * 1024 * 1024;
+
+/// Most bytes kept for a stream which doesn't accept them, the peer is disconnected once more
+/// are queued for it.
+const MAX_PENDING_SIZE: usize = 2 * (FRAME_HEADER_SIZE + MAX_FRAME_SIZE);
+
 /// Use this network bundle to add the TCP transport layer to your game.
 pub struct TcpNetworkBundle {
     listener: Option<TcpListener>,
@@ -27,6 +42,8 @@ pub struct TcpNetworkBundle {
 }
 
 impl TcpNetworkBundle {
+    /// Creates a bundle accepting connections on the given listener, if any, and reading up to
+    /// `recv_buffer_size_bytes` from a stream at once.
     pub fn new(listener: Option<TcpListener>, recv_buffer_size_bytes: usize) -> Self {
         Self {
             listener,
@@ -94,12 +111,23 @@ impl System for TcpStreamManagementSystem {
                                 s.set_nonblocking(true).expect("Setting non-blocking mode");
                                 s.set_nodelay(true).expect("Setting nodelay");
                                 net.streams.insert(message.destination, (true, s));
+                                event_channel.single_write(NetworkSimulationEvent::Connect(
+                                    message.destination,
+                                ));
                             }
                         });
 
                         // Remove inactive connections
-                        net.streams.retain(|addr, (active, _)| {
+                        let TcpNetworkResource {
+                            streams,
+                            read_buffers,
+                            write_buffers,
+                            ..
+                        } = net.deref_mut();
+                        streams.retain(|addr, (active, _)| {
                             if !*active {
+                                read_buffers.remove(addr);
+                                write_buffers.remove(addr);
                                 event_channel
                                     .single_write(NetworkSimulationEvent::Disconnect(*addr));
                             }
@@ -184,6 +212,27 @@ impl System for TcpNetworkSendSystem {
                                 }
                             }
                         }
+
+                        // Write what the streams couldn't accept in previous frames
+                        let TcpNetworkResource {
+                            streams,
+                            write_buffers,
+                            ..
+                        } = net.deref_mut();
+                        for (addr, pending) in write_buffers.iter_mut() {
+                            if pending.is_empty() {
+                                continue;
+                            }
+                            if let Some((active, stream)) = streams.get_mut(addr) {
+                                if let Err(e) = flush(stream, pending) {
+                                    *active = false;
+                                    channel.single_write(NetworkSimulationEvent::ConnectionError(
+                                        e,
+                                        Some(*addr),
+                                    ));
+                                }
+                            }
+                        }
                     },
                 ),
         )
@@ -195,13 +244,97 @@ fn write_message(
     net: &mut TcpNetworkResource,
     channel: &mut EventChannel<NetworkSimulationEvent>,
 ) {
-    if let Some((_, stream)) = net.get_stream(message.destination) {
-        if let Err(e) = stream.write(&message.payload) {
-            channel.single_write(NetworkSimulationEvent::SendError(e, message));
+    if message.payload.len() > MAX_FRAME_SIZE {
+        let error = io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "message exceeds the maximum frame size",
+        );
+        channel.single_write(NetworkSimulationEvent::SendError(error, message));
+        return;
+    }
+    if let Some((active, stream)) = net.streams.get_mut(&message.destination) {
+        if !*active {
+            return;
+        }
+        let pending = net.write_buffers.entry(message.destination).or_default();
+        match queue_frame(stream, pending, &message.payload) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                // the peer doesn't read what is sent to it
+                *active = false;
+                pending.clear();
+                channel.single_write(NetworkSimulationEvent::ConnectionError(
+                    e,
+                    Some(message.destination),
+                ));
+            }
+            Err(e) => channel.single_write(NetworkSimulationEvent::SendError(e, message)),
         }
     }
 }
 
+/// Queues a message for a stream and writes as much as it accepts, failing with `WouldBlock`
+/// if more than `MAX_PENDING_SIZE` bytes are left.
+fn queue_frame(stream: &mut TcpStream, pending: &mut Vec<u8>, payload: &[u8]) -> io::Result<()> {
+    pending.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+    pending.extend_from_slice(payload);
+    flush(stream, pending)?;
+    if pending.len() > MAX_PENDING_SIZE {
+        return Err(io::Error::new(
+            io::ErrorKind::WouldBlock,
+            "too many bytes are waiting to be sent to the peer",
+        ));
+    }
+    Ok(())
+}
+
+/// Writes as many of the pending bytes as the stream accepts without blocking, and keeps the
+/// rest for the next frame.
+fn flush(stream: &mut TcpStream, pending: &mut Vec<u8>) -> io::Result<()> {
+    let mut written = 0;
+    let result = loop {
+        if written == pending.len() {
+            break Ok(());
+        }
+        match stream.write(&pending[written..]) {
+            Ok(0) => break Err(io::ErrorKind::WriteZero.into()),
+            Ok(len) => written += len,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => break Ok(()),
+            Err(e) => break Err(e),
+        }
+    };
+    pending.drain(..written);
+    result
+}
+
+/// Splits the complete messages off the start of the bytes received from a stream.
+fn split_frames(buffer: &mut Vec<u8>) -> io::Result<Vec<Bytes>> {
+    let mut frames = Vec::new();
+    let mut start = 0;
+    while buffer.len() - start >= FRAME_HEADER_SIZE {
+        let mut header = [0; FRAME_HEADER_SIZE];
+        header.copy_from_slice(&buffer[start..start + FRAME_HEADER_SIZE]);
+        let len = u32::from_be_bytes(header) as usize;
+        if len > MAX_FRAME_SIZE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "message exceeds the maximum frame size",
+            ));
+        }
+        let end = start + FRAME_HEADER_SIZE + len;
+        if buffer.len() < end {
+            break;
+        }
+        frames.push(Bytes::copy_from_slice(
+            &buffer[start + FRAME_HEADER_SIZE..end],
+        ));
+        start = end;
+    }
+    buffer.drain(..start);
+    Ok(frames)
+}
+
 /// System to receive messages from all open `TcpStream`s.
 pub struct TcpNetworkRecvSystem;
 
@@ -213,7 +346,7 @@ impl System for TcpNetworkRecvSystem {
                 .write_resource::<EventChannel<NetworkSimulationEvent>>()
                 .build(move |_commands, _world, (net, event_channel), _| {
                     let resource = net.deref_mut();
-                    for (_, (active, stream)) in resource.streams.iter_mut() {
+                    for (addr, (active, stream)) in resource.streams.iter_mut() {
                         // If we can't get a peer_addr, there is likely something pretty wrong with the
                         // connection so we'll mark it inactive.
                         let peer_addr = match stream.peer_addr() {
@@ -229,13 +362,28 @@ impl System for TcpNetworkRecvSystem {
                             match stream.read(&mut resource.recv_buffer) {
                                 Ok(recv_len) => {
                                     if recv_len > 0 {
-                                        let event = NetworkSimulationEvent::Message(
-                                            peer_addr,
-                                            Bytes::copy_from_slice(
-                                                &resource.recv_buffer[..recv_len],
-                                            ),
-                                        );
-                                        event_channel.single_write(event);
+                                        let received =
+                                            resource.read_buffers.entry(*addr).or_default();
+                                        received
+                                            .extend_from_slice(&resource.recv_buffer[..recv_len]);
+                                        match split_frames(received) {
+                                            Ok(frames) => {
+                                                for frame in frames {
+                                                    event_channel.single_write(
+                                                        NetworkSimulationEvent::Message(
+                                                            peer_addr, frame,
+                                                        ),
+                                                    );
+                                                }
+                                            }
+                                            Err(e) => {
+                                                event_channel.single_write(
+                                                    NetworkSimulationEvent::RecvError(e),
+                                                );
+                                                *active = false;
+                                                break;
+                                            }
+                                        }
                                     } else {
                                         *active = false;
                                         break;
@@ -262,19 +410,27 @@ impl System for TcpNetworkRecvSystem {
     }
 }
 
+/// Resource that owns the TCP listener and all open streams.
 #[derive(Default)]
 pub struct TcpNetworkResource {
     listener: Option<TcpListener>,
     streams: HashMap<SocketAddr, (bool, TcpStream)>,
     recv_buffer: Vec<u8>,
+    // Bytes received after the last complete message of every stream
+    read_buffers: HashMap<SocketAddr, Vec<u8>>,
+    // Bytes every stream couldn't accept yet without blocking
+    write_buffers: HashMap<SocketAddr, Vec<u8>>,
 }
 
 impl TcpNetworkResource {
+    /// Creates a new instance of the `TcpNetworkResource`.
     pub fn new(listener: Option<TcpListener>, recv_buffer_size_bytes: usize) -> Self {
         Self {
             listener,
             streams: HashMap::new(),
             recv_buffer: vec![0; recv_buffer_size_bytes],
+            read_buffers: HashMap::new(),
+            write_buffers: HashMap::new(),
         }
     }
 
@@ -306,6 +462,68 @@ impl TcpNetworkResource {
     /// Drops the stream with the given `SocketAddr`. This will be called when a peer seems to have
     /// been disconnected
     pub fn drop_stream(&mut self, addr: SocketAddr) -> Option<(bool, TcpStream)> {
+        self.read_buffers.remove(&addr);
+        self.write_buffers.remove(&addr);
         self.streams.remove(&addr)
     }
+
+    /// Returns the number of bytes queued for the stream with the given `SocketAddr` which it
+    /// couldn't accept yet.
+    pub fn pending_bytes(&self, addr: SocketAddr) -> usize {
+        self.write_buffers.get(&addr).map_or(0, Vec::len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(payload: &[u8]) -> Vec<u8> {
+        let mut bytes = (payload.len() as u32).to_be_bytes().to_vec();
+        bytes.extend_from_slice(payload);
+        bytes
+    }
+
+    #[test]
+    fn test_split_frames_across_reads() {
+        let mut stream = frame(b"hello");
+        stream.extend(frame(b""));
+        stream.extend(frame(b"world"));
+
+        let mut buffer = stream[..7].to_vec();
+        assert!(split_frames(&mut buffer).unwrap().is_empty());
+
+        buffer.extend_from_slice(&stream[7..]);
+        let frames = split_frames(&mut buffer).unwrap();
+        assert_eq!(frames, vec![&b"hello"[..], &b""[..], &b"world"[..]]);
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn test_pending_bytes_are_limited() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut stream = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        stream.set_nonblocking(true).unwrap();
+        // the peer never reads
+        let _peer = listener.accept().unwrap();
+
+        let payload = vec![0; MAX_FRAME_SIZE];
+        let mut pending = Vec::new();
+        let mut queued = 0;
+        let error = loop {
+            match queue_frame(&mut stream, &mut pending, &payload) {
+                Ok(()) => queued += 1,
+                Err(e) => break e,
+            }
+            assert!(queued < 64, "the socket accepted {} frames", queued);
+        };
+        assert_eq!(error.kind(), io::ErrorKind::WouldBlock);
+        assert!(pending.len() > MAX_PENDING_SIZE);
+    }
+
+    #[test]
+    fn test_split_frames_rejects_oversized_frames() {
+        let mut buffer = ((MAX_FRAME_SIZE + 1) as u32).to_be_bytes().to_vec();
+        assert!(split_frames(&mut buffer).is_err());
+    }
 }
@@ -1,23 +1,39 @@
 //! Network systems implementation backed by the UDP network protocol.
 
-use std::{io, net::UdpSocket};
+use std::{io, net::UdpSocket, time::Instant};
 
 use amethyst_core::{ecs::*, EventChannel};
 use amethyst_error::Error;
 use bytes::Bytes;
 
 use crate::simulation::{
+    channels::DeliveryChannels,
     events::NetworkSimulationEvent,
+    metrics::NetworkMetrics,
     requirements::DeliveryRequirement,
     timing::{NetworkSimulationTime, NetworkSimulationTimeSystem},
     transport::TransportResource,
 };
 
 /// Use this network bundle to add the UDP transport layer to your game.
+///
+/// Plain UDP only supports `DeliveryRequirement::Unreliable`. Use `with_delivery_channels` to
+/// support every `DeliveryRequirement`, at the cost of a small header on every datagram which
+/// the remote end has to understand, i.e. it has to use delivery channels as well.
 #[derive(new)]
 pub struct UdpNetworkBundle {
     socket: Option<UdpSocket>,
     recv_buffer_size_bytes: usize,
+    #[new(default)]
+    channels: Option<DeliveryChannels>,
+}
+
+impl UdpNetworkBundle {
+    /// Sends and receives all messages through the given delivery channels.
+    pub fn with_delivery_channels(mut self, channels: DeliveryChannels) -> Self {
+        self.channels = Some(channels);
+        self
+    }
 }
 
 impl SystemBundle for UdpNetworkBundle {
@@ -30,7 +46,9 @@ impl SystemBundle for UdpNetworkBundle {
         resources.insert(UdpSocketResource::new(
             self.socket.take(),
             self.recv_buffer_size_bytes,
+            self.channels.take(),
         ));
+        resources.get_or_default::<NetworkMetrics>();
 
         builder
             .add_system(NetworkSimulationTimeSystem)
@@ -54,7 +72,37 @@ impl System for UdpNetworkSendSystem {
                 .write_resource::<EventChannel<NetworkSimulationEvent>>()
                 .build(
                     move |_commands, _world, (transport, socket, sim_time, channel), _| {
-                        if let Some(socket) = socket.get_mut() {
+                        let UdpSocketResource {
+                            ref mut socket,
+                            ref mut channels,
+                            ..
+                        } = **socket;
+                        if let (Some(socket), Some(channels)) = (socket.as_mut(), channels.as_mut())
+                        {
+                            let now = Instant::now();
+                            let messages = transport
+                                .drain_messages_to_send(|_| sim_time.should_send_message_now());
+                            for message in messages {
+                                let datagram = channels.encode(&message, now);
+                                if let Err(e) = socket.send_to(&datagram, message.destination) {
+                                    channel.single_write(NetworkSimulationEvent::SendError(
+                                        e, message,
+                                    ));
+                                }
+                            }
+                            if sim_time.should_send_message_now() {
+                                for (addr, datagram) in channels.resends(now) {
+                                    if let Err(e) = socket.send_to(&datagram, addr) {
+                                        channel.single_write(
+                                            NetworkSimulationEvent::ConnectionError(e, Some(addr)),
+                                        );
+                                    }
+                                }
+                                for addr in channels.lost_peers() {
+                                    channel.single_write(NetworkSimulationEvent::Disconnect(addr));
+                                }
+                            }
+                        } else if let Some(socket) = socket.as_mut() {
                             let messages = transport
                                 .drain_messages_to_send(|_| sim_time.should_send_message_now());
                             for message in messages {
@@ -92,34 +140,62 @@ impl System for UdpNetworkReceiveSystem {
         Box::new(
             SystemBuilder::new("UdpNetworkReceiveSystem")
                 .write_resource::<UdpSocketResource>()
+                .write_resource::<NetworkMetrics>()
                 .write_resource::<EventChannel<NetworkSimulationEvent>>()
-                .build(move |_commands, _world, (socket, event_channel), _| {
-                    let UdpSocketResource {
-                        ref mut socket,
-                        ref mut recv_buffer,
-                    } = **socket;
-                    if let Some(socket) = socket {
-                        loop {
-                            match socket.recv_from(recv_buffer) {
-                                Ok((recv_len, address)) => {
-                                    let event = NetworkSimulationEvent::Message(
-                                        address,
-                                        Bytes::copy_from_slice(&recv_buffer[..recv_len]),
-                                    );
-                                    // TODO: Handle other types of events.
-                                    event_channel.single_write(event);
-                                }
-                                Err(e) => {
-                                    if e.kind() != io::ErrorKind::WouldBlock {
-                                        event_channel
-                                            .single_write(NetworkSimulationEvent::RecvError(e));
+                .build(
+                    move |_commands, _world, (socket, metrics, event_channel), _| {
+                        let UdpSocketResource {
+                            ref mut socket,
+                            ref mut recv_buffer,
+                            ref mut channels,
+                        } = **socket;
+                        let now = Instant::now();
+                        if let Some(socket) = socket {
+                            loop {
+                                match socket.recv_from(recv_buffer) {
+                                    Ok((recv_len, address)) => {
+                                        let datagram = &recv_buffer[..recv_len];
+                                        if let Some(channels) = channels {
+                                            match channels.decode(address, now, datagram) {
+                                                Ok(payloads) => {
+                                                    for payload in payloads {
+                                                        event_channel.single_write(
+                                                            NetworkSimulationEvent::Message(
+                                                                address, payload,
+                                                            ),
+                                                        );
+                                                    }
+                                                }
+                                                Err(e) => {
+                                                    event_channel.single_write(
+                                                        NetworkSimulationEvent::RecvError(e),
+                                                    );
+                                                }
+                                            }
+                                            continue;
+                                        }
+                                        let event = NetworkSimulationEvent::Message(
+                                            address,
+                                            Bytes::copy_from_slice(datagram),
+                                        );
+                                        // TODO: Handle other types of events.
+                                        event_channel.single_write(event);
+                                    }
+                                    Err(e) => {
+                                        if e.kind() != io::ErrorKind::WouldBlock {
+                                            event_channel
+                                                .single_write(NetworkSimulationEvent::RecvError(e));
+                                        }
+                                        break;
                                     }
-                                    break;
                                 }
                             }
                         }
-                    }
-                }),
+                        if let Some(channels) = channels {
+                            channels.report(metrics);
+                        }
+                    },
+                ),
         )
     }
 }
@@ -129,13 +205,19 @@ impl System for UdpNetworkReceiveSystem {
 pub struct UdpSocketResource {
     socket: Option<UdpSocket>,
     recv_buffer: Vec<u8>,
+    channels: Option<DeliveryChannels>,
 }
 
 impl UdpSocketResource {
-    fn new(socket: Option<UdpSocket>, recv_buffer_size_bytes: usize) -> Self {
+    fn new(
+        socket: Option<UdpSocket>,
+        recv_buffer_size_bytes: usize,
+        channels: Option<DeliveryChannels>,
+    ) -> Self {
         Self {
             socket,
             recv_buffer: vec![0; recv_buffer_size_bytes],
+            channels,
         }
     }
 }
@@ -160,4 +242,14 @@ impl UdpSocketResource {
     pub fn drop_socket(&mut self) {
         self.socket = None;
     }
+
+    /// Returns the delivery channels if the socket uses them.
+    pub fn channels(&self) -> Option<&DeliveryChannels> {
+        self.channels.as_ref()
+    }
+
+    /// Returns a mutable reference to the delivery channels if the socket uses them.
+    pub fn channels_mut(&mut self) -> Option<&mut DeliveryChannels> {
+        self.channels.as_mut()
+    }
 }
@@ -0,0 +1,271 @@
+//! Network systems implementation backed by WebRTC data channels, for unreliable messages in
+//! browser builds.
+//!
+//! Connecting two peers with WebRTC requires exchanging offers, answers and ICE candidates over
+//! some signaling channel, e.g. a WebSocket to a lobby server, which is up to the game. Once the
+//! data channels of a peer are open, hand them to the `WebRtcNetworkResource` along with an
+//! address identifying the peer, and messages to that address are sent over them.
+
+use std::{
+    cell::RefCell,
+    collections::{HashMap, VecDeque},
+    io,
+    net::SocketAddr,
+    rc::Rc,
+};
+
+use amethyst_core::{ecs::*, EventChannel};
+use amethyst_error::Error;
+use bytes::Bytes;
+use js_sys::{ArrayBuffer, Uint8Array};
+use wasm_bindgen::{closure::Closure, JsCast, JsValue};
+use web_sys::{MessageEvent, RtcDataChannel, RtcDataChannelState, RtcDataChannelType};
+
+use crate::simulation::{
+    events::NetworkSimulationEvent,
+    requirements::DeliveryRequirement,
+    timing::{NetworkSimulationTime, NetworkSimulationTimeSystem},
+    transport::TransportResource,
+};
+
+/// Use this network bundle to add the WebRTC transport layer to your game.
+#[derive(Default)]
+pub struct WebRtcNetworkBundle;
+
+impl SystemBundle for WebRtcNetworkBundle {
+    fn load(
+        &mut self,
+        _world: &mut World,
+        resources: &mut Resources,
+        builder: &mut DispatcherBuilder,
+    ) -> Result<(), Error> {
+        resources.get_or_default::<WebRtcNetworkResource>();
+
+        builder
+            .add_system(NetworkSimulationTimeSystem)
+            .add_system(WebRtcNetworkSendSystem)
+            .add_system(WebRtcNetworkRecvSystem);
+
+        Ok(())
+    }
+}
+
+/// What the callbacks of a data channel received since the last frame.
+#[derive(Default)]
+struct Inbox {
+    messages: VecDeque<Vec<u8>>,
+    closed: bool,
+    error: Option<String>,
+}
+
+/// A data channel along with the callbacks filling its inbox.
+struct DataChannel {
+    channel: RtcDataChannel,
+    inbox: Rc<RefCell<Inbox>>,
+    _on_message: Closure<dyn FnMut(MessageEvent)>,
+    _on_close: Closure<dyn FnMut(JsValue)>,
+    _on_error: Closure<dyn FnMut(JsValue)>,
+}
+
+impl DataChannel {
+    fn new(channel: RtcDataChannel) -> Self {
+        channel.set_binary_type(RtcDataChannelType::Arraybuffer);
+        let inbox = Rc::new(RefCell::new(Inbox::default()));
+
+        let messages = inbox.clone();
+        let on_message = Closure::wrap(Box::new(move |event: MessageEvent| {
+            if let Ok(buffer) = event.data().dyn_into::<ArrayBuffer>() {
+                messages
+                    .borrow_mut()
+                    .messages
+                    .push_back(Uint8Array::new(&buffer).to_vec());
+            }
+        }) as Box<dyn FnMut(MessageEvent)>);
+        let closed = inbox.clone();
+        let on_close = Closure::wrap(Box::new(move |_: JsValue| {
+            closed.borrow_mut().closed = true;
+        }) as Box<dyn FnMut(JsValue)>);
+        let errored = inbox.clone();
+        let on_error = Closure::wrap(Box::new(move |error: JsValue| {
+            errored.borrow_mut().error = Some(format!("{:?}", error));
+        }) as Box<dyn FnMut(JsValue)>);
+
+        channel.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+        channel.set_onclose(Some(on_close.as_ref().unchecked_ref()));
+        channel.set_onerror(Some(on_error.as_ref().unchecked_ref()));
+
+        Self {
+            channel,
+            inbox,
+            _on_message: on_message,
+            _on_close: on_close,
+            _on_error: on_error,
+        }
+    }
+
+    fn send(&self, payload: &[u8]) -> Result<(), JsValue> {
+        if self.channel.ready_state() != RtcDataChannelState::Open {
+            return Err(JsValue::from_str("data channel is not open"));
+        }
+        self.channel.send_with_u8_array(payload)
+    }
+}
+
+impl Drop for DataChannel {
+    fn drop(&mut self) {
+        self.channel.set_onmessage(None);
+        self.channel.set_onclose(None);
+        self.channel.set_onerror(None);
+        self.channel.close();
+    }
+}
+
+/// The data channels of a peer.
+struct Peer {
+    unreliable: DataChannel,
+    reliable: Option<DataChannel>,
+}
+
+impl Peer {
+    fn channels(&self) -> impl Iterator<Item = &DataChannel> {
+        std::iter::once(&self.unreliable).chain(self.reliable.as_ref())
+    }
+}
+
+/// System to send messages over the data channels of their destinations.
+pub struct WebRtcNetworkSendSystem;
+
+impl System for WebRtcNetworkSendSystem {
+    fn build(self) -> Box<dyn ParallelRunnable> {
+        Box::new(
+            SystemBuilder::new("WebRtcNetworkSendSystem")
+                .write_resource::<TransportResource>()
+                .read_resource::<WebRtcNetworkResource>()
+                .read_resource::<NetworkSimulationTime>()
+                .write_resource::<EventChannel<NetworkSimulationEvent>>()
+                .build(
+                    move |_commands, _world, (transport, net, sim_time, channel), _| {
+                        let messages = transport
+                            .drain_messages_to_send(|_| sim_time.should_send_message_now());
+                        for message in messages {
+                            let peer = match net.peers.get(&message.destination) {
+                                Some(peer) => peer,
+                                None => {
+                                    channel.single_write(NetworkSimulationEvent::SendError(
+                                        io::Error::new(
+                                            io::ErrorKind::NotConnected,
+                                            "no data channel to the destination",
+                                        ),
+                                        message,
+                                    ));
+                                    continue;
+                                }
+                            };
+                            let data_channel = match message.delivery {
+                                DeliveryRequirement::Unreliable
+                                | DeliveryRequirement::UnreliableSequenced(_)
+                                | DeliveryRequirement::Default => &peer.unreliable,
+                                delivery => {
+                                    peer.reliable.as_ref().unwrap_or_else(|| {
+                                        panic!(
+                                        "{:?} requires a reliable data channel to the destination.",
+                                        delivery
+                                    )
+                                    })
+                                }
+                            };
+                            if let Err(e) = data_channel.send(&message.payload) {
+                                channel.single_write(NetworkSimulationEvent::SendError(
+                                    io::Error::new(io::ErrorKind::Other, format!("{:?}", e)),
+                                    message,
+                                ));
+                            }
+                        }
+                    },
+                ),
+        )
+    }
+}
+
+/// System to receive messages from the data channels of all peers.
+pub struct WebRtcNetworkRecvSystem;
+
+impl System for WebRtcNetworkRecvSystem {
+    fn build(self) -> Box<dyn ParallelRunnable> {
+        Box::new(
+            SystemBuilder::new("WebRtcNetworkRecvSystem")
+                .write_resource::<WebRtcNetworkResource>()
+                .write_resource::<EventChannel<NetworkSimulationEvent>>()
+                .build(move |_commands, _world, (net, event_channel), _| {
+                    net.peers.retain(|addr, peer| {
+                        let mut closed = false;
+                        for data_channel in peer.channels() {
+                            let mut inbox = data_channel.inbox.borrow_mut();
+                            for payload in inbox.messages.drain(..) {
+                                event_channel.single_write(NetworkSimulationEvent::Message(
+                                    *addr,
+                                    Bytes::from(payload),
+                                ));
+                            }
+                            if let Some(error) = inbox.error.take() {
+                                event_channel.single_write(
+                                    NetworkSimulationEvent::ConnectionError(
+                                        io::Error::new(io::ErrorKind::Other, error),
+                                        Some(*addr),
+                                    ),
+                                );
+                            }
+                            closed |= inbox.closed;
+                        }
+                        if closed {
+                            event_channel.single_write(NetworkSimulationEvent::Disconnect(*addr));
+                        }
+                        !closed
+                    });
+                }),
+        )
+    }
+}
+
+/// Resource that owns the data channels of all peers.
+#[derive(Default)]
+pub struct WebRtcNetworkResource {
+    peers: HashMap<SocketAddr, Peer>,
+}
+
+// wasm32 is single threaded, so the resource is never actually shared between threads.
+unsafe impl Send for WebRtcNetworkResource {}
+unsafe impl Sync for WebRtcNetworkResource {}
+
+impl WebRtcNetworkResource {
+    /// Sends messages to `addr` over the given open data channels.
+    ///
+    /// `unreliable` should be created with `ordered: false` and `maxRetransmits: 0`, and is used
+    /// for `Unreliable`, `UnreliableSequenced` and `Default` messages. The other messages need a
+    /// `reliable` channel, created with the default settings. Since unreliable data channels
+    /// don't drop older messages, `UnreliableSequenced` messages may arrive out of order.
+    pub fn add_peer(
+        &mut self,
+        addr: SocketAddr,
+        unreliable: RtcDataChannel,
+        reliable: Option<RtcDataChannel>,
+    ) {
+        self.peers.insert(
+            addr,
+            Peer {
+                unreliable: DataChannel::new(unreliable),
+                reliable: reliable.map(DataChannel::new),
+            },
+        );
+    }
+
+    /// Returns true if there are data channels to the given address.
+    pub fn has_peer(&self, addr: SocketAddr) -> bool {
+        self.peers.contains_key(&addr)
+    }
+
+    /// Closes the data channels to the given address.
+    pub fn remove_peer(&mut self, addr: SocketAddr) {
+        self.peers.remove(&addr);
+    }
+}
@@ -0,0 +1,35 @@
+//! Network systems implementation backed by WebSockets, for games which have to run in a
+//! browser or connect to one.
+//!
+//! Native builds can both accept and open connections, while browser builds can only connect
+//! to a server. Like TCP, WebSockets only support `DeliveryRequirement::ReliableOrdered`.
+
+#[cfg(not(target_arch = "wasm32"))]
+mod native;
+#[cfg(target_arch = "wasm32")]
+mod web;
+
+use log::warn;
+#[cfg(not(target_arch = "wasm32"))]
+pub use native::*;
+#[cfg(target_arch = "wasm32")]
+pub use web::*;
+
+use crate::simulation::{message::Message, requirements::DeliveryRequirement};
+
+/// Checks that a message can be delivered over a WebSocket, panicking otherwise like the other
+/// transports do.
+fn check_delivery(message: &Message) {
+    match message.delivery {
+        DeliveryRequirement::ReliableOrdered(Some(_)) => {
+            warn!("Streams are not supported by WebSockets and will be ignored.");
+        }
+        DeliveryRequirement::ReliableOrdered(_) | DeliveryRequirement::Default => {}
+        delivery => {
+            panic!(
+                "{:?} is unsupported. WebSockets only support ReliableOrdered by design.",
+                delivery
+            )
+        }
+    }
+}
@@ -0,0 +1,607 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    io,
+    net::{SocketAddr, TcpListener, TcpStream},
+    ops::DerefMut,
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use amethyst_core::{ecs::*, EventChannel};
+use amethyst_error::Error;
+use bytes::Bytes;
+use log::warn;
+use tungstenite::{
+    handshake::{
+        client::ClientHandshake,
+        server::{NoCallback, ServerHandshake},
+        HandshakeError, HandshakeRole, MidHandshake,
+    },
+    Error as WsError, Message as WsMessage, WebSocket,
+};
+
+use super::check_delivery;
+use crate::simulation::{
+    events::NetworkSimulationEvent,
+    message::Message,
+    timing::{NetworkSimulationTime, NetworkSimulationTimeSystem},
+    transport::TransportResource,
+};
+
+/// Time after which a connection which didn't finish its handshake is dropped.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Use this network bundle to add the WebSocket transport layer to your game.
+pub struct WebSocketNetworkBundle {
+    listener: Option<TcpListener>,
+}
+
+impl WebSocketNetworkBundle {
+    /// Creates a bundle accepting connections on the given listener, if any. Connections to
+    /// other hosts are opened when the first message is sent to them.
+    pub fn new(listener: Option<TcpListener>) -> Self {
+        Self { listener }
+    }
+}
+
+impl SystemBundle for WebSocketNetworkBundle {
+    fn load(
+        &mut self,
+        _world: &mut World,
+        resources: &mut Resources,
+        builder: &mut DispatcherBuilder,
+    ) -> Result<(), Error> {
+        resources.insert(WebSocketNetworkResource::new(self.listener.take()));
+
+        builder
+            .add_system(NetworkSimulationTimeSystem)
+            .add_system(WebSocketConnectionListenerSystem)
+            .add_system(WebSocketStreamManagementSystem)
+            .add_system(WebSocketNetworkSendSystem)
+            .add_system(WebSocketNetworkRecvSystem);
+
+        Ok(())
+    }
+}
+
+/// System to accept incoming WebSocket connections, whose handshakes are continued by the
+/// `WebSocketStreamManagementSystem`.
+pub struct WebSocketConnectionListenerSystem;
+
+impl System for WebSocketConnectionListenerSystem {
+    fn build(self) -> Box<dyn ParallelRunnable> {
+        Box::new(
+            SystemBuilder::new("WebSocketConnectionListenerSystem")
+                .write_resource::<WebSocketNetworkResource>()
+                .write_resource::<EventChannel<NetworkSimulationEvent>>()
+                .build(move |_commands, _world, (net, event_channel), _| {
+                    let resource = net.deref_mut();
+                    if let Some(ref listener) = resource.listener {
+                        loop {
+                            match listener.accept() {
+                                Ok((stream, addr)) => {
+                                    let accepted = stream
+                                        .set_nonblocking(true)
+                                        .and_then(|_| stream.set_nodelay(true));
+                                    match accepted {
+                                        Ok(()) => {
+                                            resource.pending.insert(
+                                                addr,
+                                                Pending::new(Handshake::Accepted(stream)),
+                                            );
+                                        }
+                                        Err(e) => {
+                                            event_channel.single_write(
+                                                NetworkSimulationEvent::ConnectionError(
+                                                    e,
+                                                    Some(addr),
+                                                ),
+                                            );
+                                        }
+                                    }
+                                }
+                                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                                    break;
+                                }
+                                Err(e) => {
+                                    event_channel.single_write(
+                                        NetworkSimulationEvent::ConnectionError(e, None),
+                                    );
+                                    break;
+                                }
+                            };
+                        }
+                    }
+                }),
+        )
+    }
+}
+
+/// System opening connections to the destinations of messages, continuing the handshakes of
+/// new connections and dropping closed ones.
+pub struct WebSocketStreamManagementSystem;
+
+impl System for WebSocketStreamManagementSystem {
+    fn build(self) -> Box<dyn ParallelRunnable> {
+        Box::new(
+            SystemBuilder::new("WebSocketStreamManagementSystem")
+                .write_resource::<WebSocketNetworkResource>()
+                .read_resource::<TransportResource>()
+                .write_resource::<EventChannel<NetworkSimulationEvent>>()
+                .build(
+                    move |_commands, _world, (net, transport, event_channel), _| {
+                        for message in transport.get_messages() {
+                            let addr = message.destination;
+                            if net.sockets.contains_key(&addr) || net.pending.contains_key(&addr) {
+                                continue;
+                            }
+                            match connect(addr) {
+                                Ok(handshake) => {
+                                    net.pending.insert(addr, Pending::new(handshake));
+                                }
+                                Err(e) => {
+                                    event_channel.single_write(
+                                        NetworkSimulationEvent::ConnectionError(e, Some(addr)),
+                                    );
+                                }
+                            }
+                        }
+
+                        for (addr, pending) in std::mem::take(&mut net.pending) {
+                            let Pending {
+                                handshake,
+                                started,
+                                outbox,
+                            } = pending;
+                            let progress = if started.elapsed() > HANDSHAKE_TIMEOUT {
+                                Err(io::Error::new(
+                                    io::ErrorKind::TimedOut,
+                                    "WebSocket handshake timed out",
+                                ))
+                            } else {
+                                handshake.resume(addr)
+                            };
+                            match progress {
+                                Ok(Progress::Open(socket)) => {
+                                    net.sockets.insert(
+                                        addr,
+                                        Connection {
+                                            socket,
+                                            active: true,
+                                            outbox,
+                                        },
+                                    );
+                                    event_channel
+                                        .single_write(NetworkSimulationEvent::Connect(addr));
+                                }
+                                Ok(Progress::Pending(handshake)) => {
+                                    net.pending.insert(
+                                        addr,
+                                        Pending {
+                                            handshake,
+                                            started,
+                                            outbox,
+                                        },
+                                    );
+                                }
+                                Err(e) => {
+                                    report_unsent(outbox, &e, event_channel);
+                                    event_channel.single_write(
+                                        NetworkSimulationEvent::ConnectionError(e, Some(addr)),
+                                    );
+                                }
+                            }
+                        }
+
+                        net.sockets.retain(|addr, connection| {
+                            if !connection.active {
+                                let closed =
+                                    io::Error::new(io::ErrorKind::NotConnected, "WebSocket closed");
+                                report_unsent(
+                                    connection.outbox.drain(..).collect(),
+                                    &closed,
+                                    event_channel,
+                                );
+                                event_channel
+                                    .single_write(NetworkSimulationEvent::Disconnect(*addr));
+                            }
+                            connection.active
+                        });
+                    },
+                ),
+        )
+    }
+}
+
+/// Sends a `SendError` for each message which won't be written, because of the given error.
+fn report_unsent(
+    messages: VecDeque<Message>,
+    error: &io::Error,
+    event_channel: &mut EventChannel<NetworkSimulationEvent>,
+) {
+    for message in messages {
+        event_channel.single_write(NetworkSimulationEvent::SendError(
+            io::Error::new(error.kind(), error.to_string()),
+            message,
+        ));
+    }
+}
+
+/// Starts connecting to the given address. The TCP stream is connected on another thread, as
+/// that blocks until the host answers.
+fn connect(addr: SocketAddr) -> io::Result<Handshake> {
+    let stream = Arc::new(Mutex::new(None));
+    let connected = stream.clone();
+    thread::Builder::new()
+        .name(format!("WebSocket connection to {}", addr))
+        .spawn(move || {
+            let stream = TcpStream::connect(addr);
+            if let Ok(mut connected) = connected.lock() {
+                *connected = Some(stream);
+            }
+        })?;
+    Ok(Handshake::Connecting(stream))
+}
+
+/// System to send messages over the open WebSockets.
+///
+/// Messages to WebSockets which are still connecting, or can't take more data for now, are
+/// queued and written in a later frame.
+pub struct WebSocketNetworkSendSystem;
+
+impl System for WebSocketNetworkSendSystem {
+    fn build(self) -> Box<dyn ParallelRunnable> {
+        Box::new(
+            SystemBuilder::new("WebSocketNetworkSendSystem")
+                .write_resource::<TransportResource>()
+                .write_resource::<WebSocketNetworkResource>()
+                .read_resource::<NetworkSimulationTime>()
+                .write_resource::<EventChannel<NetworkSimulationEvent>>()
+                .build(
+                    move |_commands, _world, (transport, net, sim_time, channel), _| {
+                        let messages = transport
+                            .drain_messages_to_send(|_| sim_time.should_send_message_now());
+                        for message in messages {
+                            check_delivery(&message);
+                            let outbox = match net.sockets.get_mut(&message.destination) {
+                                Some(connection) => &mut connection.outbox,
+                                None => {
+                                    match net.pending.get_mut(&message.destination) {
+                                        Some(pending) => &mut pending.outbox,
+                                        None => {
+                                            channel.single_write(
+                                                NetworkSimulationEvent::SendError(
+                                                    io::Error::new(
+                                                        io::ErrorKind::NotConnected,
+                                                        "No WebSocket to the destination",
+                                                    ),
+                                                    message,
+                                                ),
+                                            );
+                                            continue;
+                                        }
+                                    }
+                                }
+                            };
+                            outbox.push_back(message);
+                        }
+                        for connection in net.sockets.values_mut() {
+                            connection.flush(channel);
+                        }
+                    },
+                ),
+        )
+    }
+}
+
+/// System to receive messages from all open WebSockets.
+pub struct WebSocketNetworkRecvSystem;
+
+impl System for WebSocketNetworkRecvSystem {
+    fn build(self) -> Box<dyn ParallelRunnable> {
+        Box::new(
+            SystemBuilder::new("WebSocketNetworkRecvSystem")
+                .write_resource::<WebSocketNetworkResource>()
+                .write_resource::<EventChannel<NetworkSimulationEvent>>()
+                .build(move |_commands, _world, (net, event_channel), _| {
+                    for (addr, connection) in net.sockets.iter_mut() {
+                        let active = &mut connection.active;
+                        loop {
+                            match connection.socket.read_message() {
+                                Ok(WsMessage::Binary(payload)) => {
+                                    event_channel.single_write(NetworkSimulationEvent::Message(
+                                        *addr,
+                                        Bytes::from(payload),
+                                    ));
+                                }
+                                Ok(WsMessage::Close(_)) => {
+                                    *active = false;
+                                    break;
+                                }
+                                Ok(message) => {
+                                    if !message.is_ping() && !message.is_pong() {
+                                        warn!(
+                                            "Ignoring non-binary WebSocket message from {}",
+                                            addr
+                                        );
+                                    }
+                                }
+                                Err(WsError::Io(ref e))
+                                    if e.kind() == io::ErrorKind::WouldBlock =>
+                                {
+                                    break;
+                                }
+                                Err(WsError::ConnectionClosed) | Err(WsError::AlreadyClosed) => {
+                                    *active = false;
+                                    break;
+                                }
+                                Err(WsError::Io(e)) => {
+                                    if e.kind() == io::ErrorKind::ConnectionReset {
+                                        *active = false;
+                                    } else {
+                                        event_channel
+                                            .single_write(NetworkSimulationEvent::RecvError(e));
+                                    }
+                                    break;
+                                }
+                                Err(e) => {
+                                    event_channel.single_write(NetworkSimulationEvent::RecvError(
+                                        io::Error::new(io::ErrorKind::InvalidData, e.to_string()),
+                                    ));
+                                    *active = false;
+                                    break;
+                                }
+                            }
+                        }
+                    }
+                }),
+        )
+    }
+}
+
+/// The handshake of a new connection, which is continued every frame since the streams don't
+/// block.
+enum Handshake {
+    /// The TCP stream is connected on another thread, which stores it once it's done.
+    Connecting(Arc<Mutex<Option<io::Result<TcpStream>>>>),
+    /// The handshake with the server is in progress.
+    Client(MidHandshake<ClientHandshake<TcpStream>>),
+    /// A client was accepted and hasn't started its handshake yet.
+    Accepted(TcpStream),
+    /// The handshake with an accepted client is in progress.
+    Server(MidHandshake<ServerHandshake<TcpStream, NoCallback>>),
+}
+
+/// What a handshake is waiting for next.
+enum Progress {
+    Open(WebSocket<TcpStream>),
+    Pending(Handshake),
+}
+
+impl Handshake {
+    /// Continues the handshake as far as possible without blocking.
+    fn resume(self, addr: SocketAddr) -> io::Result<Progress> {
+        match self {
+            Handshake::Connecting(stream) => {
+                let connected = stream.lock().ok().and_then(|mut stream| stream.take());
+                match connected {
+                    Some(connected) => {
+                        let stream = connected?;
+                        stream.set_nonblocking(true)?;
+                        stream.set_nodelay(true)?;
+                        let result = tungstenite::client(format!("ws://{}/", addr), stream);
+                        progress(result.map(|(socket, _)| socket), Handshake::Client)
+                    }
+                    None => Ok(Progress::Pending(Handshake::Connecting(stream))),
+                }
+            }
+            Handshake::Client(handshake) => {
+                progress(
+                    handshake.handshake().map(|(socket, _)| socket),
+                    Handshake::Client,
+                )
+            }
+            Handshake::Accepted(stream) => progress(tungstenite::accept(stream), Handshake::Server),
+            Handshake::Server(handshake) => progress(handshake.handshake(), Handshake::Server),
+        }
+    }
+}
+
+/// Keeps a handshake which was interrupted because the stream would block.
+fn progress<R: HandshakeRole>(
+    result: Result<WebSocket<TcpStream>, HandshakeError<R>>,
+    interrupted: fn(MidHandshake<R>) -> Handshake,
+) -> io::Result<Progress> {
+    match result {
+        Ok(socket) => Ok(Progress::Open(socket)),
+        Err(HandshakeError::Interrupted(handshake)) => {
+            Ok(Progress::Pending(interrupted(handshake)))
+        }
+        Err(HandshakeError::Failure(e)) => {
+            Err(io::Error::new(
+                io::ErrorKind::ConnectionAborted,
+                e.to_string(),
+            ))
+        }
+    }
+}
+
+/// A connection which is still doing its handshake, with the messages waiting for it.
+struct Pending {
+    handshake: Handshake,
+    started: Instant,
+    outbox: VecDeque<Message>,
+}
+
+impl Pending {
+    fn new(handshake: Handshake) -> Self {
+        Self {
+            handshake,
+            started: Instant::now(),
+            outbox: VecDeque::new(),
+        }
+    }
+}
+
+/// An open WebSocket, with the messages waiting to be written to it.
+struct Connection {
+    socket: WebSocket<TcpStream>,
+    active: bool,
+    outbox: VecDeque<Message>,
+}
+
+impl Connection {
+    /// Writes the queued messages until the socket can't take more, keeping the rest for the
+    /// next frame.
+    fn flush(&mut self, event_channel: &mut EventChannel<NetworkSimulationEvent>) {
+        // A message the stream couldn't take is queued by the socket, and written first.
+        match self.socket.write_pending() {
+            Ok(()) => {}
+            Err(WsError::Io(ref e)) if e.kind() == io::ErrorKind::WouldBlock => return,
+            Err(_) => {
+                self.active = false;
+                return;
+            }
+        }
+        while let Some(message) = self.outbox.pop_front() {
+            match self
+                .socket
+                .write_message(WsMessage::Binary(message.payload.to_vec()))
+            {
+                Ok(()) => {}
+                Err(WsError::Io(ref e)) if e.kind() == io::ErrorKind::WouldBlock => return,
+                Err(WsError::SendQueueFull(_)) => {
+                    self.outbox.push_front(message);
+                    return;
+                }
+                Err(WsError::ConnectionClosed) | Err(WsError::AlreadyClosed) => {
+                    self.outbox.push_front(message);
+                    self.active = false;
+                    return;
+                }
+                Err(WsError::Io(e)) => {
+                    event_channel.single_write(NetworkSimulationEvent::SendError(e, message));
+                    self.active = false;
+                    return;
+                }
+                Err(e) => {
+                    event_channel.single_write(NetworkSimulationEvent::SendError(
+                        io::Error::new(io::ErrorKind::Other, e.to_string()),
+                        message,
+                    ));
+                }
+            }
+        }
+    }
+}
+
+/// Resource that owns the WebSocket listener and all WebSockets.
+#[derive(Default)]
+pub struct WebSocketNetworkResource {
+    listener: Option<TcpListener>,
+    sockets: HashMap<SocketAddr, Connection>,
+    pending: HashMap<SocketAddr, Pending>,
+}
+
+impl WebSocketNetworkResource {
+    /// Creates a new instance of the `WebSocketNetworkResource`.
+    pub fn new(listener: Option<TcpListener>) -> Self {
+        if let Some(listener) = listener.as_ref() {
+            listener
+                .set_nonblocking(true)
+                .expect("Setting nonblocking mode");
+        }
+        Self {
+            listener,
+            sockets: HashMap::new(),
+            pending: HashMap::new(),
+        }
+    }
+
+    /// Returns an immutable reference to the listener if there is one configured.
+    pub fn get(&self) -> Option<&TcpListener> {
+        self.listener.as_ref()
+    }
+
+    /// Sets the bound listener to the `WebSocketNetworkResource`.
+    pub fn set_listener(&mut self, listener: TcpListener) {
+        listener
+            .set_nonblocking(true)
+            .expect("Setting nonblocking mode");
+        self.listener = Some(listener);
+    }
+
+    /// Drops the listener from the `WebSocketNetworkResource`.
+    pub fn drop_listener(&mut self) {
+        self.listener = None;
+    }
+
+    /// Returns true if there is an open WebSocket to the given address.
+    pub fn is_connected(&self, addr: SocketAddr) -> bool {
+        self.sockets.contains_key(&addr)
+    }
+
+    /// Closes the WebSocket to the given address, or stops opening it.
+    pub fn disconnect(&mut self, addr: SocketAddr) {
+        self.pending.remove(&addr);
+        if let Some(connection) = self.sockets.get_mut(&addr) {
+            let _ = connection.socket.close(None);
+            let _ = connection.socket.write_pending();
+            connection.active = false;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use amethyst_core::Time;
+
+    use super::*;
+
+    #[test]
+    fn test_handshakes_do_not_block_the_dispatcher() {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let server = listener.local_addr().unwrap();
+        let mut world = World::default();
+        let mut resources = Resources::default();
+        resources.insert(Time::default());
+        resources.insert(TransportResource::default());
+        resources.insert(NetworkSimulationTime::default());
+        resources.insert(EventChannel::<NetworkSimulationEvent>::default());
+        let mut reader = resources
+            .get_mut::<EventChannel<NetworkSimulationEvent>>()
+            .unwrap()
+            .register_reader();
+        let mut builder = DispatcherBuilder::default();
+        builder.add_bundle(WebSocketNetworkBundle::new(Some(listener)));
+        let mut dispatcher = builder.build(&mut world, &mut resources).unwrap();
+
+        // a client which never sends its handshake is accepted first
+        let _silent = TcpStream::connect(server).unwrap();
+        resources
+            .get_mut::<TransportResource>()
+            .unwrap()
+            .send(server, b"hello");
+
+        let mut received = None;
+        for _ in 0..500 {
+            dispatcher.execute(&mut world, &mut resources);
+            let events = resources
+                .get::<EventChannel<NetworkSimulationEvent>>()
+                .unwrap();
+            for event in events.read(&mut reader) {
+                if let NetworkSimulationEvent::Message(_, payload) = event {
+                    received = Some(payload.clone());
+                }
+            }
+            if received.is_some() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(2));
+        }
+        assert_eq!(received.as_deref(), Some(&b"hello"[..]));
+    }
+}
@@ -0,0 +1,275 @@
+use std::{
+    cell::RefCell,
+    collections::{HashMap, VecDeque},
+    io,
+    net::SocketAddr,
+    rc::Rc,
+};
+
+use amethyst_core::{ecs::*, EventChannel};
+use amethyst_error::Error;
+use bytes::Bytes;
+use js_sys::{ArrayBuffer, Uint8Array};
+use wasm_bindgen::{closure::Closure, JsCast, JsValue};
+use web_sys::{BinaryType, MessageEvent, WebSocket};
+
+use super::check_delivery;
+use crate::simulation::{
+    events::NetworkSimulationEvent,
+    timing::{NetworkSimulationTime, NetworkSimulationTimeSystem},
+    transport::TransportResource,
+};
+
+/// Use this network bundle to add the WebSocket transport layer to your game.
+#[derive(Default)]
+pub struct WebSocketNetworkBundle {
+    secure: bool,
+}
+
+impl WebSocketNetworkBundle {
+    /// Creates a bundle opening a connection to every host a message is sent to.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Connects with `wss://` instead of `ws://`, which pages served over HTTPS require.
+    pub fn with_secure(mut self, secure: bool) -> Self {
+        self.secure = secure;
+        self
+    }
+}
+
+impl SystemBundle for WebSocketNetworkBundle {
+    fn load(
+        &mut self,
+        _world: &mut World,
+        resources: &mut Resources,
+        builder: &mut DispatcherBuilder,
+    ) -> Result<(), Error> {
+        resources.insert(WebSocketNetworkResource::new(self.secure));
+
+        builder
+            .add_system(NetworkSimulationTimeSystem)
+            .add_system(WebSocketNetworkSendSystem)
+            .add_system(WebSocketNetworkRecvSystem);
+
+        Ok(())
+    }
+}
+
+/// What the callbacks of a WebSocket received since the last frame.
+#[derive(Default)]
+struct Inbox {
+    messages: VecDeque<Vec<u8>>,
+    opened: bool,
+    closed: bool,
+    error: Option<String>,
+}
+
+/// A WebSocket of the browser along with the callbacks filling its inbox.
+struct Connection {
+    socket: WebSocket,
+    inbox: Rc<RefCell<Inbox>>,
+    outbox: VecDeque<Bytes>,
+    connected: bool,
+    _on_message: Closure<dyn FnMut(MessageEvent)>,
+    _on_open: Closure<dyn FnMut(JsValue)>,
+    _on_close: Closure<dyn FnMut(JsValue)>,
+    _on_error: Closure<dyn FnMut(JsValue)>,
+}
+
+impl Connection {
+    fn open(url: &str) -> Result<Self, JsValue> {
+        let socket = WebSocket::new(url)?;
+        socket.set_binary_type(BinaryType::Arraybuffer);
+        let inbox = Rc::new(RefCell::new(Inbox::default()));
+
+        let messages = inbox.clone();
+        let on_message = Closure::wrap(Box::new(move |event: MessageEvent| {
+            if let Ok(buffer) = event.data().dyn_into::<ArrayBuffer>() {
+                messages
+                    .borrow_mut()
+                    .messages
+                    .push_back(Uint8Array::new(&buffer).to_vec());
+            }
+        }) as Box<dyn FnMut(MessageEvent)>);
+        let opened = inbox.clone();
+        let on_open = Closure::wrap(Box::new(move |_: JsValue| {
+            opened.borrow_mut().opened = true;
+        }) as Box<dyn FnMut(JsValue)>);
+        let closed = inbox.clone();
+        let on_close = Closure::wrap(Box::new(move |_: JsValue| {
+            closed.borrow_mut().closed = true;
+        }) as Box<dyn FnMut(JsValue)>);
+        let errored = inbox.clone();
+        let on_error = Closure::wrap(Box::new(move |error: JsValue| {
+            errored.borrow_mut().error = Some(format!("{:?}", error));
+        }) as Box<dyn FnMut(JsValue)>);
+
+        socket.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+        socket.set_onopen(Some(on_open.as_ref().unchecked_ref()));
+        socket.set_onclose(Some(on_close.as_ref().unchecked_ref()));
+        socket.set_onerror(Some(on_error.as_ref().unchecked_ref()));
+
+        Ok(Self {
+            socket,
+            inbox,
+            outbox: VecDeque::new(),
+            connected: false,
+            _on_message: on_message,
+            _on_open: on_open,
+            _on_close: on_close,
+            _on_error: on_error,
+        })
+    }
+
+    /// Sends the queued messages once the WebSocket is open.
+    fn flush(&mut self) -> Result<(), JsValue> {
+        if self.socket.ready_state() != WebSocket::OPEN {
+            return Ok(());
+        }
+        while let Some(payload) = self.outbox.front() {
+            self.socket.send_with_u8_array(payload)?;
+            self.outbox.pop_front();
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        self.socket.set_onmessage(None);
+        self.socket.set_onopen(None);
+        self.socket.set_onclose(None);
+        self.socket.set_onerror(None);
+        let _ = self.socket.close();
+    }
+}
+
+/// System to send messages, connecting to their destinations first if needed.
+pub struct WebSocketNetworkSendSystem;
+
+impl System for WebSocketNetworkSendSystem {
+    fn build(self) -> Box<dyn ParallelRunnable> {
+        Box::new(
+            SystemBuilder::new("WebSocketNetworkSendSystem")
+                .write_resource::<TransportResource>()
+                .write_resource::<WebSocketNetworkResource>()
+                .read_resource::<NetworkSimulationTime>()
+                .write_resource::<EventChannel<NetworkSimulationEvent>>()
+                .build(
+                    move |_commands, _world, (transport, net, sim_time, channel), _| {
+                        let messages = transport
+                            .drain_messages_to_send(|_| sim_time.should_send_message_now());
+                        for message in messages {
+                            check_delivery(&message);
+                            let scheme = if net.secure { "wss" } else { "ws" };
+                            if !net.connections.contains_key(&message.destination) {
+                                let url = format!("{}://{}/", scheme, message.destination);
+                                match Connection::open(&url) {
+                                    Ok(connection) => {
+                                        net.connections.insert(message.destination, connection);
+                                    }
+                                    Err(e) => {
+                                        channel.single_write(NetworkSimulationEvent::SendError(
+                                            js_error(io::ErrorKind::ConnectionRefused, &e),
+                                            message,
+                                        ));
+                                        continue;
+                                    }
+                                }
+                            }
+                            if let Some(connection) = net.connections.get_mut(&message.destination)
+                            {
+                                connection.outbox.push_back(message.payload);
+                            }
+                        }
+                        for (addr, connection) in net.connections.iter_mut() {
+                            if let Err(e) = connection.flush() {
+                                channel.single_write(NetworkSimulationEvent::ConnectionError(
+                                    js_error(io::ErrorKind::Other, &e),
+                                    Some(*addr),
+                                ));
+                            }
+                        }
+                    },
+                ),
+        )
+    }
+}
+
+/// System to receive messages from all open WebSockets.
+pub struct WebSocketNetworkRecvSystem;
+
+impl System for WebSocketNetworkRecvSystem {
+    fn build(self) -> Box<dyn ParallelRunnable> {
+        Box::new(
+            SystemBuilder::new("WebSocketNetworkRecvSystem")
+                .write_resource::<WebSocketNetworkResource>()
+                .write_resource::<EventChannel<NetworkSimulationEvent>>()
+                .build(move |_commands, _world, (net, event_channel), _| {
+                    net.connections.retain(|addr, connection| {
+                        let mut inbox = connection.inbox.borrow_mut();
+                        if inbox.opened && !connection.connected {
+                            connection.connected = true;
+                            event_channel.single_write(NetworkSimulationEvent::Connect(*addr));
+                        }
+                        for payload in inbox.messages.drain(..) {
+                            event_channel.single_write(NetworkSimulationEvent::Message(
+                                *addr,
+                                Bytes::from(payload),
+                            ));
+                        }
+                        if let Some(error) = inbox.error.take() {
+                            event_channel.single_write(NetworkSimulationEvent::ConnectionError(
+                                io::Error::new(io::ErrorKind::Other, error),
+                                Some(*addr),
+                            ));
+                        }
+                        if inbox.closed {
+                            event_channel.single_write(NetworkSimulationEvent::Disconnect(*addr));
+                        }
+                        !inbox.closed
+                    });
+                }),
+        )
+    }
+}
+
+/// Resource that owns the WebSockets opened by the browser.
+#[derive(Default)]
+pub struct WebSocketNetworkResource {
+    connections: HashMap<SocketAddr, Connection>,
+    secure: bool,
+}
+
+// wasm32 is single threaded, so the resource is never actually shared between threads.
+unsafe impl Send for WebSocketNetworkResource {}
+unsafe impl Sync for WebSocketNetworkResource {}
+
+impl WebSocketNetworkResource {
+    /// Creates a new instance of the `WebSocketNetworkResource`.
+    pub fn new(secure: bool) -> Self {
+        Self {
+            connections: HashMap::new(),
+            secure,
+        }
+    }
+
+    /// Returns true if there is an open WebSocket to the given address.
+    pub fn is_connected(&self, addr: SocketAddr) -> bool {
+        self.connections
+            .get(&addr)
+            .map_or(false, |connection| connection.connected)
+    }
+
+    /// Closes the WebSocket to the given address.
+    pub fn disconnect(&mut self, addr: SocketAddr) {
+        self.connections.remove(&addr);
+    }
+}
+
+/// Converts an exception thrown by the browser into an `io::Error`.
+fn js_error(kind: io::ErrorKind, error: &JsValue) -> io::Error {
+    io::Error::new(kind, format!("{:?}", error))
+}
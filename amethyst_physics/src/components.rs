@@ -0,0 +1,95 @@
+//! Components adding entities to the physics world.
+
+use derivative::Derivative;
+use rapier3d::{
+    dynamics::{RigidBodyBuilder, RigidBodyHandle},
+    geometry::{ColliderBuilder, ColliderHandle},
+};
+
+/// Rigid body of an entity, added to the `PhysicsWorld` at the position of its `Transform` by
+/// the `PhysicsSystem`.
+///
+/// Dynamic bodies move the `Transform`, while kinematic and static bodies follow it. The
+/// `Transform` of an entity with a parent is relative to it, while the body is positioned in
+/// the world, see `PhysicsSystem`.
+#[derive(Derivative)]
+#[derivative(Debug)]
+pub struct RigidBody {
+    #[derivative(Debug = "ignore")]
+    pub(crate) builder: RigidBodyBuilder,
+    pub(crate) handle: Option<RigidBodyHandle>,
+}
+
+impl RigidBody {
+    /// Creates a rigid body, the position of the builder being replaced by the `Transform`.
+    pub fn new(builder: RigidBodyBuilder) -> Self {
+        RigidBody {
+            builder,
+            handle: None,
+        }
+    }
+
+    /// A body moved by the forces applied to it.
+    pub fn dynamic() -> Self {
+        Self::new(RigidBodyBuilder::new_dynamic())
+    }
+
+    /// A body moved by its `Transform`, pushing the dynamic bodies in its way.
+    pub fn kinematic() -> Self {
+        Self::new(RigidBodyBuilder::new_kinematic())
+    }
+
+    /// A body which doesn't move, unless its `Transform` is changed.
+    pub fn fixed() -> Self {
+        Self::new(RigidBodyBuilder::new_static())
+    }
+
+    /// The handle of the body in the `PhysicsWorld`, once it is added to it, to apply forces or
+    /// change its velocity.
+    pub fn handle(&self) -> Option<RigidBodyHandle> {
+        self.handle
+    }
+}
+
+/// Collider of an entity, attached to its `RigidBody` by the `PhysicsSystem`.
+///
+/// An entity with a collider but no rigid body gets a static one. Use a compound shape for
+/// several shapes on the same entity.
+#[derive(Derivative)]
+#[derivative(Debug)]
+pub struct Collider {
+    #[derivative(Debug = "ignore")]
+    pub(crate) builder: ColliderBuilder,
+    pub(crate) handle: Option<ColliderHandle>,
+}
+
+impl Collider {
+    /// Creates a collider, positioned relative to the rigid body by the builder.
+    pub fn new(builder: ColliderBuilder) -> Self {
+        Collider {
+            builder,
+            handle: None,
+        }
+    }
+
+    /// A sphere with the given radius.
+    pub fn ball(radius: f32) -> Self {
+        Self::new(ColliderBuilder::ball(radius))
+    }
+
+    /// A box with the given half extents.
+    pub fn cuboid(half_x: f32, half_y: f32, half_z: f32) -> Self {
+        Self::new(ColliderBuilder::cuboid(half_x, half_y, half_z))
+    }
+
+    /// A sensor detecting the colliders entering it as `PhysicsEvent::TriggerEntered`, without
+    /// colliding with them.
+    pub fn sensor(builder: ColliderBuilder) -> Self {
+        Self::new(builder.sensor(true))
+    }
+
+    /// The handle of the collider in the `PhysicsWorld`, once it is added to it.
+    pub fn handle(&self) -> Option<ColliderHandle> {
+        self.handle
+    }
+}
@@ -0,0 +1,16 @@
+//! Events of the physics world.
+
+use amethyst_core::ecs::Entity;
+
+/// Sent by the `PhysicsSystem` when colliders start or stop touching each other.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PhysicsEvent {
+    /// The colliders of the two entities started touching.
+    ContactStarted(Entity, Entity),
+    /// The colliders of the two entities stopped touching.
+    ContactStopped(Entity, Entity),
+    /// A collider entered a sensor, one of the two entities being the sensor.
+    TriggerEntered(Entity, Entity),
+    /// A collider left a sensor, one of the two entities being the sensor.
+    TriggerExited(Entity, Entity),
+}
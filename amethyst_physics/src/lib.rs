@@ -0,0 +1,35 @@
+//! # amethyst_physics
+//!
+//! Physics integration of `rapier`.
+//!
+//! Entities get a `RigidBody` and a `Collider` component, which the `PhysicsSystem` adds to the
+//! `PhysicsWorld` at the position of their `Transform`. The world is stepped on the fixed
+//! timestep of `Time`, and the new positions of the dynamic bodies are written back to their
+//! `Transform`. Contacts and sensor overlaps are sent as `PhysicsEvent`s, and the `PhysicsWorld`
+//! answers ray casts and shape queries.
+
+#![doc(
+    html_logo_url = "https://amethyst.rs/brand/logo-standard.svg",
+    html_root_url = "https://docs.amethyst.rs/stable"
+)]
+#![warn(
+    missing_debug_implementations,
+    missing_docs,
+    rust_2018_idioms,
+    rust_2018_compatibility
+)]
+#![warn(clippy::all)]
+
+pub use rapier3d as rapier;
+
+pub use crate::{
+    components::{Collider, RigidBody},
+    event::PhysicsEvent,
+    system::{PhysicsBundle, PhysicsSystem},
+    world::{PhysicsWorld, RayHit},
+};
+
+mod components;
+mod event;
+mod system;
+mod world;
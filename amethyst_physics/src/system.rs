@@ -0,0 +1,304 @@
+//! Stepping the physics world and syncing it with the entities.
+
+use amethyst_core::{
+    dispatcher::{DispatcherBuilder, System, SystemBundle},
+    ecs::{systems::ParallelRunnable, *},
+    math::{Isometry3, Matrix3, Matrix4, Point3, UnitQuaternion, Vector3, U3},
+    shrev::EventChannel,
+    timing::Time,
+    transform::Transform,
+};
+use amethyst_error::Error;
+use fnv::FnvHashSet;
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+
+use crate::{Collider, PhysicsEvent, PhysicsWorld, RigidBody};
+
+/// Steps the `PhysicsWorld` once by the fixed timestep of `Time`, added to the fixed update
+/// schedule so it runs once per fixed tick.
+///
+/// Before stepping, the `RigidBody` and `Collider` components which are new are added to the
+/// world, the removed ones are removed from it, and the kinematic and static bodies, including
+/// the ones of colliders without a `RigidBody`, are moved to their `Transform`. After stepping,
+/// the dynamic bodies are written to their `Transform` and the `PhysicsEvent`s are sent.
+///
+/// Bodies are positioned in the world, so the `Transform`s of entities with a parent are
+/// converted from and to the space of their parent, as of the last `TransformSystem` run.
+/// The scale of the entities and their parents isn't applied to the bodies.
+#[derive(Debug, Default)]
+pub struct PhysicsSystem;
+
+impl System for PhysicsSystem {
+    fn build(self) -> Box<dyn ParallelRunnable> {
+        let mut live = FnvHashSet::default();
+
+        Box::new(
+            SystemBuilder::new("PhysicsSystem")
+                .read_resource::<Time>()
+                .write_resource::<PhysicsWorld>()
+                .write_resource::<EventChannel<PhysicsEvent>>()
+                .with_query(<(Entity, Write<RigidBody>, Write<Transform>)>::query())
+                .with_query(<(Entity, Write<Collider>, Read<Transform>)>::query())
+                .build(
+                    move |_commands,
+                          world,
+                          (time, physics, events),
+                          (body_query, collider_query)| {
+                        #[cfg(feature = "profiler")]
+                        profile_scope!("physics_system");
+
+                        live.clear();
+                        for (entity, body, transform) in body_query.iter_mut(world) {
+                            live.insert(*entity);
+                            let handle = match body.handle {
+                                Some(handle) if physics.bodies().get(handle).is_some() => handle,
+                                _ => {
+                                    let handle = physics.insert_body(
+                                        *entity,
+                                        &body.builder,
+                                        world_position(transform),
+                                    );
+                                    body.handle = Some(handle);
+                                    continue;
+                                }
+                            };
+                            let body = physics.bodies_mut().get_mut(handle).unwrap();
+                            let position = world_position(transform);
+                            if body.is_kinematic() {
+                                body.set_next_kinematic_position(position);
+                            } else if body.is_static() && *body.position() != position {
+                                body.set_position(position, true);
+                            }
+                        }
+                        physics.retain_bodies(|entity| live.contains(&entity));
+
+                        live.clear();
+                        for (entity, collider, transform) in collider_query.iter_mut(world) {
+                            live.insert(*entity);
+                            match collider.handle {
+                                Some(handle) if physics.colliders().get(handle).is_some() => {
+                                    physics.move_static_body(*entity, &world_position(transform));
+                                }
+                                _ => {
+                                    collider.handle = Some(physics.insert_collider(
+                                        *entity,
+                                        &collider.builder,
+                                        world_position(transform),
+                                    ));
+                                }
+                            }
+                        }
+                        physics.retain_colliders(|entity| live.contains(&entity));
+
+                        physics.step(time.fixed_seconds());
+                        physics.update_queries();
+
+                        for (_, body, transform) in body_query.iter_mut(world) {
+                            let body = match body.handle.and_then(|h| physics.bodies().get(h)) {
+                                Some(body) => body,
+                                None => continue,
+                            };
+                            if body.is_dynamic() {
+                                let position = local_position(transform, body.position());
+                                transform.set_isometry(position);
+                            }
+                        }
+                        physics.drain_events(|event| events.single_write(event));
+                    },
+                ),
+        )
+    }
+}
+
+/// Position in the world of the body of an entity with the given `Transform`.
+fn world_position(transform: &Transform) -> Isometry3<f32> {
+    let parent = transform.parent_matrix();
+    let translation = parent.transform_point(&Point3::from(*transform.translation()));
+    Isometry3::from_parts(
+        translation.coords.into(),
+        rotation(parent) * transform.rotation(),
+    )
+}
+
+/// Position relative to the parent of an entity with the given `Transform`, for the position of
+/// its body in the world.
+fn local_position(transform: &Transform, position: &Isometry3<f32>) -> Isometry3<f32> {
+    let parent = transform.parent_matrix();
+    let translation = match parent.try_inverse() {
+        Some(inverse) => inverse.transform_point(&Point3::from(position.translation.vector)),
+        None => Point3::from(position.translation.vector),
+    };
+    Isometry3::from_parts(
+        translation.coords.into(),
+        rotation(parent).inverse() * position.rotation,
+    )
+}
+
+/// Rotation of an affine transformation matrix, without its scale.
+fn rotation(matrix: &Matrix4<f32>) -> UnitQuaternion<f32> {
+    let mut rotation: Matrix3<f32> = matrix.fixed_slice::<U3, U3>(0, 0).into_owned();
+    for mut axis in rotation.column_iter_mut() {
+        axis.try_normalize_mut(std::f32::EPSILON);
+    }
+    UnitQuaternion::from_matrix(&rotation)
+}
+
+/// Inserts the `PhysicsWorld` and `EventChannel<PhysicsEvent>` resources if missing, and adds the
+/// `PhysicsSystem` to the fixed update schedule.
+///
+/// The fixed updates run before the systems of the frame, so the `TransformBundle` sees where
+/// the bodies moved to.
+#[derive(Debug)]
+pub struct PhysicsBundle {
+    gravity: Vector3<f32>,
+}
+
+impl Default for PhysicsBundle {
+    fn default() -> Self {
+        PhysicsBundle {
+            gravity: Vector3::new(0.0, -9.81, 0.0),
+        }
+    }
+}
+
+impl PhysicsBundle {
+    /// Creates a bundle with the gravity of the Earth, towards negative y.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the gravity of the `PhysicsWorld` it creates.
+    pub fn with_gravity(mut self, gravity: Vector3<f32>) -> Self {
+        self.gravity = gravity;
+        self
+    }
+}
+
+impl SystemBundle for PhysicsBundle {
+    fn load(
+        &mut self,
+        _world: &mut World,
+        resources: &mut Resources,
+        builder: &mut DispatcherBuilder,
+    ) -> Result<(), Error> {
+        let gravity = self.gravity;
+        resources.get_or_insert_with(|| PhysicsWorld::new(gravity));
+        resources.get_or_default::<EventChannel<PhysicsEvent>>();
+        builder.add_fixed_system(PhysicsSystem);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::FRAC_PI_2;
+
+    use amethyst_core::{
+        math::UnitQuaternion,
+        transform::{Parent, TransformBundle},
+    };
+
+    use super::*;
+    use crate::Collider;
+
+    fn physics_world() -> (World, Resources, Dispatcher) {
+        let mut world = World::default();
+        let mut resources = Resources::default();
+        resources.insert(Time::default());
+        let dispatcher = DispatcherBuilder::default()
+            .add_bundle(TransformBundle)
+            .add_bundle(PhysicsBundle::new())
+            .build(&mut world, &mut resources)
+            .unwrap();
+        (world, resources, dispatcher)
+    }
+
+    fn translation(world: &World, entity: Entity) -> Vector3<f32> {
+        *world
+            .entry_ref(entity)
+            .unwrap()
+            .into_component::<Transform>()
+            .unwrap()
+            .translation()
+    }
+
+    #[test]
+    fn dynamic_bodies_move_their_transform() {
+        let (mut world, mut resources, mut dispatcher) = physics_world();
+        let mut transform = Transform::default();
+        transform.set_translation_xyz(0.0, 5.0, 0.0);
+        let ball = world.push((transform, RigidBody::dynamic(), Collider::ball(0.5)));
+
+        for _ in 0..30 {
+            dispatcher.execute_fixed(&mut world, &mut resources);
+        }
+
+        let translation = translation(&world, ball);
+        assert!(translation.y < 4.5, "ball at {}", translation.y);
+        assert_eq!(translation.x, 0.0);
+    }
+
+    #[test]
+    fn bodies_of_children_are_positioned_in_the_world() {
+        let (mut world, mut resources, mut dispatcher) = physics_world();
+        let mut transform = Transform::default();
+        transform.set_translation_xyz(10.0, 0.0, 0.0);
+        transform.set_rotation(UnitQuaternion::from_axis_angle(
+            &Vector3::y_axis(),
+            FRAC_PI_2,
+        ));
+        let parent = world.push((transform,));
+        let mut transform = Transform::default();
+        transform.set_translation_xyz(1.0, 5.0, 0.0);
+        let ball = world.push((
+            transform,
+            Parent(parent),
+            RigidBody::dynamic(),
+            Collider::ball(0.5),
+        ));
+
+        // the parent positions are computed by the frame systems, which take a few frames to
+        // settle a new hierarchy
+        for _ in 0..3 {
+            dispatcher.execute(&mut world, &mut resources);
+        }
+        for _ in 0..30 {
+            dispatcher.execute_fixed(&mut world, &mut resources);
+        }
+
+        let handle = world
+            .entry_ref(ball)
+            .unwrap()
+            .into_component::<RigidBody>()
+            .unwrap()
+            .handle()
+            .unwrap();
+        let physics = resources.get::<PhysicsWorld>().unwrap();
+        let position = physics.bodies().get(handle).unwrap().position().translation;
+        assert!(
+            (position.x - 10.0).abs() < 1e-4,
+            "body at {}",
+            position.vector
+        );
+        assert!(
+            (position.z + 1.0).abs() < 1e-4,
+            "body at {}",
+            position.vector
+        );
+        assert!(position.y < 4.5, "body at {}", position.vector);
+
+        let translation = translation(&world, ball);
+        assert!(
+            (translation.x - 1.0).abs() < 1e-4,
+            "ball at {}",
+            translation
+        );
+        assert!(translation.z.abs() < 1e-4, "ball at {}", translation);
+        assert!(
+            (translation.y - position.y).abs() < 1e-4,
+            "ball at {}",
+            translation
+        );
+    }
+}
@@ -0,0 +1,442 @@
+//! The physics world and its queries.
+
+use amethyst_core::{
+    ecs::Entity,
+    math::{Isometry3, Point3, Vector3},
+};
+use derivative::Derivative;
+use fnv::FnvHashMap;
+use rapier3d::{
+    crossbeam::channel::{unbounded, Receiver},
+    dynamics::{
+        IntegrationParameters, JointHandle, JointParams, JointSet, RigidBodyBuilder,
+        RigidBodyHandle, RigidBodySet,
+    },
+    geometry::{
+        BroadPhase, ColliderBuilder, ColliderHandle, ColliderSet, ContactEvent, InteractionGroups,
+        IntersectionEvent, NarrowPhase, Ray, Shape,
+    },
+    pipeline::{ChannelEventCollector, PhysicsPipeline, QueryPipeline},
+};
+
+use crate::PhysicsEvent;
+
+/// The closest collider hit by a ray, see `PhysicsWorld::cast_ray`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RayHit {
+    /// The entity of the collider
+    pub entity: Entity,
+    /// The collider which was hit
+    pub collider: ColliderHandle,
+    /// The distance along the ray, in multiples of its direction
+    pub toi: f32,
+    /// The point where the ray hit the collider
+    pub point: Point3<f32>,
+    /// The normal of the collider at that point
+    pub normal: Vector3<f32>,
+}
+
+/// World resource holding the rigid bodies and colliders of the entities, stepped by the
+/// `PhysicsSystem`.
+///
+/// The bodies can be changed directly through `bodies_mut`, e.g. to apply forces, with the
+/// handles of the `RigidBody` components.
+#[derive(Derivative)]
+#[derivative(Debug)]
+pub struct PhysicsWorld {
+    /// The gravity applied to the dynamic bodies
+    pub gravity: Vector3<f32>,
+    #[derivative(Debug = "ignore")]
+    pipeline: PhysicsPipeline,
+    #[derivative(Debug = "ignore")]
+    integration_parameters: IntegrationParameters,
+    #[derivative(Debug = "ignore")]
+    broad_phase: BroadPhase,
+    #[derivative(Debug = "ignore")]
+    narrow_phase: NarrowPhase,
+    #[derivative(Debug = "ignore")]
+    bodies: RigidBodySet,
+    #[derivative(Debug = "ignore")]
+    colliders: ColliderSet,
+    #[derivative(Debug = "ignore")]
+    joints: JointSet,
+    #[derivative(Debug = "ignore")]
+    query_pipeline: QueryPipeline,
+    #[derivative(Debug = "ignore")]
+    event_collector: ChannelEventCollector,
+    #[derivative(Debug = "ignore")]
+    contact_events: Receiver<ContactEvent>,
+    #[derivative(Debug = "ignore")]
+    intersection_events: Receiver<IntersectionEvent>,
+    bodies_of: FnvHashMap<Entity, RigidBodyHandle>,
+    // the static bodies created for colliders without a rigid body
+    static_bodies_of: FnvHashMap<Entity, RigidBodyHandle>,
+    colliders_of: FnvHashMap<Entity, ColliderHandle>,
+    entities: FnvHashMap<ColliderHandle, Entity>,
+}
+
+impl Default for PhysicsWorld {
+    fn default() -> Self {
+        PhysicsWorld::new(Vector3::new(0.0, -9.81, 0.0))
+    }
+}
+
+impl PhysicsWorld {
+    /// Creates an empty world with the given gravity.
+    pub fn new(gravity: Vector3<f32>) -> Self {
+        let (intersection_send, intersection_events) = unbounded();
+        let (contact_send, contact_events) = unbounded();
+        PhysicsWorld {
+            gravity,
+            pipeline: PhysicsPipeline::new(),
+            integration_parameters: IntegrationParameters::default(),
+            broad_phase: BroadPhase::new(),
+            narrow_phase: NarrowPhase::new(),
+            bodies: RigidBodySet::new(),
+            colliders: ColliderSet::new(),
+            joints: JointSet::new(),
+            query_pipeline: QueryPipeline::new(),
+            event_collector: ChannelEventCollector::new(intersection_send, contact_send),
+            contact_events,
+            intersection_events,
+            bodies_of: FnvHashMap::default(),
+            static_bodies_of: FnvHashMap::default(),
+            colliders_of: FnvHashMap::default(),
+            entities: FnvHashMap::default(),
+        }
+    }
+
+    /// The rigid bodies.
+    pub fn bodies(&self) -> &RigidBodySet {
+        &self.bodies
+    }
+
+    /// The rigid bodies, to apply forces or change their velocities.
+    pub fn bodies_mut(&mut self) -> &mut RigidBodySet {
+        &mut self.bodies
+    }
+
+    /// The colliders.
+    pub fn colliders(&self) -> &ColliderSet {
+        &self.colliders
+    }
+
+    /// The parameters of the simulation. The timestep is always the fixed timestep of `Time`.
+    pub fn integration_parameters_mut(&mut self) -> &mut IntegrationParameters {
+        &mut self.integration_parameters
+    }
+
+    /// Links two rigid bodies with a joint.
+    pub fn insert_joint<J>(
+        &mut self,
+        body1: RigidBodyHandle,
+        body2: RigidBodyHandle,
+        joint: J,
+    ) -> JointHandle
+    where
+        J: Into<JointParams>,
+    {
+        self.joints.insert(&mut self.bodies, body1, body2, joint)
+    }
+
+    /// The rigid body of an entity, including the static body created for a collider without
+    /// one.
+    pub fn body_of(&self, entity: Entity) -> Option<RigidBodyHandle> {
+        self.bodies_of
+            .get(&entity)
+            .or_else(|| self.static_bodies_of.get(&entity))
+            .copied()
+    }
+
+    /// The entity of a collider.
+    pub fn entity(&self, collider: ColliderHandle) -> Option<Entity> {
+        self.entities.get(&collider).copied()
+    }
+
+    /// Casts a ray, returning the closest collider of the groups it hits within `max_toi` times
+    /// its direction. A ray starting inside a collider hits it at its origin.
+    pub fn cast_ray(
+        &self,
+        origin: Point3<f32>,
+        direction: Vector3<f32>,
+        max_toi: f32,
+        groups: InteractionGroups,
+    ) -> Option<RayHit> {
+        let ray = Ray::new(origin, direction);
+        let (collider, intersection) = self.query_pipeline.cast_ray_and_get_normal(
+            &self.colliders,
+            &ray,
+            max_toi,
+            true,
+            groups,
+        )?;
+        Some(RayHit {
+            entity: self.entity(collider)?,
+            collider,
+            toi: intersection.toi,
+            point: ray.point_at(intersection.toi),
+            normal: intersection.normal,
+        })
+    }
+
+    /// The entities of the colliders of the groups intersecting a shape at the given position.
+    pub fn intersections_with_shape(
+        &self,
+        position: &Isometry3<f32>,
+        shape: &dyn Shape,
+        groups: InteractionGroups,
+    ) -> Vec<Entity> {
+        let mut entities = Vec::new();
+        self.query_pipeline.intersections_with_shape(
+            &self.colliders,
+            position,
+            shape,
+            groups,
+            |collider, _| {
+                entities.extend(self.entity(collider));
+                true
+            },
+        );
+        entities
+    }
+
+    pub(crate) fn insert_body(
+        &mut self,
+        entity: Entity,
+        builder: &RigidBodyBuilder,
+        position: Isometry3<f32>,
+    ) -> RigidBodyHandle {
+        if let Some(body) = self.static_bodies_of.remove(&entity) {
+            // the `PhysicsSystem` adds the colliders again, to the new body
+            self.bodies
+                .remove(body, &mut self.colliders, &mut self.joints);
+            self.forget_removed_colliders();
+        }
+        let mut body = builder.build();
+        body.set_position(position, true);
+        let handle = self.bodies.insert(body);
+        self.bodies_of.insert(entity, handle);
+        handle
+    }
+
+    /// Moves the static body created for the collider of an entity without a rigid body.
+    pub(crate) fn move_static_body(&mut self, entity: Entity, position: &Isometry3<f32>) {
+        let handle = match self.static_bodies_of.get(&entity) {
+            Some(handle) => *handle,
+            None => return,
+        };
+        if let Some(body) = self.bodies.get_mut(handle) {
+            if body.position() != position {
+                body.set_position(*position, true);
+            }
+        }
+    }
+
+    pub(crate) fn insert_collider(
+        &mut self,
+        entity: Entity,
+        builder: &ColliderBuilder,
+        position: Isometry3<f32>,
+    ) -> ColliderHandle {
+        let parent = match self.bodies_of.get(&entity) {
+            Some(parent) => *parent,
+            None => {
+                let mut body = RigidBodyBuilder::new_static().build();
+                body.set_position(position, true);
+                let parent = self.bodies.insert(body);
+                self.static_bodies_of.insert(entity, parent);
+                parent
+            }
+        };
+        let handle = self
+            .colliders
+            .insert(builder.build(), parent, &mut self.bodies);
+        self.colliders_of.insert(entity, handle);
+        self.entities.insert(handle, entity);
+        handle
+    }
+
+    /// Removes the bodies, with their colliders, of the entities which don't have a `RigidBody`
+    /// anymore.
+    pub(crate) fn retain_bodies(&mut self, mut keep: impl FnMut(Entity) -> bool) {
+        let bodies = &mut self.bodies;
+        let colliders = &mut self.colliders;
+        let joints = &mut self.joints;
+        self.bodies_of.retain(|entity, handle| {
+            keep(*entity) || {
+                bodies.remove(*handle, colliders, joints);
+                false
+            }
+        });
+        self.forget_removed_colliders();
+    }
+
+    /// Removes the colliders of the entities which don't have a `Collider` anymore.
+    pub(crate) fn retain_colliders(&mut self, mut keep: impl FnMut(Entity) -> bool) {
+        let bodies = &mut self.bodies;
+        let colliders = &mut self.colliders;
+        let joints = &mut self.joints;
+        let static_bodies_of = &mut self.static_bodies_of;
+        self.colliders_of.retain(|entity, handle| {
+            keep(*entity) || {
+                colliders.remove(*handle, bodies, true);
+                if let Some(body) = static_bodies_of.remove(entity) {
+                    bodies.remove(body, colliders, joints);
+                }
+                false
+            }
+        });
+        self.forget_removed_colliders();
+    }
+
+    fn forget_removed_colliders(&mut self) {
+        let colliders = &self.colliders;
+        self.colliders_of
+            .retain(|_, handle| colliders.get(*handle).is_some());
+        self.entities
+            .retain(|handle, _| colliders.get(*handle).is_some());
+    }
+
+    pub(crate) fn step(&mut self, dt: f32) {
+        self.integration_parameters.dt = dt;
+        self.pipeline.step(
+            &self.gravity,
+            &self.integration_parameters,
+            &mut self.broad_phase,
+            &mut self.narrow_phase,
+            &mut self.bodies,
+            &mut self.colliders,
+            &mut self.joints,
+            None,
+            None,
+            &self.event_collector,
+        );
+    }
+
+    pub(crate) fn update_queries(&mut self) {
+        self.query_pipeline.update(&self.bodies, &self.colliders);
+    }
+
+    /// The events of the steps since the last call, between colliders which still exist.
+    pub(crate) fn drain_events(&self, mut send: impl FnMut(PhysicsEvent)) {
+        while let Ok(event) = self.contact_events.try_recv() {
+            let (collider1, collider2, started) = match event {
+                ContactEvent::Started(collider1, collider2) => (collider1, collider2, true),
+                ContactEvent::Stopped(collider1, collider2) => (collider1, collider2, false),
+            };
+            if let (Some(entity1), Some(entity2)) = (self.entity(collider1), self.entity(collider2))
+            {
+                send(if started {
+                    PhysicsEvent::ContactStarted(entity1, entity2)
+                } else {
+                    PhysicsEvent::ContactStopped(entity1, entity2)
+                });
+            }
+        }
+        while let Ok(event) = self.intersection_events.try_recv() {
+            if let (Some(entity1), Some(entity2)) =
+                (self.entity(event.collider1), self.entity(event.collider2))
+            {
+                send(if event.intersecting {
+                    PhysicsEvent::TriggerEntered(entity1, entity2)
+                } else {
+                    PhysicsEvent::TriggerExited(entity1, entity2)
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use amethyst_core::ecs::World;
+
+    use super::*;
+
+    #[test]
+    fn dynamic_bodies_fall_onto_static_colliders() {
+        let mut world = World::default();
+        let ground = world.push(());
+        let ball = world.push(());
+
+        let mut physics = PhysicsWorld::default();
+        physics.insert_collider(
+            ground,
+            &ColliderBuilder::cuboid(10.0, 0.5, 10.0),
+            Isometry3::identity(),
+        );
+        let body = physics.insert_body(
+            ball,
+            &RigidBodyBuilder::new_dynamic(),
+            Isometry3::translation(0.0, 5.0, 0.0),
+        );
+        physics.insert_collider(ball, &ColliderBuilder::ball(0.5), Isometry3::identity());
+
+        let mut events = Vec::new();
+        for _ in 0..300 {
+            physics.step(1.0 / 60.0);
+            physics.drain_events(|event| events.push(event));
+        }
+        physics.update_queries();
+
+        let height = physics.bodies().get(body).unwrap().position().translation.y;
+        assert!(height < 5.0 && height > 0.5, "ball at {}", height);
+        assert!(events
+            .iter()
+            .any(|event| matches!(event, PhysicsEvent::ContactStarted(..))));
+
+        let hit = physics
+            .cast_ray(
+                Point3::new(3.0, 5.0, 0.0),
+                -Vector3::y(),
+                10.0,
+                InteractionGroups::all(),
+            )
+            .unwrap();
+        assert_eq!(hit.entity, ground);
+        assert!((hit.point.y - 0.5).abs() < 1e-3);
+    }
+
+    #[test]
+    fn removed_entities_leave_the_world() {
+        let mut world = World::default();
+        let ground = world.push(());
+
+        let mut physics = PhysicsWorld::default();
+        let collider = physics.insert_collider(
+            ground,
+            &ColliderBuilder::cuboid(1.0, 1.0, 1.0),
+            Isometry3::identity(),
+        );
+        assert!(physics.body_of(ground).is_some());
+
+        physics.retain_colliders(|_| false);
+        assert!(physics.colliders().get(collider).is_none());
+        assert!(physics.body_of(ground).is_none());
+        assert_eq!(physics.bodies().len(), 0);
+    }
+
+    #[test]
+    fn colliders_without_bodies_follow_their_entity() {
+        let mut world = World::default();
+        let entity = world.push(());
+
+        let mut physics = PhysicsWorld::default();
+        let collider =
+            physics.insert_collider(entity, &ColliderBuilder::ball(0.5), Isometry3::identity());
+        let position = Isometry3::translation(0.0, 2.0, 0.0);
+        physics.move_static_body(entity, &position);
+        let fixed = physics.body_of(entity).unwrap();
+        assert_eq!(physics.bodies().get(fixed).unwrap().position(), &position);
+
+        let body = physics.insert_body(entity, &RigidBodyBuilder::new_dynamic(), position);
+        assert!(physics.colliders().get(collider).is_none());
+        assert_eq!(physics.body_of(entity), Some(body));
+        assert_eq!(physics.bodies().len(), 1);
+
+        let collider =
+            physics.insert_collider(entity, &ColliderBuilder::ball(0.5), Isometry3::identity());
+        assert_eq!(physics.colliders().get(collider).unwrap().parent(), body);
+        assert_eq!(physics.bodies().len(), 1);
+    }
+}
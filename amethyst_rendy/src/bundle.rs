@@ -11,11 +11,15 @@ use crate::{
     camera::ActiveCamera,
     mtl::{Material, MaterialDefaults},
     rendy::{
-        command::QueueId,
+        command::{QueueId, RenderPassEncoder},
         factory::Factory,
         graph::{
-            render::{RenderGroupBuilder, RenderPassNodeBuilder, SubpassBuilder},
-            GraphBuilder, ImageId, NodeId,
+            render::{
+                PrepareResult, RenderGroup, RenderGroupBuilder, RenderPassNodeBuilder,
+                SubpassBuilder,
+            },
+            BufferAccess, BufferId, GraphBuilder, GraphContext, ImageAccess, ImageId, NodeBuffer,
+            NodeId, NodeImage,
         },
         hal,
         wsi::Surface,
@@ -688,7 +692,10 @@ impl<B: Backend> TargetPlan<B> {
         for action in actions.drain(..).map(|a| a.1) {
             match action {
                 RenderableAction::RenderGroup(group) => {
-                    subpass.add_dyn_group(group);
+                    subpass.add_dyn_group(Box::new(TargetGroupBuilder {
+                        target: self.key,
+                        inner: group,
+                    }));
                 }
             }
         }
@@ -759,6 +766,108 @@ impl<B: Backend, G: RenderGroupBuilder<B, GraphAuxData> + 'static> IntoAction<B>
     }
 }
 
+// Builds the render group with the target it draws to in the `GraphAuxData`.
+#[derive(Debug)]
+struct TargetGroupBuilder<B: Backend> {
+    target: Target,
+    inner: Box<dyn RenderGroupBuilder<B, GraphAuxData>>,
+}
+
+impl<B: Backend> RenderGroupBuilder<B, GraphAuxData> for TargetGroupBuilder<B> {
+    fn colors(&self) -> usize {
+        self.inner.colors()
+    }
+
+    fn depth(&self) -> bool {
+        self.inner.depth()
+    }
+
+    fn buffers(&self) -> Vec<(BufferId, BufferAccess)> {
+        self.inner.buffers()
+    }
+
+    fn images(&self) -> Vec<(ImageId, ImageAccess)> {
+        self.inner.images()
+    }
+
+    fn dependencies(&self) -> Vec<NodeId> {
+        self.inner.dependencies()
+    }
+
+    fn build<'a>(
+        self: Box<Self>,
+        ctx: &GraphContext<B>,
+        factory: &mut Factory<B>,
+        queue: QueueId,
+        aux: &GraphAuxData,
+        framebuffer_width: u32,
+        framebuffer_height: u32,
+        subpass: hal::pass::Subpass<'_, B>,
+        buffers: Vec<NodeBuffer>,
+        images: Vec<NodeImage>,
+    ) -> Result<Box<dyn RenderGroup<B, GraphAuxData>>, hal::pso::CreationError> {
+        let target = self.target;
+        let inner = self.inner.build(
+            ctx,
+            factory,
+            queue,
+            &GraphAuxData { target, ..*aux },
+            framebuffer_width,
+            framebuffer_height,
+            subpass,
+            buffers,
+            images,
+        )?;
+        Ok(Box::new(TargetGroup { target, inner }))
+    }
+}
+
+// Passes the target it draws to to the render group, so it renders from the camera of the target.
+#[derive(Debug)]
+struct TargetGroup<B: Backend> {
+    target: Target,
+    inner: Box<dyn RenderGroup<B, GraphAuxData>>,
+}
+
+impl<B: Backend> RenderGroup<B, GraphAuxData> for TargetGroup<B> {
+    fn prepare(
+        &mut self,
+        factory: &Factory<B>,
+        queue: QueueId,
+        index: usize,
+        subpass: hal::pass::Subpass<'_, B>,
+        aux: &GraphAuxData,
+    ) -> PrepareResult {
+        let aux = GraphAuxData {
+            target: self.target,
+            ..*aux
+        };
+        self.inner.prepare(factory, queue, index, subpass, &aux)
+    }
+
+    fn draw_inline(
+        &mut self,
+        encoder: RenderPassEncoder<'_, B>,
+        index: usize,
+        subpass: hal::pass::Subpass<'_, B>,
+        aux: &GraphAuxData,
+    ) {
+        let aux = GraphAuxData {
+            target: self.target,
+            ..*aux
+        };
+        self.inner.draw_inline(encoder, index, subpass, &aux);
+    }
+
+    fn dispose(self: Box<Self>, factory: &mut Factory<B>, aux: &GraphAuxData) {
+        let aux = GraphAuxData {
+            target: self.target,
+            ..*aux
+        };
+        self.inner.dispose(factory, &aux);
+    }
+}
+
 /// Collection of predefined constants for action ordering in the builtin targets.
 /// Two actions with the same order will be applied in their insertion order.
 /// The list is provided mostly as a comparison point. If you can't find the exact
@@ -8,6 +8,8 @@ use amethyst_core::{
 };
 use type_uuid::TypeUuid;
 
+use crate::bundle::Target;
+
 /// Camera struct.
 ///
 /// Contains a projection matrix to convert from world/eye-space
@@ -240,6 +242,13 @@ pub struct ActiveCamera {
     pub entity: Option<Entity>,
 }
 
+/// Component rendering a target other than `Target::Main` from the camera of its entity, such
+/// as the target presented to an additional window by `RenderToWindow::for_window`.
+///
+/// Targets without such a camera, and `Target::Main`, are rendered from the `ActiveCamera`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CameraTarget(pub Target);
+
 #[cfg(test)]
 mod tests {
     //! Tests for amethysts camera implementation.
@@ -0,0 +1,209 @@
+//! Loading meshes from binary FBX files.
+use std::io::Cursor;
+
+use amethyst_assets::Format;
+use amethyst_error::{format_err, Error};
+use fbxcel_dom::{
+    any::AnyDocument,
+    v7400::{
+        data::mesh::{layer::TypedLayerElementHandle, PolygonVertexIndex, PolygonVertices},
+        object::{geometry::TypedGeometryHandle, TypedObjectHandle},
+        Document,
+    },
+};
+use rendy::mesh::{MeshBuilder, Normal, Position, TexCoord};
+use serde::{Deserialize, Serialize};
+use type_uuid::TypeUuid;
+
+use crate::types::MeshData;
+
+/// 'Fbx' mesh format `Format` implementation, enabled by the `fbx` feature.
+///
+/// Loads the first mesh geometry of a binary FBX file, with the normals and texture coordinates
+/// of its first layer. Polygons are split into triangle fans, so they must be convex. The ASCII
+/// variant of the format isn't supported.
+#[derive(
+    Debug,
+    Default,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Serialize,
+    Deserialize,
+    TypeUuid,
+)]
+#[uuid = "3e4b8e9a-5a8f-4f55-9c4e-2f0d0c7a61b3"]
+pub struct FbxFormat;
+
+amethyst_assets::register_importer!(".fbx", FbxFormat);
+impl Format<MeshData> for FbxFormat {
+    fn name(&self) -> &'static str {
+        "FBX"
+    }
+
+    fn import_simple(&self, bytes: Vec<u8>) -> Result<MeshData, Error> {
+        let document = match AnyDocument::from_seekable_reader(Cursor::new(bytes))? {
+            AnyDocument::V7400(_, document) => document,
+            _ => return Err(format_err!("Unsupported FBX version")),
+        };
+        let (positions, normals, tex_coords) = load_vertices(&document)?;
+        let mut builder = MeshBuilder::new();
+        builder.add_vertices(positions);
+        builder.add_vertices(normals);
+        builder.add_vertices(tex_coords);
+        Ok(builder.into())
+    }
+}
+
+/// The positions, normals and texture coordinates of the vertices of a mesh.
+type Vertices = (Vec<Position>, Vec<Normal>, Vec<TexCoord>);
+
+/// The triangulated vertices of the first mesh of a document.
+fn load_vertices(document: &Document) -> Result<Vertices, Error> {
+    let mut meshes = document.objects().filter_map(|object| {
+        match object.get_typed() {
+            TypedObjectHandle::Geometry(TypedGeometryHandle::Mesh(mesh)) => Some(mesh),
+            _ => None,
+        }
+    });
+    let mesh = meshes
+        .next()
+        .ok_or_else(|| format_err!("FBX file contains no mesh"))?;
+    if meshes.next().is_some() {
+        log::warn!("FBX file contains more than one mesh, only loading the first");
+    }
+
+    let triangles = mesh
+        .polygon_vertices()
+        .and_then(|polygons| polygons.triangulate_each(triangulate))
+        .map_err(|e| format_err!("Invalid FBX mesh: {}", e))?;
+    let mut normals = None;
+    let mut tex_coords = None;
+    if let Some(layer) = mesh.layers().next() {
+        for entry in layer.layer_element_entries() {
+            // layer elements of types unknown to the parser are skipped
+            match entry.typed_layer_element() {
+                Ok(TypedLayerElementHandle::Normal(handle)) if normals.is_none() => {
+                    normals = handle.normals().ok();
+                }
+                Ok(TypedLayerElementHandle::Uv(handle)) if tex_coords.is_none() => {
+                    tex_coords = handle.uv().ok();
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let mut positions = Vec::with_capacity(triangles.len());
+    let mut vertex_normals = Vec::with_capacity(triangles.len());
+    let mut vertex_tex_coords = Vec::with_capacity(triangles.len());
+    for vertex in triangles.triangle_vertex_indices() {
+        let position = triangles.control_point(vertex).ok_or_else(|| {
+            format_err!("FBX polygon vertex {} has no position", vertex.to_usize())
+        })?;
+        positions.push(Position([
+            position.x as f32,
+            position.y as f32,
+            position.z as f32,
+        ]));
+        let normal = normals
+            .as_ref()
+            .and_then(|normals| normals.normal(&triangles, vertex).ok())
+            .map_or([0.0; 3], |n| [n.x as f32, n.y as f32, n.z as f32]);
+        vertex_normals.push(Normal(normal));
+        let tex_coord = tex_coords
+            .as_ref()
+            .and_then(|tex_coords| tex_coords.uv(&triangles, vertex).ok())
+            .map_or([0.0; 2], |uv| [uv.x as f32, uv.y as f32]);
+        vertex_tex_coords.push(TexCoord(tex_coord));
+    }
+    Ok((positions, vertex_normals, vertex_tex_coords))
+}
+
+fn triangulate(
+    _: &PolygonVertices<'_>,
+    polygon: &[PolygonVertexIndex],
+    triangles: &mut Vec<[PolygonVertexIndex; 3]>,
+) -> Result<(), anyhow::Error> {
+    triangle_fan(polygon, triangles);
+    Ok(())
+}
+
+/// Splits a convex polygon into triangles sharing its first vertex. Polygons of less than three
+/// vertices, such as the edges of wireframes, are skipped.
+fn triangle_fan<T: Copy>(polygon: &[T], triangles: &mut Vec<[T; 3]>) {
+    for pair in polygon.get(1..).unwrap_or_default().windows(2) {
+        triangles.push([polygon[0], pair[0], pair[1]]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use fbxcel::{low::v7400::AttributeValue, tree_v7400};
+    use fbxcel_dom::v7400::Loader;
+
+    use super::*;
+
+    #[test]
+    fn polygons_are_split_into_fans() {
+        let mut triangles = Vec::new();
+        triangle_fan(&[0, 1, 2, 3, 4], &mut triangles);
+        triangle_fan(&[5, 6], &mut triangles);
+        triangle_fan::<u32>(&[], &mut triangles);
+        assert_eq!(triangles, vec![[0, 1, 2], [0, 2, 3], [0, 3, 4]]);
+    }
+
+    #[test]
+    fn quads_are_loaded_with_their_normals() {
+        let tree = tree_v7400! {
+            Documents: {
+                Document: [1i64, "", "Scene"] {}
+            }
+            Definitions: {}
+            Objects: {
+                Geometry: [2i64, "Quad\u{0}\u{1}Geometry", "Mesh"] {
+                    Vertices: (vec![AttributeValue::from(vec![
+                        0.0f64, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 1.0, 0.0, 0.0, 1.0, 0.0,
+                    ])]) {}
+                    // the last vertex of a polygon is stored as its bitwise complement
+                    PolygonVertexIndex: (vec![AttributeValue::from(vec![0i32, 1, 2, !3])]) {}
+                    LayerElementNormal: [0i32] {
+                        MappingInformationType: ["ByControlPoint"] {}
+                        ReferenceInformationType: ["Direct"] {}
+                        Normals: (vec![AttributeValue::from(vec![
+                            0.0f64, 0.0, 1.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0,
+                        ])]) {}
+                    }
+                    Layer: [0i32] {
+                        LayerElement: {
+                            Type: ["LayerElementNormal"] {}
+                            TypedIndex: [0i32] {}
+                        }
+                    }
+                }
+            }
+            Connections: {}
+        };
+        let document = Loader::new().load_from_tree(tree).unwrap();
+
+        let (positions, normals, tex_coords) = load_vertices(&document).unwrap();
+        let positions: Vec<_> = positions.iter().map(|p| p.0).collect();
+        assert_eq!(
+            positions,
+            vec![
+                [0.0, 0.0, 0.0],
+                [1.0, 0.0, 0.0],
+                [1.0, 1.0, 0.0],
+                [0.0, 0.0, 0.0],
+                [1.0, 1.0, 0.0],
+                [0.0, 1.0, 0.0],
+            ]
+        );
+        assert!(normals.iter().all(|n| n.0 == [0.0, 0.0, 1.0]));
+        assert!(tex_coords.iter().all(|t| t.0 == [0.0, 0.0]));
+    }
+}
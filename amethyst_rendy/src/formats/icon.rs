@@ -0,0 +1,87 @@
+//! Window icons loaded as assets.
+use amethyst_assets::{
+    register_asset_type, register_importer, Asset, AssetProcessorSystem, Format,
+};
+use amethyst_error::Error;
+use amethyst_window::{Icon, WindowCommand};
+use serde::{Deserialize, Serialize};
+use type_uuid::TypeUuid;
+
+/// The pixels of a window icon.
+///
+/// Once loaded, set it as the icon of the window with `WindowCommand::SetIcon`, e.g. with
+/// `WindowIcon::command`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, TypeUuid)]
+#[uuid = "b8cee07d-9a30-4420-ade6-88af7b5ac540"]
+pub struct WindowIcon {
+    /// The pixels, in rows of RGBA bytes from the top left corner
+    pub rgba: Vec<u8>,
+    /// The width of the icon in pixels
+    pub width: u32,
+    /// The height of the icon in pixels
+    pub height: u32,
+}
+
+register_asset_type!(WindowIcon => WindowIcon; AssetProcessorSystem<WindowIcon>);
+
+impl Asset for WindowIcon {
+    fn name() -> &'static str {
+        "renderer::WindowIcon"
+    }
+    type Data = Self;
+}
+
+impl WindowIcon {
+    /// Decodes an image file, in any of the formats textures are loaded from or as an `.ico`
+    /// file, in which case the largest image is used.
+    pub fn from_image(bytes: &[u8]) -> Result<Self, Error> {
+        let image = image::load_from_memory(bytes)?.to_rgba8();
+        Ok(WindowIcon {
+            width: image.width(),
+            height: image.height(),
+            rgba: image.into_raw(),
+        })
+    }
+
+    /// Creates the icon of a window.
+    pub fn to_icon(&self) -> Result<Icon, Error> {
+        Ok(Icon::from_rgba(self.rgba.clone(), self.width, self.height)?)
+    }
+
+    /// The command setting this icon as the icon of the window.
+    pub fn command(&self) -> Result<WindowCommand, Error> {
+        Ok(WindowCommand::SetIcon(Some(self.to_icon()?)))
+    }
+}
+
+/// Format for loading a `WindowIcon` from an image file.
+///
+/// `.ico` files are imported as icons, other images are imported as textures unless this format
+/// is given when loading them.
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Serialize,
+    Deserialize,
+    TypeUuid,
+)]
+#[uuid = "b93adbc7-f793-4a1d-b3e3-f5ec8727def9"]
+pub struct IconFormat;
+
+register_importer!(".ico", IconFormat);
+impl Format<WindowIcon> for IconFormat {
+    fn name(&self) -> &'static str {
+        "ICON"
+    }
+
+    fn import_simple(&self, bytes: Vec<u8>) -> Result<WindowIcon, Error> {
+        WindowIcon::from_image(&bytes)
+    }
+}
@@ -1,3 +1,8 @@
 //! Pre-defined graphical formats and data provided by amethyst_rendy
+#[cfg(feature = "fbx")]
+pub mod fbx;
+#[cfg(feature = "window")]
+pub mod icon;
 pub mod mesh;
+pub mod mtl;
 pub mod texture;
@@ -0,0 +1,235 @@
+//! Module for the Wavefront `.mtl` material libraries of `.obj` meshes.
+use std::str::FromStr;
+
+use amethyst_assets::{
+    register_asset_type, register_importer, Asset, AssetProcessorSystem, Format, Handle,
+};
+use amethyst_error::{format_err, Error};
+use palette::Srgba;
+use serde::{Deserialize, Serialize};
+use type_uuid::TypeUuid;
+
+use crate::{mtl::Material, types::Texture};
+
+/// A material of a `MtlLibrary`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MtlMaterial {
+    /// Name of the material, referenced by the `usemtl` statements of `.obj` files
+    pub name: String,
+    /// Ambient color (`Ka`)
+    pub ambient: [f32; 3],
+    /// Diffuse color (`Kd`)
+    pub diffuse: [f32; 3],
+    /// Specular color (`Ks`)
+    pub specular: [f32; 3],
+    /// Emissive color (`Ke`)
+    pub emissive: [f32; 3],
+    /// Specular exponent (`Ns`)
+    pub shininess: f32,
+    /// Opacity, from `d` or the inverse of `Tr`
+    pub dissolve: f32,
+    /// Path of the diffuse texture (`map_Kd`), relative to the library
+    pub diffuse_map: Option<String>,
+    /// Path of the emissive texture (`map_Ke`), relative to the library
+    pub emissive_map: Option<String>,
+    /// Path of the normal map (`norm`, `map_Bump` or `bump`), relative to the library
+    pub normal_map: Option<String>,
+}
+
+impl Default for MtlMaterial {
+    fn default() -> Self {
+        MtlMaterial {
+            name: String::new(),
+            ambient: [0.0; 3],
+            diffuse: [1.0; 3],
+            specular: [0.0; 3],
+            emissive: [0.0; 3],
+            shininess: 0.0,
+            dissolve: 1.0,
+            diffuse_map: None,
+            emissive_map: None,
+            normal_map: None,
+        }
+    }
+}
+
+impl MtlMaterial {
+    /// Build a `Material` from this one. `texture` loads the texture at a path of the library,
+    /// and `color` creates a single color texture. Maps missing from the library are taken
+    /// from `defaults`.
+    pub fn to_material<T, C>(&self, defaults: &Material, mut texture: T, mut color: C) -> Material
+    where
+        T: FnMut(&str) -> Handle<Texture>,
+        C: FnMut(Srgba) -> Handle<Texture>,
+    {
+        let [r, g, b] = self.diffuse;
+        let albedo = match self.diffuse_map {
+            Some(ref path) => texture(path),
+            None => color(Srgba::new(r, g, b, self.dissolve)),
+        };
+        let [r, g, b] = self.emissive;
+        let emission = match self.emissive_map {
+            Some(ref path) => texture(path),
+            None if self.emissive != [0.0; 3] => color(Srgba::new(r, g, b, 1.0)),
+            None => defaults.emission.clone(),
+        };
+        let normal = match self.normal_map {
+            Some(ref path) => texture(path),
+            None => defaults.normal.clone(),
+        };
+        Material {
+            albedo,
+            emission,
+            normal,
+            ..defaults.clone()
+        }
+    }
+}
+
+/// Materials of a Wavefront `.mtl` file.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, TypeUuid)]
+#[uuid = "25a44127-c90a-486b-ab3b-c3918bc6892f"]
+pub struct MtlLibrary {
+    /// The materials, in the order of the file
+    pub materials: Vec<MtlMaterial>,
+}
+
+register_asset_type!(MtlLibrary => MtlLibrary; AssetProcessorSystem<MtlLibrary>);
+
+impl Asset for MtlLibrary {
+    fn name() -> &'static str {
+        "renderer::MtlLibrary"
+    }
+    type Data = Self;
+}
+
+impl MtlLibrary {
+    /// Get the material with the given name.
+    pub fn get(&self, name: &str) -> Option<&MtlMaterial> {
+        self.materials.iter().find(|material| material.name == name)
+    }
+
+    /// Parse the contents of a `.mtl` file. Unsupported statements are ignored.
+    pub fn parse(source: &str) -> Result<Self, Error> {
+        let mut materials = Vec::new();
+        for (number, line) in source.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            let mut words = line.split_whitespace();
+            let statement = match words.next() {
+                Some(statement) => statement,
+                None => continue,
+            };
+            let arguments = words.collect::<Vec<_>>();
+            let error = || format_err!("Invalid `{}` statement at line {}", statement, number + 1);
+
+            if statement == "newmtl" {
+                materials.push(MtlMaterial {
+                    name: arguments.join(" "),
+                    ..Default::default()
+                });
+                continue;
+            }
+            let material = materials.last_mut().ok_or_else(error)?;
+            match statement {
+                "Ka" => material.ambient = parse_color(&arguments).ok_or_else(error)?,
+                "Kd" => material.diffuse = parse_color(&arguments).ok_or_else(error)?,
+                "Ks" => material.specular = parse_color(&arguments).ok_or_else(error)?,
+                "Ke" => material.emissive = parse_color(&arguments).ok_or_else(error)?,
+                "Ns" => material.shininess = parse(&arguments).ok_or_else(error)?,
+                "d" => material.dissolve = parse(&arguments).ok_or_else(error)?,
+                "Tr" => material.dissolve = 1.0 - parse::<f32>(&arguments).ok_or_else(error)?,
+                "map_Kd" => {
+                    material.diffuse_map = Some(texture_path(&arguments).ok_or_else(error)?)
+                }
+                "map_Ke" => {
+                    material.emissive_map = Some(texture_path(&arguments).ok_or_else(error)?)
+                }
+                "norm" | "map_Bump" | "map_bump" | "bump" => {
+                    material.normal_map = Some(texture_path(&arguments).ok_or_else(error)?)
+                }
+                _ => {}
+            }
+        }
+        Ok(MtlLibrary { materials })
+    }
+}
+
+fn parse<T: FromStr>(arguments: &[&str]) -> Option<T> {
+    arguments.first().and_then(|argument| argument.parse().ok())
+}
+
+fn parse_color(arguments: &[&str]) -> Option<[f32; 3]> {
+    let r = parse(arguments)?;
+    // a single value is used for all the channels
+    let g = arguments.get(1).map_or(Some(r), |g| g.parse().ok())?;
+    let b = arguments.get(2).map_or(Some(r), |b| b.parse().ok())?;
+    Some([r, g, b])
+}
+
+// The path is the last argument, after the texture options such as `-bm 1.0`
+fn texture_path(arguments: &[&str]) -> Option<String> {
+    arguments.last().map(|path| (*path).to_string())
+}
+
+/// 'Mtl' material library `Format` implementation.
+#[derive(
+    Debug,
+    Default,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Serialize,
+    Deserialize,
+    TypeUuid,
+)]
+#[uuid = "c9d692e6-9931-4fca-8c9d-0fa8892f8855"]
+pub struct MtlFormat;
+
+register_importer!(".mtl", MtlFormat);
+impl Format<MtlLibrary> for MtlFormat {
+    fn name(&self) -> &'static str {
+        "MTL"
+    }
+
+    fn import_simple(&self, bytes: Vec<u8>) -> Result<MtlLibrary, Error> {
+        MtlLibrary::parse(&String::from_utf8(bytes)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_materials() {
+        let library = MtlLibrary::parse(
+            "# exported\n\
+             newmtl brick\n\
+             Kd 0.5 0.25 0.125\n\
+             Ke 1\n\
+             d 0.75\n\
+             map_Kd -bm 1.0 textures/brick.png\n\
+             illum 2\n\
+             \n\
+             newmtl glass\n\
+             Tr 0.9\n",
+        )
+        .unwrap();
+
+        let brick = library.get("brick").unwrap();
+        assert_eq!(brick.diffuse, [0.5, 0.25, 0.125]);
+        assert_eq!(brick.emissive, [1.0; 3]);
+        assert_eq!(brick.dissolve, 0.75);
+        assert_eq!(brick.diffuse_map.as_deref(), Some("textures/brick.png"));
+        let glass = library.get("glass").unwrap();
+        assert!((glass.dissolve - 0.1).abs() < 1e-6);
+        assert_eq!(glass.diffuse, [1.0; 3]);
+
+        assert!(MtlLibrary::parse("Kd 1 1 1").is_err());
+        assert!(MtlLibrary::parse("newmtl a\nKd red").is_err());
+    }
+}
@@ -63,6 +63,7 @@ pub mod debug_drawing;
 pub mod error;
 pub mod formats;
 pub mod light;
+pub mod morph;
 pub mod mtl;
 pub mod pipeline;
 pub mod plugins;
@@ -101,7 +102,7 @@ pub use rendy::{
 #[doc(inline)]
 pub use crate::{
     bundle::{RenderPlugin, RenderingBundle},
-    camera::{ActiveCamera, Camera},
+    camera::{ActiveCamera, Camera, CameraTarget},
     formats::texture::ImageFormat,
     mtl::{Material, MaterialDefaults},
     plugins::*,
@@ -0,0 +1,195 @@
+//! Morph target (blend shape) implementation for renderer.
+use rendy::mesh::{MeshBuilder, Normal, Position, Tangent};
+
+/// Offsets of the vertices of a mesh for a single morph target, e.g. a smile or a blink
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct MorphTarget {
+    /// Name of the target, if any
+    pub name: Option<String>,
+    /// Position offsets, one per vertex
+    pub positions: Vec<[f32; 3]>,
+    /// Normal offsets, one per vertex, or empty if the target doesn't change the normals
+    pub normals: Vec<[f32; 3]>,
+    /// Tangent offsets, one per vertex, or empty if the target doesn't change the tangents
+    pub tangents: Vec<[f32; 3]>,
+}
+
+/// Morph targets of a mesh, should be attached to mesh entities together with `MorphWeights`.
+///
+/// Holds the base vertex attributes which are morphed, and the rest of the mesh, so the mesh
+/// can be rebuilt with the morphed attributes whenever the weights change.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct MorphTargets {
+    /// Base positions of the vertices
+    pub positions: Vec<Position>,
+    /// Base normals of the vertices, if the mesh has normals
+    pub normals: Option<Vec<Normal>>,
+    /// Base tangents of the vertices, if the mesh has tangents
+    pub tangents: Option<Vec<Tangent>>,
+    /// The indices and the vertex attributes which aren't morphed, such as texture coordinates
+    #[serde(deserialize_with = "crate::types::deserialize_data")]
+    pub attributes: MeshBuilder<'static>,
+    /// The morph targets
+    pub targets: Vec<MorphTarget>,
+}
+
+impl MorphTargets {
+    /// Get the index of the target with the given name
+    pub fn target_index(&self, name: &str) -> Option<usize> {
+        self.targets
+            .iter()
+            .position(|t| t.name.as_ref().map_or(false, |n| n == name))
+    }
+
+    /// Compute the positions of the vertices with the given weights of the targets.
+    pub fn morphed_positions(&self, weights: &[f32]) -> Vec<Position> {
+        self.positions
+            .iter()
+            .enumerate()
+            .map(|(i, p)| Position(self.morph(p.0, i, weights, |t| &t.positions)))
+            .collect()
+    }
+
+    /// Compute the normals of the vertices with the given weights of the targets.
+    pub fn morphed_normals(&self, weights: &[f32]) -> Option<Vec<Normal>> {
+        self.normals.as_ref().map(|normals| {
+            normals
+                .iter()
+                .enumerate()
+                .map(|(i, n)| {
+                    let [x, y, z] = self.morph(n.0, i, weights, |t| &t.normals);
+                    let length = (x * x + y * y + z * z).sqrt();
+                    if length > 0. {
+                        Normal([x / length, y / length, z / length])
+                    } else {
+                        Normal(n.0)
+                    }
+                })
+                .collect()
+        })
+    }
+
+    /// Compute the tangents of the vertices with the given weights of the targets.
+    pub fn morphed_tangents(&self, weights: &[f32]) -> Option<Vec<Tangent>> {
+        self.tangents.as_ref().map(|tangents| {
+            tangents
+                .iter()
+                .enumerate()
+                .map(|(i, t)| {
+                    let [x, y, z, w] = t.0;
+                    let [x, y, z] = self.morph([x, y, z], i, weights, |t| &t.tangents);
+                    Tangent([x, y, z, w])
+                })
+                .collect()
+        })
+    }
+
+    /// Build the mesh with the given weights of the targets.
+    pub fn build_mesh(&self, weights: &[f32]) -> MeshBuilder<'static> {
+        let mut builder = self.attributes.clone();
+        builder.add_vertices(self.morphed_positions(weights));
+        if let Some(normals) = self.morphed_normals(weights) {
+            builder.add_vertices(normals);
+        }
+        if let Some(tangents) = self.morphed_tangents(weights) {
+            builder.add_vertices(tangents);
+        }
+        builder
+    }
+
+    fn morph<F>(&self, base: [f32; 3], vertex: usize, weights: &[f32], offsets: F) -> [f32; 3]
+    where
+        F: Fn(&MorphTarget) -> &Vec<[f32; 3]>,
+    {
+        self.targets
+            .iter()
+            .zip(weights)
+            .filter(|(_, w)| **w != 0.)
+            .fold(base, |value, (target, weight)| {
+                match offsets(target).get(vertex) {
+                    Some(offset) => {
+                        [
+                            value[0] + offset[0] * weight,
+                            value[1] + offset[1] * weight,
+                            value[2] + offset[2] * weight,
+                        ]
+                    }
+                    None => value,
+                }
+            })
+    }
+}
+
+/// Weights of the morph targets of a mesh, for controlling them at runtime or by animation.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MorphWeights {
+    /// The weights, in the order of the targets
+    pub weights: Vec<f32>,
+}
+
+impl MorphWeights {
+    /// Create weights of 0 for the given number of targets
+    pub fn new(count: usize) -> Self {
+        MorphWeights {
+            weights: vec![0.; count],
+        }
+    }
+
+    /// Get the weight of a target, 0 if it isn't set
+    pub fn weight(&self, index: usize) -> f32 {
+        self.weights.get(index).cloned().unwrap_or(0.)
+    }
+
+    /// Set the weight of a target
+    pub fn set_weight(&mut self, index: usize, weight: f32) {
+        if index >= self.weights.len() {
+            self.weights.resize(index + 1, 0.);
+        }
+        self.weights[index] = weight;
+    }
+}
+
+impl From<Vec<f32>> for MorphWeights {
+    fn from(weights: Vec<f32>) -> Self {
+        MorphWeights { weights }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn morphed_attributes_add_weighted_offsets() {
+        let targets = MorphTargets {
+            positions: vec![Position([0., 0., 0.]), Position([1., 0., 0.])],
+            normals: Some(vec![Normal([0., 1., 0.]), Normal([0., 1., 0.])]),
+            tangents: None,
+            attributes: MeshBuilder::new(),
+            targets: vec![
+                MorphTarget {
+                    name: Some("up".into()),
+                    positions: vec![[0., 1., 0.], [0., 2., 0.]],
+                    normals: vec![[1., -1., 0.], [0., 0., 0.]],
+                    tangents: Vec::new(),
+                },
+                MorphTarget {
+                    name: None,
+                    positions: vec![[1., 0., 0.], [1., 0., 0.]],
+                    ..Default::default()
+                },
+            ],
+        };
+        assert_eq!(targets.target_index("up"), Some(0));
+
+        let positions = targets.morphed_positions(&[0.5, 1.]);
+        assert_eq!(
+            positions,
+            vec![Position([1., 0.5, 0.]), Position([2., 1., 0.])]
+        );
+
+        // normals are renormalized
+        let normals = targets.morphed_normals(&[1.]).unwrap();
+        assert_eq!(normals, vec![Normal([1., 0., 0.]), Normal([0., 1., 0.])]);
+    }
+}
@@ -191,13 +191,16 @@ impl<B: Backend, T: Base3DPassDef> RenderGroup<B, GraphAuxData> for DrawBase3D<B
     ) -> PrepareResult {
         profile_scope_impl!("prepare opaque");
 
-        let GraphAuxData { world, resources } = aux;
+        let GraphAuxData {
+            world, resources, ..
+        } = aux;
 
         let visibility = resources.get::<Visibility>().unwrap();
+        let visibility = visibility.for_target(aux.target);
         let mesh_storage = resources.get::<AssetStorage<Mesh>>().unwrap();
 
         // Prepare environment
-        self.env.process(factory, index, world, resources);
+        self.env.process(factory, index, aux);
         self.materials.maintain();
 
         self.static_batches.clear_inner();
@@ -539,13 +542,16 @@ impl<B: Backend, T: Base3DPassDef> RenderGroup<B, GraphAuxData> for DrawBase3DTr
     ) -> PrepareResult {
         profile_scope_impl!("prepare transparent");
 
-        let GraphAuxData { world, resources } = aux;
+        let GraphAuxData {
+            world, resources, ..
+        } = aux;
 
         let visibility = resources.get::<Visibility>().unwrap();
+        let visibility = visibility.for_target(aux.target);
         let mesh_storage = resources.get::<AssetStorage<Mesh>>().unwrap();
 
         // Prepare environment
-        self.env.process(factory, index, world, resources);
+        self.env.process(factory, index, aux);
         self.materials.maintain();
 
         self.static_batches.swap_clear();
@@ -110,7 +110,9 @@ impl<B: Backend> RenderGroup<B, GraphAuxData> for DrawDebugLines<B> {
         #[cfg(feature = "profiler")]
         profile_scope!("prepare");
 
-        let GraphAuxData { world, resources } = aux;
+        let GraphAuxData {
+            world, resources, ..
+        } = aux;
 
         let old_len = self.lines.len();
         self.lines.clear();
@@ -122,7 +124,7 @@ impl<B: Backend> RenderGroup<B, GraphAuxData> for DrawDebugLines<B> {
             self.lines.extend(lines_res.drain());
         };
 
-        let cam = CameraGatherer::gather(world, resources);
+        let cam = CameraGatherer::gather_for_target(world, resources, aux.target);
         let line_width = resources
             .get::<DebugLinesParams>()
             .map(|p| p.line_width)
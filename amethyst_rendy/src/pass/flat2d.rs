@@ -106,7 +106,9 @@ impl<B: Backend> RenderGroup<B, GraphAuxData> for DrawFlat2D<B> {
         #[cfg(feature = "profiler")]
         profile_scope!("prepare opaque");
 
-        let GraphAuxData { world, resources } = aux;
+        let GraphAuxData {
+            world, resources, ..
+        } = aux;
 
         let (sprite_sheet_storage, sprites_storage, tex_storage, visibility) =
             <(
@@ -115,8 +117,9 @@ impl<B: Backend> RenderGroup<B, GraphAuxData> for DrawFlat2D<B> {
                 Read<AssetStorage<Texture>>,
                 Read<SpriteVisibility>,
             )>::fetch(resources);
+        let visibility = visibility.for_target(aux.target);
 
-        self.env.process(factory, index, world, resources);
+        self.env.process(factory, index, aux);
 
         let sprites_ref = &mut self.sprites;
         let textures_ref = &mut self.textures;
@@ -282,7 +285,9 @@ impl<B: Backend> RenderGroup<B, GraphAuxData> for DrawFlat2DTransparent<B> {
         #[cfg(feature = "profiler")]
         profile_scope!("prepare transparent");
 
-        let GraphAuxData { world, resources } = aux;
+        let GraphAuxData {
+            world, resources, ..
+        } = aux;
 
         let (sprite_sheet_storage, sprites_storage, tex_storage, visibility) =
             <(
@@ -291,8 +296,9 @@ impl<B: Backend> RenderGroup<B, GraphAuxData> for DrawFlat2DTransparent<B> {
                 Read<AssetStorage<Texture>>,
                 Read<SpriteVisibility>,
             )>::fetch(resources);
+        let visibility = visibility.for_target(aux.target);
 
-        self.env.process(factory, index, world, resources);
+        self.env.process(factory, index, aux);
 
         let sprites_ref = &mut self.sprites;
         let textures_ref = &mut self.textures;
@@ -156,7 +156,7 @@ impl<B: Backend> RenderGroup<B, GraphAuxData> for DrawSkybox<B> {
             .map(|s| s.uniform())
             .unwrap_or_else(|| self.default_settings.uniform());
 
-        self.env.process(factory, index, aux.world, aux.resources);
+        self.env.process(factory, index, aux);
         let changed = self.colors.write(factory, index, settings);
 
         if changed {
@@ -20,7 +20,7 @@ mod window {
     use std::path::Path;
 
     use amethyst_config::{Config, ConfigError};
-    use amethyst_window::{DisplayConfig, ScreenDimensions, Window, WindowBundle};
+    use amethyst_window::{DisplayConfig, ScreenDimensions, Window, WindowBundle, Windows};
     use rendy::hal::command::{ClearColor, ClearDepthStencil, ClearValue};
 
     use super::*;
@@ -32,11 +32,15 @@ mod window {
     /// A [RenderPlugin] for opening a window and displaying a render target to it.
     ///
     /// When you provide [`DisplayConfig`], it opens a window for you using [`WindowBundle`].
+    /// Additional windows opened with [`Windows`] are rendered to by plugins created with
+    /// [`RenderToWindow::for_window`].
     #[derive(Default, Debug)]
     pub struct RenderToWindow {
         target: Target,
         config: Option<DisplayConfig>,
+        window: Option<String>,
         dimensions: Option<ScreenDimensions>,
+        generation: u64,
         dirty: bool,
         clear: Option<ClearColor>,
     }
@@ -55,6 +59,22 @@ mod window {
             }
         }
 
+        /// Create RenderToWindow plugin presenting to the additional window with the given name,
+        /// opened with [`Windows::open`].
+        ///
+        /// The graph is rebuilt when the window opens, and nothing is presented while it is
+        /// closed. Select a target other than `Target::Main` for the window with
+        /// [`RenderToWindow::with_target`], and render to it with plugins using that target. The
+        /// target is rendered from the camera with a [`CameraTarget`] of it.
+        ///
+        /// [`CameraTarget`]: crate::camera::CameraTarget
+        pub fn for_window(name: impl Into<String>) -> Self {
+            Self {
+                window: Some(name.into()),
+                ..Default::default()
+            }
+        }
+
         /// Select render target which will be presented to window.
         pub fn with_target(mut self, target: Target) -> Self {
             self.target = target;
@@ -110,6 +130,33 @@ mod window {
 
         #[allow(clippy::map_clone)]
         fn should_rebuild(&mut self, world: &World, resources: &Resources) -> bool {
+            if let Some(ref name) = self.window {
+                let windows = resources.get::<Windows>();
+                let generation = windows.as_ref().map_or(0, |windows| windows.generation());
+                let new_dimensions =
+                    windows
+                        .as_ref()
+                        .and_then(|windows| windows.get(name))
+                        .map(|window| {
+                            let (width, height) = window.inner_size().into();
+                            ScreenDimensions::with_scale_factor(
+                                width,
+                                height,
+                                window.scale_factor(),
+                            )
+                        });
+                if generation != self.generation {
+                    self.generation = generation;
+                    self.dirty = true;
+                }
+                if self.dimensions != new_dimensions {
+                    self.dirty = true;
+                    self.dimensions = new_dimensions;
+                    return false;
+                }
+                return self.dirty;
+            }
+
             let new_dimensions = resources.get::<ScreenDimensions>();
             if self.dimensions.as_ref() != new_dimensions.as_deref() {
                 self.dirty = true;
@@ -128,11 +175,26 @@ mod window {
         ) -> Result<(), Error> {
             self.dirty = false;
 
-            let window = resources.get::<Window>().unwrap();
-            // Explicitly deref so we get a type that implements HasRawWindowHandle.
-            let window: &Window = &window;
-            let surface = factory.create_surface(window)?;
-            let dimensions = self.dimensions.as_ref().unwrap();
+            let dimensions = match self.dimensions {
+                Some(ref dimensions) => dimensions,
+                // the additional window is closed, or not created yet
+                None => return Ok(()),
+            };
+            let surface = match self.window {
+                Some(ref name) => {
+                    let windows = resources.get::<Windows>();
+                    match windows.as_ref().and_then(|windows| windows.get(name)) {
+                        Some(window) => factory.create_surface(window)?,
+                        None => return Ok(()),
+                    }
+                }
+                None => {
+                    let window = resources.get::<Window>().unwrap();
+                    // Explicitly deref so we get a type that implements HasRawWindowHandle.
+                    let window: &Window = &window;
+                    factory.create_surface(window)?
+                }
+            };
             let window_kind = Kind::D2(dimensions.width() as u32, dimensions.height() as u32, 1, 1);
 
             let depth_options = ImageOptions {
@@ -147,7 +209,11 @@ mod window {
                 }),
             };
 
-            plan.add_root(Target::Main);
+            plan.add_root(if self.window.is_some() {
+                self.target
+            } else {
+                Target::Main
+            });
             plan.define_pass(
                 self.target,
                 crate::bundle::TargetPlanOutputs {
@@ -7,23 +7,36 @@ use amethyst_core::{
     transform::Transform,
     Hidden, HiddenPropagate,
 };
+use fnv::FnvHashMap;
 #[cfg(feature = "profiler")]
 use thread_profiler::profile_scope;
 
 use crate::{
-    camera::{ActiveCamera, Camera},
+    bundle::Target,
+    camera::{ActiveCamera, Camera, CameraTarget},
     sprite::SpriteRender,
     transparent::Transparent,
 };
 
 /// Resource for controlling what entities should be rendered, and whether to draw them ordered or
 /// not, which is useful for transparent surfaces.
+///
+/// The fields hold the entities visible from the `ActiveCamera`, see `for_target` for the
+/// cameras rendering other targets.
 #[derive(Default, Debug)]
 pub struct SpriteVisibility {
     /// Visible entities that can be drawn in any order
     pub visible_unordered: Vec<Entity>,
     /// Visible entities that need to be drawn in the given order
     pub visible_ordered: Vec<Entity>,
+    targets: FnvHashMap<Target, SpriteVisibility>,
+}
+
+impl SpriteVisibility {
+    /// The entities visible from the camera rendering the target, see `CameraTarget`.
+    pub fn for_target(&self, target: Target) -> &SpriteVisibility {
+        self.targets.get(&target).unwrap_or(self)
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -40,6 +53,8 @@ struct Internals {
 /// The sprite render pass should draw all sprites without semi-transparent pixels, then draw the
 /// sprites with semi-transparent pixels from far to near.
 ///
+/// This is done for the `ActiveCamera`, and for each camera with a `CameraTarget`.
+///
 /// Note that this should run after `Transform` has been updated for the current frame, and
 /// before rendering occurs.
 #[derive(Debug)]
@@ -55,6 +70,7 @@ impl System for SpriteVisibilitySortingSystem {
                 .write_resource::<SpriteVisibility>()
                 .with_query(<(&Camera, &Transform)>::query())
                 .with_query(<(Entity, &Camera, &Transform)>::query())
+                .with_query(<(&Transform, &CameraTarget)>::query().filter(component::<Camera>()))
                 .with_query(
                     <(Entity, &Transform, &SpriteRender, &Transparent)>::query()
                         .filter(!component::<Hidden>() & !component::<HiddenPropagate>()),
@@ -71,19 +87,36 @@ impl System for SpriteVisibilitySortingSystem {
                           (
                         camera_query1,
                         camera_query2,
+                        target_query,
                         transparent_query,
                         non_transparent_query,
                     )| {
                         #[cfg(feature = "profiler")]
                         profile_scope!("sprite_visibility_system");
 
-                        transparent_centroids.clear();
+                        // the first camera of a target renders it, like in the `CameraGatherer`
+                        let mut sorted = Vec::new();
+                        let mut targets = std::mem::take(&mut visibility.targets);
+                        for (camera_transform, camera_target) in target_query.iter(world) {
+                            if camera_target.0 == Target::Main || sorted.contains(&camera_target.0)
+                            {
+                                continue;
+                            }
+                            sorted.push(camera_target.0);
+                            sort(
+                                camera_transform,
+                                transparent_query.iter(world).map(|(e, t, _, _)| (e, t)),
+                                non_transparent_query.iter(world).map(|(e, t, _)| (e, t)),
+                                &mut transparent_centroids,
+                                targets.entry(camera_target.0).or_default(),
+                            );
+                        }
+                        targets.retain(|target, _| sorted.contains(target));
+                        visibility.targets = targets;
+
                         visibility.visible_ordered.clear();
                         visibility.visible_unordered.clear();
-
-                        let origin = Point3::origin();
-
-                        let (camera, camera_transform) = match active_camera.entity.map_or_else(
+                        let main_camera = active_camera.entity.map_or_else(
                             || camera_query1.iter(world).next(),
                             |e| {
                                 camera_query2
@@ -93,53 +126,70 @@ impl System for SpriteVisibilitySortingSystem {
                                         (camera, camera_transform)
                                     })
                             },
-                        ) {
-                            Some(r) => r,
-                            None => return,
-                        };
-
-                        let camera_backward = camera_transform.global_matrix().column(2).xyz();
-                        let camera_centroid =
-                            camera_transform.global_matrix().transform_point(&origin);
-
-                        transparent_centroids.extend(
-                            transparent_query
-                                .iter(world)
-                                .map(|(e, t, _, _)| {
-                                    (*e, t.global_matrix().transform_point(&origin))
-                                })
-                                // filter entities behind the camera
-                                .filter(|(_, c)| (c - camera_centroid).dot(&camera_backward) < 0.0)
-                                .map(|(entity, centroid)| {
-                                    Internals {
-                                        entity,
-                                        centroid,
-                                        camera_distance: (centroid.z - camera_centroid.z).abs(),
-                                        from_camera: centroid - camera_centroid,
-                                    }
-                                }),
-                        );
-
-                        transparent_centroids.sort_by(|a, b| {
-                            b.camera_distance
-                                .partial_cmp(&a.camera_distance)
-                                .unwrap_or(Ordering::Equal)
-                        });
-
-                        visibility
-                            .visible_ordered
-                            .extend(transparent_centroids.iter().map(|c| c.entity));
-
-                        visibility.visible_unordered.extend(
-                            non_transparent_query
-                                .iter(world)
-                                .map(|(e, t, _)| (e, t.global_matrix().transform_point(&origin)))
-                                // filter entities behind the camera
-                                .filter(|(_, c)| (c - camera_centroid).dot(&camera_backward) < 0.0)
-                                .map(|(entity, _)| entity),
                         );
+                        if let Some((_, camera_transform)) = main_camera {
+                            sort(
+                                camera_transform,
+                                transparent_query.iter(world).map(|(e, t, _, _)| (e, t)),
+                                non_transparent_query.iter(world).map(|(e, t, _)| (e, t)),
+                                &mut transparent_centroids,
+                                visibility,
+                            );
+                        }
                     },
                 ),
         )
     }
 }
+
+// Sets the visibility to the sprites in front of the camera, the transparent ones sorted back to
+// front.
+fn sort<'a>(
+    camera_transform: &Transform,
+    transparent: impl Iterator<Item = (&'a Entity, &'a Transform)>,
+    non_transparent: impl Iterator<Item = (&'a Entity, &'a Transform)>,
+    transparent_centroids: &mut Vec<Internals>,
+    visibility: &mut SpriteVisibility,
+) {
+    transparent_centroids.clear();
+    visibility.visible_ordered.clear();
+    visibility.visible_unordered.clear();
+
+    let origin = Point3::origin();
+
+    let camera_backward = camera_transform.global_matrix().column(2).xyz();
+    let camera_centroid = camera_transform.global_matrix().transform_point(&origin);
+
+    transparent_centroids.extend(
+        transparent
+            .map(|(e, t)| (*e, t.global_matrix().transform_point(&origin)))
+            // filter entities behind the camera
+            .filter(|(_, c)| (c - camera_centroid).dot(&camera_backward) < 0.0)
+            .map(|(entity, centroid)| {
+                Internals {
+                    entity,
+                    centroid,
+                    camera_distance: (centroid.z - camera_centroid.z).abs(),
+                    from_camera: centroid - camera_centroid,
+                }
+            }),
+    );
+
+    transparent_centroids.sort_by(|a, b| {
+        b.camera_distance
+            .partial_cmp(&a.camera_distance)
+            .unwrap_or(Ordering::Equal)
+    });
+
+    visibility
+        .visible_ordered
+        .extend(transparent_centroids.iter().map(|c| c.entity));
+
+    visibility.visible_unordered.extend(
+        non_transparent
+            .map(|(e, t)| (e, t.global_matrix().transform_point(&origin)))
+            // filter entities behind the camera
+            .filter(|(_, c)| (c - camera_centroid).dot(&camera_backward) < 0.0)
+            .map(|(entity, _)| *entity),
+    );
+}
@@ -25,6 +25,7 @@ use crate::{
         resource::{Buffer, DescriptorSet, DescriptorSetLayout, Escape, Handle as RendyHandle},
     },
     submodules::gather::{AmbientGatherer, CameraGatherer},
+    system::GraphAuxData,
     types::Backend,
     util::{self, TapCountIter},
 };
@@ -96,14 +97,9 @@ impl<B: Backend> EnvironmentSub<B> {
         self.layout.raw()
     }
 
-    /// Performs any re-allocation and GPU memory writing required for this environment set.
-    pub fn process(
-        &mut self,
-        factory: &Factory<B>,
-        index: usize,
-        world: &World,
-        resources: &Resources,
-    ) -> bool {
+    /// Performs any re-allocation and GPU memory writing required for this environment set, seen
+    /// from the camera of the target being rendered.
+    pub fn process(&mut self, factory: &Factory<B>, index: usize, aux: &GraphAuxData) -> bool {
         #[cfg(feature = "profiler")]
         profile_scope!("process");
 
@@ -114,7 +110,7 @@ impl<B: Backend> EnvironmentSub<B> {
             }
             &mut self.per_image[index]
         };
-        this_image.process(factory, world, resources)
+        this_image.process(factory, aux)
     }
 
     /// Binds this environment set for all images.
@@ -155,7 +151,12 @@ impl<B: Backend> PerImageEnvironmentSub<B> {
         }
     }
 
-    fn process(&mut self, factory: &Factory<B>, world: &World, resources: &Resources) -> bool {
+    fn process(&mut self, factory: &Factory<B>, aux: &GraphAuxData) -> bool {
+        let GraphAuxData {
+            world,
+            resources,
+            target,
+        } = *aux;
         let align = factory
             .physical()
             .limits()
@@ -209,7 +210,7 @@ impl<B: Backend> PerImageEnvironmentSub<B> {
             let CameraGatherer {
                 camera_position,
                 projview,
-            } = CameraGatherer::gather(world, resources);
+            } = CameraGatherer::gather_for_target(world, resources, target);
 
             let mut mapped = buffer.map(factory, whole_range.clone()).unwrap();
             let mut writer = unsafe { mapped.write::<u8>(factory, whole_range).unwrap() };
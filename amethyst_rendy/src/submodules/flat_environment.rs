@@ -1,6 +1,5 @@
 //! Environment submodule for shared environmental descriptor set data.
 //! Fetches and sets projection set information for a flat pass.
-use amethyst_core::ecs::*;
 #[cfg(feature = "profiler")]
 use thread_profiler::profile_scope;
 
@@ -8,6 +7,7 @@ use crate::{
     pod::ViewArgs,
     rendy::{command::RenderPassEncoder, factory::Factory},
     submodules::{gather::CameraGatherer, uniform::DynamicUniform},
+    system::GraphAuxData,
     types::Backend,
 };
 
@@ -32,17 +32,13 @@ impl<B: Backend> FlatEnvironmentSub<B> {
         self.uniform.raw_layout()
     }
 
-    /// Performs any re-allocation and GPU memory writing required for this environment set.
-    pub fn process(
-        &mut self,
-        factory: &Factory<B>,
-        index: usize,
-        world: &World,
-        resources: &Resources,
-    ) {
+    /// Performs any re-allocation and GPU memory writing required for this environment set, seen
+    /// from the camera of the target being rendered.
+    pub fn process(&mut self, factory: &Factory<B>, index: usize, aux: &GraphAuxData) {
         #[cfg(feature = "profiler")]
         profile_scope!("process");
-        let projview = CameraGatherer::gather(world, resources).projview;
+        let projview =
+            CameraGatherer::gather_for_target(aux.world, aux.resources, aux.target).projview;
         self.uniform.write(factory, index, projview);
     }
 
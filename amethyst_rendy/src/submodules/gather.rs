@@ -9,7 +9,8 @@ use glsl_layout::{Uniform, *};
 use thread_profiler::profile_scope;
 
 use crate::{
-    camera::{ActiveCamera, Camera},
+    bundle::Target,
+    camera::{ActiveCamera, Camera, CameraTarget},
     pod::{self, IntoPod},
     resources::AmbientColor,
 };
@@ -55,19 +56,38 @@ impl CameraGatherer {
         }
     }
 
+    /// Collect the entity of the camera with a `CameraTarget` of the given target, if the target
+    /// isn't rendered from the `ActiveCamera`
+    pub fn target_camera_entity(world: &World, target: Target) -> Option<Entity> {
+        if target == Target::Main {
+            return None;
+        }
+        <(Entity, &CameraTarget)>::query()
+            .filter(component::<Camera>())
+            .iter(world)
+            .find(|(_, camera_target)| camera_target.0 == target)
+            .map(|(e, _)| *e)
+    }
+
     /// Collect `ActiveCamera` and `Camera` instances from the provided resource storage and selects
     /// the appropriate camera to use for projection, and returns the camera position and extracted
     /// projection matrix.
     ///
     /// The matrix returned is the camera's `Projection` matrix and the camera `Transform::global_view_matrix`
     pub fn gather(world: &World, resources: &Resources) -> Self {
+        Self::gather_for_target(world, resources, Target::Main)
+    }
+
+    /// Like `gather`, for the camera rendering the given target, see `CameraTarget`.
+    pub fn gather_for_target(world: &World, resources: &Resources, target: Target) -> Self {
         #[cfg(feature = "profiler")]
         profile_scope!("gather_cameras");
 
         let defcam = Camera::standard_2d(1.0, 1.0);
         let identity = Transform::default();
 
-        let camera_entity = Self::gather_camera_entity(world, resources);
+        let camera_entity = Self::target_camera_entity(world, target)
+            .or_else(|| Self::gather_camera_entity(world, resources));
 
         let camera = camera_entity
             .map(|e| world.entry_ref(e).unwrap().into_component::<Camera>().ok())
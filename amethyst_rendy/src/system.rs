@@ -14,17 +14,21 @@ use rendy::{
 use thread_profiler::profile_scope;
 
 use crate::{
+    bundle::Target,
     mtl::Material,
     types::{Backend, Mesh, MeshData, Texture, TextureData},
 };
 
 /// Auxiliary data for render graph.
 #[allow(missing_debug_implementations)]
+#[derive(Clone, Copy)]
 pub struct InternalGraphAuxData<'a> {
     /// World
     pub world: &'a World,
     /// Resources
     pub resources: &'a Resources,
+    /// The target the render group draws to, whose camera it renders from
+    pub target: Target,
 }
 
 // FIXME: It is currently impossible to pass types with lifetimes (except for a single reference)
@@ -34,7 +38,13 @@ pub struct InternalGraphAuxData<'a> {
 // Simplified issue: https://github.com/rust-lang/rust/issues/51567
 #[allow(missing_docs)]
 pub fn make_graph_aux_data(world: &World, resources: &Resources) -> GraphAuxData {
-    unsafe { std::mem::transmute(InternalGraphAuxData { world, resources }) }
+    unsafe {
+        std::mem::transmute(InternalGraphAuxData {
+            world,
+            resources,
+            target: Target::Main,
+        })
+    }
 }
 
 /// Auxiliary data for render graph. Even though it is `'static` any reference inside it must not
@@ -125,6 +135,20 @@ where
     B: Backend,
     G: 'static + GraphCreator<B>,
 {
+    #[cfg(feature = "window")]
+    {
+        let skip_rendering = resources
+            .get::<amethyst_window::BackgroundSettings>()
+            .map_or(false, |settings| settings.skip_rendering);
+        let minimized = resources
+            .get::<amethyst_window::WindowStatus>()
+            .map_or(false, |status| status.is_minimized());
+        // a minimized window has no surface to render to
+        if skip_rendering && minimized {
+            return;
+        }
+    }
+
     let mut state = resources.get_mut::<RenderState<B, G>>().unwrap();
     let rebuild = state.graph_creator.rebuild(world, resources);
     if state.graph.is_none() || rebuild {
@@ -199,7 +199,9 @@ impl From<rendy::texture::TextureBuilder<'static>> for TextureData {
     }
 }
 
-fn deserialize_data<'de, D>(deserializer: D) -> Result<rendy::mesh::MeshBuilder<'static>, D::Error>
+pub(crate) fn deserialize_data<'de, D>(
+    deserializer: D,
+) -> Result<rendy::mesh::MeshBuilder<'static>, D::Error>
 where
     D: serde::de::Deserializer<'de>,
 {
@@ -7,24 +7,37 @@ use amethyst_core::{
     transform::Transform,
     Hidden, HiddenPropagate,
 };
+use fnv::FnvHashMap;
 use indexmap::IndexSet;
 use serde::{Deserialize, Serialize};
 #[cfg(feature = "profiler")]
 use thread_profiler::profile_scope;
 
 use crate::{
-    camera::{ActiveCamera, Camera},
+    bundle::Target,
+    camera::{ActiveCamera, Camera, CameraTarget},
     transparent::Transparent,
 };
 
 /// Resource for controlling what entities should be rendered, and whether to draw them ordered or
 /// not, which is useful for transparent surfaces.
+///
+/// The fields hold the entities visible from the `ActiveCamera`, see `for_target` for the
+/// cameras rendering other targets.
 #[derive(Default, Debug)]
 pub struct Visibility {
     /// Visible entities that can be drawn in any order
     pub visible_unordered: IndexSet<Entity>,
     /// Visible entities that need to be drawn in the given order
     pub visible_ordered: Vec<Entity>,
+    targets: FnvHashMap<Target, Visibility>,
+}
+
+impl Visibility {
+    /// The entities visible from the camera rendering the target, see `CameraTarget`.
+    pub fn for_target(&self, target: Target) -> &Visibility {
+        self.targets.get(&target).unwrap_or(self)
+    }
 }
 
 /// Defines a object's bounding sphere used by frustum culling.
@@ -71,6 +84,8 @@ struct Internals {
 /// Determine what entities are visible to the camera, and which are not. Will also sort transparent
 /// entities back to front based on distance from camera.
 ///
+/// This is done for the `ActiveCamera`, and for each camera with a `CameraTarget`.
+///
 /// Note that this should run after `Transform` has been updated for the current frame, and
 /// before rendering occurs.
 #[derive(Default, Debug)]
@@ -87,6 +102,7 @@ impl System for VisibilitySortingSystem {
                 .write_resource::<Visibility>()
                 .with_query(<(&Camera, &Transform)>::query())
                 .with_query(<(Entity, &Camera, &Transform)>::query())
+                .with_query(<(&Camera, &Transform, &CameraTarget)>::query())
                 .with_query(
                     <(
                         Entity,
@@ -100,18 +116,32 @@ impl System for VisibilitySortingSystem {
                     move |commands,
                           world,
                           (active_camera, visibility),
-                          (camera_query1, camera_query2, entity_query)| {
+                          (camera_query1, camera_query2, target_query, entity_query)| {
                         #[cfg(feature = "profiler")]
                         profile_scope!("visibility_sorting_system");
 
+                        // the first camera of a target renders it, like in the `CameraGatherer`
+                        let mut sorted = Vec::new();
+                        let mut targets = std::mem::take(&mut visibility.targets);
+                        for (camera, camera_transform, camera_target) in target_query.iter(world) {
+                            if camera_target.0 == Target::Main || sorted.contains(&camera_target.0)
+                            {
+                                continue;
+                            }
+                            sorted.push(camera_target.0);
+                            self.sort(
+                                camera,
+                                camera_transform,
+                                entity_query.iter(world),
+                                targets.entry(camera_target.0).or_default(),
+                            );
+                        }
+                        targets.retain(|target, _| sorted.contains(target));
+                        visibility.targets = targets;
+
                         visibility.visible_unordered.clear();
                         visibility.visible_ordered.clear();
-                        self.transparent.clear();
-                        self.centroids.clear();
-
-                        let origin = Point3::origin();
-
-                        let (camera, camera_transform) = match active_camera.entity.map_or_else(
+                        let main_camera = active_camera.entity.map_or_else(
                             || camera_query1.iter(world).next(),
                             |e| {
                                 camera_query2
@@ -121,72 +151,93 @@ impl System for VisibilitySortingSystem {
                                         (camera, camera_transform)
                                     })
                             },
-                        ) {
-                            Some(r) => r,
-                            None => return,
-                        };
-
-                        let camera_centroid =
-                            camera_transform.global_matrix().transform_point(&origin);
-                        let frustum = Frustum::new(
-                            convert::<_, Matrix4<f32>>(camera.matrix)
-                                * camera_transform.global_matrix().try_inverse().unwrap(),
                         );
+                        if let Some((camera, camera_transform)) = main_camera {
+                            self.sort(
+                                camera,
+                                camera_transform,
+                                entity_query.iter(world),
+                                visibility,
+                            );
+                        }
+                    },
+                ),
+        )
+    }
+}
 
-                        self.centroids.extend(
-                            entity_query
-                                .iter(world)
-                                .map(|(entity, transform, transparent, sphere)| {
-                                    let pos = sphere.clone().map_or(origin, |s| s.center);
-                                    let matrix = transform.global_matrix();
-                                    (
-                                        *entity,
-                                        transparent.is_some(),
-                                        matrix.transform_point(&pos),
-                                        sphere.map_or(1.0, |s| s.radius)
-                                            * matrix[(0, 0)]
-                                                .max(matrix[(1, 1)])
-                                                .max(matrix[(2, 2)]),
-                                    )
-                                })
-                                .filter(|(_, _, centroid, radius)| {
-                                    frustum.check_sphere(centroid, *radius)
-                                })
-                                .map(|(entity, transparent, centroid, _)| {
-                                    Internals {
-                                        entity,
-                                        transparent,
-                                        centroid,
-                                        camera_distance: distance_squared(
-                                            &centroid,
-                                            &camera_centroid,
-                                        ),
-                                    }
-                                }),
-                        );
+impl VisibilitySortingSystem {
+    // Sets the visibility to the entities visible from the camera.
+    fn sort<'a>(
+        &mut self,
+        camera: &Camera,
+        camera_transform: &Transform,
+        entities: impl Iterator<
+            Item = (
+                &'a Entity,
+                &'a Transform,
+                Option<&'a Transparent>,
+                Option<&'a BoundingSphere>,
+            ),
+        >,
+        visibility: &mut Visibility,
+    ) {
+        visibility.visible_unordered.clear();
+        visibility.visible_ordered.clear();
+        self.transparent.clear();
+        self.centroids.clear();
 
-                        self.transparent
-                            .extend(self.centroids.iter().filter(|c| c.transparent).cloned());
+        let origin = Point3::origin();
 
-                        self.transparent.sort_by(|a, b| {
-                            b.camera_distance
-                                .partial_cmp(&a.camera_distance)
-                                .unwrap_or(Ordering::Equal)
-                        });
+        let camera_centroid = camera_transform.global_matrix().transform_point(&origin);
+        let frustum = Frustum::new(
+            convert::<_, Matrix4<f32>>(camera.matrix)
+                * camera_transform.global_matrix().try_inverse().unwrap(),
+        );
 
-                        visibility.visible_unordered.extend(
-                            self.centroids
-                                .iter()
-                                .filter(|c| !c.transparent)
-                                .map(|c| c.entity),
-                        );
+        self.centroids.extend(
+            entities
+                .map(|(entity, transform, transparent, sphere)| {
+                    let pos = sphere.clone().map_or(origin, |s| s.center);
+                    let matrix = transform.global_matrix();
+                    (
+                        *entity,
+                        transparent.is_some(),
+                        matrix.transform_point(&pos),
+                        sphere.map_or(1.0, |s| s.radius)
+                            * matrix[(0, 0)].max(matrix[(1, 1)]).max(matrix[(2, 2)]),
+                    )
+                })
+                .filter(|(_, _, centroid, radius)| frustum.check_sphere(centroid, *radius))
+                .map(|(entity, transparent, centroid, _)| {
+                    Internals {
+                        entity,
+                        transparent,
+                        centroid,
+                        camera_distance: distance_squared(&centroid, &camera_centroid),
+                    }
+                }),
+        );
 
-                        visibility
-                            .visible_ordered
-                            .extend(self.transparent.iter().map(|c| c.entity));
-                    },
-                ),
-        )
+        self.transparent
+            .extend(self.centroids.iter().filter(|c| c.transparent).cloned());
+
+        self.transparent.sort_by(|a, b| {
+            b.camera_distance
+                .partial_cmp(&a.camera_distance)
+                .unwrap_or(Ordering::Equal)
+        });
+
+        visibility.visible_unordered.extend(
+            self.centroids
+                .iter()
+                .filter(|c| !c.transparent)
+                .map(|c| c.entity),
+        );
+
+        visibility
+            .visible_ordered
+            .extend(self.transparent.iter().map(|c| c.entity));
     }
 }
 
@@ -230,3 +281,56 @@ impl Frustum {
         true
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::PI;
+
+    use amethyst_core::dispatcher::DispatcherBuilder;
+
+    use super::*;
+
+    fn transform(z: f32, yaw: f32) -> Transform {
+        let mut transform = Transform::default();
+        transform.set_translation_z(z).set_rotation_y_axis(yaw);
+        transform.copy_local_to_global();
+        transform
+    }
+
+    #[test]
+    fn target_cameras_have_their_own_visibility() {
+        let mut world = World::default();
+        let mut resources = Resources::default();
+        let main = world.push((Camera::perspective(1.0, PI / 3.0, 0.1), transform(0.0, 0.0)));
+        // looks the other way, towards +z
+        world.push((
+            Camera::perspective(1.0, PI / 3.0, 0.1),
+            transform(0.0, PI),
+            CameraTarget(Target::Custom("back")),
+        ));
+        let front = world.push((transform(-5.0, 0.0),));
+        let back = world.push((transform(5.0, 0.0),));
+        resources.insert(ActiveCamera { entity: Some(main) });
+        resources.insert(Visibility::default());
+
+        let mut builder = DispatcherBuilder::default();
+        builder.add_system(VisibilitySortingSystem::default());
+        let mut dispatcher = builder.build(&mut world, &mut resources).unwrap();
+        dispatcher.execute(&mut world, &mut resources);
+
+        let visibility = resources.get::<Visibility>().unwrap();
+        let visible = |target, entity| {
+            visibility
+                .for_target(target)
+                .visible_unordered
+                .contains(&entity)
+        };
+        assert!(visible(Target::Main, front));
+        assert!(!visible(Target::Main, back));
+        assert!(visible(Target::Custom("back"), back));
+        assert!(!visible(Target::Custom("back"), front));
+        // targets without a camera of their own are seen from the active camera
+        assert!(visible(Target::ShadowMap, front));
+        assert!(!visible(Target::ShadowMap, back));
+    }
+}
@@ -0,0 +1,279 @@
+//! Animated tiles, changing the sprites drawn for tiles without changing the tile maps.
+#![allow(clippy::cast_precision_loss)]
+
+use amethyst_assets::{AssetHandle, Handle, LoadHandle};
+use amethyst_core::{
+    dispatcher::System,
+    ecs::{systems::ParallelRunnable, SystemBuilder},
+    Time,
+};
+use amethyst_rendy::SpriteSheet;
+use fnv::FnvHashMap;
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+
+/// A frame of a `TileAnimation`.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TileFrame {
+    /// Index of the sprite in the sprite sheet
+    pub sprite: usize,
+    /// Duration of the frame, in seconds
+    pub duration: f32,
+}
+
+/// Looping animation of the sprite of tiles.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TileAnimation {
+    frames: Vec<TileFrame>,
+    duration: f32,
+}
+
+impl TileAnimation {
+    /// Creates an animation playing the frames in order.
+    #[must_use]
+    pub fn new(frames: Vec<TileFrame>) -> Self {
+        let duration = frames.iter().map(|frame| frame.duration.max(0.0)).sum();
+        Self { frames, duration }
+    }
+
+    /// Creates an animation showing the sprites in order, each for the same duration.
+    #[must_use]
+    pub fn uniform<I: IntoIterator<Item = usize>>(sprites: I, frame_duration: f32) -> Self {
+        Self::new(
+            sprites
+                .into_iter()
+                .map(|sprite| {
+                    TileFrame {
+                        sprite,
+                        duration: frame_duration,
+                    }
+                })
+                .collect(),
+        )
+    }
+
+    /// The frames of the animation.
+    #[must_use]
+    pub fn frames(&self) -> &[TileFrame] {
+        &self.frames
+    }
+
+    /// The duration of a loop of the animation, in seconds.
+    #[must_use]
+    pub fn duration(&self) -> f32 {
+        self.duration
+    }
+
+    /// The sprite shown at the given time since the start of the animation, `None` for
+    /// animations without frames.
+    #[must_use]
+    pub fn sprite_at(&self, time: f32) -> Option<usize> {
+        if self.duration <= 0.0 {
+            return self.frames.first().map(|frame| frame.sprite);
+        }
+        let mut time = time.rem_euclid(self.duration);
+        for frame in &self.frames {
+            if time < frame.duration {
+                return Some(frame.sprite);
+            }
+            time -= frame.duration.max(0.0);
+        }
+        self.frames.last().map(|frame| frame.sprite)
+    }
+}
+
+type AnimationKey = (Option<LoadHandle>, usize);
+
+/// Resource holding the tile animations, by the sprite the tiles are set to. The tile render
+/// pass draws the current frame of the animation instead of the sprite, so tile maps don't
+/// need to change to animate.
+///
+/// Animations are advanced by the `TileAnimationSystem`, all together so that tiles with the
+/// same animation stay in sync.
+#[derive(Debug)]
+pub struct TileAnimations {
+    animations: FnvHashMap<AnimationKey, TileAnimation>,
+    current: FnvHashMap<AnimationKey, usize>,
+    time: f32,
+    revision: u64,
+    frame: u64,
+    /// Speed multiplier of the animations, 0 to pause them
+    pub speed: f32,
+}
+
+impl Default for TileAnimations {
+    fn default() -> Self {
+        Self {
+            animations: FnvHashMap::default(),
+            current: FnvHashMap::default(),
+            time: 0.0,
+            revision: 0,
+            frame: 0,
+            speed: 1.0,
+        }
+    }
+}
+
+impl TileAnimations {
+    /// Animates the tiles set to a sprite of the given sprite sheet.
+    pub fn insert(
+        &mut self,
+        sprite_sheet: &Handle<SpriteSheet>,
+        sprite: usize,
+        animation: TileAnimation,
+    ) {
+        self.insert_key((Some(sprite_sheet.load_handle()), sprite), animation);
+    }
+
+    /// Animates the tiles set to a sprite of any sprite sheet without an animation for it.
+    pub fn insert_global(&mut self, sprite: usize, animation: TileAnimation) {
+        self.insert_key((None, sprite), animation);
+    }
+
+    fn insert_key(&mut self, key: AnimationKey, animation: TileAnimation) {
+        if let Some(sprite) = animation.sprite_at(self.time) {
+            self.current.insert(key, sprite);
+        }
+        self.animations.insert(key, animation);
+        self.revision += 1;
+    }
+
+    /// Stops animating the tiles set to a sprite of the given sprite sheet.
+    pub fn remove(
+        &mut self,
+        sprite_sheet: &Handle<SpriteSheet>,
+        sprite: usize,
+    ) -> Option<TileAnimation> {
+        let key = (Some(sprite_sheet.load_handle()), sprite);
+        self.current.remove(&key);
+        self.revision += 1;
+        self.animations.remove(&key)
+    }
+
+    /// Removes all the animations.
+    pub fn clear(&mut self) {
+        self.animations.clear();
+        self.current.clear();
+        self.revision += 1;
+    }
+
+    /// Returns true if there are no animations.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.animations.is_empty()
+    }
+
+    /// The time of the animations, in seconds.
+    #[must_use]
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    /// The sprite currently drawn for tiles set to a sprite of the given sprite sheet.
+    #[must_use]
+    pub fn sprite(&self, sprite_sheet: &Handle<SpriteSheet>, sprite: usize) -> usize {
+        if self.current.is_empty() {
+            return sprite;
+        }
+        self.current
+            .get(&(Some(sprite_sheet.load_handle()), sprite))
+            .or_else(|| self.current.get(&(None, sprite)))
+            .copied()
+            .unwrap_or(sprite)
+    }
+
+    /// Returns true if the tiles set to a sprite of the given sprite sheet are animated.
+    pub(crate) fn animates(&self, sprite_sheet: &Handle<SpriteSheet>, sprite: usize) -> bool {
+        self.current
+            .contains_key(&(Some(sprite_sheet.load_handle()), sprite))
+            || self.current.contains_key(&(None, sprite))
+    }
+
+    /// Changes every time animations are inserted or removed.
+    pub(crate) fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    /// Changes every time the sprite drawn for an animation changes.
+    pub(crate) fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    /// Advances the animations by the given time, in seconds.
+    pub fn advance(&mut self, delta: f32) {
+        self.time += delta * self.speed;
+        let time = self.time;
+        let current = &mut self.current;
+        let mut changed = false;
+        for (key, animation) in &self.animations {
+            if let Some(sprite) = animation.sprite_at(time) {
+                changed |= current.insert(*key, sprite) != Some(sprite);
+            }
+        }
+        if changed {
+            self.frame += 1;
+        }
+    }
+}
+
+/// System advancing the `TileAnimations`, which must be inserted in the resources.
+#[derive(Debug, Default)]
+pub struct TileAnimationSystem;
+
+impl System for TileAnimationSystem {
+    fn build(self) -> Box<dyn ParallelRunnable> {
+        Box::new(
+            SystemBuilder::new("TileAnimationSystem")
+                .read_resource::<Time>()
+                .write_resource::<TileAnimations>()
+                .build(move |_, _, (time, animations), _| {
+                    #[cfg(feature = "profiler")]
+                    profile_scope!("tile_animation_system");
+
+                    if !animations.is_empty() {
+                        animations.advance(time.delta_seconds());
+                    }
+                }),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn animations_loop_over_their_frames() {
+        let animation = TileAnimation::new(vec![
+            TileFrame {
+                sprite: 3,
+                duration: 0.5,
+            },
+            TileFrame {
+                sprite: 4,
+                duration: 0.25,
+            },
+        ]);
+        assert_eq!(animation.sprite_at(0.0), Some(3));
+        assert_eq!(animation.sprite_at(0.6), Some(4));
+        assert_eq!(animation.sprite_at(0.8), Some(3));
+        assert_eq!(animation.sprite_at(-0.1), Some(4));
+        assert_eq!(TileAnimation::new(vec![]).sprite_at(1.0), None);
+    }
+
+    #[test]
+    fn global_animations_change_sprites() {
+        let mut animations = TileAnimations::default();
+        animations.insert_global(1, TileAnimation::uniform(vec![1, 2, 5], 0.1));
+        animations.advance(0.25);
+        assert_eq!(animations.current.get(&(None, 1)), Some(&5));
+        assert_eq!(animations.current.get(&(None, 2)), None);
+
+        let frame = animations.frame();
+        animations.advance(0.01);
+        assert_eq!(animations.frame(), frame);
+        animations.advance(0.1);
+        assert_eq!(animations.frame(), frame + 1);
+    }
+}
@@ -0,0 +1,354 @@
+//! Rule based auto-tiling, choosing the edge and corner variants of terrain tiles from their
+//! neighbours.
+//!
+//! The terrain of each tile is kept in a `TileLayer<Option<usize>>`, indexing the terrains of
+//! `AutoTileRules`, which can be read from the tile properties of Tiled maps with
+//! `TiledMap::metadata`. `AutoTileRules::apply` resolves the sprites of a map once it's
+//! imported, and `AutoTileRules::set` changes the terrain of a tile at runtime and resolves it
+//! with its neighbours.
+#![allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+
+use std::collections::BTreeMap;
+
+use amethyst_assets::{
+    distill_importer::{typetag, SerdeImportable},
+    register_asset_type, Asset, AssetProcessorSystem,
+};
+use amethyst_core::math::Point3;
+use serde::{Deserialize, Serialize};
+use type_uuid::TypeUuid;
+
+use crate::{
+    iters::Region,
+    map::{Map, MapStorage, Tile, TileMap},
+    nav::TileLayer,
+    CoordinateEncoder, TiledTile,
+};
+
+/// Neighbour bits of the masks of the `AutoTileMode::Edges` mode.
+pub mod edge {
+    /// The tile above is of the same terrain
+    pub const NORTH: u8 = 1;
+    /// The tile on the right is of the same terrain
+    pub const EAST: u8 = 1 << 1;
+    /// The tile below is of the same terrain
+    pub const SOUTH: u8 = 1 << 2;
+    /// The tile on the left is of the same terrain
+    pub const WEST: u8 = 1 << 3;
+}
+
+/// Corner bits of the masks of the `AutoTileMode::Corners` mode.
+pub mod corner {
+    /// The top right corner is inside the terrain
+    pub const NORTH_EAST: u8 = 1;
+    /// The bottom right corner is inside the terrain
+    pub const SOUTH_EAST: u8 = 1 << 1;
+    /// The bottom left corner is inside the terrain
+    pub const SOUTH_WEST: u8 = 1 << 2;
+    /// The top left corner is inside the terrain
+    pub const NORTH_WEST: u8 = 1 << 3;
+}
+
+/// Neighbour bits of the masks of the `AutoTileMode::Blob` mode. The diagonal bits are only
+/// set when both neighbours next to them are set too, which leaves 47 different masks.
+pub mod blob {
+    /// The tile above is of the same terrain
+    pub const NORTH: u8 = 1;
+    /// The tile above on the right is of the same terrain
+    pub const NORTH_EAST: u8 = 1 << 1;
+    /// The tile on the right is of the same terrain
+    pub const EAST: u8 = 1 << 2;
+    /// The tile below on the right is of the same terrain
+    pub const SOUTH_EAST: u8 = 1 << 3;
+    /// The tile below is of the same terrain
+    pub const SOUTH: u8 = 1 << 4;
+    /// The tile below on the left is of the same terrain
+    pub const SOUTH_WEST: u8 = 1 << 5;
+    /// The tile on the left is of the same terrain
+    pub const WEST: u8 = 1 << 6;
+    /// The tile above on the left is of the same terrain
+    pub const NORTH_WEST: u8 = 1 << 7;
+}
+
+/// How the variants of a terrain are chosen.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AutoTileMode {
+    /// 16 variants, from the 4 neighbours sharing an edge, see the `edge` bits
+    Edges,
+    /// 16 variants of Wang corner tiles, a corner being inside when the 3 tiles around it are
+    /// of the terrain too, see the `corner` bits
+    Corners,
+    /// 47 variants, from the 8 neighbours, see the `blob` bits
+    Blob,
+}
+
+/// A terrain of `AutoTileRules`, with its sprites by mask.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AutoTileTerrain {
+    /// Name of the terrain
+    pub name: String,
+    /// How the variants are chosen
+    pub mode: AutoTileMode,
+    /// Sprites for each mask. When a mask has several sprites, one of them is picked for each
+    /// tile, always the same for the same tile.
+    pub variants: BTreeMap<u8, Vec<usize>>,
+    /// Sprite of the masks without variant
+    pub fallback: usize,
+}
+
+impl AutoTileTerrain {
+    /// The sprite of a tile of the terrain with the given mask.
+    #[must_use]
+    pub fn sprite(&self, mask: u8, coord: &Point3<u32>) -> usize {
+        match self.variants.get(&mask) {
+            Some(sprites) if !sprites.is_empty() => {
+                sprites[(hash(coord) % sprites.len() as u32) as usize]
+            }
+            _ => self.fallback,
+        }
+    }
+}
+
+fn hash(coord: &Point3<u32>) -> u32 {
+    let mut hash = coord
+        .x
+        .wrapping_mul(0x9e37_79b1)
+        .wrapping_add(coord.y.wrapping_mul(0x85eb_ca77))
+        .wrapping_add(coord.z.wrapping_mul(0xc2b2_ae3d));
+    hash ^= hash >> 15;
+    hash = hash.wrapping_mul(0x2c1b_3c6d);
+    hash ^ (hash >> 12)
+}
+
+fn default_border_matches() -> bool {
+    true
+}
+
+/// Auto-tiling rules asset, the terrains painted on tile maps.
+///
+/// ```ron
+/// (
+///     terrains: [(
+///         name: "grass",
+///         mode: Edges,
+///         variants: { 0: [16], 15: [5, 6, 7], 6: [0], 14: [1], 12: [2] },
+///         fallback: 5,
+///     )],
+/// )
+/// ```
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, TypeUuid)]
+#[uuid = "6f0d2a94-1e5b-4c37-8a62-d9b4e1c7035f"]
+pub struct AutoTileRules {
+    /// The terrains, indexed by the values of the terrain layers
+    pub terrains: Vec<AutoTileTerrain>,
+    /// Tiles outside of the map count as the same terrain, so that terrains reach the edges of
+    /// the map, default is true
+    #[serde(default = "default_border_matches")]
+    pub border_matches: bool,
+}
+
+impl Asset for AutoTileRules {
+    fn name() -> &'static str {
+        "tiles::AutoTileRules"
+    }
+    type Data = Self;
+}
+
+#[typetag::serde]
+impl SerdeImportable for AutoTileRules {}
+register_asset_type!(AutoTileRules => AutoTileRules; AssetProcessorSystem<AutoTileRules>);
+
+/// Tiles whose sprite can be set by auto-tiling.
+pub trait AutoTile: Tile {
+    /// Sets the sprite of the tile, `None` for tiles without terrain.
+    fn set_auto_sprite(&mut self, sprite: Option<usize>);
+}
+
+impl AutoTile for TiledTile {
+    fn set_auto_sprite(&mut self, sprite: Option<usize>) {
+        self.sprite = sprite;
+    }
+}
+
+impl AutoTileRules {
+    /// Computes the mask of a tile of the given terrain.
+    #[must_use]
+    pub fn mask(
+        &self,
+        terrains: &TileLayer<Option<usize>>,
+        coord: &Point3<u32>,
+        terrain: usize,
+    ) -> u8 {
+        let same = |dx: i64, dy: i64| {
+            let x = i64::from(coord.x) + dx;
+            let y = i64::from(coord.y) + dy;
+            if x < 0 || y < 0 {
+                return self.border_matches;
+            }
+            match terrains.get(&Point3::new(x as u32, y as u32, coord.z)) {
+                Some(other) => *other == Some(terrain),
+                None => self.border_matches,
+            }
+        };
+        let bit = |set: bool, bit: u8| if set { bit } else { 0 };
+
+        let (north, east, south, west) = (same(0, -1), same(1, 0), same(0, 1), same(-1, 0));
+        let mode = self
+            .terrains
+            .get(terrain)
+            .map_or(AutoTileMode::Edges, |terrain| terrain.mode);
+        match mode {
+            AutoTileMode::Edges => {
+                bit(north, edge::NORTH)
+                    | bit(east, edge::EAST)
+                    | bit(south, edge::SOUTH)
+                    | bit(west, edge::WEST)
+            }
+            AutoTileMode::Corners => {
+                bit(north && east && same(1, -1), corner::NORTH_EAST)
+                    | bit(south && east && same(1, 1), corner::SOUTH_EAST)
+                    | bit(south && west && same(-1, 1), corner::SOUTH_WEST)
+                    | bit(north && west && same(-1, -1), corner::NORTH_WEST)
+            }
+            AutoTileMode::Blob => {
+                bit(north, blob::NORTH)
+                    | bit(north && east && same(1, -1), blob::NORTH_EAST)
+                    | bit(east, blob::EAST)
+                    | bit(south && east && same(1, 1), blob::SOUTH_EAST)
+                    | bit(south, blob::SOUTH)
+                    | bit(south && west && same(-1, 1), blob::SOUTH_WEST)
+                    | bit(west, blob::WEST)
+                    | bit(north && west && same(-1, -1), blob::NORTH_WEST)
+            }
+        }
+    }
+
+    /// The sprite of a tile from the terrains around it, `None` for tiles without terrain.
+    #[must_use]
+    pub fn resolve(
+        &self,
+        terrains: &TileLayer<Option<usize>>,
+        coord: &Point3<u32>,
+    ) -> Option<usize> {
+        let terrain = (*terrains.get(coord)?)?;
+        let rules = self.terrains.get(terrain)?;
+        Some(rules.sprite(self.mask(terrains, coord, terrain), coord))
+    }
+
+    /// Resolves the sprites of the tiles of a region of a map from the terrain layer.
+    pub fn apply<T: AutoTile, E: CoordinateEncoder>(
+        &self,
+        map: &mut TileMap<T, E>,
+        terrains: &TileLayer<Option<usize>>,
+        region: &Region,
+    ) {
+        for coord in region {
+            let sprite = self.resolve(terrains, &coord);
+            if let Some(tile) = map.get_mut(&coord) {
+                tile.set_auto_sprite(sprite);
+            }
+        }
+    }
+
+    /// Resolves the sprites of all the tiles of a map from the terrain layer.
+    pub fn apply_all<T: AutoTile, E: CoordinateEncoder>(
+        &self,
+        map: &mut TileMap<T, E>,
+        terrains: &TileLayer<Option<usize>>,
+    ) {
+        let region = Region::new(Point3::new(0, 0, 0), Point3::from(*map.dimensions()));
+        self.apply(map, terrains, &region);
+    }
+
+    /// Sets the terrain of a tile, and resolves the sprites of the tile and its neighbours.
+    pub fn set<T: AutoTile, E: CoordinateEncoder>(
+        &self,
+        map: &mut TileMap<T, E>,
+        terrains: &mut TileLayer<Option<usize>>,
+        coord: &Point3<u32>,
+        terrain: Option<usize>,
+    ) {
+        if !terrains.set(coord, terrain) {
+            return;
+        }
+        let dimensions = terrains.dimensions();
+        let region = Region::new(
+            Point3::new(
+                coord.x.saturating_sub(1),
+                coord.y.saturating_sub(1),
+                coord.z,
+            ),
+            Point3::new(
+                (coord.x + 2).min(dimensions.x),
+                (coord.y + 2).min(dimensions.y),
+                coord.z + 1,
+            ),
+        );
+        self.apply(map, terrains, &region);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use amethyst_core::math::Vector3;
+
+    use super::*;
+    use crate::FlatEncoder;
+
+    fn rules(mode: AutoTileMode) -> AutoTileRules {
+        let mut variants = BTreeMap::new();
+        variants.insert(0, vec![100]);
+        AutoTileRules {
+            terrains: vec![AutoTileTerrain {
+                name: "grass".to_string(),
+                mode,
+                variants,
+                fallback: 0,
+            }],
+            border_matches: false,
+        }
+    }
+
+    #[test]
+    fn masks_follow_neighbours() {
+        let mut terrains = TileLayer::new(Vector3::new(3, 3, 1));
+        terrains.fill(
+            &Region::new(Point3::new(0, 0, 0), Point3::new(2, 2, 1)),
+            Some(0),
+        );
+        let center = Point3::new(1, 1, 0);
+
+        let edges = rules(AutoTileMode::Edges);
+        assert_eq!(edges.mask(&terrains, &center, 0), edge::NORTH | edge::WEST);
+        let blobs = rules(AutoTileMode::Blob);
+        assert_eq!(
+            blobs.mask(&terrains, &center, 0),
+            blob::NORTH | blob::WEST | blob::NORTH_WEST
+        );
+        let corners = rules(AutoTileMode::Corners);
+        assert_eq!(corners.mask(&terrains, &center, 0), corner::NORTH_WEST);
+        assert_eq!(
+            corners.mask(&terrains, &Point3::new(0, 0, 0), 0),
+            corner::SOUTH_EAST
+        );
+    }
+
+    #[test]
+    fn setting_terrains_updates_neighbours() {
+        let rules = rules(AutoTileMode::Edges);
+        let mut map = TileMap::<TiledTile, FlatEncoder>::new(
+            Vector3::new(3, 1, 1),
+            Vector3::new(8, 8, 1),
+            None,
+        );
+        let mut terrains = TileLayer::new(Vector3::new(3, 1, 1));
+
+        rules.set(&mut map, &mut terrains, &Point3::new(0, 0, 0), Some(0));
+        assert_eq!(map.get(&Point3::new(0, 0, 0)).unwrap().sprite, Some(100));
+        assert_eq!(map.get(&Point3::new(1, 0, 0)).unwrap().sprite, None);
+
+        rules.set(&mut map, &mut terrains, &Point3::new(1, 0, 0), Some(0));
+        assert_eq!(map.get(&Point3::new(0, 0, 0)).unwrap().sprite, Some(0));
+        assert_eq!(map.get(&Point3::new(1, 0, 0)).unwrap().sprite, Some(0));
+    }
+}
@@ -0,0 +1,349 @@
+//! Tile maps streamed in chunks around the cameras, for worlds too large to keep in memory.
+#![allow(
+    clippy::cast_possible_truncation,
+    clippy::cast_precision_loss,
+    clippy::cast_sign_loss
+)]
+
+use std::{marker::PhantomData, sync::Arc};
+
+use amethyst_assets::Handle;
+use amethyst_core::{
+    dispatcher::System,
+    ecs::{systems::ParallelRunnable, Entity, IntoQuery, SystemBuilder, TryRead},
+    math::{Point2, Point3, Vector2, Vector3},
+    transform::{Parent, Transform},
+};
+use amethyst_rendy::{camera::Camera, SpriteSheet};
+use derivative::Derivative;
+use fnv::FnvHashMap;
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+
+use crate::{
+    map::{Tile, TileMap},
+    CoordinateEncoder, MortonEncoder2D,
+};
+
+/// Provides the tiles of the chunks of a `TileChunks` when they are loaded.
+///
+/// Closures taking the coordinates of the chunk and its empty map are sources filling the
+/// chunks right away.
+pub trait ChunkSource<T: Tile, E: CoordinateEncoder>: 'static + Send + Sync {
+    /// Fills the empty map of a chunk. Returns false if the content of the chunk isn't ready
+    /// yet, e.g. while it's generated on another thread, in which case it's asked again on the
+    /// next frame.
+    fn load(&self, chunk: Point2<i32>, map: &mut TileMap<T, E>) -> bool;
+
+    /// Called with the map of a chunk before it's unloaded, e.g. to save the changes made to it.
+    fn unload(&self, _chunk: Point2<i32>, _map: &TileMap<T, E>) {}
+}
+
+impl<T, E, F> ChunkSource<T, E> for F
+where
+    T: Tile,
+    E: CoordinateEncoder,
+    F: Fn(Point2<i32>, &mut TileMap<T, E>) + 'static + Send + Sync,
+{
+    fn load(&self, chunk: Point2<i32>, map: &mut TileMap<T, E>) -> bool {
+        self(chunk, map);
+        true
+    }
+}
+
+/// Component of an infinite, orthogonal tile map made of chunks, which are loaded from its
+/// `ChunkSource` around the cameras and unloaded once they are far from all of them.
+///
+/// Each loaded chunk is a child entity of the entity of the `TileChunks`, with a `TileMap` of
+/// the chunk dimensions rendered as usual, and a `TileChunk`. Chunk coordinates grow to the
+/// right and downwards like tile coordinates, the chunk (0, 0) starting at the origin of the
+/// `TileChunks` entity.
+#[derive(Derivative)]
+#[derivative(Debug(bound = ""))]
+pub struct TileChunks<T: Tile, E: CoordinateEncoder = MortonEncoder2D> {
+    chunk_dimensions: Vector3<u32>,
+    tile_dimensions: Vector3<u32>,
+    sprite_sheet: Option<Handle<SpriteSheet>>,
+    load_radius: f32,
+    unload_radius: f32,
+    loads_per_frame: usize,
+    #[derivative(Debug = "ignore")]
+    source: Arc<dyn ChunkSource<T, E>>,
+    loaded: FnvHashMap<Point2<i32>, Entity>,
+}
+
+impl<T: Tile, E: CoordinateEncoder> TileChunks<T, E> {
+    /// Creates chunks of the given dimensions in tiles, filled by the source. Chunks are loaded
+    /// within 2 chunks of the cameras and unloaded beyond 3 chunks.
+    pub fn new<S: ChunkSource<T, E>>(
+        chunk_dimensions: Vector3<u32>,
+        tile_dimensions: Vector3<u32>,
+        sprite_sheet: Option<Handle<SpriteSheet>>,
+        source: S,
+    ) -> Self {
+        Self {
+            chunk_dimensions,
+            tile_dimensions,
+            sprite_sheet,
+            load_radius: 2.0,
+            unload_radius: 3.0,
+            loads_per_frame: 4,
+            source: Arc::new(source),
+            loaded: FnvHashMap::default(),
+        }
+    }
+
+    /// Sets the distances from the cameras, in chunks, within which chunks are loaded and
+    /// beyond which they are unloaded. The unload radius is at least the load radius.
+    #[must_use]
+    pub fn with_radius(mut self, load_radius: f32, unload_radius: f32) -> Self {
+        self.load_radius = load_radius;
+        self.unload_radius = unload_radius.max(load_radius);
+        self
+    }
+
+    /// Sets how many chunks may be loaded in a frame, the closest to the cameras first.
+    #[must_use]
+    pub fn with_loads_per_frame(mut self, loads_per_frame: usize) -> Self {
+        self.loads_per_frame = loads_per_frame.max(1);
+        self
+    }
+
+    /// The dimensions of the chunks, in tiles.
+    #[must_use]
+    pub fn chunk_dimensions(&self) -> &Vector3<u32> {
+        &self.chunk_dimensions
+    }
+
+    /// The entity of a loaded chunk.
+    #[must_use]
+    pub fn chunk(&self, chunk: Point2<i32>) -> Option<Entity> {
+        self.loaded.get(&chunk).copied()
+    }
+
+    /// Iterates over the coordinates and entities of the loaded chunks.
+    pub fn loaded(&self) -> impl Iterator<Item = (Point2<i32>, Entity)> + '_ {
+        self.loaded.iter().map(|(chunk, entity)| (*chunk, *entity))
+    }
+
+    /// Splits the coordinates of a tile of the whole map into the coordinates of its chunk and
+    /// its coordinates in the chunk.
+    #[must_use]
+    pub fn locate(&self, tile: Point2<i64>) -> (Point2<i32>, Point2<u32>) {
+        let width = i64::from(self.chunk_dimensions.x);
+        let height = i64::from(self.chunk_dimensions.y);
+        (
+            Point2::new(
+                tile.x.div_euclid(width) as i32,
+                tile.y.div_euclid(height) as i32,
+            ),
+            Point2::new(
+                tile.x.rem_euclid(width) as u32,
+                tile.y.rem_euclid(height) as u32,
+            ),
+        )
+    }
+
+    /// The size of a chunk in the space of the `TileChunks` entity.
+    fn chunk_size(&self) -> Vector2<f32> {
+        Vector2::new(
+            (self.chunk_dimensions.x * self.tile_dimensions.x) as f32,
+            (self.chunk_dimensions.y * self.tile_dimensions.y) as f32,
+        )
+    }
+
+    /// Converts a point of the space of the `TileChunks` entity to chunk units.
+    fn to_chunk_units(&self, point: &Point3<f32>) -> Vector2<f32> {
+        let size = self.chunk_size();
+        Vector2::new(point.x / size.x, -point.y / size.y)
+    }
+
+    /// The chunk containing a point of the space of the `TileChunks` entity.
+    #[must_use]
+    pub fn chunk_at(&self, point: &Point3<f32>) -> Point2<i32> {
+        let units = self.to_chunk_units(point);
+        Point2::new(units.x.floor() as i32, units.y.floor() as i32)
+    }
+
+    /// The center of a chunk in the space of the `TileChunks` entity.
+    #[must_use]
+    pub fn chunk_center(&self, chunk: Point2<i32>) -> Vector3<f32> {
+        let size = self.chunk_size();
+        Vector3::new(
+            (chunk.x as f32 + 0.5) * size.x,
+            -(chunk.y as f32 + 0.5) * size.y,
+            0.0,
+        )
+    }
+}
+
+/// Component of the entities of the loaded chunks of a `TileChunks`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TileChunk {
+    /// The entity of the `TileChunks`
+    pub chunks: Entity,
+    /// Coordinates of the chunk
+    pub chunk: Point2<i32>,
+}
+
+fn distance(units: &Vector2<f32>, chunk: Point2<i32>) -> f32 {
+    (Vector2::new(chunk.x as f32 + 0.5, chunk.y as f32 + 0.5) - units).norm()
+}
+
+/// System loading and unloading the chunks of the `TileChunks<T, E>` around the cameras. Maps
+/// without camera keep their chunks.
+///
+/// ### Type parameters:
+///
+/// - `T`: the tile type of the chunks
+/// - `E`: the coordinate encoder of the chunks
+#[derive(Derivative)]
+#[derivative(Default(bound = ""), Debug(bound = ""))]
+pub struct ChunkStreamingSystem<T: Tile, E: CoordinateEncoder = MortonEncoder2D> {
+    #[derivative(Debug = "ignore")]
+    _marker: PhantomData<(T, E)>,
+}
+
+impl<T: Tile, E: CoordinateEncoder> System for ChunkStreamingSystem<T, E> {
+    fn build(self) -> Box<dyn ParallelRunnable> {
+        let mut cameras = Vec::new();
+        let mut wanted = Vec::new();
+        let mut unloads = Vec::new();
+
+        Box::new(
+            SystemBuilder::new("ChunkStreamingSystem")
+                .with_query(<(&Camera, &Transform)>::query())
+                .with_query(<(Entity, &mut TileChunks<T, E>, TryRead<Transform>)>::query())
+                .with_query(<&TileMap<T, E>>::query())
+                .build(
+                    move |commands, world, _, (camera_query, chunks_query, map_query)| {
+                        #[cfg(feature = "profiler")]
+                        profile_scope!("chunk_streaming_system");
+
+                        cameras.clear();
+                        cameras.extend(camera_query.iter(world).map(|(_, transform)| {
+                            transform.global_matrix().transform_point(&Point3::origin())
+                        }));
+                        if cameras.is_empty() {
+                            return;
+                        }
+
+                        for (entity, chunks, transform) in chunks_query.iter_mut(world) {
+                            // Camera positions in chunk units
+                            let units: Vec<_> = cameras
+                                .iter()
+                                .map(|camera| {
+                                    let local = transform.map_or(*camera, |t| {
+                                        t.global_view_matrix().transform_point(camera)
+                                    });
+                                    chunks.to_chunk_units(&local)
+                                })
+                                .collect();
+                            let closest = |chunk| {
+                                units
+                                    .iter()
+                                    .map(|units| distance(units, chunk))
+                                    .fold(std::f32::INFINITY, f32::min)
+                            };
+
+                            let unload_radius = chunks.unload_radius;
+                            let source = &chunks.source;
+                            chunks.loaded.retain(|chunk, chunk_entity| {
+                                let keep = closest(*chunk) <= unload_radius;
+                                if !keep {
+                                    unloads.push((source.clone(), *chunk, *chunk_entity));
+                                }
+                                keep
+                            });
+
+                            wanted.clear();
+                            let radius = chunks.load_radius;
+                            for units in &units {
+                                let min_x = (units.x - radius).floor() as i32;
+                                let max_x = (units.x + radius).ceil() as i32;
+                                let min_y = (units.y - radius).floor() as i32;
+                                let max_y = (units.y + radius).ceil() as i32;
+                                for y in min_y..=max_y {
+                                    for x in min_x..=max_x {
+                                        let chunk = Point2::new(x, y);
+                                        let distance = distance(units, chunk);
+                                        if distance <= radius && !chunks.loaded.contains_key(&chunk)
+                                        {
+                                            wanted.push((distance, chunk));
+                                        }
+                                    }
+                                }
+                            }
+                            // Keep the closest camera distance of each chunk, then load the
+                            // closest chunks first
+                            wanted.sort_by(|a, b| {
+                                (a.1.x, a.1.y)
+                                    .cmp(&(b.1.x, b.1.y))
+                                    .then(a.0.partial_cmp(&b.0).unwrap())
+                            });
+                            wanted.dedup_by_key(|(_, chunk)| *chunk);
+                            wanted.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+
+                            for &(_, chunk) in wanted.iter().take(chunks.loads_per_frame) {
+                                let mut map = TileMap::new(
+                                    chunks.chunk_dimensions,
+                                    chunks.tile_dimensions,
+                                    chunks.sprite_sheet.clone(),
+                                );
+                                if chunks.source.load(chunk, &mut map) {
+                                    let chunk_entity = commands.push((
+                                        map,
+                                        Transform::from(chunks.chunk_center(chunk)),
+                                        Parent(*entity),
+                                        TileChunk {
+                                            chunks: *entity,
+                                            chunk,
+                                        },
+                                    ));
+                                    chunks.loaded.insert(chunk, chunk_entity);
+                                }
+                            }
+                        }
+
+                        for (source, chunk, chunk_entity) in unloads.drain(..) {
+                            if let Ok(map) = map_query.get(world, chunk_entity) {
+                                source.unload(chunk, map);
+                            }
+                            commands.remove(chunk_entity);
+                        }
+                    },
+                ),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default, Clone, Debug)]
+    struct TestTile;
+    impl Tile for TestTile {}
+
+    #[test]
+    fn tiles_are_located_in_chunks() {
+        let chunks = TileChunks::<TestTile>::new(
+            Vector3::new(16, 8, 1),
+            Vector3::new(10, 10, 1),
+            None,
+            |_: Point2<i32>, _: &mut TileMap<TestTile>| {},
+        );
+        assert_eq!(
+            chunks.locate(Point2::new(17, 3)),
+            (Point2::new(1, 0), Point2::new(1, 3))
+        );
+        assert_eq!(
+            chunks.locate(Point2::new(-1, -9)),
+            (Point2::new(-1, -2), Point2::new(15, 7))
+        );
+
+        let center = chunks.chunk_center(Point2::new(-1, 2));
+        assert_eq!(center, Vector3::new(-80.0, -200.0, 0.0));
+        assert_eq!(chunks.chunk_at(&Point3::from(center)), Point2::new(-1, 2));
+    }
+}
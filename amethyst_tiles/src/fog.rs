@@ -0,0 +1,439 @@
+//! Fog of war over tile maps, hiding the tiles which were never seen and darkening the tiles out
+//! of sight, with a shadowcasting field of view for the viewers.
+//!
+//! Fog of war is opt-in: only the maps with a `FogOfWar` component are drawn with it.
+#![allow(
+    clippy::cast_possible_truncation,
+    clippy::cast_precision_loss,
+    clippy::cast_sign_loss,
+    clippy::similar_names
+)]
+
+use std::marker::PhantomData;
+
+use amethyst_core::{
+    dispatcher::System,
+    ecs::{systems::ParallelRunnable, IntoQuery, SystemBuilder, TryRead},
+    math::{Point3, Vector3},
+    transform::Transform,
+};
+use amethyst_rendy::palette::Srgba;
+use derivative::Derivative;
+use fnv::FnvHashSet;
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+
+use crate::{
+    iters::Region,
+    map::{Map, Tile, TileMap},
+    nav::{TileFlags, TileLayer},
+    CoordinateEncoder, MortonEncoder2D,
+};
+
+/// What is known of a tile under the fog of war.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum TileVisibility {
+    /// Never seen, the tile isn't drawn
+    Unexplored,
+    /// Seen before but out of sight, the tile is drawn darkened
+    Explored,
+    /// In sight of a viewer, the tile is drawn as it is
+    Visible,
+}
+
+impl Default for TileVisibility {
+    fn default() -> Self {
+        TileVisibility::Unexplored
+    }
+}
+
+/// Fog of war of a `TileMap`, added to the entity of the map. The visibility of a tile is shared
+/// by all the z levels of the map.
+///
+/// Visibility changes go through the map, so that the render pass draws the changed tiles again.
+#[derive(Clone, Debug)]
+pub struct FogOfWar {
+    visibility: TileLayer<TileVisibility>,
+    visible: Vec<Point3<u32>>,
+    explored_tint: Srgba,
+    /// Flags of the `TileLayer<TileFlags>` of the map which block the sight of the
+    /// `TileViewer`s, default is `TileFlags::SOLID`
+    pub sight_blocking: TileFlags,
+}
+
+impl FogOfWar {
+    /// Creates the fog of war of a map, with every tile unexplored.
+    #[must_use]
+    pub fn new<M: Map>(map: &M) -> Self {
+        let dimensions = map.dimensions();
+        Self {
+            visibility: TileLayer::new(Vector3::new(dimensions.x, dimensions.y, 1)),
+            visible: Vec::new(),
+            explored_tint: Srgba::new(0.4, 0.4, 0.4, 1.0),
+            sight_blocking: TileFlags::SOLID,
+        }
+    }
+
+    /// Sets the tint of the explored tiles out of sight.
+    #[must_use]
+    pub fn with_explored_tint(mut self, tint: Srgba) -> Self {
+        self.explored_tint = tint;
+        self
+    }
+
+    /// The tint of the explored tiles out of sight.
+    #[must_use]
+    pub fn explored_tint(&self) -> Srgba {
+        self.explored_tint
+    }
+
+    /// The visibility of a tile, of any z level.
+    #[must_use]
+    pub fn visibility(&self, coord: &Point3<u32>) -> TileVisibility {
+        self.visibility
+            .get(&Point3::new(coord.x, coord.y, 0))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// The tiles currently in sight, on the first z level.
+    #[must_use]
+    pub fn visible(&self) -> &[Point3<u32>] {
+        &self.visible
+    }
+
+    /// Sets the tiles in sight, of any z level. The tiles which were in sight before and aren't
+    /// anymore become explored.
+    pub fn set_visible<T, E, I>(&mut self, map: &mut TileMap<T, E>, visible: I)
+    where
+        T: Tile,
+        E: CoordinateEncoder,
+        I: IntoIterator<Item = Point3<u32>>,
+    {
+        let previous = std::mem::take(&mut self.visible);
+        let mut in_sight = FnvHashSet::default();
+        for coord in visible {
+            let coord = Point3::new(coord.x, coord.y, 0);
+            if in_sight.insert(coord) && self.change(map, &coord, TileVisibility::Visible) {
+                self.visible.push(coord);
+            }
+        }
+        for coord in previous {
+            if !in_sight.contains(&coord) {
+                self.change(map, &coord, TileVisibility::Explored);
+            }
+        }
+    }
+
+    /// Explores the tiles of a region, e.g. when a map of the area is found. Tiles in sight stay
+    /// visible.
+    pub fn explore<T: Tile, E: CoordinateEncoder>(
+        &mut self,
+        map: &mut TileMap<T, E>,
+        region: &Region,
+    ) {
+        let dimensions = *self.visibility.dimensions();
+        for y in region.min.y..region.max.y.min(dimensions.y) {
+            for x in region.min.x..region.max.x.min(dimensions.x) {
+                let coord = Point3::new(x, y, 0);
+                if self.visibility(&coord) == TileVisibility::Unexplored {
+                    self.change(map, &coord, TileVisibility::Explored);
+                }
+            }
+        }
+    }
+
+    /// Covers the whole map with fog again, leaving every tile unexplored.
+    pub fn reset<T: Tile, E: CoordinateEncoder>(&mut self, map: &mut TileMap<T, E>) {
+        self.visibility.fill(
+            &Region::new(
+                Point3::origin(),
+                Point3::from(*self.visibility.dimensions()),
+            ),
+            TileVisibility::Unexplored,
+        );
+        self.visible.clear();
+        map.mark_changed(&Region::new(
+            Point3::origin(),
+            Point3::from(*map.dimensions()),
+        ));
+    }
+
+    /// Darkens the tint of an explored tile.
+    pub(crate) fn darken(&self, tint: Srgba) -> Srgba {
+        let fog = self.explored_tint;
+        Srgba::new(
+            tint.red * fog.red,
+            tint.green * fog.green,
+            tint.blue * fog.blue,
+            tint.alpha * fog.alpha,
+        )
+    }
+
+    /// Sets the visibility of a tile, marking its tiles of the map as changed. Returns false if
+    /// the tile is out of the map.
+    fn change<T: Tile, E: CoordinateEncoder>(
+        &mut self,
+        map: &mut TileMap<T, E>,
+        coord: &Point3<u32>,
+        visibility: TileVisibility,
+    ) -> bool {
+        match self.visibility.get_mut(coord) {
+            Some(current) if *current == visibility => true,
+            Some(current) => {
+                *current = visibility;
+                map.mark_changed(&Region::new(
+                    *coord,
+                    Point3::new(coord.x + 1, coord.y + 1, map.dimensions().z),
+                ));
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Field of view over the tiles of a z level of a map, computed with recursive shadowcasting.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FieldOfView {
+    /// Distance in tiles up to which tiles can be seen
+    pub radius: u32,
+}
+
+/// Multipliers turning the coordinates of the first octant into the coordinates of each octant.
+const OCTANTS: [(i64, i64, i64, i64); 8] = [
+    (1, 0, 0, -1),
+    (0, 1, -1, 0),
+    (0, -1, -1, 0),
+    (-1, 0, 0, -1),
+    (-1, 0, 0, 1),
+    (0, -1, 1, 0),
+    (0, 1, 1, 0),
+    (1, 0, 0, 1),
+];
+
+impl FieldOfView {
+    /// Creates a field of view reaching the given distance in tiles.
+    #[must_use]
+    pub fn new(radius: u32) -> Self {
+        Self { radius }
+    }
+
+    /// Finds the tiles seen from a tile, in a map of the given dimensions. The callback returns
+    /// true for the tiles blocking the sight, which are seen but hide the tiles behind them.
+    pub fn compute<F>(
+        &self,
+        dimensions: &Vector3<u32>,
+        origin: &Point3<u32>,
+        mut blocks_sight: F,
+    ) -> Vec<Point3<u32>>
+    where
+        F: FnMut(Point3<u32>) -> bool,
+    {
+        let mut visible = Vec::new();
+        if origin.x >= dimensions.x || origin.y >= dimensions.y || origin.z >= dimensions.z {
+            return visible;
+        }
+        visible.push(*origin);
+        for octant in &OCTANTS {
+            self.cast(
+                dimensions,
+                origin,
+                *octant,
+                1,
+                (1.0, 0.0),
+                &mut blocks_sight,
+                &mut visible,
+            );
+        }
+        visible.sort_by_key(|coord| (coord.y, coord.x));
+        visible.dedup();
+        visible
+    }
+
+    /// Scans the rows of an octant from the given row, between the given slopes.
+    #[allow(clippy::too_many_arguments)]
+    fn cast<F: FnMut(Point3<u32>) -> bool>(
+        &self,
+        dimensions: &Vector3<u32>,
+        origin: &Point3<u32>,
+        (xx, xy, yx, yy): (i64, i64, i64, i64),
+        row: i64,
+        (mut start, end): (f32, f32),
+        blocks_sight: &mut F,
+        visible: &mut Vec<Point3<u32>>,
+    ) {
+        if start < end {
+            return;
+        }
+        let radius = i64::from(self.radius);
+        let mut next_start = start;
+        for distance in row..=radius {
+            let dy = -distance;
+            let mut blocked = false;
+            for dx in -distance..=0 {
+                let left_slope = (dx as f32 - 0.5) / (dy as f32 + 0.5);
+                let right_slope = (dx as f32 + 0.5) / (dy as f32 - 0.5);
+                if start < right_slope {
+                    continue;
+                } else if end > left_slope {
+                    break;
+                }
+
+                let x = i64::from(origin.x) + dx * xx + dy * xy;
+                let y = i64::from(origin.y) + dx * yx + dy * yy;
+                let inside =
+                    x >= 0 && y >= 0 && x < i64::from(dimensions.x) && y < i64::from(dimensions.y);
+                let coord = Point3::new(x as u32, y as u32, origin.z);
+                if inside && dx * dx + dy * dy <= radius * radius {
+                    visible.push(coord);
+                }
+
+                let opaque = !inside || blocks_sight(coord);
+                if blocked {
+                    if opaque {
+                        next_start = right_slope;
+                    } else {
+                        blocked = false;
+                        start = next_start;
+                    }
+                } else if opaque && distance < radius {
+                    // The rest of the row behind this tile is scanned on its own
+                    blocked = true;
+                    self.cast(
+                        dimensions,
+                        origin,
+                        (xx, xy, yx, yy),
+                        distance + 1,
+                        (start, left_slope),
+                        blocks_sight,
+                        visible,
+                    );
+                    next_start = right_slope;
+                }
+            }
+            if blocked {
+                break;
+            }
+        }
+    }
+}
+
+/// Component of the entities seeing through the fog of war of the maps, from the tile under
+/// their position.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TileViewer {
+    /// Distance in tiles up to which the viewer sees
+    pub radius: u32,
+}
+
+/// System updating the `FogOfWar` of the maps from the field of view of the `TileViewer`s. The
+/// sight is blocked by the tiles with the `FogOfWar::sight_blocking` flags in the
+/// `TileLayer<TileFlags>` of the map entity, if it has one.
+#[derive(Derivative)]
+#[derivative(Default(bound = ""), Debug(bound = ""))]
+pub struct FogOfWarSystem<T: Tile, E: CoordinateEncoder = MortonEncoder2D> {
+    #[derivative(Debug = "ignore")]
+    _marker: PhantomData<(T, E)>,
+}
+
+impl<T: Tile, E: CoordinateEncoder> System for FogOfWarSystem<T, E> {
+    fn build(self) -> Box<dyn ParallelRunnable> {
+        let mut viewers = Vec::new();
+        let mut visible = Vec::new();
+
+        Box::new(
+            SystemBuilder::new("FogOfWarSystem")
+                .with_query(<(&TileViewer, &Transform)>::query())
+                .with_query(<(
+                    &mut TileMap<T, E>,
+                    &mut FogOfWar,
+                    TryRead<TileLayer<TileFlags>>,
+                    TryRead<Transform>,
+                )>::query())
+                .build(move |_, world, _, (viewer_query, map_query)| {
+                    #[cfg(feature = "profiler")]
+                    profile_scope!("fog_of_war_system");
+
+                    viewers.clear();
+                    viewers.extend(viewer_query.iter(world).map(|(viewer, transform)| {
+                        let position = transform.global_matrix().transform_point(&Point3::origin());
+                        (position, FieldOfView::new(viewer.radius))
+                    }));
+
+                    for (map, fog, flags, transform) in map_query.iter_mut(world) {
+                        visible.clear();
+                        let dimensions = *map.dimensions();
+                        for (position, view) in &viewers {
+                            if let Ok(origin) = map.to_tile(&position.coords, transform) {
+                                visible.extend(view.compute(&dimensions, &origin, |coord| {
+                                    flags.map_or(false, |flags| {
+                                        flags
+                                            .get(&coord)
+                                            .map_or(false, |f| f.intersects(fog.sight_blocking))
+                                    })
+                                }));
+                            }
+                        }
+                        fog.set_visible(map, visible.drain(..));
+                    }
+                }),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FlatEncoder;
+
+    #[derive(Clone, Debug, Default)]
+    struct TestTile;
+    impl Tile for TestTile {}
+
+    #[test]
+    fn walls_cast_shadows() {
+        let dimensions = Vector3::new(9, 9, 1);
+        let mut walls = TileLayer::new(dimensions);
+        walls.set(&Point3::new(4, 2, 0), true);
+
+        let visible = FieldOfView::new(4).compute(&dimensions, &Point3::new(4, 4, 0), |coord| {
+            *walls.get(&coord).unwrap()
+        });
+        assert!(visible.contains(&Point3::new(4, 4, 0)));
+        assert!(visible.contains(&Point3::new(4, 2, 0)));
+        assert!(!visible.contains(&Point3::new(4, 1, 0)));
+        assert!(!visible.contains(&Point3::new(4, 0, 0)));
+        assert!(visible.contains(&Point3::new(8, 4, 0)));
+        assert!(visible.contains(&Point3::new(4, 8, 0)));
+        // Out of the radius
+        assert!(!visible.contains(&Point3::new(8, 8, 0)));
+    }
+
+    #[test]
+    fn tiles_out_of_sight_stay_explored() {
+        let mut map = TileMap::<TestTile, FlatEncoder>::new(
+            Vector3::new(4, 4, 2),
+            Vector3::new(8, 8, 1),
+            None,
+        );
+        let mut fog = FogOfWar::new(&map);
+        let first = Point3::new(1, 1, 1);
+        let second = Point3::new(2, 1, 0);
+        assert_eq!(fog.visibility(&first), TileVisibility::Unexplored);
+
+        fog.set_visible(&mut map, vec![first, second]);
+        assert_eq!(fog.visibility(&first), TileVisibility::Visible);
+        assert_eq!(fog.visible().len(), 2);
+
+        let version = map.chunk_version(&Point3::new(0, 0, 1));
+        fog.set_visible(&mut map, vec![second]);
+        assert_eq!(fog.visibility(&first), TileVisibility::Explored);
+        assert_eq!(fog.visibility(&second), TileVisibility::Visible);
+        assert!(map.chunk_version(&Point3::new(0, 0, 1)) > version);
+
+        fog.reset(&mut map);
+        assert_eq!(fog.visibility(&second), TileVisibility::Unexplored);
+    }
+}
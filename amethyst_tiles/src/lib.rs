@@ -7,23 +7,37 @@
 #![deny(clippy::all, clippy::pedantic, missing_docs)]
 #![allow(dead_code, clippy::module_name_repetitions)]
 
+mod animation;
+mod chunk;
+mod fog;
 mod map;
 mod morton;
+mod nav;
 mod pass;
+mod projection;
 
+pub mod autotile;
 pub mod error;
 pub mod iters;
 pub mod pod;
+pub mod tiled;
 
 use amethyst_core::math::Vector3;
+pub use animation::{TileAnimation, TileAnimationSystem, TileAnimations, TileFrame};
+pub use autotile::{AutoTile, AutoTileMode, AutoTileRules, AutoTileTerrain};
+pub use chunk::{ChunkSource, ChunkStreamingSystem, TileChunk, TileChunks};
 pub use error::TileOutOfBoundsError;
+pub use fog::{FieldOfView, FogOfWar, FogOfWarSystem, TileViewer, TileVisibility};
 pub use iters::{MortonRegion, Region};
-pub use map::{Map, MapStorage, Tile, TileMap};
+pub use map::{Map, MapStorage, Tile, TileMap, TileOrientation, TileRotation};
 pub use morton::{MortonEncoder, MortonEncoder2D};
+pub use nav::{Pathfinder, TileFlags, TileLayer, TilePath};
 pub use pass::{
     DrawTiles2D, DrawTiles2DBounds, DrawTiles2DBoundsCameraCulling, DrawTiles2DBoundsDefault,
     DrawTiles2DDesc, RenderTiles2D,
 };
+pub use projection::{HexOrientation, StaggerAxis, StaggerIndex, TileProjection};
+pub use tiled::{TiledFormat, TiledMap, TiledTile, TiledTileset, TiledTilesetFormat};
 
 /// Trait to provide generic access to various encoding schemas. All tile storages use this to encode their coordinates
 /// and provide different spatial encoding algorithms for efficiency.
@@ -8,7 +8,11 @@ use amethyst_core::{
 };
 use amethyst_rendy::{palette::Srgba, SpriteSheet};
 
-use crate::{CoordinateEncoder, TileOutOfBoundsError};
+use crate::{iters::Region, CoordinateEncoder, TileOutOfBoundsError, TileProjection};
+
+/// Width and height of the chunks of a `TileMap` whose changes are tracked, so that the render
+/// pass only encodes the tiles of the chunks which changed again.
+pub(crate) const RENDER_CHUNK: u32 = 16;
 
 /// Trait providing generic rendering functionality to all tiles. Using a tilemap requires you to provide a `Tile` type,
 /// which must implement this trait to provide the `RenderPass` with the appropriate sprite and tint values.
@@ -22,6 +26,83 @@ pub trait Tile: 'static + Clone + Send + Sync + Default {
     fn tint(&self, coordinates: Point3<u32>, world: &World) -> Srgba {
         Srgba::new(1.0, 1.0, 1.0, 1.0)
     }
+
+    /// Takes an immutable reference to world to process this sprite and return its orientation.
+    fn orientation(&self, coordinates: Point3<u32>, world: &World) -> TileOrientation {
+        TileOrientation::default()
+    }
+}
+
+/// Counter-clockwise rotation of the sprite of a tile. The sprites of tiles rotated by a quarter
+/// turn are drawn with their width and height swapped.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+pub enum TileRotation {
+    /// Not rotated
+    Deg0,
+    /// Rotated by a quarter turn
+    Deg90,
+    /// Rotated by a half turn
+    Deg180,
+    /// Rotated by three quarter turns
+    Deg270,
+}
+
+impl Default for TileRotation {
+    fn default() -> Self {
+        TileRotation::Deg0
+    }
+}
+
+/// Orientation of the sprite of a tile, which is flipped and then rotated.
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize,
+)]
+pub struct TileOrientation {
+    /// Counter-clockwise rotation of the sprite
+    pub rotation: TileRotation,
+    /// Flipped horizontally, mirroring the left and right of the sprite
+    pub flip_x: bool,
+    /// Flipped vertically, mirroring the top and bottom of the sprite
+    pub flip_y: bool,
+}
+
+impl TileOrientation {
+    /// Creates the orientation of flip flags as stored by the Tiled editor, where the diagonal
+    /// flip swapping the x and y axes of the sprite is applied before the other flips.
+    #[must_use]
+    pub fn from_flips(horizontal: bool, vertical: bool, diagonal: bool) -> Self {
+        if diagonal {
+            // Flipping along the diagonal is a horizontal flip followed by a quarter turn, and
+            // flips done after a quarter turn are the opposite flips done before it.
+            Self {
+                rotation: TileRotation::Deg90,
+                flip_x: !vertical,
+                flip_y: horizontal,
+            }
+        } else {
+            Self {
+                rotation: TileRotation::Deg0,
+                flip_x: horizontal,
+                flip_y: vertical,
+            }
+        }
+    }
+
+    /// Reduces the orientation to whether the sprite is rotated by a quarter turn, and the
+    /// flips of the sprite before it. Half turns are flips along both axes.
+    pub(crate) fn quarter_turn_and_flips(self) -> (bool, bool, bool) {
+        let (quarter_turn, half_turn) = match self.rotation {
+            TileRotation::Deg0 => (false, false),
+            TileRotation::Deg90 => (true, false),
+            TileRotation::Deg180 => (false, true),
+            TileRotation::Deg270 => (true, true),
+        };
+        (
+            quarter_turn,
+            self.flip_x != half_turn,
+            self.flip_y != half_turn,
+        )
+    }
 }
 
 /// Trait for providing access to an underlying storage type of a 3-dimensional Tile data. This is abstracted to provide
@@ -62,6 +143,7 @@ pub trait Map {
     ) -> Result<Point3<u32>, TileOutOfBoundsError>;
 
     /// Returns the `Matrix4` transform which was created for transforming between world and tile coordinate spaces.
+    /// For staggered projections, this is the transform of the tiles which aren't shifted.
     fn transform(&self) -> &Matrix4<f32>;
 
     /// Call the underlying coordinate encoder for this map instance, which should always reduce to a u32 integer.
@@ -115,6 +197,13 @@ pub struct TileMap<T: Tile, E: CoordinateEncoder = crate::MortonEncoder2D> {
 
     pub(crate) version: u64,
 
+    /// Version of the map when each render chunk last changed, empty until a tile changes
+    #[serde(skip)]
+    pub(crate) chunk_versions: Vec<u64>,
+
+    #[serde(default)]
+    pub(crate) projection: TileProjection,
+
     #[serde(skip)]
     pub(crate) sprite_sheet: Option<Handle<SpriteSheet>>,
 
@@ -162,6 +251,114 @@ impl<T: Tile, E: CoordinateEncoder> TileMap<T, E> {
             transform,
             encoder,
             version: 1,
+            chunk_versions: Vec::new(),
+            projection: TileProjection::Orthogonal,
+        }
+    }
+
+    /// Places the tiles of the map with the given projection instead of in rows and columns.
+    #[must_use]
+    pub fn with_projection(mut self, projection: TileProjection) -> Self {
+        self.projection = projection;
+        self.transform = projection.transform(&self.dimensions, &self.tile_dimensions);
+        self.version += 1;
+        // Every chunk changed with the projection
+        self.chunk_versions.clear();
+        self
+    }
+
+    /// The projection placing the tiles of the map.
+    #[must_use]
+    pub fn projection(&self) -> TileProjection {
+        self.projection
+    }
+
+    /// Returns the transform from the coordinates of the given tile, with a negated y, to the space of the map.
+    /// This is the transform of the map, moved for the shifted tiles of staggered projections.
+    #[must_use]
+    pub fn coordinate_transform(&self, coord: &Point3<u32>) -> Matrix4<f32> {
+        if self
+            .projection
+            .stagger_index(i64::from(coord.x), i64::from(coord.y))
+            == 1
+        {
+            self.shifted_transform()
+        } else {
+            self.transform
+        }
+    }
+
+    /// The transform of the shifted tiles of staggered projections.
+    pub(crate) fn shifted_transform(&self) -> Matrix4<f32> {
+        Matrix4::new_translation(&self.projection.shift(&self.tile_dimensions)) * self.transform
+    }
+
+    /// Marks the tiles of a region as changed, so that the render pass encodes them again. Tiles
+    /// borrowed with `MapStorage::get_mut` are marked already, so this is only needed for tiles
+    /// whose sprite, tint or orientation depend on the world.
+    pub fn mark_changed(&mut self, region: &Region) {
+        if region.min.x >= region.max.x
+            || region.min.y >= region.max.y
+            || region.min.z >= region.max.z
+        {
+            return;
+        }
+        self.change_chunks(
+            &Point3::new(
+                region.min.x / RENDER_CHUNK,
+                region.min.y / RENDER_CHUNK,
+                region.min.z,
+            ),
+            &Point3::new(
+                (region.max.x - 1) / RENDER_CHUNK,
+                (region.max.y - 1) / RENDER_CHUNK,
+                region.max.z - 1,
+            ),
+        );
+    }
+
+    /// The number of render chunks along each axis.
+    pub(crate) fn chunk_counts(&self) -> Vector3<u32> {
+        Vector3::new(
+            (self.dimensions.x + RENDER_CHUNK - 1) / RENDER_CHUNK,
+            (self.dimensions.y + RENDER_CHUNK - 1) / RENDER_CHUNK,
+            self.dimensions.z,
+        )
+    }
+
+    fn chunk_index(&self, chunk: &Point3<u32>) -> Option<usize> {
+        let counts = self.chunk_counts();
+        if chunk.x >= counts.x || chunk.y >= counts.y || chunk.z >= counts.z {
+            return None;
+        }
+        Some(((chunk.z * counts.y + chunk.y) * counts.x + chunk.x) as usize)
+    }
+
+    /// The version of the map when the tiles of a render chunk last changed.
+    pub(crate) fn chunk_version(&self, chunk: &Point3<u32>) -> u64 {
+        self.chunk_index(chunk)
+            .and_then(|index| self.chunk_versions.get(index))
+            .copied()
+            .unwrap_or(self.version)
+    }
+
+    /// Bumps the version of the map, and sets it as the version of the render chunks between
+    /// the given chunks, inclusive.
+    fn change_chunks(&mut self, min: &Point3<u32>, max: &Point3<u32>) {
+        if self.chunk_versions.is_empty() {
+            let counts = self.chunk_counts();
+            self.chunk_versions
+                .resize((counts.x * counts.y * counts.z) as usize, self.version);
+        }
+        self.version += 1;
+        for z in min.z..=max.z {
+            for y in min.y..=max.y {
+                for x in min.x..=max.x {
+                    if let Some(index) = self.chunk_index(&Point3::new(x, y, z)) {
+                        self.chunk_versions[index] = self.version;
+                    }
+                }
+            }
         }
     }
 }
@@ -189,7 +386,7 @@ impl<T: Tile, E: CoordinateEncoder> Map for TileMap<T, E> {
 
     #[inline]
     fn to_world(&self, coord: &Point3<u32>, map_transform: Option<&Transform>) -> Vector3<f32> {
-        to_world(&self.transform, coord, map_transform)
+        to_world(&self.coordinate_transform(coord), coord, map_transform)
     }
 
     #[inline]
@@ -199,7 +396,11 @@ impl<T: Tile, E: CoordinateEncoder> Map for TileMap<T, E> {
         coord: &Vector3<f32>,
         map_transform: Option<&Transform>,
     ) -> Result<Point3<u32>, TileOutOfBoundsError> {
-        to_tile(&self.transform, coord, self.dimensions(), map_transform)
+        if self.projection.is_staggered() {
+            to_staggered_tile(self, coord, map_transform)
+        } else {
+            to_tile(&self.transform, coord, self.dimensions(), map_transform)
+        }
     }
 
     #[inline]
@@ -258,7 +459,13 @@ impl<T: Tile, E: CoordinateEncoder> MapStorage<T> for TileMap<T, E> {
 
     #[inline]
     fn get_raw_mut(&mut self, coord: u32) -> Option<&mut T> {
-        self.version += 1;
+        match self.encoder.decode(coord) {
+            Some((x, y, z)) => {
+                let chunk = Point3::new(x / RENDER_CHUNK, y / RENDER_CHUNK, z);
+                self.change_chunks(&chunk, &chunk);
+            }
+            None => self.version += 1,
+        }
         self.data.get_mut(coord as usize)
     }
 
@@ -269,7 +476,10 @@ impl<T: Tile, E: CoordinateEncoder> MapStorage<T> for TileMap<T, E> {
 }
 
 #[allow(clippy::cast_precision_loss)]
-fn create_transform(map_dimensions: &Vector3<u32>, tile_dimensions: &Vector3<u32>) -> Matrix4<f32> {
+pub(crate) fn create_transform(
+    map_dimensions: &Vector3<u32>,
+    tile_dimensions: &Vector3<u32>,
+) -> Matrix4<f32> {
     let tile_dimensions = Vector3::new(
         tile_dimensions.x as f32,
         tile_dimensions.y as f32,
@@ -342,6 +552,38 @@ fn to_tile(
     }
 }
 
+#[allow(clippy::cast_sign_loss, clippy::cast_possible_truncation)]
+fn to_staggered_tile<T: Tile, E: CoordinateEncoder>(
+    map: &TileMap<T, E>,
+    coord: &Vector3<f32>,
+    map_transform: Option<&Transform>,
+) -> Result<Point3<u32>, TileOutOfBoundsError> {
+    let point = Point3::from(*coord);
+    let point = map_transform.map_or(point, |map_trans| {
+        map_trans.global_view_matrix().transform_point(&point)
+    });
+
+    let tile = map
+        .projection
+        .nearest(&map.transform, &map.tile_dimensions, &point);
+    let max_dimensions = map.dimensions;
+    if tile.x < 0
+        || tile.x >= i64::from(max_dimensions.x)
+        || tile.y < 0
+        || tile.y >= i64::from(max_dimensions.y)
+        || tile.z < 0
+        || tile.z >= i64::from(max_dimensions.z)
+    {
+        let point_dimensions = Point3::new(tile.x as i32, tile.y as i32, tile.z as i32);
+        Err(TileOutOfBoundsError {
+            point_dimensions,
+            max_dimensions,
+        })
+    } else {
+        Ok(Point3::new(tile.x as u32, tile.y as u32, tile.z as u32))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use amethyst_core::math::Point3;
@@ -350,7 +592,7 @@ mod tests {
     use super::*;
     use crate::{
         morton::{MortonEncoder, MortonEncoder2D},
-        FlatEncoder,
+        FlatEncoder, HexOrientation, Region, StaggerAxis, StaggerIndex,
     };
 
     #[derive(Clone, Debug)]
@@ -370,6 +612,59 @@ mod tests {
         }
     }
 
+    #[test]
+    fn changes_are_tracked_by_chunk() {
+        let mut map = TileMap::<TestTile, FlatEncoder>::new(
+            Vector3::new(40, 20, 2),
+            Vector3::new(10, 10, 1),
+            None,
+        );
+        assert_eq!(map.chunk_counts(), Vector3::new(3, 2, 2));
+        let first = Point3::new(0, 0, 0);
+        let changed = Point3::new(2, 1, 1);
+        let version = map.chunk_version(&first);
+
+        map.get_mut(&Point3::new(35, 17, 1)).unwrap();
+        assert_eq!(map.chunk_version(&first), version);
+        assert!(map.chunk_version(&changed) > version);
+
+        map.mark_changed(&Region::new(Point3::new(0, 0, 0), Point3::new(16, 16, 1)));
+        assert!(map.chunk_version(&first) > version);
+        assert!(map.chunk_version(&Point3::new(1, 0, 0)) < map.chunk_version(&first));
+
+        let version = map.chunk_version(&changed);
+        map.get_mut_nochange(&Point3::new(35, 17, 1)).unwrap();
+        assert_eq!(map.chunk_version(&changed), version);
+    }
+
+    #[test]
+    fn orientations_reduce_to_quarter_turns_and_flips() {
+        let rotated = |rotation| {
+            TileOrientation {
+                rotation,
+                ..TileOrientation::default()
+            }
+            .quarter_turn_and_flips()
+        };
+        assert_eq!(rotated(TileRotation::Deg0), (false, false, false));
+        assert_eq!(rotated(TileRotation::Deg180), (false, true, true));
+        assert_eq!(rotated(TileRotation::Deg270), (true, true, true));
+
+        // Tiled rotates tiles clockwise by flipping them horizontally and diagonally
+        assert_eq!(
+            TileOrientation::from_flips(true, false, true),
+            TileOrientation {
+                rotation: TileRotation::Deg90,
+                flip_x: true,
+                flip_y: true,
+            }
+        );
+        assert_eq!(
+            TileOrientation::from_flips(true, true, false).quarter_turn_and_flips(),
+            rotated(TileRotation::Deg180)
+        );
+    }
+
     pub fn test_single_map<E: CoordinateEncoder>(dimensions: Vector3<u32>) {
         struct UnsafeWrapper<E: CoordinateEncoder> {
             ptr: *mut TileMap<TestTile, E>,
@@ -544,4 +839,99 @@ mod tests {
         let transform = create_transform(&Vector3::new(1, 2, 3), &Vector3::new(10, 10, 1));
         test_coord(&transform, Point3::new(0, 0, 0), Point3::new(0.0, 5.0, 0.0));
     }
+
+    fn test_projection_round_trip(projection: TileProjection) {
+        let map = TileMap::<TestTile, FlatEncoder>::new(
+            Vector3::new(7, 6, 2),
+            Vector3::new(32, 16, 1),
+            None,
+        )
+        .with_projection(projection);
+        for tile in &Region::new(Point3::new(0, 0, 0), Point3::new(7, 6, 2)) {
+            let world = map.to_world(&tile, None);
+            assert_eq!(map.to_tile(&world, None).unwrap(), tile);
+            // Points inside of the tile, off its center, are in the tile as well
+            let inside = world + Vector3::new(5.0, -2.0, 0.0);
+            assert_eq!(map.to_tile(&inside, None).unwrap(), tile);
+        }
+    }
+
+    #[test]
+    pub fn isometric_projection() {
+        let map = TileMap::<TestTile, FlatEncoder>::new(
+            Vector3::new(4, 4, 1),
+            Vector3::new(32, 16, 1),
+            None,
+        )
+        .with_projection(TileProjection::Isometric);
+        let to_world = |x, y| map.to_world(&Point3::new(x, y, 0), None);
+        assert_eq!(to_world(0, 0), Vector3::new(0.0, 24.0, 0.0));
+        assert_eq!(to_world(1, 0), Vector3::new(16.0, 16.0, 0.0));
+        assert_eq!(to_world(0, 1), Vector3::new(-16.0, 16.0, 0.0));
+        assert_eq!(to_world(3, 3), Vector3::new(0.0, -24.0, 0.0));
+        assert!(map.to_tile(&Vector3::new(0.0, 40.0, 0.0), None).is_err());
+
+        test_projection_round_trip(TileProjection::Isometric);
+    }
+
+    #[test]
+    pub fn staggered_projections() {
+        for &axis in &[StaggerAxis::X, StaggerAxis::Y] {
+            for &index in &[StaggerIndex::Odd, StaggerIndex::Even] {
+                test_projection_round_trip(TileProjection::Staggered { axis, index });
+            }
+        }
+        for &orientation in &[HexOrientation::Pointy, HexOrientation::Flat] {
+            test_projection_round_trip(TileProjection::Hexagonal {
+                orientation,
+                index: StaggerIndex::Odd,
+                side_length: 8,
+            });
+        }
+
+        let map = TileMap::<TestTile, FlatEncoder>::new(
+            Vector3::new(4, 4, 1),
+            Vector3::new(32, 16, 1),
+            None,
+        )
+        .with_projection(TileProjection::Staggered {
+            axis: StaggerAxis::Y,
+            index: StaggerIndex::Odd,
+        });
+        let first = map.to_world(&Point3::new(0, 0, 0), None);
+        assert_eq!(
+            map.to_world(&Point3::new(0, 1, 0), None) - first,
+            Vector3::new(16.0, -8.0, 0.0)
+        );
+        assert_eq!(
+            map.to_world(&Point3::new(1, 2, 0), None) - first,
+            Vector3::new(32.0, -16.0, 0.0)
+        );
+    }
+
+    #[test]
+    pub fn staggered_columns_draw_order() {
+        let projection = TileProjection::Hexagonal {
+            orientation: HexOrientation::Flat,
+            index: StaggerIndex::Odd,
+            side_length: 16,
+        };
+        let order: Vec<_> = projection
+            .draw_order(Region::new(Point3::new(0, 0, 0), Point3::new(4, 2, 1)))
+            .map(|p| (p.x, p.y))
+            .collect();
+        assert_eq!(
+            order,
+            vec![
+                (0, 0),
+                (2, 0),
+                (1, 0),
+                (3, 0),
+                (0, 1),
+                (2, 1),
+                (1, 1),
+                (3, 1)
+            ]
+        );
+    }
 }
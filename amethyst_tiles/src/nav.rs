@@ -0,0 +1,511 @@
+//! Per-tile metadata layers for collision and navigation, and an A* pathfinder over tile grids.
+#![allow(
+    clippy::cast_possible_truncation,
+    clippy::cast_precision_loss,
+    clippy::cast_sign_loss
+)]
+
+use std::{
+    cmp::Ordering,
+    collections::BinaryHeap,
+    ops::{BitAnd, BitOr, BitOrAssign},
+};
+
+use amethyst_core::{
+    math::{Point3, Vector3},
+    transform::Transform,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{iters::Region, map::Map};
+
+/// Layer of metadata of type `V` for each tile of a map, such as `TileFlags` or movement costs.
+/// Layers are usually added as components to the entity of the `TileMap` they describe.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TileLayer<V> {
+    dimensions: Vector3<u32>,
+    data: Vec<V>,
+}
+
+impl<V: Clone + Default> TileLayer<V> {
+    /// Creates a layer of the given dimensions, with the default value for every tile.
+    #[must_use]
+    pub fn new(dimensions: Vector3<u32>) -> Self {
+        Self::filled(dimensions, V::default())
+    }
+
+    /// Creates a layer of the given dimensions, with the same value for every tile.
+    #[must_use]
+    pub fn filled(dimensions: Vector3<u32>, value: V) -> Self {
+        let size = (dimensions.x * dimensions.y * dimensions.z) as usize;
+        Self {
+            dimensions,
+            data: vec![value; size],
+        }
+    }
+
+    /// Creates a layer with the dimensions of a map, with the default value for every tile.
+    #[must_use]
+    pub fn for_map<M: Map>(map: &M) -> Self {
+        Self::new(*map.dimensions())
+    }
+
+    /// Sets the value of every tile of a region.
+    pub fn fill(&mut self, region: &Region, value: V) {
+        for coord in region {
+            if let Some(tile) = self.get_mut(&coord) {
+                *tile = value.clone();
+            }
+        }
+    }
+}
+
+impl<V> TileLayer<V> {
+    /// The dimensions of the layer.
+    #[must_use]
+    pub fn dimensions(&self) -> &Vector3<u32> {
+        &self.dimensions
+    }
+
+    fn index(&self, coord: &Point3<u32>) -> Option<usize> {
+        if coord.x < self.dimensions.x && coord.y < self.dimensions.y && coord.z < self.dimensions.z
+        {
+            Some(((coord.z * self.dimensions.y + coord.y) * self.dimensions.x + coord.x) as usize)
+        } else {
+            None
+        }
+    }
+
+    /// The value of a tile, `None` outside of the layer.
+    #[must_use]
+    pub fn get(&self, coord: &Point3<u32>) -> Option<&V> {
+        self.index(coord).map(|index| &self.data[index])
+    }
+
+    /// Mutable reference to the value of a tile, `None` outside of the layer.
+    pub fn get_mut(&mut self, coord: &Point3<u32>) -> Option<&mut V> {
+        self.index(coord).map(move |index| &mut self.data[index])
+    }
+
+    /// Sets the value of a tile, returning false outside of the layer.
+    pub fn set(&mut self, coord: &Point3<u32>, value: V) -> bool {
+        if let Some(tile) = self.get_mut(coord) {
+            *tile = value;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// The value of the tile of a map at a point of the world.
+    #[must_use]
+    pub fn at_world<M: Map>(
+        &self,
+        map: &M,
+        point: &Vector3<f32>,
+        map_transform: Option<&Transform>,
+    ) -> Option<&V> {
+        map.to_tile(point, map_transform)
+            .ok()
+            .and_then(|coord| self.get(&coord))
+    }
+
+    /// Iterates over the coordinates of the tiles of a region matching a predicate.
+    #[must_use]
+    pub fn query<'a, F>(
+        &'a self,
+        region: &Region,
+        mut predicate: F,
+    ) -> impl Iterator<Item = Point3<u32>> + 'a
+    where
+        F: FnMut(&V) -> bool + 'a,
+    {
+        region
+            .iter()
+            .filter(move |coord| self.get(coord).map_or(false, |value| predicate(value)))
+    }
+
+    /// Returns true if a tile of the region matches the predicate, e.g. to test if a box
+    /// overlaps solid tiles.
+    pub fn any<F: FnMut(&V) -> bool>(&self, region: &Region, mut predicate: F) -> bool {
+        region
+            .iter()
+            .any(|coord| self.get(&coord).map_or(false, |value| predicate(value)))
+    }
+}
+
+/// Collision flags of a tile, combined with `|`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct TileFlags(pub u32);
+
+impl TileFlags {
+    /// No flags
+    pub const EMPTY: TileFlags = TileFlags(0);
+    /// The tile blocks movement
+    pub const SOLID: TileFlags = TileFlags(1);
+    /// The tile can only be crossed downwards, like a platform
+    pub const ONE_WAY: TileFlags = TileFlags(1 << 1);
+    /// The tile is water
+    pub const WATER: TileFlags = TileFlags(1 << 2);
+    /// The tile hurts
+    pub const HAZARD: TileFlags = TileFlags(1 << 3);
+    /// The first flag free for the game to define
+    pub const USER: TileFlags = TileFlags(1 << 16);
+
+    /// Returns true if all the given flags are set.
+    #[must_use]
+    pub fn contains(self, flags: TileFlags) -> bool {
+        self.0 & flags.0 == flags.0
+    }
+
+    /// Returns true if any of the given flags is set.
+    #[must_use]
+    pub fn intersects(self, flags: TileFlags) -> bool {
+        self.0 & flags.0 != 0
+    }
+
+    /// Sets the given flags.
+    pub fn insert(&mut self, flags: TileFlags) {
+        self.0 |= flags.0;
+    }
+
+    /// Clears the given flags.
+    pub fn remove(&mut self, flags: TileFlags) {
+        self.0 &= !flags.0;
+    }
+}
+
+impl BitOr for TileFlags {
+    type Output = Self;
+
+    fn bitor(self, other: Self) -> Self {
+        TileFlags(self.0 | other.0)
+    }
+}
+
+impl BitOrAssign for TileFlags {
+    fn bitor_assign(&mut self, other: Self) {
+        self.0 |= other.0;
+    }
+}
+
+impl BitAnd for TileFlags {
+    type Output = Self;
+
+    fn bitand(self, other: Self) -> Self {
+        TileFlags(self.0 & other.0)
+    }
+}
+
+impl TileLayer<TileFlags> {
+    /// Step cost for the `Pathfinder`, 1 between tiles without any of the blocking flags.
+    #[must_use]
+    pub fn blocking(
+        &self,
+        blocking: TileFlags,
+    ) -> impl FnMut(Point3<u32>, Point3<u32>) -> Option<f32> + '_ {
+        move |_, to| {
+            match self.get(&to) {
+                Some(flags) if !flags.intersects(blocking) => Some(1.0),
+                _ => None,
+            }
+        }
+    }
+}
+
+impl TileLayer<f32> {
+    /// Step cost for the `Pathfinder`, the cost of the tile moved to. Tiles with a negative or
+    /// infinite cost can't be entered.
+    #[must_use]
+    pub fn costs(&self) -> impl FnMut(Point3<u32>, Point3<u32>) -> Option<f32> + '_ {
+        move |_, to| {
+            match self.get(&to) {
+                Some(&cost) if cost >= 0.0 && cost.is_finite() => Some(cost),
+                _ => None,
+            }
+        }
+    }
+}
+
+/// A path found by the `Pathfinder`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TilePath {
+    /// The tiles of the path, from the start to the goal included
+    pub tiles: Vec<Point3<u32>>,
+    /// The total cost of the path
+    pub cost: f32,
+}
+
+#[derive(Debug, PartialEq)]
+struct Open {
+    estimate: f32,
+    index: usize,
+}
+
+impl Eq for Open {}
+
+impl PartialOrd for Open {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Open {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Reversed, for the heap to pop the lowest estimate first
+        other
+            .estimate
+            .partial_cmp(&self.estimate)
+            .unwrap_or(Ordering::Equal)
+            .then_with(|| other.index.cmp(&self.index))
+    }
+}
+
+/// A* pathfinder over the tiles of a z level of a map, with the cost of each step given by a
+/// callback.
+///
+/// The estimates assume that no step costs less than `min_cost` per tile, paths may not be the
+/// cheapest otherwise.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Pathfinder {
+    /// Allows diagonal steps, costing √2 times the callback cost. Diagonal steps can't cut the
+    /// corners of tiles which can't be entered.
+    pub diagonal: bool,
+    /// Lowest cost of a step
+    pub min_cost: f32,
+    /// Number of tiles visited before giving up
+    pub max_visited: usize,
+}
+
+impl Default for Pathfinder {
+    fn default() -> Self {
+        Self {
+            diagonal: false,
+            min_cost: 1.0,
+            max_visited: 100_000,
+        }
+    }
+}
+
+impl Pathfinder {
+    /// Creates a pathfinder, with or without diagonal steps.
+    #[must_use]
+    pub fn new(diagonal: bool) -> Self {
+        Self {
+            diagonal,
+            ..Self::default()
+        }
+    }
+
+    fn estimate(&self, from: &Point3<u32>, to: &Point3<u32>) -> f32 {
+        let dx = (from.x as f32 - to.x as f32).abs();
+        let dy = (from.y as f32 - to.y as f32).abs();
+        let distance = if self.diagonal {
+            dx.max(dy) + (std::f32::consts::SQRT_2 - 1.0) * dx.min(dy)
+        } else {
+            dx + dy
+        };
+        distance * self.min_cost
+    }
+
+    /// Finds the cheapest path between two tiles of the same z level, in a map of the given
+    /// dimensions. The cost callback gets the tiles of a step and returns its cost, `None` if
+    /// the step isn't possible.
+    pub fn find<F>(
+        &self,
+        dimensions: &Vector3<u32>,
+        start: Point3<u32>,
+        goal: Point3<u32>,
+        mut cost: F,
+    ) -> Option<TilePath>
+    where
+        F: FnMut(Point3<u32>, Point3<u32>) -> Option<f32>,
+    {
+        let (width, height) = (dimensions.x, dimensions.y);
+        if start.z != goal.z
+            || start.z >= dimensions.z
+            || start.x >= width
+            || start.y >= height
+            || goal.x >= width
+            || goal.y >= height
+        {
+            return None;
+        }
+        let z = start.z;
+        let index = |coord: &Point3<u32>| (coord.y * width + coord.x) as usize;
+        let coord = |index: usize| Point3::new(index as u32 % width, index as u32 / width, z);
+
+        let size = (width * height) as usize;
+        let mut costs = vec![std::f32::INFINITY; size];
+        let mut came_from = vec![usize::MAX; size];
+        let mut open = BinaryHeap::new();
+        costs[index(&start)] = 0.0;
+        open.push(Open {
+            estimate: self.estimate(&start, &goal),
+            index: index(&start),
+        });
+
+        let mut visited = 0;
+        while let Some(Open {
+            estimate,
+            index: current,
+        }) = open.pop()
+        {
+            let from = coord(current);
+            if from == goal {
+                let mut tiles = vec![goal];
+                let mut step = current;
+                while came_from[step] != usize::MAX {
+                    step = came_from[step];
+                    tiles.push(coord(step));
+                }
+                tiles.reverse();
+                return Some(TilePath {
+                    tiles,
+                    cost: costs[current],
+                });
+            }
+            // Skip the outdated entries of tiles reached again more cheaply
+            if estimate > costs[current] + self.estimate(&from, &goal) {
+                continue;
+            }
+            visited += 1;
+            if visited > self.max_visited {
+                return None;
+            }
+
+            for dy in -1_i64..=1 {
+                for dx in -1_i64..=1 {
+                    if (dx == 0 && dy == 0) || (!self.diagonal && dx != 0 && dy != 0) {
+                        continue;
+                    }
+                    let x = i64::from(from.x) + dx;
+                    let y = i64::from(from.y) + dy;
+                    if x < 0 || y < 0 || x >= i64::from(width) || y >= i64::from(height) {
+                        continue;
+                    }
+                    let to = Point3::new(x as u32, y as u32, z);
+                    let step_cost = if dx != 0 && dy != 0 {
+                        // No cutting corners
+                        let side_x = Point3::new(x as u32, from.y, z);
+                        let side_y = Point3::new(from.x, y as u32, z);
+                        if cost(from, side_x).is_none() || cost(from, side_y).is_none() {
+                            continue;
+                        }
+                        cost(from, to).map(|cost| cost * std::f32::consts::SQRT_2)
+                    } else {
+                        cost(from, to)
+                    };
+                    let step_cost = match step_cost {
+                        Some(step_cost) => step_cost,
+                        None => continue,
+                    };
+
+                    let next = index(&to);
+                    let to_cost = costs[current] + step_cost;
+                    if to_cost < costs[next] {
+                        costs[next] = to_cost;
+                        came_from[next] = current;
+                        open.push(Open {
+                            estimate: to_cost + self.estimate(&to, &goal),
+                            index: next,
+                        });
+                    }
+                }
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn walls() -> TileLayer<TileFlags> {
+        // A wall along x = 2 with a gap at y = 4
+        let mut layer = TileLayer::new(Vector3::new(5, 5, 1));
+        layer.fill(
+            &Region::new(Point3::new(2, 0, 0), Point3::new(3, 4, 1)),
+            TileFlags::SOLID,
+        );
+        layer
+    }
+
+    #[test]
+    fn layers_are_queried() {
+        let layer = walls();
+        assert_eq!(layer.get(&Point3::new(2, 1, 0)), Some(&TileFlags::SOLID));
+        assert_eq!(layer.get(&Point3::new(2, 4, 0)), Some(&TileFlags::EMPTY));
+        assert_eq!(layer.get(&Point3::new(5, 0, 0)), None);
+
+        let everything = Region::new(Point3::new(0, 0, 0), Point3::new(5, 5, 1));
+        let solid = layer.query(&everything, |flags| flags.contains(TileFlags::SOLID));
+        assert_eq!(solid.count(), 4);
+        assert!(!layer.any(
+            &Region::new(Point3::new(0, 0, 0), Point3::new(2, 5, 1)),
+            |flags| flags.intersects(TileFlags::SOLID | TileFlags::HAZARD)
+        ));
+    }
+
+    #[test]
+    fn paths_go_around_walls() {
+        let layer = walls();
+        let path = Pathfinder::new(false)
+            .find(
+                layer.dimensions(),
+                Point3::new(0, 0, 0),
+                Point3::new(4, 0, 0),
+                layer.blocking(TileFlags::SOLID),
+            )
+            .unwrap();
+        assert_eq!(path.tiles.len(), 13);
+        assert!(path.tiles.contains(&Point3::new(2, 4, 0)));
+        assert!((path.cost - 12.0).abs() < 1e-5);
+
+        let path = Pathfinder::new(true)
+            .find(
+                layer.dimensions(),
+                Point3::new(0, 0, 0),
+                Point3::new(4, 0, 0),
+                layer.blocking(TileFlags::SOLID),
+            )
+            .unwrap();
+        assert_eq!(path.tiles.len(), 11);
+
+        let mut closed = layer;
+        closed.set(&Point3::new(2, 4, 0), TileFlags::SOLID);
+        assert!(Pathfinder::new(true)
+            .find(
+                closed.dimensions(),
+                Point3::new(0, 0, 0),
+                Point3::new(4, 0, 0),
+                closed.blocking(TileFlags::SOLID),
+            )
+            .is_none());
+    }
+
+    #[test]
+    fn paths_follow_costs() {
+        let mut costs = TileLayer::filled(Vector3::new(3, 3, 1), 1.0_f32);
+        costs.set(&Point3::new(1, 0, 0), 10.0);
+        let path = Pathfinder::new(false)
+            .find(
+                costs.dimensions(),
+                Point3::new(0, 0, 0),
+                Point3::new(2, 0, 0),
+                costs.costs(),
+            )
+            .unwrap();
+        assert_eq!(
+            path.tiles,
+            vec![
+                Point3::new(0, 0, 0),
+                Point3::new(0, 1, 0),
+                Point3::new(1, 1, 0),
+                Point3::new(2, 1, 0),
+                Point3::new(2, 0, 0),
+            ]
+        );
+    }
+}
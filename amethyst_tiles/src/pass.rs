@@ -1,12 +1,12 @@
 #![allow(clippy::default_trait_access, clippy::use_self)]
 #![allow(unused_imports, unused_variables)]
 
-use std::marker::PhantomData;
+use std::{marker::PhantomData, ops::Range};
 
-use amethyst_assets::{AssetHandle, AssetStorage, Handle};
+use amethyst_assets::{AssetHandle, AssetStorage, Handle, LoadHandle};
 use amethyst_core::{
     dispatcher::{System, ThreadLocalSystem},
-    ecs::{component, world::World, EntityStore, IntoQuery, Resources, TryRead},
+    ecs::{component, world::World, Entity, EntityStore, IntoQuery, Resources, TryRead},
     geometry::{Plane, Ray},
     math::{self, clamp, convert, Matrix4, Point2, Point3, Vector2, Vector3, Vector4},
     transform::Transform,
@@ -16,6 +16,7 @@ use amethyst_rendy::{
     batch::{GroupIterator, OneLevelBatch, OrderedTwoLevelBatch},
     bundle::{RenderOrder, RenderPlan, RenderPlugin, Target},
     camera::{ActiveCamera, Camera},
+    palette::Srgba,
     pipeline::{PipelineDescBuilder, PipelinesBuilder},
     pod::IntoPod,
     rendy::{
@@ -34,7 +35,7 @@ use amethyst_rendy::{
         shader::{Shader, ShaderSetBuilder, SpirvShader},
     },
     resources::Tint as TintComponent,
-    sprite::{SpriteRender, SpriteSheet, Sprites},
+    sprite::{Sprite, SpriteRender, SpriteSheet, Sprites},
     sprite_visibility::SpriteVisibility,
     submodules::{
         gather::CameraGatherer, DynamicUniform, DynamicVertexBuffer, FlatEnvironmentSub, TextureId,
@@ -46,15 +47,18 @@ use amethyst_rendy::{
 };
 use amethyst_window::ScreenDimensions;
 use derivative::Derivative;
+use fnv::FnvHashMap;
 use glsl_layout::Uniform;
 #[cfg(feature = "profiler")]
 use thread_profiler::profile_scope;
 
 use crate::{
+    animation::TileAnimations,
+    fog::{FogOfWar, TileVisibility},
     iters::Region,
-    map::{Map, MapStorage, Tile, TileMap},
+    map::{Map, MapStorage, Tile, TileMap, RENDER_CHUNK},
     pod::{TileArgs, TileMapArgs},
-    CoordinateEncoder, MortonEncoder2D,
+    CoordinateEncoder, MortonEncoder2D, TileProjection,
 };
 
 lazy_static::lazy_static! {
@@ -76,6 +80,18 @@ lazy_static::lazy_static! {
 
 }
 
+/// Counter-clockwise quarter turn around the z axis.
+fn quarter_turn() -> Matrix4<f32> {
+    #[rustfmt::skip]
+    let quarter_turn = Matrix4::new(
+        0.0, -1.0, 0.0, 0.0,
+        1.0, 0.0, 0.0, 0.0,
+        0.0, 0.0, 1.0, 0.0,
+        0.0, 0.0, 0.0, 1.0,
+    );
+    quarter_turn
+}
+
 /// Trait to describe how rendering tiles may be culled for the tilemap to render
 pub trait DrawTiles2DBounds: 'static + std::fmt::Debug + Send + Sync {
     /// Returns the region to render the tiles
@@ -131,17 +147,15 @@ impl DrawTiles2DBounds for DrawTiles2DBoundsCameraCulling {
         map_transform: Option<&Transform>,
         aux: &GraphAuxData,
     ) -> Region {
-        let active_camera = aux.resources.get::<ActiveCamera>();
-        if active_camera.is_none() {
+        // the camera of the target, or else the active camera
+        let camera_entity = CameraGatherer::target_camera_entity(aux.world, aux.target)
+            .or_else(|| aux.resources.get::<ActiveCamera>()?.entity);
+        let camera_entity = match camera_entity {
+            Some(entity) => entity,
             // No active camera
-            return Region::empty();
-        }
-        let active_camera = active_camera.unwrap();
-        if active_camera.entity.is_none() {
-            // No entity in active camera
-            return Region::empty();
-        }
-        if let Ok(entry) = aux.world.entry_ref(active_camera.entity.unwrap()) {
+            None => return Region::empty(),
+        };
+        if let Ok(entry) = aux.world.entry_ref(camera_entity) {
             let tile_plane = Plane::from_point_normal(
                 &map_transform.map_or(Point3::new(0.0, 0.0, 0.0), |t| {
                     Point3::from(*t.translation())
@@ -185,17 +199,39 @@ impl DrawTiles2DBounds for DrawTiles2DBoundsCameraCulling {
             ];
             let x = i64::from(map.dimensions().x);
             let y = i64::from(map.dimensions().y);
+            // Tiles of other projections reach out of the bounds of their coordinates
+            let margin = if map.projection() == TileProjection::Orthogonal {
+                0
+            } else {
+                1
+            };
             // Cull the tilemap using the min and max coordinates along each axis of the tilemap
             #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
             return Region::new(
                 Point3::new(
-                    points.iter().map(|p| p.x).min().unwrap().max(0).min(x) as u32,
-                    points.iter().map(|p| p.y).min().unwrap().max(0).min(y) as u32,
+                    (points.iter().map(|p| p.x).min().unwrap() - margin)
+                        .max(0)
+                        .min(x) as u32,
+                    (points.iter().map(|p| p.y).min().unwrap() - margin)
+                        .max(0)
+                        .min(y) as u32,
                     0,
                 ),
                 Point3::new(
-                    points.iter().map(|p| p.x + 1).max().unwrap().max(0).min(x) as u32,
-                    points.iter().map(|p| p.y + 1).max().unwrap().max(0).min(y) as u32,
+                    points
+                        .iter()
+                        .map(|p| p.x + 1 + margin)
+                        .max()
+                        .unwrap()
+                        .max(0)
+                        .min(x) as u32,
+                    points
+                        .iter()
+                        .map(|p| p.y + 1 + margin)
+                        .max()
+                        .unwrap()
+                        .max(0)
+                        .min(y) as u32,
                     map.dimensions().z,
                 ),
             );
@@ -255,6 +291,7 @@ impl<B: Backend, T: Tile, E: CoordinateEncoder, Z: DrawTiles2DBounds>
             vertex,
             env: vec![env],
             sprites: Default::default(),
+            caches: Default::default(),
             _marker: PhantomData::default(),
             change: Default::default(),
         }))
@@ -268,6 +305,11 @@ impl<B: Backend, T: Tile, E: CoordinateEncoder, Z: DrawTiles2DBounds>
 /// for  transparency to occur correctly. If viewed from "underneath", transparency ordering issues will occur.
 ///
 /// In shorter terms, this means that the camera must "Look Down" at the tiles.
+/// - Maps with a `FogOfWar` don't draw their unexplored tiles, and darken their explored tiles out
+/// of sight.
+/// - The vertex data of tiles is kept between frames, and only encoded again for the chunks of
+/// tiles which changed through `MapStorage::get_mut` or `TileMap::mark_changed`, or whose
+/// animations changed.
 #[derive(Derivative)]
 #[derivative(Debug(bound = ""))]
 pub struct DrawTiles2D<
@@ -285,10 +327,160 @@ pub struct DrawTiles2D<
 
     env: Vec<DynamicUniform<B, TileMapArgs>>,
 
+    #[derivative(Debug = "ignore")]
+    caches: FnvHashMap<Entity, MapCache>,
+
     #[derivative(Debug = "ignore")]
     _marker: PhantomData<(T, E, Z)>,
 }
 
+/// Encoded tiles of the render chunks of a map, kept between frames.
+#[derive(Debug, Default)]
+struct MapCache {
+    /// Sprite sheet, dimensions and fog of war tint of the map
+    source: Option<(LoadHandle, Vector3<u32>, Option<Srgba>)>,
+    chunks: FnvHashMap<Point3<u32>, ChunkCache>,
+    seen: bool,
+}
+
+impl MapCache {
+    /// Iterates over the render chunks of a region.
+    fn chunks_of(region: &Region) -> impl Iterator<Item = Point3<u32>> {
+        let empty = region.min.x >= region.max.x || region.min.y >= region.max.y;
+        let min = region.min / RENDER_CHUNK;
+        let max = if empty {
+            min
+        } else {
+            Point3::new(
+                (region.max.x + RENDER_CHUNK - 1) / RENDER_CHUNK,
+                (region.max.y + RENDER_CHUNK - 1) / RENDER_CHUNK,
+                0,
+            )
+        };
+        (region.min.z..region.max.z).flat_map(move |z| {
+            (min.y..max.y).flat_map(move |y| (min.x..max.x).map(move |x| Point3::new(x, y, z)))
+        })
+    }
+
+    /// Iterates over the encoded tiles of a region in draw order, with the offsets of their map
+    /// arguments. The chunks of the region must have been encoded.
+    fn visible(&self, region: Region, passes: u32) -> impl Iterator<Item = (usize, TileArgs)> + '_ {
+        let first = region.min.x / RENDER_CHUNK;
+        let last = region.max.x.saturating_sub(1) / RENDER_CHUNK;
+        // Chunks are drawn row by row, so that tiles overlapping across chunks keep their order
+        (region.min.z..region.max.z)
+            .flat_map(move |z| {
+                (region.min.y..region.max.y).flat_map(move |y| {
+                    (0..passes).flat_map(move |pass| {
+                        (first..=last).filter_map(move |x| {
+                            let chunk = self.chunks.get(&Point3::new(x, y / RENDER_CHUNK, z))?;
+                            let segment = chunk
+                                .segments
+                                .get(((y % RENDER_CHUNK) * passes + pass) as usize)?;
+                            Some(&chunk.tiles[segment.clone()])
+                        })
+                    })
+                })
+            })
+            .flatten()
+            .filter(move |(x, ..)| *x >= region.min.x && *x < region.max.x)
+            .map(|&(_, args_offset, args)| (args_offset, args))
+    }
+}
+
+/// Encoded tiles of a render chunk of a map.
+#[derive(Debug, Default)]
+struct ChunkCache {
+    /// Version of the chunk when it was encoded
+    version: u64,
+    /// Revision of the animations when the chunk was encoded
+    revision: u64,
+    /// Frame of the animations when the chunk was encoded
+    frame: u64,
+    /// Whether some tiles of the chunk are animated
+    animated: bool,
+    /// Coordinate x, offset of the map arguments and vertex data of the tiles, in draw order
+    tiles: Vec<(u32, usize, TileArgs)>,
+    /// Tiles of each pass over each row of the chunk
+    segments: Vec<Range<usize>>,
+}
+
+impl ChunkCache {
+    fn is_current(&self, version: u64, revision: u64, frame: u64) -> bool {
+        self.version == version
+            && self.revision == revision
+            && (!self.animated || self.frame == frame)
+    }
+
+    /// Encodes the tiles of the chunk, with the callback giving the current sprite of animated
+    /// sprites.
+    fn encode<T: Tile, E: CoordinateEncoder, F: Fn(usize) -> Option<usize>>(
+        &mut self,
+        tile_map: &TileMap<T, E>,
+        chunk: &Point3<u32>,
+        sprites: &[Sprite],
+        animate: F,
+        fog: Option<&FogOfWar>,
+        world: &World,
+    ) {
+        self.tiles.clear();
+        self.segments.clear();
+        self.animated = false;
+
+        let projection = tile_map.projection();
+        let passes = projection.draw_passes();
+        let dimensions = tile_map.dimensions();
+        let min = Point3::new(chunk.x * RENDER_CHUNK, chunk.y * RENDER_CHUNK, chunk.z);
+        let max_x = (min.x + RENDER_CHUNK).min(dimensions.x);
+        let max_y = (min.y + RENDER_CHUNK).min(dimensions.y);
+        for y in min.y..max_y {
+            for pass in 0..passes {
+                let start = self.tiles.len();
+                for x in (min.x..max_x).filter(|&x| projection.is_drawn_in_pass(x, y, pass)) {
+                    let coord = Point3::new(x, y, min.z);
+                    let tile = match tile_map.get(&coord) {
+                        Some(tile) => tile,
+                        None => continue,
+                    };
+                    let visibility = fog.map(|fog| fog.visibility(&coord));
+                    if visibility == Some(TileVisibility::Unexplored) {
+                        continue;
+                    }
+                    if let Some(sprite_number) = tile.sprite(coord, world) {
+                        let sprite_number = match animate(sprite_number) {
+                            Some(animated) => {
+                                self.animated = true;
+                                animated
+                            }
+                            None => sprite_number,
+                        };
+                        let (quarter_turn, flip_x, flip_y) =
+                            tile.orientation(coord, world).quarter_turn_and_flips();
+                        let tint = match (fog, visibility) {
+                            (Some(fog), Some(TileVisibility::Explored)) => {
+                                fog.darken(tile.tint(coord, world))
+                            }
+                            _ => tile.tint(coord, world),
+                        };
+                        let args = TileArgs::from_flipped_data(
+                            sprites,
+                            sprite_number,
+                            Some(&TintComponent(tint)),
+                            &coord,
+                            flip_x,
+                            flip_y,
+                        );
+                        let stagger_index = projection.stagger_index(i64::from(x), i64::from(y));
+                        let args_offset = 2 * stagger_index + usize::from(quarter_turn);
+                        self.tiles.push((x, args_offset, args));
+                    }
+                }
+                self.segments.push(start..self.tiles.len());
+            }
+        }
+    }
+}
+
 impl<B: Backend, T: Tile, E: CoordinateEncoder, Z: DrawTiles2DBounds> RenderGroup<B, GraphAuxData>
     for DrawTiles2D<B, T, E, Z>
 {
@@ -318,77 +510,146 @@ impl<B: Backend, T: Tile, E: CoordinateEncoder, Z: DrawTiles2DBounds> RenderGrou
             .get::<AssetStorage<Sprites>>()
             .expect("Could not get Sprites storage.");
 
+        let animations = aux.resources.get::<TileAnimations>();
+
+        let (animation_revision, animation_frame) =
+            animations.as_ref().map_or((0, 0), |animations| {
+                (animations.revision(), animations.frame())
+            });
+
         let sprites_ref = &mut self.sprites;
         let textures_ref = &mut self.textures;
+        let caches = &mut self.caches;
 
         sprites_ref.swap_clear();
 
-        let CameraGatherer { projview, .. } = CameraGatherer::gather(aux.world, aux.resources);
+        let CameraGatherer { projview, .. } =
+            CameraGatherer::gather_for_target(aux.world, aux.resources, aux.target);
 
         let mut tilemap_args = vec![];
 
-        let mut query =
-            <(&TileMap<T, E>, TryRead<Transform>)>::query().filter(!component::<Hidden>());
-
-        for (tile_map, transform) in query.iter(aux.world) {
-            if let Some(sheet) = tile_map
-                .sprite_sheet
-                .as_ref()
-                .and_then(|handle| sprite_sheet_storage.get(handle))
-            {
+        let mut query = <(
+            Entity,
+            &TileMap<T, E>,
+            TryRead<Transform>,
+            TryRead<FogOfWar>,
+        )>::query()
+        .filter(!component::<Hidden>());
+
+        for (entity, tile_map, transform, fog) in query.iter(aux.world) {
+            if let Some((sheet_handle, sheet)) = tile_map.sprite_sheet.as_ref().and_then(|handle| {
+                sprite_sheet_storage
+                    .get(handle)
+                    .map(|sheet| (handle, sheet))
+            }) {
                 if let Some(sprites) = sprites_storage.get(&sheet.sprites) {
-                    let sprites = sprites.build_sprites();
+                    let (tex_id, this_changed) = match textures_ref.insert(
+                        factory,
+                        aux.resources,
+                        &sheet.texture,
+                        hal::image::Layout::ShaderReadOnlyOptimal,
+                    ) {
+                        Some(inserted) => inserted,
+                        None => continue,
+                    };
+                    changed = changed || this_changed;
 
                     let tilemap_args_index = tilemap_args.len();
-                    let map_coordinate_transform: [[f32; 4]; 4] = (*tile_map.transform()).into();
-                    let map_transform: [[f32; 4]; 4] = transform.map_or_else(
-                        || Matrix4::identity().into(),
-                        |transform| (*transform.global_matrix()).into(),
-                    );
-
-                    tilemap_args.push(TileMapArgs {
-                        proj: projview.proj,
-                        view: projview.view,
-                        map_coordinate_transform: map_coordinate_transform.into(),
-                        map_transform: map_transform.into(),
-                        sprite_dimensions: [
-                            tile_map.tile_dimensions().x as f32,
-                            tile_map.tile_dimensions().y as f32,
-                        ]
-                        .into(),
-                    });
-
-                    compute_region::<T, E, Z>(&tile_map, transform, aux)
-                        .iter()
-                        .filter_map(|coord| {
-                            let tile = tile_map.get(&coord).unwrap();
-                            if let Some(sprite_number) = tile.sprite(coord, aux.world) {
-                                let batch_data = TileArgs::from_data(
-                                    &sprites,
-                                    sprite_number,
-                                    Some(&TintComponent(tile.tint(coord, aux.world))),
-                                    &coord,
-                                );
-
-                                let (tex_id, this_changed) = textures_ref.insert(
-                                    factory,
-                                    aux.resources,
-                                    &sheet.texture,
-                                    hal::image::Layout::ShaderReadOnlyOptimal,
-                                )?;
-                                changed = changed || this_changed;
-
-                                return Some((tex_id, batch_data));
-                            }
-                            None
-                        })
-                        .for_each_group(|tex_id, batch_data| {
-                            sprites_ref.insert(tex_id, tilemap_args_index, batch_data.drain(..))
+                    let map_transform = transform
+                        .map_or_else(Matrix4::identity, |transform| *transform.global_matrix());
+
+                    // Staggered projections use a second transform for the shifted tiles, and
+                    // each transform is followed by the transform of the tiles rotated by a
+                    // quarter turn. Those rotate the axes of the sprites, and keep the tiles in
+                    // place by rotating the coordinates back.
+                    let projection = tile_map.projection();
+                    let coordinate_transforms =
+                        [*tile_map.transform(), tile_map.shifted_transform()];
+                    let count = if projection.is_staggered() { 2 } else { 1 };
+                    let quarter_turn = quarter_turn();
+                    for coordinate_transform in &coordinate_transforms[..count] {
+                        for (map_transform, coordinate_transform) in &[
+                            (map_transform, *coordinate_transform),
+                            (
+                                map_transform * quarter_turn,
+                                quarter_turn.transpose() * coordinate_transform,
+                            ),
+                        ] {
+                            let map_coordinate_transform: [[f32; 4]; 4] =
+                                (*coordinate_transform).into();
+                            let map_transform: [[f32; 4]; 4] = (*map_transform).into();
+                            tilemap_args.push(TileMapArgs {
+                                proj: projview.proj,
+                                view: projview.view,
+                                map_coordinate_transform: map_coordinate_transform.into(),
+                                map_transform: map_transform.into(),
+                                sprite_dimensions: [
+                                    tile_map.tile_dimensions().x as f32,
+                                    tile_map.tile_dimensions().y as f32,
+                                ]
+                                .into(),
+                            });
+                        }
+                    }
+
+                    let region = compute_region::<T, E, Z>(&tile_map, transform, aux);
+                    let cache = caches.entry(*entity).or_default();
+                    cache.seen = true;
+                    let source = Some((
+                        sheet_handle.load_handle(),
+                        *tile_map.dimensions(),
+                        fog.map(FogOfWar::explored_tint),
+                    ));
+                    if cache.source != source {
+                        cache.chunks.clear();
+                        cache.source = source;
+                    }
+
+                    // Only the visible chunks which changed since they were last drawn are encoded
+                    let mut built_sprites = None;
+                    for chunk in MapCache::chunks_of(&region) {
+                        let version = tile_map.chunk_version(&chunk);
+                        let cached = cache.chunks.entry(chunk).or_default();
+                        if !cached.is_current(version, animation_revision, animation_frame) {
+                            let sprites =
+                                built_sprites.get_or_insert_with(|| sprites.build_sprites());
+                            let animations = animations.as_deref();
+                            cached.encode(
+                                tile_map,
+                                &chunk,
+                                sprites,
+                                |sprite| {
+                                    animations
+                                        .filter(|animations| {
+                                            animations.animates(sheet_handle, sprite)
+                                        })
+                                        .map(|animations| animations.sprite(sheet_handle, sprite))
+                                },
+                                fog,
+                                aux.world,
+                            );
+                            cached.version = version;
+                            cached.revision = animation_revision;
+                            cached.frame = animation_frame;
+                        }
+                    }
+
+                    cache
+                        .visible(region, projection.draw_passes())
+                        .for_each_group(|args_offset, batch_data| {
+                            sprites_ref.insert(
+                                tex_id,
+                                tilemap_args_index + args_offset,
+                                batch_data.drain(..),
+                            )
                         });
                 }
             }
         }
 
+        // Forget the maps which weren't drawn
+        caches.retain(|_, cache| std::mem::replace(&mut cache.seen, false));
+
         self.textures.maintain(factory, aux.resources);
         changed = changed || self.sprites.changed();
 
@@ -86,12 +86,43 @@ impl TileArgs {
         sprite_number: usize,
         tint: Option<&TintComponent>,
         tile_coordinate: &Point3<u32>,
+    ) -> Self {
+        Self::from_flipped_data(sprites, sprite_number, tint, tile_coordinate, false, false)
+    }
+
+    /// Extracts POD vertex data from the provided storages for a tile with a flipped sprite.
+    ///
+    /// # Arguments
+    /// * `sprite_number` - The number index of the sprite in the sprite sheet.
+    /// * `tint` - An optional `TintComponent` reference for tinting this tile, if applicable.
+    /// * `tile_coordinate` - The  Point3<u32> position of this tile (in Tile Coordinate Space)
+    /// * `flip_x` - Whether the sprite is flipped horizontally.
+    /// * `flip_y` - Whether the sprite is flipped vertically.
+    #[must_use]
+    pub fn from_flipped_data<'a>(
+        sprites: &'a [Sprite],
+        sprite_number: usize,
+        tint: Option<&TintComponent>,
+        tile_coordinate: &Point3<u32>,
+        flip_x: bool,
+        flip_y: bool,
     ) -> Self {
         let sprite = &sprites[sprite_number];
+        let coords = &sprite.tex_coords;
+        let (left, right) = if flip_x {
+            (coords.right, coords.left)
+        } else {
+            (coords.left, coords.right)
+        };
+        let (top, bottom) = if flip_y {
+            (coords.bottom, coords.top)
+        } else {
+            (coords.top, coords.bottom)
+        };
 
         Self {
-            u_offset: [sprite.tex_coords.left, sprite.tex_coords.right].into(),
-            v_offset: [sprite.tex_coords.top, sprite.tex_coords.bottom].into(),
+            u_offset: [left, right].into(),
+            v_offset: [top, bottom].into(),
             tint: tint.map_or([1.0; 4].into(), |t| t.0.into_pod()),
             tile_coordinate: [tile_coordinate.x, tile_coordinate.y, tile_coordinate.z].into(),
         }
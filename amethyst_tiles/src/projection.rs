@@ -0,0 +1,282 @@
+use amethyst_core::math::{Matrix4, Point3, Vector2, Vector3};
+use serde::{Deserialize, Serialize};
+
+use crate::{iters::Region, map::create_transform};
+
+/// Axis along which every other row or column of a staggered map is shifted by half a tile.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StaggerAxis {
+    /// Columns are staggered, every other column is shifted down
+    X,
+    /// Rows are staggered, every other row is shifted right
+    Y,
+}
+
+/// Which rows or columns of a staggered map are shifted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StaggerIndex {
+    /// Odd rows or columns are shifted
+    Odd,
+    /// Even rows or columns are shifted
+    Even,
+}
+
+/// Orientation of the tiles of a hexagonal map.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HexOrientation {
+    /// Pointy top tiles, in staggered rows
+    Pointy,
+    /// Flat top tiles, in staggered columns
+    Flat,
+}
+
+/// Projection placing the tiles of a `TileMap` in the space of the map. Tiles are still drawn
+/// as sprites of the tile dimensions, centered on their positions, so isometric and hexagonal
+/// sprites must be drawn with transparent corners.
+///
+/// Tile coordinates keep the same meaning as in the Tiled editor: x increases to the right and
+/// y downwards, along the diagonals of the screen for isometric maps.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TileProjection {
+    /// Square tiles, in rows and columns.
+    Orthogonal,
+    /// Diamond isometric tiles, x going down right and y down left from the top corner.
+    Isometric,
+    /// Staggered isometric tiles, in rows or columns half a tile apart which are shifted by half
+    /// a tile every other time.
+    Staggered {
+        /// Staggered rows or columns
+        axis: StaggerAxis,
+        /// Shifted rows or columns
+        index: StaggerIndex,
+    },
+    /// Hexagonal tiles, in staggered rows or columns.
+    Hexagonal {
+        /// Pointy or flat top tiles
+        orientation: HexOrientation,
+        /// Shifted rows or columns
+        index: StaggerIndex,
+        /// Length in pixels of the sides of the tiles along the stagger axis, which is half
+        /// of the tile height for regular pointy top tiles
+        side_length: u32,
+    },
+}
+
+impl Default for TileProjection {
+    fn default() -> Self {
+        TileProjection::Orthogonal
+    }
+}
+
+#[allow(clippy::cast_precision_loss, clippy::cast_possible_truncation)]
+impl TileProjection {
+    fn stagger(self) -> Option<(StaggerAxis, StaggerIndex)> {
+        match self {
+            TileProjection::Staggered { axis, index } => Some((axis, index)),
+            TileProjection::Hexagonal {
+                orientation: HexOrientation::Pointy,
+                index,
+                ..
+            } => Some((StaggerAxis::Y, index)),
+            TileProjection::Hexagonal {
+                orientation: HexOrientation::Flat,
+                index,
+                ..
+            } => Some((StaggerAxis::X, index)),
+            _ => None,
+        }
+    }
+
+    /// Returns true for projections shifting every other row or column.
+    #[must_use]
+    pub fn is_staggered(self) -> bool {
+        self.stagger().is_some()
+    }
+
+    /// The steps in the space of the map along x and y, and the shift of staggered tiles.
+    fn basis(self, tile_dimensions: &Vector3<u32>) -> (Vector2<f32>, Vector2<f32>, Vector2<f32>) {
+        let w = tile_dimensions.x as f32;
+        let h = tile_dimensions.y as f32;
+        match self {
+            TileProjection::Orthogonal => {
+                (
+                    Vector2::new(w, 0.0),
+                    Vector2::new(0.0, -h),
+                    Vector2::zeros(),
+                )
+            }
+            TileProjection::Isometric => {
+                (
+                    Vector2::new(w / 2.0, -h / 2.0),
+                    Vector2::new(-w / 2.0, -h / 2.0),
+                    Vector2::zeros(),
+                )
+            }
+            TileProjection::Staggered {
+                axis: StaggerAxis::X,
+                ..
+            } => {
+                (
+                    Vector2::new(w / 2.0, 0.0),
+                    Vector2::new(0.0, -h),
+                    Vector2::new(0.0, -h / 2.0),
+                )
+            }
+            TileProjection::Staggered {
+                axis: StaggerAxis::Y,
+                ..
+            } => {
+                (
+                    Vector2::new(w, 0.0),
+                    Vector2::new(0.0, -h / 2.0),
+                    Vector2::new(w / 2.0, 0.0),
+                )
+            }
+            TileProjection::Hexagonal {
+                orientation: HexOrientation::Flat,
+                side_length,
+                ..
+            } => {
+                (
+                    Vector2::new((w + side_length as f32) / 2.0, 0.0),
+                    Vector2::new(0.0, -h),
+                    Vector2::new(0.0, -h / 2.0),
+                )
+            }
+            TileProjection::Hexagonal {
+                orientation: HexOrientation::Pointy,
+                side_length,
+                ..
+            } => {
+                (
+                    Vector2::new(w, 0.0),
+                    Vector2::new(0.0, -(h + side_length as f32) / 2.0),
+                    Vector2::new(w / 2.0, 0.0),
+                )
+            }
+        }
+    }
+
+    /// Creates the transform from tile coordinates, with a negated y, to the space of the map,
+    /// centering the map on its origin. Shifted tiles of staggered projections are moved by
+    /// `shift` on top of it.
+    pub(crate) fn transform(
+        self,
+        dimensions: &Vector3<u32>,
+        tile_dimensions: &Vector3<u32>,
+    ) -> Matrix4<f32> {
+        if self == TileProjection::Orthogonal {
+            return create_transform(dimensions, tile_dimensions);
+        }
+
+        let (step_x, step_y, shift) = self.basis(tile_dimensions);
+        // Tile coordinates are given with a negated y, as for the orthogonal transform
+        let step_y = -step_y;
+        let last_x = dimensions.x.saturating_sub(1) as f32;
+        let last_y = dimensions.y.saturating_sub(1) as f32;
+        let translation = -(step_x * last_x - step_y * last_y + shift) / 2.0;
+
+        #[rustfmt::skip]
+        let transform = Matrix4::new(
+            step_x.x, step_y.x, 0.0, translation.x,
+            step_x.y, step_y.y, 0.0, translation.y,
+            0.0, 0.0, tile_dimensions.z as f32, 0.0,
+            0.0, 0.0, 0.0, 1.0,
+        );
+        transform
+    }
+
+    /// Offset of the shifted tiles of staggered projections, in the space of the map.
+    pub(crate) fn shift(self, tile_dimensions: &Vector3<u32>) -> Vector3<f32> {
+        let (_, _, shift) = self.basis(tile_dimensions);
+        Vector3::new(shift.x, shift.y, 0.0)
+    }
+
+    /// Returns 1 for the shifted tiles of staggered projections, and 0 otherwise.
+    pub(crate) fn stagger_index(self, x: i64, y: i64) -> usize {
+        self.stagger().map_or(0, |(axis, index)| {
+            let along = match axis {
+                StaggerAxis::X => x,
+                StaggerAxis::Y => y,
+            };
+            usize::from((along.rem_euclid(2) == 1) == (index == StaggerIndex::Odd))
+        })
+    }
+
+    /// Finds the tile under a point of the space of the map, which may be outside of the map.
+    pub(crate) fn nearest(
+        self,
+        transform: &Matrix4<f32>,
+        tile_dimensions: &Vector3<u32>,
+        point: &Point3<f32>,
+    ) -> Point3<i64> {
+        let inverse = transform
+            .try_inverse()
+            .unwrap()
+            .transform_point(point)
+            .coords;
+        let z = inverse.z.floor() as i64;
+        if !self.is_staggered() {
+            return Point3::new(inverse.x.round() as i64, -inverse.y.round() as i64, z);
+        }
+
+        // Staggered tiles aren't on a lattice, so look for the closest of the neighbouring
+        // tiles, measuring as diamonds for isometric tiles and as circles for hexagons.
+        let shift = self.shift(tile_dimensions);
+        let width = tile_dimensions.x as f32;
+        let height = tile_dimensions.y as f32;
+        let x = inverse.x.round() as i64;
+        let y = -inverse.y.round() as i64;
+        let mut nearest = Point3::new(x, y, z);
+        let mut nearest_distance = std::f32::INFINITY;
+        for y in y - 1..=y + 1 {
+            for x in x - 1..=x + 1 {
+                let mut center =
+                    transform.transform_point(&Point3::new(x as f32, -y as f32, inverse.z));
+                if self.stagger_index(x, y) == 1 {
+                    center += shift;
+                }
+                let delta = point - center;
+                let distance = match self {
+                    TileProjection::Staggered { .. } => {
+                        delta.x.abs() / width + delta.y.abs() / height
+                    }
+                    _ => delta.x * delta.x + delta.y * delta.y,
+                };
+                if distance < nearest_distance {
+                    nearest = Point3::new(x, y, z);
+                    nearest_distance = distance;
+                }
+            }
+        }
+        nearest
+    }
+
+    /// Iterates over the tiles of a region in the order they are drawn, from the back to the
+    /// front.
+    pub(crate) fn draw_order(self, region: Region) -> impl Iterator<Item = Point3<u32>> {
+        (region.min.z..region.max.z).flat_map(move |z| {
+            (region.min.y..region.max.y).flat_map(move |y| {
+                (0..self.draw_passes()).flat_map(move |pass| {
+                    (region.min.x..region.max.x)
+                        .filter(move |&x| self.is_drawn_in_pass(x, y, pass))
+                        .map(move |x| Point3::new(x, y, z))
+                })
+            })
+        })
+    }
+
+    /// The number of times each row is iterated over to draw its tiles.
+    pub(crate) fn draw_passes(self) -> u32 {
+        // Staggered columns are drawn after the other columns of their row, which they overlap
+        match self.stagger() {
+            Some((StaggerAxis::X, _)) => 2,
+            _ => 1,
+        }
+    }
+
+    /// Returns true if the tile is drawn in the given pass over its row.
+    pub(crate) fn is_drawn_in_pass(self, x: u32, y: u32, pass: u32) -> bool {
+        self.draw_passes() == 1 || self.stagger_index(i64::from(x), i64::from(y)) == pass as usize
+    }
+}
@@ -0,0 +1,858 @@
+//! Importer for maps made with the [Tiled](https://www.mapeditor.org/) editor.
+//!
+//! `.tmx` files are imported as [`TiledMap`] assets by the [`TiledFormat`], with their tile
+//! layers, tilesets, object layers and custom properties. Once loaded, [`TiledMap::spawn`]
+//! turns the map into `TileMap<TiledTile>` entities, one per tileset, with a z level per tile
+//! layer, and spawns an entity with a [`TiledObject`] for every object.
+//!
+//! Tile layer data can be stored as CSV, uncompressed base64 or XML in the editor. External
+//! tilesets are imported from `.tsx` files as [`TiledTileset`] assets, and set on the map with
+//! [`TiledMap::set_tileset`] before spawning it.
+#![allow(
+    clippy::cast_possible_truncation,
+    clippy::cast_precision_loss,
+    clippy::cast_sign_loss
+)]
+
+use std::{collections::BTreeMap, str::FromStr};
+
+use amethyst_assets::{
+    register_asset_type, register_importer, Asset, AssetProcessorSystem, Format, Handle,
+};
+use amethyst_core::{
+    ecs::{Entity, World},
+    math::{Point3, Vector3},
+    transform::Transform,
+    Named,
+};
+use amethyst_error::{format_err, Error};
+use amethyst_rendy::SpriteSheet;
+use serde::{Deserialize, Serialize};
+use type_uuid::TypeUuid;
+
+use crate::{
+    map::{MapStorage, Tile},
+    FlatEncoder, HexOrientation, StaggerAxis, StaggerIndex, TileAnimation, TileAnimations,
+    TileFrame, TileLayer, TileMap, TileOrientation, TileProjection,
+};
+
+const FLIPPED_HORIZONTALLY: u32 = 0x8000_0000;
+const FLIPPED_VERTICALLY: u32 = 0x4000_0000;
+const FLIPPED_DIAGONALLY: u32 = 0x2000_0000;
+const GID_MASK: u32 = !(FLIPPED_HORIZONTALLY | FLIPPED_VERTICALLY | FLIPPED_DIAGONALLY);
+
+/// Value of a custom property.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum TiledProperty {
+    /// `bool` property
+    Bool(bool),
+    /// `int` and `object` properties
+    Int(i64),
+    /// `float` property
+    Float(f64),
+    /// `string`, `file` and `color` properties
+    String(String),
+}
+
+impl TiledProperty {
+    /// The value of a `bool` property.
+    #[must_use]
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            TiledProperty::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// The value of an `int` property.
+    #[must_use]
+    pub fn as_int(&self) -> Option<i64> {
+        match self {
+            TiledProperty::Int(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// The value of a `float` or `int` property.
+    #[must_use]
+    pub fn as_float(&self) -> Option<f64> {
+        match self {
+            TiledProperty::Float(value) => Some(*value),
+            TiledProperty::Int(value) => Some(*value as f64),
+            _ => None,
+        }
+    }
+
+    /// The value of a `string`, `file` or `color` property.
+    #[must_use]
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            TiledProperty::String(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+/// Custom properties, by name.
+pub type TiledProperties = BTreeMap<String, TiledProperty>;
+
+/// A tileset of a Tiled map, embedded in the map or imported from a `.tsx` file.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, TypeUuid)]
+#[uuid = "8d6a4f0e-2b1c-4e7a-9c53-1f0b6e2d7a84"]
+pub struct TiledTileset {
+    /// Name of the tileset
+    pub name: String,
+    /// Width of the tiles, in pixels
+    pub tile_width: u32,
+    /// Height of the tiles, in pixels
+    pub tile_height: u32,
+    /// Number of tiles
+    pub tile_count: u32,
+    /// Number of columns of tiles in the image
+    pub columns: u32,
+    /// Path of the image, relative to the file of the tileset
+    pub image: Option<String>,
+    /// Custom properties of the tileset
+    pub properties: TiledProperties,
+    /// Custom properties of the tiles, by local tile id
+    pub tile_properties: BTreeMap<u32, TiledProperties>,
+    /// Animations of the tiles, by local tile id
+    #[serde(default)]
+    pub animations: BTreeMap<u32, TileAnimation>,
+}
+
+impl Asset for TiledTileset {
+    fn name() -> &'static str {
+        "tiles::TiledTileset"
+    }
+    type Data = Self;
+}
+
+register_asset_type!(TiledTileset => TiledTileset; AssetProcessorSystem<TiledTileset>);
+
+/// A tileset used by a map, with the first global tile id of its tiles.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TiledMapTileset {
+    /// Global id of the first tile of the tileset
+    pub first_gid: u32,
+    /// Path of the `.tsx` file of an external tileset
+    pub source: Option<String>,
+    /// The tileset, `None` for external tilesets until it's set with `TiledMap::set_tileset`
+    pub tileset: Option<TiledTileset>,
+}
+
+/// A tile layer of a Tiled map.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TiledLayer {
+    /// Name of the layer
+    pub name: String,
+    /// Whether the layer is shown
+    pub visible: bool,
+    /// Opacity of the layer, from 0 to 1
+    pub opacity: f32,
+    /// Global tile ids of the tiles, row by row from the top left corner, with the flip flags
+    /// in their highest bits and 0 for empty tiles
+    pub tiles: Vec<u32>,
+    /// Custom properties of the layer
+    pub properties: TiledProperties,
+}
+
+/// An object of a Tiled map, also added as a component to the entities spawned for the
+/// objects.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TiledObject {
+    /// Unique id of the object in the map
+    pub id: u32,
+    /// Name of the object
+    pub name: String,
+    /// Type of the object
+    #[serde(rename = "type")]
+    pub object_type: String,
+    /// Position of the top left corner of the object, or of the bottom left corner of tile
+    /// objects, in pixels from the top left corner of the map
+    pub x: f32,
+    /// See `x`
+    pub y: f32,
+    /// Width of the object, in pixels
+    pub width: f32,
+    /// Height of the object, in pixels
+    pub height: f32,
+    /// Clockwise rotation of the object, in degrees
+    pub rotation: f32,
+    /// Global tile id of tile objects
+    pub gid: Option<u32>,
+    /// Whether the object is shown
+    pub visible: bool,
+    /// Custom properties of the object
+    pub properties: TiledProperties,
+}
+
+/// An object layer of a Tiled map.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TiledObjectGroup {
+    /// Name of the layer
+    pub name: String,
+    /// Whether the layer is shown
+    pub visible: bool,
+    /// The objects of the layer
+    pub objects: Vec<TiledObject>,
+    /// Custom properties of the layer
+    pub properties: TiledProperties,
+}
+
+/// A map made with the Tiled editor.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, TypeUuid)]
+#[uuid = "f3b9e2d1-7c4a-4b58-a6e0-92d5c1f8b3a7"]
+pub struct TiledMap {
+    /// Projection of the map, from its orientation and stagger settings
+    pub projection: TileProjection,
+    /// Width of the map, in tiles
+    pub width: u32,
+    /// Height of the map, in tiles
+    pub height: u32,
+    /// Width of the tiles, in pixels
+    pub tile_width: u32,
+    /// Height of the tiles, in pixels
+    pub tile_height: u32,
+    /// The tilesets, ordered by their first global tile id
+    pub tilesets: Vec<TiledMapTileset>,
+    /// The tile layers, from the bottom to the top, with the layers of groups flattened
+    pub layers: Vec<TiledLayer>,
+    /// The object layers, from the bottom to the top
+    pub object_groups: Vec<TiledObjectGroup>,
+    /// Custom properties of the map
+    pub properties: TiledProperties,
+}
+
+impl Asset for TiledMap {
+    fn name() -> &'static str {
+        "tiles::TiledMap"
+    }
+    type Data = Self;
+}
+
+register_asset_type!(TiledMap => TiledMap; AssetProcessorSystem<TiledMap>);
+
+/// A tile of a `TileMap` spawned from a `TiledMap`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TiledTile {
+    /// Index of the sprite in the sprite sheet of the tileset, `None` for empty tiles
+    pub sprite: Option<usize>,
+    /// Flipped along the x axis
+    pub flip_x: bool,
+    /// Flipped along the y axis
+    pub flip_y: bool,
+    /// Flipped along the diagonal, which makes rotations with the other flips
+    pub flip_diagonal: bool,
+}
+
+impl Tile for TiledTile {
+    fn sprite(&self, _: Point3<u32>, _: &World) -> Option<usize> {
+        self.sprite
+    }
+
+    fn orientation(&self, _: Point3<u32>, _: &World) -> TileOrientation {
+        TileOrientation::from_flips(self.flip_x, self.flip_y, self.flip_diagonal)
+    }
+}
+
+impl TiledMap {
+    /// Parses a `.tmx` file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file isn't a valid map, or uses a compressed tile layer format
+    /// or an infinite map.
+    pub fn from_tmx(text: &str) -> Result<Self, Error> {
+        let document = roxmltree::Document::parse(text)
+            .map_err(|e| format_err!("Failed parsing Tiled map: {}", e))?;
+        let root = document.root_element();
+        if root.tag_name().name() != "map" {
+            return Err(format_err!("Tiled map has no map element"));
+        }
+        if attribute_or(root, "infinite", 0_u32)? != 0 {
+            return Err(format_err!("Infinite Tiled maps aren't supported"));
+        }
+        let axis = match root.attribute("staggeraxis").unwrap_or("y") {
+            "x" => StaggerAxis::X,
+            _ => StaggerAxis::Y,
+        };
+        let index = match root.attribute("staggerindex").unwrap_or("odd") {
+            "even" => StaggerIndex::Even,
+            _ => StaggerIndex::Odd,
+        };
+        let projection = match root.attribute("orientation").unwrap_or("orthogonal") {
+            "orthogonal" => TileProjection::Orthogonal,
+            "isometric" => TileProjection::Isometric,
+            "staggered" => TileProjection::Staggered { axis, index },
+            "hexagonal" => {
+                TileProjection::Hexagonal {
+                    orientation: match axis {
+                        StaggerAxis::X => HexOrientation::Flat,
+                        StaggerAxis::Y => HexOrientation::Pointy,
+                    },
+                    index,
+                    side_length: attribute_or(root, "hexsidelength", 0)?,
+                }
+            }
+            other => return Err(format_err!("Unknown Tiled map orientation {:?}", other)),
+        };
+
+        let mut map = TiledMap {
+            projection,
+            width: attribute(root, "width")?,
+            height: attribute(root, "height")?,
+            tile_width: attribute(root, "tilewidth")?,
+            tile_height: attribute(root, "tileheight")?,
+            tilesets: Vec::new(),
+            layers: Vec::new(),
+            object_groups: Vec::new(),
+            properties: properties(root)?,
+        };
+        for node in root.children().filter(roxmltree::Node::is_element) {
+            if node.tag_name().name() == "tileset" {
+                let source = node.attribute("source").map(str::to_string);
+                map.tilesets.push(TiledMapTileset {
+                    first_gid: attribute(node, "firstgid")?,
+                    tileset: match source {
+                        Some(_) => None,
+                        None => Some(parse_tileset(node)?),
+                    },
+                    source,
+                });
+            }
+        }
+        map.tilesets.sort_by_key(|tileset| tileset.first_gid);
+        map.parse_layers(root)?;
+        Ok(map)
+    }
+
+    fn parse_layers(&mut self, parent: roxmltree::Node<'_, '_>) -> Result<(), Error> {
+        for node in parent.children().filter(roxmltree::Node::is_element) {
+            match node.tag_name().name() {
+                "layer" => {
+                    let count = self.width.checked_mul(self.height).ok_or_else(|| {
+                        format_err!(
+                            "Tiled map of {}x{} tiles is too large",
+                            self.width,
+                            self.height
+                        )
+                    })?;
+                    let tiles = parse_tiles(node, count as usize)?;
+                    self.layers.push(TiledLayer {
+                        name: node.attribute("name").unwrap_or_default().to_string(),
+                        visible: attribute_or(node, "visible", 1_u32)? != 0,
+                        opacity: attribute_or(node, "opacity", 1.0)?,
+                        tiles,
+                        properties: properties(node)?,
+                    });
+                }
+                "objectgroup" => {
+                    let objects = node
+                        .children()
+                        .filter(|n| n.has_tag_name("object"))
+                        .map(parse_object)
+                        .collect::<Result<_, _>>()?;
+                    self.object_groups.push(TiledObjectGroup {
+                        name: node.attribute("name").unwrap_or_default().to_string(),
+                        visible: attribute_or(node, "visible", 1_u32)? != 0,
+                        objects,
+                        properties: properties(node)?,
+                    });
+                }
+                "group" => self.parse_layers(node)?,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Sets an external tileset of the map, imported from the `.tsx` file at the given index
+    /// of `tilesets`.
+    pub fn set_tileset(&mut self, index: usize, tileset: TiledTileset) {
+        if let Some(entry) = self.tilesets.get_mut(index) {
+            entry.tileset = Some(tileset);
+        }
+    }
+
+    /// Returns the index of the tileset of a global tile id, and the local id of the tile in
+    /// the tileset.
+    #[must_use]
+    pub fn tileset_of(&self, gid: u32) -> Option<(usize, u32)> {
+        let gid = gid & GID_MASK;
+        if gid == 0 {
+            return None;
+        }
+        self.tilesets
+            .iter()
+            .rposition(|tileset| tileset.first_gid <= gid)
+            .map(|index| (index, gid - self.tilesets[index].first_gid))
+    }
+
+    /// The custom properties of the tile with the given global id.
+    #[must_use]
+    pub fn tile_properties(&self, gid: u32) -> Option<&TiledProperties> {
+        let (index, id) = self.tileset_of(gid)?;
+        self.tilesets[index]
+            .tileset
+            .as_ref()?
+            .tile_properties
+            .get(&id)
+    }
+
+    /// Converts the tiles of a tileset into a `TileMap` with the projection of the map, with a
+    /// z level per tile layer. The tiles of the other tilesets are left empty.
+    #[must_use]
+    pub fn tile_map(
+        &self,
+        tileset: usize,
+        sprite_sheet: Option<Handle<SpriteSheet>>,
+    ) -> TileMap<TiledTile, FlatEncoder> {
+        let dimensions = self.dimensions();
+        let mut tile_map = TileMap::new(dimensions, self.tile_dimensions(), sprite_sheet)
+            .with_projection(self.projection);
+        for (z, layer) in self.layers.iter().enumerate() {
+            if !layer.visible {
+                continue;
+            }
+            for (i, &gid) in layer.tiles.iter().enumerate() {
+                let id = match self.tileset_of(gid) {
+                    Some((index, id)) if index == tileset => id,
+                    _ => continue,
+                };
+                let coord = Point3::new(i as u32 % self.width, i as u32 / self.width, z as u32);
+                if let Some(tile) = tile_map.get_mut(&coord) {
+                    *tile = TiledTile {
+                        sprite: Some(id as usize),
+                        flip_x: gid & FLIPPED_HORIZONTALLY != 0,
+                        flip_y: gid & FLIPPED_VERTICALLY != 0,
+                        flip_diagonal: gid & FLIPPED_DIAGONALLY != 0,
+                    };
+                }
+            }
+        }
+        tile_map
+    }
+
+    fn dimensions(&self) -> Vector3<u32> {
+        Vector3::new(self.width, self.height, self.layers.len().max(1) as u32)
+    }
+
+    fn tile_dimensions(&self) -> Vector3<u32> {
+        Vector3::new(self.tile_width, self.tile_height, 1)
+    }
+
+    /// Creates a metadata layer with the dimensions of the tile maps of the map, from the custom
+    /// properties of the tiles, `None` for empty tiles and tiles without properties.
+    #[must_use]
+    pub fn metadata<V, F>(&self, mut value: F) -> TileLayer<V>
+    where
+        V: Clone + Default,
+        F: FnMut(Option<&TiledProperties>) -> V,
+    {
+        let mut layer = TileLayer::new(self.dimensions());
+        for (z, tiles) in self.layers.iter().enumerate() {
+            for (i, &gid) in tiles.tiles.iter().enumerate() {
+                let coord = Point3::new(i as u32 % self.width, i as u32 / self.width, z as u32);
+                layer.set(&coord, value(self.tile_properties(gid)));
+            }
+        }
+        layer
+    }
+
+    /// Adds the animations of the tiles of the tilesets, for the sprite sheets at the same
+    /// indices. The sprites of the sheets must be in the order of the tiles of the tilesets.
+    pub fn add_animations(
+        &self,
+        animations: &mut TileAnimations,
+        sprite_sheets: &[Handle<SpriteSheet>],
+    ) {
+        for (tileset, sheet) in self.tilesets.iter().zip(sprite_sheets) {
+            for (id, animation) in tileset.tileset.iter().flat_map(|t| &t.animations) {
+                animations.insert(sheet, *id as usize, animation.clone());
+            }
+        }
+    }
+
+    /// Converts a position of the map in pixels from its top left corner, as used by objects,
+    /// to the space of the `TileMap`s, which are centered on their transform. The positions of
+    /// isometric maps are along the axes of the tiles, in pixels of the tile height.
+    #[must_use]
+    pub fn to_world(&self, x: f32, y: f32) -> Vector3<f32> {
+        let transform = self
+            .projection
+            .transform(&self.dimensions(), &self.tile_dimensions());
+        if self.projection == TileProjection::Isometric {
+            // The top corner of the first tile is at the origin of the positions
+            let tile_height = self.tile_height as f32;
+            let point = Point3::new(x / tile_height - 0.5, 0.5 - y / tile_height, 0.0);
+            transform.transform_point(&point).coords
+        } else {
+            // The top left corner of the first tile, unshifted, is the corner of the map
+            let first = transform.transform_point(&Point3::origin());
+            Vector3::new(
+                first.x - self.tile_width as f32 / 2.0 + x,
+                first.y + self.tile_height as f32 / 2.0 - y,
+                0.0,
+            )
+        }
+    }
+
+    /// Spawns the map, with a `TileMap<TiledTile>` entity per tileset that has tiles, using the
+    /// sprite sheet at the same index, and an entity per object, with a `TiledObject`, a
+    /// `Named` and a `Transform`. The entities of the objects of a layer are at the z of the
+    /// tile layer below them. Returns the spawned entities.
+    pub fn spawn(&self, world: &mut World, sprite_sheets: &[Handle<SpriteSheet>]) -> Vec<Entity> {
+        let mut entities = Vec::new();
+        for (index, sheet) in sprite_sheets.iter().enumerate().take(self.tilesets.len()) {
+            let used = self.layers.iter().any(|layer| {
+                layer
+                    .tiles
+                    .iter()
+                    .any(|&gid| self.tileset_of(gid).map(|(i, _)| i) == Some(index))
+            });
+            if used {
+                let tile_map = self.tile_map(index, Some(sheet.clone()));
+                entities.push(world.push((tile_map, Transform::default())));
+            }
+        }
+        for group in self.object_groups.iter().filter(|group| group.visible) {
+            for object in group.objects.iter().filter(|object| object.visible) {
+                let mut transform = Transform::default();
+                transform.set_translation(self.to_world(object.x, object.y));
+                transform.set_rotation_2d(-object.rotation.to_radians());
+                entities.push(world.push((
+                    object.clone(),
+                    Named::new(object.name.clone()),
+                    transform,
+                )));
+            }
+        }
+        entities
+    }
+}
+
+impl TiledTileset {
+    /// Parses a `.tsx` file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file isn't a valid tileset.
+    pub fn from_tsx(text: &str) -> Result<Self, Error> {
+        let document = roxmltree::Document::parse(text)
+            .map_err(|e| format_err!("Failed parsing Tiled tileset: {}", e))?;
+        let root = document.root_element();
+        if root.tag_name().name() != "tileset" {
+            return Err(format_err!("Tiled tileset has no tileset element"));
+        }
+        parse_tileset(root)
+    }
+}
+
+fn attribute<T: FromStr>(node: roxmltree::Node<'_, '_>, name: &str) -> Result<T, Error> {
+    let value = node.attribute(name).ok_or_else(|| {
+        format_err!(
+            "Missing attribute {:?} of Tiled element {:?}",
+            name,
+            node.tag_name().name()
+        )
+    })?;
+    value
+        .parse()
+        .map_err(|_| format_err!("Invalid value {:?} of Tiled attribute {:?}", value, name))
+}
+
+fn attribute_or<T: FromStr>(
+    node: roxmltree::Node<'_, '_>,
+    name: &str,
+    default: T,
+) -> Result<T, Error> {
+    if node.attribute(name).is_some() {
+        attribute(node, name)
+    } else {
+        Ok(default)
+    }
+}
+
+fn properties(node: roxmltree::Node<'_, '_>) -> Result<TiledProperties, Error> {
+    let mut properties = TiledProperties::new();
+    let list = node.children().find(|n| n.has_tag_name("properties"));
+    for property in list
+        .iter()
+        .flat_map(roxmltree::Node::children)
+        .filter(|n| n.has_tag_name("property"))
+    {
+        let name: String = attribute(property, "name")?;
+        let text = property
+            .attribute("value")
+            .or_else(|| property.text())
+            .unwrap_or_default();
+        let value = match property.attribute("type").unwrap_or("string") {
+            "bool" => TiledProperty::Bool(text == "true"),
+            "int" | "object" => TiledProperty::Int(attribute_or(property, "value", 0)?),
+            "float" => TiledProperty::Float(attribute_or(property, "value", 0.0)?),
+            _ => TiledProperty::String(text.to_string()),
+        };
+        properties.insert(name, value);
+    }
+    Ok(properties)
+}
+
+fn parse_tileset(node: roxmltree::Node<'_, '_>) -> Result<TiledTileset, Error> {
+    let mut tile_properties = BTreeMap::new();
+    let mut animations = BTreeMap::new();
+    for tile in node.children().filter(|n| n.has_tag_name("tile")) {
+        let properties = properties(tile)?;
+        if !properties.is_empty() {
+            tile_properties.insert(attribute(tile, "id")?, properties);
+        }
+        if let Some(animation) = tile.children().find(|n| n.has_tag_name("animation")) {
+            let frames = animation
+                .children()
+                .filter(|n| n.has_tag_name("frame"))
+                .map(|frame| {
+                    Ok(TileFrame {
+                        sprite: attribute(frame, "tileid")?,
+                        duration: attribute::<f32>(frame, "duration")? / 1000.0,
+                    })
+                })
+                .collect::<Result<_, Error>>()?;
+            animations.insert(attribute(tile, "id")?, TileAnimation::new(frames));
+        }
+    }
+    let image = node
+        .children()
+        .find(|n| n.has_tag_name("image"))
+        .and_then(|image| image.attribute("source"))
+        .map(str::to_string);
+    Ok(TiledTileset {
+        name: node.attribute("name").unwrap_or_default().to_string(),
+        tile_width: attribute(node, "tilewidth")?,
+        tile_height: attribute(node, "tileheight")?,
+        tile_count: attribute_or(node, "tilecount", 0)?,
+        columns: attribute_or(node, "columns", 0)?,
+        image,
+        properties: properties(node)?,
+        tile_properties,
+        animations,
+    })
+}
+
+fn parse_tiles(layer: roxmltree::Node<'_, '_>, count: usize) -> Result<Vec<u32>, Error> {
+    let data = layer
+        .children()
+        .find(|n| n.has_tag_name("data"))
+        .ok_or_else(|| format_err!("Tiled layer has no data"))?;
+    if let Some(compression) = data.attribute("compression") {
+        return Err(format_err!(
+            "Compressed Tiled layers aren't supported, found {:?}",
+            compression
+        ));
+    }
+    let text = data.text().unwrap_or_default();
+    let tiles: Vec<u32> = match data.attribute("encoding") {
+        Some("csv") => {
+            text.split(',')
+                .map(str::trim)
+                .filter(|gid| !gid.is_empty())
+                .map(|gid| {
+                    gid.parse()
+                        .map_err(|_| format_err!("Invalid Tiled tile {:?}", gid))
+                })
+                .collect::<Result<_, _>>()?
+        }
+        Some("base64") => {
+            // the editor writes the data on a line of its own
+            let text = text.split_whitespace().collect::<String>();
+            base64::decode(&text)
+                .map_err(|e| format_err!("Invalid base64 in Tiled layer: {}", e))?
+                .chunks_exact(4)
+                .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                .collect()
+        }
+        Some(other) => return Err(format_err!("Unknown Tiled layer encoding {:?}", other)),
+        None => {
+            data.children()
+                .filter(|n| n.has_tag_name("tile"))
+                .map(|tile| attribute_or(tile, "gid", 0))
+                .collect::<Result<_, _>>()?
+        }
+    };
+    if tiles.len() == count {
+        Ok(tiles)
+    } else {
+        Err(format_err!(
+            "Tiled layer has {} tiles instead of {}",
+            tiles.len(),
+            count
+        ))
+    }
+}
+
+fn parse_object(node: roxmltree::Node<'_, '_>) -> Result<TiledObject, Error> {
+    Ok(TiledObject {
+        id: attribute_or(node, "id", 0)?,
+        name: node.attribute("name").unwrap_or_default().to_string(),
+        object_type: node
+            .attribute("type")
+            .or_else(|| node.attribute("class"))
+            .unwrap_or_default()
+            .to_string(),
+        x: attribute_or(node, "x", 0.0)?,
+        y: attribute_or(node, "y", 0.0)?,
+        width: attribute_or(node, "width", 0.0)?,
+        height: attribute_or(node, "height", 0.0)?,
+        rotation: attribute_or(node, "rotation", 0.0)?,
+        gid: node
+            .attribute("gid")
+            .map(|_| attribute(node, "gid"))
+            .transpose()?,
+        visible: attribute_or(node, "visible", 1_u32)? != 0,
+        properties: properties(node)?,
+    })
+}
+
+/// Imports Tiled maps from `.tmx` files.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, TypeUuid)]
+#[uuid = "5e1c8a7b-3d92-4f06-b4a1-c7e26d9f0b35"]
+pub struct TiledFormat;
+
+register_importer!(".tmx", TiledFormat);
+impl Format<TiledMap> for TiledFormat {
+    fn name(&self) -> &'static str {
+        "TiledMap"
+    }
+
+    fn import_simple(&self, bytes: Vec<u8>) -> Result<TiledMap, Error> {
+        TiledMap::from_tmx(&String::from_utf8(bytes)?)
+    }
+}
+
+/// Imports Tiled tilesets from `.tsx` files.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, TypeUuid)]
+#[uuid = "b2f47d19-86e3-4c5a-9d0b-3a6e8c1f72d4"]
+pub struct TiledTilesetFormat;
+
+register_importer!(".tsx", TiledTilesetFormat);
+impl Format<TiledTileset> for TiledTilesetFormat {
+    fn name(&self) -> &'static str {
+        "TiledTileset"
+    }
+
+    fn import_simple(&self, bytes: Vec<u8>) -> Result<TiledTileset, Error> {
+        TiledTileset::from_tsx(&String::from_utf8(bytes)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Map;
+
+    const MAP: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.4" orientation="orthogonal" width="3" height="2"
+     tilewidth="16" tileheight="16" infinite="0">
+ <properties>
+  <property name="music" value="forest.ogg"/>
+ </properties>
+ <tileset firstgid="1" name="ground" tilewidth="16" tileheight="16" tilecount="4" columns="2">
+  <image source="ground.png" width="32" height="32"/>
+  <tile id="1">
+   <properties>
+    <property name="solid" type="bool" value="true"/>
+   </properties>
+  </tile>
+  <tile id="2">
+   <animation>
+    <frame tileid="2" duration="100"/>
+    <frame tileid="3" duration="300"/>
+   </animation>
+  </tile>
+ </tileset>
+ <tileset firstgid="5" source="items.tsx"/>
+ <layer id="1" name="ground" width="3" height="2">
+  <data encoding="csv">
+1,2,2147483649,
+0,4,5
+</data>
+ </layer>
+ <group name="decoration">
+  <layer id="2" name="top" width="3" height="2">
+   <data encoding="base64">
+    AAAAAAAAAAADAAAAAAAAAAAAAAAAAAAA
+   </data>
+  </layer>
+ </group>
+ <objectgroup id="3" name="spawns">
+  <object id="1" name="player" type="spawn" x="24" y="8">
+   <properties>
+    <property name="health" type="int" value="3"/>
+   </properties>
+  </object>
+ </objectgroup>
+</map>
+"#;
+
+    #[test]
+    fn tmx_maps_are_parsed() {
+        let map = TiledMap::from_tmx(MAP).unwrap();
+        assert_eq!((map.width, map.height), (3, 2));
+        assert_eq!(
+            map.properties["music"],
+            TiledProperty::String("forest.ogg".to_string())
+        );
+        assert_eq!(map.tilesets.len(), 2);
+        assert_eq!(map.tilesets[1].source.as_deref(), Some("items.tsx"));
+        assert_eq!(
+            map.tile_properties(2).unwrap()["solid"].as_bool(),
+            Some(true)
+        );
+
+        assert_eq!(map.layers.len(), 2);
+        assert_eq!(map.layers[0].tiles, vec![1, 2, 0x8000_0001, 0, 4, 5]);
+        assert_eq!(map.layers[1].tiles, vec![0, 0, 3, 0, 0, 0]);
+        assert_eq!(map.tileset_of(5), Some((1, 0)));
+        let solid = map.metadata(|properties| {
+            properties
+                .and_then(|properties| properties.get("solid"))
+                .and_then(TiledProperty::as_bool)
+                .unwrap_or(false)
+        });
+        assert_eq!(solid.get(&Point3::new(1, 0, 0)), Some(&true));
+        assert_eq!(solid.get(&Point3::new(0, 0, 0)), Some(&false));
+        let animation = &map.tilesets[0].tileset.as_ref().unwrap().animations[&2];
+        assert_eq!(animation.sprite_at(0.2), Some(3));
+
+        let object = &map.object_groups[0].objects[0];
+        assert_eq!(object.object_type, "spawn");
+        assert_eq!(object.properties["health"].as_int(), Some(3));
+        assert_eq!(
+            map.to_world(object.x, object.y),
+            Vector3::new(0.0, 8.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn oversized_maps_are_rejected() {
+        let map = r#"<map width="65536" height="65536" tilewidth="16" tileheight="16">
+ <layer id="1" name="ground" width="65536" height="65536">
+  <data encoding="csv">0</data>
+ </layer>
+</map>
+"#;
+        let error = TiledMap::from_tmx(map).err().unwrap();
+        assert!(error.to_string().contains("too large"));
+    }
+
+    #[test]
+    fn tile_maps_are_built_per_tileset() {
+        let map = TiledMap::from_tmx(MAP).unwrap();
+        let tile_map = map.tile_map(0, None);
+        assert_eq!(*tile_map.dimensions(), Vector3::new(3, 2, 2));
+        let flipped = tile_map.get(&Point3::new(2, 0, 0)).unwrap();
+        assert_eq!(flipped.sprite, Some(0));
+        assert!(flipped.flip_x);
+        assert_eq!(tile_map.get(&Point3::new(2, 1, 0)).unwrap().sprite, None);
+        assert_eq!(tile_map.get(&Point3::new(2, 0, 1)).unwrap().sprite, Some(2));
+    }
+}
@@ -1,6 +1,6 @@
 //! Module for the Blink component and BlinkSystem.
 
-use amethyst_core::{ecs::*, Hidden, Time};
+use amethyst_core::{ecs::*, timing::Clock, Hidden, Time};
 #[cfg(feature = "profiler")]
 use thread_profiler::profile_scope;
 
@@ -19,11 +19,14 @@ pub struct Blink {
     pub delay: f32,
     /// Timer value keeping track of the time during the blink cycle.
     pub timer: f32,
-    /// Whether to use the scaled or unscaled time.
+    /// Whether to use the real time, or the time of the UI clock.
     pub absolute_time: bool,
 }
 
 /// System updating the `Blink` component.
+///
+/// Follows the `Ui` clock, so blinking goes on while only the game clock is paused, or the
+/// `Real` clock for `Blink`s with `absolute_time`.
 #[derive(Debug)]
 pub struct BlinkSystem;
 
@@ -38,8 +41,8 @@ impl System for BlinkSystem {
                     #[cfg(feature = "profiler")]
                     profile_scope!("blink_system");
 
-                    let abs_sec = time.delta_seconds();
-                    let abs_unscaled_sec = time.delta_real_seconds();
+                    let abs_sec = time.delta_seconds_for(Clock::Ui);
+                    let abs_unscaled_sec = time.delta_seconds_for(Clock::Real);
 
                     let (mut blinks_world, mut subworld) = world.split_for_query(&blinks);
 
@@ -73,3 +76,46 @@ impl System for BlinkSystem {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blink_follows_ui_clock() {
+        let mut world = World::default();
+        let mut resources = Resources::default();
+        let mut time = Time::default();
+        time.set_clock_scale(Clock::Ui, 0.5);
+        time.set_clock_paused(Clock::Game, true);
+        time.set_delta_seconds(1.0);
+        resources.insert(time);
+
+        let blink = |absolute_time| {
+            Blink {
+                delay: 10.0,
+                timer: 0.0,
+                absolute_time,
+            }
+        };
+        let ui = world.push((blink(false),));
+        let real = world.push((blink(true),));
+
+        let mut dispatcher = DispatcherBuilder::default()
+            .add_system(BlinkSystem)
+            .build(&mut world, &mut resources)
+            .unwrap();
+        dispatcher.execute(&mut world, &mut resources);
+
+        let timer = |entity| {
+            world
+                .entry_ref(entity)
+                .unwrap()
+                .into_component::<Blink>()
+                .unwrap()
+                .timer
+        };
+        assert_eq!(timer(ui), 0.5);
+        assert_eq!(timer(real), 1.0);
+    }
+}
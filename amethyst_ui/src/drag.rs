@@ -8,7 +8,7 @@ use amethyst_core::{
     Hidden, HiddenPropagate,
 };
 use amethyst_input::InputHandler;
-use amethyst_window::ScreenDimensions;
+use amethyst_window::{PixelPolicy, ScreenDimensions};
 use serde::{Deserialize, Serialize};
 
 use crate::{
@@ -128,6 +128,10 @@ impl System for DragWidgetSystem {
                             let (scale_x, scale_y) = match ui_transform.scale_mode {
                                 ScaleMode::Pixel => (1.0, 1.0),
                                 ScaleMode::Percent => (parent_width, parent_height),
+                                ScaleMode::Logical => {
+                                    let scale = screen_dimensions.pixel_scale(PixelPolicy::Logical);
+                                    (scale, scale)
+                                }
                             };
 
                             ui_transform.local_x += change[0] / scale_x;
@@ -5,7 +5,7 @@ use amethyst_core::{
     ecs::*,
     transform::{Children, Parent},
 };
-use amethyst_window::ScreenDimensions;
+use amethyst_window::{PixelPolicy, ScreenDimensions};
 use derivative::Derivative;
 use glyph_brush::{HorizontalAlign, VerticalAlign};
 use serde::{Deserialize, Serialize};
@@ -24,6 +24,9 @@ pub enum ScaleMode {
     Pixel,
     /// Use a proportion (%) of the parent's dimensions (or screen, if there is no parent).
     Percent,
+    /// Use the value in logical pixels, multiplied by the scale factor of the window so the
+    /// element keeps the same apparent size on monitors with a different DPI.
+    Logical,
 }
 
 /// Indicated where the anchor is, relative to the parent (or to the screen, if there is no parent).
@@ -135,6 +138,7 @@ pub enum Stretch {
 #[derive(Debug)]
 pub struct UiTransformSystem {
     screen_size: (f32, f32),
+    scale_factor: f64,
     modified_last_iter: HashSet<Entity>,
 }
 
@@ -143,6 +147,7 @@ impl UiTransformSystem {
     pub fn new() -> Self {
         Self {
             screen_size: (0.0, 0.0),
+            scale_factor: 1.0,
             modified_last_iter: HashSet::default(),
         }
     }
@@ -195,8 +200,12 @@ impl System for UiTransformSystem {
                         let current_screen_size =
                             (screen_dimensions.width(), screen_dimensions.height());
 
-                        let screen_resized = current_screen_size != self.screen_size;
+                        let screen_resized = current_screen_size != self.screen_size
+                            || (screen_dimensions.scale_factor() - self.scale_factor).abs()
+                                > f64::EPSILON;
                         self.screen_size = current_screen_size;
+                        self.scale_factor = screen_dimensions.scale_factor();
+                        let scale = screen_dimensions.pixel_scale(PixelPolicy::Logical);
                         if screen_resized {
                             // Then we process for everyone
                             process_root_iter(
@@ -317,6 +326,12 @@ impl System for UiTransformSystem {
                                     transform.pixel_width = transform.width;
                                     transform.pixel_height = transform.height;
                                 }
+                                ScaleMode::Logical => {
+                                    transform.pixel_x += transform.local_x * scale;
+                                    transform.pixel_y += transform.local_y * scale;
+                                    transform.pixel_width = transform.width * scale;
+                                    transform.pixel_height = transform.height * scale;
+                                }
                                 ScaleMode::Percent => {
                                     transform.pixel_x +=
                                         transform.local_x * parent_transform_copy.pixel_width;
@@ -393,6 +408,13 @@ where
                 transform.pixel_width = transform.width;
                 transform.pixel_height = transform.height;
             }
+            ScaleMode::Logical => {
+                let scale = screen_dim.pixel_scale(PixelPolicy::Logical);
+                transform.pixel_x += transform.local_x * scale;
+                transform.pixel_y += transform.local_y * scale;
+                transform.pixel_width = transform.width * scale;
+                transform.pixel_height = transform.height * scale;
+            }
             ScaleMode::Percent => {
                 transform.pixel_x += transform.local_x * screen_dim.width();
                 transform.pixel_y += transform.local_y * screen_dim.height();
@@ -228,7 +228,9 @@ impl<B: Backend> RenderGroup<B, GraphAuxData> for DrawUi<B> {
     ) -> PrepareResult {
         #[cfg(feature = "profiler")]
         profile_scope!("prepare");
-        let GraphAuxData { world, resources } = aux;
+        let GraphAuxData {
+            world, resources, ..
+        } = aux;
 
         let glyphs_res = resources.get::<UiGlyphsResource>().unwrap();
         let screen_dimensions = resources.get::<ScreenDimensions>().unwrap();
@@ -4,7 +4,7 @@ use amethyst_core::{
     ecs::*,
     shrev::{EventChannel, ReaderId},
 };
-use amethyst_input::{InputHandler, KeyboardModifiersState};
+use amethyst_input::{is_text_char, InputHandler, KeyboardModifiersState};
 use copypasta::{ClipboardContext, ClipboardProvider};
 use log::error;
 use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
@@ -390,15 +390,8 @@ fn highlighted_bytes(edit: &TextEditing, text: &UiText) -> Range<usize> {
 }
 
 fn should_skip_char(input: char) -> bool {
-    // Ignore obsolete control characters, and tab characters we can't render
-    // properly anyways.  Also ignore newline characters since we don't
-    // support multi-line text at the moment.
-    input < '\u{20}'
-        // Ignore delete character too
-        || input == '\u{7F}'
-        // Unicode reserves some characters for "private use".  Systems emit
-        // these for no clear reason, so we're just going to ignore all of them.
-        || ('\u{E000}'..='\u{F8FF}').contains(&input)
-        || ('\u{F0000}'..='\u{FFFFF}').contains(&input)
-        || ('\u{100000}'..='\u{10FFFF}').contains(&input)
+    // Ignore control characters, and tab characters we can't render properly
+    // anyways.  Also ignore newline characters since we don't support
+    // multi-line text at the moment.
+    !is_text_char(input)
 }
@@ -20,6 +20,8 @@ pub mod auto_fov;
 pub mod circular_buffer;
 pub mod fps_counter;
 pub mod ortho_camera;
+pub mod picking;
+pub mod pixel_camera;
 pub mod removal;
 pub mod tag;
 pub mod time_destroy;
@@ -0,0 +1,490 @@
+//! Picking entities with the mouse.
+//!
+//! Add a [Pickable] to the entities which can be picked, and the [PickingBundle] to the
+//! dispatcher. The [PickingSystem] then casts a ray from the active camera through the cursor
+//! every frame, and sends [PickEvent]s when the cursor starts and stops hovering an entity, and
+//! when the entity is clicked, like the `UiEvent`s of the ui.
+//!
+//! ```rust,ignore
+//! world.push((
+//!     transform,
+//!     mesh,
+//!     material,
+//!     Pickable::new(PickShape::Sphere {
+//!         center: Point3::origin(),
+//!         radius: 0.5,
+//!     }),
+//! ));
+//!
+//! // in a system
+//! for event in events.read(&mut reader) {
+//!     if event.event_type == PickEventType::Click {
+//!         println!("Clicked {:?} at {}", event.target, event.point);
+//!     }
+//! }
+//! ```
+
+use std::sync::Arc;
+
+use amethyst_core::{
+    ecs::*,
+    geometry::Ray,
+    math::{Point2, Point3, Vector2},
+    shrev::EventChannel,
+    transform::Transform,
+    Hidden, HiddenPropagate,
+};
+use amethyst_error::Error;
+use amethyst_input::{Button, InputHandler};
+use amethyst_rendy::{
+    camera::{ActiveCamera, Camera},
+    visibility::BoundingSphere,
+};
+use amethyst_window::ScreenDimensions;
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+use winit::event::MouseButton;
+
+/// Shape of a `Pickable` entity, in the local space of its transform.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PickShape {
+    /// A sphere
+    Sphere {
+        /// Center of the sphere
+        center: Point3<f32>,
+        /// Radius of the sphere
+        radius: f32,
+    },
+    /// An axis aligned box
+    Box {
+        /// Corner with the smallest coordinates
+        min: Point3<f32>,
+        /// Corner with the largest coordinates
+        max: Point3<f32>,
+    },
+    /// Triangles, e.g. a copy of the positions of a mesh, for precise picking
+    Triangles(Arc<Vec<[Point3<f32>; 3]>>),
+}
+
+impl PickShape {
+    /// Returns the distance along the ray of its first intersection with the shape, both in
+    /// local space.
+    pub fn intersect(&self, ray: &Ray<f32>) -> Option<f32> {
+        match self {
+            PickShape::Sphere { center, radius } => intersect_sphere(ray, center, *radius),
+            PickShape::Box { min, max } => intersect_box(ray, min, max),
+            PickShape::Triangles(triangles) => {
+                triangles
+                    .iter()
+                    .filter_map(|triangle| intersect_triangle(ray, triangle))
+                    .fold(None, |closest: Option<f32>, t| {
+                        Some(closest.map_or(t, |closest| closest.min(t)))
+                    })
+            }
+        }
+    }
+}
+
+impl From<&BoundingSphere> for PickShape {
+    fn from(sphere: &BoundingSphere) -> Self {
+        PickShape::Sphere {
+            center: sphere.center,
+            radius: sphere.radius,
+        }
+    }
+}
+
+fn intersect_sphere(ray: &Ray<f32>, center: &Point3<f32>, radius: f32) -> Option<f32> {
+    let to_center = center - ray.origin;
+    let a = ray.direction.norm_squared();
+    let b = to_center.dot(&ray.direction);
+    let c = to_center.norm_squared() - radius * radius;
+    let discriminant = b * b - a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+    let root = discriminant.sqrt();
+    [(b - root) / a, (b + root) / a]
+        .iter()
+        .copied()
+        .find(|t| *t >= 0.0)
+}
+
+fn intersect_box(ray: &Ray<f32>, min: &Point3<f32>, max: &Point3<f32>) -> Option<f32> {
+    let mut near = 0.0_f32;
+    let mut far = f32::INFINITY;
+    for axis in 0..3 {
+        let origin = ray.origin[axis];
+        let direction = ray.direction[axis];
+        if direction.abs() < f32::EPSILON {
+            if origin < min[axis] || origin > max[axis] {
+                return None;
+            }
+            continue;
+        }
+        let t1 = (min[axis] - origin) / direction;
+        let t2 = (max[axis] - origin) / direction;
+        near = near.max(t1.min(t2));
+        far = far.min(t1.max(t2));
+        if near > far {
+            return None;
+        }
+    }
+    Some(near)
+}
+
+/// Möller–Trumbore intersection, hitting both sides of the triangle.
+fn intersect_triangle(ray: &Ray<f32>, triangle: &[Point3<f32>; 3]) -> Option<f32> {
+    let edge1 = triangle[1] - triangle[0];
+    let edge2 = triangle[2] - triangle[0];
+    let p = ray.direction.cross(&edge2);
+    let determinant = edge1.dot(&p);
+    if determinant.abs() < f32::EPSILON {
+        return None;
+    }
+    let inverse = 1.0 / determinant;
+    let s = ray.origin - triangle[0];
+    let u = s.dot(&p) * inverse;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+    let q = s.cross(&edge1);
+    let v = ray.direction.dot(&q) * inverse;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+    let t = edge2.dot(&q) * inverse;
+    if t >= 0.0 {
+        Some(t)
+    } else {
+        None
+    }
+}
+
+/// Component for the entities which can be picked with the mouse.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pickable {
+    /// Shape of the entity
+    pub shape: PickShape,
+}
+
+impl Pickable {
+    /// Creates a pickable with the given shape.
+    pub fn new(shape: PickShape) -> Self {
+        Pickable { shape }
+    }
+}
+
+/// The closest entity hit by a ray.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PickHit {
+    /// The entity which was hit
+    pub entity: Entity,
+    /// The hit point, in world space
+    pub point: Point3<f32>,
+    /// Distance from the origin of the ray to the hit point
+    pub distance: f32,
+}
+
+/// Returns the closest hit of the ray with the shapes, all in world space. The shapes are
+/// given with the global matrix of their transforms.
+pub fn pick<'a, I>(ray: &Ray<f32>, shapes: I) -> Option<PickHit>
+where
+    I: IntoIterator<Item = (Entity, &'a PickShape, &'a Transform)>,
+{
+    let mut closest: Option<PickHit> = None;
+    for (entity, shape, transform) in shapes {
+        let matrix = transform.global_matrix();
+        let inverse = match matrix.try_inverse() {
+            Some(inverse) => inverse,
+            None => continue,
+        };
+        let local = Ray {
+            origin: inverse.transform_point(&ray.origin),
+            direction: inverse.transform_vector(&ray.direction),
+        };
+        if let Some(t) = shape.intersect(&local) {
+            let point = matrix.transform_point(&(local.origin + local.direction * t));
+            let distance = (point - ray.origin).norm();
+            if closest.map_or(true, |hit| distance < hit.distance) {
+                closest = Some(PickHit {
+                    entity,
+                    point,
+                    distance,
+                });
+            }
+        }
+    }
+    closest
+}
+
+/// Returns the ray from a camera through a position of the screen, in pixels from the top left
+/// corner.
+pub fn screen_ray(
+    camera: &Camera,
+    camera_transform: &Transform,
+    screen_position: Point2<f32>,
+    screen: &ScreenDimensions,
+) -> Ray<f32> {
+    camera.screen_ray(
+        screen_position,
+        Vector2::new(screen.width(), screen.height()),
+        camera_transform,
+    )
+}
+
+/// The type of a `PickEvent`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PickEventType {
+    /// When the entity is clicked, the button being pressed and released over it
+    Click,
+    /// When the button is pressed over the entity
+    ClickStart,
+    /// When the button is released after being pressed over the entity
+    ClickStop,
+    /// When the cursor gets over the entity
+    HoverStart,
+    /// When the cursor stops being over the entity
+    HoverStop,
+}
+
+/// Event sent by the `PickingSystem`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PickEvent {
+    /// The type of event
+    pub event_type: PickEventType,
+    /// The entity on which the event happened
+    pub target: Entity,
+    /// The point of the entity under the cursor, in world space, or the last one for
+    /// `HoverStop` and `ClickStop` events
+    pub point: Point3<f32>,
+}
+
+/// Resource holding the state of the picking, and its settings.
+#[derive(Debug, Clone)]
+pub struct Picking {
+    /// The button used to click, the left mouse button by default
+    pub button: Button,
+    /// Picks nothing while false, e.g. while the cursor is over the ui
+    pub enabled: bool,
+    hovered: Option<PickHit>,
+    pressed: Option<PickHit>,
+    was_down: bool,
+}
+
+impl Default for Picking {
+    fn default() -> Self {
+        Picking {
+            button: Button::Mouse(MouseButton::Left),
+            enabled: true,
+            hovered: None,
+            pressed: None,
+            was_down: false,
+        }
+    }
+}
+
+impl Picking {
+    /// The entity under the cursor, if any.
+    pub fn hovered(&self) -> Option<&PickHit> {
+        self.hovered.as_ref()
+    }
+
+    /// The entity on which the button was pressed, while it's held.
+    pub fn pressed(&self) -> Option<&PickHit> {
+        self.pressed.as_ref()
+    }
+
+    fn update(&mut self, hit: Option<PickHit>, down: bool, events: &mut EventChannel<PickEvent>) {
+        let previous = self.hovered.map(|hit| hit.entity);
+        let current = hit.map(|hit| hit.entity);
+        if previous != current {
+            if let Some(old) = self.hovered {
+                events.single_write(event(PickEventType::HoverStop, &old));
+            }
+            if let Some(new) = &hit {
+                events.single_write(event(PickEventType::HoverStart, new));
+            }
+        }
+        self.hovered = hit;
+
+        if down && !self.was_down {
+            if let Some(hit) = &hit {
+                events.single_write(event(PickEventType::ClickStart, hit));
+                self.pressed = Some(*hit);
+            }
+        } else if !down && self.was_down {
+            if let Some(pressed) = self.pressed.take() {
+                events.single_write(event(PickEventType::ClickStop, &pressed));
+                if let Some(hit) = hit.filter(|hit| hit.entity == pressed.entity) {
+                    events.single_write(event(PickEventType::Click, &hit));
+                }
+            }
+        }
+        self.was_down = down;
+    }
+}
+
+fn event(event_type: PickEventType, hit: &PickHit) -> PickEvent {
+    PickEvent {
+        event_type,
+        target: hit.entity,
+        point: hit.point,
+    }
+}
+
+/// The system that picks the `Pickable` entities under the cursor with the active camera, or
+/// the first camera if none is active. Hidden entities aren't picked.
+#[derive(Debug)]
+pub struct PickingSystem;
+
+impl System for PickingSystem {
+    fn build(self) -> Box<dyn ParallelRunnable> {
+        Box::new(
+            SystemBuilder::new("PickingSystem")
+                .read_resource::<InputHandler>()
+                .read_resource::<ScreenDimensions>()
+                .read_resource::<ActiveCamera>()
+                .write_resource::<Picking>()
+                .write_resource::<EventChannel<PickEvent>>()
+                .with_query(<(Entity, &Camera, &Transform)>::query())
+                .with_query(
+                    <(Entity, &Pickable, &Transform)>::query()
+                        .filter(!component::<Hidden>() & !component::<HiddenPropagate>()),
+                )
+                .build(
+                    |_commands,
+                     world,
+                     (input, screen, active_camera, picking, events),
+                     (cameras, pickables)| {
+                        #[cfg(feature = "profiler")]
+                        profile_scope!("picking_system");
+
+                        let down = input.button_is_down(picking.button);
+                        let hit = if picking.enabled {
+                            let camera = cameras
+                                .iter(world)
+                                .find(|(entity, _, _)| active_camera.entity == Some(**entity))
+                                .or_else(|| cameras.iter(world).next());
+                            match (camera, input.mouse_position()) {
+                                (Some((_, camera, transform)), Some((x, y))) => {
+                                    let ray =
+                                        screen_ray(camera, transform, Point2::new(x, y), &screen);
+                                    pick(
+                                        &ray,
+                                        pickables.iter(world).map(
+                                            |(entity, pickable, transform)| {
+                                                (*entity, &pickable.shape, transform)
+                                            },
+                                        ),
+                                    )
+                                }
+                                _ => None,
+                            }
+                        } else {
+                            None
+                        };
+                        picking.update(hit, down, events);
+                    },
+                ),
+        )
+    }
+}
+
+/// The bundle inserting the `Picking` and `EventChannel<PickEvent>` resources, and adding the
+/// `PickingSystem`. Add it after the `InputBundle`.
+#[derive(Debug, Default)]
+pub struct PickingBundle;
+
+impl SystemBundle for PickingBundle {
+    fn load(
+        &mut self,
+        _world: &mut World,
+        resources: &mut Resources,
+        builder: &mut DispatcherBuilder,
+    ) -> Result<(), Error> {
+        resources.insert(Picking::default());
+        if !resources.contains::<EventChannel<PickEvent>>() {
+            resources.insert(EventChannel::<PickEvent>::new());
+        }
+        builder.add_system(PickingSystem);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use amethyst_core::math::Vector3;
+
+    use super::*;
+
+    fn ray(origin: [f32; 3], direction: [f32; 3]) -> Ray<f32> {
+        Ray {
+            origin: Point3::from(Vector3::from(origin)),
+            direction: Vector3::from(direction).normalize(),
+        }
+    }
+
+    #[test]
+    fn shapes_are_intersected() {
+        let sphere = PickShape::Sphere {
+            center: Point3::new(0.0, 0.0, -5.0),
+            radius: 1.0,
+        };
+        let hit = sphere.intersect(&ray([0.0, 0.0, 0.0], [0.0, 0.0, -1.0]));
+        assert!((hit.unwrap() - 4.0).abs() < 1.0e-5);
+        assert_eq!(
+            sphere.intersect(&ray([0.0, 2.0, 0.0], [0.0, 0.0, -1.0])),
+            None
+        );
+
+        let cube = PickShape::Box {
+            min: Point3::new(-1.0, -1.0, -1.0),
+            max: Point3::new(1.0, 1.0, 1.0),
+        };
+        let hit = cube.intersect(&ray([5.0, 0.5, 0.0], [-1.0, 0.0, 0.0]));
+        assert!((hit.unwrap() - 4.0).abs() < 1.0e-5);
+        assert_eq!(cube.intersect(&ray([5.0, 0.5, 0.0], [1.0, 0.0, 0.0])), None);
+
+        let triangle = PickShape::Triangles(Arc::new(vec![[
+            Point3::new(-1.0, -1.0, 0.0),
+            Point3::new(1.0, -1.0, 0.0),
+            Point3::new(0.0, 1.0, 0.0),
+        ]]));
+        let hit = triangle.intersect(&ray([0.0, 0.0, 3.0], [0.0, 0.0, -1.0]));
+        assert!((hit.unwrap() - 3.0).abs() < 1.0e-5);
+        assert_eq!(
+            triangle.intersect(&ray([2.0, 0.0, 3.0], [0.0, 0.0, -1.0])),
+            None
+        );
+    }
+
+    #[test]
+    fn closest_hit_is_picked_in_world_space() {
+        let mut world = World::default();
+        let near = world.push(());
+        let far = world.push(());
+        let shape = PickShape::Sphere {
+            center: Point3::origin(),
+            radius: 1.0,
+        };
+        let mut near_transform = Transform::default();
+        near_transform.set_translation_xyz(0.0, 0.0, -5.0);
+        near_transform.set_scale(Vector3::new(2.0, 2.0, 2.0));
+        near_transform.copy_local_to_global();
+        let mut far_transform = Transform::default();
+        far_transform.set_translation_xyz(0.0, 0.0, -10.0);
+        far_transform.copy_local_to_global();
+
+        let hit = pick(
+            &ray([0.0, 0.0, 0.0], [0.0, 0.0, -1.0]),
+            vec![
+                (far, &shape, &far_transform),
+                (near, &shape, &near_transform),
+            ],
+        )
+        .unwrap();
+        assert_eq!(hit.entity, near);
+        assert!((hit.distance - 3.0).abs() < 1.0e-5);
+    }
+}
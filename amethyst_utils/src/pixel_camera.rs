@@ -0,0 +1,88 @@
+//! Utility to keep 2D cameras sized to the window in physical or logical pixels
+
+use amethyst_core::ecs::*;
+use amethyst_rendy::camera::Camera;
+use amethyst_window::{PixelPolicy, ScreenDimensions};
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+
+/// A component making the associated camera a 2D camera showing one world unit per pixel of the
+/// window, measured with its `PixelPolicy`, when it is managed by the `PixelCameraSystem`.
+///
+/// With `PixelPolicy::Logical`, the camera shows the same part of the world on monitors with a
+/// different DPI, while `PixelPolicy::Physical` keeps sprites pixel perfect.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct PixelCamera {
+    policy: PixelPolicy,
+    zoom: f32,
+    // The camera has to be adjusted when the parameters change or when a new camera is created.
+    #[serde(skip)]
+    dirty: bool,
+}
+
+impl PixelCamera {
+    /// Creates a new instance with the given policy and a zoom of 1.
+    pub fn new(policy: PixelPolicy) -> Self {
+        Self {
+            policy,
+            ..Default::default()
+        }
+    }
+
+    /// Set the policy the pixels are measured with
+    pub fn set_policy(&mut self, policy: PixelPolicy) {
+        self.policy = policy;
+        self.dirty = true;
+    }
+
+    /// Set the number of pixels per world unit
+    pub fn set_zoom(&mut self, zoom: f32) {
+        self.zoom = zoom;
+        self.dirty = true;
+    }
+}
+
+impl Default for PixelCamera {
+    fn default() -> Self {
+        Self {
+            policy: PixelPolicy::default(),
+            zoom: 1.0,
+            dirty: true,
+        }
+    }
+}
+
+/// System that automatically resizes cameras with a `PixelCamera` to the screen dimensions, when
+/// the window is resized or its scale factor changes.
+#[derive(Debug)]
+pub struct PixelCameraSystem;
+
+impl System for PixelCameraSystem {
+    fn build(self) -> Box<dyn ParallelRunnable> {
+        let mut last_dimensions = ScreenDimensions::new(0, 0);
+
+        Box::new(
+            SystemBuilder::new("pixel_camera_system")
+                .read_resource::<ScreenDimensions>()
+                .with_query(<(Write<Camera>, Write<PixelCamera>)>::query())
+                .build(move |_commands, subworld, screen, query| {
+                    #[cfg(feature = "profiler")]
+                    profile_scope!("pixel_camera_system");
+
+                    for (camera, pixel_camera) in query.iter_mut(subworld) {
+                        if last_dimensions != **screen || pixel_camera.dirty {
+                            let (width, height) = screen.size(pixel_camera.policy);
+                            *camera = Camera::standard_2d(
+                                width / pixel_camera.zoom,
+                                height / pixel_camera.zoom,
+                            );
+                            pixel_camera.dirty = false;
+                        }
+                    }
+                    last_dimensions = screen.clone();
+                }),
+        )
+    }
+}
@@ -0,0 +1,116 @@
+use amethyst_core::{
+    dispatcher::ThreadLocalSystem,
+    ecs::{Runnable, SystemBuilder},
+    shrev::{EventChannel, ReaderId},
+    Time,
+};
+use serde::{Deserialize, Serialize};
+use winit::{
+    event::{Event, WindowEvent},
+    window::Window,
+};
+
+/// World resource selecting what the game does while the main window is minimized.
+///
+/// The frame rate is throttled by the `FrameLimiter` meanwhile, see
+/// `FrameRateLimitConfig::minimized_fps`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BackgroundSettings {
+    /// Don't render frames while minimized, which also avoids rebuilding the swapchain of an
+    /// empty window.
+    pub skip_rendering: bool,
+    /// Pause `Time` while minimized, resuming it once the window is restored.
+    pub pause: bool,
+}
+
+impl Default for BackgroundSettings {
+    fn default() -> Self {
+        BackgroundSettings {
+            skip_rendering: true,
+            pause: false,
+        }
+    }
+}
+
+/// World resource holding whether the main window is minimized or focused, updated by the
+/// `BackgroundSystem`.
+///
+/// Occlusion by other windows isn't reported by winit, so only minimization is detected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WindowStatus {
+    minimized: bool,
+    focused: bool,
+}
+
+impl Default for WindowStatus {
+    fn default() -> Self {
+        WindowStatus {
+            minimized: false,
+            focused: true,
+        }
+    }
+}
+
+impl WindowStatus {
+    /// Returns true if the main window is minimized.
+    pub fn is_minimized(&self) -> bool {
+        self.minimized
+    }
+
+    /// Returns true if the main window has the focus.
+    pub fn is_focused(&self) -> bool {
+        self.focused
+    }
+}
+
+/// Updates the `WindowStatus` from the events of the main window, and pauses `Time` while it is
+/// minimized according to the `BackgroundSettings`.
+#[derive(Debug)]
+pub struct BackgroundSystem {
+    pub(crate) reader: ReaderId<Event<'static, ()>>,
+}
+
+impl ThreadLocalSystem<'static> for BackgroundSystem {
+    fn build(mut self) -> Box<dyn Runnable> {
+        // whether the time was paused by this system, to not resume what the game paused
+        let mut paused = false;
+
+        Box::new(
+            SystemBuilder::new("BackgroundSystem")
+                .read_resource::<EventChannel<Event<'static, ()>>>()
+                .read_resource::<BackgroundSettings>()
+                .read_resource::<Window>()
+                .write_resource::<WindowStatus>()
+                .write_resource::<Time>()
+                .build(
+                    move |_commands, _world, (events, settings, window, status, time), _query| {
+                        for event in events.read(&mut self.reader) {
+                            match *event {
+                                Event::WindowEvent {
+                                    window_id,
+                                    event: WindowEvent::Resized(size),
+                                } if window_id == window.id() => {
+                                    status.minimized = size.width == 0 || size.height == 0;
+                                }
+                                Event::WindowEvent {
+                                    window_id,
+                                    event: WindowEvent::Focused(focused),
+                                } if window_id == window.id() => status.focused = focused,
+                                _ => {}
+                            }
+                        }
+
+                        let pause = settings.pause && status.minimized;
+                        if pause && !paused && !time.is_paused() {
+                            time.set_paused(true);
+                            paused = true;
+                        } else if !pause && paused {
+                            time.set_paused(false);
+                            paused = false;
+                        }
+                    },
+                ),
+        )
+    }
+}
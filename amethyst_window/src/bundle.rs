@@ -1,9 +1,17 @@
+use std::path::PathBuf;
+
 use amethyst_config::{Config, ConfigError};
-use amethyst_core::ecs::*;
+use amethyst_core::{
+    ecs::*, frame_limiter::FrameLimiter, shrev::EventChannel, timing::DeliveryTime,
+};
 use amethyst_error::Error;
-use winit::event_loop::EventLoop;
+use winit::{event::Event, event_loop::EventLoop};
 
-use crate::{DisplayConfig, EventLoopSystem, ScreenDimensions, WindowSystem};
+use crate::{
+    BackgroundSettings, BackgroundSystem, CursorMode, CursorSystem, DisplayConfig, EventLoopSystem,
+    FileDropEvent, Monitors, ScaleFactorEvent, ScreenDimensions, WindowCommandSystem,
+    WindowCommands, WindowStatus, WindowSystem, Windows,
+};
 
 /// Screen width used in predefined display configuration.
 #[cfg(feature = "test-support")]
@@ -17,12 +25,23 @@ pub const SCREEN_HEIGHT: u32 = 600;
 #[derive(Debug)]
 pub struct WindowBundle {
     config: DisplayConfig,
+    config_path: Option<PathBuf>,
 }
 
 impl WindowBundle {
     /// Builds a new window bundle from a loaded `DisplayConfig`.
     pub fn from_config(config: DisplayConfig) -> Self {
-        WindowBundle { config }
+        WindowBundle {
+            config,
+            config_path: None,
+        }
+    }
+
+    /// Persists the changes made with `WindowCommand`s to the file at `path`, and applies the
+    /// changes made to the file while the game runs, see `WindowCommands::set_config_path`.
+    pub fn with_config_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.config_path = Some(path.into());
+        self
     }
 
     /// Builds a new window bundle by loading the `DisplayConfig` from `path`.
@@ -70,12 +89,41 @@ impl SystemBundle for WindowBundle {
 
         let (width, height) = window.inner_size().into();
 
-        resources.insert(ScreenDimensions::new(width, height));
+        resources.insert(ScreenDimensions::with_scale_factor(
+            width,
+            height,
+            window.scale_factor(),
+        ));
         resources.insert(window);
+        resources.insert(CursorMode::default());
+        resources.insert(self.config.clone());
+        let mut commands = WindowCommands::default();
+        commands.set_config_path(self.config_path.clone());
+        resources.insert(commands);
+        resources.insert(Windows::default());
+        resources.insert(Monitors::from_monitors(&event_loop));
+        resources.get_or_default::<EventChannel<DeliveryTime>>();
+        resources.get_or_default::<EventChannel<FileDropEvent>>();
+        resources.get_or_default::<EventChannel<ScaleFactorEvent>>();
+        resources.get_or_default::<FrameLimiter>();
+        resources.get_or_default::<BackgroundSettings>();
+        resources.insert(WindowStatus::default());
+
+        let mut event_channel = resources
+            .get_mut::<EventChannel<Event<'static, ()>>>()
+            .expect("Window event channel not found in resources");
+        let reader = event_channel.register_reader();
+        let background_reader = event_channel.register_reader();
+        drop(event_channel);
 
         builder
             .add_system(WindowSystem)
-            .add_thread_local(EventLoopSystem { event_loop });
+            .add_thread_local(EventLoopSystem { event_loop })
+            .add_thread_local(CursorSystem { reader })
+            .add_thread_local(WindowCommandSystem)
+            .add_thread_local(BackgroundSystem {
+                reader: background_reader,
+            });
 
         Ok(())
     }
@@ -0,0 +1,339 @@
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+
+use amethyst_config::{Config, ConfigFormat};
+use amethyst_core::{
+    dispatcher::ThreadLocalSystem,
+    ecs::{Runnable, SystemBuilder},
+    frame_limiter::FrameLimiter,
+};
+use winit::{
+    dpi::Size,
+    window::{Icon, Window},
+};
+
+use crate::{
+    config::{fullscreen, DisplayConfig},
+    monitor::{DisplayMode, MonitorIdent, Monitors},
+    taskbar::{Taskbar, TaskbarProgress},
+};
+
+/// How often the watched configuration file is checked for changes.
+const WATCH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A change of the window settings, applied at runtime by the `WindowCommandSystem`.
+#[derive(Clone, Debug)]
+pub enum WindowCommand {
+    /// Change the title of the window.
+    SetTitle(String),
+    /// Resize the window, in logical pixels.
+    SetDimensions(u32, u32),
+    /// Make the window borderless fullscreen on the given monitor, or windowed with `None`.
+    SetFullscreen(Option<MonitorIdent>),
+    /// Make the window exclusive fullscreen on the given monitor, with the closest video mode
+    /// of the monitor, see `Monitors`.
+    SetExclusiveFullscreen(MonitorIdent, DisplayMode),
+    /// Switch between windowed, and borderless fullscreen on the monitor the window is on.
+    ToggleFullscreen,
+    /// Enumerate the monitors and their video modes again, into the `Monitors` resource.
+    RefreshMonitors,
+    /// Enable or disable pacing the frames to the refresh rate of the monitor, see
+    /// `DisplayConfig::refresh_pacing`.
+    SetRefreshPacing(bool),
+    /// Change the icon of the window, or remove it with `None`.
+    SetIcon(Option<Icon>),
+    /// Show a progress on the taskbar entry of the application.
+    SetTaskbarProgress(TaskbarProgress),
+    /// Apply every setting of a configuration which can change at runtime.
+    Apply(Box<DisplayConfig>),
+    /// Load the configuration file the settings are persisted to again, and apply it.
+    Reload,
+}
+
+/// World resource queuing the `WindowCommand`s to apply to the window.
+///
+/// The `DisplayConfig` resource always holds the current settings. When a config path is set,
+/// changes to the persisted settings are written back to it as RON, and changes made to the
+/// file while the game runs are applied to the window.
+///
+/// `transparent`, `multitouch`, `drag_and_drop` and `icon` are only used when creating the
+/// window, use `WindowCommand::SetIcon` to change the icon later.
+#[derive(Debug, Default)]
+pub struct WindowCommands {
+    commands: Vec<WindowCommand>,
+    config_path: Option<PathBuf>,
+    desktop_entry: Option<String>,
+}
+
+impl WindowCommands {
+    /// Queues a command, applied during the next frame.
+    pub fn push(&mut self, command: WindowCommand) {
+        self.commands.push(command);
+    }
+
+    /// Sets the file the settings are persisted to and reloaded from, or `None` to stop.
+    pub fn set_config_path(&mut self, path: Option<PathBuf>) {
+        self.config_path = path;
+    }
+
+    /// The file the settings are persisted to and reloaded from.
+    pub fn config_path(&self) -> Option<&Path> {
+        self.config_path.as_deref()
+    }
+
+    /// Sets the name of the `.desktop` file of the application, without its extension, which
+    /// the taskbar progress is shown on with Linux launchers. Defaults to the name of the
+    /// executable.
+    pub fn set_desktop_entry(&mut self, name: Option<String>) {
+        self.desktop_entry = name;
+    }
+}
+
+/// Applies the `WindowCommands` to the `Window` and the `DisplayConfig` resource.
+///
+/// With refresh pacing, the `FrameLimiter` is told the refresh rate of the current monitor.
+#[derive(Debug)]
+pub struct WindowCommandSystem;
+
+impl ThreadLocalSystem<'static> for WindowCommandSystem {
+    fn build(self) -> Box<dyn Runnable> {
+        let mut paced = false;
+        let mut watched: Option<(PathBuf, Option<SystemTime>)> = None;
+        let mut last_check = Instant::now();
+        let mut taskbar = Taskbar::default();
+
+        Box::new(
+            SystemBuilder::new("WindowCommandSystem")
+                .write_resource::<WindowCommands>()
+                .write_resource::<DisplayConfig>()
+                .write_resource::<FrameLimiter>()
+                .write_resource::<Monitors>()
+                .read_resource::<Window>()
+                .build(
+                    move |_commands,
+                          _world,
+                          (commands, config, limiter, monitors, window),
+                          _query| {
+                        let commands: &mut WindowCommands = commands;
+                        if !paced {
+                            limiter.set_vsync(paced_refresh_rate(window, config));
+                            paced = true;
+                        }
+
+                        // start watching when the path changes, without reloading the file
+                        let path = commands.config_path.clone();
+                        if watched.as_ref().map(|(watched, _)| watched) != path.as_ref() {
+                            watched = path.map(|path| {
+                                let modified = modified(&path);
+                                (path, modified)
+                            });
+                        }
+                        if let Some((ref path, ref mut last_modified)) = watched {
+                            if last_check.elapsed() >= WATCH_INTERVAL {
+                                last_check = Instant::now();
+                                let modified = modified(path);
+                                if modified != *last_modified {
+                                    *last_modified = modified;
+                                    commands.push(WindowCommand::Reload);
+                                }
+                            }
+                        }
+
+                        let mut changed = false;
+                        for command in commands.commands.drain(..) {
+                            let mut new_config = config.clone();
+                            match command {
+                                WindowCommand::SetTitle(title) => new_config.title = title,
+                                WindowCommand::SetDimensions(width, height) => {
+                                    new_config.dimensions = Some((width, height));
+                                }
+                                WindowCommand::SetFullscreen(monitor) => {
+                                    new_config.fullscreen = monitor;
+                                    new_config.fullscreen_mode = None;
+                                }
+                                WindowCommand::SetExclusiveFullscreen(monitor, mode) => {
+                                    new_config.fullscreen = Some(monitor);
+                                    new_config.fullscreen_mode = Some(mode);
+                                }
+                                WindowCommand::ToggleFullscreen => {
+                                    new_config.fullscreen = match new_config.fullscreen {
+                                        Some(_) => None,
+                                        None => {
+                                            window.current_monitor().and_then(|monitor| {
+                                                MonitorIdent::from_monitor_id(&**window, monitor)
+                                            })
+                                        }
+                                    };
+                                    new_config.fullscreen_mode = None;
+                                }
+                                WindowCommand::RefreshMonitors => {
+                                    **monitors = Monitors::from_monitors(&**window);
+                                    continue;
+                                }
+                                WindowCommand::SetRefreshPacing(paced) => {
+                                    new_config.refresh_pacing = paced;
+                                }
+                                WindowCommand::SetIcon(icon) => {
+                                    window.set_window_icon(icon.clone());
+                                    config.loaded_icon = icon;
+                                    continue;
+                                }
+                                WindowCommand::SetTaskbarProgress(progress) => {
+                                    let entry = desktop_entry(commands.desktop_entry.as_deref());
+                                    taskbar.set_progress(window, &entry, progress);
+                                    continue;
+                                }
+                                WindowCommand::Apply(applied) => new_config = *applied,
+                                WindowCommand::Reload => {
+                                    let path = match commands.config_path {
+                                        Some(ref path) => path,
+                                        None => continue,
+                                    };
+                                    match DisplayConfig::load(path) {
+                                        Ok(loaded) => new_config = loaded,
+                                        Err(e) => {
+                                            log::error!(
+                                                "Failed to reload the display config from `{}`: {}",
+                                                path.display(),
+                                                e
+                                            );
+                                            continue;
+                                        }
+                                    }
+                                }
+                            }
+                            changed |= apply(window, config, limiter, new_config);
+                        }
+
+                        if !changed {
+                            return;
+                        }
+                        if let Some(ref path) = commands.config_path {
+                            match config.write_format(ConfigFormat::Ron, path) {
+                                Ok(()) => {
+                                    // our own write isn't a change to reload
+                                    if let Some((_, ref mut last_modified)) = watched {
+                                        *last_modified = modified(path);
+                                    }
+                                }
+                                Err(e) => {
+                                    log::error!(
+                                        "Failed to save the display config to `{}`: {}",
+                                        path.display(),
+                                        e
+                                    );
+                                }
+                            }
+                        }
+                    },
+                ),
+        )
+    }
+}
+
+// The name of the desktop file of the application, by default the name of the executable.
+fn desktop_entry(name: Option<&str>) -> String {
+    name.map(str::to_string).unwrap_or_else(|| {
+        std::env::current_exe()
+            .ok()
+            .and_then(|exe| Some(exe.file_stem()?.to_string_lossy().into_owned()))
+            .unwrap_or_default()
+    })
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    path.metadata()
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+// Sets the window to the new settings, which then replace the current ones. Returns whether a
+// persisted setting changed.
+fn apply(
+    window: &Window,
+    config: &mut DisplayConfig,
+    limiter: &mut FrameLimiter,
+    mut new: DisplayConfig,
+) -> bool {
+    let mut changed = false;
+    if new.title != config.title {
+        window.set_title(&new.title);
+        changed = true;
+    }
+    if new.fullscreen != config.fullscreen || new.fullscreen_mode != config.fullscreen_mode {
+        changed = true;
+        window.set_fullscreen(
+            new.fullscreen
+                .as_ref()
+                .map(|ident| fullscreen(ident, new.fullscreen_mode.as_ref(), window)),
+        );
+    }
+    if new.dimensions != config.dimensions {
+        if let Some(dimensions) = new.dimensions {
+            window.set_inner_size(Size::Logical(dimensions.into()));
+        }
+        changed = true;
+    }
+    if new.min_dimensions != config.min_dimensions {
+        window.set_min_inner_size(new.min_dimensions.map(|d| Size::Logical(d.into())));
+        changed = true;
+    }
+    if new.max_dimensions != config.max_dimensions {
+        window.set_max_inner_size(new.max_dimensions.map(|d| Size::Logical(d.into())));
+        changed = true;
+    }
+    if new.visibility != config.visibility {
+        window.set_visible(new.visibility);
+        changed = true;
+    }
+    if new.always_on_top != config.always_on_top {
+        window.set_always_on_top(new.always_on_top);
+        changed = true;
+    }
+    if new.decorations != config.decorations {
+        window.set_decorations(new.decorations);
+        changed = true;
+    }
+    if new.maximized != config.maximized {
+        window.set_maximized(new.maximized);
+        changed = true;
+    }
+    if new.resizable != config.resizable {
+        window.set_resizable(new.resizable);
+        changed = true;
+    }
+    changed |= new.refresh_pacing != config.refresh_pacing;
+    if new.refresh_pacing != config.refresh_pacing || new.fullscreen_mode != config.fullscreen_mode
+    {
+        limiter.set_vsync(paced_refresh_rate(window, &new));
+    }
+
+    // the window was created with these, keep them as they are
+    new.transparent = config.transparent;
+    new.multitouch = config.multitouch;
+    new.drag_and_drop = config.drag_and_drop;
+    new.icon = config.icon.take();
+    new.loaded_icon = config.loaded_icon.take();
+    *config = new;
+    changed
+}
+
+// The refresh rate of the exclusive fullscreen mode, or the highest refresh rate of the current
+// resolution of the monitor the window is on, if frames are paced to it.
+fn paced_refresh_rate(window: &Window, config: &DisplayConfig) -> Option<u32> {
+    if !config.refresh_pacing {
+        return None;
+    }
+    if let (Some(_), Some(mode)) = (&config.fullscreen, &config.fullscreen_mode) {
+        return Some(u32::from(mode.refresh_rate));
+    }
+    let monitor = window.current_monitor()?;
+    let size = monitor.size();
+    monitor
+        .video_modes()
+        .filter(|mode| mode.size() == size)
+        .map(|mode| u32::from(mode.refresh_rate()))
+        .max()
+}
@@ -11,7 +11,7 @@ use {
     winit::platform::windows::{IconExtWindows, WindowBuilderExtWindows},
 };
 
-use crate::monitor::{MonitorIdent, MonitorsAccess};
+use crate::monitor::{DisplayMode, MonitorIdent, MonitorsAccess};
 
 /// Configuration for a window display.
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -23,6 +23,10 @@ pub struct DisplayConfig {
     /// Defaults to `None`, which means fullscreen is off.
     #[serde(default)]
     pub fullscreen: Option<MonitorIdent>,
+    /// The video mode of exclusive fullscreen, or `None` for a borderless fullscreen window
+    /// covering the monitor at its current resolution.
+    #[serde(default)]
+    pub fullscreen_mode: Option<DisplayMode>,
     /// Current window dimensions, measured in pixels (px).
     #[serde(default)]
     pub dimensions: Option<(u32, u32)>,
@@ -59,6 +63,15 @@ pub struct DisplayConfig {
     /// window.
     #[serde(default)]
     pub transparent: bool,
+    /// Whether the `FrameLimiter` paces frames to the refresh rate of the monitor. The renderer
+    /// always presents with vsync, this only keeps the limiter from sleeping past a refresh.
+    #[serde(default = "default_refresh_pacing")]
+    pub refresh_pacing: bool,
+    /// Whether files can be dragged onto the window, see `FileDropEvent`. This is always
+    /// enabled except on Windows, where it prevents audio from being initialized on the same
+    /// thread.
+    #[serde(default)]
+    pub drag_and_drop: bool,
 
     /// A programmatically loaded window icon; not present in serialization.
     /// Takes precedence over `icon`.
@@ -93,6 +106,7 @@ impl Default for DisplayConfig {
         DisplayConfig {
             title: default_title(),
             fullscreen: None,
+            fullscreen_mode: None,
             dimensions: None,
             min_dimensions: None,
             max_dimensions: None,
@@ -104,6 +118,8 @@ impl Default for DisplayConfig {
             multitouch: false,
             resizable: default_resizable(),
             transparent: false,
+            refresh_pacing: default_refresh_pacing(),
+            drag_and_drop: false,
             loaded_icon: None,
         }
     }
@@ -125,6 +141,10 @@ fn default_resizable() -> bool {
     true
 }
 
+fn default_refresh_pacing() -> bool {
+    true
+}
+
 impl DisplayConfig {
     /// Creates a `winit::WindowBuilder` using the values set in the `DisplayConfig`.
     ///
@@ -140,7 +160,8 @@ impl DisplayConfig {
             window_icon: None,
             fullscreen: self
                 .fullscreen
-                .map(|ident| Fullscreen::Borderless(Some(ident.monitor_id(monitors)))),
+                .as_ref()
+                .map(|ident| fullscreen(ident, self.fullscreen_mode.as_ref(), monitors)),
             resizable: self.resizable,
             inner_size: self.dimensions.map(|d| d.into()).map(Size::Logical),
             min_inner_size: self.min_dimensions.map(|d| d.into()).map(Size::Logical),
@@ -151,7 +172,7 @@ impl DisplayConfig {
 
         #[cfg(target_os = "windows")]
         {
-            builder = builder.with_drag_and_drop(false);
+            builder = builder.with_drag_and_drop(self.drag_and_drop);
         }
         builder.window = attrs;
         builder = builder.with_window_icon(self.loaded_icon);
@@ -177,3 +198,16 @@ impl DisplayConfig {
         builder
     }
 }
+
+// Exclusive fullscreen with the closest video mode, or borderless without a mode.
+pub(crate) fn fullscreen(
+    ident: &MonitorIdent,
+    mode: Option<&DisplayMode>,
+    monitors: &impl MonitorsAccess,
+) -> Fullscreen {
+    let monitor = ident.monitor_id(monitors);
+    match mode.and_then(|mode| mode.video_mode(&monitor)) {
+        Some(video_mode) => Fullscreen::Exclusive(video_mode),
+        None => Fullscreen::Borderless(Some(monitor)),
+    }
+}
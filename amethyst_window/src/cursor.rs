@@ -0,0 +1,86 @@
+use amethyst_core::{
+    dispatcher::ThreadLocalSystem,
+    ecs::{Runnable, SystemBuilder},
+    shrev::{EventChannel, ReaderId},
+};
+use serde::{Deserialize, Serialize};
+use winit::{
+    event::{Event, WindowEvent},
+    window::Window,
+};
+
+/// World resource selecting how the cursor behaves while the window is focused.
+///
+/// Modes which grab the cursor are suspended while the window is unfocused and applied again
+/// once focus returns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CursorMode {
+    /// The cursor is visible and can move freely.
+    Normal,
+    /// The cursor is invisible while over the window, but can still leave it.
+    Hidden,
+    /// The cursor is visible, but can't leave the window.
+    Confined,
+    /// The cursor is invisible and can't leave the window.
+    ///
+    /// Use the raw mouse motion from the input handler to read movement in this mode.
+    Captured,
+}
+
+impl Default for CursorMode {
+    fn default() -> Self {
+        CursorMode::Normal
+    }
+}
+
+impl CursorMode {
+    /// Returns true if the cursor is kept inside the window in this mode.
+    pub fn is_grabbed(self) -> bool {
+        matches!(self, CursorMode::Confined | CursorMode::Captured)
+    }
+
+    /// Returns true if the cursor is visible in this mode.
+    pub fn is_visible(self) -> bool {
+        matches!(self, CursorMode::Normal | CursorMode::Confined)
+    }
+}
+
+/// Applies the `CursorMode` resource to the `Window`.
+#[derive(Debug)]
+pub struct CursorSystem {
+    pub(crate) reader: ReaderId<Event<'static, ()>>,
+}
+
+impl ThreadLocalSystem<'static> for CursorSystem {
+    fn build(mut self) -> Box<dyn Runnable> {
+        let mut applied: Option<CursorMode> = None;
+        let mut focused = true;
+
+        Box::new(
+            SystemBuilder::new("CursorSystem")
+                .read_resource::<EventChannel<Event<'static, ()>>>()
+                .read_resource::<CursorMode>()
+                .read_resource::<Window>()
+                .build(move |_commands, _world, (events, mode, window), _query| {
+                    for event in events.read(&mut self.reader) {
+                        if let Event::WindowEvent {
+                            event: WindowEvent::Focused(is_focused),
+                            ..
+                        } = *event
+                        {
+                            focused = is_focused;
+                        }
+                    }
+
+                    let target = if focused { **mode } else { CursorMode::Normal };
+                    if applied != Some(target) {
+                        if let Err(e) = window.set_cursor_grab(target.is_grabbed()) {
+                            log::warn!("Failed to set cursor grab state: {}", e);
+                        }
+                        window.set_cursor_visible(target.is_visible());
+                        applied = Some(target);
+                    }
+                }),
+        )
+    }
+}
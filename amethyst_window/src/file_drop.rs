@@ -0,0 +1,62 @@
+use std::path::PathBuf;
+
+use winit::{
+    event::{Event, WindowEvent},
+    window::WindowId,
+};
+
+/// Files dragged onto a window from the OS, sent by the `EventLoopSystem` to the
+/// `EventChannel<FileDropEvent>` resource.
+///
+/// On Windows, `DisplayConfig::drag_and_drop` must be enabled to receive these events.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FileDropEvent {
+    /// A file is dragged over the window. This is sent once per file when several are dragged.
+    Hovered {
+        /// The window the file is over
+        window_id: WindowId,
+        /// The path of the file
+        path: PathBuf,
+    },
+    /// The files dragged over the window left it, or the drag was cancelled.
+    HoverCancelled {
+        /// The window the files were over
+        window_id: WindowId,
+    },
+    /// A file was dropped onto the window. This is sent once per file when several are dropped.
+    Dropped {
+        /// The window the file was dropped onto
+        window_id: WindowId,
+        /// The path of the file
+        path: PathBuf,
+    },
+}
+
+impl FileDropEvent {
+    pub(crate) fn from_event(event: &Event<'static, ()>) -> Option<Self> {
+        match event {
+            Event::WindowEvent { window_id, event } => {
+                let window_id = *window_id;
+                match event {
+                    WindowEvent::HoveredFile(path) => {
+                        Some(FileDropEvent::Hovered {
+                            window_id,
+                            path: path.clone(),
+                        })
+                    }
+                    WindowEvent::HoveredFileCancelled => {
+                        Some(FileDropEvent::HoverCancelled { window_id })
+                    }
+                    WindowEvent::DroppedFile(path) => {
+                        Some(FileDropEvent::Dropped {
+                            window_id,
+                            path: path.clone(),
+                        })
+                    }
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+}
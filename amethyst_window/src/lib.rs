@@ -14,20 +14,34 @@
 #![warn(clippy::all)]
 #![allow(clippy::new_without_default)]
 
+mod background;
 mod bundle;
+mod command;
 mod config;
+mod cursor;
+mod file_drop;
 mod monitor;
 mod resources;
+mod scale_factor;
 mod system;
+mod taskbar;
+mod windows;
 
-pub use winit::window::Window;
+pub use winit::window::{Icon, Window};
 
 #[cfg(feature = "test-support")]
 pub use crate::bundle::{SCREEN_HEIGHT, SCREEN_WIDTH};
 pub use crate::{
+    background::{BackgroundSettings, BackgroundSystem, WindowStatus},
     bundle::WindowBundle,
+    command::{WindowCommand, WindowCommandSystem, WindowCommands},
     config::DisplayConfig,
-    monitor::{MonitorIdent, MonitorsAccess},
-    resources::ScreenDimensions,
+    cursor::{CursorMode, CursorSystem},
+    file_drop::FileDropEvent,
+    monitor::{DisplayMode, MonitorIdent, MonitorInfo, Monitors, MonitorsAccess},
+    resources::{PixelPolicy, ScreenDimensions},
+    scale_factor::ScaleFactorEvent,
     system::*,
+    taskbar::TaskbarProgress,
+    windows::Windows,
 };
@@ -1,7 +1,11 @@
-use std::collections::VecDeque;
+use std::{cmp::Ordering, collections::VecDeque};
 
 use serde::{Deserialize, Serialize};
-use winit::{event_loop::EventLoop, monitor::MonitorHandle, window::Window};
+use winit::{
+    event_loop::EventLoop,
+    monitor::{MonitorHandle, VideoMode},
+    window::Window,
+};
 
 /// A struct that can resolve monitors.
 /// Usually either a Window or an EventLoop.
@@ -88,3 +92,121 @@ impl MonitorIdent {
             .unwrap_or_else(|| monitors.primary().expect("No Primary Monitor Found!"))
     }
 }
+
+/// A video mode of a monitor, used for exclusive fullscreen.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Deserialize, Serialize)]
+pub struct DisplayMode {
+    /// Resolution of the mode, in physical pixels (px).
+    pub dimensions: (u32, u32),
+    /// Bits per pixel of the mode.
+    pub bit_depth: u16,
+    /// Refresh rate of the mode, in Hz.
+    pub refresh_rate: u16,
+}
+
+impl DisplayMode {
+    /// Get the mode of a winit video mode.
+    pub fn from_video_mode(mode: &VideoMode) -> Self {
+        DisplayMode {
+            dimensions: mode.size().into(),
+            bit_depth: mode.bit_depth(),
+            refresh_rate: mode.refresh_rate(),
+        }
+    }
+
+    /// Select the video mode of the monitor that matches this mode most closely: the same
+    /// resolution if possible, then the same refresh rate and bit depth.
+    pub fn video_mode(&self, monitor: &MonitorHandle) -> Option<VideoMode> {
+        let (width, height) = self.dimensions;
+        monitor.video_modes().min_by_key(|mode| {
+            let size = mode.size();
+            (
+                (i64::from(size.width) - i64::from(width)).abs()
+                    + (i64::from(size.height) - i64::from(height)).abs(),
+                (i32::from(mode.refresh_rate()) - i32::from(self.refresh_rate)).abs(),
+                (i32::from(mode.bit_depth()) - i32::from(self.bit_depth)).abs(),
+            )
+        })
+    }
+
+    // Orders modes by area, then resolution, refresh rate and bit depth.
+    fn cmp_size_and_rate(&self, other: &Self) -> Ordering {
+        let area = |mode: &Self| u64::from(mode.dimensions.0) * u64::from(mode.dimensions.1);
+        area(self)
+            .cmp(&area(other))
+            .then(self.dimensions.cmp(&other.dimensions))
+            .then(self.refresh_rate.cmp(&other.refresh_rate))
+            .then(self.bit_depth.cmp(&other.bit_depth))
+    }
+}
+
+/// A monitor, as found when the `Monitors` resource was last refreshed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MonitorInfo {
+    /// The identifier of the monitor, for the fullscreen settings of `DisplayConfig`.
+    pub ident: MonitorIdent,
+    /// Whether this is the primary monitor.
+    pub primary: bool,
+    /// Current resolution of the monitor, in physical pixels (px).
+    pub dimensions: (u32, u32),
+    /// Position of the top left corner of the monitor on the desktop, in physical pixels (px).
+    pub position: (i32, i32),
+    /// Number of physical pixels per logical pixel.
+    pub scale_factor: f64,
+    /// The video modes of the monitor, from the largest resolution and highest refresh rate.
+    pub modes: Vec<DisplayMode>,
+}
+
+/// World resource listing the monitors and their video modes.
+///
+/// It is filled when the window is created, push `WindowCommand::RefreshMonitors` to enumerate
+/// them again, e.g. when showing the display settings.
+#[derive(Clone, Debug, Default)]
+pub struct Monitors {
+    monitors: Vec<MonitorInfo>,
+}
+
+impl Monitors {
+    /// Enumerate the monitors.
+    pub fn from_monitors(monitors: &impl MonitorsAccess) -> Self {
+        let primary = monitors.primary();
+        let infos = monitors
+            .iter()
+            .into_iter()
+            .filter_map(|monitor| {
+                let ident = MonitorIdent::from_monitor_id(monitors, monitor.clone())?;
+                let mut modes = monitor
+                    .video_modes()
+                    .map(|mode| DisplayMode::from_video_mode(&mode))
+                    .collect::<Vec<_>>();
+                modes.sort_by(|a, b| b.cmp_size_and_rate(a));
+                modes.dedup();
+                let position = monitor.position();
+                Some(MonitorInfo {
+                    ident,
+                    primary: primary.as_ref() == Some(&monitor),
+                    dimensions: monitor.size().into(),
+                    position: (position.x, position.y),
+                    scale_factor: monitor.scale_factor(),
+                    modes,
+                })
+            })
+            .collect();
+        Monitors { monitors: infos }
+    }
+
+    /// Iterates over the monitors.
+    pub fn iter(&self) -> impl Iterator<Item = &MonitorInfo> {
+        self.monitors.iter()
+    }
+
+    /// The primary monitor.
+    pub fn primary(&self) -> Option<&MonitorInfo> {
+        self.monitors.iter().find(|monitor| monitor.primary)
+    }
+
+    /// The monitor with the given identifier.
+    pub fn get(&self, ident: &MonitorIdent) -> Option<&MonitorInfo> {
+        self.monitors.iter().find(|monitor| monitor.ident == *ident)
+    }
+}
@@ -1,4 +1,23 @@
 use amethyst_core::math::Vector2;
+use serde::{Deserialize, Serialize};
+
+/// Whether sizes are measured in physical pixels, the pixels of the monitor, or in logical
+/// pixels, which are physical pixels divided by the scale factor of the window.
+///
+/// Logical pixels keep the same apparent size on monitors with different DPI.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PixelPolicy {
+    /// Physical pixels (px).
+    Physical,
+    /// Logical pixels, scaled by the scale factor.
+    Logical,
+}
+
+impl Default for PixelPolicy {
+    fn default() -> Self {
+        PixelPolicy::Physical
+    }
+}
 
 /// World resource that stores screen dimensions.
 #[derive(Debug, PartialEq, Clone)]
@@ -9,6 +28,8 @@ pub struct ScreenDimensions {
     pub(crate) h: f64,
     /// Width divided by height.
     aspect_ratio: f32,
+    /// Physical pixels per logical pixel.
+    scale_factor: f64,
     pub(crate) dirty: bool,
 }
 
@@ -19,31 +40,73 @@ impl ScreenDimensions {
             w: f64::from(w),
             h: f64::from(h),
             aspect_ratio: w as f32 / h as f32,
+            scale_factor: 1.0,
             dirty: false,
         }
     }
 
-    /// Returns the current logical size of window as diagonal vector.
+    /// Creates a new screen dimensions object with the given width and height in physical
+    /// pixels, and the scale factor of the window.
+    pub fn with_scale_factor(w: u32, h: u32, scale_factor: f64) -> Self {
+        ScreenDimensions {
+            scale_factor,
+            ..ScreenDimensions::new(w, h)
+        }
+    }
+
+    /// Returns the current physical size of window as diagonal vector.
     pub fn diagonal(&self) -> Vector2<f32> {
         Vector2::new(self.width(), self.height())
     }
 
-    /// Returns the current logical width of the window.
+    /// Returns the current physical width of the window.
     pub fn width(&self) -> f32 {
         self.w as f32
     }
 
-    /// Returns the current logical height of the window.
+    /// Returns the current physical height of the window.
     pub fn height(&self) -> f32 {
         self.h as f32
     }
 
+    /// Returns the number of physical pixels per logical pixel, which changes when the window
+    /// moves to a monitor with a different DPI.
+    pub fn scale_factor(&self) -> f64 {
+        self.scale_factor
+    }
+
+    /// Returns the current logical width of the window.
+    pub fn logical_width(&self) -> f32 {
+        (self.w / self.scale_factor) as f32
+    }
+
+    /// Returns the current logical height of the window.
+    pub fn logical_height(&self) -> f32 {
+        (self.h / self.scale_factor) as f32
+    }
+
+    /// Returns the width and height of the window, measured with the given policy.
+    pub fn size(&self, policy: PixelPolicy) -> (f32, f32) {
+        match policy {
+            PixelPolicy::Physical => (self.width(), self.height()),
+            PixelPolicy::Logical => (self.logical_width(), self.logical_height()),
+        }
+    }
+
+    /// Returns the scale from pixels of the given policy to physical pixels.
+    pub fn pixel_scale(&self, policy: PixelPolicy) -> f32 {
+        match policy {
+            PixelPolicy::Physical => 1.0,
+            PixelPolicy::Logical => self.scale_factor as f32,
+        }
+    }
+
     /// Returns the current aspect ratio of the window.
     pub fn aspect_ratio(&self) -> f32 {
         self.aspect_ratio
     }
 
-    /// Updates the width and height of the screen and recomputes the aspect
+    /// Updates the width and height of the screen, in physical pixels, and recomputes the aspect
     /// ratio.
     ///
     /// Only use this if you need to programmatically set the resolution of your game.
@@ -55,4 +118,8 @@ impl ScreenDimensions {
         self.aspect_ratio = w as f32 / h as f32;
         self.dirty = true;
     }
+
+    pub(crate) fn set_scale_factor(&mut self, scale_factor: f64) {
+        self.scale_factor = scale_factor;
+    }
 }
@@ -0,0 +1,17 @@
+use winit::window::WindowId;
+
+/// Sent by the `EventLoopSystem` to the `EventChannel<ScaleFactorEvent>` resource when the scale
+/// factor of a window changes, e.g. when it moves to a monitor with a different DPI or the DPI
+/// settings of the OS change.
+///
+/// The window is resized to `dimensions` right after, which the `WindowSystem` copies to the
+/// `ScreenDimensions` along with the scale factor.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ScaleFactorEvent {
+    /// The window whose scale factor changed
+    pub window_id: WindowId,
+    /// The new number of physical pixels per logical pixel
+    pub scale_factor: f64,
+    /// The new size of the window, in physical pixels (px)
+    pub dimensions: (u32, u32),
+}
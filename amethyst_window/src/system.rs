@@ -1,17 +1,21 @@
 use amethyst_core::{
     dispatcher::{System, ThreadLocalSystem},
     ecs::{systems::ParallelRunnable, Runnable, SystemBuilder},
+    timing::{DeliveryTime, Instant},
     EventChannel,
 };
 use winit::{
     dpi::Size,
-    event::Event,
+    event::{Event, WindowEvent},
     event_loop::{ControlFlow, EventLoop},
     platform::run_return::EventLoopExtRunReturn,
     window::Window,
 };
 
-use crate::resources::ScreenDimensions;
+use crate::{
+    file_drop::FileDropEvent, resources::ScreenDimensions, scale_factor::ScaleFactorEvent,
+    windows::Windows,
+};
 
 /// Manages window dimensions
 #[derive(Debug)]
@@ -30,10 +34,15 @@ impl System for WindowSystem {
 
                     // Send resource size changes to the window
                     if screen_dimensions.dirty {
-                        window.set_inner_size(Size::Logical((width, height).into()));
+                        window.set_inner_size(Size::Physical((width as u32, height as u32).into()));
                         screen_dimensions.dirty = false;
                     }
 
+                    let scale_factor = window.scale_factor();
+                    if (scale_factor - screen_dimensions.scale_factor()).abs() > f64::EPSILON {
+                        screen_dimensions.set_scale_factor(scale_factor);
+                    }
+
                     let (window_width, window_height): (f64, f64) = window.inner_size().into();
 
                     // Send window size changes to the resource
@@ -52,6 +61,12 @@ impl System for WindowSystem {
 ///
 /// This system must be active for any `GameState` to receive
 /// any `StateEvent::Window` event into it's `handle_event` method.
+///
+/// It also creates the windows requested with `Windows::open`, and sends the files dropped onto
+/// the windows as `FileDropEvent`s and the changes of scale factor as `ScaleFactorEvent`s.
+///
+/// The time winit delivered each event at is written to `EventChannel<DeliveryTime>`, in the
+/// order of the events.
 #[derive(Debug)]
 pub struct EventLoopSystem {
     pub(crate) event_loop: EventLoop<()>,
@@ -60,25 +75,58 @@ pub struct EventLoopSystem {
 impl ThreadLocalSystem<'static> for EventLoopSystem {
     fn build(mut self) -> Box<dyn Runnable> {
         let mut events = Vec::with_capacity(128);
+        let mut times = Vec::with_capacity(128);
+        let mut scale_events = Vec::new();
 
         Box::new(
             SystemBuilder::new("EventsLoopSystem")
                 .write_resource::<EventChannel<Event<'static, ()>>>()
-                .build(move |_commands, _world, event_channel, _query| {
-                    self.event_loop.run_return(|event, _, flow| {
-                        match event {
-                            Event::WindowEvent { .. } => {
-                                events.push(event.to_static().unwrap());
+                .write_resource::<EventChannel<DeliveryTime>>()
+                .write_resource::<EventChannel<FileDropEvent>>()
+                .write_resource::<EventChannel<ScaleFactorEvent>>()
+                .write_resource::<Windows>()
+                .build(
+                    move |_commands,
+                          _world,
+                          (event_channel, time_channel, drop_channel, scale_channel, windows),
+                          _query| {
+                        windows.create_pending(&self.event_loop);
+                        self.event_loop.run_return(|event, _, flow| {
+                            match event {
+                                // borrows the new size, so it can't be made 'static
+                                Event::WindowEvent {
+                                    window_id,
+                                    event:
+                                        WindowEvent::ScaleFactorChanged {
+                                            scale_factor,
+                                            new_inner_size,
+                                        },
+                                } => {
+                                    scale_events.push(ScaleFactorEvent {
+                                        window_id,
+                                        scale_factor,
+                                        dimensions: (*new_inner_size).into(),
+                                    });
+                                }
+                                Event::WindowEvent { .. } | Event::DeviceEvent { .. } => {
+                                    events.push(event.to_static().unwrap());
+                                    times.push(DeliveryTime(Instant::now()));
+                                }
+                                _ => {}
                             }
-                            Event::DeviceEvent { .. } => {
-                                events.push(event.to_static().unwrap());
+                            *flow = ControlFlow::Exit;
+                        });
+                        for event in &events {
+                            windows.handle_event(event);
+                            if let Some(drop) = FileDropEvent::from_event(event) {
+                                drop_channel.single_write(drop);
                             }
-                            _ => {}
                         }
-                        *flow = ControlFlow::Exit;
-                    });
-                    event_channel.drain_vec_write(&mut events);
-                }),
+                        event_channel.drain_vec_write(&mut events);
+                        time_channel.drain_vec_write(&mut times);
+                        scale_channel.drain_vec_write(&mut scale_events);
+                    },
+                ),
         )
     }
 }
@@ -0,0 +1,245 @@
+use winit::window::Window;
+
+/// The progress shown on the taskbar entry of the application, set with
+/// `WindowCommand::SetTaskbarProgress`.
+///
+/// It is shown by the Windows taskbar, and on Linux by the Unity launcher and the docks
+/// implementing its `LauncherEntry` API, which only show the fraction. Other platforms ignore it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TaskbarProgress {
+    /// No progress is shown.
+    None,
+    /// Work of unknown length is in progress. Not shown on Linux.
+    Indeterminate,
+    /// The fraction of the work done, from 0 to 1.
+    Normal(f32),
+    /// The fraction of the work done, which is paused.
+    Paused(f32),
+    /// The fraction of the work done, which failed.
+    Error(f32),
+}
+
+impl Default for TaskbarProgress {
+    fn default() -> Self {
+        TaskbarProgress::None
+    }
+}
+
+impl TaskbarProgress {
+    // The fraction of the work done, if there is one to show.
+    fn fraction(self) -> Option<f32> {
+        match self {
+            TaskbarProgress::None | TaskbarProgress::Indeterminate => None,
+            TaskbarProgress::Normal(fraction)
+            | TaskbarProgress::Paused(fraction)
+            | TaskbarProgress::Error(fraction) => Some(fraction.max(0.0).min(1.0)),
+        }
+    }
+}
+
+/// The taskbar entry of the application, connected to when the progress is first set.
+#[derive(Debug, Default)]
+pub(crate) struct Taskbar(platform::Taskbar);
+
+impl Taskbar {
+    /// Shows the progress on the taskbar entry of the window, or of the application whose desktop
+    /// entry has the given name on Linux.
+    pub(crate) fn set_progress(
+        &mut self,
+        window: &Window,
+        desktop_entry: &str,
+        progress: TaskbarProgress,
+    ) {
+        self.0.set_progress(window, desktop_entry, progress);
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod platform {
+    use std::ptr;
+
+    use winapi::{
+        ctypes::c_void,
+        shared::{windef::HWND, winerror::SUCCEEDED, wtypesbase::CLSCTX_INPROC_SERVER},
+        um::{
+            combaseapi::{CoCreateInstance, CoInitializeEx},
+            objbase::COINIT_APARTMENTTHREADED,
+            shobjidl_core::{
+                CLSID_TaskbarList, ITaskbarList3, TBPF_ERROR, TBPF_INDETERMINATE, TBPF_NOPROGRESS,
+                TBPF_NORMAL, TBPF_PAUSED,
+            },
+        },
+        Interface,
+    };
+    use winit::{platform::windows::WindowExtWindows, window::Window};
+
+    use super::TaskbarProgress;
+
+    // The total the fraction of the work done is scaled to.
+    const TOTAL: u64 = 10_000;
+
+    #[derive(Debug)]
+    pub(super) enum Taskbar {
+        Disconnected,
+        Connected(*mut ITaskbarList3),
+        Failed,
+    }
+
+    impl Default for Taskbar {
+        fn default() -> Self {
+            Taskbar::Disconnected
+        }
+    }
+
+    impl Taskbar {
+        pub(super) fn set_progress(
+            &mut self,
+            window: &Window,
+            _desktop_entry: &str,
+            progress: TaskbarProgress,
+        ) {
+            if let Taskbar::Disconnected = self {
+                *self = connect();
+            }
+            let list = match *self {
+                Taskbar::Connected(list) => list,
+                _ => return,
+            };
+            let state = match progress {
+                TaskbarProgress::None => TBPF_NOPROGRESS,
+                TaskbarProgress::Indeterminate => TBPF_INDETERMINATE,
+                TaskbarProgress::Normal(_) => TBPF_NORMAL,
+                TaskbarProgress::Paused(_) => TBPF_PAUSED,
+                TaskbarProgress::Error(_) => TBPF_ERROR,
+            };
+            let hwnd = window.hwnd() as HWND;
+            // SAFETY: the list stays alive until it's released on drop, and the window handle is
+            // valid while the window is borrowed.
+            unsafe {
+                // setting the value switches to the normal state, so the state is set last
+                if let Some(fraction) = progress.fraction() {
+                    (*list).SetProgressValue(
+                        hwnd,
+                        (f64::from(fraction) * TOTAL as f64) as u64,
+                        TOTAL,
+                    );
+                }
+                (*list).SetProgressState(hwnd, state);
+            }
+        }
+    }
+
+    impl Drop for Taskbar {
+        fn drop(&mut self) {
+            if let Taskbar::Connected(list) = *self {
+                // SAFETY: the list was created by `connect` and isn't used after this.
+                unsafe {
+                    (*list).Release();
+                }
+            }
+        }
+    }
+
+    fn connect() -> Taskbar {
+        let mut list: *mut ITaskbarList3 = ptr::null_mut();
+        // SAFETY: the list is only used when it was created and initialized. COM may already be
+        // initialized on the thread of the window, in which case this does nothing.
+        unsafe {
+            CoInitializeEx(ptr::null_mut(), COINIT_APARTMENTTHREADED);
+            let created = CoCreateInstance(
+                &CLSID_TaskbarList,
+                ptr::null_mut(),
+                CLSCTX_INPROC_SERVER,
+                &ITaskbarList3::uuidof(),
+                &mut list as *mut *mut ITaskbarList3 as *mut *mut c_void,
+            );
+            if !SUCCEEDED(created) {
+                log::warn!("Failed to create the taskbar list: {:#x}", created);
+                return Taskbar::Failed;
+            }
+            let initialized = (*list).HrInit();
+            if !SUCCEEDED(initialized) {
+                log::warn!("Failed to initialize the taskbar list: {:#x}", initialized);
+                (*list).Release();
+                return Taskbar::Failed;
+            }
+        }
+        Taskbar::Connected(list)
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use std::collections::HashMap;
+
+    use winit::window::Window;
+    use zbus::Connection;
+    use zvariant::Value;
+
+    use super::TaskbarProgress;
+
+    #[derive(Debug)]
+    pub(super) enum Taskbar {
+        Disconnected,
+        Connected(Connection),
+        Failed,
+    }
+
+    impl Default for Taskbar {
+        fn default() -> Self {
+            Taskbar::Disconnected
+        }
+    }
+
+    impl Taskbar {
+        pub(super) fn set_progress(
+            &mut self,
+            _window: &Window,
+            desktop_entry: &str,
+            progress: TaskbarProgress,
+        ) {
+            if let Taskbar::Disconnected = self {
+                *self = match Connection::new_session() {
+                    Ok(connection) => Taskbar::Connected(connection),
+                    Err(e) => {
+                        log::warn!("Failed to connect to the session bus: {}", e);
+                        Taskbar::Failed
+                    }
+                };
+            }
+            let connection = match self {
+                Taskbar::Connected(connection) => connection,
+                _ => return,
+            };
+
+            let fraction = progress.fraction();
+            let mut properties = HashMap::new();
+            properties.insert("progress", Value::from(f64::from(fraction.unwrap_or(0.0))));
+            properties.insert("progress-visible", Value::from(fraction.is_some()));
+            let uri = format!("application://{}.desktop", desktop_entry);
+            if let Err(e) = connection.emit_signal(
+                None,
+                "/",
+                "com.canonical.Unity.LauncherEntry",
+                "Update",
+                &(uri.as_str(), properties),
+            ) {
+                log::warn!("Failed to update the launcher entry: {}", e);
+            }
+        }
+    }
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "linux")))]
+mod platform {
+    use winit::window::Window;
+
+    use super::TaskbarProgress;
+
+    #[derive(Debug, Default)]
+    pub(super) struct Taskbar;
+
+    impl Taskbar {
+        pub(super) fn set_progress(&mut self, _: &Window, _: &str, _: TaskbarProgress) {}
+    }
+}
@@ -0,0 +1,116 @@
+use winit::{
+    event::{Event, WindowEvent},
+    event_loop::EventLoop,
+    window::{Window, WindowId},
+};
+
+use crate::config::DisplayConfig;
+
+/// World resource holding the windows opened besides the main `Window`, by name.
+///
+/// Windows are created by the `EventLoopSystem` during the frame after `open` is called, and
+/// are closed when their close button is pressed, while closing the main window still closes
+/// the application. Events of every window go through the same event channel, compare their
+/// `window_id` with `Windows::id` to tell them apart.
+#[derive(Debug, Default)]
+pub struct Windows {
+    windows: Vec<(String, Window)>,
+    pending: Vec<(String, DisplayConfig)>,
+    focused: Option<WindowId>,
+    generation: u64,
+}
+
+impl Windows {
+    /// Requests a new window, replacing an open window with the same name.
+    pub fn open<S: Into<String>>(&mut self, name: S, config: DisplayConfig) {
+        let name = name.into();
+        self.pending.retain(|(pending, _)| *pending != name);
+        self.pending.push((name, config));
+    }
+
+    /// Closes a window, returning false if there is no such window.
+    pub fn close(&mut self, name: &str) -> bool {
+        let pending = self.pending.len();
+        self.pending.retain(|(pending, _)| pending != name);
+        match self.windows.iter().position(|(open, _)| open == name) {
+            Some(index) => {
+                let (_, window) = self.windows.remove(index);
+                if self.focused == Some(window.id()) {
+                    self.focused = None;
+                }
+                self.generation += 1;
+                true
+            }
+            None => pending != self.pending.len(),
+        }
+    }
+
+    /// The window with the given name, once it is created.
+    pub fn get(&self, name: &str) -> Option<&Window> {
+        self.windows
+            .iter()
+            .find(|(open, _)| open == name)
+            .map(|(_, window)| window)
+    }
+
+    /// The id of the window with the given name, to match its events.
+    pub fn id(&self, name: &str) -> Option<WindowId> {
+        self.get(name).map(Window::id)
+    }
+
+    /// The name of the window with the given id, `None` for the main window.
+    pub fn name(&self, id: WindowId) -> Option<&str> {
+        self.windows
+            .iter()
+            .find(|(_, window)| window.id() == id)
+            .map(|(name, _)| name.as_str())
+    }
+
+    /// Iterates over the open windows and their names.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Window)> {
+        self.windows
+            .iter()
+            .map(|(name, window)| (name.as_str(), window))
+    }
+
+    /// The id of the focused window, which may be the main window, or `None` if the application
+    /// isn't focused.
+    pub fn focused(&self) -> Option<WindowId> {
+        self.focused
+    }
+
+    /// Changes every time a window is opened or closed, to rebuild what depends on them.
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    pub(crate) fn create_pending(&mut self, event_loop: &EventLoop<()>) {
+        for (name, config) in self.pending.drain(..) {
+            match config.into_window_builder(event_loop).build(event_loop) {
+                Ok(window) => {
+                    self.windows.retain(|(open, _)| *open != name);
+                    self.windows.push((name, window));
+                    self.generation += 1;
+                }
+                Err(e) => log::error!("Failed to create the window `{}`: {}", name, e),
+            }
+        }
+    }
+
+    pub(crate) fn handle_event(&mut self, event: &Event<'static, ()>) {
+        if let Event::WindowEvent { window_id, event } = event {
+            match event {
+                WindowEvent::Focused(true) => self.focused = Some(*window_id),
+                WindowEvent::Focused(false) if self.focused == Some(*window_id) => {
+                    self.focused = None;
+                }
+                WindowEvent::CloseRequested => {
+                    if let Some(name) = self.name(*window_id).map(str::to_string) {
+                        self.close(&name);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
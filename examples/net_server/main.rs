@@ -1,17 +1,13 @@
 // SERVER
-use std::{net::TcpListener, time::Duration};
+use std::{net::TcpListener, path::PathBuf};
 
 use amethyst::{
-    core::{
-        ecs::{System, SystemBundle},
-        frame_limiter::FrameRateLimitStrategy,
-    },
+    core::ecs::{System, SystemBundle},
     network::simulation::{
         tcp::TcpNetworkBundle, NetworkSimulationEvent, NetworkSimulationTime, TransportResource,
     },
     prelude::*,
     shrev::{EventChannel, ReaderId},
-    utils::application_root_dir,
     Result,
 };
 use log::{error, info};
@@ -23,7 +19,7 @@ fn main() -> Result<()> {
     let listener = TcpListener::bind("0.0.0.0:3457")?;
     listener.set_nonblocking(true)?;
 
-    let assets_dir = application_root_dir()?.join("examples/net_server");
+    let assets_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
 
     let mut game_data = DispatcherBuilder::default();
     game_data
@@ -31,10 +27,7 @@ fn main() -> Result<()> {
         .add_bundle(SpamReceiveBundle);
 
     let game = Application::build(assets_dir, GameState)?
-        .with_frame_limit(
-            FrameRateLimitStrategy::SleepAndYield(Duration::from_millis(2)),
-            60,
-        )
+        .headless(60)
         .build(game_data)?;
     game.run();
     Ok(())
@@ -3,26 +3,35 @@
 use std::{env, marker::PhantomData, path::Path, sync::Arc, time::Duration};
 
 use derivative::Derivative;
-use log::{debug, info, log_enabled, trace, Level};
+use log::{debug, error, info, log_enabled, trace, Level};
 use rayon::ThreadPoolBuilder;
 #[cfg(feature = "profiler")]
 use thread_profiler::{profile_scope, register_thread_with_profiler, write_profile};
-use winit::event::{Event, WindowEvent};
 
 use crate::{
     assets::{start_asset_daemon, DefaultLoader, Source},
     core::{
+        crash::CrashReporter,
         frame_limiter::{FrameLimiter, FrameRateLimitConfig, FrameRateLimitStrategy},
         shrev::{EventChannel, ReaderId},
+        state_stack::StateStack,
         timing::{Stopwatch, Time},
         ArcThreadPool, EventReader,
     },
     ecs::*,
     error::Error,
     game_data::{DataDispose, DataInit},
-    state::{State, StateData, StateMachine, TransEvent},
+    state::{State, StateData, StateLifecycleEvent, StateMachine, TransEvent},
     state_event::{StateEvent, StateEventReader},
 };
+#[cfg(feature = "window")]
+use crate::{
+    window::Window,
+    winit::{
+        event::{Event, WindowEvent},
+        window::WindowId,
+    },
+};
 
 /// `CoreApplication` is the application implementation for the game engine. This is fully generic
 /// over the state type and event type.
@@ -51,11 +60,13 @@ where
     reader: R,
     #[derivative(Debug = "ignore")]
     events: Vec<E>,
+    #[cfg(feature = "window")]
     #[derivative(Debug = "ignore")]
     event_reader_id: ReaderId<Event<'static, ()>>,
     #[derivative(Debug = "ignore")]
     trans_reader_id: ReaderId<TransEvent<T, E>>,
     states: StateMachine<'a, T, E>,
+    #[cfg(feature = "window")]
     ignore_window_close: bool,
     data: T,
 }
@@ -230,27 +241,49 @@ where
             None
         };
 
-        self.initialize();
+        self.start();
+        while self.is_running() {
+            self.run_frame();
+        }
+        self.stop();
+    }
 
+    /// Starts the game, to then call `run_frame` until `is_running` returns false, followed by
+    /// `stop`.
+    ///
+    /// `run` does all of this, these are for driving the game loop from elsewhere, such as an
+    /// event loop which owns the thread.
+    pub fn start(&mut self) {
+        self.initialize();
         self.resources.get_mut::<Stopwatch>().unwrap().start();
+    }
 
-        while self.states.is_running() {
-            self.advance_frame();
-            {
-                #[cfg(feature = "profiler")]
-                profile_scope!("frame_limiter wait");
-                self.resources.get_mut::<FrameLimiter>().unwrap().wait();
-            }
-            {
-                let mut stopwatch = self.resources.get_mut::<Stopwatch>().unwrap();
-                let elapsed = stopwatch.elapsed();
-                let mut time = self.resources.get_mut::<Time>().unwrap();
-                time.increment_frame_number();
-                time.set_delta_time(elapsed);
-                stopwatch.stop();
-                stopwatch.restart();
-            }
+    /// Returns true until the game state indicates that the game is no longer running.
+    pub fn is_running(&self) -> bool {
+        self.states.is_running()
+    }
+
+    /// Runs a single frame of the game loop, waiting for the `FrameLimiter` afterwards.
+    pub fn run_frame(&mut self) {
+        self.advance_frame();
+        {
+            #[cfg(feature = "profiler")]
+            profile_scope!("frame_limiter wait");
+            self.resources.get_mut::<FrameLimiter>().unwrap().wait();
+        }
+        {
+            let mut stopwatch = self.resources.get_mut::<Stopwatch>().unwrap();
+            let elapsed = stopwatch.elapsed();
+            let mut time = self.resources.get_mut::<Time>().unwrap();
+            time.increment_frame_number();
+            time.set_delta_time(elapsed);
+            stopwatch.stop();
+            stopwatch.restart();
         }
+    }
+
+    /// Cleans up once the game stopped running.
+    pub fn stop(&mut self) {
         self.shutdown();
     }
 
@@ -267,42 +300,77 @@ where
             .expect("Tried to start state machine without any states present");
     }
 
-    // React to window close events
+    // React to close events of the main window, and pass the focus of the windows to the frame
+    // limiter
+    #[cfg(feature = "window")]
     fn should_close(&mut self) -> bool {
-        if self.ignore_window_close {
-            false
-        } else {
-            let reader_id = &mut self.event_reader_id;
-            self.resources
-                .get_mut::<EventChannel<Event<'_, ()>>>()
-                .unwrap()
-                .read(reader_id)
-                .any(|e| {
-                    if cfg!(target_os = "ios") {
-                        matches!(
-                            e,
-                            Event::WindowEvent {
-                                event: WindowEvent::Destroyed,
-                                ..
-                            }
-                        )
-                    } else {
-                        matches!(
-                            e,
-                            Event::WindowEvent {
-                                event: WindowEvent::CloseRequested,
-                                ..
-                            }
-                        )
-                    }
-                })
+        // additional windows are closed on their own, without closing the application
+        let main_window = self.resources.get::<Window>().map(|window| window.id());
+        let is_main = |id: &WindowId| main_window.map_or(true, |main| main == *id);
+        let reader_id = &mut self.event_reader_id;
+        let mut close = false;
+        let mut focused = None;
+        let mut minimized = None;
+        for event in self
+            .resources
+            .get_mut::<EventChannel<Event<'_, ()>>>()
+            .unwrap()
+            .read(reader_id)
+        {
+            match event {
+                Event::WindowEvent {
+                    event: WindowEvent::Destroyed,
+                    window_id,
+                } if cfg!(target_os = "ios") && is_main(window_id) => close = true,
+                Event::WindowEvent {
+                    event: WindowEvent::CloseRequested,
+                    window_id,
+                } if !cfg!(target_os = "ios") && is_main(window_id) => close = true,
+                Event::WindowEvent {
+                    event: WindowEvent::Focused(focus),
+                    ..
+                } => focused = Some(*focus),
+                Event::WindowEvent {
+                    event: WindowEvent::Resized(size),
+                    window_id,
+                } if is_main(window_id) => minimized = Some(size.width == 0 || size.height == 0),
+                _ => {}
+            }
+        }
+
+        if let Some(mut limiter) = self.resources.get_mut::<FrameLimiter>() {
+            if let Some(focused) = focused {
+                limiter.set_focused(focused);
+            }
+            if let Some(minimized) = minimized {
+                limiter.set_minimized(minimized);
+            }
+        }
+        close && !self.ignore_window_close
+    }
+
+    // Without windows, only the states and crashes stop the application
+    #[cfg(not(feature = "window"))]
+    fn should_close(&mut self) -> bool {
+        false
+    }
+
+    // Shut down after a system panicked, unless the crash reporter is told to keep running
+    fn crashed(&self) -> bool {
+        let crashed = self
+            .resources
+            .get::<CrashReporter>()
+            .map_or(false, |reporter| reporter.should_shutdown());
+        if crashed {
+            error!("Shutting down after a crash");
         }
+        crashed
     }
 
     /// Advances the game world by one tick.
     fn advance_frame(&mut self) {
         trace!("Advancing frame (`Application::advance_frame`)");
-        if self.should_close() {
+        if self.should_close() || self.crashed() {
             let world = &mut self.world;
             let resources = &mut self.resources;
             let states = &mut self.states;
@@ -358,6 +426,7 @@ where
                     &mut self.resources,
                     &mut self.data,
                 ));
+                self.data.fixed_update(&mut self.world, &mut self.resources);
             }
             {
                 self.resources
@@ -416,6 +485,7 @@ pub struct ApplicationBuilder<S, T, E, R> {
     pub world: World,
     /// Used by bundles to initialize any resources in the world
     pub resources: Resources,
+    #[cfg(feature = "window")]
     ignore_window_close: bool,
     phantom: PhantomData<(T, E, R)>,
 }
@@ -545,16 +615,21 @@ where
         // FIXME check that the loader is added to the resources
         // resources.insert(Loader::new(path.as_ref().to_owned(), pool.clone()));
         resources.insert(pool);
+        #[cfg(feature = "window")]
         resources.insert(EventChannel::<Event<'static, ()>>::with_capacity(2000));
+        #[cfg(feature = "window")]
+        resources.insert(EventChannel::<crate::core::timing::DeliveryTime>::with_capacity(2000));
         //resources.insert(EventChannel::<UiEvent>::with_capacity(40));
         resources.insert(FrameLimiter::default());
         resources.insert(Stopwatch::default());
         resources.insert(Time::default());
+        resources.insert(CrashReporter::default());
 
         Ok(Self {
             initial_state,
             world,
             resources,
+            #[cfg(feature = "window")]
             ignore_window_close: false,
             phantom: PhantomData,
         })
@@ -775,6 +850,74 @@ where
         self
     }
 
+    /// Runs the application as a headless dedicated server, ticking `tick_rate` times per second.
+    ///
+    /// Frames are limited to the tick rate, fixed updates run once per tick, and window close
+    /// events are ignored. Build the server with `default-features = false` and the `server`
+    /// feature, so the window, renderer, audio and input crates aren't compiled in, and don't add
+    /// bundles which need them.
+    ///
+    /// # Parameters
+    ///
+    /// `tick_rate`: The number of ticks per second.
+    ///
+    /// # Returns
+    ///
+    /// This function returns the ApplicationBuilder after modifying it.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use amethyst::prelude::*;
+    ///
+    /// struct ServerState;
+    /// impl SimpleState for ServerState {}
+    ///
+    /// # fn main() -> amethyst::Result<()> {
+    /// let game = Application::build("assets/", ServerState)?
+    ///     .headless(30)
+    ///     .build(DispatcherBuilder::default())?;
+    /// game.run();
+    /// #     Ok(())
+    /// # }
+    /// ```
+    pub fn headless(self, tick_rate: u32) -> Self {
+        assert!(tick_rate > 0, "Headless application with a tick rate of 0");
+        info!("Running headless at {} ticks per second", tick_rate);
+        #[cfg(feature = "window")]
+        let builder = self.ignore_window_close(true);
+        #[cfg(not(feature = "window"))]
+        let builder = self;
+        builder
+            .with_frame_limit(
+                FrameRateLimitStrategy::SleepAndYield(Duration::from_millis(2)),
+                tick_rate,
+            )
+            .with_fixed_step_length(Duration::from_secs(1) / tick_rate)
+    }
+
+    /// Sets the function called when a system panics, e.g. to upload a crash report or show a
+    /// dialog. The panic is caught and turned into an `Error` naming the system, and the
+    /// application shuts down at the end of the frame.
+    ///
+    /// # Parameters
+    ///
+    /// `handler`: The function called with the error of every crash.
+    ///
+    /// # Returns
+    ///
+    /// This function returns the ApplicationBuilder after modifying it.
+    pub fn with_crash_handler<F>(self, handler: F) -> Self
+    where
+        F: FnMut(&Error) + Send + Sync + 'static,
+    {
+        self.resources
+            .get_mut::<CrashReporter>()
+            .unwrap()
+            .set_handler(handler);
+        self
+    }
+
     /// Tells the resulting application window to ignore close events if ignore is true.
     /// This will make your game window unresponsive to operating system close commands.
     /// Use with caution.
@@ -786,6 +929,7 @@ where
     /// # Returns
     ///
     /// This function returns the ApplicationBuilder after modifying it.
+    #[cfg(feature = "window")]
     pub fn ignore_window_close(mut self, ignore: bool) -> Self {
         self.ignore_window_close = ignore;
         self
@@ -827,6 +971,7 @@ where
 
         let data = init.build(&mut self.world, &mut self.resources)?;
 
+        #[cfg(feature = "window")]
         let event_reader_id = self
             .resources
             .get_mut::<EventChannel<Event<'static, ()>>>()
@@ -836,6 +981,9 @@ where
         let mut trans_event_channel = EventChannel::<TransEvent<T, E>>::with_capacity(2);
         let trans_reader_id = trans_event_channel.register_reader();
         self.resources.insert(trans_event_channel);
+        self.resources
+            .insert(EventChannel::<StateLifecycleEvent>::new());
+        self.resources.insert(StateStack::default());
 
         let mut reader = X::default();
         reader.setup(&mut self.resources);
@@ -846,8 +994,10 @@ where
             states: StateMachine::new(self.initial_state),
             reader,
             events: Vec::new(),
+            #[cfg(feature = "window")]
             ignore_window_close: self.ignore_window_close,
             data,
+            #[cfg(feature = "window")]
             event_reader_id,
             trans_reader_id,
         })
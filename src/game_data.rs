@@ -10,6 +10,10 @@ pub trait DataInit<T> {
 pub trait DataDispose {
     /// Perform disposal
     fn dispose(&mut self, world: &mut World, resources: &mut Resources);
+
+    /// Run a fixed update step, called by the application after the `fixed_update` of the
+    /// states for every fixed update step of the frame.
+    fn fixed_update(&mut self, _world: &mut World, _resources: &mut Resources) {}
 }
 
 /// Default game data.
@@ -33,6 +37,13 @@ impl GameData {
         }
     }
 
+    /// Run a fixed update step by executing the fixed systems of the internal [Dispatcher]
+    pub fn fixed_update(&mut self, world: &mut World, resources: &mut Resources) {
+        if let Some(dispatcher) = &mut self.dispatcher {
+            dispatcher.execute_fixed(world, resources);
+        }
+    }
+
     /// Dispose game data, dropping the dispatcher
     pub fn dispose(&mut self, world: &mut World, resources: &mut Resources) {
         if let Some(dispatcher) = self.dispatcher.take() {
@@ -49,6 +60,10 @@ impl DataDispose for GameData {
     fn dispose(&mut self, world: &mut World, resources: &mut Resources) {
         self.dispose(world, resources);
     }
+
+    fn fixed_update(&mut self, world: &mut World, resources: &mut Resources) {
+        self.fixed_update(world, resources);
+    }
 }
 
 impl DataInit<GameData> for DispatcherBuilder {
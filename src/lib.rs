@@ -81,17 +81,21 @@ pub use amethyst_assets as assets;
 #[cfg(feature = "audio")]
 pub use amethyst_audio as audio;
 pub use amethyst_config as config;
+#[cfg(feature = "controls")]
 pub use amethyst_controls as controls;
 pub use amethyst_core as core;
 pub use amethyst_derive as derive;
 pub use amethyst_error as error;
 #[cfg(feature = "gltf")]
 pub use amethyst_gltf as gltf;
+#[cfg(feature = "input")]
 pub use amethyst_input as input;
 #[cfg(feature = "locale")]
 pub use amethyst_locale as locale;
 #[cfg(feature = "network")]
 pub use amethyst_network as network;
+#[cfg(feature = "physics")]
+pub use amethyst_physics as physics;
 #[cfg(feature = "renderer")]
 pub use amethyst_rendy as renderer;
 #[cfg(feature = "tiles")]
@@ -100,7 +104,9 @@ pub use amethyst_tiles as tiles;
 pub use amethyst_ui as ui;
 #[cfg(feature = "utils")]
 pub use amethyst_utils as utils;
+#[cfg(feature = "window")]
 pub use amethyst_window as window;
+#[cfg(any(feature = "window", feature = "input"))]
 pub use winit;
 
 pub use self::{
@@ -112,9 +118,10 @@ pub use self::{
     },
     error::Error,
     game_data::{DataDispose, DataInit, GameData},
+    settings::{Settings, SettingsBundle, SettingsEvent},
     state::{
-        EmptyState, EmptyTrans, SimpleState, SimpleTrans, State, StateData, StateMachine, Trans,
-        TransEvent,
+        EmptyState, EmptyTrans, SimpleState, SimpleTrans, State, StateData, StateLifecycleEvent,
+        StateMachine, StatePayload, Trans, TransEvent,
     },
     state_event::{StateEvent, StateEventReader},
 };
@@ -125,6 +132,7 @@ pub mod prelude;
 
 mod app;
 mod game_data;
+mod settings;
 mod state;
 mod state_event;
 
@@ -7,7 +7,8 @@ pub use crate::{
     ecs::*,
     game_data::{DataInit, GameData},
     state::{
-        EmptyState, EmptyTrans, SimpleState, SimpleTrans, State, StateData, Trans, TransEvent,
+        EmptyState, EmptyTrans, SimpleState, SimpleTrans, State, StateData, StatePayload, Trans,
+        TransEvent,
     },
     state_event::StateEvent,
 };
@@ -0,0 +1,321 @@
+//! Engine-wide settings, persisted to a directory of RON files.
+
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use amethyst_config::{Config, ConfigError, ConfigFormat};
+use log::{error, warn};
+use serde::{de::DeserializeOwned, Serialize};
+#[cfg(feature = "profiler")]
+use thread_profiler::profile_scope;
+
+use crate::{
+    core::{ecs::*, shrev::EventChannel},
+    error::Error,
+};
+
+/// Event sent when a section of the `Settings` changed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SettingsEvent {
+    /// The section with the name was modified
+    Changed(String),
+}
+
+/// A section of the settings, with its type erased.
+trait Section: Any + Send + Sync {
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+    fn save(&self, path: &Path) -> Result<(), ConfigError>;
+}
+
+impl<T> Section for T
+where
+    T: Serialize + DeserializeOwned + Send + Sync + 'static,
+{
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn save(&self, path: &Path) -> Result<(), ConfigError> {
+        self.write_format(ConfigFormat::Ron, path)
+    }
+}
+
+struct Entry {
+    name: String,
+    value: Box<dyn Section>,
+    dirty: bool,
+}
+
+/// Resource holding the typed settings registered by bundles, such as graphics, audio or
+/// controls options, giving options menus a single place to read and change them.
+///
+/// Every section is stored in its own `<name>.ron` file of the settings directory. Registering
+/// a section loads its file, or takes the default value if the file doesn't exist; mark the
+/// fields of the section with `#[serde(default)]` so files saved by older versions still load.
+/// Modified sections are saved at the end of the frame when autosave is enabled, and a
+/// [SettingsEvent] is sent for them.
+///
+/// ```rust,ignore
+/// #[derive(Default, Serialize, Deserialize)]
+/// #[serde(default)]
+/// struct AudioSettings {
+///     volume: f32,
+/// }
+///
+/// // in the load method of a bundle
+/// resources
+///     .get_mut::<Settings>()
+///     .expect("add the SettingsBundle first")
+///     .register::<AudioSettings>("audio");
+///
+/// // in an options menu
+/// settings.modify(|audio: &mut AudioSettings| audio.volume = 0.5);
+/// ```
+#[allow(missing_debug_implementations)]
+pub struct Settings {
+    directory: Option<PathBuf>,
+    autosave: bool,
+    sections: HashMap<TypeId, Entry>,
+    changed: Vec<String>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings::in_memory()
+    }
+}
+
+impl Settings {
+    /// Creates settings persisted in the given directory, saving changes automatically.
+    pub fn new<P: Into<PathBuf>>(directory: P) -> Self {
+        Settings {
+            directory: Some(directory.into()),
+            autosave: true,
+            sections: HashMap::new(),
+            changed: Vec::new(),
+        }
+    }
+
+    /// Creates settings which are never loaded or saved, e.g. for tests.
+    pub fn in_memory() -> Self {
+        Settings {
+            directory: None,
+            autosave: false,
+            sections: HashMap::new(),
+            changed: Vec::new(),
+        }
+    }
+
+    /// Sets whether modified sections are saved at the end of the frame.
+    pub fn set_autosave(&mut self, autosave: bool) {
+        self.autosave = autosave;
+    }
+
+    /// The directory the settings are persisted in.
+    pub fn directory(&self) -> Option<&Path> {
+        self.directory.as_deref()
+    }
+
+    /// Registers a section of type `T` under the given name, loading it from its file. A
+    /// section registered twice keeps its current value.
+    pub fn register<T>(&mut self, name: &str) -> &mut Self
+    where
+        T: Serialize + DeserializeOwned + Default + Send + Sync + 'static,
+    {
+        if self.sections.contains_key(&TypeId::of::<T>()) {
+            return self;
+        }
+        let value = match self.path(name) {
+            Some(path) if path.exists() => {
+                T::load(&path).unwrap_or_else(|e| {
+                    warn!(
+                        "Failed to load the settings {:?}, using the defaults: {}",
+                        path, e
+                    );
+                    T::default()
+                })
+            }
+            _ => T::default(),
+        };
+        self.sections.insert(
+            TypeId::of::<T>(),
+            Entry {
+                name: name.to_string(),
+                value: Box::new(value),
+                dirty: false,
+            },
+        );
+        self
+    }
+
+    /// Get the section of type `T`.
+    pub fn get<T: 'static>(&self) -> Option<&T> {
+        self.sections
+            .get(&TypeId::of::<T>())
+            .and_then(|entry| entry.value.as_any().downcast_ref())
+    }
+
+    /// Modifies the section of type `T`, which marks it as changed. Returns false if the
+    /// section isn't registered.
+    pub fn modify<T: 'static, F: FnOnce(&mut T)>(&mut self, f: F) -> bool {
+        let entry = match self.sections.get_mut(&TypeId::of::<T>()) {
+            Some(entry) => entry,
+            None => return false,
+        };
+        match entry.value.as_any_mut().downcast_mut() {
+            Some(value) => {
+                f(value);
+                entry.dirty = true;
+                if !self.changed.contains(&entry.name) {
+                    self.changed.push(entry.name.clone());
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Replaces the section of type `T`. Returns false if the section isn't registered.
+    pub fn set<T: 'static>(&mut self, value: T) -> bool {
+        self.modify(|section: &mut T| *section = value)
+    }
+
+    /// Returns true if some sections were modified since they were last saved.
+    pub fn is_dirty(&self) -> bool {
+        self.sections.values().any(|entry| entry.dirty)
+    }
+
+    /// Saves the modified sections to their files.
+    pub fn save(&mut self) -> Result<(), ConfigError> {
+        let directory = match &self.directory {
+            Some(directory) => directory,
+            None => return Ok(()),
+        };
+        fs::create_dir_all(directory)?;
+        for entry in self.sections.values_mut().filter(|entry| entry.dirty) {
+            entry
+                .value
+                .save(&directory.join(format!("{}.ron", entry.name)))?;
+            entry.dirty = false;
+        }
+        Ok(())
+    }
+
+    fn path(&self, name: &str) -> Option<PathBuf> {
+        self.directory
+            .as_ref()
+            .map(|directory| directory.join(format!("{}.ron", name)))
+    }
+
+    fn take_changed(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.changed)
+    }
+}
+
+/// Sends the changes of the `Settings`, and saves them when autosave is enabled.
+fn settings_tick(_world: &mut World, resources: &mut Resources) {
+    #[cfg(feature = "profiler")]
+    profile_scope!("settings_tick");
+
+    let mut settings = match resources.get_mut::<Settings>() {
+        Some(settings) => settings,
+        None => return,
+    };
+    let changed = settings.take_changed();
+    if changed.is_empty() {
+        return;
+    }
+    if settings.autosave {
+        if let Err(e) = settings.save() {
+            error!("Failed to save the settings: {}", e);
+        }
+    }
+    if let Some(mut events) = resources.get_mut::<EventChannel<SettingsEvent>>() {
+        events.iter_write(changed.into_iter().map(SettingsEvent::Changed));
+    }
+}
+
+/// Bundle inserting the [Settings] resource and the `EventChannel<SettingsEvent>`, and saving
+/// the settings when they change. Add it before the bundles registering their settings.
+#[derive(Debug)]
+pub struct SettingsBundle {
+    directory: Option<PathBuf>,
+}
+
+impl SettingsBundle {
+    /// Creates a bundle persisting the settings in the given directory.
+    pub fn new<P: Into<PathBuf>>(directory: P) -> Self {
+        SettingsBundle {
+            directory: Some(directory.into()),
+        }
+    }
+
+    /// Creates a bundle whose settings are never loaded or saved.
+    pub fn in_memory() -> Self {
+        SettingsBundle { directory: None }
+    }
+}
+
+impl SystemBundle for SettingsBundle {
+    fn load(
+        &mut self,
+        _world: &mut World,
+        resources: &mut Resources,
+        builder: &mut DispatcherBuilder,
+    ) -> Result<(), Error> {
+        if !resources.contains::<Settings>() {
+            resources.insert(match self.directory.take() {
+                Some(directory) => Settings::new(directory),
+                None => Settings::in_memory(),
+            });
+        }
+        resources.insert(EventChannel::<SettingsEvent>::new());
+        builder.add_thread_local_fn(settings_tick);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+    #[serde(default)]
+    struct AudioSettings {
+        volume: f32,
+        muted: bool,
+    }
+
+    #[test]
+    fn modified_settings_are_saved_and_reloaded() {
+        let directory =
+            std::env::temp_dir().join(format!("amethyst_settings_{}", std::process::id()));
+        let mut settings = Settings::new(&directory);
+        settings.register::<AudioSettings>("audio");
+        assert_eq!(
+            settings.get::<AudioSettings>(),
+            Some(&AudioSettings::default())
+        );
+
+        assert!(settings.modify(|audio: &mut AudioSettings| audio.volume = 0.5));
+        assert_eq!(settings.take_changed(), vec!["audio".to_string()]);
+        settings.save().unwrap();
+        assert!(!settings.is_dirty());
+
+        let mut reloaded = Settings::new(&directory);
+        reloaded.register::<AudioSettings>("audio");
+        assert_eq!(reloaded.get::<AudioSettings>().unwrap().volume, 0.5);
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
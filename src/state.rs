@@ -1,13 +1,21 @@
 //! Utilities for game state management.
 
-use std::fmt::{Debug, Display, Formatter, Result as FmtResult};
+use std::{
+    any::Any,
+    fmt::{Debug, Display, Formatter, Result as FmtResult},
+};
 
-use amethyst_input::is_close_requested;
 use derivative::Derivative;
 #[cfg(feature = "profiler")]
 use thread_profiler::profile_scope;
 
-use crate::{ecs::*, GameData, StateEvent};
+#[cfg(feature = "window")]
+use crate::winit::event::{Event, WindowEvent};
+use crate::{
+    core::{shrev::EventChannel, state_stack::StateStack},
+    ecs::*,
+    GameData, StateEvent,
+};
 
 /// Error type for errors occurring in `StateMachine`
 #[derive(Debug)]
@@ -62,6 +70,9 @@ pub enum Trans<T, E> {
     /// Remove the active state and resume the next state on the stack or stop
     /// if there are none.
     Pop,
+    /// Like `Pop`, passing a payload to the `on_resume_with` method of the resumed state, e.g.
+    /// the choice of a dialog state.
+    PopWith(StatePayload),
     /// Pause the active state and push a new state onto the stack.
     Push(Box<dyn State<T, E>>),
     /// Remove the current state on the stack and insert a different one.
@@ -80,6 +91,7 @@ impl<T, E> Debug for Trans<T, E> {
         match self {
             Trans::None => f.write_str("None"),
             Trans::Pop => f.write_str("Pop"),
+            Trans::PopWith(_) => f.write_str("PopWith"),
             Trans::Push(_) => f.write_str("Push"),
             Trans::Switch(_) => f.write_str("Switch"),
             Trans::Replace(_) => f.write_str("Replace"),
@@ -90,6 +102,47 @@ impl<T, E> Debug for Trans<T, E> {
     }
 }
 
+impl<T, E: Send + Sync + 'static> Trans<T, E> {
+    /// Pause the active state and push the given state, e.g.
+    /// `Trans::push(GameState::new(level_params))`.
+    pub fn push<S: State<T, E> + 'static>(state: S) -> Self {
+        Trans::Push(Box::new(state))
+    }
+
+    /// Remove the active state and insert the given state.
+    pub fn switch<S: State<T, E> + 'static>(state: S) -> Self {
+        Trans::Switch(Box::new(state))
+    }
+
+    /// Remove all states and insert the given state.
+    pub fn replace<S: State<T, E> + 'static>(state: S) -> Self {
+        Trans::Replace(Box::new(state))
+    }
+
+    /// Remove the active state and resume the next state with a payload.
+    pub fn pop_with<P: Any + Send>(payload: P) -> Self {
+        Trans::PopWith(Box::new(payload))
+    }
+}
+
+/// Data passed to a resumed state by `Trans::PopWith`, to be downcast to its type.
+pub type StatePayload = Box<dyn Any + Send>;
+
+/// Event sent on the `EventChannel<StateLifecycleEvent>` when the state machine calls a
+/// lifecycle method of a state, with the position of the state on the stack, 0 being the
+/// bottom. Systems can use them to react to states being pushed and popped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StateLifecycleEvent {
+    /// The state at the position started
+    Started(usize),
+    /// The state at the position stopped, and was removed
+    Stopped(usize),
+    /// The state at the position was paused by a pushed state
+    Paused(usize),
+    /// The state at the position resumed, after the state above it was popped
+    Resumed(usize),
+}
+
 /// Event queue to trigger state `Trans` from other places than a `State`'s methods.
 /// FIXME: needs example
 /// # Example:
@@ -109,6 +162,11 @@ pub type SimpleTrans = Trans<GameData, StateEvent>;
 
 /// A trait which defines game states that can be used by the state machine.
 pub trait State<T, E: Send + Sync + 'static> {
+    /// Name of the state in the `StateStack` resource, its type name by default.
+    fn name(&self) -> &'static str {
+        std::any::type_name::<Self>()
+    }
+
     /// Executed when the game state begins.
     fn on_start(&mut self, _data: StateData<'_, T>) {}
 
@@ -121,6 +179,12 @@ pub trait State<T, E: Send + Sync + 'static> {
     /// Executed when the application returns to this game state once again.
     fn on_resume(&mut self, _data: StateData<'_, T>) {}
 
+    /// Executed instead of `on_resume` when the state above was popped with a payload. By
+    /// default this ignores the payload and calls `on_resume`.
+    fn on_resume_with(&mut self, data: StateData<'_, T>, _payload: StatePayload) {
+        self.on_resume(data)
+    }
+
     /// Executed on every frame before updating, for use in reacting to events.
     fn handle_event(&mut self, _data: StateData<'_, T>, _event: E) -> Trans<T, E> {
         Trans::None
@@ -165,14 +229,15 @@ pub trait EmptyState {
     /// Executed when the application returns to this game state once again.
     fn on_resume(&mut self, _data: StateData<'_, ()>) {}
 
+    /// Executed instead of `on_resume` when the state above was popped with a payload.
+    fn on_resume_with(&mut self, data: StateData<'_, ()>, _payload: StatePayload) {
+        self.on_resume(data)
+    }
+
     /// Executed on every frame before updating, for use in reacting to events.
     fn handle_event(&mut self, _data: StateData<'_, ()>, event: StateEvent) -> EmptyTrans {
-        if let StateEvent::Window(event) = &event {
-            if is_close_requested(&event) {
-                Trans::Quit
-            } else {
-                Trans::None
-            }
+        if is_close_requested(&event) {
+            Trans::Quit
         } else {
             Trans::None
         }
@@ -222,6 +287,11 @@ impl<T: EmptyState> State<(), StateEvent> for T {
         self.on_resume(data)
     }
 
+    /// Executed instead of `on_resume` when the state above was popped with a payload.
+    fn on_resume_with(&mut self, data: StateData<'_, ()>, payload: StatePayload) {
+        self.on_resume_with(data, payload)
+    }
+
     /// Executed on every frame before updating, for use in reacting to events.
     fn handle_event(&mut self, data: StateData<'_, ()>, event: StateEvent) -> EmptyTrans {
         self.handle_event(data, event)
@@ -268,14 +338,15 @@ pub trait SimpleState {
     /// Executed when the application returns to this game state once again.
     fn on_resume(&mut self, _data: StateData<'_, GameData>) {}
 
+    /// Executed instead of `on_resume` when the state above was popped with a payload.
+    fn on_resume_with(&mut self, data: StateData<'_, GameData>, _payload: StatePayload) {
+        self.on_resume(data)
+    }
+
     /// Executed on every frame before updating, for use in reacting to events.
     fn handle_event(&mut self, _data: StateData<'_, GameData>, event: StateEvent) -> SimpleTrans {
-        if let StateEvent::Window(event) = &event {
-            if is_close_requested(&event) {
-                Trans::Quit
-            } else {
-                Trans::None
-            }
+        if is_close_requested(&event) {
+            Trans::Quit
         } else {
             Trans::None
         }
@@ -325,6 +396,11 @@ impl<T: SimpleState> State<GameData, StateEvent> for T {
         self.on_resume(data)
     }
 
+    /// Executed instead of `on_resume` when the state above was popped with a payload.
+    fn on_resume_with(&mut self, data: StateData<'_, GameData>, payload: StatePayload) {
+        self.on_resume_with(data, payload)
+    }
+
     /// Executed on every frame before updating, for use in reacting to events.
     fn handle_event(&mut self, data: StateData<'_, GameData>, event: StateEvent) -> SimpleTrans {
         self.handle_event(data, event)
@@ -389,8 +465,17 @@ impl<'a, T, E: Send + Sync + 'static> StateMachine<'a, T, E> {
                 .state_stack
                 .last_mut()
                 .ok_or(StateError::NoStatesPresent)?;
-            state.on_start(data);
+            state.on_start(StateData {
+                world: data.world,
+                resources: data.resources,
+                data: data.data,
+            });
+            notify(
+                data.resources,
+                StateLifecycleEvent::Started(self.state_stack.len() - 1),
+            );
             self.running = true;
+            self.sync_stack(data.resources);
         }
         Ok(())
     }
@@ -524,25 +609,40 @@ impl<'a, T, E: Send + Sync + 'static> StateMachine<'a, T, E> {
     /// sequentially in the order of insertion.
     pub fn transition(&mut self, request: Trans<T, E>, data: StateData<'_, T>) {
         if self.running {
+            let StateData {
+                world,
+                resources,
+                data,
+            } = data;
             match request {
-                Trans::None => (),
-                Trans::Pop => self.pop(data),
-                Trans::Push(state) => self.push(state, data),
-                Trans::Switch(state) => self.switch(state, data),
-                Trans::Replace(state) => self.replace(state, data),
-                Trans::NewStack(states) => self.new_stack(states, data),
+                Trans::None => return,
+                Trans::Pop => self.pop(StateData::new(world, resources, data), None),
+                Trans::PopWith(payload) => {
+                    self.pop(StateData::new(world, resources, data), Some(payload))
+                }
+                Trans::Push(state) => self.push(state, StateData::new(world, resources, data)),
+                Trans::Switch(state) => self.switch(state, StateData::new(world, resources, data)),
+                Trans::Replace(state) => {
+                    self.replace(state, StateData::new(world, resources, data))
+                }
+                Trans::NewStack(states) => {
+                    self.new_stack(states, StateData::new(world, resources, data))
+                }
                 Trans::Sequence(sequence) => {
                     for trans in sequence {
-                        let temp_data = StateData {
-                            world: data.world,
-                            resources: data.resources,
-                            data: data.data,
-                        };
-                        self.transition(trans, temp_data);
+                        self.transition(trans, StateData::new(world, resources, data));
                     }
                 }
-                Trans::Quit => self.stop(data),
+                Trans::Quit => self.stop(StateData::new(world, resources, data)),
             }
+            self.sync_stack(resources);
+        }
+    }
+
+    /// Updates the `StateStack` resource, if the application inserted it.
+    fn sync_stack(&self, resources: &Resources) {
+        if let Some(mut stack) = resources.get_mut::<StateStack>() {
+            stack.set(self.state_stack.iter().map(|state| state.name()));
         }
     }
 
@@ -560,6 +660,10 @@ impl<'a, T, E: Send + Sync + 'static> StateMachine<'a, T, E> {
                     resources,
                     data,
                 });
+                notify(
+                    resources,
+                    StateLifecycleEvent::Stopped(self.state_stack.len()),
+                );
             }
 
             self.state_stack.push(state);
@@ -571,6 +675,10 @@ impl<'a, T, E: Send + Sync + 'static> StateMachine<'a, T, E> {
                 resources,
                 data,
             });
+            notify(
+                resources,
+                StateLifecycleEvent::Started(self.state_stack.len() - 1),
+            );
         }
     }
 
@@ -588,6 +696,10 @@ impl<'a, T, E: Send + Sync + 'static> StateMachine<'a, T, E> {
                     resources,
                     data,
                 });
+                notify(
+                    resources,
+                    StateLifecycleEvent::Paused(self.state_stack.len() - 1),
+                );
             }
 
             self.state_stack.push(state);
@@ -599,12 +711,16 @@ impl<'a, T, E: Send + Sync + 'static> StateMachine<'a, T, E> {
                 resources,
                 data,
             });
+            notify(
+                resources,
+                StateLifecycleEvent::Started(self.state_stack.len() - 1),
+            );
         }
     }
 
     /// Stops and removes the active state and un-pauses the next state on the
-    /// stack (if any).
-    fn pop(&mut self, data: StateData<'_, T>) {
+    /// stack (if any), passing it the payload.
+    fn pop(&mut self, data: StateData<'_, T>, payload: Option<StatePayload>) {
         if self.running {
             let StateData {
                 world,
@@ -617,14 +733,26 @@ impl<'a, T, E: Send + Sync + 'static> StateMachine<'a, T, E> {
                     resources,
                     data,
                 });
+                notify(
+                    resources,
+                    StateLifecycleEvent::Stopped(self.state_stack.len()),
+                );
             }
 
             if let Some(state) = self.state_stack.last_mut() {
-                state.on_resume(StateData {
+                let data = StateData {
                     world,
                     resources,
                     data,
-                });
+                };
+                match payload {
+                    Some(payload) => state.on_resume_with(data, payload),
+                    None => state.on_resume(data),
+                }
+                notify(
+                    resources,
+                    StateLifecycleEvent::Resumed(self.state_stack.len() - 1),
+                );
             } else {
                 self.running = false;
             }
@@ -646,6 +774,10 @@ impl<'a, T, E: Send + Sync + 'static> StateMachine<'a, T, E> {
                     resources,
                     data,
                 });
+                notify(
+                    resources,
+                    StateLifecycleEvent::Stopped(self.state_stack.len()),
+                );
             }
 
             //Push the new state
@@ -658,6 +790,7 @@ impl<'a, T, E: Send + Sync + 'static> StateMachine<'a, T, E> {
                 resources,
                 data,
             });
+            notify(resources, StateLifecycleEvent::Started(0));
         }
     }
 
@@ -676,6 +809,10 @@ impl<'a, T, E: Send + Sync + 'static> StateMachine<'a, T, E> {
                     resources,
                     data,
                 });
+                notify(
+                    resources,
+                    StateLifecycleEvent::Stopped(self.state_stack.len()),
+                );
             }
 
             // push the new states
@@ -690,6 +827,7 @@ impl<'a, T, E: Send + Sync + 'static> StateMachine<'a, T, E> {
                     resources,
                     data,
                 });
+                notify(resources, StateLifecycleEvent::Started(count));
                 if count != state_count - 1 {
                     // pause on each state but the last
                     new_state.on_pause(StateData {
@@ -697,6 +835,7 @@ impl<'a, T, E: Send + Sync + 'static> StateMachine<'a, T, E> {
                         resources,
                         data,
                     });
+                    notify(resources, StateLifecycleEvent::Paused(count));
                 }
             }
         }
@@ -716,13 +855,43 @@ impl<'a, T, E: Send + Sync + 'static> StateMachine<'a, T, E> {
                     resources,
                     data,
                 });
+                notify(
+                    resources,
+                    StateLifecycleEvent::Stopped(self.state_stack.len()),
+                );
             }
 
             self.running = false;
+            self.sync_stack(resources);
         }
     }
 }
 
+/// Returns true if the event asks to close a window, which the default `handle_event` of the
+/// simple states quits on.
+#[cfg(feature = "window")]
+fn is_close_requested(event: &StateEvent) -> bool {
+    matches!(
+        event,
+        StateEvent::Window(Event::WindowEvent {
+            event: WindowEvent::CloseRequested,
+            ..
+        })
+    )
+}
+
+#[cfg(not(feature = "window"))]
+fn is_close_requested(_: &StateEvent) -> bool {
+    false
+}
+
+/// Sends a lifecycle event, if the application inserted the channel.
+fn notify(resources: &Resources, event: StateLifecycleEvent) {
+    if let Some(mut channel) = resources.get_mut::<EventChannel<StateLifecycleEvent>>() {
+        channel.single_write(event);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -840,6 +1009,72 @@ mod tests {
         assert_eq!(sm.state_stack.len(), 3);
     }
 
+    struct StateDialog;
+    struct StateMenu(Option<u32>);
+
+    impl State<(), ()> for StateDialog {
+        fn update(&mut self, _: StateData<'_, ()>) -> Trans<(), ()> {
+            Trans::pop_with(42u32)
+        }
+    }
+
+    impl State<(), ()> for StateMenu {
+        fn on_resume_with(&mut self, _: StateData<'_, ()>, payload: StatePayload) {
+            self.0 = payload.downcast::<u32>().ok().map(|choice| *choice);
+        }
+
+        fn update(&mut self, _: StateData<'_, ()>) -> Trans<(), ()> {
+            match self.0 {
+                Some(_) => Trans::None,
+                None => Trans::push(StateDialog),
+            }
+        }
+    }
+
+    #[test]
+    fn pop_with_payload() {
+        use crate::ecs::World;
+
+        let mut world = World::default();
+        let mut resources = Resources::default();
+        let mut channel = EventChannel::<StateLifecycleEvent>::new();
+        let mut reader = channel.register_reader();
+        resources.insert(channel);
+        resources.insert(StateStack::default());
+
+        let mut sm = StateMachine::new(StateMenu(None));
+        // Unwrap here is fine because start can only fail when there are no states in the machine.
+        sm.start(StateData::new(&mut world, &mut resources, &mut ()))
+            .unwrap();
+
+        for _ in 0..3 {
+            sm.update(StateData::new(&mut world, &mut resources, &mut ()));
+        }
+        // the menu got the choice of the dialog, so it didn't push it again
+        assert_eq!(sm.state_stack.len(), 1);
+        assert!(resources
+            .get::<StateStack>()
+            .unwrap()
+            .is_on_top::<StateMenu>());
+
+        let events: Vec<_> = resources
+            .get::<EventChannel<StateLifecycleEvent>>()
+            .unwrap()
+            .read(&mut reader)
+            .copied()
+            .collect();
+        assert_eq!(
+            events,
+            vec![
+                StateLifecycleEvent::Started(0),
+                StateLifecycleEvent::Paused(0),
+                StateLifecycleEvent::Started(1),
+                StateLifecycleEvent::Stopped(1),
+                StateLifecycleEvent::Resumed(0),
+            ]
+        );
+    }
+
     #[test]
     fn replace() {
         use crate::ecs::World;
@@ -1,7 +1,14 @@
-use winit::event::Event;
-
+#[cfg(feature = "input")]
+use crate::input::InputEvent;
 #[cfg(feature = "ui")]
 use crate::ui::UiEvent;
+#[cfg(feature = "window")]
+use crate::winit::event::Event;
+// the reader doesn't read any channel without the features of the events
+#[cfg_attr(
+    not(any(feature = "window", feature = "input", feature = "ui")),
+    allow(unused_imports)
+)]
 use crate::{
     core::{
         ecs::*,
@@ -9,7 +16,6 @@ use crate::{
         EventReader,
     },
     derive::EventReader,
-    input::InputEvent,
 };
 
 /// The enum holding the different types of event that can be received in a `State` in the
@@ -18,10 +24,12 @@ use crate::{
 #[reader(StateEventReader)]
 pub enum StateEvent {
     /// Events sent by the winit window.
+    #[cfg(feature = "window")]
     Window(Event<'static, ()>),
     /// Events sent by the ui system.
     #[cfg(feature = "ui")]
     Ui(UiEvent),
     /// Events sent by the input system.
+    #[cfg(feature = "input")]
     Input(InputEvent),
 }